@@ -1,202 +1,1014 @@
-pub trait Alert {}
+/// The operation was denied by the policy of all binaries, covering all
+/// resources (e.g. all ports).
+pub const REASON_WILDCARD_DENY_ALL: u8 = 1;
+/// The operation was denied by the policy of all binaries, listing the
+/// resource.
+pub const REASON_WILDCARD_DENY: u8 = 2;
+/// The operation was denied by the policy of the binary, covering all
+/// resources.
+pub const REASON_BINARY_DENY_ALL: u8 = 3;
+/// The operation was denied by the policy of the binary, listing the
+/// resource.
+pub const REASON_BINARY_DENY: u8 = 4;
+/// All binaries are denied by default and no policy allowed the operation.
+pub const REASON_DEFAULT_DENY: u8 = 5;
+/// The resource is protected and the binary is not allowed to access it.
+pub const REASON_PROTECTED: u8 = 6;
+/// The operation was escalated and denied by a user space verdict.
+pub const REASON_ESCALATION_VERDICT: u8 = 7;
+/// The operation was escalated and denied by the fallback verdict, without a
+/// user space verdict.
+pub const REASON_ESCALATION_FALLBACK: u8 = 8;
+/// The executed binary has no arguments (`argc` is 0).
+pub const REASON_NO_ARGS: u8 = 9;
+/// The operation was denied by the policy of all binaries, listing the port
+/// (or socket kind), while that policy doesn't allow all of them
+/// (`socket_bind`, `socket_listen` and `socket_create`, where
+/// [`REASON_WILDCARD_DENY`] means an exception to an allow-all policy).
+pub const REASON_WILDCARD_DENY_LISTED: u8 = 10;
+/// The operation was allowed by the policies, but the socket has an option
+/// set which is denied for the binary.
+pub const REASON_SOCKET_OPTION: u8 = 11;
+/// The destination is a link-local or instance metadata address, denied by
+/// the built-in metadata rules of `socket_connect`.
+pub const REASON_METADATA: u8 = 12;
+/// The process has already bound as many distinct ports as its bind limit
+/// allows (`socket_bind`).
+pub const REASON_BIND_LIMIT: u8 = 13;
+/// The destination has already been connected to as many times as its
+/// connect rate limit allows in the current window (`socket_connect`).
+pub const REASON_RATE_LIMIT: u8 = 14;
+/// The process was denied by the hook as many times within the window of its
+/// binary's retry escalation as its threshold, see `RETRY_ESCALATIONS`. It
+/// replaces the reason of the denial.
+pub const REASON_PERSISTENT: u8 = 15;
+/// No policy allowed the bind in its family, while the rules for the other
+/// family (`AF_INET` for an `AF_INET6` bind and vice versa) allow the port,
+/// e.g. a bind of `AF_INET6` meeting an allow rule scoped to `ipv4`. It
+/// replaces [`REASON_DEFAULT_DENY`] (`socket_bind`).
+pub const REASON_FAMILY_MISMATCH: u8 = 16;
+
+/// Returns whether the reason is a decision of the policy of all binaries,
+/// so the message of the wildcard rule applies even if the binary has its
+/// own one.
+pub fn is_wildcard_reason(reason: u8) -> bool {
+    matches!(
+        reason,
+        REASON_WILDCARD_DENY_ALL
+            | REASON_WILDCARD_DENY
+            | REASON_WILDCARD_DENY_LISTED
+            | REASON_DEFAULT_DENY
+            | REASON_FAMILY_MISMATCH
+    )
+}
+
+/// Message ID of alerts without a message.
+pub const MESSAGE_NONE: u16 = 0;
+
+/// Channel of alerts of binaries without a channel, routed to the primary
+/// sink.
+pub const CHANNEL_DEFAULT: u8 = 0;
+
+pub trait Alert {
+    /// Returns the policy namespace of the process which triggered the alert.
+    fn namespace(&self) -> u32;
+
+    /// Returns the inode of the binary of the process which triggered the
+    /// alert.
+    fn binprm_inode(&self) -> u64;
+
+    /// Returns the `REASON_*` code of the alert.
+    fn reason(&self) -> u8;
+
+    /// Sets the `REASON_*` code of the alert.
+    fn set_reason(&mut self, reason: u8);
+
+    /// Sets the ID of the message of the rule which denied the operation,
+    /// which user space maps to a text.
+    fn set_message_id(&mut self, message_id: u16);
+
+    /// Sets the channel of the binary which triggered the alert, which user
+    /// space routes alerts to sinks by.
+    fn set_channel(&mut self, channel: u8);
+}
 
 #[repr(C)]
 #[derive(Copy, Clone)]
 pub struct BprmCheckSecurity {
     pub pid: u32,
-    _padding: u32,
+    pub namespace: u32,
+    pub session: u64,
     pub binprm_inode: u64,
+    pub message_id: u16,
+    pub reason: u8,
+    pub channel: u8,
+    _padding: [u8; 4],
 }
 
 impl BprmCheckSecurity {
-    pub fn new(pid: u32, binprm_inode: u64) -> Self {
+    pub fn new(pid: u32, namespace: u32, session: u64, reason: u8, binprm_inode: u64) -> Self {
         Self {
             pid,
-            _padding: 0,
+            namespace,
+            session,
+            reason,
             binprm_inode,
+            message_id: MESSAGE_NONE,
+            channel: CHANNEL_DEFAULT,
+            _padding: [0; 4],
         }
     }
 }
 
-impl Alert for BprmCheckSecurity {}
+impl Alert for BprmCheckSecurity {
+    fn namespace(&self) -> u32 {
+        self.namespace
+    }
+
+    fn binprm_inode(&self) -> u64 {
+        self.binprm_inode
+    }
+
+    fn reason(&self) -> u8 {
+        self.reason
+    }
+
+    fn set_reason(&mut self, reason: u8) {
+        self.reason = reason;
+    }
+
+    fn set_message_id(&mut self, message_id: u16) {
+        self.message_id = message_id;
+    }
+
+    fn set_channel(&mut self, channel: u8) {
+        self.channel = channel;
+    }
+}
 
 #[repr(C)]
 #[derive(Copy, Clone)]
 pub struct FileOpen {
     pub pid: u32,
-    _padding: u32,
+    pub namespace: u32,
+    pub session: u64,
     pub binprm_inode: u64,
     pub inode: u64,
+    pub message_id: u16,
+    pub reason: u8,
+    pub channel: u8,
+    _padding: [u8; 4],
 }
 
 impl FileOpen {
-    pub fn new(pid: u32, binprm_inode: u64, inode: u64) -> Self {
+    pub fn new(
+        pid: u32,
+        namespace: u32,
+        session: u64,
+        reason: u8,
+        binprm_inode: u64,
+        inode: u64,
+    ) -> Self {
         Self {
             pid,
-            _padding: 0,
+            namespace,
+            session,
+            reason,
             binprm_inode,
             inode,
+            message_id: MESSAGE_NONE,
+            channel: CHANNEL_DEFAULT,
+            _padding: [0; 4],
+        }
+    }
+}
+
+impl Alert for FileOpen {
+    fn namespace(&self) -> u32 {
+        self.namespace
+    }
+
+    fn binprm_inode(&self) -> u64 {
+        self.binprm_inode
+    }
+
+    fn reason(&self) -> u8 {
+        self.reason
+    }
+
+    fn set_reason(&mut self, reason: u8) {
+        self.reason = reason;
+    }
+
+    fn set_message_id(&mut self, message_id: u16) {
+        self.message_id = message_id;
+    }
+
+    fn set_channel(&mut self, channel: u8) {
+        self.channel = channel;
+    }
+}
+
+#[repr(C)]
+#[derive(Copy, Clone)]
+pub struct InodeCreate {
+    pub pid: u32,
+    pub namespace: u32,
+    pub session: u64,
+    pub binprm_inode: u64,
+    pub dir_inode: u64,
+    pub mode: u16,
+    pub message_id: u16,
+    pub reason: u8,
+    pub channel: u8,
+    _padding: [u8; 2],
+}
+
+impl InodeCreate {
+    pub fn new(
+        pid: u32,
+        namespace: u32,
+        session: u64,
+        reason: u8,
+        binprm_inode: u64,
+        dir_inode: u64,
+        mode: u16,
+    ) -> Self {
+        Self {
+            pid,
+            namespace,
+            session,
+            reason,
+            binprm_inode,
+            dir_inode,
+            mode,
+            message_id: MESSAGE_NONE,
+            channel: CHANNEL_DEFAULT,
+            _padding: [0; 2],
         }
     }
 }
 
-impl Alert for FileOpen {}
+impl Alert for InodeCreate {
+    fn namespace(&self) -> u32 {
+        self.namespace
+    }
+
+    fn binprm_inode(&self) -> u64 {
+        self.binprm_inode
+    }
+
+    fn reason(&self) -> u8 {
+        self.reason
+    }
+
+    fn set_reason(&mut self, reason: u8) {
+        self.reason = reason;
+    }
+
+    fn set_message_id(&mut self, message_id: u16) {
+        self.message_id = message_id;
+    }
+
+    fn set_channel(&mut self, channel: u8) {
+        self.channel = channel;
+    }
+}
 
 #[repr(C)]
 #[derive(Copy, Clone)]
 pub struct TaskFixSetuid {
     pub pid: u32,
-    _padding: u32,
+    pub namespace: u32,
+    pub session: u64,
     pub binprm_inode: u64,
     pub old_uid: u32,
     pub old_gid: u32,
     pub new_uid: u32,
     pub new_gid: u32,
+    /// PID (TGID) of the parent of the process.
+    pub ppid: u32,
+    pub message_id: u16,
+    pub reason: u8,
+    pub channel: u8,
+    /// Command name of the thread, padded with NULs.
+    pub comm: [u8; 16],
 }
 
 impl TaskFixSetuid {
+    #[allow(clippy::too_many_arguments)]
     pub fn new(
         pid: u32,
+        namespace: u32,
+        session: u64,
+        reason: u8,
         binprm_inode: u64,
         old_uid: u32,
         old_gid: u32,
         new_uid: u32,
         new_gid: u32,
+        ppid: u32,
+        comm: [u8; 16],
     ) -> Self {
         Self {
             pid,
-            _padding: 0,
+            namespace,
+            session,
+            reason,
             binprm_inode,
             old_uid,
             old_gid,
             new_uid,
             new_gid,
+            ppid,
+            message_id: MESSAGE_NONE,
+            channel: CHANNEL_DEFAULT,
+            comm,
+        }
+    }
+}
+
+impl Alert for TaskFixSetuid {
+    fn namespace(&self) -> u32 {
+        self.namespace
+    }
+
+    fn binprm_inode(&self) -> u64 {
+        self.binprm_inode
+    }
+
+    fn reason(&self) -> u8 {
+        self.reason
+    }
+
+    fn set_reason(&mut self, reason: u8) {
+        self.reason = reason;
+    }
+
+    fn set_message_id(&mut self, message_id: u16) {
+        self.message_id = message_id;
+    }
+
+    fn set_channel(&mut self, channel: u8) {
+        self.channel = channel;
+    }
+}
+
+#[repr(C)]
+#[derive(Copy, Clone)]
+pub struct TaskFixSetgid {
+    pub pid: u32,
+    pub namespace: u32,
+    pub session: u64,
+    pub binprm_inode: u64,
+    pub old_gid: u32,
+    pub old_egid: u32,
+    pub new_gid: u32,
+    pub new_egid: u32,
+    pub message_id: u16,
+    pub reason: u8,
+    pub channel: u8,
+    _padding: [u8; 4],
+}
+
+impl TaskFixSetgid {
+    #[allow(clippy::too_many_arguments)]
+    pub fn new(
+        pid: u32,
+        namespace: u32,
+        session: u64,
+        reason: u8,
+        binprm_inode: u64,
+        old_gid: u32,
+        old_egid: u32,
+        new_gid: u32,
+        new_egid: u32,
+    ) -> Self {
+        Self {
+            pid,
+            namespace,
+            session,
+            reason,
+            binprm_inode,
+            old_gid,
+            old_egid,
+            new_gid,
+            new_egid,
+            message_id: MESSAGE_NONE,
+            channel: CHANNEL_DEFAULT,
+            _padding: [0; 4],
         }
     }
 }
 
-impl Alert for TaskFixSetuid {}
+impl Alert for TaskFixSetgid {
+    fn namespace(&self) -> u32 {
+        self.namespace
+    }
+
+    fn binprm_inode(&self) -> u64 {
+        self.binprm_inode
+    }
+
+    fn reason(&self) -> u8 {
+        self.reason
+    }
+
+    fn set_reason(&mut self, reason: u8) {
+        self.reason = reason;
+    }
+
+    fn set_message_id(&mut self, message_id: u16) {
+        self.message_id = message_id;
+    }
+
+    fn set_channel(&mut self, channel: u8) {
+        self.channel = channel;
+    }
+}
 
 #[repr(C)]
 #[derive(Copy, Clone)]
 pub struct SbMount {
     pub pid: u32,
-    _padding: u32,
+    pub namespace: u32,
+    pub session: u64,
     pub binprm_inode: u64,
+    pub message_id: u16,
+    pub reason: u8,
+    pub channel: u8,
+    _padding: [u8; 4],
 }
 
 impl SbMount {
-    pub fn new(pid: u32, binprm_inode: u64) -> Self {
+    pub fn new(pid: u32, namespace: u32, session: u64, reason: u8, binprm_inode: u64) -> Self {
         Self {
             pid,
-            _padding: 0,
+            namespace,
+            session,
+            reason,
             binprm_inode,
+            message_id: MESSAGE_NONE,
+            channel: CHANNEL_DEFAULT,
+            _padding: [0; 4],
         }
     }
 }
 
-impl Alert for SbMount {}
+impl Alert for SbMount {
+    fn namespace(&self) -> u32 {
+        self.namespace
+    }
+
+    fn binprm_inode(&self) -> u64 {
+        self.binprm_inode
+    }
+
+    fn reason(&self) -> u8 {
+        self.reason
+    }
+
+    fn set_reason(&mut self, reason: u8) {
+        self.reason = reason;
+    }
+
+    fn set_message_id(&mut self, message_id: u16) {
+        self.message_id = message_id;
+    }
+
+    fn set_channel(&mut self, channel: u8) {
+        self.channel = channel;
+    }
+}
 
 #[repr(C)]
 #[derive(Copy, Clone)]
 pub struct SbRemount {
     pub pid: u32,
-    _padding: u32,
+    pub namespace: u32,
+    pub session: u64,
     pub binprm_inode: u64,
+    pub message_id: u16,
+    pub reason: u8,
+    pub channel: u8,
+    _padding: [u8; 4],
 }
 
 impl SbRemount {
-    pub fn new(pid: u32, binprm_inode: u64) -> Self {
+    pub fn new(pid: u32, namespace: u32, session: u64, reason: u8, binprm_inode: u64) -> Self {
+        Self {
+            pid,
+            namespace,
+            session,
+            reason,
+            binprm_inode,
+            message_id: MESSAGE_NONE,
+            channel: CHANNEL_DEFAULT,
+            _padding: [0; 4],
+        }
+    }
+}
+
+impl Alert for SbRemount {
+    fn namespace(&self) -> u32 {
+        self.namespace
+    }
+
+    fn binprm_inode(&self) -> u64 {
+        self.binprm_inode
+    }
+
+    fn reason(&self) -> u8 {
+        self.reason
+    }
+
+    fn set_reason(&mut self, reason: u8) {
+        self.reason = reason;
+    }
+
+    fn set_message_id(&mut self, message_id: u16) {
+        self.message_id = message_id;
+    }
+
+    fn set_channel(&mut self, channel: u8) {
+        self.channel = channel;
+    }
+}
+
+/// Alert of a `bpf` syscall by a binary not allowed to use it.
+#[repr(C)]
+#[derive(Copy, Clone)]
+pub struct Bpf {
+    pub pid: u32,
+    pub namespace: u32,
+    pub session: u64,
+    pub binprm_inode: u64,
+    /// `bpf` command, e.g. `BPF_PROG_LOAD` (5).
+    pub cmd: u32,
+    pub message_id: u16,
+    pub reason: u8,
+    pub channel: u8,
+}
+
+impl Bpf {
+    pub fn new(
+        pid: u32,
+        namespace: u32,
+        session: u64,
+        reason: u8,
+        binprm_inode: u64,
+        cmd: u32,
+    ) -> Self {
         Self {
             pid,
-            _padding: 0,
+            namespace,
+            session,
+            reason,
             binprm_inode,
+            cmd,
+            message_id: MESSAGE_NONE,
+            channel: CHANNEL_DEFAULT,
         }
     }
 }
 
-impl Alert for SbRemount {}
+impl Alert for Bpf {
+    fn namespace(&self) -> u32 {
+        self.namespace
+    }
+
+    fn binprm_inode(&self) -> u64 {
+        self.binprm_inode
+    }
+
+    fn reason(&self) -> u8 {
+        self.reason
+    }
+
+    fn set_reason(&mut self, reason: u8) {
+        self.reason = reason;
+    }
+
+    fn set_message_id(&mut self, message_id: u16) {
+        self.message_id = message_id;
+    }
+
+    fn set_channel(&mut self, channel: u8) {
+        self.channel = channel;
+    }
+}
 
 #[repr(C)]
 #[derive(Copy, Clone)]
 pub struct SbUmount {
     pub pid: u32,
-    _padding: u32,
+    pub namespace: u32,
+    pub session: u64,
     pub binprm_inode: u64,
+    pub message_id: u16,
+    pub reason: u8,
+    pub channel: u8,
+    _padding: [u8; 4],
 }
 
 impl SbUmount {
-    pub fn new(pid: u32, binprm_inode: u64) -> Self {
+    pub fn new(pid: u32, namespace: u32, session: u64, reason: u8, binprm_inode: u64) -> Self {
         Self {
             pid,
-            _padding: 0,
+            namespace,
+            session,
+            reason,
             binprm_inode,
+            message_id: MESSAGE_NONE,
+            channel: CHANNEL_DEFAULT,
+            _padding: [0; 4],
         }
     }
 }
 
-impl Alert for SbUmount {}
+impl Alert for SbUmount {
+    fn namespace(&self) -> u32 {
+        self.namespace
+    }
+
+    fn binprm_inode(&self) -> u64 {
+        self.binprm_inode
+    }
+
+    fn reason(&self) -> u8 {
+        self.reason
+    }
+
+    fn set_reason(&mut self, reason: u8) {
+        self.reason = reason;
+    }
+
+    fn set_message_id(&mut self, message_id: u16) {
+        self.message_id = message_id;
+    }
+
+    fn set_channel(&mut self, channel: u8) {
+        self.channel = channel;
+    }
+}
 
 #[repr(C)]
 #[derive(Copy, Clone)]
 pub struct SocketBind {
     pub pid: u32,
-    _padding1: u32,
+    pub namespace: u32,
+    pub session: u64,
     pub binprm_inode: u64,
     pub port: u16,
-    _padding2: [u16; 3],
+    pub family: u16,
+    pub message_id: u16,
+    pub reason: u8,
+    pub channel: u8,
+    /// Number of distinct ports the process has bound, set in alerts of
+    /// [`REASON_BIND_LIMIT`] (0 otherwise).
+    pub count: u32,
+    _padding2: [u8; 4],
 }
 
 impl SocketBind {
-    pub fn new(pid: u32, binprm_inode: u64, port: u16) -> Self {
+    pub fn new(
+        pid: u32,
+        namespace: u32,
+        session: u64,
+        reason: u8,
+        binprm_inode: u64,
+        family: u16,
+        port: u16,
+    ) -> Self {
+        Self {
+            pid,
+            namespace,
+            session,
+            reason,
+            binprm_inode,
+            port,
+            family,
+            message_id: MESSAGE_NONE,
+            channel: CHANNEL_DEFAULT,
+            count: 0,
+            _padding2: [0; 4],
+        }
+    }
+}
+
+impl Alert for SocketBind {
+    fn namespace(&self) -> u32 {
+        self.namespace
+    }
+
+    fn binprm_inode(&self) -> u64 {
+        self.binprm_inode
+    }
+
+    fn reason(&self) -> u8 {
+        self.reason
+    }
+
+    fn set_reason(&mut self, reason: u8) {
+        self.reason = reason;
+    }
+
+    fn set_message_id(&mut self, message_id: u16) {
+        self.message_id = message_id;
+    }
+
+    fn set_channel(&mut self, channel: u8) {
+        self.channel = channel;
+    }
+}
+
+#[repr(C)]
+#[derive(Copy, Clone)]
+pub struct SocketCreate {
+    pub pid: u32,
+    pub namespace: u32,
+    pub session: u64,
+    pub binprm_inode: u64,
+    pub family: u16,
+    pub socket_type: u16,
+    pub protocol: u16,
+    pub message_id: u16,
+    pub reason: u8,
+    pub channel: u8,
+    _padding: [u8; 6],
+}
+
+impl SocketCreate {
+    #[allow(clippy::too_many_arguments)]
+    pub fn new(
+        pid: u32,
+        namespace: u32,
+        session: u64,
+        reason: u8,
+        binprm_inode: u64,
+        family: u16,
+        socket_type: u16,
+        protocol: u16,
+    ) -> Self {
+        Self {
+            pid,
+            namespace,
+            session,
+            reason,
+            binprm_inode,
+            family,
+            socket_type,
+            protocol,
+            message_id: MESSAGE_NONE,
+            channel: CHANNEL_DEFAULT,
+            _padding: [0; 6],
+        }
+    }
+}
+
+impl Alert for SocketCreate {
+    fn namespace(&self) -> u32 {
+        self.namespace
+    }
+
+    fn binprm_inode(&self) -> u64 {
+        self.binprm_inode
+    }
+
+    fn reason(&self) -> u8 {
+        self.reason
+    }
+
+    fn set_reason(&mut self, reason: u8) {
+        self.reason = reason;
+    }
+
+    fn set_message_id(&mut self, message_id: u16) {
+        self.message_id = message_id;
+    }
+
+    fn set_channel(&mut self, channel: u8) {
+        self.channel = channel;
+    }
+}
+
+#[repr(C)]
+#[derive(Copy, Clone)]
+pub struct SocketListen {
+    pub pid: u32,
+    pub namespace: u32,
+    pub session: u64,
+    pub binprm_inode: u64,
+    pub port: u16,
+    pub family: u16,
+    pub message_id: u16,
+    pub reason: u8,
+    pub channel: u8,
+}
+
+impl SocketListen {
+    pub fn new(
+        pid: u32,
+        namespace: u32,
+        session: u64,
+        reason: u8,
+        binprm_inode: u64,
+        family: u16,
+        port: u16,
+    ) -> Self {
         Self {
             pid,
-            _padding1: 0,
+            namespace,
+            session,
+            reason,
             binprm_inode,
             port,
-            _padding2: [0; 3],
+            family,
+            message_id: MESSAGE_NONE,
+            channel: CHANNEL_DEFAULT,
         }
     }
 }
 
-impl Alert for SocketBind {}
+impl Alert for SocketListen {
+    fn namespace(&self) -> u32 {
+        self.namespace
+    }
+
+    fn binprm_inode(&self) -> u64 {
+        self.binprm_inode
+    }
+
+    fn reason(&self) -> u8 {
+        self.reason
+    }
+
+    fn set_reason(&mut self, reason: u8) {
+        self.reason = reason;
+    }
+
+    fn set_message_id(&mut self, message_id: u16) {
+        self.message_id = message_id;
+    }
+
+    fn set_channel(&mut self, channel: u8) {
+        self.channel = channel;
+    }
+}
 
 #[repr(C)]
 #[derive(Copy, Clone)]
 pub struct SocketConnect {
     pub pid: u32,
-    _padding1: u32,
+    pub namespace: u32,
+    pub session: u64,
     pub binprm_inode: u64,
     pub addr_v4: u32,
-    _padding2: u32,
+    pub message_id: u16,
+    pub reason: u8,
+    pub channel: u8,
     pub addr_v6: [u8; 16],
+    /// Number of connects to the destination counted in the current window
+    /// of its rate limit, set in alerts of [`REASON_RATE_LIMIT`] (0
+    /// otherwise).
+    pub rate: u32,
+    /// Destination port.
+    pub port: u16,
+    _padding: [u8; 2],
 }
 
 impl SocketConnect {
-    pub fn new_ipv4(pid: u32, binprm_inode: u64, addr_v4: u32) -> Self {
+    pub fn new_ipv4(
+        pid: u32,
+        namespace: u32,
+        session: u64,
+        reason: u8,
+        binprm_inode: u64,
+        addr_v4: u32,
+    ) -> Self {
         Self {
             pid,
-            _padding1: 0,
+            namespace,
+            session,
+            reason,
             binprm_inode,
             addr_v4,
-            _padding2: 0,
+            message_id: MESSAGE_NONE,
+            channel: CHANNEL_DEFAULT,
             addr_v6: [0; 16],
+            rate: 0,
+            port: 0,
+            _padding: [0; 2],
         }
     }
 
-    pub fn new_ipv6(pid: u32, binprm_inode: u64, addr_v6: [u8; 16]) -> Self {
+    pub fn new_ipv6(
+        pid: u32,
+        namespace: u32,
+        session: u64,
+        reason: u8,
+        binprm_inode: u64,
+        addr_v6: [u8; 16],
+    ) -> Self {
         Self {
             pid,
-            _padding1: 0,
+            namespace,
+            session,
+            reason,
             binprm_inode,
             addr_v4: 0,
-            _padding2: 0,
+            message_id: MESSAGE_NONE,
+            channel: CHANNEL_DEFAULT,
             addr_v6,
+            rate: 0,
+            port: 0,
+            _padding: [0; 2],
         }
     }
 }
 
-impl Alert for SocketConnect {}
+impl Alert for SocketConnect {
+    fn namespace(&self) -> u32 {
+        self.namespace
+    }
+
+    fn binprm_inode(&self) -> u64 {
+        self.binprm_inode
+    }
+
+    fn reason(&self) -> u8 {
+        self.reason
+    }
+
+    fn set_reason(&mut self, reason: u8) {
+        self.reason = reason;
+    }
+
+    fn set_message_id(&mut self, message_id: u16) {
+        self.message_id = message_id;
+    }
+
+    fn set_channel(&mut self, channel: u8) {
+        self.channel = channel;
+    }
+}
+
+// Alerts are read from perf buffers by copying the bytes, so each of them
+// has its padding spelled out and a fixed layout (size, alignment).
+assert_layout!(BprmCheckSecurity, 32, 8);
+assert_layout!(FileOpen, 40, 8);
+assert_layout!(InodeCreate, 40, 8);
+assert_layout!(TaskFixSetuid, 64, 8);
+assert_layout!(TaskFixSetgid, 48, 8);
+assert_layout!(SbMount, 32, 8);
+assert_layout!(SbRemount, 32, 8);
+assert_layout!(SbUmount, 32, 8);
+assert_layout!(SocketBind, 40, 8);
+assert_layout!(SocketCreate, 40, 8);
+assert_layout!(SocketListen, 32, 8);
+assert_layout!(SocketConnect, 56, 8);
+assert_layout!(Bpf, 32, 8);
+
+/// Size of the largest alert of all hooks. Buffers alerts are read through
+/// have to fit it.
+pub const MAX_ALERT_SIZE: usize = max_size(&[
+    core::mem::size_of::<BprmCheckSecurity>(),
+    core::mem::size_of::<FileOpen>(),
+    core::mem::size_of::<InodeCreate>(),
+    core::mem::size_of::<TaskFixSetuid>(),
+    core::mem::size_of::<TaskFixSetgid>(),
+    core::mem::size_of::<SbMount>(),
+    core::mem::size_of::<SbRemount>(),
+    core::mem::size_of::<SbUmount>(),
+    core::mem::size_of::<SocketBind>(),
+    core::mem::size_of::<SocketCreate>(),
+    core::mem::size_of::<SocketListen>(),
+    core::mem::size_of::<SocketConnect>(),
+    core::mem::size_of::<Bpf>(),
+]);
+
+/// Budget of a single alert. Alerts are built on the 512-byte stack of the
+/// eBPF programs and every record takes space in the perf buffers of all
+/// CPUs, so an alert growing past the budget fails the build instead of
+/// the verifier or the readers.
+pub const ALERT_SIZE_BUDGET: usize = 128;
+
+const _: () = assert!(
+    MAX_ALERT_SIZE <= ALERT_SIZE_BUDGET,
+    "the largest alert exceeds `ALERT_SIZE_BUDGET`",
+);
+
+const fn max_size(sizes: &[usize]) -> usize {
+    let mut max = 0;
+    let mut i = 0;
+    while i < sizes.len() {
+        if sizes[i] > max {
+            max = sizes[i];
+        }
+        i += 1;
+    }
+    max
+}
 
 #[cfg(feature = "user")]
 pub mod user {
@@ -206,8 +1018,12 @@ pub mod user {
 
     unsafe impl Pod for BprmCheckSecurity {}
     unsafe impl Pod for FileOpen {}
+    unsafe impl Pod for InodeCreate {}
     unsafe impl Pod for SbMount {}
     unsafe impl Pod for SocketBind {}
     unsafe impl Pod for SocketConnect {}
+    unsafe impl Pod for SocketCreate {}
+    unsafe impl Pod for SocketListen {}
     unsafe impl Pod for TaskFixSetuid {}
+    unsafe impl Pod for TaskFixSetgid {}
 }
@@ -1,2 +1,13 @@
-/// Wildcard for the inode.
+/// Inode of the keys of wildcard policies. It's a sentinel only in user
+/// space and in keys without a wildcard tag (see
+/// [`InodeKey`](crate::policy::InodeKey)), which is why binaries with this
+/// inode can't be the subjects of policies.
 pub const INODE_WILDCARD: u64 = 0;
+/// Namespace of policies applied to processes without an assigned namespace.
+pub const NAMESPACE_DEFAULT: u32 = 0;
+/// IPv4 family.
+pub const AF_INET: u16 = 2;
+/// IPv6 family.
+pub const AF_INET6: u16 = 10;
+/// Packet (raw link-layer) family.
+pub const AF_PACKET: u16 = 17;
@@ -0,0 +1,568 @@
+//! Decision logic of the eBPF programs, free of map lookups and kernel
+//! accesses, so user space can evaluate policies the same way (see
+//! `ebpfguard::simulate`).
+//!
+//! The programs look up the map entries and pass them in, the functions here
+//! only decide.
+
+use crate::{
+    alerts::{
+        CHANNEL_DEFAULT, REASON_BINARY_DENY, REASON_BINARY_DENY_ALL, REASON_DEFAULT_DENY,
+        REASON_FAMILY_MISMATCH, REASON_METADATA, REASON_PROTECTED, REASON_WILDCARD_DENY,
+        REASON_WILDCARD_DENY_ALL, REASON_WILDCARD_DENY_LISTED,
+    },
+    policy::{
+        Binaries, ConnectRate, IpAddrs, Paths, PortRange, PortRanges, Ports, RateWindow,
+        RetryEscalation, SocketKinds, MAX_PORTS, VERDICT_DENY,
+    },
+};
+
+pub enum Mode {
+    Allowlist,
+    Denylist,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Action {
+    Allow,
+    /// Denies the operation, with the reason reported in alerts (one of the
+    /// `REASON_*` constants in [`alerts`](crate::alerts)).
+    Deny(u8),
+}
+
+impl Action {
+    /// Converts a verdict written by user space to an action, denying with
+    /// the given reason.
+    #[inline(always)]
+    pub fn from_verdict(verdict: u8, reason: u8) -> Self {
+        match verdict {
+            VERDICT_DENY => Action::Deny(reason),
+            _ => Action::Allow,
+        }
+    }
+
+    /// Returns the action of a matching rule in the given mode. When
+    /// denying, the reason tells whether the rule belongs to the wildcard or
+    /// to the binary and whether it covers everything or lists the resource.
+    #[inline(always)]
+    pub fn matched(mode: &Mode, wildcard: bool, all: bool) -> Self {
+        match mode {
+            Mode::Allowlist => Action::Allow,
+            Mode::Denylist => Action::Deny(match (wildcard, all) {
+                (true, true) => REASON_WILDCARD_DENY_ALL,
+                (true, false) => REASON_WILDCARD_DENY,
+                (false, true) => REASON_BINARY_DENY_ALL,
+                (false, false) => REASON_BINARY_DENY,
+            }),
+        }
+    }
+}
+
+impl From<Action> for i32 {
+    fn from(action: Action) -> Self {
+        match action {
+            Action::Allow => 0,
+            Action::Deny(_) => -1,
+        }
+    }
+}
+
+/// Entries of a policy map for all binaries and for the binary of the
+/// process, as looked up by the caller.
+#[derive(Copy, Clone)]
+pub struct Rules<T> {
+    pub wildcard: Option<T>,
+    pub binary: Option<T>,
+}
+
+/// Allowed and denied ports of a subject (all binaries or a binary) for
+/// binds, as looked up by the caller.
+#[derive(Copy, Clone)]
+pub struct PortRules<'a> {
+    pub allowed: Option<&'a Ports>,
+    pub denied: Option<&'a Ports>,
+}
+
+impl<'a> PortRules<'a> {
+    /// Returns whether the subject has any rule.
+    #[inline(always)]
+    pub fn is_some(&self) -> bool {
+        self.allowed.is_some() || self.denied.is_some()
+    }
+
+    /// Returns the rules of the subject scoped to the family of the bind if
+    /// it has any, otherwise its family-agnostic rules (`self`).
+    ///
+    /// A policy scoped to a family thus replaces the family-agnostic policy
+    /// of the same subject for binds of that family, while the precedence
+    /// between the policies of all binaries and of the binary stays the same:
+    /// a family-agnostic policy of the binary still overrides a scoped policy
+    /// of all binaries.
+    #[inline(always)]
+    pub fn scoped(self, family: PortRules<'a>) -> Self {
+        if family.is_some() {
+            family
+        } else {
+            self
+        }
+    }
+}
+
+/// Returns whether a bind of the port is allowed before any policy is
+/// checked: port 0 lets the kernel pick an ephemeral port, and the range
+/// exempt in the namespace (if any) bypasses enforcement.
+#[inline(always)]
+pub fn socket_bind_exempt(port: u16, exempt: Option<&PortRange>) -> bool {
+    if port == 0 {
+        return true;
+    }
+    match exempt {
+        Some(range) => range.contains(port),
+        None => false,
+    }
+}
+
+/// Decides an `AF_INET` or `AF_INET6` bind of the port based on the allowed and denied
+/// ports. Returns `None` if the policies don't decide it, in which case the
+/// bind can be escalated.
+#[inline(always)]
+pub fn socket_bind(allowed: Rules<&Ports>, denied: Rules<&Ports>, port: u16) -> Option<Action> {
+    if let Some(ports) = allowed.wildcard {
+        if ports.all() {
+            if let Some(ports) = denied.wildcard {
+                if ports.all() {
+                    return Some(Action::Deny(REASON_WILDCARD_DENY_ALL));
+                }
+                if ports.ports[..MAX_PORTS - 1].contains(&port) {
+                    return Some(Action::Deny(REASON_WILDCARD_DENY));
+                }
+            }
+
+            if let Some(ports) = denied.binary {
+                if ports.all() {
+                    return Some(Action::Deny(REASON_BINARY_DENY_ALL));
+                }
+                if ports.ports[..MAX_PORTS - 1].contains(&port) {
+                    return Some(Action::Deny(REASON_BINARY_DENY));
+                }
+            }
+        } else if ports.ports[..MAX_PORTS - 1].contains(&port) {
+            return Some(Action::Allow);
+        }
+    }
+
+    if let Some(ports) = denied.wildcard {
+        if ports.all() {
+            if let Some(ports) = allowed.wildcard {
+                if ports.all() || ports.ports[..MAX_PORTS - 1].contains(&port) {
+                    return Some(Action::Allow);
+                }
+            }
+
+            if let Some(ports) = allowed.binary {
+                if ports.all() || ports.ports[..MAX_PORTS - 1].contains(&port) {
+                    return Some(Action::Allow);
+                }
+            }
+
+            return Some(Action::Deny(REASON_DEFAULT_DENY));
+        } else if ports.ports[..MAX_PORTS - 1].contains(&port) {
+            return Some(Action::Deny(REASON_WILDCARD_DENY_LISTED));
+        }
+    }
+
+    None
+}
+
+/// Returns the reason of a bind denied by default ([`socket_bind`] returned
+/// [`REASON_DEFAULT_DENY`]), from the rules for the other family than the
+/// one of the bind: [`REASON_FAMILY_MISMATCH`] if they allow the port, so
+/// the alert shows that the policies of the other family were likely meant
+/// to cover it, otherwise [`REASON_DEFAULT_DENY`].
+#[inline(always)]
+pub fn socket_bind_default_deny(
+    other_allowed: Rules<&Ports>,
+    other_denied: Rules<&Ports>,
+    port: u16,
+) -> Action {
+    match socket_bind(other_allowed, other_denied, port) {
+        Some(Action::Allow) => Action::Deny(REASON_FAMILY_MISMATCH),
+        _ => Action::Deny(REASON_DEFAULT_DENY),
+    }
+}
+
+/// Decides the creation of a socket of the family and type based on the
+/// allowed and denied socket kinds, with the same precedence as
+/// [`socket_bind`]. Sockets which the policies don't decide are allowed.
+#[inline(always)]
+pub fn socket_create(
+    allowed: Rules<&SocketKinds>,
+    denied: Rules<&SocketKinds>,
+    family: u16,
+    ty: u16,
+) -> Action {
+    if let Some(kinds) = allowed.wildcard {
+        if kinds.all() {
+            if let Some(kinds) = denied.wildcard {
+                if kinds.all() {
+                    return Action::Deny(REASON_WILDCARD_DENY_ALL);
+                }
+                if kinds.contains(family, ty) {
+                    return Action::Deny(REASON_WILDCARD_DENY);
+                }
+            }
+
+            if let Some(kinds) = denied.binary {
+                if kinds.all() {
+                    return Action::Deny(REASON_BINARY_DENY_ALL);
+                }
+                if kinds.contains(family, ty) {
+                    return Action::Deny(REASON_BINARY_DENY);
+                }
+            }
+        } else if kinds.contains(family, ty) {
+            return Action::Allow;
+        }
+    }
+
+    if let Some(kinds) = denied.wildcard {
+        if kinds.all() {
+            if let Some(kinds) = allowed.wildcard {
+                if kinds.all() || kinds.contains(family, ty) {
+                    return Action::Allow;
+                }
+            }
+
+            if let Some(kinds) = allowed.binary {
+                if kinds.all() || kinds.contains(family, ty) {
+                    return Action::Allow;
+                }
+            }
+
+            return Action::Deny(REASON_DEFAULT_DENY);
+        } else if kinds.contains(family, ty) {
+            return Action::Deny(REASON_WILDCARD_DENY_LISTED);
+        }
+    }
+
+    Action::Allow
+}
+
+/// Decides an operation controlled by a plain per-binary allow/deny model
+/// (like binds of `AF_PACKET` sockets or `sb_mount`), where only the
+/// presence of the entries matters.
+///
+/// The check is opt-in: without a wildcard entry in either map, the
+/// operation is allowed.
+#[inline(always)]
+pub fn binary_rules<A, D>(allowed: Rules<A>, denied: Rules<D>) -> Action {
+    if allowed.wildcard.is_some() {
+        return check_presence(denied, Mode::Denylist);
+    }
+
+    if denied.wildcard.is_some() {
+        return check_presence(allowed, Mode::Allowlist);
+    }
+
+    Action::Allow
+}
+
+#[inline(always)]
+fn check_presence<T>(rules: Rules<T>, mode: Mode) -> Action {
+    if rules.wildcard.is_some() {
+        return Action::matched(&mode, true, true);
+    }
+
+    if rules.binary.is_some() {
+        return Action::matched(&mode, false, true);
+    }
+
+    match mode {
+        Mode::Allowlist => Action::Deny(REASON_DEFAULT_DENY),
+        Mode::Denylist => Action::Allow,
+    }
+}
+
+/// Decides a creation of a file in a directory, where `dirs` are the inodes
+/// of the directory and its parents. Paths match if they list any of them.
+///
+/// With the wildcard allowing all paths, the binary's denied paths are
+/// checked first, then its allowed paths (which exempt the binary from the
+/// wildcard's denied paths), then the wildcard's denied paths. With the
+/// wildcard denying all paths, only the allowed paths of the wildcard and
+/// the binary let the creation through. Without either, it's allowed.
+#[inline(always)]
+pub fn inode_create(allowed: Rules<&Paths>, denied: Rules<&Paths>, dirs: &[u64]) -> Action {
+    if let Some(paths) = allowed.wildcard {
+        if paths.all() {
+            if let Some(paths) = denied.binary {
+                if paths.all() {
+                    return Action::Deny(REASON_BINARY_DENY_ALL);
+                }
+                if paths.contains_any(dirs) {
+                    return Action::Deny(REASON_BINARY_DENY);
+                }
+            }
+
+            if let Some(paths) = allowed.binary {
+                if paths.all() || paths.contains_any(dirs) {
+                    return Action::Allow;
+                }
+            }
+
+            if let Some(paths) = denied.wildcard {
+                if paths.all() {
+                    return Action::Deny(REASON_WILDCARD_DENY_ALL);
+                }
+                if paths.contains_any(dirs) {
+                    return Action::Deny(REASON_WILDCARD_DENY);
+                }
+            }
+
+            return Action::Allow;
+        }
+    }
+
+    if let Some(paths) = denied.wildcard {
+        if paths.all() {
+            if let Some(paths) = allowed.wildcard {
+                if paths.contains_any(dirs) {
+                    return Action::Allow;
+                }
+            }
+
+            if let Some(paths) = allowed.binary {
+                if paths.all() || paths.contains_any(dirs) {
+                    return Action::Allow;
+                }
+            }
+
+            return Action::Deny(REASON_DEFAULT_DENY);
+        }
+    }
+
+    Action::Allow
+}
+
+/// Decides a connect of the binary to the address (of either family).
+///
+/// The protected binaries of the address (if any) are checked first, then
+/// the link-local and metadata CIDRs containing the address, then the denied
+/// CIDRs containing the address (the values of both are ignored), and then
+/// the allowed and denied addresses.
+#[inline(always)]
+pub fn socket_connect<T, U, C, const V: usize>(
+    binprm_inode: u64,
+    addr: U,
+    protected: Option<&Binaries>,
+    metadata_cidrs: Rules<C>,
+    denied_cidrs: Rules<C>,
+    allowed: Rules<&T>,
+    denied: Rules<&T>,
+) -> Action
+where
+    T: IpAddrs<U, V>,
+    U: Copy + PartialEq,
+{
+    if let Some(binaries) = protected {
+        if !binaries.contains(binprm_inode) {
+            return Action::Deny(REASON_PROTECTED);
+        }
+    }
+
+    if metadata_cidrs.wildcard.is_some() || metadata_cidrs.binary.is_some() {
+        return Action::Deny(REASON_METADATA);
+    }
+
+    if denied_cidrs.wildcard.is_some() {
+        return Action::Deny(REASON_WILDCARD_DENY);
+    }
+    if denied_cidrs.binary.is_some() {
+        return Action::Deny(REASON_BINARY_DENY);
+    }
+
+    if let Some(addrs) = allowed.wildcard {
+        if addrs.all() {
+            return check_addresses(denied, addr, Mode::Denylist);
+        }
+    }
+
+    if let Some(addrs) = denied.wildcard {
+        if addrs.all() {
+            return check_addresses(allowed, addr, Mode::Allowlist);
+        }
+    }
+
+    Action::Allow
+}
+
+/// Decides a connect to the destination port based on the allowed and
+/// denied port ranges. The ranges of the binary containing the port take
+/// precedence over the ones of all binaries. Within a subject, the narrowest
+/// range containing the port decides, the denied one if an allowed and a
+/// denied range are as narrow, so a single allowed port is an exception
+/// within a denied range and vice versa. Connects to ports which no range
+/// denies are allowed.
+#[inline(always)]
+pub fn socket_connect_ports(
+    allowed: Rules<&PortRanges>,
+    denied: Rules<&PortRanges>,
+    port: u16,
+) -> Action {
+    if let Some(action) = match_port_ranges(allowed.binary, denied.binary, port, false) {
+        return action;
+    }
+    match_port_ranges(allowed.wildcard, denied.wildcard, port, true).unwrap_or(Action::Allow)
+}
+
+#[inline(always)]
+fn match_port_ranges(
+    allowed: Option<&PortRanges>,
+    denied: Option<&PortRanges>,
+    port: u16,
+    wildcard: bool,
+) -> Option<Action> {
+    let allowed = allowed.and_then(|ranges| ranges.narrowest(port));
+    let denied = denied.and_then(|ranges| ranges.narrowest(port));
+    match (allowed, denied) {
+        (Some(allowed), Some(denied)) if allowed < denied => Some(Action::Allow),
+        (_, Some(_)) => Some(Action::matched(&Mode::Denylist, wildcard, false)),
+        (Some(_), None) => Some(Action::Allow),
+        (None, None) => None,
+    }
+}
+
+#[inline(always)]
+fn check_addresses<T, U, const V: usize>(rules: Rules<&T>, addr: U, mode: Mode) -> Action
+where
+    T: IpAddrs<U, V>,
+    U: Copy + PartialEq,
+{
+    if let Some(addrs) = rules.wildcard {
+        if let Some(action) = match_addresses(addrs, addr, &mode, true) {
+            return action;
+        }
+    }
+
+    if let Some(addrs) = rules.binary {
+        if let Some(action) = match_addresses(addrs, addr, &mode, false) {
+            return action;
+        }
+    }
+
+    match mode {
+        Mode::Allowlist => Action::Deny(REASON_DEFAULT_DENY),
+        Mode::Denylist => Action::Allow,
+    }
+}
+
+#[inline(always)]
+fn match_addresses<T, U, const V: usize>(
+    addrs: &T,
+    addr: U,
+    mode: &Mode,
+    wildcard: bool,
+) -> Option<Action>
+where
+    T: IpAddrs<U, V>,
+    U: Copy + PartialEq,
+{
+    if addrs.all() {
+        return Some(Action::matched(mode, wildcard, true));
+    }
+
+    if addrs.addrs()[..V].contains(&addr) {
+        return Some(Action::matched(mode, wildcard, false));
+    }
+
+    None
+}
+
+/// Returns the alert rate-limit window (in nanoseconds) of a binary: its own
+/// window if set, otherwise the default window of the namespace. 0 means
+/// alerts are not rate-limited.
+#[inline(always)]
+pub fn alert_window(windows: Rules<&u64>) -> u64 {
+    match windows.binary.or(windows.wildcard) {
+        Some(window) => *window,
+        None => 0,
+    }
+}
+
+/// Returns the alert channel of a binary: its own channel if set, otherwise
+/// the default channel of the namespace, or `CHANNEL_DEFAULT` without one.
+#[inline(always)]
+pub fn alert_channel(channels: Rules<&u8>) -> u8 {
+    match channels.binary.or(channels.wildcard) {
+        Some(channel) => *channel,
+        None => CHANNEL_DEFAULT,
+    }
+}
+
+/// Returns whether an alert at `now` is suppressed, because the previous
+/// alert of the same binary and hook (at `last`, if any) was emitted less
+/// than `window` nanoseconds ago. Suppressed alerts don't move the window.
+#[inline(always)]
+pub fn alert_suppressed(window: u64, last: Option<u64>, now: u64) -> bool {
+    match last {
+        Some(last) => now.wrapping_sub(last) < window,
+        None => false,
+    }
+}
+
+/// Returns the retry escalation of a binary: its own escalation if set,
+/// otherwise the default escalation of the namespace. `None` means denials
+/// are not counted.
+#[inline(always)]
+pub fn retry_escalation(escalations: Rules<&RetryEscalation>) -> Option<&RetryEscalation> {
+    escalations.binary.or(escalations.wildcard)
+}
+
+/// Counts a denial at `now` of a process by a hook, given the current window
+/// of its denials (if any), and returns the window to store. The denial is a
+/// persistent violation if the count of the window reaches the threshold of
+/// the escalation. A window older than the escalation's one starts over.
+#[inline(always)]
+pub fn count_denial(
+    escalation: &RetryEscalation,
+    window: Option<&RateWindow>,
+    now: u64,
+) -> RateWindow {
+    match window {
+        Some(window) if now.wrapping_sub(window.start) < escalation.window => {
+            RateWindow::new(window.start, window.count.saturating_add(1))
+        }
+        _ => RateWindow::new(now, 1),
+    }
+}
+
+/// Returns the bind limit of a binary (the number of distinct ports each of
+/// its processes may bind): its own limit if set, otherwise the default limit
+/// of the namespace. `None` means binds are not limited.
+#[inline(always)]
+pub fn bind_limit(limits: Rules<&u32>) -> Option<u32> {
+    limits.binary.or(limits.wildcard).copied()
+}
+
+/// Counts a connect at `now` against the rate limit of its destination,
+/// given the current window of the destination (if any). Returns the window
+/// to store if the connect is allowed, or the number of connects counted in
+/// the window if the limit is reached. A window older than the limit's one
+/// starts over.
+#[inline(always)]
+pub fn connect_rate(
+    rate: &ConnectRate,
+    window: Option<&RateWindow>,
+    now: u64,
+) -> Result<RateWindow, u32> {
+    match window {
+        Some(window) if now.wrapping_sub(window.start) < rate.window => {
+            if window.count >= rate.limit {
+                Err(window.count)
+            } else {
+                Ok(RateWindow::new(window.start, window.count + 1))
+            }
+        }
+        _ => Ok(RateWindow::new(now, 1)),
+    }
+}
@@ -1,5 +1,25 @@
 #![cfg_attr(not(feature = "user"), no_std)]
 
+/// Fails the build if the size or alignment of a type shared with user space
+/// changes. Alerts and map keys and values cross the BPF boundary as raw
+/// bytes (see the `Pod` impls), so their layout is part of the interface
+/// between the eBPF programs and user space, which has to be updated along.
+macro_rules! assert_layout {
+    ($ty:ty, $size:expr, $align:expr) => {
+        const _: () = {
+            assert!(
+                core::mem::size_of::<$ty>() == $size,
+                concat!("size of `", stringify!($ty), "` changed"),
+            );
+            assert!(
+                core::mem::align_of::<$ty>() == $align,
+                concat!("alignment of `", stringify!($ty), "` changed"),
+            );
+        };
+    };
+}
+
 pub mod alerts;
 pub mod consts;
+pub mod decision;
 pub mod policy;
@@ -1,7 +1,157 @@
+use crate::consts::{AF_INET, AF_INET6, INODE_WILDCARD};
+
 pub const MAX_PATHS: usize = 4;
 pub const MAX_PORTS: usize = 4;
 pub const MAX_IPV4ADDRS: usize = 1;
 pub const MAX_IPV6ADDRS: usize = 1;
+pub const MAX_BINARIES: usize = 4;
+pub const MAX_SOCKET_KINDS: usize = 4;
+pub const MAX_PORT_RANGES: usize = 4;
+
+/// `SO_REUSEADDR` socket option flag.
+pub const SOCKET_OPTION_REUSEADDR: u8 = 1 << 0;
+/// `SO_REUSEPORT` socket option flag.
+pub const SOCKET_OPTION_REUSEPORT: u8 = 1 << 1;
+/// `SO_BINDTODEVICE` socket option flag.
+pub const SOCKET_OPTION_BINDTODEVICE: u8 = 1 << 2;
+
+/// Key of the per-binary policy maps.
+///
+/// Policies of all namespaces are stored in the same maps, distinguished by
+/// the `namespace` part of the key, so adding namespaces doesn't add maps.
+///
+/// The wildcard policy is told apart by the `wildcard` tag, not by its inode
+/// ([`INODE_WILDCARD`]), so a binary whose inode happens to be the sentinel
+/// gets a key of its own instead of the policy of all binaries.
+#[repr(C)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct InodeKey {
+    pub inode: u64,
+    pub namespace: u32,
+    /// 1 for the key of the wildcard policy, 0 for the key of a binary.
+    pub wildcard: u32,
+}
+
+impl InodeKey {
+    /// Returns the key of the binary (or file) with the given inode, even
+    /// if the inode is [`INODE_WILDCARD`].
+    pub fn new(namespace: u32, inode: u64) -> Self {
+        Self {
+            inode,
+            namespace,
+            wildcard: 0,
+        }
+    }
+
+    /// Returns the key of the wildcard policy in the given namespace.
+    pub fn wildcard(namespace: u32) -> Self {
+        Self {
+            inode: INODE_WILDCARD,
+            namespace,
+            wildcard: 1,
+        }
+    }
+}
+
+/// Key of the per-binary maps of single files, which `file_open` glob
+/// patterns are expanded to.
+#[repr(C)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct FileInodeKey {
+    pub binprm_inode: u64,
+    pub inode: u64,
+    pub namespace: u32,
+    /// Wildcard tag of the key of the binary, see [`InodeKey`].
+    pub wildcard: u32,
+}
+
+impl FileInodeKey {
+    pub fn new(key: InodeKey, inode: u64) -> Self {
+        Self {
+            binprm_inode: key.inode,
+            inode,
+            namespace: key.namespace,
+            wildcard: key.wildcard,
+        }
+    }
+}
+
+/// Key layout flag including the destination port in the keys of the maps of
+/// protected addresses.
+pub const KEY_LAYOUT_PORT: u8 = 1 << 0;
+/// Key layout flag including the address family in the keys of the maps of
+/// protected addresses.
+pub const KEY_LAYOUT_FAMILY: u8 = 1 << 1;
+
+/// Key of the maps of protected IPv4 addresses.
+///
+/// The key layout (a set of `KEY_LAYOUT_*` flags, configured per namespace)
+/// tells which fields are part of the key. Fields outside of the layout are
+/// always 0, so user space and the eBPF programs build the same key for the
+/// same layout. The canonical layouts (12 bytes each) are:
+///
+/// * address only (no flags): `addr`, `namespace`,
+/// * address and port ([`KEY_LAYOUT_PORT`]): `addr`, `namespace`, `port`,
+/// * with [`KEY_LAYOUT_FAMILY`], either of them with `family` (`AF_INET`).
+#[repr(C)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct Ipv4Key {
+    pub addr: u32,
+    pub namespace: u32,
+    pub port: u16,
+    pub family: u16,
+}
+
+impl Ipv4Key {
+    pub fn new(layout: u8, namespace: u32, addr: u32, port: u16) -> Self {
+        Self {
+            addr,
+            namespace,
+            port: key_port(layout, port),
+            family: key_family(layout, AF_INET),
+        }
+    }
+}
+
+/// Key of the maps of protected IPv6 addresses, with the same layouts (24
+/// bytes each) as [`Ipv4Key`] and `family` being `AF_INET6`.
+#[repr(C)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct Ipv6Key {
+    pub addr: [u8; 16],
+    pub namespace: u32,
+    pub port: u16,
+    pub family: u16,
+}
+
+impl Ipv6Key {
+    pub fn new(layout: u8, namespace: u32, addr: [u8; 16], port: u16) -> Self {
+        Self {
+            addr,
+            namespace,
+            port: key_port(layout, port),
+            family: key_family(layout, AF_INET6),
+        }
+    }
+}
+
+#[inline(always)]
+fn key_port(layout: u8, port: u16) -> u16 {
+    if layout & KEY_LAYOUT_PORT != 0 {
+        port
+    } else {
+        0
+    }
+}
+
+#[inline(always)]
+fn key_family(layout: u8, family: u16) -> u16 {
+    if layout & KEY_LAYOUT_FAMILY != 0 {
+        family
+    } else {
+        0
+    }
+}
 
 #[repr(C)]
 #[derive(Copy, Clone)]
@@ -9,6 +159,19 @@ pub struct Paths {
     pub paths: [u64; MAX_PATHS],
 }
 
+impl Paths {
+    pub fn all(&self) -> bool {
+        self.paths[0] == 0
+    }
+
+    /// Returns whether one of the given inodes is listed.
+    pub fn contains_any(&self, inodes: &[u64]) -> bool {
+        self.paths[..MAX_PATHS - 1]
+            .iter()
+            .any(|path| *path != 0 && inodes.contains(path))
+    }
+}
+
 #[repr(C)]
 #[derive(Copy, Clone, Debug)]
 pub struct Ports {
@@ -31,6 +194,97 @@ impl Ports {
     }
 }
 
+/// Socket kinds matched by `socket_create` policies. Each kind is the family
+/// in the upper 16 bits and the type in the lower 16 bits, type 0 matching
+/// all types of the family (socket types start at 1). As with [`Ports`], an
+/// empty array means all kinds.
+#[repr(C)]
+#[derive(Copy, Clone, Debug)]
+pub struct SocketKinds {
+    pub kinds: [u32; MAX_SOCKET_KINDS],
+}
+
+impl SocketKinds {
+    pub fn new(kinds: [u32; MAX_SOCKET_KINDS]) -> Self {
+        Self { kinds }
+    }
+
+    pub fn new_all() -> Self {
+        Self {
+            kinds: [0; MAX_SOCKET_KINDS],
+        }
+    }
+
+    /// Packs a family and a type (0 for all types) into a kind.
+    pub fn kind(family: u16, ty: u16) -> u32 {
+        (family as u32) << 16 | ty as u32
+    }
+
+    pub fn all(&self) -> bool {
+        self.kinds[0] == 0
+    }
+
+    /// Returns whether a socket of the family and type is listed.
+    pub fn contains(&self, family: u16, ty: u16) -> bool {
+        let exact = Self::kind(family, ty);
+        let any_type = Self::kind(family, 0);
+        self.kinds
+            .iter()
+            .any(|kind| *kind != 0 && (*kind == exact || *kind == any_type))
+    }
+}
+
+/// Inclusive range of ports.
+#[repr(C)]
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct PortRange {
+    pub start: u16,
+    pub end: u16,
+}
+
+impl PortRange {
+    pub fn new(start: u16, end: u16) -> Self {
+        Self { start, end }
+    }
+
+    pub fn contains(&self, port: u16) -> bool {
+        self.start <= port && port <= self.end
+    }
+}
+
+/// Ranges of destination ports of `socket_connect_ports` policies. Unused
+/// slots are `0-0`, which no range of a policy can be (connects to port 0
+/// are never matched).
+#[repr(C)]
+#[derive(Copy, Clone, Debug, Default)]
+pub struct PortRanges {
+    pub ranges: [PortRange; MAX_PORT_RANGES],
+}
+
+impl PortRanges {
+    pub fn new(ranges: [PortRange; MAX_PORT_RANGES]) -> Self {
+        Self { ranges }
+    }
+
+    /// Returns the width (`end - start`) of the narrowest range containing
+    /// the port, if any does.
+    #[inline(always)]
+    pub fn narrowest(&self, port: u16) -> Option<u16> {
+        let mut narrowest = None;
+        for range in self.ranges.iter() {
+            if range.end == 0 || !range.contains(port) {
+                continue;
+            }
+            let width = range.end - range.start;
+            match narrowest {
+                Some(narrowest) if narrowest <= width => {}
+                _ => narrowest = Some(width),
+            }
+        }
+        narrowest
+    }
+}
+
 pub trait IpAddrs<T, const U: usize> {
     fn all(&self) -> bool;
     fn addrs(&self) -> [T; U];
@@ -96,14 +350,524 @@ impl IpAddrs<[u8; 16], MAX_IPV6ADDRS> for Ipv6Addrs {
     }
 }
 
+/// Maximum number of CIDRs in each of the CIDR maps of `socket_connect`.
+pub const MAX_CIDRS: u32 = 65536;
+
+/// Maximum number of CIDRs in each of the link-local and metadata address
+/// maps of `socket_connect`.
+pub const MAX_METADATA_CIDRS: u32 = 1024;
+
+/// Maximum number of destinations with a connect rate limit in each of the
+/// rate limit maps of `socket_connect`, across all namespaces.
+pub const MAX_RATE_LIMITS: u32 = 1024;
+
+/// Maximum number of binaries exempt from the policies of the `bpf` hook,
+/// across all namespaces.
+pub const MAX_BPF_EXEMPT: u32 = 16;
+
+/// Bit of the namespace in the keys of the policy maps which tells the two
+/// generations of the policies of a hook apart. The programs look up the
+/// generation set for the hook in `POLICY_GENERATIONS`, and atomic plans
+/// write the other one before switching the hook to it. Namespaces have to
+/// be below it.
+pub const GENERATION_BIT: u32 = 1 << 31;
+
+/// Returns the namespace of the policy map keys of the generation (0 or 1)
+/// of the policies in the namespace.
+#[inline(always)]
+pub fn generation_namespace(namespace: u32, generation: u32) -> u32 {
+    if generation & 1 == 0 {
+        namespace
+    } else {
+        namespace | GENERATION_BIT
+    }
+}
+
+/// Returns the namespace of a policy map key without its generation.
+#[inline(always)]
+pub fn untagged_namespace(namespace: u32) -> u32 {
+    namespace & !GENERATION_BIT
+}
+
+/// Length (in bits) of the prefix of CIDR keys covering the binary inode and
+/// the namespace, which precede the address.
+pub const CIDR_KEY_PREFIX_LEN: u32 = 96;
+
+/// Data of the longest prefix match keys of the IPv4 CIDR maps. All fields
+/// are big-endian, so the prefix is matched in field order: the binary, the
+/// namespace and then the address prefix.
+///
+/// Unlike [`InodeKey`], CIDR keys have no room for a wildcard tag: the
+/// wildcard rules are the ones of [`INODE_WILDCARD`], and the programs skip
+/// the lookup of the rules of a binary whose inode is the sentinel.
+#[repr(C)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct Ipv4CidrKey {
+    pub inode: u64,
+    pub namespace: u32,
+    pub addr: u32,
+}
+
+impl Ipv4CidrKey {
+    pub fn new(namespace: u32, inode: u64, addr: u32) -> Self {
+        Self {
+            inode: inode.to_be(),
+            namespace: namespace.to_be(),
+            addr: addr.to_be(),
+        }
+    }
+}
+
+/// Data of the longest prefix match keys of the IPv6 CIDR maps, see
+/// [`Ipv4CidrKey`].
+#[repr(C)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct Ipv6CidrKey {
+    pub inode: u64,
+    pub namespace: u32,
+    pub addr: [u8; 16],
+    _padding: u32,
+}
+
+impl Ipv6CidrKey {
+    pub fn new(namespace: u32, inode: u64, addr: [u8; 16]) -> Self {
+        Self {
+            inode: inode.to_be(),
+            namespace: namespace.to_be(),
+            addr,
+            _padding: 0,
+        }
+    }
+}
+
+/// Length of the command name (`comm`) of a task, including the terminating
+/// NUL (`TASK_COMM_LEN`). Longer names are truncated by the kernel.
+pub const COMM_LEN: usize = 16;
+
+/// Length (in bits) of the prefix of comm keys covering the namespace, which
+/// precedes the command name.
+pub const COMM_KEY_PREFIX_LEN: u32 = 32;
+
+/// Data of the longest prefix match keys of the comm maps. The namespace is
+/// big-endian, so the prefix is matched in field order: the namespace and
+/// then the bytes of the command name.
+#[repr(C)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct CommKey {
+    pub namespace: u32,
+    pub comm: [u8; COMM_LEN],
+}
+
+impl CommKey {
+    pub fn new(namespace: u32, comm: [u8; COMM_LEN]) -> Self {
+        Self {
+            namespace: namespace.to_be(),
+            comm,
+        }
+    }
+}
+
+/// Inodes of binaries permitted to access a protected resource.
+#[repr(C)]
+#[derive(Copy, Clone)]
+pub struct Binaries {
+    pub binaries: [u64; MAX_BINARIES],
+}
+
+impl Binaries {
+    pub fn new(binaries: [u64; MAX_BINARIES]) -> Self {
+        Self { binaries }
+    }
+
+    #[inline(always)]
+    pub fn contains(&self, binprm_inode: u64) -> bool {
+        // Unused slots are 0, which must not list a binary whose inode is
+        // the wildcard sentinel.
+        binprm_inode != INODE_WILDCARD && self.binaries[..MAX_BINARIES].contains(&binprm_inode)
+    }
+}
+
+/// Verdict allowing the operation.
+pub const VERDICT_ALLOW: u8 = 0;
+/// Verdict denying the operation.
+pub const VERDICT_DENY: u8 = 1;
+
+/// Key of the verdicts for `socket_bind` operations escalated to user space.
+/// The family is the one the rules were looked up for (`AF_INET` or
+/// `AF_INET6`).
+#[repr(C)]
+#[derive(Copy, Clone)]
+pub struct SocketBindVerdictKey {
+    pub binprm_inode: u64,
+    pub port: u16,
+    pub family: u16,
+    pub namespace: u32,
+}
+
+impl SocketBindVerdictKey {
+    pub fn new(namespace: u32, binprm_inode: u64, family: u16, port: u16) -> Self {
+        Self {
+            binprm_inode,
+            port,
+            family,
+            namespace,
+        }
+    }
+}
+
+/// Key of the temporary allows of `socket_bind`, granting a binary (or all
+/// binaries) binds of a port in a namespace regardless of the policies.
+#[repr(C)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct SocketBindGrantKey {
+    pub binprm_inode: u64,
+    pub namespace: u32,
+    pub port: u16,
+    /// Wildcard tag of the key of the binary, see [`InodeKey`].
+    pub wildcard: u16,
+}
+
+impl SocketBindGrantKey {
+    pub fn new(key: InodeKey, port: u16) -> Self {
+        Self {
+            binprm_inode: key.inode,
+            namespace: key.namespace,
+            port,
+            wildcard: key.wildcard as u16,
+        }
+    }
+}
+
+/// Key of a file by its inode number and the device of its filesystem
+/// (`super_block::s_dev`, in the kernel's encoding), for maps shared by all
+/// namespaces, like `EXEMPT_BPF`. Unlike [`InodeKey`], files of different
+/// filesystems with the same inode number get keys of their own.
+#[repr(C)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct DevInodeKey {
+    pub inode: u64,
+    pub dev: u32,
+    _padding: u32,
+}
+
+impl DevInodeKey {
+    pub fn new(inode: u64, dev: u32) -> Self {
+        Self {
+            inode,
+            dev,
+            _padding: 0,
+        }
+    }
+}
+
+/// Key of per-process state, identifying a process by its PID (TGID) and the
+/// start time of its thread group leader (`task_struct::start_time`), so a
+/// process reusing the PID of an exited one doesn't inherit its state.
+#[repr(C)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct ProcessKey {
+    pub start_time: u64,
+    pub pid: u32,
+    _padding: u32,
+}
+
+impl ProcessKey {
+    pub fn new(pid: u32, start_time: u64) -> Self {
+        Self {
+            start_time,
+            pid,
+            _padding: 0,
+        }
+    }
+}
+
+/// Key of the ports bound by a process, see [`ProcessKey`].
+#[repr(C)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct ProcessPortKey {
+    pub start_time: u64,
+    pub pid: u32,
+    pub port: u16,
+    _padding: u16,
+}
+
+impl ProcessPortKey {
+    pub fn new(process: ProcessKey, port: u16) -> Self {
+        Self {
+            start_time: process.start_time,
+            pid: process.pid,
+            port,
+            _padding: 0,
+        }
+    }
+}
+
+/// Key of the groups which `task_fix_setgid` treats as privileged in a
+/// namespace, in addition to group 0.
+#[repr(C)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct GidKey {
+    pub namespace: u32,
+    pub gid: u32,
+}
+
+impl GidKey {
+    pub fn new(namespace: u32, gid: u32) -> Self {
+        Self { namespace, gid }
+    }
+}
+
+/// Connect rate limit of a destination of `socket_connect`: at most `limit`
+/// connects in each window of `window` nanoseconds, counted across all
+/// processes of the namespace. Longest prefix lookups don't return the
+/// matched prefix, so `id` names the counter of the destination (see
+/// [`RateKey`]).
+#[repr(C)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct ConnectRate {
+    pub window: u64,
+    pub limit: u32,
+    pub id: u32,
+}
+
+impl ConnectRate {
+    pub fn new(window: u64, limit: u32, id: u32) -> Self {
+        Self { window, limit, id }
+    }
+}
+
+/// Key of the connect counter of a rate-limited destination in a namespace.
+#[repr(C)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct RateKey {
+    pub namespace: u32,
+    pub id: u32,
+}
+
+impl RateKey {
+    pub fn new(namespace: u32, id: u32) -> Self {
+        Self { namespace, id }
+    }
+}
+
+/// Connects to a rate-limited destination counted in the window starting at
+/// `start` (`bpf_ktime_get_ns`).
+#[repr(C)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct RateWindow {
+    pub start: u64,
+    pub count: u32,
+    _padding: u32,
+}
+
+impl RateWindow {
+    pub fn new(start: u64, count: u32) -> Self {
+        Self {
+            start,
+            count,
+            _padding: 0,
+        }
+    }
+}
+
+/// Retry escalation of a binary: its `threshold`-th denial by the same hook
+/// within `window` nanoseconds in one process, and every later one in the
+/// window, is reported as a persistent violation.
+#[repr(C)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct RetryEscalation {
+    pub window: u64,
+    pub threshold: u32,
+    _padding: u32,
+}
+
+impl RetryEscalation {
+    pub fn new(window: u64, threshold: u32) -> Self {
+        Self {
+            window,
+            threshold,
+            _padding: 0,
+        }
+    }
+}
+
+/// Key of the denials of a process by a hook, counted for retry escalations,
+/// see [`ProcessKey`].
+#[repr(C)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct RetryKey {
+    pub start_time: u64,
+    pub pid: u32,
+    pub hook: u32,
+}
+
+impl RetryKey {
+    pub fn new(process: ProcessKey, hook: u32) -> Self {
+        Self {
+            start_time: process.start_time,
+            pid: process.pid,
+            hook,
+        }
+    }
+}
+
+/// Config flag telling that `exempt_ports` of [`Config`] is set.
+pub const CONFIG_EXEMPT_PORTS: u8 = 1 << 0;
+
+/// Settings of a policy namespace which apply to all its binaries, the value
+/// of the `CONFIG` map (keyed by namespace).
+///
+/// The programs read the entry of the namespace once per invocation, and a
+/// namespace without an entry has the default (all zero) settings. The
+/// layout (8 bytes) is:
+///
+/// | Offset | Field          | Read by          |
+/// |--------|----------------|------------------|
+/// | 0      | `exempt_ports` | `socket_bind`    |
+/// | 4      | `key_layout`   | `socket_connect` |
+/// | 5      | `flags`        | all              |
+/// | 6      | padding        |                  |
+///
+/// New settings take the padding first, so the size stays the same.
+#[repr(C)]
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct Config {
+    /// Range of ports exempt from `socket_bind` enforcement, used only with
+    /// [`CONFIG_EXEMPT_PORTS`].
+    pub exempt_ports: PortRange,
+    /// `KEY_LAYOUT_*` flags of the protected address maps.
+    pub key_layout: u8,
+    /// `CONFIG_*` flags.
+    pub flags: u8,
+    _padding: [u8; 2],
+}
+
+impl Config {
+    /// Returns the range of ports exempt from `socket_bind` enforcement, if
+    /// any.
+    pub fn exempt_ports(&self) -> Option<&PortRange> {
+        (self.flags & CONFIG_EXEMPT_PORTS != 0).then_some(&self.exempt_ports)
+    }
+
+    pub fn set_exempt_ports(&mut self, ports: Option<PortRange>) {
+        match ports {
+            Some(ports) => {
+                self.exempt_ports = ports;
+                self.flags |= CONFIG_EXEMPT_PORTS;
+            }
+            None => {
+                self.exempt_ports = PortRange::default();
+                self.flags &= !CONFIG_EXEMPT_PORTS;
+            }
+        }
+    }
+}
+
+/// IDs of the LSM hooks, distinguishing rules of different hooks in maps
+/// shared by all of them.
+pub const HOOK_BPRM_CHECK_SECURITY: u32 = 1;
+pub const HOOK_FILE_OPEN: u32 = 2;
+pub const HOOK_SB_MOUNT: u32 = 3;
+pub const HOOK_SB_REMOUNT: u32 = 4;
+pub const HOOK_SB_UMOUNT: u32 = 5;
+pub const HOOK_SOCKET_BIND: u32 = 6;
+pub const HOOK_SOCKET_CONNECT: u32 = 7;
+pub const HOOK_SOCKET_LISTEN: u32 = 8;
+pub const HOOK_TASK_FIX_SETUID: u32 = 9;
+pub const HOOK_INODE_CREATE: u32 = 10;
+pub const HOOK_SOCKET_CREATE: u32 = 11;
+pub const HOOK_TASK_FIX_SETGID: u32 = 12;
+pub const HOOK_BPF: u32 = 13;
+
+/// Key of maps shared by all hooks with entries per hook and binary (or all
+/// binaries) in a namespace, like message IDs and alert rate-limit state.
+#[repr(C)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct HookKey {
+    pub inode: u64,
+    pub namespace: u32,
+    pub hook: u32,
+    /// Wildcard tag of the key of the binary, see [`InodeKey`].
+    pub wildcard: u32,
+    _padding: u32,
+}
+
+impl HookKey {
+    pub fn new(key: InodeKey, hook: u32) -> Self {
+        Self {
+            inode: key.inode,
+            namespace: key.namespace,
+            hook,
+            wildcard: key.wildcard,
+            _padding: 0,
+        }
+    }
+}
+
+// Map keys are hashed and compared byte by byte by the kernel, so keys have
+// their padding spelled out (and zeroed by their constructors) and, like the
+// values, a fixed layout (size, alignment).
+assert_layout!(InodeKey, 16, 8);
+assert_layout!(FileInodeKey, 24, 8);
+assert_layout!(Ipv4Key, 12, 4);
+assert_layout!(Ipv6Key, 24, 4);
+assert_layout!(Ipv4CidrKey, 16, 8);
+assert_layout!(Ipv6CidrKey, 32, 8);
+assert_layout!(SocketBindVerdictKey, 16, 8);
+assert_layout!(SocketBindGrantKey, 16, 8);
+assert_layout!(HookKey, 24, 8);
+assert_layout!(DevInodeKey, 16, 8);
+assert_layout!(ProcessKey, 16, 8);
+assert_layout!(ProcessPortKey, 16, 8);
+assert_layout!(GidKey, 8, 4);
+assert_layout!(ConnectRate, 16, 8);
+assert_layout!(RateKey, 8, 4);
+assert_layout!(RateWindow, 16, 8);
+assert_layout!(RetryEscalation, 16, 8);
+assert_layout!(RetryKey, 16, 8);
+assert_layout!(Config, 8, 2);
+assert_layout!(CommKey, 20, 4);
+assert_layout!(Paths, 32, 8);
+assert_layout!(Ports, 8, 2);
+assert_layout!(PortRange, 4, 2);
+assert_layout!(PortRanges, 16, 2);
+assert_layout!(SocketKinds, 16, 4);
+assert_layout!(Ipv4Addrs, 4, 4);
+assert_layout!(Ipv6Addrs, 16, 1);
+assert_layout!(Binaries, 32, 8);
+
 #[cfg(feature = "user")]
 pub mod user {
     use super::*;
 
     use aya::Pod;
 
+    unsafe impl Pod for Binaries {}
+    unsafe impl Pod for Config {}
+    unsafe impl Pod for DevInodeKey {}
+    unsafe impl Pod for FileInodeKey {}
+    unsafe impl Pod for HookKey {}
+    unsafe impl Pod for InodeKey {}
+    unsafe impl Pod for Ipv4Key {}
+    unsafe impl Pod for Ipv6Key {}
+    unsafe impl Pod for Ipv4CidrKey {}
+    unsafe impl Pod for Ipv6CidrKey {}
     unsafe impl Pod for Paths {}
     unsafe impl Pod for Ports {}
+    unsafe impl Pod for PortRange {}
+    unsafe impl Pod for PortRanges {}
+    unsafe impl Pod for SocketKinds {}
     unsafe impl Pod for Ipv4Addrs {}
     unsafe impl Pod for Ipv6Addrs {}
+    unsafe impl Pod for SocketBindVerdictKey {}
+    unsafe impl Pod for SocketBindGrantKey {}
+    unsafe impl Pod for CommKey {}
+    unsafe impl Pod for ProcessKey {}
+    unsafe impl Pod for ProcessPortKey {}
+    unsafe impl Pod for GidKey {}
+    unsafe impl Pod for ConnectRate {}
+    unsafe impl Pod for RateKey {}
+    unsafe impl Pod for RateWindow {}
+    unsafe impl Pod for RetryEscalation {}
+    unsafe impl Pod for RetryKey {}
 }
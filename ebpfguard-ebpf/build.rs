@@ -20,6 +20,7 @@ fn main() {
         "sockaddr",
         "sockaddr_in",
         "sockaddr_in6",
+        "socket",
         "task_struct",
     ];
 
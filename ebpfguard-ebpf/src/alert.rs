@@ -0,0 +1,95 @@
+use aya_bpf::{helpers::bpf_ktime_get_ns, maps::PerfEventArray, BpfContext};
+use ebpfguard_common::{
+    alerts::{Alert, REASON_PERSISTENT},
+    decision::{self, Rules},
+    policy::{HookKey, InodeKey, RetryKey},
+};
+
+use crate::{
+    maps::{ALERT_CHANNELS, ALERT_WINDOWS, LAST_ALERTS, RETRY_ESCALATIONS, RETRY_WINDOWS},
+    message::with_message_id,
+    process::current_process,
+};
+
+/// Outputs the alert of a denied operation to the map, with the message ID of
+/// the rule (see [`with_message_id`]) and the channel of the binary (see
+/// [`with_channel`]).
+///
+/// Alerts are rate-limited per binary and hook: an alert is dropped if the
+/// previous one of the binary for the hook was emitted within the window set
+/// for the binary in the `ALERT_WINDOWS` map, or within the default window of
+/// the namespace if the binary has none. The operation is still denied.
+///
+/// Denials are counted per process and hook for retry escalations (see
+/// [`with_retries`]) before the rate limit, so dropped alerts count too.
+#[inline(always)]
+pub(crate) fn output_alert<C: BpfContext, A: Alert>(
+    ctx: &C,
+    map: &PerfEventArray<A>,
+    hook: u32,
+    alert: A,
+) {
+    let alert = with_channel(with_message_id(hook, alert));
+    let (alert, threshold) = with_retries(ctx, hook, alert);
+
+    let key = InodeKey::new(alert.namespace(), alert.binprm_inode());
+    let window = decision::alert_window(Rules {
+        wildcard: unsafe { ALERT_WINDOWS.get(&InodeKey::wildcard(key.namespace)) },
+        binary: unsafe { ALERT_WINDOWS.get(&key) },
+    });
+    if window > 0 {
+        let last_key = HookKey::new(key, hook);
+        let now = unsafe { bpf_ktime_get_ns() };
+        let last = unsafe { LAST_ALERTS.get(&last_key) }.copied();
+        if !threshold && decision::alert_suppressed(window, last, now) {
+            return;
+        }
+        let _ = LAST_ALERTS.insert(&last_key, &now, 0);
+    }
+
+    map.output(ctx, &alert, 0);
+}
+
+/// Counts the denial against the retry escalation of the binary, as set in
+/// the `RETRY_ESCALATIONS` map, falling back to the default escalation of
+/// the namespace. Denials from the threshold on within the window carry
+/// `REASON_PERSISTENT` instead of their own reason. Also returns whether
+/// this denial reached the threshold, whose alert isn't rate-limited.
+#[inline(always)]
+fn with_retries<C: BpfContext, A: Alert>(ctx: &C, hook: u32, mut alert: A) -> (A, bool) {
+    let key = InodeKey::new(alert.namespace(), alert.binprm_inode());
+    let escalation = match decision::retry_escalation(Rules {
+        wildcard: unsafe { RETRY_ESCALATIONS.get(&InodeKey::wildcard(key.namespace)) },
+        binary: unsafe { RETRY_ESCALATIONS.get(&key) },
+    }) {
+        Some(escalation) => escalation,
+        None => return (alert, false),
+    };
+    let process = match current_process(ctx.pid()) {
+        Ok(process) => process,
+        Err(_) => return (alert, false),
+    };
+
+    let retry_key = RetryKey::new(process, hook);
+    let now = unsafe { bpf_ktime_get_ns() };
+    let window = decision::count_denial(escalation, unsafe { RETRY_WINDOWS.get(&retry_key) }, now);
+    let _ = RETRY_WINDOWS.insert(&retry_key, &window, 0);
+
+    if window.count >= escalation.threshold {
+        alert.set_reason(REASON_PERSISTENT);
+    }
+    (alert, window.count == escalation.threshold)
+}
+
+/// Attaches the channel of the binary which triggered the alert to it, as set
+/// in the `ALERT_CHANNELS` map, falling back to the default channel of the
+/// namespace.
+#[inline(always)]
+pub(crate) fn with_channel<A: Alert>(mut alert: A) -> A {
+    let key = InodeKey::new(alert.namespace(), alert.binprm_inode());
+    alert.set_channel(decision::alert_channel(Rules {
+        wildcard: unsafe { ALERT_CHANNELS.get(&InodeKey::wildcard(key.namespace)) },
+        binary: unsafe { ALERT_CHANNELS.get(&key) },
+    }));
+    alert
+}
@@ -3,7 +3,10 @@ use aya_bpf::{
     helpers::{bpf_get_current_task, bpf_probe_read_kernel},
 };
 
-use crate::{exe_file_inode, inode_i_ino, mm_exe_file, task_struct_mm, vmlinux::task_struct};
+use crate::{
+    exe_file_inode, inode_i_ino, inode_i_sb, mm_exe_file, super_block_s_dev, task_struct_mm,
+    vmlinux::{inode, task_struct},
+};
 
 /// Returns the inode of the current binary.
 ///
@@ -19,12 +22,27 @@ use crate::{exe_file_inode, inode_i_ino, mm_exe_file, task_struct_mm, vmlinux::t
 /// ```
 #[inline(always)]
 pub(crate) fn current_binprm_inode() -> Result<u64, c_long> {
-    let binprm_inode = unsafe {
+    let binprm_inode = unsafe { bpf_probe_read_kernel(inode_i_ino(current_exe_inode()?))? };
+    Ok(binprm_inode)
+}
+
+/// Returns the device of the filesystem of the current binary, in the
+/// kernel's encoding (`super_block::s_dev`).
+#[inline(always)]
+pub(crate) fn current_binprm_dev() -> Result<u32, c_long> {
+    let dev = unsafe {
+        let sb = bpf_probe_read_kernel(inode_i_sb(current_exe_inode()?))?;
+        bpf_probe_read_kernel(super_block_s_dev(sb))?
+    };
+    Ok(dev)
+}
+
+#[inline(always)]
+fn current_exe_inode() -> Result<*const inode, c_long> {
+    unsafe {
         let task = bpf_get_current_task() as *mut task_struct;
         let mm = bpf_probe_read_kernel(task_struct_mm(task))?;
         let file = bpf_probe_read_kernel(mm_exe_file(mm))?;
-        let f_inode = bpf_probe_read_kernel(exe_file_inode(file))?;
-        bpf_probe_read_kernel(inode_i_ino(f_inode))?
-    };
-    Ok(binprm_inode)
+        bpf_probe_read_kernel(exe_file_inode(file))
+    }
 }
@@ -0,0 +1,128 @@
+use aya_bpf::{
+    cty::{c_int, c_long},
+    maps::HashMap,
+    programs::LsmContext,
+    BpfContext,
+};
+use ebpfguard_common::{
+    alerts::{self, REASON_BINARY_DENY_ALL, REASON_DEFAULT_DENY, REASON_WILDCARD_DENY_ALL},
+    policy::{untagged_namespace, DevInodeKey, InodeKey, HOOK_BPF},
+};
+
+use crate::{
+    alert::output_alert,
+    binprm::{current_binprm_dev, current_binprm_inode},
+    generation::policy_namespace,
+    maps::{ALERT_BPF, ALLOWED_BPF, DENIED_BPF, EXEMPT_BPF},
+    namespace::current_namespace,
+    session::current_session,
+    Action, Mode,
+};
+
+/// Inspects the context of `bpf` LSM hook and decides whether to allow or
+/// deny the syscall based on the state of the `ALLOWED_BPF` and `DENIED_BPF`
+/// maps, so that other processes can't load programs or change maps which
+/// would disable the enforcement.
+///
+/// Binaries in the `EXEMPT_BPF` map (the policy managers, by inode and
+/// device) are always allowed, in all namespaces and before the policies are
+/// looked up, so a deny-all policy doesn't lock the policy manager out of its
+/// own maps.
+///
+/// If denied, the syscall is logged to the `ALERT_BPF` map with its command.
+///
+/// # Example
+///
+/// ```rust
+/// use aya_bpf::{macros::lsm, programs::LsmContext};
+///
+/// #[lsm(name = "my_program")]
+/// pub fn my_program(ctx: LsmContext) -> i32 {
+///     bpf(ctx).into()
+/// }
+/// ```
+pub fn bpf(ctx: LsmContext) -> Result<Action, c_long> {
+    let binprm_inode = current_binprm_inode()?;
+    let exempt = DevInodeKey::new(binprm_inode, current_binprm_dev()?);
+    if unsafe { EXEMPT_BPF.get(&exempt).is_some() } {
+        return Ok(Action::Allow);
+    }
+
+    let cmd: c_int = unsafe { ctx.arg(0) };
+    let namespace = policy_namespace(current_namespace(), HOOK_BPF);
+    let key = InodeKey::new(namespace, binprm_inode);
+    let wildcard = InodeKey::wildcard(namespace);
+
+    if unsafe { ALLOWED_BPF.get(&wildcard).is_some() } {
+        return Ok(check_conditions_and_alert(
+            &ctx,
+            &DENIED_BPF,
+            key,
+            cmd as u32,
+            Mode::Denylist,
+        ));
+    }
+
+    if unsafe { DENIED_BPF.get(&wildcard).is_some() } {
+        return Ok(check_conditions_and_alert(
+            &ctx,
+            &ALLOWED_BPF,
+            key,
+            cmd as u32,
+            Mode::Allowlist,
+        ));
+    }
+
+    Ok(Action::Allow)
+}
+
+#[inline(always)]
+fn check_conditions_and_alert(
+    ctx: &LsmContext,
+    map: &HashMap<InodeKey, u8>,
+    key: InodeKey,
+    cmd: u32,
+    mode: Mode,
+) -> Action {
+    match check_conditions(map, key, mode) {
+        Action::Deny(reason) => {
+            output_alert(
+                ctx,
+                &ALERT_BPF,
+                HOOK_BPF,
+                alerts::Bpf::new(
+                    ctx.pid(),
+                    untagged_namespace(key.namespace),
+                    current_session(ctx.pid()),
+                    reason,
+                    key.inode,
+                    cmd,
+                ),
+            );
+            Action::Deny(reason)
+        }
+        action => action,
+    }
+}
+
+#[inline(always)]
+fn check_conditions(map: &HashMap<InodeKey, u8>, key: InodeKey, mode: Mode) -> Action {
+    if unsafe { map.get(&InodeKey::wildcard(key.namespace)).is_some() } {
+        return match mode {
+            Mode::Allowlist => Action::Allow,
+            Mode::Denylist => Action::Deny(REASON_WILDCARD_DENY_ALL),
+        };
+    }
+
+    if unsafe { map.get(&key).is_some() } {
+        return match mode {
+            Mode::Allowlist => Action::Allow,
+            Mode::Denylist => Action::Deny(REASON_BINARY_DENY_ALL),
+        };
+    }
+
+    match mode {
+        Mode::Allowlist => Action::Deny(REASON_DEFAULT_DENY),
+        Mode::Denylist => Action::Allow,
+    }
+}
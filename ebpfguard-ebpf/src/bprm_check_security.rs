@@ -1,8 +1,16 @@
 use aya_bpf::{cty::c_long, programs::LsmContext, BpfContext};
-use ebpfguard_common::alerts;
+use ebpfguard_common::{
+    alerts::{self, REASON_NO_ARGS},
+    policy::HOOK_BPRM_CHECK_SECURITY,
+};
 
 use crate::{
-    binprm::current_binprm_inode, linux_binprm_argc, maps::ALERT_BPRM_CHECK_SECURITY,
+    alert::output_alert,
+    binprm::current_binprm_inode,
+    linux_binprm_argc,
+    maps::ALERT_BPRM_CHECK_SECURITY,
+    namespace::current_namespace,
+    session::{current_session, start_session},
     vmlinux::linux_binprm,
 };
 
@@ -13,13 +21,22 @@ pub fn bprm_check_security(ctx: LsmContext) -> Result<i32, c_long> {
     let old_binprm_inode = current_binprm_inode()?;
 
     if argc < 1 {
-        ALERT_BPRM_CHECK_SECURITY.output(
+        output_alert(
             &ctx,
-            &alerts::BprmCheckSecurity::new(ctx.pid(), old_binprm_inode),
-            0,
+            &ALERT_BPRM_CHECK_SECURITY,
+            HOOK_BPRM_CHECK_SECURITY,
+            alerts::BprmCheckSecurity::new(
+                ctx.pid(),
+                current_namespace(),
+                current_session(ctx.pid()),
+                REASON_NO_ARGS,
+                old_binprm_inode,
+            ),
         );
         return Ok(-1);
     }
 
+    start_session(ctx.pid());
+
     Ok(0)
 }
@@ -0,0 +1,13 @@
+use ebpfguard_common::policy::Config;
+
+use crate::maps::CONFIG;
+
+/// Returns the settings of the namespace, the default ones if it has no
+/// entry in the `CONFIG` map. Hooks read them once per invocation and pass
+/// the copy on, so a decision doesn't mix settings of two updates.
+#[inline(always)]
+pub(crate) fn current_config(namespace: u32) -> Config {
+    unsafe { CONFIG.get(&namespace) }
+        .copied()
+        .unwrap_or_default()
+}
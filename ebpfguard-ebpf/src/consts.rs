@@ -1,4 +1 @@
-/// IPv4 family.
-pub const AF_INET: u16 = 2;
-/// IPv6 family.
-pub const AF_INET6: u16 = 10;
+pub use ebpfguard_common::consts::{AF_INET, AF_INET6, AF_PACKET};
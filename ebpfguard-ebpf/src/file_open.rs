@@ -1,14 +1,20 @@
 use aya_bpf::{cty::c_long, maps::HashMap, programs::LsmContext, BpfContext};
 use ebpfguard_common::{
-    alerts,
-    consts::INODE_WILDCARD,
-    policy::{Paths, MAX_PATHS},
+    alerts::{self, REASON_DEFAULT_DENY, REASON_PROTECTED},
+    policy::{untagged_namespace, FileInodeKey, InodeKey, Paths, HOOK_FILE_OPEN, MAX_PATHS},
 };
 
 use crate::{
+    alert::output_alert,
     binprm::current_binprm_inode,
     dentry_i_ino, file_dentry, file_inode,
-    maps::{ALERT_FILE_OPEN, ALLOWED_FILE_OPEN, DENIED_FILE_OPEN},
+    generation::policy_namespace,
+    maps::{
+        ALERT_FILE_OPEN, ALLOWED_FILE_OPEN, ALLOWED_FILE_OPEN_INODES, DENIED_FILE_OPEN,
+        DENIED_FILE_OPEN_INODES, PROTECTED_FILE_OPEN,
+    },
+    namespace::current_namespace,
+    session::current_session,
     vmlinux::file,
     Action, Mode,
 };
@@ -19,6 +25,15 @@ const MAX_DIR_DEPTH: usize = 16;
 /// deny the operation based on the state of the `ALLOWED_FILE_OPEN` and
 /// `DENIED_FILE_OPEN` maps.
 ///
+/// Files present in the `PROTECTED_FILE_OPEN` map can be opened only by the
+/// binaries listed for them. That check comes first: a binary missing from
+/// the list is denied regardless of its own rules, while a listed binary is
+/// still subject to the `ALLOWED_FILE_OPEN` and `DENIED_FILE_OPEN` rules.
+///
+/// Files listed for a binary in the `ALLOWED_FILE_OPEN_INODES`/
+/// `DENIED_FILE_OPEN_INODES` maps (expanded from glob patterns in user space)
+/// extend the paths of the `ALLOWED_FILE_OPEN`/`DENIED_FILE_OPEN` rules.
+///
 /// If denied, the operation is logged to the `ALERT_FILE_OPEN` map.
 ///
 /// # Example
@@ -35,30 +50,55 @@ const MAX_DIR_DEPTH: usize = 16;
 pub fn file_open(ctx: LsmContext) -> Result<Action, c_long> {
     let file: *const file = unsafe { ctx.arg(0) };
 
+    let namespace = policy_namespace(current_namespace(), HOOK_FILE_OPEN);
     let binprm_inode = current_binprm_inode()?;
     let inode = unsafe { file_inode(file) };
 
-    if let Some(paths) = unsafe { ALLOWED_FILE_OPEN.get(&INODE_WILDCARD) } {
+    if let Some(binaries) = unsafe { PROTECTED_FILE_OPEN.get(&InodeKey::new(namespace, inode)) } {
+        if !binaries.contains(binprm_inode) {
+            output_alert(
+                &ctx,
+                &ALERT_FILE_OPEN,
+                HOOK_FILE_OPEN,
+                alerts::FileOpen::new(
+                    ctx.pid(),
+                    untagged_namespace(namespace),
+                    current_session(ctx.pid()),
+                    REASON_PROTECTED,
+                    binprm_inode,
+                    inode,
+                ),
+            );
+            return Ok(Action::Deny(REASON_PROTECTED));
+        }
+    }
+
+    let key = InodeKey::new(namespace, binprm_inode);
+    let wildcard = InodeKey::wildcard(namespace);
+
+    if let Some(paths) = unsafe { ALLOWED_FILE_OPEN.get(&wildcard) } {
         if paths.paths[0] == 0 {
             return Ok(check_conditions_and_alert(
                 &ctx,
                 &DENIED_FILE_OPEN,
+                &DENIED_FILE_OPEN_INODES,
                 file,
                 inode,
-                binprm_inode,
+                key,
                 Mode::Denylist,
             ));
         }
     }
 
-    if let Some(paths) = unsafe { DENIED_FILE_OPEN.get(&INODE_WILDCARD) } {
+    if let Some(paths) = unsafe { DENIED_FILE_OPEN.get(&wildcard) } {
         if paths.paths[0] == 0 {
             return Ok(check_conditions_and_alert(
                 &ctx,
                 &ALLOWED_FILE_OPEN,
+                &ALLOWED_FILE_OPEN_INODES,
                 file,
                 inode,
-                binprm_inode,
+                key,
                 Mode::Allowlist,
             ));
         }
@@ -70,47 +110,66 @@ pub fn file_open(ctx: LsmContext) -> Result<Action, c_long> {
 #[inline(always)]
 fn check_conditions_and_alert(
     ctx: &LsmContext,
-    map: &HashMap<u64, Paths>,
+    map: &HashMap<InodeKey, Paths>,
+    inodes_map: &HashMap<FileInodeKey, u8>,
     file: *const file,
     inode: u64,
-    binprm_inode: u64,
+    key: InodeKey,
     mode: Mode,
 ) -> Action {
-    match check_conditions(map, file, inode, binprm_inode, mode) {
+    match check_conditions(map, inodes_map, file, inode, key, mode) {
         Action::Allow => Action::Allow,
-        Action::Deny => {
-            ALERT_FILE_OPEN.output(
+        Action::Deny(reason) => {
+            output_alert(
                 ctx,
-                &alerts::FileOpen::new(ctx.pid(), binprm_inode, inode),
-                0,
+                &ALERT_FILE_OPEN,
+                HOOK_FILE_OPEN,
+                alerts::FileOpen::new(
+                    ctx.pid(),
+                    untagged_namespace(key.namespace),
+                    current_session(ctx.pid()),
+                    reason,
+                    key.inode,
+                    inode,
+                ),
             );
-            Action::Deny
+            Action::Deny(reason)
         }
     }
 }
 
 #[inline(always)]
 fn check_conditions(
-    map: &HashMap<u64, Paths>,
+    map: &HashMap<InodeKey, Paths>,
+    inodes_map: &HashMap<FileInodeKey, u8>,
     file: *const file,
     inode: u64,
-    binprm_inode: u64,
+    key: InodeKey,
     mode: Mode,
 ) -> Action {
-    if let Some(paths) = unsafe { map.get(&INODE_WILDCARD) } {
-        if let Some(action) = check_paths(&paths.paths, file, inode, &mode) {
+    if let Some(paths) = unsafe { map.get(&InodeKey::wildcard(key.namespace)) } {
+        if let Some(action) = check_paths(&paths.paths, file, inode, &mode, true) {
             return action;
         }
     }
 
-    if let Some(paths) = unsafe { map.get(&binprm_inode) } {
-        if let Some(action) = check_paths(&paths.paths, file, inode, &mode) {
+    if let Some(paths) = unsafe { map.get(&key) } {
+        if let Some(action) = check_paths(&paths.paths, file, inode, &mode, false) {
             return action;
         }
     }
 
+    let wildcard = InodeKey::wildcard(key.namespace);
+    if let Some(action) = check_inodes(inodes_map, wildcard, file, inode, &mode, true) {
+        return action;
+    }
+
+    if let Some(action) = check_inodes(inodes_map, key, file, inode, &mode, false) {
+        return action;
+    }
+
     match mode {
-        Mode::Allowlist => Action::Deny,
+        Mode::Allowlist => Action::Deny(REASON_DEFAULT_DENY),
         Mode::Denylist => Action::Allow,
     }
 }
@@ -121,22 +180,17 @@ fn check_paths(
     file: *const file,
     inode: u64,
     mode: &Mode,
+    wildcard: bool,
 ) -> Option<Action> {
     if paths[0] == 0 {
-        return Some(match mode {
-            Mode::Allowlist => Action::Allow,
-            Mode::Denylist => Action::Deny,
-        });
+        return Some(Action::matched(mode, wildcard, true));
     }
 
     if paths[..MAX_PATHS - 1].contains(&inode) {
-        return Some(match mode {
-            Mode::Allowlist => Action::Allow,
-            Mode::Denylist => Action::Deny,
-        });
+        return Some(Action::matched(mode, wildcard, false));
     }
 
-    check_parents(paths, file, inode, mode)
+    check_parents(paths, file, inode, mode, wildcard)
 }
 
 #[inline(always)]
@@ -145,6 +199,7 @@ fn check_parents(
     file: *const file,
     mut previous_inode: u64,
     mode: &Mode,
+    wildcard: bool,
 ) -> Option<Action> {
     let mut parent_dentry = unsafe { file_dentry(file) };
     for _ in 0..MAX_DIR_DEPTH {
@@ -156,10 +211,43 @@ fn check_parents(
             break;
         }
         if paths[..MAX_PATHS - 1].contains(&inode) {
-            return Some(match mode {
-                Mode::Allowlist => Action::Allow,
-                Mode::Denylist => Action::Deny,
-            });
+            return Some(Action::matched(mode, wildcard, false));
+        }
+        previous_inode = inode;
+        parent_dentry = unsafe { (*parent_dentry).d_parent };
+    }
+
+    None
+}
+
+/// Checks whether the file or any of its parent directories is listed for
+/// the binary in the given map of single files.
+#[inline(always)]
+fn check_inodes(
+    map: &HashMap<FileInodeKey, u8>,
+    key: InodeKey,
+    file: *const file,
+    mut previous_inode: u64,
+    mode: &Mode,
+    wildcard: bool,
+) -> Option<Action> {
+    let action = Action::matched(mode, wildcard, false);
+
+    if unsafe { map.get(&FileInodeKey::new(key, previous_inode)).is_some() } {
+        return Some(action);
+    }
+
+    let mut parent_dentry = unsafe { file_dentry(file) };
+    for _ in 0..MAX_DIR_DEPTH {
+        if parent_dentry.is_null() {
+            break;
+        }
+        let inode = unsafe { dentry_i_ino(parent_dentry) };
+        if inode == previous_inode {
+            break;
+        }
+        if unsafe { map.get(&FileInodeKey::new(key, inode)).is_some() } {
+            return Some(action);
         }
         previous_inode = inode;
         parent_dentry = unsafe { (*parent_dentry).d_parent };
@@ -0,0 +1,17 @@
+use ebpfguard_common::policy::{generation_namespace, HookKey, InodeKey};
+
+use crate::maps::POLICY_GENERATIONS;
+
+/// Returns the namespace of the keys of the hook's policy maps in the
+/// namespace, tagged with the generation of its policies set in
+/// `POLICY_GENERATIONS`. Hooks look up all their policy maps with it, so a
+/// decision sees either the policies before an atomic plan or the ones after
+/// it. Alerts and the settings maps keep the namespace itself.
+#[inline(always)]
+pub(crate) fn policy_namespace(namespace: u32, hook: u32) -> u32 {
+    let key = HookKey::new(InodeKey::wildcard(namespace), hook);
+    let generation = unsafe { POLICY_GENERATIONS.get(&key) }
+        .copied()
+        .unwrap_or(0);
+    generation_namespace(namespace, generation)
+}
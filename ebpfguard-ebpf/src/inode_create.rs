@@ -0,0 +1,120 @@
+use aya_bpf::{cty::c_long, helpers::bpf_probe_read_kernel, programs::LsmContext, BpfContext};
+use ebpfguard_common::{
+    alerts,
+    decision::{self, Rules},
+    policy::{untagged_namespace, InodeKey, HOOK_INODE_CREATE},
+};
+
+use crate::{
+    alert::output_alert,
+    binprm::current_binprm_inode,
+    dentry_i_ino,
+    generation::policy_namespace,
+    inode_i_ino,
+    maps::{ALERT_INODE_CREATE, ALLOWED_INODE_CREATE, DENIED_INODE_CREATE},
+    namespace::current_namespace,
+    session::current_session,
+    vmlinux::{dentry, inode},
+    Action,
+};
+
+const MAX_DIR_DEPTH: usize = 16;
+
+/// Inspects the context of `inode_create` LSM hook, called before a regular
+/// file is created (by `open` with `O_CREAT`, `creat` or `mknod`), and decides
+/// whether to allow or deny the creation based on the state of the
+/// `ALLOWED_INODE_CREATE` and `DENIED_INODE_CREATE` maps.
+///
+/// Paths in the maps are directories, covering the files created in them and
+/// in their subdirectories (up to a depth of 16). The decision is made by
+/// [`decision::inode_create`].
+///
+/// If denied, the operation is logged to the `ALERT_INODE_CREATE` map.
+///
+/// # Example
+///
+/// ```rust
+/// use aya_bpf::{macros::lsm, programs::LsmContext};
+/// use ebpfguard_ebpf::inode_create;
+///
+/// #[lsm(name = "my_program")]
+/// pub fn my_program(ctx: LsmContext) -> i32 {
+///     match inode_create::inode_create(ctx) {
+///         Ok(ret) => ret.into(),
+///         Err(_) => 0,
+///     }
+/// }
+/// ```
+pub fn inode_create(ctx: LsmContext) -> Result<Action, c_long> {
+    let dir: *const inode = unsafe { ctx.arg(0) };
+    let dentry: *const dentry = unsafe { ctx.arg(1) };
+    let mode: u16 = unsafe { ctx.arg(2) };
+
+    let namespace = policy_namespace(current_namespace(), HOOK_INODE_CREATE);
+    let key = InodeKey::new(namespace, current_binprm_inode()?);
+    let wildcard = InodeKey::wildcard(namespace);
+
+    let allowed = Rules {
+        wildcard: unsafe { ALLOWED_INODE_CREATE.get(&wildcard) },
+        binary: unsafe { ALLOWED_INODE_CREATE.get(&key) },
+    };
+    let denied = Rules {
+        wildcard: unsafe { DENIED_INODE_CREATE.get(&wildcard) },
+        binary: unsafe { DENIED_INODE_CREATE.get(&key) },
+    };
+    // The check is opt-in, skip walking the directories if it's off.
+    if allowed.wildcard.is_none() && denied.wildcard.is_none() {
+        return Ok(Action::Allow);
+    }
+
+    let dir_inode = unsafe { bpf_probe_read_kernel(inode_i_ino(dir))? };
+    let dirs = parent_inodes(dentry, dir_inode);
+
+    let action = decision::inode_create(allowed, denied, &dirs);
+    if let Action::Deny(reason) = action {
+        output_alert(
+            &ctx,
+            &ALERT_INODE_CREATE,
+            HOOK_INODE_CREATE,
+            alerts::InodeCreate::new(
+                ctx.pid(),
+                untagged_namespace(namespace),
+                current_session(ctx.pid()),
+                reason,
+                key.inode,
+                dir_inode,
+                mode,
+            ),
+        );
+    }
+    Ok(action)
+}
+
+/// Returns the inodes of the directory of the created file (`dentry`) and of
+/// its parents, up to the root or [`MAX_DIR_DEPTH`]. Unused slots are 0.
+#[inline(always)]
+fn parent_inodes(dentry: *const dentry, dir_inode: u64) -> [u64; MAX_DIR_DEPTH] {
+    let mut dirs = [0; MAX_DIR_DEPTH];
+    dirs[0] = dir_inode;
+
+    let mut previous_inode = dir_inode;
+    let mut parent_dentry = unsafe { (*dentry).d_parent };
+    for dir in dirs.iter_mut().skip(1) {
+        if parent_dentry.is_null() {
+            break;
+        }
+        parent_dentry = unsafe { (*parent_dentry).d_parent };
+        if parent_dentry.is_null() {
+            break;
+        }
+        let inode = unsafe { dentry_i_ino(parent_dentry) };
+        // The root is its own parent.
+        if inode == previous_inode {
+            break;
+        }
+        *dir = inode;
+        previous_inode = inode;
+    }
+
+    dirs
+}
@@ -1,16 +1,29 @@
 #![no_std]
 #![no_main]
 
+pub mod alert;
 pub mod binprm;
+pub mod bpf;
 pub mod bprm_check_security;
+pub mod config;
 pub mod consts;
 pub mod file_open;
+pub mod generation;
+pub mod inode_create;
 pub mod maps;
+pub mod message;
+pub mod namespace;
+pub mod process;
 pub mod sb_mount;
 pub mod sb_remount;
 pub mod sb_umount;
+pub mod session;
 pub mod socket_bind;
 pub mod socket_connect;
+pub mod socket_create;
+pub mod socket_listen;
+pub mod socket_options;
+pub mod task_fix_setgid;
 pub mod task_fix_setuid;
 #[allow(non_upper_case_globals)]
 #[allow(non_snake_case)]
@@ -18,8 +31,9 @@ pub mod task_fix_setuid;
 #[allow(dead_code)]
 pub mod vmlinux;
 
-use aya_bpf::cty::{c_ushort, c_void};
+use aya_bpf::cty::{c_uchar, c_ushort, c_void};
 use aya_bpf::{cty::c_int, cty::c_uint, cty::c_ulong};
+pub use ebpfguard_common::decision::{Action, Mode};
 
 use vmlinux::cred;
 use vmlinux::dentry;
@@ -30,10 +44,13 @@ use vmlinux::mm_struct;
 use vmlinux::sockaddr;
 use vmlinux::sockaddr_in;
 use vmlinux::sockaddr_in6;
+use vmlinux::socket;
+use vmlinux::super_block;
 use vmlinux::task_struct;
 
 #[allow(improper_ctypes)]
 extern "C" {
+    fn cred_egid_val(target: *const cred) -> c_uint;
     fn cred_gid_val(target: *const cred) -> c_uint;
     fn cred_uid_val(target: *const cred) -> c_uint;
     fn dentry_i_ino(target: *const dentry) -> c_ulong;
@@ -41,6 +58,7 @@ extern "C" {
     fn file_dentry(target: *const file) -> *const dentry;
     fn file_inode(target: *const file) -> c_ulong;
     fn inode_i_ino(inode: *const inode) -> *const c_ulong;
+    fn inode_i_sb(inode: *const inode) -> *const *const super_block;
     fn linux_binprm_argc(task: *const linux_binprm) -> c_int;
     fn mm_exe_file(target: *const mm_struct) -> *const *const file;
     fn sockaddr_in_sin_addr_s_addr(task: *const sockaddr_in) -> c_uint;
@@ -50,24 +68,16 @@ extern "C" {
         sockaddr: *const sockaddr_in6,
         array: &[u8; 16],
     ) -> c_void;
+    fn socket_sk_family(target: *const socket) -> c_ushort;
+    fn socket_sk_num(target: *const socket) -> c_ushort;
+    fn socket_sk_reuse(target: *const socket) -> c_uchar;
+    fn socket_sk_reuseport(target: *const socket) -> c_uchar;
+    fn socket_sk_bound_dev_if(target: *const socket) -> c_int;
+    fn super_block_s_dev(sb: *const super_block) -> *const c_uint;
+    fn task_struct_group_leader(target: *const task_struct) -> *const *const task_struct;
     fn task_struct_mm(target: *const task_struct) -> *const *const mm_struct;
+    fn task_struct_real_parent(target: *const task_struct) -> *const *const task_struct;
+    fn task_struct_start_time(target: *const task_struct) -> *const c_ulong;
+    fn task_struct_tgid(target: *const task_struct) -> *const c_int;
 }
 
-pub enum Mode {
-    Allowlist,
-    Denylist,
-}
-
-pub enum Action {
-    Allow,
-    Deny,
-}
-
-impl From<Action> for i32 {
-    fn from(action: Action) -> Self {
-        match action {
-            Action::Allow => 0,
-            Action::Deny => -1,
-        }
-    }
-}
@@ -4,11 +4,21 @@
 use aya_bpf::{macros::lsm, programs::LsmContext};
 
 use ebpfguard_ebpf::{
-    bprm_check_security::bprm_check_security, file_open::file_open, sb_mount::sb_mount,
-    sb_remount::sb_remount, sb_umount::sb_umount, socket_bind::socket_bind,
-    socket_connect::socket_connect, task_fix_setuid::task_fix_setuid,
+    bpf::bpf, bprm_check_security::bprm_check_security, file_open::file_open,
+    inode_create::inode_create, sb_mount::sb_mount, sb_remount::sb_remount, sb_umount::sb_umount,
+    socket_bind::socket_bind, socket_connect::socket_connect, socket_create::socket_create,
+    socket_listen::socket_listen, task_fix_setgid::task_fix_setgid,
+    task_fix_setuid::task_fix_setuid,
 };
 
+#[lsm(name = "bpf")]
+pub fn prog_bpf(ctx: LsmContext) -> i32 {
+    match bpf(ctx) {
+        Ok(ret) => ret.into(),
+        Err(_) => 0,
+    }
+}
+
 #[lsm(name = "bprm_check_security")]
 pub fn prog_bprm_check_security(ctx: LsmContext) -> i32 {
     match bprm_check_security(ctx) {
@@ -25,6 +35,14 @@ pub fn prog_file_open(ctx: LsmContext) -> i32 {
     }
 }
 
+#[lsm(name = "inode_create")]
+pub fn prog_inode_create(ctx: LsmContext) -> i32 {
+    match inode_create(ctx) {
+        Ok(ret) => ret.into(),
+        Err(_) => 0,
+    }
+}
+
 #[lsm(name = "task_fix_setuid")]
 pub fn prog_task_fix_setuid(ctx: LsmContext) -> i32 {
     match task_fix_setuid(ctx) {
@@ -33,6 +51,14 @@ pub fn prog_task_fix_setuid(ctx: LsmContext) -> i32 {
     }
 }
 
+#[lsm(name = "task_fix_setgid")]
+pub fn prog_task_fix_setgid(ctx: LsmContext) -> i32 {
+    match task_fix_setgid(ctx) {
+        Ok(ret) => ret,
+        Err(_) => 0,
+    }
+}
+
 #[lsm(name = "sb_mount")]
 pub fn prog_sb_mount(ctx: LsmContext) -> i32 {
     match sb_mount(ctx) {
@@ -73,6 +99,22 @@ pub fn prog_socket_connect(ctx: LsmContext) -> i32 {
     }
 }
 
+#[lsm(name = "socket_create")]
+pub fn prog_socket_create(ctx: LsmContext) -> i32 {
+    match socket_create(ctx) {
+        Ok(ret) => ret.into(),
+        Err(_) => 0,
+    }
+}
+
+#[lsm(name = "socket_listen")]
+pub fn prog_socket_listen(ctx: LsmContext) -> i32 {
+    match socket_listen(ctx) {
+        Ok(ret) => ret.into(),
+        Err(_) => 0,
+    }
+}
+
 #[panic_handler]
 fn panic(_info: &core::panic::PanicInfo) -> ! {
     unsafe { core::hint::unreachable_unchecked() }
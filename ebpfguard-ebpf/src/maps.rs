@@ -1,8 +1,67 @@
 use aya_bpf::{
+    bindings::BPF_F_NO_PREALLOC,
     macros::map,
-    maps::{HashMap, PerfEventArray},
+    maps::{HashMap, LpmTrie, LruHashMap, PerfEventArray},
 };
-use ebpfguard_common::{alerts, policy};
+use ebpfguard_common::{
+    alerts,
+    policy::{
+        self, CommKey, ConnectRate, DevInodeKey, FileInodeKey, GidKey, HookKey, InodeKey,
+        Ipv4CidrKey, Ipv4Key, Ipv6CidrKey, Ipv6Key, ProcessKey, ProcessPortKey, RateKey,
+        RateWindow, RetryEscalation, RetryKey, SocketBindGrantKey, MAX_BPF_EXEMPT, MAX_CIDRS,
+        MAX_METADATA_CIDRS, MAX_RATE_LIMITS,
+    },
+};
+
+/// Map of policy namespaces assigned to cgroups (by cgroup ID).
+#[map]
+pub static POLICY_NAMESPACES: HashMap<u64, u32> = HashMap::pinned(1024, 0);
+
+/// Map of the settings of each policy namespace (see [`policy::Config`]).
+/// Without an entry, a namespace has the default settings.
+#[map]
+pub static CONFIG: HashMap<u32, policy::Config> = HashMap::pinned(1024, 0);
+
+/// Map of the generations (0 or 1) of the policies of the hooks in each
+/// namespace (keyed by the wildcard key of the namespace and the hook), see
+/// `policy::GENERATION_BIT`. Hooks look up their policy maps with the keys of
+/// the generation set here, 0 without an entry.
+#[map]
+pub static POLICY_GENERATIONS: HashMap<HookKey, u32> = HashMap::pinned(1024, 0);
+
+/// Map of message IDs of the rules of all hooks, attached to their alerts.
+#[map]
+pub static MESSAGE_IDS: HashMap<HookKey, u16> = HashMap::pinned(1024, 0);
+
+/// Map of alert rate-limit windows (in nanoseconds) of each binary. The
+/// wildcard entry is the default window of the namespace.
+#[map]
+pub static ALERT_WINDOWS: HashMap<InodeKey, u64> = HashMap::pinned(1024, 0);
+
+/// Map of alert channels of each binary, stamped into its alerts for user
+/// space to route them by. The wildcard entry is the default channel of the
+/// namespace.
+#[map]
+pub static ALERT_CHANNELS: HashMap<InodeKey, u8> = HashMap::pinned(1024, 0);
+
+/// Map of the times (`bpf_ktime_get_ns`) of the last alerts emitted for each
+/// binary and hook, checked against `ALERT_WINDOWS`.
+#[map]
+pub static LAST_ALERTS: LruHashMap<HookKey, u64> = LruHashMap::pinned(8192, 0);
+
+/// Map of retry escalations of each binary, see `RetryEscalation`. The
+/// wildcard entry is the default escalation of the namespace.
+#[map]
+pub static RETRY_ESCALATIONS: HashMap<InodeKey, RetryEscalation> = HashMap::pinned(1024, 0);
+
+/// Map of the denials counted in the current window of each process and
+/// hook, checked against `RETRY_ESCALATIONS`.
+#[map]
+pub static RETRY_WINDOWS: LruHashMap<RetryKey, RateWindow> = LruHashMap::pinned(8192, 0);
+
+/// Map of session IDs of processes (by PID and start time), started on exec.
+#[map]
+pub static SESSIONS: LruHashMap<ProcessKey, u64> = LruHashMap::pinned(8192, 0);
 
 #[map]
 pub static ALERT_BPRM_CHECK_SECURITY: PerfEventArray<alerts::BprmCheckSecurity> =
@@ -10,36 +69,81 @@ pub static ALERT_BPRM_CHECK_SECURITY: PerfEventArray<alerts::BprmCheckSecurity>
 
 /// Map of allowed file open paths for each binary.
 #[map]
-pub static ALLOWED_FILE_OPEN: HashMap<u64, policy::Paths> = HashMap::pinned(1024, 0);
+pub static ALLOWED_FILE_OPEN: HashMap<InodeKey, policy::Paths> = HashMap::pinned(1024, 0);
 
 /// Map of denied file open paths for each binary.
 #[map]
-pub static DENIED_FILE_OPEN: HashMap<u64, policy::Paths> = HashMap::pinned(1024, 0);
+pub static DENIED_FILE_OPEN: HashMap<InodeKey, policy::Paths> = HashMap::pinned(1024, 0);
+
+/// Map of files (and directories) allowed to open for each binary, expanded
+/// from glob patterns.
+#[map]
+pub static ALLOWED_FILE_OPEN_INODES: HashMap<FileInodeKey, u8> = HashMap::pinned(16384, 0);
+
+/// Map of files (and directories) denied to open for each binary, expanded
+/// from glob patterns.
+#[map]
+pub static DENIED_FILE_OPEN_INODES: HashMap<FileInodeKey, u8> = HashMap::pinned(16384, 0);
+
+/// Map of binaries allowed to open each protected file.
+#[map]
+pub static PROTECTED_FILE_OPEN: HashMap<InodeKey, policy::Binaries> = HashMap::pinned(1024, 0);
 
 /// Map of alerts for `file_open` LSM hook inspection.
 #[map]
 pub static ALERT_FILE_OPEN: PerfEventArray<alerts::FileOpen> = PerfEventArray::pinned(1024, 0);
 
+/// Map of directories in which each binary is allowed to create files.
+#[map]
+pub static ALLOWED_INODE_CREATE: HashMap<InodeKey, policy::Paths> = HashMap::pinned(1024, 0);
+
+/// Map of directories in which each binary is denied to create files.
+#[map]
+pub static DENIED_INODE_CREATE: HashMap<InodeKey, policy::Paths> = HashMap::pinned(1024, 0);
+
+/// Map of alerts for `inode_create` LSM hook inspection.
+#[map]
+pub static ALERT_INODE_CREATE: PerfEventArray<alerts::InodeCreate> =
+    PerfEventArray::pinned(1024, 0);
+
 /// Map indicating which binaries are allowed to use `setuid`.
 #[map]
-pub static ALLOWED_TASK_FIX_SETUID: HashMap<u64, u8> = HashMap::pinned(1024, 0);
+pub static ALLOWED_TASK_FIX_SETUID: HashMap<InodeKey, u8> = HashMap::pinned(1024, 0);
 
 /// Map indicating which binaries are denied to use `setuid`.
 #[map]
-pub static DENIED_TASK_FIX_SETUID: HashMap<u64, u8> = HashMap::pinned(1024, 0);
+pub static DENIED_TASK_FIX_SETUID: HashMap<InodeKey, u8> = HashMap::pinned(1024, 0);
 
 /// Map of alerts for `setuid` LSM hook inspection.
 #[map]
 pub static ALERT_TASK_FIX_SETUID: PerfEventArray<alerts::TaskFixSetuid> =
     PerfEventArray::pinned(1024, 0);
 
+/// Map indicating which binaries are allowed to switch to privileged groups.
+#[map]
+pub static ALLOWED_TASK_FIX_SETGID: HashMap<InodeKey, u8> = HashMap::pinned(1024, 0);
+
+/// Map indicating which binaries are denied to switch to privileged groups.
+#[map]
+pub static DENIED_TASK_FIX_SETGID: HashMap<InodeKey, u8> = HashMap::pinned(1024, 0);
+
+/// Map of groups treated as privileged by `task_fix_setgid` in each
+/// namespace, in addition to group 0.
+#[map]
+pub static PRIVILEGED_TASK_FIX_SETGID: HashMap<GidKey, u8> = HashMap::pinned(1024, 0);
+
+/// Map of alerts for `task_fix_setgid` LSM hook inspection.
+#[map]
+pub static ALERT_TASK_FIX_SETGID: PerfEventArray<alerts::TaskFixSetgid> =
+    PerfEventArray::pinned(1024, 0);
+
 // Map indicating which binaries are allowed to mount filesystems.
 #[map]
-pub static ALLOWED_SB_MOUNT: HashMap<u64, u8> = HashMap::pinned(1024, 0);
+pub static ALLOWED_SB_MOUNT: HashMap<InodeKey, u8> = HashMap::pinned(1024, 0);
 
 // Map indicating which binaries are denied to mount filesystems.
 #[map]
-pub static DENIED_SB_MOUNT: HashMap<u64, u8> = HashMap::pinned(1024, 0);
+pub static DENIED_SB_MOUNT: HashMap<InodeKey, u8> = HashMap::pinned(1024, 0);
 
 // Map of alerts for `sb_mount` LSM hook inspection.
 #[map]
@@ -47,11 +151,11 @@ pub static ALERT_SB_MOUNT: PerfEventArray<alerts::SbMount> = PerfEventArray::pin
 
 // Map indicating which binaries are allowed to remount filesystems.
 #[map]
-pub static ALLOWED_SB_REMOUNT: HashMap<u64, u8> = HashMap::pinned(1024, 0);
+pub static ALLOWED_SB_REMOUNT: HashMap<InodeKey, u8> = HashMap::pinned(1024, 0);
 
 // Map indicating which binaries are denied to remount filesystems.
 #[map]
-pub static DENIED_SB_REMOUNT: HashMap<u64, u8> = HashMap::pinned(1024, 0);
+pub static DENIED_SB_REMOUNT: HashMap<InodeKey, u8> = HashMap::pinned(1024, 0);
 
 // Map of alerts for `sb_remount` LSM hook inspection.
 #[map]
@@ -59,43 +163,264 @@ pub static ALERT_SB_REMOUNT: PerfEventArray<alerts::SbRemount> = PerfEventArray:
 
 // Map indicating which binaries are allowed to unmount filesystems.
 #[map]
-pub static ALLOWED_SB_UMOUNT: HashMap<u64, u8> = HashMap::pinned(1024, 0);
+pub static ALLOWED_SB_UMOUNT: HashMap<InodeKey, u8> = HashMap::pinned(1024, 0);
 
 // Map indicating which binaries are denied to unmount filesystems.
 #[map]
-pub static DENIED_SB_UMOUNT: HashMap<u64, u8> = HashMap::pinned(1024, 0);
+pub static DENIED_SB_UMOUNT: HashMap<InodeKey, u8> = HashMap::pinned(1024, 0);
 
 // Map of alerts for `sb_umount` LSM hook inspection.
 #[map]
 pub static ALERT_SB_UMOUNT: PerfEventArray<alerts::SbUmount> = PerfEventArray::pinned(1024, 0);
 
+/// Map indicating which binaries are allowed to use the `bpf` syscall.
+#[map]
+pub static ALLOWED_BPF: HashMap<InodeKey, u8> = HashMap::pinned(1024, 0);
+
+/// Map indicating which binaries are denied to use the `bpf` syscall.
+#[map]
+pub static DENIED_BPF: HashMap<InodeKey, u8> = HashMap::pinned(1024, 0);
+
+/// Map of binaries (by inode and device) exempt from the `bpf` policies of
+/// all namespaces, i.e. the binaries of the policy managers, which change
+/// the maps through the syscall.
+#[map]
+pub static EXEMPT_BPF: HashMap<DevInodeKey, u8> = HashMap::pinned(MAX_BPF_EXEMPT, 0);
+
+/// Map of alerts for `bpf` LSM hook inspection.
+#[map]
+pub static ALERT_BPF: PerfEventArray<alerts::Bpf> = PerfEventArray::pinned(1024, 0);
+
 /// Map of allowed socket bind ports for each binary.
 #[map]
-pub static ALLOWED_SOCKET_BIND: HashMap<u64, policy::Ports> = HashMap::pinned(1024, 0);
+pub static ALLOWED_SOCKET_BIND: HashMap<InodeKey, policy::Ports> = HashMap::pinned(1024, 0);
 
 /// Map of denied socket bind ports for each binary.
 #[map]
-pub static DENIED_SOCKET_BIND: HashMap<u64, policy::Ports> = HashMap::pinned(1024, 0);
+pub static DENIED_SOCKET_BIND: HashMap<InodeKey, policy::Ports> = HashMap::pinned(1024, 0);
+
+/// Map of allowed ports of `AF_INET` binds for each binary, replacing its
+/// entries in `ALLOWED_SOCKET_BIND`/`DENIED_SOCKET_BIND` for them.
+#[map]
+pub static ALLOWED_SOCKET_BIND_V4: HashMap<InodeKey, policy::Ports> = HashMap::pinned(1024, 0);
+
+/// Map of denied ports of `AF_INET` binds for each binary.
+#[map]
+pub static DENIED_SOCKET_BIND_V4: HashMap<InodeKey, policy::Ports> = HashMap::pinned(1024, 0);
+
+/// Map of allowed ports of `AF_INET6` binds for each binary, replacing its
+/// entries in `ALLOWED_SOCKET_BIND`/`DENIED_SOCKET_BIND` for them.
+#[map]
+pub static ALLOWED_SOCKET_BIND_V6: HashMap<InodeKey, policy::Ports> = HashMap::pinned(1024, 0);
+
+/// Map of denied ports of `AF_INET6` binds for each binary.
+#[map]
+pub static DENIED_SOCKET_BIND_V6: HashMap<InodeKey, policy::Ports> = HashMap::pinned(1024, 0);
+
+/// Map of allowed ports for each command name prefix, for binaries without
+/// their own entries in `ALLOWED_SOCKET_BIND`/`DENIED_SOCKET_BIND`.
+#[map]
+pub static ALLOWED_SOCKET_BIND_COMM: LpmTrie<CommKey, policy::Ports> =
+    LpmTrie::pinned(1024, BPF_F_NO_PREALLOC);
+
+/// Map of denied ports for each command name prefix, for binaries without
+/// their own entries in `ALLOWED_SOCKET_BIND`/`DENIED_SOCKET_BIND`.
+#[map]
+pub static DENIED_SOCKET_BIND_COMM: LpmTrie<CommKey, policy::Ports> =
+    LpmTrie::pinned(1024, BPF_F_NO_PREALLOC);
+
+/// Map indicating which binaries are allowed to bind `AF_PACKET` sockets.
+#[map]
+pub static ALLOWED_SOCKET_BIND_PACKET: HashMap<InodeKey, u8> = HashMap::pinned(1024, 0);
+
+/// Map indicating which binaries are denied to bind `AF_PACKET` sockets.
+#[map]
+pub static DENIED_SOCKET_BIND_PACKET: HashMap<InodeKey, u8> = HashMap::pinned(1024, 0);
+
+/// Map of ports temporarily allowed to bind for each binary, regardless of
+/// the policies. Entries are removed by user space when they expire.
+#[map]
+pub static GRANTS_SOCKET_BIND: HashMap<SocketBindGrantKey, u8> = HashMap::pinned(1024, 0);
 
 /// Map of alerts for `socket_bind` LSM hook inspection.
 #[map]
 pub static ALERT_SOCKET_BIND: PerfEventArray<alerts::SocketBind> = PerfEventArray::pinned(1024, 0);
 
+/// Map indicating which binaries have undecided socket binds escalated to
+/// user space, with the fallback verdict applied until user space answers.
+#[map]
+pub static ESCALATE_SOCKET_BIND: HashMap<InodeKey, u8> = HashMap::pinned(1024, 0);
+
+/// Map of verdicts for escalated socket binds, written by user space.
+#[map]
+pub static VERDICT_SOCKET_BIND: LruHashMap<policy::SocketBindVerdictKey, u8> =
+    LruHashMap::pinned(1024, 0);
+
+/// Map of policy generations for socket binds in each policy namespace,
+/// bumped by user space after every change of the socket bind policies.
+#[map]
+pub static GENERATION_SOCKET_BIND: HashMap<u32, u64> = HashMap::pinned(1024, 0);
+
+/// Map of socket binds allowed by the policy maps, with the policy generation
+/// they were decided in. Entries of older generations are stale.
+#[map]
+pub static CACHE_SOCKET_BIND: LruHashMap<policy::SocketBindVerdictKey, u64> =
+    LruHashMap::pinned(1024, 0);
+
+/// Map of socket options (`SOCKET_OPTION_*` flags) for which otherwise
+/// allowed socket binds are denied, for each binary.
+#[map]
+pub static OPTIONS_SOCKET_BIND: HashMap<InodeKey, u8> = HashMap::pinned(1024, 0);
+
+/// Map of the numbers of distinct ports each process may bind, for each
+/// binary. The wildcard entry is the default limit of the namespace.
+#[map]
+pub static BIND_LIMIT_SOCKET_BIND: HashMap<InodeKey, u32> = HashMap::pinned(1024, 0);
+
+/// Map of the numbers of distinct ports bound by processes with a bind
+/// limit, checked against `BIND_LIMIT_SOCKET_BIND`.
+#[map]
+pub static BIND_COUNT_SOCKET_BIND: LruHashMap<ProcessKey, u32> = LruHashMap::pinned(8192, 0);
+
+/// Map of the ports counted in `BIND_COUNT_SOCKET_BIND`, so binding a port
+/// again doesn't count it twice.
+#[map]
+pub static BOUND_PORTS_SOCKET_BIND: LruHashMap<ProcessPortKey, u8> = LruHashMap::pinned(8192, 0);
+
+/// Map of socket binds escalated to user space for a verdict.
+#[map]
+pub static ALERT_SOCKET_BIND_ESCALATION: PerfEventArray<alerts::SocketBind> =
+    PerfEventArray::pinned(1024, 0);
+
+/// Map of socket kinds (family and type) each binary is allowed to create.
+#[map]
+pub static ALLOWED_SOCKET_CREATE: HashMap<InodeKey, policy::SocketKinds> = HashMap::pinned(1024, 0);
+
+/// Map of socket kinds (family and type) each binary is denied to create.
+#[map]
+pub static DENIED_SOCKET_CREATE: HashMap<InodeKey, policy::SocketKinds> = HashMap::pinned(1024, 0);
+
+/// Second slot of `ALLOWED_SOCKET_CREATE`, see `SLOT_SOCKET_CREATE`.
+#[map]
+pub static ALLOWED_SOCKET_CREATE_1: HashMap<InodeKey, policy::SocketKinds> =
+    HashMap::pinned(1024, 0);
+
+/// Second slot of `DENIED_SOCKET_CREATE`, see `SLOT_SOCKET_CREATE`.
+#[map]
+pub static DENIED_SOCKET_CREATE_1: HashMap<InodeKey, policy::SocketKinds> =
+    HashMap::pinned(1024, 0);
+
+/// Map of the slot of socket create maps (0 for `ALLOWED_SOCKET_CREATE` and
+/// `DENIED_SOCKET_CREATE`, 1 for the `_1` maps) each policy namespace reads.
+/// User space fills the other slot and switches to it to swap all policies
+/// at once.
+#[map]
+pub static SLOT_SOCKET_CREATE: HashMap<u32, u32> = HashMap::pinned(1024, 0);
+
+/// Map of alerts for `socket_create` LSM hook inspection.
+#[map]
+pub static ALERT_SOCKET_CREATE: PerfEventArray<alerts::SocketCreate> =
+    PerfEventArray::pinned(1024, 0);
+
+/// Map of allowed socket listen ports for each binary.
+#[map]
+pub static ALLOWED_SOCKET_LISTEN: HashMap<InodeKey, policy::Ports> = HashMap::pinned(1024, 0);
+
+/// Map of denied socket listen ports for each binary.
+#[map]
+pub static DENIED_SOCKET_LISTEN: HashMap<InodeKey, policy::Ports> = HashMap::pinned(1024, 0);
+
+/// Map of socket options (`SOCKET_OPTION_*` flags) for which otherwise
+/// allowed socket listens are denied, for each binary.
+#[map]
+pub static OPTIONS_SOCKET_LISTEN: HashMap<InodeKey, u8> = HashMap::pinned(1024, 0);
+
+/// Map of alerts for `socket_listen` LSM hook inspection.
+#[map]
+pub static ALERT_SOCKET_LISTEN: PerfEventArray<alerts::SocketListen> =
+    PerfEventArray::pinned(1024, 0);
+
 /// Map of allowed socket connect IPv4 addresses for each binary.
 #[map]
-pub static ALLOWED_SOCKET_CONNECT_V4: HashMap<u64, policy::Ipv4Addrs> = HashMap::pinned(1024, 0);
+pub static ALLOWED_SOCKET_CONNECT_V4: HashMap<InodeKey, policy::Ipv4Addrs> =
+    HashMap::pinned(1024, 0);
 
 /// Map of denied socket connect IPv4 addresses for each binary.
 #[map]
-pub static DENIED_SOCKET_CONNECT_V4: HashMap<u64, policy::Ipv4Addrs> = HashMap::pinned(1024, 0);
+pub static DENIED_SOCKET_CONNECT_V4: HashMap<InodeKey, policy::Ipv4Addrs> =
+    HashMap::pinned(1024, 0);
 
 /// Map of allowed socket connect IPv6 addresses for each binary.
 #[map]
-pub static ALLOWED_SOCKET_CONNECT_V6: HashMap<u64, policy::Ipv6Addrs> = HashMap::pinned(1024, 0);
+pub static ALLOWED_SOCKET_CONNECT_V6: HashMap<InodeKey, policy::Ipv6Addrs> =
+    HashMap::pinned(1024, 0);
 
 /// Map of denied socket connect IPv6 addresses for each binary.
 #[map]
-pub static DENIED_SOCKET_CONNECT_V6: HashMap<u64, policy::Ipv6Addrs> = HashMap::pinned(1024, 0);
+pub static DENIED_SOCKET_CONNECT_V6: HashMap<InodeKey, policy::Ipv6Addrs> =
+    HashMap::pinned(1024, 0);
+
+/// Map of ranges of destination ports allowed within the denied ranges of
+/// `DENIED_SOCKET_CONNECT_PORTS`, for each binary.
+#[map]
+pub static ALLOWED_SOCKET_CONNECT_PORTS: HashMap<InodeKey, policy::PortRanges> =
+    HashMap::pinned(1024, 0);
+
+/// Map of ranges of destination ports each binary is denied to connect to.
+#[map]
+pub static DENIED_SOCKET_CONNECT_PORTS: HashMap<InodeKey, policy::PortRanges> =
+    HashMap::pinned(1024, 0);
+
+/// Map of binaries allowed to connect to each protected IPv4 address.
+#[map]
+pub static PROTECTED_SOCKET_CONNECT_V4: HashMap<Ipv4Key, policy::Binaries> =
+    HashMap::pinned(1024, 0);
+
+/// Map of binaries allowed to connect to each protected IPv6 address.
+#[map]
+pub static PROTECTED_SOCKET_CONNECT_V6: HashMap<Ipv6Key, policy::Binaries> =
+    HashMap::pinned(1024, 0);
+
+/// Map of denied IPv4 CIDRs for each binary, matched by the longest prefix.
+#[map]
+pub static DENIED_SOCKET_CONNECT_CIDR_V4: LpmTrie<Ipv4CidrKey, u8> =
+    LpmTrie::pinned(MAX_CIDRS, BPF_F_NO_PREALLOC);
+
+/// Map of denied IPv6 CIDRs for each binary, matched by the longest prefix.
+#[map]
+pub static DENIED_SOCKET_CONNECT_CIDR_V6: LpmTrie<Ipv6CidrKey, u8> =
+    LpmTrie::pinned(MAX_CIDRS, BPF_F_NO_PREALLOC);
+
+/// Map of link-local and metadata IPv4 CIDRs denied for each binary, matched
+/// by the longest prefix.
+#[map]
+pub static DENIED_SOCKET_CONNECT_METADATA_V4: LpmTrie<Ipv4CidrKey, u8> =
+    LpmTrie::pinned(MAX_METADATA_CIDRS, BPF_F_NO_PREALLOC);
+
+/// Map of link-local and metadata IPv6 CIDRs denied for each binary, matched
+/// by the longest prefix.
+#[map]
+pub static DENIED_SOCKET_CONNECT_METADATA_V6: LpmTrie<Ipv6CidrKey, u8> =
+    LpmTrie::pinned(MAX_METADATA_CIDRS, BPF_F_NO_PREALLOC);
+
+/// Map of connect rate limits of IPv4 destinations (for all binaries),
+/// matched by the longest prefix.
+#[map]
+pub static RATE_SOCKET_CONNECT_V4: LpmTrie<Ipv4CidrKey, ConnectRate> =
+    LpmTrie::pinned(MAX_RATE_LIMITS, BPF_F_NO_PREALLOC);
+
+/// Map of connect rate limits of IPv6 destinations (for all binaries),
+/// matched by the longest prefix.
+#[map]
+pub static RATE_SOCKET_CONNECT_V6: LpmTrie<Ipv6CidrKey, ConnectRate> =
+    LpmTrie::pinned(MAX_RATE_LIMITS, BPF_F_NO_PREALLOC);
+
+/// Map of the connects counted in the current window of each rate-limited
+/// destination, checked against `RATE_SOCKET_CONNECT_V4`/
+/// `RATE_SOCKET_CONNECT_V6`.
+#[map]
+pub static RATE_WINDOWS_SOCKET_CONNECT: HashMap<RateKey, RateWindow> =
+    HashMap::pinned(MAX_RATE_LIMITS, 0);
 
 /// Map of alerts for `socket_connect` LSM hook inspection.
 #[map]
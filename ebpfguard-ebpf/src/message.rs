@@ -0,0 +1,32 @@
+use ebpfguard_common::{
+    alerts::{self, Alert},
+    policy::{HookKey, InodeKey},
+};
+
+use crate::maps::MESSAGE_IDS;
+
+/// Attaches the message ID of the rule which denied the operation to the
+/// alert, as set in the `MESSAGE_IDS` map for the hook.
+///
+/// Denials by the policy of all binaries carry the message of the wildcard
+/// rule. Other denials carry the message of the binary, falling back to the
+/// wildcard one. Without a message, the alert keeps `MESSAGE_NONE`.
+#[inline(always)]
+pub(crate) fn with_message_id<A: Alert>(hook: u32, mut alert: A) -> A {
+    let namespace = alert.namespace();
+    let wildcard = HookKey::new(InodeKey::wildcard(namespace), hook);
+
+    let mut message_id = None;
+    if !alerts::is_wildcard_reason(alert.reason()) {
+        let key = HookKey::new(InodeKey::new(namespace, alert.binprm_inode()), hook);
+        message_id = unsafe { MESSAGE_IDS.get(&key) }.copied();
+    }
+    if message_id.is_none() {
+        message_id = unsafe { MESSAGE_IDS.get(&wildcard) }.copied();
+    }
+
+    if let Some(message_id) = message_id {
+        alert.set_message_id(message_id);
+    }
+    alert
+}
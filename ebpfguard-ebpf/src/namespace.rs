@@ -0,0 +1,18 @@
+use aya_bpf::helpers::bpf_get_current_cgroup_id;
+use ebpfguard_common::consts::NAMESPACE_DEFAULT;
+
+use crate::maps::POLICY_NAMESPACES;
+
+/// Returns the policy namespace of the current process, assigned to its
+/// cgroup in the `POLICY_NAMESPACES` map. Processes in cgroups without an
+/// assigned namespace belong to `NAMESPACE_DEFAULT`.
+///
+/// Only the cgroup of the process is looked up, namespaces are not inherited
+/// from parent cgroups.
+#[inline(always)]
+pub(crate) fn current_namespace() -> u32 {
+    let cgroup_id = unsafe { bpf_get_current_cgroup_id() };
+    unsafe { POLICY_NAMESPACES.get(&cgroup_id) }
+        .copied()
+        .unwrap_or(NAMESPACE_DEFAULT)
+}
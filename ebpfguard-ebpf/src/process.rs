@@ -0,0 +1,34 @@
+use aya_bpf::{
+    cty::c_long,
+    helpers::{bpf_get_current_task, bpf_probe_read_kernel},
+};
+use ebpfguard_common::policy::ProcessKey;
+
+use crate::{
+    task_struct_group_leader, task_struct_real_parent, task_struct_start_time, task_struct_tgid,
+    vmlinux::task_struct,
+};
+
+/// Returns the key of the current process with the given PID (TGID), with
+/// the start time of its thread group leader, which all threads of the
+/// process share and which differs between processes reusing the PID.
+#[inline(always)]
+pub(crate) fn current_process(pid: u32) -> Result<ProcessKey, c_long> {
+    let start_time = unsafe {
+        let task = bpf_get_current_task() as *mut task_struct;
+        let leader = bpf_probe_read_kernel(task_struct_group_leader(task))?;
+        bpf_probe_read_kernel(task_struct_start_time(leader))?
+    };
+    Ok(ProcessKey::new(pid, start_time))
+}
+
+/// Returns the PID (TGID) of the parent of the current process.
+#[inline(always)]
+pub(crate) fn current_ppid() -> Result<u32, c_long> {
+    let ppid = unsafe {
+        let task = bpf_get_current_task() as *mut task_struct;
+        let parent = bpf_probe_read_kernel(task_struct_real_parent(task))?;
+        bpf_probe_read_kernel(task_struct_tgid(parent))?
+    };
+    Ok(ppid as u32)
+}
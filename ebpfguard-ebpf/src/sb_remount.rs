@@ -1,9 +1,16 @@
 use aya_bpf::{cty::c_long, maps::HashMap, programs::LsmContext, BpfContext};
-use ebpfguard_common::{alerts, consts::INODE_WILDCARD};
+use ebpfguard_common::{
+    alerts::{self, REASON_BINARY_DENY_ALL, REASON_DEFAULT_DENY, REASON_WILDCARD_DENY_ALL},
+    policy::{untagged_namespace, InodeKey, HOOK_SB_REMOUNT},
+};
 
 use crate::{
+    alert::output_alert,
     binprm::current_binprm_inode,
+    generation::policy_namespace,
     maps::{ALERT_SB_REMOUNT, ALLOWED_SB_REMOUNT, DENIED_SB_REMOUNT},
+    namespace::current_namespace,
+    session::current_session,
     Action, Mode,
 };
 
@@ -24,22 +31,24 @@ use crate::{
 /// }
 /// ```
 pub fn sb_remount(ctx: LsmContext) -> Result<Action, c_long> {
-    let binprm_inode = current_binprm_inode()?;
+    let namespace = policy_namespace(current_namespace(), HOOK_SB_REMOUNT);
+    let key = InodeKey::new(namespace, current_binprm_inode()?);
+    let wildcard = InodeKey::wildcard(namespace);
 
-    if unsafe { ALLOWED_SB_REMOUNT.get(&INODE_WILDCARD).is_some() } {
+    if unsafe { ALLOWED_SB_REMOUNT.get(&wildcard).is_some() } {
         return Ok(check_conditions_and_alert(
             &ctx,
             &DENIED_SB_REMOUNT,
-            binprm_inode,
+            key,
             Mode::Denylist,
         ));
     }
 
-    if unsafe { DENIED_SB_REMOUNT.get(&INODE_WILDCARD).is_some() } {
+    if unsafe { DENIED_SB_REMOUNT.get(&wildcard).is_some() } {
         return Ok(check_conditions_and_alert(
             &ctx,
             &ALLOWED_SB_REMOUNT,
-            binprm_inode,
+            key,
             Mode::Allowlist,
         ));
     }
@@ -50,37 +59,48 @@ pub fn sb_remount(ctx: LsmContext) -> Result<Action, c_long> {
 #[inline(always)]
 fn check_conditions_and_alert(
     ctx: &LsmContext,
-    map: &HashMap<u64, u8>,
-    binprm_inode: u64,
+    map: &HashMap<InodeKey, u8>,
+    key: InodeKey,
     mode: Mode,
 ) -> Action {
-    match check_conditions(map, binprm_inode, mode) {
-        Action::Deny => {
-            ALERT_SB_REMOUNT.output(ctx, &alerts::SbRemount::new(ctx.pid(), binprm_inode), 0);
-            Action::Deny
+    match check_conditions(map, key, mode) {
+        Action::Deny(reason) => {
+            output_alert(
+                ctx,
+                &ALERT_SB_REMOUNT,
+                HOOK_SB_REMOUNT,
+                alerts::SbRemount::new(
+                    ctx.pid(),
+                    untagged_namespace(key.namespace),
+                    current_session(ctx.pid()),
+                    reason,
+                    key.inode,
+                ),
+            );
+            Action::Deny(reason)
         }
         action => action,
     }
 }
 
 #[inline(always)]
-fn check_conditions(map: &HashMap<u64, u8>, binprm_inode: u64, mode: Mode) -> Action {
-    if unsafe { map.get(&INODE_WILDCARD).is_some() } {
+fn check_conditions(map: &HashMap<InodeKey, u8>, key: InodeKey, mode: Mode) -> Action {
+    if unsafe { map.get(&InodeKey::wildcard(key.namespace)).is_some() } {
         return match mode {
             Mode::Allowlist => Action::Allow,
-            Mode::Denylist => Action::Deny,
+            Mode::Denylist => Action::Deny(REASON_WILDCARD_DENY_ALL),
         };
     }
 
-    if unsafe { map.get(&binprm_inode).is_some() } {
+    if unsafe { map.get(&key).is_some() } {
         return match mode {
             Mode::Allowlist => Action::Allow,
-            Mode::Denylist => Action::Deny,
+            Mode::Denylist => Action::Deny(REASON_BINARY_DENY_ALL),
         };
     }
 
     match mode {
-        Mode::Allowlist => Action::Deny,
+        Mode::Allowlist => Action::Deny(REASON_DEFAULT_DENY),
         Mode::Denylist => Action::Allow,
     }
 }
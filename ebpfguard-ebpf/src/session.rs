@@ -0,0 +1,35 @@
+use aya_bpf::helpers::bpf_ktime_get_ns;
+
+use crate::{maps::SESSIONS, process::current_process};
+
+/// Session ID assigned to processes which were started before the eBPF
+/// programs were loaded, or whose session was evicted from the `SESSIONS`
+/// map.
+pub const SESSION_UNKNOWN: u64 = 0;
+
+/// Starts a new session for the process with the given PID (TGID). Called on
+/// every exec checked by `bprm_check_security`, so all threads of the process
+/// share the session, and a new exec in the same process starts a new one
+/// (even if the exec fails later). Forked children don't inherit the session.
+///
+/// The session ID is the monotonic time of the exec in nanoseconds, which is
+/// unique per boot unless two processes exec within the same nanosecond.
+/// Sessions are keyed by the process (see
+/// [`ProcessKey`](ebpfguard_common::policy::ProcessKey)), so a process
+/// reusing the PID of an exited one doesn't inherit its session.
+#[inline(always)]
+pub(crate) fn start_session(pid: u32) {
+    let session = unsafe { bpf_ktime_get_ns() };
+    if let Ok(process) = current_process(pid) {
+        let _ = SESSIONS.insert(&process, &session, 0);
+    }
+}
+
+/// Returns the session ID of the current process (see [`start_session`]).
+#[inline(always)]
+pub(crate) fn current_session(pid: u32) -> u64 {
+    current_process(pid)
+        .ok()
+        .and_then(|process| unsafe { SESSIONS.get(&process) }.copied())
+        .unwrap_or(SESSION_UNKNOWN)
+}
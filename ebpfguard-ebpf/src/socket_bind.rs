@@ -1,12 +1,41 @@
-use aya_bpf::{cty::c_long, programs::LsmContext, BpfContext};
-use ebpfguard_common::{alerts, consts::INODE_WILDCARD, policy::MAX_PORTS};
+use aya_bpf::{
+    cty::c_long, helpers::bpf_probe_read_kernel, maps::lpm_trie::Key, programs::LsmContext,
+    BpfContext,
+};
+use ebpfguard_common::{
+    alerts::{
+        self, REASON_BIND_LIMIT, REASON_DEFAULT_DENY, REASON_ESCALATION_FALLBACK,
+        REASON_ESCALATION_VERDICT, REASON_SOCKET_OPTION,
+    },
+    decision::{self, PortRules, Rules},
+    policy::{
+        untagged_namespace, CommKey, InodeKey, ProcessPortKey, SocketBindGrantKey,
+        SocketBindVerdictKey, COMM_KEY_PREFIX_LEN, COMM_LEN, HOOK_SOCKET_BIND,
+    },
+};
 
 use crate::{
+    alert::{output_alert, with_channel},
     binprm::current_binprm_inode,
-    consts::AF_INET,
-    maps::{ALERT_SOCKET_BIND, ALLOWED_SOCKET_BIND, DENIED_SOCKET_BIND},
-    sockaddr_in_sin_port, sockaddr_sa_family,
-    vmlinux::{sockaddr, sockaddr_in},
+    config::current_config,
+    consts::{AF_INET, AF_INET6, AF_PACKET},
+    generation::policy_namespace,
+    maps::{
+        ALERT_SOCKET_BIND, ALERT_SOCKET_BIND_ESCALATION, ALLOWED_SOCKET_BIND,
+        ALLOWED_SOCKET_BIND_COMM, ALLOWED_SOCKET_BIND_PACKET, ALLOWED_SOCKET_BIND_V4,
+        ALLOWED_SOCKET_BIND_V6, BIND_COUNT_SOCKET_BIND, BIND_LIMIT_SOCKET_BIND,
+        BOUND_PORTS_SOCKET_BIND, CACHE_SOCKET_BIND, DENIED_SOCKET_BIND, DENIED_SOCKET_BIND_COMM,
+        DENIED_SOCKET_BIND_PACKET, DENIED_SOCKET_BIND_V4, DENIED_SOCKET_BIND_V6,
+        ESCALATE_SOCKET_BIND, GENERATION_SOCKET_BIND, GRANTS_SOCKET_BIND, OPTIONS_SOCKET_BIND,
+        VERDICT_SOCKET_BIND,
+    },
+    message::with_message_id,
+    namespace::current_namespace,
+    process::current_process,
+    session::current_session,
+    sockaddr_in6_sin6_addr_in6_u_u6_addr8, sockaddr_in_sin_port, sockaddr_sa_family,
+    socket_options::denied_options,
+    vmlinux::{sockaddr, sockaddr_in, sockaddr_in6, socket},
     Action,
 };
 
@@ -14,6 +43,30 @@ use crate::{
 /// or deny the bind operation based on the state of the `ALLOWED_SOCKET_BIND`
 /// and `DENIED_SOCKET_BIND` maps.
 ///
+/// Binds of ports in the range exempt in the `CONFIG` entry of the namespace
+/// (if any) are allowed before the maps are checked, and so are binds
+/// temporarily allowed for the binary, see [`granted`].
+///
+/// The rules of these maps apply to binds of both `AF_INET` and `AF_INET6`
+/// sockets. Rules scoped to one family, in the `ALLOWED_SOCKET_BIND_V4`/
+/// `DENIED_SOCKET_BIND_V4` and `ALLOWED_SOCKET_BIND_V6`/
+/// `DENIED_SOCKET_BIND_V6` maps, replace them for binds of that family, see
+/// [`PortRules::scoped`]. Binds of IPv4-mapped IPv6 addresses (`::ffff:0:0/96`)
+/// are IPv4 binds.
+///
+/// Binaries without entries in these maps can be matched by their command
+/// name instead, see [`binary_rules`].
+///
+/// Binds which none of these maps decide can be escalated to user space, see
+/// [`escalate`].
+///
+/// Binds allowed by these maps are cached in the `CACHE_SOCKET_BIND` map, see
+/// [`socket_bind_inet`].
+///
+/// Binds of `AF_PACKET` sockets are checked separately against the
+/// `ALLOWED_SOCKET_BIND_PACKET` and `DENIED_SOCKET_BIND_PACKET` maps. Other
+/// families are always allowed.
+///
 /// If denied, the operation is logged to the `ALERT_SOCKET_BIND` map.
 ///
 /// # Example
@@ -34,98 +87,423 @@ use crate::{
 pub fn socket_bind(ctx: LsmContext) -> Result<Action, c_long> {
     let sockaddr: *const sockaddr = unsafe { ctx.arg(1) };
 
-    if unsafe { sockaddr_sa_family(sockaddr) } != AF_INET {
-        return Ok(Action::Allow);
+    match unsafe { sockaddr_sa_family(sockaddr) } {
+        AF_INET => {
+            let sockaddr_in = sockaddr as *const sockaddr_in;
+            let port = u16::from_be(unsafe { sockaddr_in_sin_port(sockaddr_in) });
+            socket_bind_inet(ctx, AF_INET, port)
+        }
+        AF_INET6 => socket_bind_v6(ctx, sockaddr),
+        AF_PACKET => socket_bind_packet(ctx),
+        _ => Ok(Action::Allow),
     }
+}
 
-    let sockaddr_in: *const sockaddr_in = sockaddr as *const sockaddr_in;
-    let port = u16::from_be(unsafe { sockaddr_in_sin_port(sockaddr_in) });
+/// Decides an `AF_INET6` bind, as an IPv4 bind if the address is
+/// IPv4-mapped.
+#[inline(always)]
+fn socket_bind_v6(ctx: LsmContext, sockaddr: *const sockaddr) -> Result<Action, c_long> {
+    let sockaddr_in6: sockaddr_in6 =
+        unsafe { bpf_probe_read_kernel(sockaddr as *const sockaddr_in6)? };
+    let addr: [u8; 16] = [0; 16];
+    unsafe { sockaddr_in6_sin6_addr_in6_u_u6_addr8(&sockaddr_in6, &addr) };
+    let port = u16::from_be(sockaddr_in6.sin6_port);
+
+    let mapped = addr[..10] == [0; 10] && addr[10] == 0xff && addr[11] == 0xff;
+    let family = if mapped { AF_INET } else { AF_INET6 };
+    socket_bind_inet(ctx, family, port)
+}
 
-    if port == 0 {
+/// Checks the bind against the `CACHE_SOCKET_BIND` map first. Binds allowed
+/// by the policy maps are cached with the policy generation of the namespace
+/// (from the `GENERATION_SOCKET_BIND` map), which user space bumps after
+/// changing the policies.
+///
+/// The generation is read before the policy maps (and before
+/// [`policy_namespace`], which atomic plans switch before the bump), so a
+/// bind decided with policies older than a change is cached with the
+/// generation before the bump and never overrides the new policies.
+/// Escalated binds are not cached.
+///
+/// Binds decided by command name rules neither use nor fill the cache, which
+/// is keyed by binary: threads of the same binary can have different names.
+///
+/// Binds are cached per family, since rules scoped to a family can decide
+/// them differently.
+///
+/// Allowed binds are then checked against socket option rules, see
+/// [`check_options_and_alert`], and against the bind limit of the binary,
+/// see [`check_bind_limit`].
+///
+/// Grants, bind limits and escalations are settings of the namespace, looked
+/// up with `key`, while the policy maps are looked up with `policy_key`.
+#[inline(always)]
+fn socket_bind_inet(ctx: LsmContext, family: u16, port: u16) -> Result<Action, c_long> {
+    let namespace = current_namespace();
+    let config = current_config(namespace);
+    if decision::socket_bind_exempt(port, config.exempt_ports()) {
         return Ok(Action::Allow);
     }
 
-    let binprm_inode = current_binprm_inode()?;
-
-    if let Some(ports) = unsafe { ALLOWED_SOCKET_BIND.get(&INODE_WILDCARD) } {
-        if ports.all() {
-            if let Some(ports) = unsafe { DENIED_SOCKET_BIND.get(&INODE_WILDCARD) } {
-                if ports.all() {
-                    ALERT_SOCKET_BIND.output(
-                        &ctx,
-                        &alerts::SocketBind::new(ctx.pid(), binprm_inode, port),
-                        0,
-                    );
-                    return Ok(Action::Deny);
-                }
-                if ports.ports[..MAX_PORTS - 1].contains(&port) {
-                    ALERT_SOCKET_BIND.output(
-                        &ctx,
-                        &alerts::SocketBind::new(ctx.pid(), binprm_inode, port),
-                        0,
-                    );
-                    return Ok(Action::Deny);
-                }
-            }
+    let key = InodeKey::new(namespace, current_binprm_inode()?);
+    if granted(key, port) {
+        return Ok(Action::Allow);
+    }
 
-            if let Some(ports) = unsafe { DENIED_SOCKET_BIND.get(&binprm_inode) } {
-                if ports.all() {
-                    ALERT_SOCKET_BIND.output(
-                        &ctx,
-                        &alerts::SocketBind::new(ctx.pid(), binprm_inode, port),
-                        0,
-                    );
-                    return Ok(Action::Deny);
-                }
-                if ports.ports[..MAX_PORTS - 1].contains(&port) {
-                    ALERT_SOCKET_BIND.output(
-                        &ctx,
-                        &alerts::SocketBind::new(ctx.pid(), binprm_inode, port),
-                        0,
-                    );
-                    return Ok(Action::Deny);
+    let generation = unsafe { GENERATION_SOCKET_BIND.get(&namespace) }
+        .copied()
+        .unwrap_or(0);
+    let policy_key = InodeKey::new(policy_namespace(namespace, HOOK_SOCKET_BIND), key.inode);
+    let binary = binary_rules(&ctx, policy_key, family);
+    let cache_key = SocketBindVerdictKey::new(namespace, key.inode, family, port);
+    let cached = match unsafe { CACHE_SOCKET_BIND.get(&cache_key) } {
+        Some(cached) => !binary.by_comm && *cached == generation,
+        None => false,
+    };
+    let action = if cached {
+        Action::Allow
+    } else {
+        match check_policies(&ctx, policy_key, &binary, family, port) {
+            Some(Action::Allow) => {
+                if !binary.by_comm {
+                    let _ = CACHE_SOCKET_BIND.insert(&cache_key, &generation, 0);
                 }
+                Action::Allow
             }
-        } else if ports.ports[..MAX_PORTS - 1].contains(&port) {
-            return Ok(Action::Allow);
+            Some(action) => action,
+            None => escalate(&ctx, key, family, port),
         }
+    };
+
+    match action {
+        Action::Allow => match check_options_and_alert(&ctx, policy_key, family, port) {
+            Action::Allow => check_bind_limit(&ctx, key, family, port),
+            action => Ok(action),
+        },
+        action => Ok(action),
     }
+}
 
-    if let Some(ports) = unsafe { DENIED_SOCKET_BIND.get(&INODE_WILDCARD) } {
-        if ports.all() {
-            if let Some(ports) = unsafe { ALLOWED_SOCKET_BIND.get(&INODE_WILDCARD) } {
-                if ports.all() {
-                    return Ok(Action::Allow);
-                }
-                if ports.ports[..MAX_PORTS - 1].contains(&port) {
-                    return Ok(Action::Allow);
-                }
-            }
+/// Returns whether binds of the port are temporarily allowed for the binary
+/// (or all binaries) in the `GRANTS_SOCKET_BIND` map. A grant is terminal:
+/// it overrides denies, socket options, escalation and bind limits, and the
+/// bind is neither alerted nor cached, so the grant ends without a
+/// generation bump.
+#[inline(always)]
+fn granted(key: InodeKey, port: u16) -> bool {
+    let binary = SocketBindGrantKey::new(key, port);
+    let wildcard = SocketBindGrantKey::new(InodeKey::wildcard(key.namespace), port);
+    unsafe { GRANTS_SOCKET_BIND.get(&binary) }.is_some()
+        || unsafe { GRANTS_SOCKET_BIND.get(&wildcard) }.is_some()
+}
 
-            if let Some(ports) = unsafe { ALLOWED_SOCKET_BIND.get(&binprm_inode) } {
-                if ports.all() {
-                    return Ok(Action::Allow);
-                }
-                if ports.ports[..MAX_PORTS - 1].contains(&port) {
-                    return Ok(Action::Allow);
-                }
+/// Denies binds allowed by the policies if the process has already bound as
+/// many distinct ports as the bind limit of the binary (or the default limit
+/// of the namespace) in the `BIND_LIMIT_SOCKET_BIND` map.
+///
+/// Ports are counted per process in the `BIND_COUNT_SOCKET_BIND` map when
+/// their first bind is allowed, even if the bind fails later in the kernel
+/// (e.g. because the port is in use), and binding a counted port again is
+/// always allowed. Processes are keyed with their start time, so a reused
+/// PID starts from zero. Concurrent binds of threads of the same process can
+/// exceed the limit by the number of threads.
+#[inline(always)]
+fn check_bind_limit(
+    ctx: &LsmContext,
+    key: InodeKey,
+    family: u16,
+    port: u16,
+) -> Result<Action, c_long> {
+    let limit = match decision::bind_limit(Rules {
+        wildcard: unsafe { BIND_LIMIT_SOCKET_BIND.get(&InodeKey::wildcard(key.namespace)) },
+        binary: unsafe { BIND_LIMIT_SOCKET_BIND.get(&key) },
+    }) {
+        Some(limit) => limit,
+        None => return Ok(Action::Allow),
+    };
+
+    let process = current_process(ctx.pid())?;
+    let port_key = ProcessPortKey::new(process, port);
+    if unsafe { BOUND_PORTS_SOCKET_BIND.get(&port_key) }.is_some() {
+        return Ok(Action::Allow);
+    }
+
+    let count = unsafe { BIND_COUNT_SOCKET_BIND.get(&process) }
+        .copied()
+        .unwrap_or(0);
+    if count >= limit {
+        let mut alert = alerts::SocketBind::new(
+            ctx.pid(),
+            key.namespace,
+            current_session(ctx.pid()),
+            REASON_BIND_LIMIT,
+            key.inode,
+            family,
+            port,
+        );
+        alert.count = count;
+        output_alert(ctx, &ALERT_SOCKET_BIND, HOOK_SOCKET_BIND, alert);
+        return Ok(Action::Deny(REASON_BIND_LIMIT));
+    }
+
+    BIND_COUNT_SOCKET_BIND.insert(&process, &(count + 1), 0)?;
+    BOUND_PORTS_SOCKET_BIND.insert(&port_key, &1, 0)?;
+    Ok(Action::Allow)
+}
+
+/// Denies binds allowed by the policies if the socket has options set which
+/// are denied in the `OPTIONS_SOCKET_BIND` map. Options can differ between
+/// sockets of the same binary and port, so they are checked after the cache.
+#[inline(always)]
+fn check_options_and_alert(ctx: &LsmContext, key: InodeKey, family: u16, port: u16) -> Action {
+    let sock: *const socket = unsafe { ctx.arg(0) };
+    if denied_options(&OPTIONS_SOCKET_BIND, key, sock) == 0 {
+        return Action::Allow;
+    }
+
+    output_alert(
+        ctx,
+        &ALERT_SOCKET_BIND,
+        HOOK_SOCKET_BIND,
+        alerts::SocketBind::new(
+            ctx.pid(),
+            untagged_namespace(key.namespace),
+            current_session(ctx.pid()),
+            REASON_SOCKET_OPTION,
+            key.inode,
+            family,
+            port,
+        ),
+    );
+    Action::Deny(REASON_SOCKET_OPTION)
+}
+
+/// Allowed and denied ports of the binary of the process.
+struct BinaryRules {
+    rules: PortRules<'static>,
+    /// Whether the rules were matched by the command name.
+    by_comm: bool,
+}
+
+/// Looks up the rules of the subject in the family-agnostic maps, scoped by
+/// the rules of the subject for the family of the bind.
+#[inline(always)]
+fn port_rules(key: &InodeKey, family: u16) -> PortRules<'static> {
+    let rules = PortRules {
+        allowed: unsafe { ALLOWED_SOCKET_BIND.get(key) },
+        denied: unsafe { DENIED_SOCKET_BIND.get(key) },
+    };
+    let family_rules = match family {
+        AF_INET6 => PortRules {
+            allowed: unsafe { ALLOWED_SOCKET_BIND_V6.get(key) },
+            denied: unsafe { DENIED_SOCKET_BIND_V6.get(key) },
+        },
+        _ => PortRules {
+            allowed: unsafe { ALLOWED_SOCKET_BIND_V4.get(key) },
+            denied: unsafe { DENIED_SOCKET_BIND_V4.get(key) },
+        },
+    };
+    rules.scoped(family_rules)
+}
+
+/// Looks up the rules of the binary, see [`port_rules`]. A binary without
+/// rules for the family of the bind is matched by the command name of the
+/// current task instead, against the longest prefix in the
+/// `ALLOWED_SOCKET_BIND_COMM` and `DENIED_SOCKET_BIND_COMM` maps, so rules of
+/// the binary always win over command name rules. Command name rules apply to
+/// both families.
+#[inline(always)]
+fn binary_rules(ctx: &LsmContext, key: InodeKey, family: u16) -> BinaryRules {
+    let rules = port_rules(&key, family);
+    if rules.is_some() {
+        return BinaryRules {
+            rules,
+            by_comm: false,
+        };
+    }
+
+    let comm = match ctx.command() {
+        Ok(comm) => comm,
+        Err(_) => {
+            return BinaryRules {
+                rules,
+                by_comm: false,
             }
+        }
+    };
+    let comm_key = Key::new(
+        COMM_KEY_PREFIX_LEN + 8 * COMM_LEN as u32,
+        CommKey::new(key.namespace, comm),
+    );
+    let rules = PortRules {
+        allowed: ALLOWED_SOCKET_BIND_COMM.get(&comm_key),
+        denied: DENIED_SOCKET_BIND_COMM.get(&comm_key),
+    };
+    BinaryRules {
+        rules,
+        by_comm: rules.is_some(),
+    }
+}
 
-            ALERT_SOCKET_BIND.output(
-                &ctx,
-                &alerts::SocketBind::new(ctx.pid(), binprm_inode, port),
-                0,
-            );
-            return Ok(Action::Deny);
-        } else if ports.ports[..MAX_PORTS - 1].contains(&port) {
-            ALERT_SOCKET_BIND.output(
-                &ctx,
-                &alerts::SocketBind::new(ctx.pid(), binprm_inode, port),
+/// Decides the bind based on the policy maps, with [`decision::socket_bind`].
+/// Returns `None` if they don't decide it.
+///
+/// A bind denied by default is checked against the rules for the other
+/// family, so the alert tells when a policy scoped to that family would have
+/// allowed it, see [`decision::socket_bind_default_deny`].
+#[inline(always)]
+fn check_policies(
+    ctx: &LsmContext,
+    key: InodeKey,
+    binary: &BinaryRules,
+    family: u16,
+    port: u16,
+) -> Option<Action> {
+    let wildcard = port_rules(&InodeKey::wildcard(key.namespace), family);
+    let allowed = Rules {
+        wildcard: wildcard.allowed,
+        binary: binary.rules.allowed,
+    };
+    let denied = Rules {
+        wildcard: wildcard.denied,
+        binary: binary.rules.denied,
+    };
+
+    let action = match decision::socket_bind(allowed, denied, port) {
+        Some(Action::Deny(REASON_DEFAULT_DENY)) => {
+            let other = match family {
+                AF_INET6 => AF_INET,
+                _ => AF_INET6,
+            };
+            let wildcard = port_rules(&InodeKey::wildcard(key.namespace), other);
+            let binary = port_rules(&key, other);
+            Some(decision::socket_bind_default_deny(
+                Rules {
+                    wildcard: wildcard.allowed,
+                    binary: binary.allowed,
+                },
+                Rules {
+                    wildcard: wildcard.denied,
+                    binary: binary.denied,
+                },
+                port,
+            ))
+        }
+        action => action,
+    };
+    if let Some(Action::Deny(reason)) = action {
+        output_alert(
+            ctx,
+            &ALERT_SOCKET_BIND,
+            HOOK_SOCKET_BIND,
+            alerts::SocketBind::new(
+                ctx.pid(),
+                untagged_namespace(key.namespace),
+                current_session(ctx.pid()),
+                reason,
+                key.inode,
+                family,
+                port,
+            ),
+        );
+    }
+    action
+}
+
+/// Handles a bind which the policy maps couldn't decide.
+///
+/// If the binary (or the wildcard) has an entry in the
+/// `ESCALATE_SOCKET_BIND` map, the verdict is looked up in the
+/// `VERDICT_SOCKET_BIND` map, written by user space. Without a verdict, the
+/// bind is reported to the `ALERT_SOCKET_BIND_ESCALATION` map and the fallback
+/// verdict stored in `ESCALATE_SOCKET_BIND` is applied, since an LSM hook
+/// can't wait for user space. The user space verdict therefore takes effect
+/// for subsequent binds only.
+///
+/// Escalation alerts are not rate-limited, since user space decides on them.
+#[inline(always)]
+fn escalate(ctx: &LsmContext, key: InodeKey, family: u16, port: u16) -> Action {
+    let fallback = match unsafe { ESCALATE_SOCKET_BIND.get(&key) } {
+        Some(fallback) => *fallback,
+        None => match unsafe { ESCALATE_SOCKET_BIND.get(&InodeKey::wildcard(key.namespace)) } {
+            Some(fallback) => *fallback,
+            None => return Action::Allow,
+        },
+    };
+
+    let alert = |reason| {
+        alerts::SocketBind::new(
+            ctx.pid(),
+            key.namespace,
+            current_session(ctx.pid()),
+            reason,
+            key.inode,
+            family,
+            port,
+        )
+    };
+
+    let verdict_key = SocketBindVerdictKey::new(key.namespace, key.inode, family, port);
+    let action = match unsafe { VERDICT_SOCKET_BIND.get(&verdict_key) } {
+        Some(verdict) => Action::from_verdict(*verdict, REASON_ESCALATION_VERDICT),
+        None => {
+            ALERT_SOCKET_BIND_ESCALATION.output(
+                ctx,
+                &with_channel(with_message_id(
+                    HOOK_SOCKET_BIND,
+                    alert(REASON_ESCALATION_FALLBACK),
+                )),
                 0,
             );
-            return Ok(Action::Deny);
+            Action::from_verdict(fallback, REASON_ESCALATION_FALLBACK)
         }
+    };
+
+    if let Action::Deny(reason) = action {
+        output_alert(ctx, &ALERT_SOCKET_BIND, HOOK_SOCKET_BIND, alert(reason));
     }
 
-    Ok(Action::Allow)
+    action
+}
+
+/// Decides whether the current binary is allowed to bind an `AF_PACKET`
+/// socket. Packet sockets give access to raw link-layer traffic, so they are
+/// controlled with a plain per-binary allow/deny model (like `sb_mount`),
+/// without looking at the socket address.
+///
+/// The check is opt-in: without a wildcard entry in either map, all packet
+/// socket binds are allowed.
+#[inline(always)]
+fn socket_bind_packet(ctx: LsmContext) -> Result<Action, c_long> {
+    let namespace = policy_namespace(current_namespace(), HOOK_SOCKET_BIND);
+    let key = InodeKey::new(namespace, current_binprm_inode()?);
+    let wildcard = InodeKey::wildcard(namespace);
+
+    let allowed = Rules {
+        wildcard: unsafe { ALLOWED_SOCKET_BIND_PACKET.get(&wildcard) },
+        binary: unsafe { ALLOWED_SOCKET_BIND_PACKET.get(&key) },
+    };
+    let denied = Rules {
+        wildcard: unsafe { DENIED_SOCKET_BIND_PACKET.get(&wildcard) },
+        binary: unsafe { DENIED_SOCKET_BIND_PACKET.get(&key) },
+    };
+
+    let action = decision::binary_rules(allowed, denied);
+    if let Action::Deny(reason) = action {
+        output_alert(
+            &ctx,
+            &ALERT_SOCKET_BIND,
+            HOOK_SOCKET_BIND,
+            alerts::SocketBind::new(
+                ctx.pid(),
+                untagged_namespace(key.namespace),
+                current_session(ctx.pid()),
+                reason,
+                key.inode,
+                AF_PACKET,
+                0,
+            ),
+        );
+    }
+    Ok(action)
 }
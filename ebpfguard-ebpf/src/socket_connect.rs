@@ -1,22 +1,40 @@
 use aya_bpf::{
-    cty::c_long, helpers::bpf_probe_read_kernel, maps::HashMap, programs::LsmContext, BpfContext,
+    cty::c_long,
+    helpers::{bpf_ktime_get_ns, bpf_probe_read_kernel},
+    maps::{lpm_trie::Key, LpmTrie},
+    programs::LsmContext,
+    BpfContext,
 };
 use ebpfguard_common::{
-    alerts,
+    alerts::{self, REASON_RATE_LIMIT},
     consts::INODE_WILDCARD,
-    policy::{IpAddrs, Ipv4Addrs, Ipv6Addrs},
+    decision::{self, Rules},
+    policy::{
+        ConnectRate, InodeKey, Ipv4CidrKey, Ipv4Key, Ipv6CidrKey, Ipv6Key, RateKey,
+        CIDR_KEY_PREFIX_LEN, HOOK_SOCKET_CONNECT,
+    },
 };
 
 use crate::{
+    alert::output_alert,
     binprm::current_binprm_inode,
+    config::current_config,
     consts::{AF_INET, AF_INET6},
+    generation::policy_namespace,
     maps::{
-        ALERT_SOCKET_CONNECT, ALLOWED_SOCKET_CONNECT_V4, ALLOWED_SOCKET_CONNECT_V6,
-        DENIED_SOCKET_CONNECT_V4, DENIED_SOCKET_CONNECT_V6,
+        ALERT_SOCKET_CONNECT, ALLOWED_SOCKET_CONNECT_PORTS, ALLOWED_SOCKET_CONNECT_V4,
+        ALLOWED_SOCKET_CONNECT_V6, DENIED_SOCKET_CONNECT_CIDR_V4, DENIED_SOCKET_CONNECT_CIDR_V6,
+        DENIED_SOCKET_CONNECT_METADATA_V4, DENIED_SOCKET_CONNECT_METADATA_V6,
+        DENIED_SOCKET_CONNECT_PORTS, DENIED_SOCKET_CONNECT_V4, DENIED_SOCKET_CONNECT_V6,
+        PROTECTED_SOCKET_CONNECT_V4, PROTECTED_SOCKET_CONNECT_V6, RATE_SOCKET_CONNECT_V4,
+        RATE_SOCKET_CONNECT_V6, RATE_WINDOWS_SOCKET_CONNECT,
     },
-    sockaddr_in6_sin6_addr_in6_u_u6_addr8, sockaddr_in_sin_addr_s_addr, sockaddr_sa_family,
+    namespace::current_namespace,
+    session::current_session,
+    sockaddr_in6_sin6_addr_in6_u_u6_addr8, sockaddr_in_sin_addr_s_addr, sockaddr_in_sin_port,
+    sockaddr_sa_family,
     vmlinux::{sockaddr, sockaddr_in, sockaddr_in6},
-    Action, Mode,
+    Action,
 };
 
 /// Inspects the context of `socket_connect` LSM hook and decides whether to
@@ -24,6 +42,37 @@ use crate::{
 /// `ALLOWED_SOCKET_CONNECT_V4`/`ALLOWED_SOCKET_CONNECT_V6` and
 /// `DENIED_SOCKET_CONNECT_V4`/`DENIED_SOCKET_CONNECT_V6` maps.
 ///
+/// Addresses present in the `PROTECTED_SOCKET_CONNECT_V4`/
+/// `PROTECTED_SOCKET_CONNECT_V6` maps can be connected to only by the binaries
+/// listed for them. That check comes first: a binary missing from the list is
+/// denied regardless of its own rules, while a listed binary is still subject
+/// to the per-binary allow/deny rules.
+///
+/// The keys of the protected address maps are built with the key layout in
+/// the `CONFIG` entry of the namespace, so an address can be protected as a
+/// whole or per destination port (see
+/// [`Ipv4Key`](ebpfguard_common::policy::Ipv4Key)).
+///
+/// Link-local and instance metadata addresses in the
+/// `DENIED_SOCKET_CONNECT_METADATA_V4`/`DENIED_SOCKET_CONNECT_METADATA_V6`
+/// maps (for all binaries or for the binary) are denied next, with their own
+/// reason.
+///
+/// Addresses in CIDRs denied in the `DENIED_SOCKET_CONNECT_CIDR_V4`/
+/// `DENIED_SOCKET_CONNECT_CIDR_V6` maps (for all binaries or for the binary)
+/// are denied next, regardless of the allow/deny rules.
+///
+/// The decision is made by [`decision::socket_connect`] from the looked up
+/// entries, which user space simulations share.
+///
+/// Connects allowed by these rules are then checked against the ranges of
+/// destination ports in the `ALLOWED_SOCKET_CONNECT_PORTS`/
+/// `DENIED_SOCKET_CONNECT_PORTS` maps, which can only deny, see
+/// [`check_ports`].
+///
+/// Connects allowed by the policies are finally counted against the rate
+/// limit of their destination, see [`check_rate`].
+///
 /// # Example
 ///
 /// ```rust
@@ -49,38 +98,103 @@ pub fn socket_connect(ctx: LsmContext) -> Result<Action, c_long> {
     }
 }
 
+/// Looks up the CIDRs of the binary in the longest prefix match map. CIDR
+/// keys have no wildcard tag (see [`Ipv4CidrKey`]), so a binary whose inode is
+/// [`INODE_WILDCARD`] has no CIDRs of its own instead of the wildcard ones.
+#[inline(always)]
+fn binary_cidr<K, V>(map: &LpmTrie<K, V>, inode: u64, key: Key<K>) -> Option<&V> {
+    if inode == INODE_WILDCARD {
+        return None;
+    }
+    map.get(&key)
+}
+
 #[inline(always)]
 fn socket_connect_v4(ctx: LsmContext, sockaddr: *const sockaddr) -> Result<Action, c_long> {
     let sockaddr_in: *const sockaddr_in = sockaddr as *const sockaddr_in;
     let addr = u32::from_be(unsafe { sockaddr_in_sin_addr_s_addr(sockaddr_in) });
+    let port = u16::from_be(unsafe { sockaddr_in_sin_port(sockaddr_in) });
 
-    let binprm_inode = current_binprm_inode()?;
+    let namespace = current_namespace();
+    let layout = current_config(namespace).key_layout;
+    let key_namespace = policy_namespace(namespace, HOOK_SOCKET_CONNECT);
+    let key = InodeKey::new(key_namespace, current_binprm_inode()?);
+    let wildcard = InodeKey::wildcard(key_namespace);
 
-    if let Some(addrs) = unsafe { ALLOWED_SOCKET_CONNECT_V4.get(&INODE_WILDCARD) } {
-        if addrs.all() {
-            return Ok(check_conditions_and_alert_v4(
-                &ctx,
-                &DENIED_SOCKET_CONNECT_V4,
-                addr,
-                binprm_inode,
-                Mode::Denylist,
-            ));
-        }
-    }
+    let prefix_len = CIDR_KEY_PREFIX_LEN + 32;
+    let metadata_cidrs = Rules {
+        wildcard: DENIED_SOCKET_CONNECT_METADATA_V4.get(&Key::new(
+            prefix_len,
+            Ipv4CidrKey::new(key_namespace, INODE_WILDCARD, addr),
+        )),
+        binary: binary_cidr(
+            &DENIED_SOCKET_CONNECT_METADATA_V4,
+            key.inode,
+            Key::new(prefix_len, Ipv4CidrKey::new(key_namespace, key.inode, addr)),
+        ),
+    };
+    let denied_cidrs = Rules {
+        wildcard: DENIED_SOCKET_CONNECT_CIDR_V4.get(&Key::new(
+            prefix_len,
+            Ipv4CidrKey::new(key_namespace, INODE_WILDCARD, addr),
+        )),
+        binary: binary_cidr(
+            &DENIED_SOCKET_CONNECT_CIDR_V4,
+            key.inode,
+            Key::new(prefix_len, Ipv4CidrKey::new(key_namespace, key.inode, addr)),
+        ),
+    };
+    let allowed = Rules {
+        wildcard: unsafe { ALLOWED_SOCKET_CONNECT_V4.get(&wildcard) },
+        binary: unsafe { ALLOWED_SOCKET_CONNECT_V4.get(&key) },
+    };
+    let denied = Rules {
+        wildcard: unsafe { DENIED_SOCKET_CONNECT_V4.get(&wildcard) },
+        binary: unsafe { DENIED_SOCKET_CONNECT_V4.get(&key) },
+    };
 
-    if let Some(addrs) = unsafe { DENIED_SOCKET_CONNECT_V4.get(&INODE_WILDCARD) } {
-        if addrs.all() {
-            return Ok(check_conditions_and_alert_v4(
-                &ctx,
-                &ALLOWED_SOCKET_CONNECT_V4,
-                addr,
-                binprm_inode,
-                Mode::Allowlist,
+    let action = decision::socket_connect(
+        key.inode,
+        addr,
+        unsafe {
+            PROTECTED_SOCKET_CONNECT_V4.get(&Ipv4Key::new(layout, key_namespace, addr, port))
+        },
+        metadata_cidrs,
+        denied_cidrs,
+        allowed,
+        denied,
+    );
+    let action = match action {
+        Action::Allow => check_ports(key, port),
+        action => action,
+    };
+    let (action, rate) = match action {
+        Action::Allow => {
+            let rate = RATE_SOCKET_CONNECT_V4.get(&Key::new(
+                prefix_len,
+                Ipv4CidrKey::new(namespace, INODE_WILDCARD, addr),
             ));
+            match check_rate(namespace, rate)? {
+                Some(rate) => (Action::Deny(REASON_RATE_LIMIT), rate),
+                None => (Action::Allow, 0),
+            }
         }
+        action => (action, 0),
+    };
+    if let Action::Deny(reason) = action {
+        let mut alert = alerts::SocketConnect::new_ipv4(
+            ctx.pid(),
+            namespace,
+            current_session(ctx.pid()),
+            reason,
+            key.inode,
+            addr,
+        );
+        alert.port = port;
+        alert.rate = rate;
+        output_alert(&ctx, &ALERT_SOCKET_CONNECT, HOOK_SOCKET_CONNECT, alert);
     }
-
-    Ok(Action::Allow)
+    Ok(action)
 }
 
 #[inline(always)]
@@ -90,126 +204,131 @@ fn socket_connect_v6(ctx: LsmContext, sockaddr: *const sockaddr) -> Result<Actio
     let sockaddr_in6: sockaddr_in6 = unsafe { bpf_probe_read_kernel(sockaddr_in6)? };
     let addr: [u8; 16] = [0; 16];
     unsafe { sockaddr_in6_sin6_addr_in6_u_u6_addr8(&sockaddr_in6, &addr) };
+    let port = u16::from_be(sockaddr_in6.sin6_port);
 
-    let binprm_inode = current_binprm_inode()?;
+    let namespace = current_namespace();
+    let layout = current_config(namespace).key_layout;
+    let key_namespace = policy_namespace(namespace, HOOK_SOCKET_CONNECT);
+    let key = InodeKey::new(key_namespace, current_binprm_inode()?);
+    let wildcard = InodeKey::wildcard(key_namespace);
 
-    if let Some(addrs) = unsafe { ALLOWED_SOCKET_CONNECT_V6.get(&INODE_WILDCARD) } {
-        if addrs.all() {
-            return Ok(check_conditions_and_alert_v6(
-                &ctx,
-                &DENIED_SOCKET_CONNECT_V6,
-                addr,
-                binprm_inode,
-                Mode::Denylist,
-            ));
-        }
-    }
+    let prefix_len = CIDR_KEY_PREFIX_LEN + 128;
+    let metadata_cidrs = Rules {
+        wildcard: DENIED_SOCKET_CONNECT_METADATA_V6.get(&Key::new(
+            prefix_len,
+            Ipv6CidrKey::new(key_namespace, INODE_WILDCARD, addr),
+        )),
+        binary: binary_cidr(
+            &DENIED_SOCKET_CONNECT_METADATA_V6,
+            key.inode,
+            Key::new(prefix_len, Ipv6CidrKey::new(key_namespace, key.inode, addr)),
+        ),
+    };
+    let denied_cidrs = Rules {
+        wildcard: DENIED_SOCKET_CONNECT_CIDR_V6.get(&Key::new(
+            prefix_len,
+            Ipv6CidrKey::new(key_namespace, INODE_WILDCARD, addr),
+        )),
+        binary: binary_cidr(
+            &DENIED_SOCKET_CONNECT_CIDR_V6,
+            key.inode,
+            Key::new(prefix_len, Ipv6CidrKey::new(key_namespace, key.inode, addr)),
+        ),
+    };
+    let allowed = Rules {
+        wildcard: unsafe { ALLOWED_SOCKET_CONNECT_V6.get(&wildcard) },
+        binary: unsafe { ALLOWED_SOCKET_CONNECT_V6.get(&key) },
+    };
+    let denied = Rules {
+        wildcard: unsafe { DENIED_SOCKET_CONNECT_V6.get(&wildcard) },
+        binary: unsafe { DENIED_SOCKET_CONNECT_V6.get(&key) },
+    };
 
-    if let Some(addrs) = unsafe { DENIED_SOCKET_CONNECT_V6.get(&INODE_WILDCARD) } {
-        if addrs.all() {
-            return Ok(check_conditions_and_alert_v6(
-                &ctx,
-                &ALLOWED_SOCKET_CONNECT_V6,
-                addr,
-                binprm_inode,
-                Mode::Allowlist,
+    let action = decision::socket_connect(
+        key.inode,
+        addr,
+        unsafe {
+            PROTECTED_SOCKET_CONNECT_V6.get(&Ipv6Key::new(layout, key_namespace, addr, port))
+        },
+        metadata_cidrs,
+        denied_cidrs,
+        allowed,
+        denied,
+    );
+    let action = match action {
+        Action::Allow => check_ports(key, port),
+        action => action,
+    };
+    let (action, rate) = match action {
+        Action::Allow => {
+            let rate = RATE_SOCKET_CONNECT_V6.get(&Key::new(
+                prefix_len,
+                Ipv6CidrKey::new(namespace, INODE_WILDCARD, addr),
             ));
+            match check_rate(namespace, rate)? {
+                Some(rate) => (Action::Deny(REASON_RATE_LIMIT), rate),
+                None => (Action::Allow, 0),
+            }
         }
+        action => (action, 0),
+    };
+    if let Action::Deny(reason) = action {
+        let mut alert = alerts::SocketConnect::new_ipv6(
+            ctx.pid(),
+            namespace,
+            current_session(ctx.pid()),
+            reason,
+            key.inode,
+            addr,
+        );
+        alert.port = port;
+        alert.rate = rate;
+        output_alert(&ctx, &ALERT_SOCKET_CONNECT, HOOK_SOCKET_CONNECT, alert);
     }
-
-    Ok(Action::Allow)
-}
-
-#[inline(always)]
-fn check_conditions_and_alert_v4(
-    ctx: &LsmContext,
-    map: &HashMap<u64, Ipv4Addrs>,
-    addr: u32,
-    binprm_inode: u64,
-    mode: Mode,
-) -> Action {
-    match check_conditions(map, addr, binprm_inode, mode) {
-        Action::Deny => {
-            ALERT_SOCKET_CONNECT.output(
-                ctx,
-                &alerts::SocketConnect::new_ipv4(ctx.pid(), binprm_inode, addr),
-                0,
-            );
-            Action::Deny
-        }
-        action => action,
-    }
+    Ok(action)
 }
 
+/// Decides a connect to the destination port by the port ranges of the
+/// `socket_connect_ports` policies of the binary and of all binaries, see
+/// [`decision::socket_connect_ports`].
 #[inline(always)]
-fn check_conditions_and_alert_v6(
-    ctx: &LsmContext,
-    map: &HashMap<u64, Ipv6Addrs>,
-    addr: [u8; 16],
-    binprm_inode: u64,
-    mode: Mode,
-) -> Action {
-    match check_conditions(map, addr, binprm_inode, mode) {
-        Action::Deny => {
-            ALERT_SOCKET_CONNECT.output(
-                ctx,
-                &alerts::SocketConnect::new_ipv6(ctx.pid(), binprm_inode, addr),
-                0,
-            );
-            Action::Deny
-        }
-        action => action,
-    }
+fn check_ports(key: InodeKey, port: u16) -> Action {
+    let wildcard = InodeKey::wildcard(key.namespace);
+    decision::socket_connect_ports(
+        Rules {
+            wildcard: unsafe { ALLOWED_SOCKET_CONNECT_PORTS.get(&wildcard) },
+            binary: unsafe { ALLOWED_SOCKET_CONNECT_PORTS.get(&key) },
+        },
+        Rules {
+            wildcard: unsafe { DENIED_SOCKET_CONNECT_PORTS.get(&wildcard) },
+            binary: unsafe { DENIED_SOCKET_CONNECT_PORTS.get(&key) },
+        },
+        port,
+    )
 }
 
+/// Counts a connect allowed by the policies against the rate limit of its
+/// destination in the namespace, if it has one. Returns the number of
+/// connects counted in the current window in the
+/// `RATE_WINDOWS_SOCKET_CONNECT` map if the limit is reached.
+///
+/// Connects are counted for all processes when the hook allows them, even if
+/// they fail later in the kernel (e.g. because nothing listens). The window
+/// is read and written without a lock, so concurrent connects on different
+/// CPUs can exceed the limit by the number of CPUs.
 #[inline(always)]
-fn check_conditions<T, U, const V: usize>(
-    map: &HashMap<u64, T>,
-    addr: U,
-    binprm_inode: u64,
-    mode: Mode,
-) -> Action
-where
-    T: IpAddrs<U, V>,
-    U: Copy + PartialEq,
-{
-    if let Some(addrs) = unsafe { map.get(&INODE_WILDCARD) } {
-        if let Some(action) = check_addresses(addrs, addr, &mode) {
-            return action;
-        }
-    }
-
-    if let Some(addrs) = unsafe { map.get(&binprm_inode) } {
-        if let Some(action) = check_addresses(addrs, addr, &mode) {
-            return action;
+fn check_rate(namespace: u32, rate: Option<&ConnectRate>) -> Result<Option<u32>, c_long> {
+    let rate = match rate {
+        Some(rate) => rate,
+        None => return Ok(None),
+    };
+    let key = RateKey::new(namespace, rate.id);
+    let now = unsafe { bpf_ktime_get_ns() };
+    match decision::connect_rate(rate, unsafe { RATE_WINDOWS_SOCKET_CONNECT.get(&key) }, now) {
+        Ok(window) => {
+            RATE_WINDOWS_SOCKET_CONNECT.insert(&key, &window, 0)?;
+            Ok(None)
         }
+        Err(count) => Ok(Some(count)),
     }
-
-    match mode {
-        Mode::Allowlist => Action::Deny,
-        Mode::Denylist => Action::Allow,
-    }
-}
-
-#[inline(always)]
-fn check_addresses<T, U, const V: usize>(addrs: &T, addr: U, mode: &Mode) -> Option<Action>
-where
-    T: IpAddrs<U, V>,
-    U: Copy + PartialEq,
-{
-    if addrs.all() {
-        return Some(match mode {
-            Mode::Allowlist => Action::Allow,
-            Mode::Denylist => Action::Deny,
-        });
-    }
-
-    if addrs.addrs()[..V].contains(&addr) {
-        return Some(match mode {
-            Mode::Allowlist => Action::Allow,
-            Mode::Denylist => Action::Deny,
-        });
-    }
-
-    None
 }
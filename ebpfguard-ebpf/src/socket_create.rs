@@ -0,0 +1,98 @@
+use aya_bpf::{cty::c_long, programs::LsmContext, BpfContext};
+use ebpfguard_common::{
+    alerts,
+    decision::{self, Rules},
+    policy::{InodeKey, HOOK_SOCKET_CREATE},
+};
+
+use crate::{
+    alert::output_alert,
+    binprm::current_binprm_inode,
+    generation::policy_namespace,
+    maps::{
+        ALERT_SOCKET_CREATE, ALLOWED_SOCKET_CREATE, ALLOWED_SOCKET_CREATE_1, DENIED_SOCKET_CREATE,
+        DENIED_SOCKET_CREATE_1, SLOT_SOCKET_CREATE,
+    },
+    namespace::current_namespace,
+    session::current_session,
+    Action,
+};
+
+/// Inspects the context of `socket_create` LSM hook, called before a socket
+/// is created, and decides whether to allow or deny the creation based on
+/// the family and type of the socket and the state of the
+/// `ALLOWED_SOCKET_CREATE` and `DENIED_SOCKET_CREATE` maps (or their `_1`
+/// slot, as set in `SLOT_SOCKET_CREATE` for the namespace), with the same
+/// precedence as `socket_bind` (see [`decision::socket_create`]).
+///
+/// The type is passed without the `SOCK_NONBLOCK` and `SOCK_CLOEXEC` flags.
+/// Sockets created by the kernel for its own use (`kern` set) are always
+/// allowed.
+///
+/// If denied, the operation is logged to the `ALERT_SOCKET_CREATE` map.
+///
+/// # Example
+///
+/// ```rust
+/// use aya_bpf::{macros::lsm, programs::LsmContext};
+/// use ebpfguard_ebpf::socket_create;
+///
+/// #[lsm(name = "my_program")]
+/// pub fn my_program(ctx: LsmContext) -> i32 {
+///     match socket_create::socket_create(ctx) {
+///         Ok(ret) => ret.into(),
+///         Err(_) => 0,
+///     }
+/// }
+/// ```
+#[inline(always)]
+pub fn socket_create(ctx: LsmContext) -> Result<Action, c_long> {
+    let family: i32 = unsafe { ctx.arg(0) };
+    let ty: i32 = unsafe { ctx.arg(1) };
+    let protocol: i32 = unsafe { ctx.arg(2) };
+    let kern: i32 = unsafe { ctx.arg(3) };
+    if kern != 0 {
+        return Ok(Action::Allow);
+    }
+    let (family, ty) = (family as u16, ty as u16);
+
+    let namespace = current_namespace();
+    let key_namespace = policy_namespace(namespace, HOOK_SOCKET_CREATE);
+    let key = InodeKey::new(key_namespace, current_binprm_inode()?);
+    let wildcard = InodeKey::wildcard(key_namespace);
+
+    // The slot is read once, so all rules come from the same slot even if
+    // user space switches slots in the meantime.
+    let (allowed_map, denied_map) = match unsafe { SLOT_SOCKET_CREATE.get(&namespace) } {
+        Some(1) => (&ALLOWED_SOCKET_CREATE_1, &DENIED_SOCKET_CREATE_1),
+        _ => (&ALLOWED_SOCKET_CREATE, &DENIED_SOCKET_CREATE),
+    };
+    let allowed = Rules {
+        wildcard: unsafe { allowed_map.get(&wildcard) },
+        binary: unsafe { allowed_map.get(&key) },
+    };
+    let denied = Rules {
+        wildcard: unsafe { denied_map.get(&wildcard) },
+        binary: unsafe { denied_map.get(&key) },
+    };
+
+    let action = decision::socket_create(allowed, denied, family, ty);
+    if let Action::Deny(reason) = action {
+        output_alert(
+            &ctx,
+            &ALERT_SOCKET_CREATE,
+            HOOK_SOCKET_CREATE,
+            alerts::SocketCreate::new(
+                ctx.pid(),
+                namespace,
+                current_session(ctx.pid()),
+                reason,
+                key.inode,
+                family,
+                ty,
+                protocol as u16,
+            ),
+        );
+    }
+    Ok(action)
+}
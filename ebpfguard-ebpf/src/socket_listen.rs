@@ -0,0 +1,148 @@
+use aya_bpf::{cty::c_long, programs::LsmContext, BpfContext};
+use ebpfguard_common::{
+    alerts::{
+        self, REASON_BINARY_DENY, REASON_BINARY_DENY_ALL, REASON_DEFAULT_DENY,
+        REASON_SOCKET_OPTION, REASON_WILDCARD_DENY, REASON_WILDCARD_DENY_ALL,
+        REASON_WILDCARD_DENY_LISTED,
+    },
+    policy::{untagged_namespace, InodeKey, HOOK_SOCKET_LISTEN, MAX_PORTS},
+};
+
+use crate::{
+    alert::output_alert,
+    binprm::current_binprm_inode,
+    consts::{AF_INET, AF_INET6},
+    generation::policy_namespace,
+    maps::{
+        ALERT_SOCKET_LISTEN, ALLOWED_SOCKET_LISTEN, DENIED_SOCKET_LISTEN, OPTIONS_SOCKET_LISTEN,
+    },
+    namespace::current_namespace,
+    session::current_session,
+    socket_options::denied_options,
+    socket_sk_family, socket_sk_num,
+    vmlinux::socket,
+    Action,
+};
+
+/// Inspects the context of `socket_listen` LSM hook and decides whether to
+/// allow or deny the listen operation based on the state of the
+/// `ALLOWED_SOCKET_LISTEN` and `DENIED_SOCKET_LISTEN` maps, with the same
+/// precedence as `socket_bind`.
+///
+/// The hook gets the socket (already bound) and the backlog, but no address.
+/// The local port (`skc_num`) and address (`skc_rcv_saddr`/
+/// `skc_v6_rcv_saddr`) are available in the socket, only the port is matched.
+/// Both `AF_INET` and `AF_INET6` sockets are checked, other families are
+/// always allowed.
+///
+/// Allowed listens are denied if the socket has options set which are denied
+/// in the `OPTIONS_SOCKET_LISTEN` map.
+///
+/// If denied, the operation is logged to the `ALERT_SOCKET_LISTEN` map.
+///
+/// # Example
+///
+/// ```rust
+/// use aya_bpf::{macros::lsm, programs::LsmContext};
+/// use ebpfguard_ebpf::socket_listen;
+///
+/// #[lsm(name = "my_program")]
+/// pub fn my_program(ctx: LsmContext) -> i32 {
+///     match socket_listen::socket_listen(ctx) {
+///         Ok(ret) => ret.into(),
+///         Err(_) => 0,
+///     }
+/// }
+/// ```
+#[inline(always)]
+pub fn socket_listen(ctx: LsmContext) -> Result<Action, c_long> {
+    let sock: *const socket = unsafe { ctx.arg(0) };
+
+    let family = unsafe { socket_sk_family(sock) };
+    if family != AF_INET && family != AF_INET6 {
+        return Ok(Action::Allow);
+    }
+    let port = unsafe { socket_sk_num(sock) };
+
+    let namespace = policy_namespace(current_namespace(), HOOK_SOCKET_LISTEN);
+    let key = InodeKey::new(namespace, current_binprm_inode()?);
+
+    let action = match check_ports(key, port) {
+        Action::Allow if denied_options(&OPTIONS_SOCKET_LISTEN, key, sock) != 0 => {
+            Action::Deny(REASON_SOCKET_OPTION)
+        }
+        action => action,
+    };
+
+    match action {
+        Action::Deny(reason) => {
+            output_alert(
+                &ctx,
+                &ALERT_SOCKET_LISTEN,
+                HOOK_SOCKET_LISTEN,
+                alerts::SocketListen::new(
+                    ctx.pid(),
+                    untagged_namespace(namespace),
+                    current_session(ctx.pid()),
+                    reason,
+                    key.inode,
+                    family,
+                    port,
+                ),
+            );
+            Ok(Action::Deny(reason))
+        }
+        action => Ok(action),
+    }
+}
+
+#[inline(always)]
+fn check_ports(key: InodeKey, port: u16) -> Action {
+    let wildcard = InodeKey::wildcard(key.namespace);
+
+    if let Some(ports) = unsafe { ALLOWED_SOCKET_LISTEN.get(&wildcard) } {
+        if ports.all() {
+            if let Some(ports) = unsafe { DENIED_SOCKET_LISTEN.get(&wildcard) } {
+                if ports.all() {
+                    return Action::Deny(REASON_WILDCARD_DENY_ALL);
+                }
+                if ports.ports[..MAX_PORTS - 1].contains(&port) {
+                    return Action::Deny(REASON_WILDCARD_DENY);
+                }
+            }
+
+            if let Some(ports) = unsafe { DENIED_SOCKET_LISTEN.get(&key) } {
+                if ports.all() {
+                    return Action::Deny(REASON_BINARY_DENY_ALL);
+                }
+                if ports.ports[..MAX_PORTS - 1].contains(&port) {
+                    return Action::Deny(REASON_BINARY_DENY);
+                }
+            }
+        } else if ports.ports[..MAX_PORTS - 1].contains(&port) {
+            return Action::Allow;
+        }
+    }
+
+    if let Some(ports) = unsafe { DENIED_SOCKET_LISTEN.get(&wildcard) } {
+        if ports.all() {
+            if let Some(ports) = unsafe { ALLOWED_SOCKET_LISTEN.get(&wildcard) } {
+                if ports.all() || ports.ports[..MAX_PORTS - 1].contains(&port) {
+                    return Action::Allow;
+                }
+            }
+
+            if let Some(ports) = unsafe { ALLOWED_SOCKET_LISTEN.get(&key) } {
+                if ports.all() || ports.ports[..MAX_PORTS - 1].contains(&port) {
+                    return Action::Allow;
+                }
+            }
+
+            return Action::Deny(REASON_DEFAULT_DENY);
+        } else if ports.ports[..MAX_PORTS - 1].contains(&port) {
+            return Action::Deny(REASON_WILDCARD_DENY_LISTED);
+        }
+    }
+
+    Action::Allow
+}
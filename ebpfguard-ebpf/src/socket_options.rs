@@ -0,0 +1,48 @@
+use aya_bpf::maps::HashMap;
+use ebpfguard_common::policy::{
+    InodeKey, SOCKET_OPTION_BINDTODEVICE, SOCKET_OPTION_REUSEADDR, SOCKET_OPTION_REUSEPORT,
+};
+
+use crate::{socket_sk_bound_dev_if, socket_sk_reuse, socket_sk_reuseport, vmlinux::socket};
+
+/// Returns the socket options (`SOCKET_OPTION_*` flags) set on the socket
+/// which are denied for the binary or for all binaries in the given map.
+///
+/// The socket is read only if any options are denied, so binaries without
+/// option rules don't pay for it.
+#[inline(always)]
+pub(crate) fn denied_options(
+    map: &HashMap<InodeKey, u8>,
+    key: InodeKey,
+    sock: *const socket,
+) -> u8 {
+    let mut denied = 0;
+    if let Some(options) = unsafe { map.get(&InodeKey::wildcard(key.namespace)) } {
+        denied |= *options;
+    }
+    if let Some(options) = unsafe { map.get(&key) } {
+        denied |= *options;
+    }
+
+    if denied == 0 {
+        return 0;
+    }
+
+    denied & socket_options(sock)
+}
+
+/// Returns the options set on the socket, as `SOCKET_OPTION_*` flags.
+#[inline(always)]
+fn socket_options(sock: *const socket) -> u8 {
+    let mut options = 0;
+    if unsafe { socket_sk_reuse(sock) } != 0 {
+        options |= SOCKET_OPTION_REUSEADDR;
+    }
+    if unsafe { socket_sk_reuseport(sock) } != 0 {
+        options |= SOCKET_OPTION_REUSEPORT;
+    }
+    if unsafe { socket_sk_bound_dev_if(sock) } != 0 {
+        options |= SOCKET_OPTION_BINDTODEVICE;
+    }
+    options
+}
@@ -0,0 +1,113 @@
+use aya_bpf::{cty::c_long, programs::LsmContext, BpfContext};
+use ebpfguard_common::{
+    alerts::{self, REASON_BINARY_DENY_ALL, REASON_DEFAULT_DENY},
+    policy::{GidKey, InodeKey, HOOK_TASK_FIX_SETGID},
+};
+
+use crate::{
+    alert::output_alert,
+    binprm::current_binprm_inode,
+    cred_egid_val, cred_gid_val,
+    generation::policy_namespace,
+    maps::{
+        ALERT_TASK_FIX_SETGID, ALLOWED_TASK_FIX_SETGID, DENIED_TASK_FIX_SETGID,
+        PRIVILEGED_TASK_FIX_SETGID,
+    },
+    namespace::current_namespace,
+    session::current_session,
+    vmlinux::cred,
+};
+
+/// Inspects the context of `task_fix_setgid` LSM hook and decides whether to
+/// allow or deny the operation based on the state of the
+/// `ALLOWED_TASK_FIX_SETGID` and `DENIED_TASK_FIX_SETGID` maps, the same way
+/// as `task_fix_setuid`.
+///
+/// Only transitions to a privileged group (group 0 or a group in the
+/// `PRIVILEGED_TASK_FIX_SETGID` map of the namespace) are checked: the new
+/// real or effective group is privileged and differs from the old one. Other
+/// transitions are always allowed.
+///
+/// If denied, the operation is logged to the `ALERT_TASK_FIX_SETGID` map.
+///
+/// # Example
+///
+/// ```rust
+/// use aya_bpf::{macros::lsm, programs::LsmContext};
+/// use ebpfguard_ebpf::task_fix_setgid;
+///
+/// #[lsm(name = "my_program")]
+/// pub fn my_program(ctx: LsmContext) -> i32 {
+///     match task_fix_setgid::task_fix_setgid(ctx) {
+///         Ok(ret) => ret,
+///         Err(_) => 0,
+///     }
+/// }
+/// ```
+pub fn task_fix_setgid(ctx: LsmContext) -> Result<i32, c_long> {
+    let new: *const cred = unsafe { ctx.arg(0) };
+    let old: *const cred = unsafe { ctx.arg(1) };
+
+    let old_gid = unsafe { cred_gid_val(old) };
+    let old_egid = unsafe { cred_egid_val(old) };
+    let new_gid = unsafe { cred_gid_val(new) };
+    let new_egid = unsafe { cred_egid_val(new) };
+
+    let namespace = current_namespace();
+    let privileged = |gid: u32| {
+        gid == 0
+            || unsafe { PRIVILEGED_TASK_FIX_SETGID.get(&GidKey::new(namespace, gid)) }.is_some()
+    };
+    let to_privileged = (new_gid != old_gid && privileged(new_gid))
+        || (new_egid != old_egid && privileged(new_egid));
+    if !to_privileged {
+        return Ok(0);
+    }
+
+    let key_namespace = policy_namespace(namespace, HOOK_TASK_FIX_SETGID);
+    let binprm_inode = current_binprm_inode()?;
+    let key = InodeKey::new(key_namespace, binprm_inode);
+    let wildcard = InodeKey::wildcard(key_namespace);
+
+    let alert = |reason| {
+        alerts::TaskFixSetgid::new(
+            ctx.pid(),
+            namespace,
+            current_session(ctx.pid()),
+            reason,
+            binprm_inode,
+            old_gid,
+            old_egid,
+            new_gid,
+            new_egid,
+        )
+    };
+
+    if unsafe { ALLOWED_TASK_FIX_SETGID.get(&wildcard) }.is_some() {
+        if unsafe { DENIED_TASK_FIX_SETGID.get(&key).is_some() } {
+            output_alert(
+                &ctx,
+                &ALERT_TASK_FIX_SETGID,
+                HOOK_TASK_FIX_SETGID,
+                alert(REASON_BINARY_DENY_ALL),
+            );
+            return Ok(-1);
+        }
+        return Ok(0);
+    }
+
+    if unsafe { DENIED_TASK_FIX_SETGID.get(&wildcard) }.is_some() {
+        if unsafe { ALLOWED_TASK_FIX_SETGID.get(&key).is_some() } {
+            return Ok(0);
+        }
+        output_alert(
+            &ctx,
+            &ALERT_TASK_FIX_SETGID,
+            HOOK_TASK_FIX_SETGID,
+            alert(REASON_DEFAULT_DENY),
+        );
+        return Ok(-1);
+    }
+
+    Ok(0)
+}
@@ -1,10 +1,18 @@
-use aya_bpf::{cty::c_long, programs::LsmContext, BpfContext};
-use ebpfguard_common::{alerts, consts::INODE_WILDCARD};
+use aya_bpf::{cty::c_long, helpers::bpf_get_current_comm, programs::LsmContext, BpfContext};
+use ebpfguard_common::{
+    alerts::{self, REASON_BINARY_DENY_ALL, REASON_DEFAULT_DENY},
+    policy::{InodeKey, HOOK_TASK_FIX_SETUID},
+};
 
 use crate::{
+    alert::output_alert,
     binprm::current_binprm_inode,
     cred_gid_val, cred_uid_val,
+    generation::policy_namespace,
     maps::{ALERT_TASK_FIX_SETUID, ALLOWED_TASK_FIX_SETUID, DENIED_TASK_FIX_SETUID},
+    namespace::current_namespace,
+    process::current_ppid,
+    session::current_session,
     vmlinux::cred,
 };
 
@@ -12,19 +20,22 @@ use crate::{
 /// allow or deny the operation based on the state of the `ALLOWED_SETUID`
 /// and `DENIED_SETUID` maps.
 ///
-/// If denied, the operation is logged to the `ALERT_SETUID` map.
+/// If denied, the operation is logged to the `ALERT_SETUID` map, with the
+/// command name and parent PID of the process besides the binary inode, so
+/// the alert tells which program tried to switch and who started it.
 ///
 /// # Example
 ///
 /// ```rust
 /// use aya_bpf::{macros::lsm, programs::LsmContext};
-/// use ebpfguard_ebpf::setuid;
+/// use ebpfguard_ebpf::task_fix_setuid;
 ///
 /// #[lsm(name = "my_program")]
 /// pub fn my_program(ctx: LsmContext) -> i32 {
-///    match setuid::setuid(ctx) {
-///       Ok(ret) => ret,
-///       Err(_) => 0,
+///     match task_fix_setuid::task_fix_setuid(ctx) {
+///         Ok(ret) => ret,
+///         Err(_) => 0,
+///     }
 /// }
 /// ```
 pub fn task_fix_setuid(ctx: LsmContext) -> Result<i32, c_long> {
@@ -36,42 +47,51 @@ pub fn task_fix_setuid(ctx: LsmContext) -> Result<i32, c_long> {
     let new_uid = unsafe { cred_uid_val(new) };
     let new_gid = unsafe { cred_gid_val(new) };
 
+    let namespace = current_namespace();
+    let key_namespace = policy_namespace(namespace, HOOK_TASK_FIX_SETUID);
     let binprm_inode = current_binprm_inode()?;
+    let key = InodeKey::new(key_namespace, binprm_inode);
+    let wildcard = InodeKey::wildcard(key_namespace);
+
+    // Only read when denying, so allowed switches don't pay for it.
+    let alert = |reason| -> Result<alerts::TaskFixSetuid, c_long> {
+        Ok(alerts::TaskFixSetuid::new(
+            ctx.pid(),
+            namespace,
+            current_session(ctx.pid()),
+            reason,
+            binprm_inode,
+            old_uid,
+            old_gid,
+            new_uid,
+            new_gid,
+            current_ppid()?,
+            bpf_get_current_comm()?,
+        ))
+    };
 
-    if unsafe { ALLOWED_TASK_FIX_SETUID.get(&INODE_WILDCARD) }.is_some() {
-        if unsafe { DENIED_TASK_FIX_SETUID.get(&binprm_inode).is_some() } {
-            ALERT_TASK_FIX_SETUID.output(
+    if unsafe { ALLOWED_TASK_FIX_SETUID.get(&wildcard) }.is_some() {
+        if unsafe { DENIED_TASK_FIX_SETUID.get(&key).is_some() } {
+            output_alert(
                 &ctx,
-                &alerts::TaskFixSetuid::new(
-                    ctx.pid(),
-                    binprm_inode,
-                    old_uid,
-                    old_gid,
-                    new_uid,
-                    new_gid,
-                ),
-                0,
+                &ALERT_TASK_FIX_SETUID,
+                HOOK_TASK_FIX_SETUID,
+                alert(REASON_BINARY_DENY_ALL)?,
             );
             return Ok(-1);
         }
         return Ok(0);
     }
 
-    if unsafe { DENIED_TASK_FIX_SETUID.get(&INODE_WILDCARD) }.is_some() {
-        if unsafe { ALLOWED_TASK_FIX_SETUID.get(&binprm_inode).is_some() } {
+    if unsafe { DENIED_TASK_FIX_SETUID.get(&wildcard) }.is_some() {
+        if unsafe { ALLOWED_TASK_FIX_SETUID.get(&key).is_some() } {
             return Ok(0);
         }
-        ALERT_TASK_FIX_SETUID.output(
+        output_alert(
             &ctx,
-            &alerts::TaskFixSetuid::new(
-                ctx.pid(),
-                binprm_inode,
-                old_uid,
-                old_gid,
-                new_uid,
-                new_gid,
-            ),
-            0,
+            &ALERT_TASK_FIX_SETUID,
+            HOOK_TASK_FIX_SETUID,
+            alert(REASON_DEFAULT_DENY)?,
         );
         return Ok(-1);
     }
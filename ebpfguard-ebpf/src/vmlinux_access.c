@@ -1,13 +1,49 @@
 #include "vmlinux.h"
 
+/*
+ * Reads a bitfield of a BTF-typed pointer with CO-RE relocations, like
+ * BPF_CORE_READ_BITFIELD of libbpf. The builtin's second argument is the
+ * kind of the relocation: 0 byte offset, 1 byte size, 4 left shift and 5
+ * right shift of the 64-bit value.
+ */
+#define READ_BITFIELD(s, field) ({						\
+	unsigned long long val = 0;						\
+	const void *p = (const void *)s +					\
+		__builtin_preserve_field_info((s)->field, 0);			\
+	switch (__builtin_preserve_field_info((s)->field, 1)) {		\
+	case 1: val = *(const unsigned char *)p; break;				\
+	case 2: val = *(const unsigned short *)p; break;			\
+	case 4: val = *(const unsigned int *)p; break;				\
+	case 8: val = *(const unsigned long long *)p; break;			\
+	}									\
+	val <<= __builtin_preserve_field_info((s)->field, 4);			\
+	val >>= __builtin_preserve_field_info((s)->field, 5);			\
+	val;									\
+})
+
 pid_t task_struct_pid(struct task_struct *task)
 {
 	return __builtin_preserve_access_index(task->pid);
 }
 
-pid_t task_struct_tgid(struct task_struct *task)
+pid_t * task_struct_tgid(struct task_struct *task)
 {
-	return __builtin_preserve_access_index(task->tgid);
+	return __builtin_preserve_access_index(&task->tgid);
+}
+
+struct task_struct ** task_struct_real_parent(struct task_struct *task)
+{
+	return __builtin_preserve_access_index(&task->real_parent);
+}
+
+struct task_struct ** task_struct_group_leader(struct task_struct *task)
+{
+	return __builtin_preserve_access_index(&task->group_leader);
+}
+
+uint64_t * task_struct_start_time(struct task_struct *task)
+{
+	return __builtin_preserve_access_index(&task->start_time);
 }
 
 struct mm_struct ** task_struct_mm(struct task_struct *task)
@@ -45,6 +81,16 @@ uint64_t * inode_i_ino(struct inode *inode)
 	return __builtin_preserve_access_index(&inode->i_ino);
 }
 
+struct super_block ** inode_i_sb(struct inode *inode)
+{
+	return __builtin_preserve_access_index(&inode->i_sb);
+}
+
+dev_t * super_block_s_dev(struct super_block *sb)
+{
+	return __builtin_preserve_access_index(&sb->s_dev);
+}
+
 int32_t linux_binprm_argc(struct linux_binprm *target)
 {
 	return __builtin_preserve_access_index(target->argc);
@@ -77,7 +123,41 @@ uid_t cred_gid_val(struct cred *target)
 	return __builtin_preserve_access_index(target->gid.val);
 }
 
+gid_t cred_egid_val(struct cred *target)
+{
+	return __builtin_preserve_access_index(target->egid.val);
+}
+
 uint16_t sockaddr_in_sin_port(struct sockaddr_in *target)
 {
 	return __builtin_preserve_access_index(target->sin_port);
 }
+
+uint16_t socket_sk_family(struct socket *target)
+{
+	return __builtin_preserve_access_index(target->sk->__sk_common.skc_family);
+}
+
+uint16_t socket_sk_num(struct socket *target)
+{
+	return __builtin_preserve_access_index(target->sk->__sk_common.skc_num);
+}
+
+uint8_t socket_sk_reuse(struct socket *target)
+{
+	struct sock_common *sk_common =
+		__builtin_preserve_access_index(&target->sk->__sk_common);
+	return READ_BITFIELD(sk_common, skc_reuse);
+}
+
+uint8_t socket_sk_reuseport(struct socket *target)
+{
+	struct sock_common *sk_common =
+		__builtin_preserve_access_index(&target->sk->__sk_common);
+	return READ_BITFIELD(sk_common, skc_reuseport);
+}
+
+int socket_sk_bound_dev_if(struct socket *target)
+{
+	return __builtin_preserve_access_index(target->sk->__sk_common.skc_bound_dev_if);
+}
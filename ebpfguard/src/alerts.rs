@@ -1,26 +1,511 @@
-use ebpfguard_common::alerts;
+//! Alerts about operations denied by the eBPF programs.
+//!
+//! Alerts can be correlated with the `session` field, which identifies a
+//! process from its exec. The guarantees are:
+//!
+//! * All alerts triggered by a process (any of its threads) between two execs
+//!   carry the same session, so e.g. a denied connect can be linked to a
+//!   denied bind of the same process.
+//! * A new exec starts a new session, while the PID stays the same.
+//! * Sessions are tracked only while the `bprm_check_security` hook is
+//!   attached. Processes which were executed before, forked children which
+//!   didn't exec yet and processes evicted from the session map (which keeps
+//!   the latest 8192 processes) have session 0. A process reusing the PID of
+//!   an exited one carries its session until it execs.
+//! * Session IDs are the boot-time nanosecond timestamp of the exec, so they
+//!   are not unique across reboots.
+//!
+//! There is no per-event correlation: a single syscall checked by multiple
+//! hooks produces unrelated alerts, which only share the session and PID.
+//!
+//! Alerts of each stream (each call of a hook's `alerts()`) carry a `seq`
+//! field, increasing by one with every alert, starting from 1. Alerts which
+//! the kernel dropped because the perf buffer of a CPU was full still take
+//! their sequence numbers, so a consumer can detect missed alerts with a
+//! [`GapDetector`]. The limits are:
+//!
+//! * Sequences are kept in memory. A new stream, e.g. after an agent restart,
+//!   starts from 1 again, which the [`GapDetector`] of a consumer resuming
+//!   from a stored cursor reports as [`Gap::Restarted`]. Alerts are not
+//!   replayed: the kernel drops events from its buffers once they are read,
+//!   and alerts emitted while no stream was reading are lost without a trace.
+//! * The kernel reports dropped events per CPU buffer, without their policy
+//!   namespace, so a gap may also be reported for dropped alerts of other
+//!   namespaces.
+//! * Alerts from different CPUs are numbered in the order they are read,
+//!   which is not necessarily the order they happened in.
+//!
+//! Alerts are read through a perf buffer per CPU, sized by [`AlertBuffers`].
+//!
+//! Agents of several hosts forwarding to one collector can report the same
+//! event, e.g. a binary denied the same port on every host of a cluster.
+//! [`Alert::event_fingerprint`] hashes the fields describing the event,
+//! without the ones specific to the host or the process, so the collector can
+//! deduplicate them.
+
+use ebpfguard_common::alerts::{self, CHANNEL_DEFAULT, MAX_ALERT_SIZE, MESSAGE_NONE};
 use serde::Serialize;
 use std::{
+    fmt,
     net::{IpAddr, Ipv4Addr, Ipv6Addr},
-    path::PathBuf,
+    os::unix::ffi::OsStrExt,
+    path::{Path, PathBuf},
+    time::Duration,
 };
 
-use crate::policy::PolicySubject;
+use crate::{error::EbpfguardError, policy::PolicySubject};
+
+/// Smallest page size of the supported architectures, which perf buffer
+/// sizes are checked against.
+const MIN_PAGE_SIZE: usize = 4096;
+
+/// Per-record overhead in perf buffers: the `perf_event_header` and the size
+/// of the raw sample.
+const PERF_RECORD_HEADER: usize = 12;
+
+/// Sizes of the buffers alerts are read through, set with
+/// [`PolicyManager::set_alert_buffers`](crate::PolicyManager::set_alert_buffers).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AlertBuffers {
+    /// Pages of the perf buffer of each CPU, a power of two. More pages let
+    /// bursts of alerts wait longer for the reader before they're dropped.
+    pub pages: usize,
+    /// Capacity of each buffer a record is copied into when read.
+    pub record_size: usize,
+}
+
+impl Default for AlertBuffers {
+    fn default() -> Self {
+        Self {
+            pages: 2,
+            record_size: 1024,
+        }
+    }
+}
+
+impl AlertBuffers {
+    /// Returns the size of the largest record the buffers fit.
+    pub fn record_budget(&self) -> usize {
+        let ring = (self.pages * MIN_PAGE_SIZE).saturating_sub(PERF_RECORD_HEADER);
+        ring.min(self.record_size)
+    }
+
+    /// Checks that the buffers fit the largest alert of all hooks
+    /// ([`MAX_ALERT_SIZE`]), so no hook's alerts can be truncated.
+    pub fn check(&self) -> Result<(), EbpfguardError> {
+        if !self.pages.is_power_of_two() {
+            return Err(EbpfguardError::InvalidAlertPages(self.pages));
+        }
+        let budget = self.record_budget();
+        if budget < MAX_ALERT_SIZE {
+            return Err(EbpfguardError::AlertBuffersTooSmall {
+                size: MAX_ALERT_SIZE,
+                budget,
+            });
+        }
+        Ok(())
+    }
+}
+
+pub trait Alert: Serialize {
+    /// Sets the sequence number of the alert in its stream.
+    fn set_seq(&mut self, seq: u64);
+
+    /// Returns the ID of the message of the rule which denied the operation,
+    /// 0 if the rule has no message (see [`Messages`](crate::messages::Messages)).
+    fn message_id(&self) -> u16;
+
+    /// Returns the channel of the binary which triggered the alert,
+    /// [`CHANNEL_DEFAULT`](alerts::CHANNEL_DEFAULT) if it has none (see
+    /// [`ChannelRouter`](crate::sink::ChannelRouter)).
+    fn channel(&self) -> u8;
+
+    /// Returns a hash of the event the alert reports, equal for alerts of
+    /// the same event raised on different hosts or by different processes.
+    ///
+    /// The hash is 64-bit FNV-1a over the fields, so it's the same across
+    /// hosts, runs and versions of this library (as long as the fields of the
+    /// alert don't change). Fingerprints of all alerts include the type of
+    /// the alert and `namespace`, and of all but [`Heartbeat`] `reason`,
+    /// `message_id` and `subject`, followed by:
+    ///
+    /// * [`Bpf`]: `cmd`.
+    /// * [`BprmCheckSecurity`], [`SbMount`], [`SbRemount`], [`SbUmount`]:
+    ///   nothing else.
+    /// * [`FileOpen`]: `path`.
+    /// * [`InodeCreate`]: `dir` and `mode`.
+    /// * [`SocketBind`], [`SocketBindEscalation`], [`SocketListen`]: `family`
+    ///   and `port`.
+    /// * [`SocketConnect`]: `addr` and `port`.
+    /// * [`SocketCreate`]: `family`, `socket_type` and `protocol`.
+    /// * [`TaskFixSetuid`]: `old_uid`, `old_gid`, `new_uid`, `new_gid` and
+    ///   `comm`.
+    /// * [`TaskFixSetgid`]: `old_gid`, `old_egid`, `new_gid` and `new_egid`.
+    /// * [`Heartbeat`]: `hooks`.
+    ///
+    /// Left out are `seq`, `pid`, `session` and `channel`, and the fields
+    /// depending on the state of the process or of the host: `count` of
+    /// [`SocketBind`], `binprm_inode` of [`SocketBindEscalation`] (it's in
+    /// the subject), `rate` of [`SocketConnect`], `ppid` of [`TaskFixSetuid`]
+    /// and `uptime` of [`Heartbeat`]. Heartbeats of hosts with the same
+    /// hooks are thus equal, and shouldn't be deduplicated.
+    ///
+    /// Subjects and paths are hashed as they are: the kernel reports inode
+    /// numbers, which differ between hosts unless they share the filesystem,
+    /// so consumers replacing them with paths have to do so before.
+    fn event_fingerprint(&self) -> u64;
+}
+
+/// 64-bit FNV-1a hash of the fields of an alert, see
+/// [`Alert::event_fingerprint`].
+struct Fingerprint(u64);
+
+impl Fingerprint {
+    const OFFSET_BASIS: u64 = 0xcbf2_9ce4_8422_2325;
+    const PRIME: u64 = 0x0100_0000_01b3;
+
+    fn new(alert: &str) -> Self {
+        Self(Self::OFFSET_BASIS).bytes(alert.as_bytes())
+    }
+
+    /// Starts the fingerprint of an alert with the fields common to the
+    /// alerts of the hooks.
+    fn common(
+        alert: &str,
+        namespace: u32,
+        reason: Reason,
+        message_id: u16,
+        subject: &PolicySubject,
+    ) -> Self {
+        let fingerprint = Self::new(alert)
+            .u64(namespace.into())
+            .u64(reason.code().into())
+            .u64(message_id.into());
+        match subject {
+            PolicySubject::Binary(path) => fingerprint.u64(1).path(path),
+            PolicySubject::All => fingerprint.u64(0),
+        }
+    }
+
+    fn write(mut self, bytes: &[u8]) -> Self {
+        for &byte in bytes {
+            self.0 ^= u64::from(byte);
+            self.0 = self.0.wrapping_mul(Self::PRIME);
+        }
+        self
+    }
+
+    /// Hashes a field of variable length, prefixed with the length so that
+    /// consecutive fields can't shift into each other.
+    fn bytes(self, bytes: &[u8]) -> Self {
+        self.write(&(bytes.len() as u64).to_le_bytes()).write(bytes)
+    }
+
+    fn u64(self, value: u64) -> Self {
+        self.write(&value.to_le_bytes())
+    }
+
+    fn path(self, path: &Path) -> Self {
+        self.bytes(path.as_os_str().as_bytes())
+    }
+
+    fn addr(self, addr: &IpAddr) -> Self {
+        match addr {
+            IpAddr::V4(addr) => self.u64(4).bytes(&addr.octets()),
+            IpAddr::V6(addr) => self.u64(6).bytes(&addr.octets()),
+        }
+    }
+
+    fn finish(self) -> u64 {
+        self.0
+    }
+}
+
+/// Missed alerts detected by a [`GapDetector`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Gap {
+    /// Alerts with sequence numbers `from..from + count` were missed.
+    Missed { from: u64, count: u64 },
+    /// The sequence went back, i.e. the stream was restarted and alerts after
+    /// the cursor may have been missed without being counted.
+    Restarted,
+}
+
+/// Detects missed alerts from sequence numbers of a stream.
+///
+/// # Example
+///
+/// ```
+/// use ebpfguard::alerts::{Gap, GapDetector};
+///
+/// let mut gaps = GapDetector::resume(41);
+/// assert_eq!(gaps.observe(42), None);
+/// assert_eq!(gaps.observe(45), Some(Gap::Missed { from: 43, count: 2 }));
+/// assert_eq!(gaps.cursor(), 45);
+/// ```
+#[derive(Debug, Default)]
+pub struct GapDetector {
+    cursor: u64,
+}
+
+impl GapDetector {
+    /// Creates a detector for a stream read from the beginning.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Creates a detector for a consumer resuming after the alert with the
+    /// given sequence number, e.g. stored before a restart of the consumer.
+    pub fn resume(cursor: u64) -> Self {
+        Self { cursor }
+    }
+
+    /// Returns the sequence number of the last observed alert.
+    pub fn cursor(&self) -> u64 {
+        self.cursor
+    }
+
+    /// Observes the sequence number of the next alert of the stream and
+    /// returns alerts missed before it, if any.
+    pub fn observe(&mut self, seq: u64) -> Option<Gap> {
+        let expected = self.cursor + 1;
+        self.cursor = seq;
+        if seq < expected {
+            Some(Gap::Restarted)
+        } else if seq > expected {
+            Some(Gap::Missed {
+                from: expected,
+                count: seq - expected,
+            })
+        } else {
+            None
+        }
+    }
+}
+
+/// Decision point of the eBPF program which denied the operation.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Reason {
+    /// Denied by the policy of all binaries, covering all resources.
+    WildcardDenyAll,
+    /// Denied by the policy of all binaries, listing the resource as an
+    /// exception to an allow-all policy.
+    WildcardDeny,
+    /// Denied by the policy of all binaries, listing the resource, without an
+    /// allow-all policy.
+    WildcardDenyListed,
+    /// Denied by the policy of the binary, covering all resources.
+    BinaryDenyAll,
+    /// Denied by the policy of the binary, listing the resource.
+    BinaryDeny,
+    /// Denied by default, no policy allowed the operation.
+    DefaultDeny,
+    /// The resource is protected and the binary is not allowed to access it.
+    Protected,
+    /// Escalated and denied by a user space verdict.
+    EscalationVerdict,
+    /// Escalated and denied by the fallback verdict.
+    EscalationFallback,
+    /// Executed binary without arguments.
+    NoArgs,
+    /// Allowed by the policies, but the socket has a denied option set.
+    SocketOption,
+    /// Connect to a link-local or instance metadata address.
+    Metadata,
+    /// The process has already bound as many distinct ports as it may.
+    BindLimit,
+    /// The destination has already been connected to as many times as its
+    /// rate limit allows in the current window.
+    RateLimit,
+    /// Denied as many times within the window of the binary's retry
+    /// escalation as its threshold, replacing the reason of the denial.
+    Persistent,
+    /// Denied by default, no policy allowed the bind in its family, while the
+    /// rules for the other family allow the port (e.g. an `AF_INET6` bind
+    /// meeting an allow rule scoped to `ipv4`). The family of the bind is in
+    /// the `family` field of the alert.
+    FamilyMismatch,
+    /// Code unknown to this version of user space.
+    Unknown(u8),
+}
+
+impl From<u8> for Reason {
+    fn from(reason: u8) -> Self {
+        match reason {
+            alerts::REASON_WILDCARD_DENY_ALL => Reason::WildcardDenyAll,
+            alerts::REASON_WILDCARD_DENY => Reason::WildcardDeny,
+            alerts::REASON_WILDCARD_DENY_LISTED => Reason::WildcardDenyListed,
+            alerts::REASON_BINARY_DENY_ALL => Reason::BinaryDenyAll,
+            alerts::REASON_BINARY_DENY => Reason::BinaryDeny,
+            alerts::REASON_DEFAULT_DENY => Reason::DefaultDeny,
+            alerts::REASON_PROTECTED => Reason::Protected,
+            alerts::REASON_ESCALATION_VERDICT => Reason::EscalationVerdict,
+            alerts::REASON_ESCALATION_FALLBACK => Reason::EscalationFallback,
+            alerts::REASON_NO_ARGS => Reason::NoArgs,
+            alerts::REASON_SOCKET_OPTION => Reason::SocketOption,
+            alerts::REASON_METADATA => Reason::Metadata,
+            alerts::REASON_BIND_LIMIT => Reason::BindLimit,
+            alerts::REASON_RATE_LIMIT => Reason::RateLimit,
+            alerts::REASON_PERSISTENT => Reason::Persistent,
+            alerts::REASON_FAMILY_MISMATCH => Reason::FamilyMismatch,
+            reason => Reason::Unknown(reason),
+        }
+    }
+}
+
+impl Reason {
+    /// Returns the code the eBPF programs report the reason with.
+    fn code(self) -> u8 {
+        match self {
+            Reason::WildcardDenyAll => alerts::REASON_WILDCARD_DENY_ALL,
+            Reason::WildcardDeny => alerts::REASON_WILDCARD_DENY,
+            Reason::WildcardDenyListed => alerts::REASON_WILDCARD_DENY_LISTED,
+            Reason::BinaryDenyAll => alerts::REASON_BINARY_DENY_ALL,
+            Reason::BinaryDeny => alerts::REASON_BINARY_DENY,
+            Reason::DefaultDeny => alerts::REASON_DEFAULT_DENY,
+            Reason::Protected => alerts::REASON_PROTECTED,
+            Reason::EscalationVerdict => alerts::REASON_ESCALATION_VERDICT,
+            Reason::EscalationFallback => alerts::REASON_ESCALATION_FALLBACK,
+            Reason::NoArgs => alerts::REASON_NO_ARGS,
+            Reason::SocketOption => alerts::REASON_SOCKET_OPTION,
+            Reason::Metadata => alerts::REASON_METADATA,
+            Reason::BindLimit => alerts::REASON_BIND_LIMIT,
+            Reason::RateLimit => alerts::REASON_RATE_LIMIT,
+            Reason::Persistent => alerts::REASON_PERSISTENT,
+            Reason::FamilyMismatch => alerts::REASON_FAMILY_MISMATCH,
+            Reason::Unknown(reason) => reason,
+        }
+    }
+}
+
+impl fmt::Display for Reason {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Reason::WildcardDenyAll => write!(f, "denied for all binaries"),
+            Reason::WildcardDeny => write!(f, "denied for all binaries as an exception"),
+            Reason::WildcardDenyListed => write!(f, "listed as denied for all binaries"),
+            Reason::BinaryDenyAll => write!(f, "denied for the binary"),
+            Reason::BinaryDeny => write!(f, "listed as denied for the binary"),
+            Reason::DefaultDeny => write!(f, "denied by default"),
+            Reason::Protected => write!(f, "protected from the binary"),
+            Reason::EscalationVerdict => write!(f, "denied by user space verdict"),
+            Reason::EscalationFallback => write!(f, "denied by fallback verdict"),
+            Reason::NoArgs => write!(f, "executed without arguments"),
+            Reason::SocketOption => write!(f, "denied socket option"),
+            Reason::Metadata => write!(f, "denied link-local or metadata address"),
+            Reason::BindLimit => write!(f, "bind limit exceeded"),
+            Reason::RateLimit => write!(f, "connect rate limit exceeded"),
+            Reason::Persistent => write!(f, "persistent violation"),
+            Reason::FamilyMismatch => write!(f, "denied by default, allowed for the other family"),
+            Reason::Unknown(reason) => write!(f, "unknown reason {reason}"),
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct Bpf {
+    pub seq: u64,
+    pub pid: u32,
+    pub namespace: u32,
+    pub session: u64,
+    pub reason: Reason,
+    pub message_id: u16,
+    pub channel: u8,
+    pub subject: PolicySubject,
+    /// `bpf` command of the denied syscall, e.g. `BPF_PROG_LOAD` (5).
+    pub cmd: u32,
+}
+
+impl Alert for Bpf {
+    fn set_seq(&mut self, seq: u64) {
+        self.seq = seq;
+    }
+
+    fn message_id(&self) -> u16 {
+        self.message_id
+    }
+
+    fn channel(&self) -> u8 {
+        self.channel
+    }
+
+    fn event_fingerprint(&self) -> u64 {
+        Fingerprint::common(
+            "bpf",
+            self.namespace,
+            self.reason,
+            self.message_id,
+            &self.subject,
+        )
+        .u64(self.cmd.into())
+        .finish()
+    }
+}
 
-pub trait Alert: Serialize {}
+impl From<alerts::Bpf> for Bpf {
+    fn from(alert: alerts::Bpf) -> Self {
+        Self {
+            seq: 0,
+            pid: alert.pid,
+            namespace: alert.namespace,
+            session: alert.session,
+            reason: alert.reason.into(),
+            message_id: alert.message_id,
+            channel: alert.channel,
+            subject: PolicySubject::Binary(PathBuf::from(alert.binprm_inode.to_string())),
+            cmd: alert.cmd,
+        }
+    }
+}
 
 #[derive(Debug, Serialize)]
 pub struct BprmCheckSecurity {
+    pub seq: u64,
     pub pid: u32,
+    pub namespace: u32,
+    pub session: u64,
+    pub reason: Reason,
+    pub message_id: u16,
+    pub channel: u8,
     pub subject: PolicySubject,
 }
 
-impl Alert for BprmCheckSecurity {}
+impl Alert for BprmCheckSecurity {
+    fn set_seq(&mut self, seq: u64) {
+        self.seq = seq;
+    }
+
+    fn message_id(&self) -> u16 {
+        self.message_id
+    }
+
+    fn channel(&self) -> u8 {
+        self.channel
+    }
+
+    fn event_fingerprint(&self) -> u64 {
+        Fingerprint::common(
+            "bprm_check_security",
+            self.namespace,
+            self.reason,
+            self.message_id,
+            &self.subject,
+        )
+        .finish()
+    }
+}
 
 impl From<alerts::BprmCheckSecurity> for BprmCheckSecurity {
     fn from(alert: alerts::BprmCheckSecurity) -> Self {
         Self {
+            seq: 0,
             pid: alert.pid,
+            namespace: alert.namespace,
+            session: alert.session,
+            reason: alert.reason.into(),
+            message_id: alert.message_id,
+            channel: alert.channel,
             subject: PolicySubject::Binary(PathBuf::from(alert.binprm_inode.to_string())),
         }
     }
@@ -28,35 +513,166 @@ impl From<alerts::BprmCheckSecurity> for BprmCheckSecurity {
 
 #[derive(Debug, Serialize)]
 pub struct FileOpen {
+    pub seq: u64,
     pub pid: u32,
+    pub namespace: u32,
+    pub session: u64,
+    pub reason: Reason,
+    pub message_id: u16,
+    pub channel: u8,
     pub subject: PolicySubject,
     pub path: PathBuf,
 }
 
-impl Alert for FileOpen {}
+impl Alert for FileOpen {
+    fn set_seq(&mut self, seq: u64) {
+        self.seq = seq;
+    }
+
+    fn message_id(&self) -> u16 {
+        self.message_id
+    }
+
+    fn channel(&self) -> u8 {
+        self.channel
+    }
+
+    fn event_fingerprint(&self) -> u64 {
+        Fingerprint::common(
+            "file_open",
+            self.namespace,
+            self.reason,
+            self.message_id,
+            &self.subject,
+        )
+        .path(&self.path)
+        .finish()
+    }
+}
 
 impl From<alerts::FileOpen> for FileOpen {
     fn from(alert: alerts::FileOpen) -> Self {
         Self {
+            seq: 0,
             pid: alert.pid,
+            namespace: alert.namespace,
+            session: alert.session,
+            reason: alert.reason.into(),
+            message_id: alert.message_id,
+            channel: alert.channel,
             subject: PolicySubject::Binary(PathBuf::from(alert.binprm_inode.to_string())),
             path: PathBuf::from(alert.inode.to_string()),
         }
     }
 }
 
+#[derive(Debug, Serialize)]
+pub struct InodeCreate {
+    pub seq: u64,
+    pub pid: u32,
+    pub namespace: u32,
+    pub session: u64,
+    pub reason: Reason,
+    pub message_id: u16,
+    pub channel: u8,
+    pub subject: PolicySubject,
+    /// Directory in which the file was to be created.
+    pub dir: PathBuf,
+    /// Mode (type and permission bits) requested for the file.
+    pub mode: u16,
+}
+
+impl Alert for InodeCreate {
+    fn set_seq(&mut self, seq: u64) {
+        self.seq = seq;
+    }
+
+    fn message_id(&self) -> u16 {
+        self.message_id
+    }
+
+    fn channel(&self) -> u8 {
+        self.channel
+    }
+
+    fn event_fingerprint(&self) -> u64 {
+        Fingerprint::common(
+            "inode_create",
+            self.namespace,
+            self.reason,
+            self.message_id,
+            &self.subject,
+        )
+        .path(&self.dir)
+        .u64(self.mode.into())
+        .finish()
+    }
+}
+
+impl From<alerts::InodeCreate> for InodeCreate {
+    fn from(alert: alerts::InodeCreate) -> Self {
+        Self {
+            seq: 0,
+            pid: alert.pid,
+            namespace: alert.namespace,
+            session: alert.session,
+            reason: alert.reason.into(),
+            message_id: alert.message_id,
+            channel: alert.channel,
+            subject: PolicySubject::Binary(PathBuf::from(alert.binprm_inode.to_string())),
+            dir: PathBuf::from(alert.dir_inode.to_string()),
+            mode: alert.mode,
+        }
+    }
+}
+
 #[derive(Debug, Serialize)]
 pub struct SbMount {
+    pub seq: u64,
     pub pid: u32,
+    pub namespace: u32,
+    pub session: u64,
+    pub reason: Reason,
+    pub message_id: u16,
+    pub channel: u8,
     pub subject: PolicySubject,
 }
 
-impl Alert for SbMount {}
+impl Alert for SbMount {
+    fn set_seq(&mut self, seq: u64) {
+        self.seq = seq;
+    }
+
+    fn message_id(&self) -> u16 {
+        self.message_id
+    }
+
+    fn channel(&self) -> u8 {
+        self.channel
+    }
+
+    fn event_fingerprint(&self) -> u64 {
+        Fingerprint::common(
+            "sb_mount",
+            self.namespace,
+            self.reason,
+            self.message_id,
+            &self.subject,
+        )
+        .finish()
+    }
+}
 
 impl From<alerts::SbMount> for SbMount {
     fn from(alert: alerts::SbMount) -> Self {
         Self {
+            seq: 0,
             pid: alert.pid,
+            namespace: alert.namespace,
+            session: alert.session,
+            reason: alert.reason.into(),
+            message_id: alert.message_id,
+            channel: alert.channel,
             subject: PolicySubject::Binary(PathBuf::from(alert.binprm_inode.to_string())),
         }
     }
@@ -64,16 +680,51 @@ impl From<alerts::SbMount> for SbMount {
 
 #[derive(Debug, Serialize)]
 pub struct SbRemount {
+    pub seq: u64,
     pub pid: u32,
+    pub namespace: u32,
+    pub session: u64,
+    pub reason: Reason,
+    pub message_id: u16,
+    pub channel: u8,
     pub subject: PolicySubject,
 }
 
-impl Alert for SbRemount {}
+impl Alert for SbRemount {
+    fn set_seq(&mut self, seq: u64) {
+        self.seq = seq;
+    }
+
+    fn message_id(&self) -> u16 {
+        self.message_id
+    }
+
+    fn channel(&self) -> u8 {
+        self.channel
+    }
+
+    fn event_fingerprint(&self) -> u64 {
+        Fingerprint::common(
+            "sb_remount",
+            self.namespace,
+            self.reason,
+            self.message_id,
+            &self.subject,
+        )
+        .finish()
+    }
+}
 
 impl From<alerts::SbRemount> for SbRemount {
     fn from(alert: alerts::SbRemount) -> Self {
         Self {
+            seq: 0,
             pid: alert.pid,
+            namespace: alert.namespace,
+            session: alert.session,
+            reason: alert.reason.into(),
+            message_id: alert.message_id,
+            channel: alert.channel,
             subject: PolicySubject::Binary(PathBuf::from(alert.binprm_inode.to_string())),
         }
     }
@@ -81,16 +732,51 @@ impl From<alerts::SbRemount> for SbRemount {
 
 #[derive(Debug, Serialize)]
 pub struct SbUmount {
+    pub seq: u64,
     pub pid: u32,
+    pub namespace: u32,
+    pub session: u64,
+    pub reason: Reason,
+    pub message_id: u16,
+    pub channel: u8,
     pub subject: PolicySubject,
 }
 
-impl Alert for SbUmount {}
+impl Alert for SbUmount {
+    fn set_seq(&mut self, seq: u64) {
+        self.seq = seq;
+    }
+
+    fn message_id(&self) -> u16 {
+        self.message_id
+    }
+
+    fn channel(&self) -> u8 {
+        self.channel
+    }
+
+    fn event_fingerprint(&self) -> u64 {
+        Fingerprint::common(
+            "sb_umount",
+            self.namespace,
+            self.reason,
+            self.message_id,
+            &self.subject,
+        )
+        .finish()
+    }
+}
 
 impl From<alerts::SbUmount> for SbUmount {
     fn from(alert: alerts::SbUmount) -> Self {
         Self {
+            seq: 0,
             pid: alert.pid,
+            namespace: alert.namespace,
+            session: alert.session,
+            reason: alert.reason.into(),
+            message_id: alert.message_id,
+            channel: alert.channel,
             subject: PolicySubject::Binary(PathBuf::from(alert.binprm_inode.to_string())),
         }
     }
@@ -98,18 +784,181 @@ impl From<alerts::SbUmount> for SbUmount {
 
 #[derive(Debug, Serialize)]
 pub struct SocketBind {
+    pub seq: u64,
     pub pid: u32,
+    pub namespace: u32,
+    pub session: u64,
+    pub reason: Reason,
+    pub message_id: u16,
+    pub channel: u8,
     pub subject: PolicySubject,
+    pub family: u16,
     pub port: u16,
+    /// Number of distinct ports the process has bound, for
+    /// [`Reason::BindLimit`] (0 otherwise).
+    pub count: u32,
 }
 
-impl Alert for SocketBind {}
+impl Alert for SocketBind {
+    fn set_seq(&mut self, seq: u64) {
+        self.seq = seq;
+    }
+
+    fn message_id(&self) -> u16 {
+        self.message_id
+    }
+
+    fn channel(&self) -> u8 {
+        self.channel
+    }
+
+    fn event_fingerprint(&self) -> u64 {
+        Fingerprint::common(
+            "socket_bind",
+            self.namespace,
+            self.reason,
+            self.message_id,
+            &self.subject,
+        )
+        .u64(self.family.into())
+        .u64(self.port.into())
+        .finish()
+    }
+}
 
 impl From<alerts::SocketBind> for SocketBind {
     fn from(alert: alerts::SocketBind) -> Self {
         Self {
+            seq: 0,
+            pid: alert.pid,
+            namespace: alert.namespace,
+            session: alert.session,
+            reason: alert.reason.into(),
+            message_id: alert.message_id,
+            channel: alert.channel,
+            subject: PolicySubject::Binary(PathBuf::from(alert.binprm_inode.to_string())),
+            family: alert.family,
+            port: alert.port,
+            count: alert.count,
+        }
+    }
+}
+
+/// Socket bind which the policy maps couldn't decide, escalated to user space
+/// for a verdict.
+#[derive(Debug, Serialize)]
+pub struct SocketBindEscalation {
+    pub seq: u64,
+    pub pid: u32,
+    pub namespace: u32,
+    pub session: u64,
+    pub reason: Reason,
+    pub message_id: u16,
+    pub channel: u8,
+    pub subject: PolicySubject,
+    pub binprm_inode: u64,
+    pub family: u16,
+    pub port: u16,
+}
+
+impl Alert for SocketBindEscalation {
+    fn set_seq(&mut self, seq: u64) {
+        self.seq = seq;
+    }
+
+    fn message_id(&self) -> u16 {
+        self.message_id
+    }
+
+    fn channel(&self) -> u8 {
+        self.channel
+    }
+
+    fn event_fingerprint(&self) -> u64 {
+        Fingerprint::common(
+            "socket_bind_escalation",
+            self.namespace,
+            self.reason,
+            self.message_id,
+            &self.subject,
+        )
+        .u64(self.family.into())
+        .u64(self.port.into())
+        .finish()
+    }
+}
+
+impl From<alerts::SocketBind> for SocketBindEscalation {
+    fn from(alert: alerts::SocketBind) -> Self {
+        Self {
+            seq: 0,
+            pid: alert.pid,
+            namespace: alert.namespace,
+            session: alert.session,
+            reason: alert.reason.into(),
+            message_id: alert.message_id,
+            channel: alert.channel,
+            subject: PolicySubject::Binary(PathBuf::from(alert.binprm_inode.to_string())),
+            binprm_inode: alert.binprm_inode,
+            family: alert.family,
+            port: alert.port,
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct SocketListen {
+    pub seq: u64,
+    pub pid: u32,
+    pub namespace: u32,
+    pub session: u64,
+    pub reason: Reason,
+    pub message_id: u16,
+    pub channel: u8,
+    pub subject: PolicySubject,
+    pub family: u16,
+    pub port: u16,
+}
+
+impl Alert for SocketListen {
+    fn set_seq(&mut self, seq: u64) {
+        self.seq = seq;
+    }
+
+    fn message_id(&self) -> u16 {
+        self.message_id
+    }
+
+    fn channel(&self) -> u8 {
+        self.channel
+    }
+
+    fn event_fingerprint(&self) -> u64 {
+        Fingerprint::common(
+            "socket_listen",
+            self.namespace,
+            self.reason,
+            self.message_id,
+            &self.subject,
+        )
+        .u64(self.family.into())
+        .u64(self.port.into())
+        .finish()
+    }
+}
+
+impl From<alerts::SocketListen> for SocketListen {
+    fn from(alert: alerts::SocketListen) -> Self {
+        Self {
+            seq: 0,
             pid: alert.pid,
+            namespace: alert.namespace,
+            session: alert.session,
+            reason: alert.reason.into(),
+            message_id: alert.message_id,
+            channel: alert.channel,
             subject: PolicySubject::Binary(PathBuf::from(alert.binprm_inode.to_string())),
+            family: alert.family,
             port: alert.port,
         }
     }
@@ -117,12 +966,47 @@ impl From<alerts::SocketBind> for SocketBind {
 
 #[derive(Debug, Serialize)]
 pub struct SocketConnect {
+    pub seq: u64,
     pub pid: u32,
+    pub namespace: u32,
+    pub session: u64,
+    pub reason: Reason,
+    pub message_id: u16,
+    pub channel: u8,
     pub subject: PolicySubject,
     pub addr: IpAddr,
+    pub port: u16,
+    /// Number of connects to the destination in the current window of its
+    /// rate limit, set in alerts of [`Reason::RateLimit`] (0 otherwise).
+    pub rate: u32,
 }
 
-impl Alert for SocketConnect {}
+impl Alert for SocketConnect {
+    fn set_seq(&mut self, seq: u64) {
+        self.seq = seq;
+    }
+
+    fn message_id(&self) -> u16 {
+        self.message_id
+    }
+
+    fn channel(&self) -> u8 {
+        self.channel
+    }
+
+    fn event_fingerprint(&self) -> u64 {
+        Fingerprint::common(
+            "socket_connect",
+            self.namespace,
+            self.reason,
+            self.message_id,
+            &self.subject,
+        )
+        .addr(&self.addr)
+        .u64(self.port.into())
+        .finish()
+    }
+}
 
 impl From<alerts::SocketConnect> for SocketConnect {
     fn from(alert: alerts::SocketConnect) -> Self {
@@ -132,34 +1016,414 @@ impl From<alerts::SocketConnect> for SocketConnect {
             IpAddr::V6(Ipv6Addr::from(alert.addr_v6))
         };
         Self {
+            seq: 0,
             pid: alert.pid,
+            namespace: alert.namespace,
+            session: alert.session,
+            reason: alert.reason.into(),
+            message_id: alert.message_id,
+            channel: alert.channel,
             subject: PolicySubject::Binary(PathBuf::from(alert.binprm_inode.to_string())),
             addr,
+            port: alert.port,
+            rate: alert.rate,
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct SocketCreate {
+    pub seq: u64,
+    pub pid: u32,
+    pub namespace: u32,
+    pub session: u64,
+    pub reason: Reason,
+    pub message_id: u16,
+    pub channel: u8,
+    pub subject: PolicySubject,
+    pub family: u16,
+    pub socket_type: u16,
+    pub protocol: u16,
+}
+
+impl Alert for SocketCreate {
+    fn set_seq(&mut self, seq: u64) {
+        self.seq = seq;
+    }
+
+    fn message_id(&self) -> u16 {
+        self.message_id
+    }
+
+    fn channel(&self) -> u8 {
+        self.channel
+    }
+
+    fn event_fingerprint(&self) -> u64 {
+        Fingerprint::common(
+            "socket_create",
+            self.namespace,
+            self.reason,
+            self.message_id,
+            &self.subject,
+        )
+        .u64(self.family.into())
+        .u64(self.socket_type.into())
+        .u64(self.protocol.into())
+        .finish()
+    }
+}
+
+impl From<alerts::SocketCreate> for SocketCreate {
+    fn from(alert: alerts::SocketCreate) -> Self {
+        Self {
+            seq: 0,
+            pid: alert.pid,
+            namespace: alert.namespace,
+            session: alert.session,
+            reason: alert.reason.into(),
+            message_id: alert.message_id,
+            channel: alert.channel,
+            subject: PolicySubject::Binary(PathBuf::from(alert.binprm_inode.to_string())),
+            family: alert.family,
+            socket_type: alert.socket_type,
+            protocol: alert.protocol,
         }
     }
 }
 
 #[derive(Debug, Serialize)]
 pub struct TaskFixSetuid {
+    pub seq: u64,
     pub pid: u32,
+    pub namespace: u32,
+    pub session: u64,
+    pub reason: Reason,
+    pub message_id: u16,
+    pub channel: u8,
     pub subject: PolicySubject,
     pub old_uid: u32,
     pub old_gid: u32,
     pub new_uid: u32,
     pub new_gid: u32,
+    /// PID of the parent of the process.
+    pub ppid: u32,
+    /// Command name of the process (of its thread, truncated by the kernel
+    /// to 15 bytes).
+    pub comm: String,
 }
 
-impl Alert for TaskFixSetuid {}
+impl Alert for TaskFixSetuid {
+    fn set_seq(&mut self, seq: u64) {
+        self.seq = seq;
+    }
+
+    fn message_id(&self) -> u16 {
+        self.message_id
+    }
+
+    fn channel(&self) -> u8 {
+        self.channel
+    }
+
+    fn event_fingerprint(&self) -> u64 {
+        Fingerprint::common(
+            "task_fix_setuid",
+            self.namespace,
+            self.reason,
+            self.message_id,
+            &self.subject,
+        )
+        .u64(self.old_uid.into())
+        .u64(self.old_gid.into())
+        .u64(self.new_uid.into())
+        .u64(self.new_gid.into())
+        .bytes(self.comm.as_bytes())
+        .finish()
+    }
+}
 
 impl From<alerts::TaskFixSetuid> for TaskFixSetuid {
     fn from(alert: alerts::TaskFixSetuid) -> Self {
         Self {
+            seq: 0,
             pid: alert.pid,
+            namespace: alert.namespace,
+            session: alert.session,
+            reason: alert.reason.into(),
+            message_id: alert.message_id,
+            channel: alert.channel,
             subject: PolicySubject::Binary(PathBuf::from(alert.binprm_inode.to_string())),
             old_uid: alert.old_uid,
             old_gid: alert.old_gid,
             new_uid: alert.new_uid,
             new_gid: alert.new_gid,
+            ppid: alert.ppid,
+            comm: comm(&alert.comm),
+        }
+    }
+}
+
+/// Converts the NUL-terminated command name of an alert to a string.
+fn comm(comm: &[u8]) -> String {
+    let len = comm.iter().position(|&b| b == 0).unwrap_or(comm.len());
+    String::from_utf8_lossy(&comm[..len]).into_owned()
+}
+
+#[derive(Debug, Serialize)]
+pub struct TaskFixSetgid {
+    pub seq: u64,
+    pub pid: u32,
+    pub namespace: u32,
+    pub session: u64,
+    pub reason: Reason,
+    pub message_id: u16,
+    pub channel: u8,
+    pub subject: PolicySubject,
+    pub old_gid: u32,
+    pub old_egid: u32,
+    pub new_gid: u32,
+    pub new_egid: u32,
+}
+
+impl Alert for TaskFixSetgid {
+    fn set_seq(&mut self, seq: u64) {
+        self.seq = seq;
+    }
+
+    fn message_id(&self) -> u16 {
+        self.message_id
+    }
+
+    fn channel(&self) -> u8 {
+        self.channel
+    }
+
+    fn event_fingerprint(&self) -> u64 {
+        Fingerprint::common(
+            "task_fix_setgid",
+            self.namespace,
+            self.reason,
+            self.message_id,
+            &self.subject,
+        )
+        .u64(self.old_gid.into())
+        .u64(self.old_egid.into())
+        .u64(self.new_gid.into())
+        .u64(self.new_egid.into())
+        .finish()
+    }
+}
+
+impl From<alerts::TaskFixSetgid> for TaskFixSetgid {
+    fn from(alert: alerts::TaskFixSetgid) -> Self {
+        Self {
+            seq: 0,
+            pid: alert.pid,
+            namespace: alert.namespace,
+            session: alert.session,
+            reason: alert.reason.into(),
+            message_id: alert.message_id,
+            channel: alert.channel,
+            subject: PolicySubject::Binary(PathBuf::from(alert.binprm_inode.to_string())),
+            old_gid: alert.old_gid,
+            old_egid: alert.old_egid,
+            new_gid: alert.new_gid,
+            new_egid: alert.new_egid,
+        }
+    }
+}
+
+/// Heartbeat emitted periodically by
+/// [`PolicyManager::heartbeat`](crate::PolicyManager::heartbeat), so
+/// consumers can tell a quiet period from a dead policy manager.
+#[derive(Debug, Clone, Serialize)]
+pub struct Heartbeat {
+    pub seq: u64,
+    pub namespace: u32,
+    /// Time since the policy manager was created.
+    pub uptime: Duration,
+    /// Hooks handed out by the policy manager before the heartbeat started.
+    pub hooks: Vec<HookStatus>,
+}
+
+impl Alert for Heartbeat {
+    fn set_seq(&mut self, seq: u64) {
+        self.seq = seq;
+    }
+
+    fn message_id(&self) -> u16 {
+        MESSAGE_NONE
+    }
+
+    fn channel(&self) -> u8 {
+        CHANNEL_DEFAULT
+    }
+
+    fn event_fingerprint(&self) -> u64 {
+        let mut fingerprint = Fingerprint::new("heartbeat").u64(self.namespace.into());
+        for hook in &self.hooks {
+            fingerprint = fingerprint
+                .bytes(hook.name.as_bytes())
+                .u64(hook.attached.into());
+        }
+        fingerprint.finish()
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct HookStatus {
+    pub name: &'static str,
+    /// Whether the program of the hook is attached, as in
+    /// [`HookHealth::attached`](crate::health::HookHealth::attached).
+    pub attached: bool,
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_reason_from_code() {
+        let reasons: Vec<Reason> = (1..=16).map(Reason::from).collect();
+        for (i, reason) in reasons.iter().enumerate() {
+            assert!(!matches!(reason, Reason::Unknown(_)), "{reason:?}");
+            assert_eq!(Reason::from(reason.code()), *reason);
+            for other in &reasons[i + 1..] {
+                assert_ne!(reason, other);
+                assert_ne!(reason.to_string(), other.to_string());
+            }
+        }
+        assert_eq!(Reason::from(0), Reason::Unknown(0));
+        assert_eq!(
+            Reason::from(alerts::REASON_DEFAULT_DENY).to_string(),
+            "denied by default"
+        );
+    }
+
+    fn socket_bind(pid: u32, port: u16) -> SocketBind {
+        SocketBind {
+            seq: u64::from(pid),
+            pid,
+            namespace: 1,
+            session: u64::from(pid) << 32,
+            reason: Reason::BindLimit,
+            message_id: 3,
+            channel: pid as u8,
+            subject: PolicySubject::Binary(PathBuf::from("/usr/bin/nc")),
+            family: 2,
+            port,
+            count: pid,
+        }
+    }
+
+    #[test]
+    fn test_event_fingerprint() {
+        // Same event on two hosts, by processes with different PIDs,
+        // sessions, channels and bind counts.
+        let alert = socket_bind(100, 8080);
+        assert_eq!(
+            alert.event_fingerprint(),
+            socket_bind(200, 8080).event_fingerprint()
+        );
+
+        assert_ne!(
+            alert.event_fingerprint(),
+            socket_bind(100, 8081).event_fingerprint()
+        );
+        let mut other = socket_bind(100, 8080);
+        other.namespace = 2;
+        assert_ne!(alert.event_fingerprint(), other.event_fingerprint());
+        let mut other = socket_bind(100, 8080);
+        other.subject = PolicySubject::All;
+        assert_ne!(alert.event_fingerprint(), other.event_fingerprint());
+
+        // Alerts of different hooks with the same fields.
+        let listen = SocketListen {
+            seq: alert.seq,
+            pid: alert.pid,
+            namespace: alert.namespace,
+            session: alert.session,
+            reason: alert.reason,
+            message_id: alert.message_id,
+            channel: alert.channel,
+            subject: PolicySubject::Binary(PathBuf::from("/usr/bin/nc")),
+            family: alert.family,
+            port: alert.port,
+        };
+        assert_ne!(alert.event_fingerprint(), listen.event_fingerprint());
+
+        // Fingerprints are stable, so they can be compared with ones
+        // computed by other versions.
+        let heartbeat = Heartbeat {
+            seq: 1,
+            namespace: 1,
+            uptime: Duration::from_secs(60),
+            hooks: vec![],
+        };
+        assert_eq!(heartbeat.event_fingerprint(), 0x8f44_7bd3_cf04_bd65);
+    }
+
+    #[test]
+    fn test_comm() {
+        assert_eq!(comm(b"sudo\0\0\0\0"), "sudo");
+        assert_eq!(comm(b"exactly16bytes!!"), "exactly16bytes!!");
+        assert_eq!(comm(b"\0"), "");
+    }
+
+    #[test]
+    fn test_alert_buffers() {
+        AlertBuffers::default().check().unwrap();
+        AlertBuffers {
+            pages: 1,
+            record_size: MAX_ALERT_SIZE,
+        }
+        .check()
+        .unwrap();
+
+        let err = AlertBuffers {
+            pages: 2,
+            record_size: MAX_ALERT_SIZE - 1,
+        }
+        .check()
+        .unwrap_err();
+        assert!(matches!(
+            err,
+            EbpfguardError::AlertBuffersTooSmall {
+                size: MAX_ALERT_SIZE,
+                budget,
+            } if budget == MAX_ALERT_SIZE - 1
+        ));
+
+        for pages in [0, 3] {
+            let buffers = AlertBuffers {
+                pages,
+                ..Default::default()
+            };
+            assert!(matches!(
+                buffers.check(),
+                Err(EbpfguardError::InvalidAlertPages(_))
+            ));
         }
     }
+
+    #[test]
+    fn test_gap_detector() {
+        let mut gaps = GapDetector::new();
+        for seq in 1..=3 {
+            assert_eq!(gaps.observe(seq), None);
+        }
+        assert_eq!(gaps.observe(7), Some(Gap::Missed { from: 4, count: 3 }));
+        assert_eq!(gaps.observe(8), None);
+        assert_eq!(gaps.cursor(), 8);
+
+        // Consumer restarted with a stored cursor, stream continues.
+        let mut gaps = GapDetector::resume(8);
+        assert_eq!(gaps.observe(9), None);
+
+        // Stream restarted, e.g. after an agent restart.
+        let mut gaps = GapDetector::resume(9);
+        assert_eq!(gaps.observe(1), Some(Gap::Restarted));
+        assert_eq!(gaps.observe(2), None);
+    }
 }
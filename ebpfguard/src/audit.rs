@@ -0,0 +1,230 @@
+//! Audit events of policy changes.
+//!
+//! Every successful call of a method changing what the eBPF programs enforce
+//! emits one [`AuditEvent`], with the key it changed (e.g. the subject of a
+//! policy) and the values of the key before and after the change. These are:
+//!
+//! * the `add_*policy`, `swap_policies`, `set_*`, `escalate`,
+//!   `grant_temporary_allow`, `revoke_temporary_allow` and `exempt_binary`
+//!   methods of the hooks, and the expiry of temporary allows,
+//! * `assign_cgroup`, `set_message_id`, `set_alert_window`,
+//!   `set_retry_escalation`, `set_alert_channel`, `apply_plan`,
+//!   `apply_plan_atomic`, `clear_hook` and `upgrade` of
+//!   [`PolicyManager`](crate::PolicyManager).
+//!
+//! Failed calls emit no event, even if they changed some of the maps before
+//! failing. Events are received with
+//! [`PolicyManager::audit_events`](crate::PolicyManager::audit_events), and
+//! can be forwarded to a sink of their own, separate from the alerts of the
+//! hooks:
+//!
+//! ```no_run
+//! use ebpfguard::{
+//!     sink::{SinkConfig, UnixSocketSink},
+//!     PolicyManager,
+//! };
+//!
+//! # #[tokio::main(flavor = "current_thread")]
+//! # async fn main() {
+//! let mut policy_manager = PolicyManager::with_default_path().unwrap();
+//! let audit = UnixSocketSink::new(SinkConfig::new("/run/audit.sock"));
+//! audit.forward(policy_manager.audit_events());
+//! # }
+//! ```
+//!
+//! Events carry a `seq` field, increasing by one with every event of the
+//! policy manager and its hooks, starting from 1. Events which don't fit into
+//! the buffer of the receiver take their sequence numbers too, so a consumer
+//! can detect missed (or removed) events with a
+//! [`GapDetector`](crate::alerts::GapDetector). The limits are:
+//!
+//! * Events are emitted by the process changing the policies through this
+//!   library, which the `pid` and `uid` fields identify. The pinned maps can
+//!   be changed by other processes with `CAP_BPF` without any event.
+//! * Old values are the ones listed by the hook (e.g. with `list_policies`),
+//!   read only while a receiver exists. Changes made by `apply_plan` and
+//!   `apply_plan_atomic` are reported as the plan, without old values, and
+//!   entries removed by `clear_hook` as the old value.
+
+use std::{
+    fs,
+    os::unix::fs::MetadataExt,
+    sync::{Arc, Mutex},
+    time::SystemTime,
+};
+
+use serde::Serialize;
+use serde_yaml::Value;
+use tokio::sync::mpsc::{self, error::TrySendError, Receiver, Sender};
+
+use crate::messages::Hook;
+
+/// Number of events buffered for the receiver of
+/// [`PolicyManager::audit_events`](crate::PolicyManager::audit_events).
+pub const AUDIT_BUFFER: usize = 1024;
+
+/// Change of a policy.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct AuditEvent {
+    pub seq: u64,
+    pub time: SystemTime,
+    /// PID of the process which made the change.
+    pub pid: u32,
+    /// Effective UID of the process which made the change, if it could be
+    /// read.
+    pub uid: Option<u32>,
+    pub namespace: u32,
+    /// Hook whose policies changed, `None` for changes of the policy manager
+    /// affecting all hooks.
+    pub hook: Option<Hook>,
+    /// Name of the method which made the change, e.g. `add_policy`.
+    pub operation: &'static str,
+    pub key: Value,
+    /// Value of the key before the change, `None` if there was none.
+    pub old: Option<Value>,
+    /// Value of the key after the change, `None` if it was removed or the
+    /// change has no value (e.g. a new CIDR database).
+    pub new: Option<Value>,
+}
+
+/// Emitter of audit events, shared by the policy manager and its hooks.
+#[derive(Clone, Default)]
+pub(crate) struct AuditLog {
+    state: Arc<Mutex<AuditState>>,
+    namespace: u32,
+    hook: Option<Hook>,
+}
+
+#[derive(Default)]
+struct AuditState {
+    seq: u64,
+    tx: Option<Sender<AuditEvent>>,
+}
+
+impl AuditLog {
+    /// Returns an emitter of events of the hook in the namespace, sharing
+    /// the receiver and the sequence with this one.
+    pub(crate) fn scoped(&self, namespace: u32, hook: Option<Hook>) -> Self {
+        Self {
+            state: self.state.clone(),
+            namespace,
+            hook,
+        }
+    }
+
+    /// Returns a new receiver of events, replacing the previous one.
+    pub(crate) fn subscribe(&self) -> Receiver<AuditEvent> {
+        let (tx, rx) = mpsc::channel(AUDIT_BUFFER);
+        self.state.lock().unwrap().tx = Some(tx);
+        rx
+    }
+
+    /// Whether events are received, i.e. old values have to be read.
+    pub(crate) fn enabled(&self) -> bool {
+        match &self.state.lock().unwrap().tx {
+            Some(tx) => !tx.is_closed(),
+            None => false,
+        }
+    }
+
+    /// Emits an event of the change, if events are received.
+    pub(crate) fn record(
+        &self,
+        operation: &'static str,
+        key: Value,
+        old: Option<Value>,
+        new: Option<Value>,
+    ) {
+        let mut state = self.state.lock().unwrap();
+        let tx = match &state.tx {
+            Some(tx) if !tx.is_closed() => tx.clone(),
+            _ => return,
+        };
+        state.seq += 1;
+        let event = AuditEvent {
+            seq: state.seq,
+            time: SystemTime::now(),
+            pid: std::process::id(),
+            // /proc/self is owned by the effective UID of the process.
+            uid: fs::metadata("/proc/self").map(|m| m.uid()).ok(),
+            namespace: self.namespace,
+            hook: self.hook,
+            operation,
+            key,
+            old,
+            new,
+        };
+        // The sequence number stays taken, so the dropped event shows up as
+        // a gap.
+        if let Err(TrySendError::Closed(_)) = tx.try_send(event) {
+            state.tx = None;
+        }
+    }
+}
+
+/// Converts the key or value of a change to the value of an event.
+pub(crate) fn value<T: Serialize>(value: &T) -> Value {
+    serde_yaml::to_value(value).unwrap_or(Value::Null)
+}
+
+/// Returns the listed policies matching `key` as the old value of a change,
+/// or `None` if there are none.
+pub(crate) fn previous<T, F>(policies: Vec<T>, key: F) -> Option<Value>
+where
+    T: Serialize,
+    F: Fn(&T) -> bool,
+{
+    let policies = policies.into_iter().filter(key).collect::<Vec<_>>();
+    (!policies.is_empty()).then(|| value(&policies))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_record() {
+        let audit = AuditLog::default();
+        // Nothing is emitted, and no sequence number taken, without a
+        // receiver.
+        audit.record("set_exempt_ports", Value::Null, None, None);
+        assert!(!audit.enabled());
+
+        let mut rx = audit.subscribe();
+        assert!(audit.enabled());
+        let hook = audit.scoped(3, Some(Hook::SocketBind));
+        hook.record(
+            "set_bind_limit",
+            value(&"all"),
+            Some(value(&4)),
+            Some(value(&8)),
+        );
+        audit.record("assign_cgroup", value(&"/sys/fs/cgroup/a"), None, None);
+
+        let event = rx.try_recv().unwrap();
+        assert_eq!(event.seq, 1);
+        assert_eq!(event.pid, std::process::id());
+        assert_eq!(event.namespace, 3);
+        assert_eq!(event.hook, Some(Hook::SocketBind));
+        assert_eq!(event.operation, "set_bind_limit");
+        assert_eq!(event.old, Some(Value::from(4)));
+        assert_eq!(event.new, Some(Value::from(8)));
+        let event = rx.try_recv().unwrap();
+        assert_eq!(event.seq, 2);
+        assert_eq!(event.hook, None);
+        assert!(rx.try_recv().is_err());
+
+        drop(rx);
+        assert!(!audit.enabled());
+    }
+
+    #[test]
+    fn test_previous() {
+        let policies = vec![("a", 1), ("b", 2), ("a", 3)];
+        assert_eq!(
+            previous(policies.clone(), |(key, _)| *key == "a"),
+            Some(value(&vec![("a", 1), ("a", 3)]))
+        );
+        assert_eq!(previous(policies, |(key, _)| *key == "c"), None);
+    }
+}
@@ -0,0 +1,76 @@
+//! Settings of a policy namespace which apply to all its binaries, kept in
+//! one entry of the `CONFIG` map (see
+//! [`Config`](ebpfguard_common::policy::Config) for the layout).
+//!
+//! Hooks read and change their own fields through their typed methods, e.g.
+//! [`SocketBind::set_exempt_ports`](crate::hooks::socket_bind::SocketBind::set_exempt_ports)
+//! and
+//! [`SocketConnect::set_key_layout`](crate::hooks::socket_connect::SocketConnect::set_key_layout).
+//! Every change reads the entry again and writes it back whole, so changes
+//! of different fields made by the hooks of this process don't overwrite
+//! each other. Like the other settings of a namespace, they must not be
+//! changed by multiple policy managers at once.
+
+use std::{path::Path, sync::Mutex};
+
+use aya::maps::{HashMap, Map, MapData, MapError};
+use ebpfguard_common::policy::Config;
+
+use crate::error::EbpfguardError;
+
+/// Serializes the changes of the entries made by this process.
+static UPDATES: Mutex<()> = Mutex::new(());
+
+/// Handle of the `CONFIG` entry of a namespace.
+pub(crate) struct ConfigMap {
+    map: HashMap<MapData, u32, Config>,
+    namespace: u32,
+}
+
+impl ConfigMap {
+    /// Opens the pinned map. It's shared by the hooks, so each of them opens
+    /// a handle of its own.
+    pub(crate) fn open(maps_path: &Path, namespace: u32) -> Result<Self, EbpfguardError> {
+        let name = "CONFIG";
+        let data = MapData::from_pin(maps_path.join(name))
+            .map_err(|e| EbpfguardError::from_map_error(name, e))?;
+        let map = HashMap::try_from(Map::HashMap(data))
+            .map_err(|e| EbpfguardError::from_map_error(name, e))?;
+
+        Ok(Self { map, namespace })
+    }
+
+    /// Returns the settings of the namespace, the default ones if it has no
+    /// entry.
+    pub(crate) fn get(&self) -> Result<Config, EbpfguardError> {
+        match self.map.get(&self.namespace, 0) {
+            Ok(config) => Ok(config),
+            Err(MapError::KeyNotFound) => Ok(Config::default()),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    /// Changes the settings of the namespace with `change` and returns the
+    /// previous ones. The entry is removed when the settings are back to the
+    /// default ones.
+    pub(crate) fn update<F>(&mut self, change: F) -> Result<Config, EbpfguardError>
+    where
+        F: FnOnce(&mut Config),
+    {
+        let _guard = UPDATES.lock().unwrap();
+        let old = self.get()?;
+        let mut config = old;
+        change(&mut config);
+
+        if config == Config::default() {
+            match self.map.remove(&self.namespace) {
+                Ok(()) | Err(MapError::KeyNotFound) => {}
+                Err(e) => return Err(e.into()),
+            }
+        } else if config != old {
+            self.map.insert(self.namespace, config, 0)?;
+        }
+
+        Ok(old)
+    }
+}
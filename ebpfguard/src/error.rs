@@ -1,7 +1,14 @@
+use aya::maps::MapError;
 use thiserror::Error;
 
 #[derive(Debug, Error)]
 pub enum EbpfguardError {
+    #[error("Alert buffers fit records of up to {budget} bytes, the largest alert has {size}")]
+    AlertBuffersTooSmall { size: usize, budget: usize },
+
+    #[error("Alert rate-limit window too long (max {0:?})")]
+    AlertWindowTooLong(std::time::Duration),
+
     #[error(
         "BPF LSM module is not enabled. Check prerequisites doc for instructions how to enable it."
     )]
@@ -16,15 +23,189 @@ pub enum EbpfguardError {
     #[error("Failed to load BPF program: {0}")]
     BpfProgramError(#[from] aya::programs::ProgramError),
 
+    #[error("Heartbeat interval must not be zero")]
+    HeartbeatIntervalZero,
+
     #[error("I/O error: {0}")]
     IO(#[from] std::io::Error),
 
+    #[error("Invalid number of alert buffer pages {0}, has to be a power of two")]
+    InvalidAlertPages(usize),
+
+    #[error("Invalid CIDR `{0}`")]
+    InvalidCidr(String),
+
+    #[error("Invalid CIDR database entry at line {0}")]
+    InvalidCidrDatabase(usize),
+
+    #[error("Invalid command name pattern `{0}`")]
+    InvalidCommPattern(String),
+
+    #[error("Invalid port range `{0}`, ports have to be 1 to 65535 and the start at most the end")]
+    InvalidPortRange(String),
+
+    #[error(
+        "Invalid connect rate limit of {connects} connects per {window:?}, both have to be non-zero and the window at most a day"
+    )]
+    InvalidRateLimit {
+        connects: u32,
+        window: std::time::Duration,
+    },
+
+    #[error(
+        "Invalid retry escalation after {denials} denials per {window:?}, both have to be non-zero and the window at most a day"
+    )]
+    InvalidRetryEscalation {
+        denials: u32,
+        window: std::time::Duration,
+    },
+
+    #[error("Invalid entry of map `{0}` in a snapshot")]
+    InvalidSnapshotEntry(String),
+
+    #[error("Key layout can't change while protected addresses are set in the namespace")]
+    KeyLayoutInUse,
+
+    #[error("Port of the policy doesn't match the key layout (port in keys: {0})")]
+    KeyLayoutMismatch(bool),
+
+    #[error("Failed to use a BPF link: {0}")]
+    Link(#[from] aya::programs::links::LinkError),
+
+    #[error("Links are not pinned, set a pin path first")]
+    LinksNotPinned,
+
     #[error("Map error: {0}")]
-    Map(#[from] aya::maps::MapError),
+    Map(#[from] MapError),
+
+    #[error("Map `{0}` not found in the eBPF object")]
+    MapNotFound(String),
+
+    #[error(
+        "Map `{map}` {part} size mismatch: eBPF object defines {expected} bytes, user space type has {size} bytes"
+    )]
+    MapSizeMismatch {
+        map: String,
+        part: &'static str,
+        size: usize,
+        expected: usize,
+    },
+
+    #[error("No CIDR database set to resolve ASN and country selectors")]
+    NoCidrDatabase,
+
+    #[error("Failed to pin a BPF link: {0}")]
+    Pin(#[from] aya::pin::PinError),
+
+    #[error("Map `{0}` changed since the plan was computed")]
+    PlanOutdated(String),
 
     #[error("Failed to open a perf buffer: {0}")]
     PerfBuffer(#[from] aya::maps::perf::PerfBufferError),
 
+    #[error("Map `{0}` can't be used through a raw handle without breaking the eBPF programs")]
+    RawMapRefused(String),
+
+    #[error("Namespace {0} is reserved, namespaces have to be below 2^31")]
+    ReservedNamespace(u32),
+
+    #[error("Failed to apply a plan ({0}) and to roll it back ({1}), the policy maps are partially changed")]
+    RollbackFailed(Box<EbpfguardError>, Box<EbpfguardError>),
+
+    #[error("Too many binaries allowed to access a protected resource (max {0})")]
+    TooManyBinaries(usize),
+
+    #[error("Too many socket kinds in a socket_create policy (max {0})")]
+    TooManySocketKinds(usize),
+
+    #[error("Too many port ranges in a socket_connect_ports policy (max {0})")]
+    TooManyPortRanges(usize),
+
+    #[error("Too many CIDRs denied in socket_connect_geo policies (max {0})")]
+    TooManyCidrs(usize),
+
+    #[error("Unsupported policy file version {version} (supported versions: {min} to {max})")]
+    UnsupportedPolicyVersion { version: u32, min: u32, max: u32 },
+
+    #[error("A verdict callback is already registered")]
+    VerdictCallbackRegistered,
+
+    #[error("Binary {0:?} has the inode reserved for the policies of all binaries")]
+    WildcardInode(std::path::PathBuf),
+
     #[error("Failed to parse policies from YAML: {0}")]
     YAML(#[from] serde_yaml::Error),
 }
+
+impl EbpfguardError {
+    /// Converts an error returned by a map conversion into an error carrying
+    /// the name of the map. Key and value size mismatches indicate an ABI skew
+    /// between `ebpfguard-common` types used in user space and the ones the
+    /// eBPF object was built with.
+    pub(crate) fn from_map_error(map: &str, error: MapError) -> Self {
+        match error {
+            MapError::InvalidKeySize { size, expected } => Self::MapSizeMismatch {
+                map: map.to_owned(),
+                part: "key",
+                size,
+                expected,
+            },
+            MapError::InvalidValueSize { size, expected } => Self::MapSizeMismatch {
+                map: map.to_owned(),
+                part: "value",
+                size,
+                expected,
+            },
+            error => Self::Map(error),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_map_key_size_mismatch() {
+        let error = EbpfguardError::from_map_error(
+            "ALLOWED_SOCKET_BIND",
+            MapError::InvalidKeySize {
+                size: 4,
+                expected: 8,
+            },
+        );
+        assert!(matches!(
+            &error,
+            EbpfguardError::MapSizeMismatch {
+                map,
+                part: "key",
+                size: 4,
+                expected: 8,
+            } if map == "ALLOWED_SOCKET_BIND"
+        ));
+        assert_eq!(
+            error.to_string(),
+            "Map `ALLOWED_SOCKET_BIND` key size mismatch: eBPF object defines 8 bytes, user space type has 4 bytes"
+        );
+    }
+
+    #[test]
+    fn test_map_value_size_mismatch() {
+        let error = EbpfguardError::from_map_error(
+            "DENIED_SOCKET_BIND",
+            MapError::InvalidValueSize {
+                size: 10,
+                expected: 8,
+            },
+        );
+        assert!(matches!(
+            &error,
+            EbpfguardError::MapSizeMismatch {
+                map,
+                part: "value",
+                size: 10,
+                expected: 8,
+            } if map == "DENIED_SOCKET_BIND"
+        ));
+    }
+}
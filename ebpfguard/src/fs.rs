@@ -1,8 +1,155 @@
-use std::{fs, os::unix::fs::MetadataExt, path::Path};
+use std::{fs, io, ops::RangeInclusive, os::unix::fs::MetadataExt, path::Path};
 
-pub fn inode<P: AsRef<Path>>(path: P) -> Result<u64, std::io::Error> {
+use ebpfguard_common::{consts::INODE_WILDCARD, policy::DevInodeKey};
+
+use crate::error::EbpfguardError;
+
+/// Resolver of paths (of binaries, files and directories in policies) to the
+/// inodes the eBPF programs see, set with
+/// [`PolicyManager::set_inode_resolver`](crate::PolicyManager::set_inode_resolver)
+/// for setups where `stat` on the path doesn't give them, e.g. binaries
+/// inside a chroot or on a network filesystem mounted differently.
+///
+/// A resolver returns the inode number (`i_ino`) of the file and the device
+/// of its filesystem in the kernel's encoding (`super_block::s_dev`, 12 bits
+/// of major and 20 bits of minor number, not the encoding of `stat`), as a
+/// [`DevInodeKey`]. Most maps are keyed by the inode number alone, which the
+/// policy manager combines with the namespace (see
+/// `ebpfguard_common::policy::InodeKey`), so files of different devices with
+/// the same number share their policies there. The maps shared by all
+/// namespaces (e.g. `EXEMPT_BPF`) are keyed by both.
+pub trait InodeResolver: Send + Sync {
+    fn resolve(&self, path: &Path) -> Result<DevInodeKey, io::Error>;
+}
+
+/// Default resolver, the inode number and device of the path as `stat`
+/// returns them, following symlinks.
+pub struct LocalInodeResolver;
+
+impl InodeResolver for LocalInodeResolver {
+    fn resolve(&self, path: &Path) -> Result<DevInodeKey, io::Error> {
+        let metadata = fs::metadata(path)?;
+        Ok(DevInodeKey::new(metadata.ino(), kernel_dev(metadata.dev())))
+    }
+}
+
+/// Returns the inode and device of a binary which is the subject of a
+/// policy, refusing binaries whose inode is [`INODE_WILDCARD`]. User space
+/// resolves the wildcard subject to that inode, and the keys without a
+/// wildcard tag (e.g. the CIDR keys) can't tell such a binary apart from all
+/// binaries.
+pub fn binary_key<P: AsRef<Path>>(
+    resolver: &dyn InodeResolver,
+    path: P,
+) -> Result<DevInodeKey, EbpfguardError> {
     let path = path.as_ref();
-    let metadata = fs::metadata(path)?;
-    let inode = metadata.ino();
-    Ok(inode)
+    let key = resolver.resolve(path)?;
+    if key.inode == INODE_WILDCARD {
+        return Err(EbpfguardError::WildcardInode(path.to_owned()));
+    }
+    Ok(key)
+}
+
+/// Returns the inode of a binary which is the subject of a policy, see
+/// [`binary_key`].
+pub fn binary_inode<P: AsRef<Path>>(
+    resolver: &dyn InodeResolver,
+    path: P,
+) -> Result<u64, EbpfguardError> {
+    Ok(binary_key(resolver, path)?.inode)
+}
+
+/// Converts a device number of `stat` (glibc's encoding) into the kernel's
+/// (`MKDEV`, 12 bits of major and 20 bits of minor number).
+fn kernel_dev(dev: u64) -> u32 {
+    let major = ((dev >> 8) & 0xfff) | ((dev >> 32) & !0xfff);
+    let minor = (dev & 0xff) | ((dev >> 12) & !0xff);
+    ((major << 20) | (minor & 0xfffff)) as u32
+}
+
+/// Returns the range of ephemeral ports used by the system for binds to port
+/// 0 and outgoing connections (`net.ipv4.ip_local_port_range`).
+pub fn ephemeral_port_range() -> Result<RangeInclusive<u16>, io::Error> {
+    parse_port_range(&fs::read_to_string(
+        "/proc/sys/net/ipv4/ip_local_port_range",
+    )?)
+}
+
+fn parse_port_range(range: &str) -> Result<RangeInclusive<u16>, io::Error> {
+    let invalid = || io::Error::new(io::ErrorKind::InvalidData, "invalid port range");
+
+    let mut ports = range.split_whitespace().map(|port| port.parse::<u16>());
+    match (ports.next(), ports.next(), ports.next()) {
+        (Some(Ok(start)), Some(Ok(end)), None) => Ok(start..=end),
+        _ => Err(invalid()),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::path::PathBuf;
+
+    use super::*;
+    use crate::policy::{inode::InodeSubjectMap, Paths, PolicySubject};
+
+    /// Resolves made-up paths, and the others like the default resolver.
+    struct FakeResolver;
+
+    impl InodeResolver for FakeResolver {
+        fn resolve(&self, path: &Path) -> Result<DevInodeKey, io::Error> {
+            match path.to_str() {
+                Some("/fake/bin/agent") => Ok(DevInodeKey::new(4242, (8 << 20) | 1)),
+                Some("/fake/bin/wildcard") => Ok(DevInodeKey::new(INODE_WILDCARD, 0)),
+                _ => LocalInodeResolver.resolve(path),
+            }
+        }
+    }
+
+    #[test]
+    fn test_inode_resolver() {
+        assert!(binary_key(&LocalInodeResolver, "/fake/bin/agent").is_err());
+
+        let resolver = &FakeResolver;
+        assert_eq!(
+            binary_key(resolver, "/fake/bin/agent").unwrap(),
+            DevInodeKey::new(4242, (8 << 20) | 1)
+        );
+        assert_eq!(binary_inode(resolver, "/fake/bin/agent").unwrap(), 4242);
+        assert!(matches!(
+            binary_inode(resolver, "/fake/bin/wildcard"),
+            Err(EbpfguardError::WildcardInode(_))
+        ));
+        let mut subjects = InodeSubjectMap::default();
+        let agent = PathBuf::from("/fake/bin/agent");
+        let subject = PolicySubject::Binary(agent.clone());
+        assert_eq!(subjects.resolve_path(resolver, subject).unwrap(), 4242);
+        assert_eq!(subjects.resolve_binary(4242), agent);
+        let paths = Paths::Paths(vec![agent]).resolve(resolver).unwrap();
+        assert_eq!(paths.paths[0], 4242);
+
+        let metadata = fs::metadata("/").unwrap();
+        assert_eq!(
+            resolver.resolve(Path::new("/")).unwrap(),
+            DevInodeKey::new(metadata.ino(), kernel_dev(metadata.dev())),
+            "other paths should resolve like with stat"
+        );
+    }
+
+    #[test]
+    fn test_kernel_dev() {
+        // sda1 (8:1)
+        assert_eq!(kernel_dev(0x801), (8 << 20) | 1);
+        // nvme0n1p1 (259:1)
+        assert_eq!(kernel_dev(0x10301), (259 << 20) | 1);
+        // Minor numbers above 255, e.g. of anonymous devices (0:300).
+        assert_eq!(kernel_dev(0x10002c), 300);
+    }
+
+    #[test]
+    fn test_parse_port_range() {
+        assert_eq!(parse_port_range("32768\t60999\n").unwrap(), 32768..=60999);
+        assert!(parse_port_range("32768").is_err());
+        assert!(parse_port_range("32768 60999 1").is_err());
+        assert!(parse_port_range("32768 65536").is_err());
+    }
 }
@@ -0,0 +1,87 @@
+//! Generations of the policies of the hooks in a policy namespace, kept in
+//! the `POLICY_GENERATIONS` map (keyed by the wildcard key of the namespace
+//! and the hook).
+//!
+//! The policy maps hold up to two generations of the policies of each hook,
+//! told apart by [`GENERATION_BIT`] in the namespace of their keys. The
+//! programs only read the generation set for the hook, 0 without an entry,
+//! so the hooks build the keys they write and filter the keys they list with
+//! [`Generation::policy_namespace`]. Atomic plans write the other generation
+//! and then switch the hook to it, see
+//! [`PolicyManager::apply_plan_atomic`](crate::PolicyManager::apply_plan_atomic).
+
+use std::path::Path;
+
+use aya::maps::{HashMap, Map, MapData, MapError};
+use ebpfguard_common::policy::{generation_namespace, HookKey, InodeKey, GENERATION_BIT};
+
+use crate::error::EbpfguardError;
+
+/// Handle of the `POLICY_GENERATIONS` entry of a hook in a namespace.
+pub(crate) struct Generation {
+    map: HashMap<MapData, HookKey, u32>,
+    key: HookKey,
+    namespace: u32,
+}
+
+impl Generation {
+    /// Opens the pinned map. It's shared by the hooks, so each of them opens
+    /// a handle of its own. Fails for namespaces with [`GENERATION_BIT`] set,
+    /// whose keys would be the ones of another generation.
+    pub(crate) fn open(
+        maps_path: &Path,
+        namespace: u32,
+        hook: u32,
+    ) -> Result<Self, EbpfguardError> {
+        if namespace & GENERATION_BIT != 0 {
+            return Err(EbpfguardError::ReservedNamespace(namespace));
+        }
+        let name = "POLICY_GENERATIONS";
+        let data = MapData::from_pin(maps_path.join(name))
+            .map_err(|e| EbpfguardError::from_map_error(name, e))?;
+        let map = HashMap::try_from(Map::HashMap(data))
+            .map_err(|e| EbpfguardError::from_map_error(name, e))?;
+
+        Ok(Self {
+            map,
+            key: HookKey::new(InodeKey::wildcard(namespace), hook),
+            namespace,
+        })
+    }
+
+    /// Returns the generation of the hook's policies which the programs
+    /// read, 0 until the first atomic plan changing them.
+    pub(crate) fn get(&self) -> Result<u32, EbpfguardError> {
+        match self.map.get(&self.key, 0) {
+            Ok(generation) => Ok(generation & 1),
+            Err(MapError::KeyNotFound) => Ok(0),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    /// Returns the namespace of the keys of the hook's policy maps which the
+    /// programs read.
+    pub(crate) fn policy_namespace(&self) -> Result<u32, EbpfguardError> {
+        Ok(generation_namespace(self.namespace, self.get()?))
+    }
+
+    /// Returns the namespace of the keys of the generation of the hook's
+    /// policies which the programs don't read.
+    pub(crate) fn staged_namespace(&self) -> Result<u32, EbpfguardError> {
+        Ok(generation_namespace(self.namespace, 1 - self.get()?))
+    }
+
+    /// Switches the programs to the other generation of the hook's policies
+    /// with a single map update. The entry is removed when the generation is
+    /// back to 0.
+    pub(crate) fn flip(&mut self) -> Result<(), EbpfguardError> {
+        match self.get()? {
+            0 => self.map.insert(self.key, 1, 0)?,
+            _ => match self.map.remove(&self.key) {
+                Ok(()) | Err(MapError::KeyNotFound) => {}
+                Err(e) => return Err(e.into()),
+            },
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,260 @@
+//! Health of the policy manager, for liveness and readiness probes (see
+//! [`PolicyManager::health`](crate::PolicyManager::health)).
+
+use std::sync::{
+    atomic::{AtomicU64, AtomicUsize, Ordering},
+    Arc, Weak,
+};
+
+use crate::alerts::AlertBuffers;
+
+/// Health state, ordered from the best to the worst.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum State {
+    /// Everything works as intended.
+    Healthy,
+    /// Policies are enforced, but alerts are being lost or maps are close to
+    /// full, so new policies might not fit.
+    Degraded,
+    /// Policies are not enforced or alerts are not read: a program which was
+    /// attached is detached, an alert reader stopped or a map is missing.
+    Failed,
+}
+
+/// Utilization above which a map is degraded.
+pub const MAP_UTILIZATION_DEGRADED: f64 = 0.9;
+
+#[derive(Debug, Clone)]
+pub struct Health {
+    /// The worst state of the hooks and maps.
+    pub state: State,
+    /// Hooks managed by the policy manager.
+    pub hooks: Vec<HookHealth>,
+    /// Policy maps.
+    pub maps: Vec<MapHealth>,
+}
+
+impl Health {
+    pub(crate) fn new(hooks: Vec<HookHealth>, maps: Vec<MapHealth>) -> Self {
+        let state = hooks
+            .iter()
+            .map(|hook| hook.state)
+            .chain(maps.iter().map(|map| map.state))
+            .max()
+            .unwrap_or(State::Healthy);
+        Self { state, hooks, maps }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct HookHealth {
+    pub name: &'static str,
+    /// Whether the policy manager attached the program of the hook, as
+    /// opposed to only managing its policies.
+    pub intended: bool,
+    /// Whether the program is attached, by a link held by the hook or pinned.
+    pub attached: bool,
+    /// Number of alert readers (one per CPU for each alert channel) started
+    /// and still running.
+    pub alert_readers: usize,
+    /// Number of alert readers which stopped, e.g. because the receiver of
+    /// the alerts was dropped.
+    pub stopped_alert_readers: usize,
+    /// Number of alerts lost since the hook is managed.
+    pub lost_events: u64,
+    /// Alerts lost per second since the previous health check.
+    pub lost_rate: f64,
+    pub state: State,
+}
+
+impl HookHealth {
+    pub(crate) fn new(
+        name: &'static str,
+        intended: bool,
+        attached: bool,
+        stats: &AlertStats,
+    ) -> Self {
+        let started = stats.started.load(Ordering::Relaxed);
+        let running = stats.running.load(Ordering::Relaxed);
+        Self {
+            name,
+            intended,
+            attached,
+            alert_readers: running,
+            stopped_alert_readers: started.saturating_sub(running),
+            lost_events: stats.lost.load(Ordering::Relaxed),
+            lost_rate: 0.0,
+            state: State::Healthy,
+        }
+    }
+
+    /// Sets the rate of alerts lost since the previous health check, `lost`
+    /// of them in `secs` seconds, and evaluates the state.
+    pub(crate) fn with_lost(mut self, lost: u64, secs: f64) -> Self {
+        if secs > 0.0 {
+            self.lost_rate = lost as f64 / secs;
+        }
+        self.state = if (self.intended && !self.attached) || self.stopped_alert_readers > 0 {
+            State::Failed
+        } else if lost > 0 {
+            State::Degraded
+        } else {
+            State::Healthy
+        };
+        self
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct MapHealth {
+    pub name: &'static str,
+    /// Number of entries, `None` if the map couldn't be read.
+    pub entries: Option<usize>,
+    pub max_entries: usize,
+    pub state: State,
+}
+
+impl MapHealth {
+    pub(crate) fn new(name: &'static str, entries: Option<usize>, max_entries: usize) -> Self {
+        let mut map = Self {
+            name,
+            entries,
+            max_entries,
+            state: State::Failed,
+        };
+        map.state = match map.utilization() {
+            None => State::Failed,
+            Some(utilization) if utilization >= MAP_UTILIZATION_DEGRADED => State::Degraded,
+            Some(_) => State::Healthy,
+        };
+        map
+    }
+
+    /// Returns the ratio of used entries, from 0 to 1.
+    pub fn utilization(&self) -> Option<f64> {
+        self.entries
+            .map(|entries| entries as f64 / self.max_entries as f64)
+    }
+}
+
+/// Statistics of the alert readers of a hook, shared by the readers and the
+/// policy manager.
+#[derive(Debug, Default)]
+pub(crate) struct AlertStats {
+    started: AtomicUsize,
+    running: AtomicUsize,
+    lost: AtomicU64,
+}
+
+impl AlertStats {
+    /// Registers a started reader, which is considered running until the
+    /// returned guard is dropped.
+    pub(crate) fn reader(self: &Arc<Self>) -> ReaderGuard {
+        self.started.fetch_add(1, Ordering::Relaxed);
+        self.running.fetch_add(1, Ordering::Relaxed);
+        ReaderGuard(self.clone())
+    }
+
+    pub(crate) fn add_lost(&self, lost: u64) {
+        self.lost.fetch_add(lost, Ordering::Relaxed);
+    }
+
+    pub(crate) fn lost(&self) -> u64 {
+        self.lost.load(Ordering::Relaxed)
+    }
+}
+
+/// Marks an alert reader as stopped when dropped, which also happens when
+/// the reader task panics.
+pub(crate) struct ReaderGuard(Arc<AlertStats>);
+
+impl Drop for ReaderGuard {
+    fn drop(&mut self) {
+        self.0.running.fetch_sub(1, Ordering::Relaxed);
+    }
+}
+
+/// Part of a hook shared with the policy manager. The policy manager tells
+/// whether the hook (and so the link of its program) is still alive by a
+/// weak reference to it.
+#[derive(Debug, Default)]
+pub(crate) struct HookMonitor {
+    alive: Arc<()>,
+    pub(crate) alerts: Arc<AlertStats>,
+    /// Buffers the alerts of the hook are read through.
+    pub(crate) buffers: AlertBuffers,
+}
+
+impl HookMonitor {
+    pub(crate) fn alive(&self) -> Weak<()> {
+        Arc::downgrade(&self.alive)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_hook_state() {
+        let stats = Arc::new(AlertStats::default());
+
+        let hook = HookHealth::new("socket_bind", true, true, &stats).with_lost(0, 1.0);
+        assert_eq!(hook.state, State::Healthy);
+
+        // Managed only, the program is not expected to be attached.
+        let hook = HookHealth::new("socket_bind", false, false, &stats).with_lost(0, 1.0);
+        assert_eq!(hook.state, State::Healthy);
+
+        let hook = HookHealth::new("socket_bind", true, false, &stats).with_lost(0, 1.0);
+        assert_eq!(hook.state, State::Failed);
+
+        let reader = stats.reader();
+        stats.add_lost(10);
+        let hook = HookHealth::new("socket_bind", true, true, &stats).with_lost(10, 2.0);
+        assert_eq!(hook.state, State::Degraded);
+        assert_eq!(hook.alert_readers, 1);
+        assert_eq!(hook.lost_events, 10);
+        assert_eq!(hook.lost_rate, 5.0);
+
+        drop(reader);
+        let hook = HookHealth::new("socket_bind", true, true, &stats).with_lost(0, 1.0);
+        assert_eq!(hook.state, State::Failed);
+        assert_eq!(hook.alert_readers, 0);
+        assert_eq!(hook.stopped_alert_readers, 1);
+    }
+
+    #[test]
+    fn test_map_state() {
+        assert_eq!(
+            MapHealth::new("ALLOWED_SOCKET_BIND", Some(10), 1024).state,
+            State::Healthy
+        );
+        assert_eq!(
+            MapHealth::new("ALLOWED_SOCKET_BIND", Some(1000), 1024).state,
+            State::Degraded
+        );
+        assert_eq!(
+            MapHealth::new("ALLOWED_SOCKET_BIND", None, 1024).state,
+            State::Failed
+        );
+    }
+
+    #[test]
+    fn test_health_state() {
+        let stats = Arc::new(AlertStats::default());
+        let healthy = HookHealth::new("sb_mount", true, true, &stats).with_lost(0, 1.0);
+        let detached = HookHealth::new("sb_umount", true, false, &stats).with_lost(0, 1.0);
+        let full = MapHealth::new("ALLOWED_SB_MOUNT", Some(1024), 1024);
+
+        assert_eq!(Health::new(vec![], vec![]).state, State::Healthy);
+        assert_eq!(
+            Health::new(vec![healthy.clone()], vec![full.clone()]).state,
+            State::Degraded
+        );
+        assert_eq!(
+            Health::new(vec![healthy, detached], vec![full]).state,
+            State::Failed
+        );
+    }
+}
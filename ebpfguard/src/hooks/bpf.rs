@@ -0,0 +1,174 @@
+use std::{
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+use aya::{
+    maps::{HashMap, MapData},
+    programs::lsm::LsmLink,
+};
+use ebpfguard_common::{
+    alerts as ebpf_alerts,
+    policy::{DevInodeKey, InodeKey},
+};
+use tokio::sync::mpsc::Receiver;
+
+use crate::{
+    alerts,
+    audit::{self, AuditLog},
+    error::EbpfguardError,
+    fs::{self, InodeResolver, LocalInodeResolver},
+    generation::Generation,
+    health::HookMonitor,
+    policy::{self, inode::subject_key},
+};
+
+use super::{perf_array_alerts, AlertArray, INODE_SUBJECT_MAP};
+
+pub struct Bpf {
+    #[allow(dead_code)]
+    pub(crate) program_link: Option<LsmLink>,
+    pub(crate) allowed_map: HashMap<MapData, InodeKey, u8>,
+    pub(crate) denied_map: HashMap<MapData, InodeKey, u8>,
+    pub(crate) exempt_map: HashMap<MapData, DevInodeKey, u8>,
+    pub(crate) audit: AuditLog,
+    pub(crate) monitor: HookMonitor,
+    pub(crate) perf_array: AlertArray,
+    pub(crate) resolver: Arc<dyn InodeResolver>,
+    pub(crate) generation: Generation,
+    pub(crate) namespace: u32,
+}
+
+impl Bpf {
+    pub async fn add_policy(&mut self, policy: policy::Bpf) -> Result<(), EbpfguardError> {
+        let old = if self.audit.enabled() {
+            audit::previous(self.list_policies().await?, |p| p.subject == policy.subject)
+        } else {
+            None
+        };
+        let (audit_key, new) = (audit::value(&policy.subject), audit::value(&policy));
+
+        let bin_inode = {
+            let mut map = INODE_SUBJECT_MAP.lock().await;
+            map.resolve_path(&*self.resolver, policy.subject)?
+        };
+
+        let key = subject_key(self.generation.policy_namespace()?, bin_inode);
+        if policy.allow {
+            self.allowed_map.insert(key, 0, 0)?;
+        } else {
+            self.denied_map.insert(key, 0, 0)?;
+        }
+
+        self.audit.record("add_policy", audit_key, old, Some(new));
+
+        Ok(())
+    }
+
+    pub async fn list_policies(&self) -> Result<Vec<policy::Bpf>, EbpfguardError> {
+        let mut policies = Vec::new();
+        let namespace = self.generation.policy_namespace()?;
+
+        for (entries, allow) in [(&self.allowed_map, true), (&self.denied_map, false)] {
+            for res in entries.iter() {
+                let (key, _) = res?;
+                if key.namespace != namespace {
+                    continue;
+                }
+
+                let subject = {
+                    let map = INODE_SUBJECT_MAP.lock().await;
+                    map.resolve_inode(key.inode)
+                };
+
+                policies.push(policy::Bpf { subject, allow });
+            }
+        }
+
+        Ok(policies)
+    }
+
+    /// Exempts the binary from the `bpf` policies of all namespaces, like
+    /// the binary of the policy manager, which is exempt since the hook was
+    /// first managed. Exempt e.g. the binary of a new version of the policy
+    /// manager before upgrading to it, since a deny-all policy would
+    /// otherwise deny it the pinned maps.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use ebpfguard::PolicyManager;
+    ///
+    /// # #[tokio::main(flavor = "current_thread")]
+    /// # async fn main() {
+    /// let mut policy_manager = PolicyManager::with_default_path().unwrap();
+    /// let mut bpf = policy_manager.attach_bpf().unwrap();
+    /// bpf.exempt_binary("/usr/local/bin/agent.new").await.unwrap();
+    /// # }
+    /// ```
+    pub async fn exempt_binary<P: AsRef<Path>>(&mut self, path: P) -> Result<(), EbpfguardError> {
+        let path = path.as_ref().to_path_buf();
+        let audit_key = audit::value(&path);
+
+        let key = {
+            let mut map = INODE_SUBJECT_MAP.lock().await;
+            map.resolve_binary_key(&*self.resolver, path)?
+        };
+        let old = self
+            .exempt_map
+            .get(&key, 0)
+            .ok()
+            .map(|_| audit::value(&true));
+        self.exempt_map.insert(key, 0, 0)?;
+
+        self.audit
+            .record("exempt_binary", audit_key, old, Some(audit::value(&true)));
+
+        Ok(())
+    }
+
+    /// Exempts the binary of the running process, see
+    /// [`PolicyManager::attach_bpf`](crate::PolicyManager::attach_bpf).
+    pub(crate) fn exempt_current(&mut self) -> Result<(), EbpfguardError> {
+        let key = exe_key(&std::env::current_exe()?)?;
+        self.exempt_map.insert(key, 0, 0)?;
+
+        Ok(())
+    }
+
+    /// Returns the binaries exempt from the `bpf` policies of all
+    /// namespaces.
+    pub async fn exempt_binaries(&self) -> Result<Vec<PathBuf>, EbpfguardError> {
+        let exe = std::env::current_exe()?;
+        let exe_key = exe_key(&exe)?;
+
+        let map = INODE_SUBJECT_MAP.lock().await;
+        let mut binaries = Vec::new();
+        for key in self.exempt_map.keys() {
+            let key = key?;
+            binaries.push(if key == exe_key {
+                exe.clone()
+            } else {
+                map.resolve_binary(key.inode)
+            });
+        }
+
+        Ok(binaries)
+    }
+
+    pub async fn alerts(&mut self) -> Result<Receiver<alerts::Bpf>, EbpfguardError> {
+        perf_array_alerts::<ebpf_alerts::Bpf, alerts::Bpf>(
+            &mut self.perf_array,
+            self.namespace,
+            &self.monitor,
+        )
+        .await
+    }
+}
+
+/// Returns the key of the binary of the running process in `EXEMPT_BPF`.
+/// The kernel executed it from the view of this process, so it's resolved
+/// with `stat` whatever the resolver, like cgroups.
+fn exe_key(path: &Path) -> Result<DevInodeKey, EbpfguardError> {
+    fs::binary_key(&LocalInodeResolver, path)
+}
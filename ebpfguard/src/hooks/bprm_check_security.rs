@@ -1,24 +1,25 @@
-use aya::{
-    maps::{AsyncPerfEventArray, MapData},
-    programs::lsm::LsmLink,
-};
+use aya::programs::lsm::LsmLink;
 use ebpfguard_common::alerts as ebpf_alerts;
 use tokio::sync::mpsc::Receiver;
 
-use crate::{alerts, error::EbpfguardError};
+use crate::{alerts, error::EbpfguardError, health::HookMonitor};
 
-use super::perf_array_alerts;
+use super::{perf_array_alerts, AlertArray};
 
 pub struct BprmCheckSecurity {
     #[allow(dead_code)]
     pub(crate) program_link: Option<LsmLink>,
-    pub(crate) perf_array: AsyncPerfEventArray<MapData>,
+    pub(crate) monitor: HookMonitor,
+    pub(crate) perf_array: AlertArray,
+    pub(crate) namespace: u32,
 }
 
 impl BprmCheckSecurity {
     pub async fn alerts(&mut self) -> Result<Receiver<alerts::BprmCheckSecurity>, EbpfguardError> {
         perf_array_alerts::<ebpf_alerts::BprmCheckSecurity, alerts::BprmCheckSecurity>(
             &mut self.perf_array,
+            self.namespace,
+            &self.monitor,
         )
         .await
     }
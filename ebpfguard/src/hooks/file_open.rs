@@ -1,48 +1,176 @@
+use std::{collections::HashSet, sync::Arc, time::Duration};
+
 use aya::{
-    maps::{AsyncPerfEventArray, HashMap, MapData},
+    maps::{HashMap, MapData, MapError},
     programs::lsm::LsmLink,
 };
-use ebpfguard_common::{alerts as ebpf_alerts, policy as ebpf_policy};
-use tokio::sync::mpsc::Receiver;
+use ebpfguard_common::{
+    alerts as ebpf_alerts,
+    policy::{self as ebpf_policy, FileInodeKey, InodeKey},
+};
+use log::warn;
+use tokio::{
+    sync::{mpsc::Receiver, Mutex},
+    task::{self, JoinHandle},
+};
 
-use crate::{alerts, error::EbpfguardError, policy};
+use crate::{
+    alerts,
+    audit::{self, AuditLog},
+    error::EbpfguardError,
+    fs::InodeResolver,
+    generation::Generation,
+    health::HookMonitor,
+    policy::glob,
+    policy::{self, inode::subject_key},
+};
 
-use super::{perf_array_alerts, INODE_SUBJECT_MAP};
+use super::{binaries_paths, perf_array_alerts, resolve_binaries, AlertArray, INODE_SUBJECT_MAP};
 
 pub struct FileOpen {
     #[allow(dead_code)]
     pub(crate) program_link: Option<LsmLink>,
-    pub(crate) allowed_map: HashMap<MapData, u64, ebpf_policy::Paths>,
-    pub(crate) denied_map: HashMap<MapData, u64, ebpf_policy::Paths>,
-    pub(crate) perf_array: AsyncPerfEventArray<MapData>,
+    pub(crate) allowed_map: HashMap<MapData, InodeKey, ebpf_policy::Paths>,
+    pub(crate) denied_map: HashMap<MapData, InodeKey, ebpf_policy::Paths>,
+    pub(crate) protected_map: HashMap<MapData, InodeKey, ebpf_policy::Binaries>,
+    pub(crate) globs: Arc<Mutex<GlobRules>>,
+    pub(crate) audit: AuditLog,
+    pub(crate) monitor: HookMonitor,
+    pub(crate) perf_array: AlertArray,
+    pub(crate) resolver: Arc<dyn InodeResolver>,
+    pub(crate) generation: Generation,
+    pub(crate) namespace: u32,
+}
+
+/// Glob patterns of `file_open_glob` policies and the inodes they are
+/// currently expanded to.
+pub(crate) struct GlobRules {
+    allowed_map: HashMap<MapData, FileInodeKey, u8>,
+    denied_map: HashMap<MapData, FileInodeKey, u8>,
+    patterns: Vec<GlobPattern>,
+    installed: HashSet<(FileInodeKey, bool)>,
+    generation: Generation,
+    resolver: Arc<dyn InodeResolver>,
+}
+
+struct GlobPattern {
+    /// Key of the subject in the namespace of the policy manager, without
+    /// the generation of the policies.
+    key: InodeKey,
+    pattern: String,
+    allow: bool,
+}
+
+impl GlobRules {
+    pub(crate) fn new(
+        allowed_map: HashMap<MapData, FileInodeKey, u8>,
+        denied_map: HashMap<MapData, FileInodeKey, u8>,
+        generation: Generation,
+        resolver: Arc<dyn InodeResolver>,
+    ) -> Self {
+        Self {
+            allowed_map,
+            denied_map,
+            patterns: Vec::new(),
+            installed: HashSet::new(),
+            generation,
+            resolver,
+        }
+    }
+
+    /// Expands all patterns again, adds the inodes of newly matching files to
+    /// the maps and removes the inodes of files which don't match anymore.
+    ///
+    /// The keys are installed in the generation of the policies which the
+    /// programs read. After an atomic plan switched it, the keys of the
+    /// previous generation are removed (if the plan didn't remove them
+    /// already), and the copies the plan made are kept.
+    fn refresh(&mut self) -> Result<(), EbpfguardError> {
+        let namespace = self.generation.policy_namespace()?;
+        let mut expanded = HashSet::new();
+        for pattern in self.patterns.iter() {
+            for path in glob::expand(&pattern.pattern) {
+                // The file might be gone already.
+                if let Ok(resolved) = self.resolver.resolve(&path) {
+                    let subject = InodeKey {
+                        namespace,
+                        ..pattern.key
+                    };
+                    let key = FileInodeKey::new(subject, resolved.inode);
+                    expanded.insert((key, pattern.allow));
+                }
+            }
+        }
+
+        let added: Vec<_> = expanded.difference(&self.installed).copied().collect();
+        let removed: Vec<_> = self.installed.difference(&expanded).copied().collect();
+
+        for (key, allow) in added {
+            let map = if allow {
+                &mut self.allowed_map
+            } else {
+                &mut self.denied_map
+            };
+            map.insert(key, 0, 0)?;
+            self.installed.insert((key, allow));
+        }
+        for (key, allow) in removed {
+            let map = if allow {
+                &mut self.allowed_map
+            } else {
+                &mut self.denied_map
+            };
+            match map.remove(&key) {
+                Ok(()) | Err(MapError::KeyNotFound) => {}
+                Err(e) => return Err(e.into()),
+            }
+            self.installed.remove(&(key, allow));
+        }
+
+        Ok(())
+    }
 }
 
 impl FileOpen {
     pub async fn add_policy(&mut self, policy: policy::FileOpen) -> Result<(), EbpfguardError> {
+        let old = if self.audit.enabled() {
+            audit::previous(self.list_policies().await?, |p| p.subject == policy.subject)
+        } else {
+            None
+        };
+        let (audit_key, new) = (audit::value(&policy.subject), audit::value(&policy));
+
         let bin_inode = {
             let mut map = INODE_SUBJECT_MAP.lock().await;
-            map.resolve_path(policy.subject)?
+            map.resolve_path(&*self.resolver, policy.subject)?
         };
 
-        let allow: ebpf_policy::Paths = policy.allow.into();
-        let deny: ebpf_policy::Paths = policy.deny.into();
+        let allow = policy.allow.resolve(&*self.resolver)?;
+        let deny = policy.deny.resolve(&*self.resolver)?;
+
+        let key = subject_key(self.generation.policy_namespace()?, bin_inode);
+        self.allowed_map.insert(key, allow, 0)?;
+        self.denied_map.insert(key, deny, 0)?;
 
-        self.allowed_map.insert(bin_inode, allow, 0)?;
-        self.denied_map.insert(bin_inode, deny, 0)?;
+        self.audit.record("add_policy", audit_key, old, Some(new));
 
         Ok(())
     }
 
     pub async fn list_policies(&self) -> Result<Vec<policy::FileOpen>, EbpfguardError> {
         let mut policies = Vec::new();
+        let namespace = self.generation.policy_namespace()?;
 
         for res in self.allowed_map.iter() {
-            let (bin_inode, allow) = res?;
-            let deny = self.denied_map.get(&bin_inode, 0)?;
+            let (key, allow) = res?;
+            if key.namespace != namespace {
+                continue;
+            }
+            let deny = self.denied_map.get(&key, 0)?;
 
             let subject = {
                 let map = INODE_SUBJECT_MAP.lock().await;
-                map.resolve_inode(bin_inode)
+                map.resolve_inode(key.inode)
             };
 
             policies.push(policy::FileOpen {
@@ -55,7 +183,159 @@ impl FileOpen {
         Ok(policies)
     }
 
+    pub async fn add_glob_policy(
+        &mut self,
+        policy: policy::FileOpenGlob,
+    ) -> Result<(), EbpfguardError> {
+        let old = if self.audit.enabled() {
+            audit::previous(self.list_glob_policies().await?, |p| {
+                p.subject == policy.subject
+            })
+        } else {
+            None
+        };
+        let (audit_key, new) = (audit::value(&policy.subject), audit::value(&policy));
+
+        let bin_inode = {
+            let mut map = INODE_SUBJECT_MAP.lock().await;
+            map.resolve_path(&*self.resolver, policy.subject)?
+        };
+        let key = subject_key(self.namespace, bin_inode);
+
+        let mut globs = self.globs.lock().await;
+        for (patterns, allow) in [(policy.allow, true), (policy.deny, false)] {
+            for pattern in patterns {
+                globs.patterns.push(GlobPattern {
+                    key,
+                    pattern,
+                    allow,
+                });
+            }
+        }
+        globs.refresh()?;
+
+        self.audit
+            .record("add_glob_policy", audit_key, old, Some(new));
+
+        Ok(())
+    }
+
+    pub async fn list_glob_policies(&self) -> Result<Vec<policy::FileOpenGlob>, EbpfguardError> {
+        let mut policies: Vec<(InodeKey, policy::FileOpenGlob)> = Vec::new();
+
+        let globs = self.globs.lock().await;
+        for pattern in globs.patterns.iter() {
+            let i = match policies.iter().position(|(key, _)| *key == pattern.key) {
+                Some(i) => i,
+                None => {
+                    let subject = {
+                        let map = INODE_SUBJECT_MAP.lock().await;
+                        map.resolve_inode(pattern.key.inode)
+                    };
+                    policies.push((
+                        pattern.key,
+                        policy::FileOpenGlob {
+                            subject,
+                            allow: Vec::new(),
+                            deny: Vec::new(),
+                        },
+                    ));
+                    policies.len() - 1
+                }
+            };
+            let policy = &mut policies[i].1;
+            if pattern.allow {
+                policy.allow.push(pattern.pattern.clone());
+            } else {
+                policy.deny.push(pattern.pattern.clone());
+            }
+        }
+
+        Ok(policies.into_iter().map(|(_, policy)| policy).collect())
+    }
+
+    /// Starts a background task expanding the glob patterns again every
+    /// `period`, so the policies cover files created after the patterns were
+    /// added and stop covering the deleted ones.
+    ///
+    /// Files created between two refreshes are not covered by the patterns
+    /// until the next refresh (unless a matching parent directory is), so
+    /// `period` bounds the window in which they can be opened despite a deny
+    /// pattern. Abort the returned handle to stop the watcher.
+    pub fn watch_globs(&self, period: Duration) -> JoinHandle<()> {
+        let globs = self.globs.clone();
+
+        task::spawn(async move {
+            let mut interval = tokio::time::interval(period);
+            loop {
+                interval.tick().await;
+                if let Err(e) = globs.lock().await.refresh() {
+                    warn!("failed to refresh file_open glob patterns: {e}");
+                }
+            }
+        })
+    }
+
+    pub async fn add_protected_policy(
+        &mut self,
+        policy: policy::FileOpenProtected,
+    ) -> Result<(), EbpfguardError> {
+        let inode = {
+            let mut map = INODE_SUBJECT_MAP.lock().await;
+            map.resolve_file(&*self.resolver, policy.path.clone())?
+        };
+        let old = if self.audit.enabled() {
+            audit::previous(self.list_protected_policies().await?, |p| {
+                p.path == policy.path
+            })
+        } else {
+            None
+        };
+        let (audit_key, new) = (audit::value(&policy.path), audit::value(&policy));
+        let binaries = resolve_binaries(&*self.resolver, policy.allow).await?;
+
+        let namespace = self.generation.policy_namespace()?;
+        self.protected_map
+            .insert(InodeKey::new(namespace, inode), binaries, 0)?;
+
+        self.audit
+            .record("add_protected_policy", audit_key, old, Some(new));
+
+        Ok(())
+    }
+
+    pub async fn list_protected_policies(
+        &self,
+    ) -> Result<Vec<policy::FileOpenProtected>, EbpfguardError> {
+        let mut policies = Vec::new();
+        let namespace = self.generation.policy_namespace()?;
+
+        for res in self.protected_map.iter() {
+            let (key, binaries) = res?;
+            if key.namespace != namespace {
+                continue;
+            }
+
+            let path = {
+                let map = INODE_SUBJECT_MAP.lock().await;
+                map.resolve_binary(key.inode)
+            };
+
+            policies.push(policy::FileOpenProtected {
+                path,
+                allow: binaries_paths(&binaries).await,
+            });
+        }
+
+        Ok(policies)
+    }
+
     pub async fn alerts(&mut self) -> Result<Receiver<alerts::FileOpen>, EbpfguardError> {
-        perf_array_alerts::<ebpf_alerts::FileOpen, alerts::FileOpen>(&mut self.perf_array).await
+        perf_array_alerts::<ebpf_alerts::FileOpen, alerts::FileOpen>(
+            &mut self.perf_array,
+            self.namespace,
+            &self.monitor,
+        )
+        .await
     }
 }
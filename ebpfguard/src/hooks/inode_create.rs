@@ -0,0 +1,129 @@
+use std::sync::Arc;
+
+use aya::{
+    maps::{HashMap, MapData, MapError},
+    programs::lsm::LsmLink,
+};
+use ebpfguard_common::{
+    alerts as ebpf_alerts,
+    policy::{self as ebpf_policy, InodeKey},
+};
+use tokio::sync::mpsc::Receiver;
+
+use crate::{
+    alerts,
+    audit::{self, AuditLog},
+    error::EbpfguardError,
+    fs::InodeResolver,
+    generation::Generation,
+    health::HookMonitor,
+    policy::{self, inode::subject_key},
+};
+
+use super::{perf_array_alerts, AlertArray, INODE_SUBJECT_MAP};
+
+pub struct InodeCreate {
+    #[allow(dead_code)]
+    pub(crate) program_link: Option<LsmLink>,
+    pub(crate) allowed_map: HashMap<MapData, InodeKey, ebpf_policy::Paths>,
+    pub(crate) denied_map: HashMap<MapData, InodeKey, ebpf_policy::Paths>,
+    pub(crate) audit: AuditLog,
+    pub(crate) monitor: HookMonitor,
+    pub(crate) perf_array: AlertArray,
+    pub(crate) resolver: Arc<dyn InodeResolver>,
+    pub(crate) generation: Generation,
+    pub(crate) namespace: u32,
+}
+
+impl InodeCreate {
+    pub async fn add_policy(&mut self, policy: policy::InodeCreate) -> Result<(), EbpfguardError> {
+        let old = if self.audit.enabled() {
+            audit::previous(self.list_policies().await?, |p| p.subject == policy.subject)
+        } else {
+            None
+        };
+        let (audit_key, new) = (audit::value(&policy.subject), audit::value(&policy));
+
+        let bin_inode = {
+            let mut map = INODE_SUBJECT_MAP.lock().await;
+            map.resolve_path(&*self.resolver, policy.subject)?
+        };
+
+        let key = subject_key(self.generation.policy_namespace()?, bin_inode);
+        set_paths(&*self.resolver, &mut self.allowed_map, key, policy.allow)?;
+        set_paths(&*self.resolver, &mut self.denied_map, key, policy.deny)?;
+
+        self.audit.record("add_policy", audit_key, old, Some(new));
+
+        Ok(())
+    }
+
+    pub async fn list_policies(&self) -> Result<Vec<policy::InodeCreate>, EbpfguardError> {
+        let mut policies = Vec::new();
+
+        let namespace = self.generation.policy_namespace()?;
+        let mut keys = Vec::new();
+        for res in self.allowed_map.keys().chain(self.denied_map.keys()) {
+            let key = res?;
+            if key.namespace == namespace && !keys.contains(&key) {
+                keys.push(key);
+            }
+        }
+
+        for key in keys {
+            let subject = {
+                let map = INODE_SUBJECT_MAP.lock().await;
+                map.resolve_inode(key.inode)
+            };
+
+            policies.push(policy::InodeCreate {
+                subject,
+                allow: get_paths(&self.allowed_map, &key)?,
+                deny: get_paths(&self.denied_map, &key)?,
+            });
+        }
+
+        Ok(policies)
+    }
+
+    pub async fn alerts(&mut self) -> Result<Receiver<alerts::InodeCreate>, EbpfguardError> {
+        perf_array_alerts::<ebpf_alerts::InodeCreate, alerts::InodeCreate>(
+            &mut self.perf_array,
+            self.namespace,
+            &self.monitor,
+        )
+        .await
+    }
+}
+
+/// Stores the paths of the subject. An empty list of paths is stored as a
+/// missing entry, since the eBPF program reads an empty array as all paths.
+fn set_paths(
+    resolver: &dyn InodeResolver,
+    map: &mut HashMap<MapData, InodeKey, ebpf_policy::Paths>,
+    key: InodeKey,
+    paths: policy::Paths,
+) -> Result<(), EbpfguardError> {
+    match paths {
+        policy::Paths::Paths(paths) if paths.is_empty() => match map.remove(&key) {
+            Ok(()) | Err(MapError::KeyNotFound) => {}
+            Err(e) => return Err(e.into()),
+        },
+        paths => {
+            map.insert(key, paths.resolve(resolver)?, 0)?;
+        }
+    }
+
+    Ok(())
+}
+
+fn get_paths(
+    map: &HashMap<MapData, InodeKey, ebpf_policy::Paths>,
+    key: &InodeKey,
+) -> Result<policy::Paths, EbpfguardError> {
+    match map.get(key, 0) {
+        Ok(paths) => Ok(paths.into()),
+        Err(MapError::KeyNotFound) => Ok(policy::Paths::Paths(Vec::new())),
+        Err(e) => Err(e.into()),
+    }
+}
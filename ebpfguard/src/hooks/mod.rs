@@ -1,11 +1,17 @@
-use std::fmt::Debug;
+use std::{
+    any::Any,
+    collections::HashMap as StdHashMap,
+    fmt::Debug,
+    path::PathBuf,
+    sync::{Arc, Mutex as StdMutex},
+};
 
 use aya::{
     maps::{AsyncPerfEventArray, MapData},
     util::online_cpus,
 };
 use bytes::BytesMut;
-use ebpfguard_common::alerts as ebpf_alerts;
+use ebpfguard_common::{alerts as ebpf_alerts, policy as ebpf_policy};
 use once_cell::sync::Lazy;
 use tokio::{
     sync::{
@@ -15,47 +21,91 @@ use tokio::{
     task,
 };
 
-use crate::{alerts, error::EbpfguardError, policy, InodeSubjectMap};
+use crate::{
+    alerts, error::EbpfguardError, fs::InodeResolver, health::HookMonitor, policy, InodeSubjectMap,
+};
 
+pub mod bpf;
 pub mod bprm_check_security;
 pub mod file_open;
+pub mod inode_create;
 pub mod sb_mount;
 pub mod sb_remount;
 pub mod sb_umount;
 pub mod socket_bind;
 pub mod socket_connect;
+pub mod socket_create;
+pub mod socket_listen;
+pub mod task_fix_setgid;
 pub mod task_fix_setuid;
 
+use bpf::Bpf;
 use bprm_check_security::BprmCheckSecurity;
 use file_open::FileOpen;
+use inode_create::InodeCreate;
 use sb_mount::SbMount;
 use socket_bind::SocketBind;
 use socket_connect::SocketConnect;
+use socket_create::SocketCreate;
+use socket_listen::SocketListen;
+use task_fix_setgid::TaskFixSetgid;
 use task_fix_setuid::TaskFixSetuid;
 
 static INODE_SUBJECT_MAP: Lazy<Mutex<InodeSubjectMap>> =
     Lazy::new(|| Mutex::new(InodeSubjectMap::default()));
 
 pub struct All {
+    pub bpf: Bpf,
     pub bprm_check_security: BprmCheckSecurity,
     pub file_open: FileOpen,
+    pub inode_create: InodeCreate,
     pub sb_mount: SbMount,
     pub sb_remount: sb_remount::SbRemount,
     pub sb_umount: sb_umount::SbUmount,
     pub socket_bind: SocketBind,
     pub socket_connect: SocketConnect,
+    pub socket_create: SocketCreate,
+    pub socket_listen: SocketListen,
+    pub task_fix_setgid: TaskFixSetgid,
     pub task_fix_setuid: TaskFixSetuid,
 }
 
 impl All {
     pub async fn add_policy(&mut self, policy: policy::Policy) -> Result<(), EbpfguardError> {
         match policy {
+            policy::Policy::Bpf(policy) => self.bpf.add_policy(policy).await?,
             policy::Policy::FileOpen(policy) => self.file_open.add_policy(policy).await?,
+            policy::Policy::FileOpenGlob(policy) => self.file_open.add_glob_policy(policy).await?,
+            policy::Policy::FileOpenProtected(policy) => {
+                self.file_open.add_protected_policy(policy).await?
+            }
+            policy::Policy::InodeCreate(policy) => self.inode_create.add_policy(policy).await?,
             policy::Policy::SbMount(policy) => self.sb_mount.add_policy(policy).await?,
             policy::Policy::SbRemount(policy) => self.sb_remount.add_policy(policy).await?,
             policy::Policy::SbUmount(policy) => self.sb_umount.add_policy(policy).await?,
             policy::Policy::SocketBind(policy) => self.socket_bind.add_policy(policy).await?,
+            policy::Policy::SocketBindComm(policy) => self.socket_bind.add_comm_policy(policy)?,
+            policy::Policy::SocketBindPacket(policy) => {
+                self.socket_bind.add_packet_policy(policy).await?
+            }
             policy::Policy::SocketConnect(policy) => self.socket_connect.add_policy(policy).await?,
+            policy::Policy::SocketConnectGeo(policy) => {
+                self.socket_connect.add_geo_policy(policy).await?
+            }
+            policy::Policy::SocketConnectMetadata(policy) => {
+                self.socket_connect.add_metadata_policy(policy).await?
+            }
+            policy::Policy::SocketConnectPorts(policy) => {
+                self.socket_connect.add_ports_policy(policy).await?
+            }
+            policy::Policy::SocketConnectProtected(policy) => {
+                self.socket_connect.add_protected_policy(policy).await?
+            }
+            policy::Policy::SocketCreate(policy) => self.socket_create.add_policy(policy).await?,
+            policy::Policy::SocketListen(policy) => self.socket_listen.add_policy(policy).await?,
+            policy::Policy::TaskFixSetgid(policy) => {
+                self.task_fix_setgid.add_policy(policy).await?
+            }
             policy::Policy::TaskFixSetuid(policy) => {
                 self.task_fix_setuid.add_policy(policy).await?
             }
@@ -65,37 +115,212 @@ impl All {
     }
 }
 
-pub async fn perf_array_alerts<E, U>(
-    perf_array: &mut AsyncPerfEventArray<MapData>,
+/// Resolves paths of binaries allowed to access a protected resource to
+/// their inodes.
+pub(crate) async fn resolve_binaries(
+    resolver: &dyn InodeResolver,
+    paths: Vec<PathBuf>,
+) -> Result<ebpf_policy::Binaries, EbpfguardError> {
+    if paths.len() > ebpf_policy::MAX_BINARIES {
+        return Err(EbpfguardError::TooManyBinaries(ebpf_policy::MAX_BINARIES));
+    }
+
+    let mut binaries = [0; ebpf_policy::MAX_BINARIES];
+    let mut map = INODE_SUBJECT_MAP.lock().await;
+    for (i, path) in paths.into_iter().enumerate() {
+        binaries[i] = map.resolve_path(resolver, policy::PolicySubject::Binary(path))?;
+    }
+
+    Ok(ebpf_policy::Binaries::new(binaries))
+}
+
+/// Resolves inodes of binaries allowed to access a protected resource to
+/// their paths.
+pub(crate) async fn binaries_paths(binaries: &ebpf_policy::Binaries) -> Vec<PathBuf> {
+    let map = INODE_SUBJECT_MAP.lock().await;
+    binaries
+        .binaries
+        .iter()
+        .take_while(|inode| **inode != 0)
+        .map(|inode| map.resolve_binary(*inode))
+        .collect()
+}
+
+/// Returns the path which the inode was resolved from by this process, if
+/// any.
+pub(crate) async fn resolved_path(inode: u64) -> Option<PathBuf> {
+    let map = INODE_SUBJECT_MAP.lock().await;
+    map.path(inode).cloned()
+}
+
+/// A perf event array of alerts, with the path it's pinned at.
+pub(crate) struct AlertArray {
+    pub(crate) perf_array: AsyncPerfEventArray<MapData>,
+    pub(crate) path: PathBuf,
+}
+
+/// What the reader of a CPU buffer passes on to the task numbering the
+/// alerts of a subscription.
+enum Read<U> {
+    /// Events the kernel dropped because the buffer was full.
+    Lost(u64),
+    Alert(U),
+}
+
+/// Subscriptions to the alerts of a perf event array, by policy namespace.
+type Subscriptions<U> = Arc<StdMutex<StdHashMap<u32, Vec<mpsc::Sender<Read<U>>>>>>;
+
+/// Subscriptions to the perf event arrays read by this process, by the path
+/// the array is pinned at. The hooks of all namespaces share the arrays and
+/// opening the buffer of a CPU replaces its previous reader, so the buffers
+/// of each array are opened once and read for all its subscriptions.
+static ALERT_SUBSCRIPTIONS: Lazy<StdMutex<StdHashMap<PathBuf, Box<dyn Any + Send>>>> =
+    Lazy::new(|| StdMutex::new(StdHashMap::new()));
+
+/// Reads alerts from the given perf event array through the buffers of the
+/// hook, forwarding only the ones which belong to `namespace`, numbered with
+/// sequence numbers (see [`alerts`](crate::alerts)). Readers and lost alerts
+/// are counted in the hook's stats, for health checks.
+///
+/// The buffers are opened by the first subscription to the array, with the
+/// sizes of its hook, and stay open for the later ones. Each subscription
+/// receives all alerts of its namespace, numbered on their own.
+pub(crate) async fn perf_array_alerts<E, U>(
+    alert_array: &mut AlertArray,
+    namespace: u32,
+    monitor: &HookMonitor,
 ) -> Result<Receiver<U>, EbpfguardError>
 where
-    E: ebpf_alerts::Alert,
+    E: ebpf_alerts::Alert + Copy,
     U: alerts::Alert + Debug + Send + From<E> + 'static,
 {
     let (tx, rx) = mpsc::channel(32);
+    // The readers of all CPUs pass the alerts of the namespace on to a
+    // single task, which numbers them in the order it sends them.
+    let (read_tx, mut read_rx) = mpsc::channel(32);
 
     let cpus = online_cpus()?;
-    for cpu_id in cpus {
-        let tx = tx.clone();
-        let mut buf = perf_array.open(cpu_id, None)?;
-
-        task::spawn(async move {
-            let mut buffers = (0..10)
-                .map(|_| BytesMut::with_capacity(1024))
-                .collect::<Vec<_>>();
-            loop {
-                let events = buf.read_events(&mut buffers).await.unwrap();
-                for buf in buffers.iter_mut().take(events.read) {
-                    let alert: U = {
-                        let ptr = buf.as_ptr() as *const E;
-                        let alert = unsafe { ptr.read_unaligned() };
-                        alert.into()
-                    };
-                    tx.send(alert).await.unwrap();
+    {
+        let mut arrays = ALERT_SUBSCRIPTIONS.lock().unwrap();
+        let subscriptions = match arrays.get(&alert_array.path) {
+            Some(subscriptions) => subscriptions
+                .downcast_ref::<Subscriptions<U>>()
+                .expect("alerts of a perf event array are read as one type")
+                .clone(),
+            None => {
+                let subscriptions = Subscriptions::<U>::default();
+                for cpu_id in cpus.iter().copied() {
+                    read_buffer::<E, U>(alert_array, cpu_id, monitor, subscriptions.clone())?;
                 }
+                arrays.insert(alert_array.path.clone(), Box::new(subscriptions.clone()));
+                subscriptions
             }
-        });
+        };
+        subscriptions
+            .lock()
+            .unwrap()
+            .entry(namespace)
+            .or_default()
+            .push(read_tx);
     }
 
+    let stats = monitor.alerts.clone();
+    let readers = cpus.iter().map(|_| stats.reader()).collect::<Vec<_>>();
+    task::spawn(async move {
+        let _readers = readers;
+        let mut seq = 0u64;
+        while let Some(read) = read_rx.recv().await {
+            match read {
+                // Dropped alerts take sequence numbers, to show up as a gap.
+                Read::Lost(count) => {
+                    stats.add_lost(count);
+                    seq += count;
+                }
+                Read::Alert(mut alert) => {
+                    seq += 1;
+                    alert.set_seq(seq);
+                    if tx.send(alert).await.is_err() {
+                        break;
+                    }
+                }
+            }
+        }
+    });
+
     Ok(rx)
 }
+
+/// Opens the buffer of the CPU and spawns its reader, which passes the
+/// alerts on to the subscription of their namespace and the lost events on
+/// to all subscriptions, since the kernel doesn't tell their namespace.
+fn read_buffer<E, U>(
+    alert_array: &mut AlertArray,
+    cpu_id: u32,
+    monitor: &HookMonitor,
+    subscriptions: Subscriptions<U>,
+) -> Result<(), EbpfguardError>
+where
+    E: ebpf_alerts::Alert + Copy,
+    U: alerts::Alert + Debug + Send + From<E> + 'static,
+{
+    let buffers = monitor.buffers;
+    let mut buf = alert_array.perf_array.open(cpu_id, Some(buffers.pages))?;
+
+    task::spawn(async move {
+        let mut buffers = (0..10)
+            .map(|_| BytesMut::with_capacity(buffers.record_size))
+            .collect::<Vec<_>>();
+        loop {
+            let events = buf.read_events(&mut buffers).await.unwrap();
+            if events.lost > 0 {
+                let senders = subscriptions
+                    .lock()
+                    .unwrap()
+                    .values()
+                    .flatten()
+                    .cloned()
+                    .collect::<Vec<_>>();
+                for sender in senders {
+                    let lost = Read::Lost(events.lost as u64);
+                    if sender.send(lost).await.is_err() {
+                        unsubscribe(&subscriptions, &sender);
+                    }
+                }
+            }
+            for buf in buffers.iter_mut().take(events.read) {
+                // Converted for each subscription up front, so the alert read
+                // from the buffer isn't held across a send.
+                let alerts = {
+                    let ptr = buf.as_ptr() as *const E;
+                    let alert = unsafe { ptr.read_unaligned() };
+                    let senders = subscriptions
+                        .lock()
+                        .unwrap()
+                        .get(&alert.namespace())
+                        .cloned()
+                        .unwrap_or_default();
+                    senders
+                        .into_iter()
+                        .map(|sender| (sender, U::from(alert)))
+                        .collect::<Vec<_>>()
+                };
+                for (sender, alert) in alerts {
+                    if sender.send(Read::Alert(alert)).await.is_err() {
+                        unsubscribe(&subscriptions, &sender);
+                    }
+                }
+            }
+        }
+    });
+
+    Ok(())
+}
+
+/// Removes the subscription whose receiver was dropped.
+fn unsubscribe<U>(subscriptions: &Subscriptions<U>, sender: &mpsc::Sender<Read<U>>) {
+    let mut subscriptions = subscriptions.lock().unwrap();
+    for senders in subscriptions.values_mut() {
+        senders.retain(|subscribed| !subscribed.same_channel(sender));
+    }
+    subscriptions.retain(|_, senders| !senders.is_empty());
+}
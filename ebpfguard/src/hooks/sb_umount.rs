@@ -1,47 +1,76 @@
+use std::sync::Arc;
+
 use aya::{
-    maps::{AsyncPerfEventArray, HashMap, MapData},
+    maps::{HashMap, MapData},
     programs::lsm::LsmLink,
 };
-use ebpfguard_common::alerts as ebpf_alerts;
+use ebpfguard_common::{alerts as ebpf_alerts, policy::InodeKey};
 use tokio::sync::mpsc::Receiver;
 
-use crate::{alerts, error::EbpfguardError, policy};
+use crate::{
+    alerts,
+    audit::{self, AuditLog},
+    error::EbpfguardError,
+    fs::InodeResolver,
+    generation::Generation,
+    health::HookMonitor,
+    policy::{self, inode::subject_key},
+};
 
-use super::{perf_array_alerts, INODE_SUBJECT_MAP};
+use super::{perf_array_alerts, AlertArray, INODE_SUBJECT_MAP};
 
 pub struct SbUmount {
     #[allow(dead_code)]
     pub(crate) program_link: Option<LsmLink>,
-    pub(crate) allowed_map: HashMap<MapData, u64, u8>,
-    pub(crate) denied_map: HashMap<MapData, u64, u8>,
-    pub(crate) perf_array: AsyncPerfEventArray<MapData>,
+    pub(crate) allowed_map: HashMap<MapData, InodeKey, u8>,
+    pub(crate) denied_map: HashMap<MapData, InodeKey, u8>,
+    pub(crate) audit: AuditLog,
+    pub(crate) monitor: HookMonitor,
+    pub(crate) perf_array: AlertArray,
+    pub(crate) resolver: Arc<dyn InodeResolver>,
+    pub(crate) generation: Generation,
+    pub(crate) namespace: u32,
 }
 
 impl SbUmount {
     pub async fn add_policy(&mut self, policy: policy::SbUmount) -> Result<(), EbpfguardError> {
+        let old = if self.audit.enabled() {
+            audit::previous(self.list_policies().await?, |p| p.subject == policy.subject)
+        } else {
+            None
+        };
+        let (audit_key, new) = (audit::value(&policy.subject), audit::value(&policy));
+
         let bin_inode = {
             let mut map = INODE_SUBJECT_MAP.lock().await;
-            map.resolve_path(policy.subject)?
+            map.resolve_path(&*self.resolver, policy.subject)?
         };
 
+        let key = subject_key(self.generation.policy_namespace()?, bin_inode);
         if policy.allow {
-            self.allowed_map.insert(bin_inode, 0, 0)?;
+            self.allowed_map.insert(key, 0, 0)?;
         } else {
-            self.denied_map.insert(bin_inode, 0, 0)?;
+            self.denied_map.insert(key, 0, 0)?;
         }
 
+        self.audit.record("add_policy", audit_key, old, Some(new));
+
         Ok(())
     }
 
     pub async fn list_policies(&self) -> Result<Vec<policy::SbUmount>, EbpfguardError> {
         let mut policies = Vec::new();
+        let namespace = self.generation.policy_namespace()?;
 
         for res in self.allowed_map.iter() {
-            let (bin_inode, _) = res?;
+            let (key, _) = res?;
+            if key.namespace != namespace {
+                continue;
+            }
 
             let subject = {
                 let map = INODE_SUBJECT_MAP.lock().await;
-                map.resolve_inode(bin_inode)
+                map.resolve_inode(key.inode)
             };
 
             policies.push(policy::SbUmount {
@@ -51,11 +80,14 @@ impl SbUmount {
         }
 
         for res in self.denied_map.iter() {
-            let (bin_inode, _) = res?;
+            let (key, _) = res?;
+            if key.namespace != namespace {
+                continue;
+            }
 
             let subject = {
                 let map = INODE_SUBJECT_MAP.lock().await;
-                map.resolve_inode(bin_inode)
+                map.resolve_inode(key.inode)
             };
 
             policies.push(policy::SbUmount {
@@ -68,6 +100,11 @@ impl SbUmount {
     }
 
     pub async fn alerts(&mut self) -> Result<Receiver<alerts::SbUmount>, EbpfguardError> {
-        perf_array_alerts::<ebpf_alerts::SbUmount, alerts::SbUmount>(&mut self.perf_array).await
+        perf_array_alerts::<ebpf_alerts::SbUmount, alerts::SbUmount>(
+            &mut self.perf_array,
+            self.namespace,
+            &self.monitor,
+        )
+        .await
     }
 }
@@ -1,62 +1,681 @@
+use std::{collections::HashMap as StdHashMap, ops::RangeInclusive, sync::Arc, time::Duration};
+
 use aya::{
-    maps::{AsyncPerfEventArray, HashMap, MapData},
+    maps::{
+        lpm_trie::{Key, LpmTrie},
+        HashMap, MapData, MapError,
+    },
     programs::lsm::LsmLink,
 };
-use ebpfguard_common::{alerts as ebpf_alerts, policy as ebpf_policy};
-
-use tokio::sync::mpsc::Receiver;
+use ebpfguard_common::{
+    alerts as ebpf_alerts,
+    policy::{self as ebpf_policy, CommKey, InodeKey, SocketBindGrantKey, COMM_KEY_PREFIX_LEN},
+};
+use log::warn;
+use serde_yaml::Value;
+use tokio::{
+    sync::{mpsc::Receiver, Mutex},
+    task,
+    time::Instant,
+};
 
-use crate::{alerts, error::EbpfguardError, policy};
+use crate::{
+    alerts,
+    audit::{self, AuditLog},
+    config::ConfigMap,
+    error::EbpfguardError,
+    fs::InodeResolver,
+    generation::Generation,
+    health::HookMonitor,
+    policy::{self, comm::CommPattern, inode::subject_key},
+};
 
-use super::{perf_array_alerts, INODE_SUBJECT_MAP};
+use super::{perf_array_alerts, AlertArray, INODE_SUBJECT_MAP};
 
 pub struct SocketBind {
     #[allow(dead_code)]
     pub(crate) program_link: Option<LsmLink>,
-    pub(crate) allowed_map: HashMap<MapData, u64, ebpf_policy::Ports>,
-    pub(crate) denied_map: HashMap<MapData, u64, ebpf_policy::Ports>,
-    pub(crate) perf_array: AsyncPerfEventArray<MapData>,
+    pub(crate) allowed_map: HashMap<MapData, InodeKey, ebpf_policy::Ports>,
+    pub(crate) denied_map: HashMap<MapData, InodeKey, ebpf_policy::Ports>,
+    pub(crate) allowed_v4_map: HashMap<MapData, InodeKey, ebpf_policy::Ports>,
+    pub(crate) denied_v4_map: HashMap<MapData, InodeKey, ebpf_policy::Ports>,
+    pub(crate) allowed_v6_map: HashMap<MapData, InodeKey, ebpf_policy::Ports>,
+    pub(crate) denied_v6_map: HashMap<MapData, InodeKey, ebpf_policy::Ports>,
+    pub(crate) options_map: HashMap<MapData, InodeKey, u8>,
+    pub(crate) allowed_comm_map: LpmTrie<MapData, CommKey, ebpf_policy::Ports>,
+    pub(crate) denied_comm_map: LpmTrie<MapData, CommKey, ebpf_policy::Ports>,
+    pub(crate) allowed_packet_map: HashMap<MapData, InodeKey, u8>,
+    pub(crate) denied_packet_map: HashMap<MapData, InodeKey, u8>,
+    pub(crate) escalate_map: HashMap<MapData, InodeKey, u8>,
+    pub(crate) verdict_map: Option<HashMap<MapData, ebpf_policy::SocketBindVerdictKey, u8>>,
+    pub(crate) generation_map: HashMap<MapData, u32, u64>,
+    pub(crate) config: ConfigMap,
+    pub(crate) bind_limit_map: HashMap<MapData, InodeKey, u32>,
+    pub(crate) grants: Arc<Mutex<Grants>>,
+    pub(crate) audit: AuditLog,
+    pub(crate) monitor: HookMonitor,
+    pub(crate) perf_array: AlertArray,
+    pub(crate) escalation_perf_array: AlertArray,
+    pub(crate) resolver: Arc<dyn InodeResolver>,
+    pub(crate) generation: Generation,
+    pub(crate) namespace: u32,
+}
+
+/// Temporary allows of a namespace, shared with the tasks revoking them when
+/// they expire.
+pub(crate) struct Grants {
+    map: HashMap<MapData, SocketBindGrantKey, u8>,
+    /// Expiry of the grants made through this hook. A task revokes its grant
+    /// only if the deadline is still the one it was scheduled for, so a
+    /// renewed grant outlives the task of the previous one.
+    deadlines: StdHashMap<SocketBindGrantKey, Instant>,
+}
+
+impl Grants {
+    pub(crate) fn new(map: HashMap<MapData, SocketBindGrantKey, u8>) -> Self {
+        Self {
+            map,
+            deadlines: StdHashMap::new(),
+        }
+    }
+
+    /// Removes the grant, returning the time it had left if it was made
+    /// through this hook.
+    fn revoke(&mut self, key: &SocketBindGrantKey) -> Result<Option<Duration>, EbpfguardError> {
+        match self.map.remove(key) {
+            Ok(()) | Err(MapError::KeyNotFound) => {}
+            Err(e) => return Err(e.into()),
+        }
+        Ok(self
+            .deadlines
+            .remove(key)
+            .map(|deadline| deadline.saturating_duration_since(Instant::now())))
+    }
 }
 
 impl SocketBind {
+    /// Adds a policy. It replaces the previous policy of the subject with the
+    /// same family (see [`policy::SocketBind`] for how policies scoped to a
+    /// family combine with family-agnostic ones).
     pub async fn add_policy(&mut self, policy: policy::SocketBind) -> Result<(), EbpfguardError> {
+        let old = if self.audit.enabled() {
+            audit::previous(self.list_policies().await?, |p| {
+                p.subject == policy.subject && p.family == policy.family
+            })
+        } else {
+            None
+        };
+        let (audit_key, new) = (
+            audit::value(&(&policy.subject, policy.family)),
+            audit::value(&policy),
+        );
+
         let bin_inode = {
             let mut map = INODE_SUBJECT_MAP.lock().await;
-            map.resolve_path(policy.subject)?
+            map.resolve_path(&*self.resolver, policy.subject)?
+        };
+
+        let allow: ebpf_policy::Ports = policy.allow.into();
+        let deny: ebpf_policy::Ports = policy.deny.into();
+        let options = policy::SocketOption::to_flags(&policy.deny_options);
+
+        let key = subject_key(self.generation.policy_namespace()?, bin_inode);
+        let (allowed_map, denied_map) = self.port_maps(policy.family);
+        allowed_map.insert(key, allow, 0)?;
+        denied_map.insert(key, deny, 0)?;
+        if policy.family.is_none() || options != 0 {
+            self.options_map.insert(key, options, 0)?;
+        }
+        self.bump_generation()?;
+
+        self.audit.record("add_policy", audit_key, old, Some(new));
+
+        Ok(())
+    }
+
+    /// Returns the allowed and denied port maps of policies of the family.
+    #[allow(clippy::type_complexity)]
+    fn port_maps(
+        &mut self,
+        family: Option<policy::BindFamily>,
+    ) -> (
+        &mut HashMap<MapData, InodeKey, ebpf_policy::Ports>,
+        &mut HashMap<MapData, InodeKey, ebpf_policy::Ports>,
+    ) {
+        match family {
+            None => (&mut self.allowed_map, &mut self.denied_map),
+            Some(policy::BindFamily::Ipv4) => (&mut self.allowed_v4_map, &mut self.denied_v4_map),
+            Some(policy::BindFamily::Ipv6) => (&mut self.allowed_v6_map, &mut self.denied_v6_map),
+        }
+    }
+
+    /// Adds a policy matching processes by their command name. It replaces
+    /// the previous policy of the same pattern.
+    ///
+    /// See [`policy::SocketBindComm`] for why command names must not be
+    /// relied on to contain untrusted processes.
+    pub fn add_comm_policy(
+        &mut self,
+        policy: policy::SocketBindComm,
+    ) -> Result<(), EbpfguardError> {
+        let old = if self.audit.enabled() {
+            audit::previous(self.list_comm_policies()?, |p| p.comm == policy.comm)
+        } else {
+            None
         };
+        let (audit_key, new) = (audit::value(&policy.comm), audit::value(&policy));
 
         let allow: ebpf_policy::Ports = policy.allow.into();
         let deny: ebpf_policy::Ports = policy.deny.into();
 
-        self.allowed_map.insert(bin_inode, allow, 0)?;
-        self.denied_map.insert(bin_inode, deny, 0)?;
+        let key = comm_key(self.generation.policy_namespace()?, &policy.comm);
+        self.allowed_comm_map.insert(&key, allow, 0)?;
+        self.denied_comm_map.insert(&key, deny, 0)?;
+        self.bump_generation()?;
+
+        self.audit
+            .record("add_comm_policy", audit_key, old, Some(new));
 
         Ok(())
     }
 
+    pub fn list_comm_policies(&self) -> Result<Vec<policy::SocketBindComm>, EbpfguardError> {
+        let mut policies = Vec::new();
+        let namespace = self.generation.policy_namespace()?;
+
+        for key in self.allowed_comm_map.keys() {
+            let key = key?;
+            // Copied out, since fields of LPM trie keys can't be borrowed.
+            let data = key.data;
+            if u32::from_be(data.namespace) != namespace {
+                continue;
+            }
+            let allow = self.allowed_comm_map.get(&key, 0)?;
+            let deny = self.denied_comm_map.get(&key, 0)?;
+            let len = (key.prefix_len - COMM_KEY_PREFIX_LEN) as usize / 8;
+
+            policies.push(policy::SocketBindComm {
+                comm: CommPattern::from_bytes(&data.comm, len),
+                allow: allow.into(),
+                deny: deny.into(),
+            });
+        }
+
+        Ok(policies)
+    }
+
+    /// Invalidates the binds cached by the eBPF program in the namespace. Has
+    /// to be called after every change of `allowed_map` or `denied_map`.
+    ///
+    /// Bumps are not atomic, so policies of a namespace must not be changed
+    /// by multiple policy managers at once.
+    fn bump_generation(&mut self) -> Result<(), EbpfguardError> {
+        bump_generation(&mut self.generation_map, self.namespace)
+    }
+
+    /// Exempts binds of the ports in the range (e.g. the ephemeral ports, see
+    /// [`fs::ephemeral_port_range`](crate::fs::ephemeral_port_range)) from
+    /// enforcement in the namespace, or removes the exemption if `None`.
+    /// There is no exemption by default.
+    ///
+    /// The exemption takes precedence over all policies: binds of the exempt
+    /// ports are allowed without checking allow/deny rules, socket options
+    /// and escalation, and are never alerted. Binds to port 0 (which let the
+    /// kernel pick an ephemeral port) are always allowed.
+    pub fn set_exempt_ports(
+        &mut self,
+        ports: Option<RangeInclusive<u16>>,
+    ) -> Result<(), EbpfguardError> {
+        let old = if self.audit.enabled() {
+            self.exempt_ports()?.map(|ports| audit::value(&ports))
+        } else {
+            None
+        };
+        let new = ports.as_ref().map(audit::value);
+
+        let range = ports.map(|ports| ebpf_policy::PortRange::new(*ports.start(), *ports.end()));
+        self.config
+            .update(|config| config.set_exempt_ports(range))?;
+
+        self.audit.record("set_exempt_ports", Value::Null, old, new);
+
+        Ok(())
+    }
+
+    /// Returns the range of ports exempt from enforcement in the namespace.
+    pub fn exempt_ports(&self) -> Result<Option<RangeInclusive<u16>>, EbpfguardError> {
+        let config = self.config.get()?;
+        Ok(config.exempt_ports().map(|range| range.start..=range.end))
+    }
+
+    /// Limits the number of distinct ports each process of the binary (or,
+    /// with [`PolicySubject::All`](policy::PolicySubject::All), of binaries
+    /// without their own limit) may bind in the namespace, or removes the
+    /// limit if `None`. There is no limit by default.
+    ///
+    /// The limit applies to binds allowed by the policies. Once a process has
+    /// bound `limit` distinct ports, binds of further ports are denied with
+    /// [`Reason::BindLimit`](alerts::Reason::BindLimit), and the alert
+    /// carries the count. Binding a port counted before is always allowed.
+    /// Ports count from their first allowed bind, even if the kernel fails
+    /// the bind later, and are never released.
+    pub async fn set_bind_limit(
+        &mut self,
+        subject: policy::PolicySubject,
+        limit: Option<u32>,
+    ) -> Result<(), EbpfguardError> {
+        let audit_key = audit::value(&subject);
+        let bin_inode = {
+            let mut map = INODE_SUBJECT_MAP.lock().await;
+            map.resolve_path(&*self.resolver, subject)?
+        };
+        let key = subject_key(self.namespace, bin_inode);
+        let old = self.bind_limit_map.get(&key, 0).ok();
+
+        match limit {
+            Some(limit) => self.bind_limit_map.insert(key, limit, 0)?,
+            None => match self.bind_limit_map.remove(&key) {
+                Ok(()) | Err(MapError::KeyNotFound) => {}
+                Err(e) => return Err(e.into()),
+            },
+        }
+
+        self.audit.record(
+            "set_bind_limit",
+            audit_key,
+            old.map(|old| audit::value(&old)),
+            limit.map(|limit| audit::value(&limit)),
+        );
+
+        Ok(())
+    }
+
+    /// Returns the bind limit set for the subject in the namespace, without
+    /// falling back to the default limit.
+    pub async fn bind_limit(
+        &self,
+        subject: policy::PolicySubject,
+    ) -> Result<Option<u32>, EbpfguardError> {
+        let bin_inode = {
+            let mut map = INODE_SUBJECT_MAP.lock().await;
+            map.resolve_path(&*self.resolver, subject)?
+        };
+
+        match self
+            .bind_limit_map
+            .get(&subject_key(self.namespace, bin_inode), 0)
+        {
+            Ok(limit) => Ok(Some(limit)),
+            Err(MapError::KeyNotFound) => Ok(None),
+            Err(e) => Err(e.into()),
+        }
+    }
+
     pub async fn list_policies(&self) -> Result<Vec<policy::SocketBind>, EbpfguardError> {
         let mut policies = Vec::new();
+        let namespace = self.generation.policy_namespace()?;
+
+        let maps = [
+            (None, &self.allowed_map, &self.denied_map),
+            (
+                Some(policy::BindFamily::Ipv4),
+                &self.allowed_v4_map,
+                &self.denied_v4_map,
+            ),
+            (
+                Some(policy::BindFamily::Ipv6),
+                &self.allowed_v6_map,
+                &self.denied_v6_map,
+            ),
+        ];
+        for (family, allowed_map, denied_map) in maps {
+            for res in allowed_map.iter() {
+                let (key, allow) = res?;
+                if key.namespace != namespace {
+                    continue;
+                }
+                let deny = denied_map.get(&key, 0)?;
+                let options = self.options_map.get(&key, 0).unwrap_or(0);
 
-        for res in self.allowed_map.iter() {
-            let (bin_inode, allow) = res?;
-            let deny = self.denied_map.get(&bin_inode, 0)?;
+                let subject = {
+                    let map = INODE_SUBJECT_MAP.lock().await;
+                    map.resolve_inode(key.inode)
+                };
+
+                policies.push(policy::SocketBind {
+                    subject,
+                    allow: allow.into(),
+                    deny: deny.into(),
+                    deny_options: policy::SocketOption::from_flags(options),
+                    family,
+                });
+            }
+        }
+
+        Ok(policies)
+    }
+
+    pub async fn add_packet_policy(
+        &mut self,
+        policy: policy::SocketBindPacket,
+    ) -> Result<(), EbpfguardError> {
+        let old = if self.audit.enabled() {
+            audit::previous(self.list_packet_policies().await?, |p| {
+                p.subject == policy.subject
+            })
+        } else {
+            None
+        };
+        let (audit_key, new) = (audit::value(&policy.subject), audit::value(&policy));
+
+        let bin_inode = {
+            let mut map = INODE_SUBJECT_MAP.lock().await;
+            map.resolve_path(&*self.resolver, policy.subject)?
+        };
+
+        let key = subject_key(self.generation.policy_namespace()?, bin_inode);
+        if policy.allow {
+            self.allowed_packet_map.insert(key, 0, 0)?;
+        } else {
+            self.denied_packet_map.insert(key, 0, 0)?;
+        }
+
+        self.audit
+            .record("add_packet_policy", audit_key, old, Some(new));
+
+        Ok(())
+    }
+
+    pub async fn list_packet_policies(
+        &self,
+    ) -> Result<Vec<policy::SocketBindPacket>, EbpfguardError> {
+        let mut policies = Vec::new();
+        let namespace = self.generation.policy_namespace()?;
+
+        for res in self.allowed_packet_map.iter() {
+            let (key, _) = res?;
+            if key.namespace != namespace {
+                continue;
+            }
 
             let subject = {
                 let map = INODE_SUBJECT_MAP.lock().await;
-                map.resolve_inode(bin_inode)
+                map.resolve_inode(key.inode)
             };
 
-            policies.push(policy::SocketBind {
+            policies.push(policy::SocketBindPacket {
                 subject,
-                allow: allow.into(),
-                deny: deny.into(),
+                allow: true,
+            });
+        }
+
+        for res in self.denied_packet_map.iter() {
+            let (key, _) = res?;
+            if key.namespace != namespace {
+                continue;
+            }
+
+            let subject = {
+                let map = INODE_SUBJECT_MAP.lock().await;
+                map.resolve_inode(key.inode)
+            };
+
+            policies.push(policy::SocketBindPacket {
+                subject,
+                allow: false,
             });
         }
 
         Ok(policies)
     }
 
+    /// Enables escalation of socket binds, which the policies of the given
+    /// subject don't decide, to user space. Use [`Self::register_verdict_callback`]
+    /// to decide them.
+    ///
+    /// An LSM hook can't wait for user space, so the escalated bind itself is
+    /// decided with `fallback`. The verdict returned by the callback applies
+    /// to subsequent binds of the same binary to the same port, once user
+    /// space processes the escalation (usually within milliseconds, but
+    /// unbounded under load). Until then, and whenever no callback is
+    /// registered, `fallback` applies. Verdicts are kept in an LRU map of 1024
+    /// entries, so evicted ones get escalated again.
+    pub async fn escalate(
+        &mut self,
+        subject: policy::PolicySubject,
+        fallback: policy::Verdict,
+    ) -> Result<(), EbpfguardError> {
+        let audit_key = audit::value(&subject);
+        let bin_inode = {
+            let mut map = INODE_SUBJECT_MAP.lock().await;
+            map.resolve_path(&*self.resolver, subject)?
+        };
+
+        let key = subject_key(self.namespace, bin_inode);
+        let old = self.escalate_map.get(&key, 0).ok().map(|old| {
+            if old == ebpf_policy::VERDICT_ALLOW {
+                policy::Verdict::Allow
+            } else {
+                policy::Verdict::Deny
+            }
+        });
+        self.escalate_map.insert(key, u8::from(fallback), 0)?;
+
+        self.audit.record(
+            "escalate",
+            audit_key,
+            old.map(|old| audit::value(&old)),
+            Some(audit::value(&fallback)),
+        );
+
+        Ok(())
+    }
+
+    /// Registers a callback deciding socket binds escalated to user space. It
+    /// runs in a background task and can be registered only once.
+    pub async fn register_verdict_callback<F>(&mut self, callback: F) -> Result<(), EbpfguardError>
+    where
+        F: Fn(&alerts::SocketBindEscalation) -> policy::Verdict + Send + 'static,
+    {
+        let mut verdict_map = self
+            .verdict_map
+            .take()
+            .ok_or(EbpfguardError::VerdictCallbackRegistered)?;
+        let mut rx = perf_array_alerts::<ebpf_alerts::SocketBind, alerts::SocketBindEscalation>(
+            &mut self.escalation_perf_array,
+            self.namespace,
+            &self.monitor,
+        )
+        .await?;
+
+        task::spawn(async move {
+            while let Some(escalation) = rx.recv().await {
+                let verdict = callback(&escalation);
+                let key = ebpf_policy::SocketBindVerdictKey::new(
+                    escalation.namespace,
+                    escalation.binprm_inode,
+                    escalation.family,
+                    escalation.port,
+                );
+                if let Err(e) = verdict_map.insert(key, u8::from(verdict), 0) {
+                    warn!("failed to store socket_bind verdict: {e}");
+                }
+            }
+        });
+
+        Ok(())
+    }
+
+    /// Temporarily allows the binary (or, with
+    /// [`PolicySubject::All`](policy::PolicySubject::All), all binaries) to
+    /// bind the port in the namespace for `duration`, e.g. as a break-glass
+    /// exception. Granting the port again restarts the duration.
+    ///
+    /// The grant is terminal: it overrides the denies of all policies,
+    /// socket options, escalation and bind limits, and its binds are not
+    /// alerted. When the duration ends, a task of the runtime revokes the
+    /// grant, emitting a `revoke_temporary_allow` audit event. A grant whose
+    /// task doesn't get to run (e.g. because the process exited) stays in
+    /// the pinned map until it's revoked with
+    /// [`revoke_temporary_allow`](Self::revoke_temporary_allow).
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use std::{path::PathBuf, time::Duration};
+    ///
+    /// use ebpfguard::{policy::PolicySubject, PolicyManager};
+    ///
+    /// # #[tokio::main(flavor = "current_thread")]
+    /// # async fn main() {
+    /// let mut policy_manager = PolicyManager::with_default_path().unwrap();
+    /// let mut socket_bind = policy_manager.attach_socket_bind().unwrap();
+    ///
+    /// socket_bind
+    ///     .grant_temporary_allow(
+    ///         PolicySubject::Binary(PathBuf::from("/usr/sbin/sshd")),
+    ///         22,
+    ///         Duration::from_secs(600),
+    ///     )
+    ///     .await
+    ///     .unwrap();
+    /// # }
+    /// ```
+    pub async fn grant_temporary_allow(
+        &mut self,
+        subject: policy::PolicySubject,
+        port: u16,
+        duration: Duration,
+    ) -> Result<(), EbpfguardError> {
+        let audit_key = audit::value(&(&subject, port));
+        let bin_inode = {
+            let mut map = INODE_SUBJECT_MAP.lock().await;
+            map.resolve_path(&*self.resolver, subject)?
+        };
+        let key = SocketBindGrantKey::new(subject_key(self.namespace, bin_inode), port);
+
+        let deadline = Instant::now() + duration;
+        let old = {
+            let mut grants = self.grants.lock().await;
+            grants.map.insert(key, 0, 0)?;
+            grants.deadlines.insert(key, deadline)
+        };
+
+        self.audit.record(
+            "grant_temporary_allow",
+            audit_key.clone(),
+            old.map(|old| audit::value(&old.saturating_duration_since(Instant::now()))),
+            Some(audit::value(&duration)),
+        );
+
+        let grants = self.grants.clone();
+        let audit = self.audit.clone();
+        task::spawn(async move {
+            tokio::time::sleep_until(deadline).await;
+            let mut grants = grants.lock().await;
+            // Renewed or revoked in the meantime.
+            if grants.deadlines.get(&key) != Some(&deadline) {
+                return;
+            }
+            match grants.revoke(&key) {
+                Ok(_) => audit.record(
+                    "revoke_temporary_allow",
+                    audit_key,
+                    Some(audit::value(&duration)),
+                    None,
+                ),
+                Err(e) => warn!("failed to revoke an expired temporary allow: {e}"),
+            }
+        });
+
+        Ok(())
+    }
+
+    /// Revokes the temporary allow of the port for the subject before it
+    /// expires, including grants left by another process.
+    pub async fn revoke_temporary_allow(
+        &mut self,
+        subject: policy::PolicySubject,
+        port: u16,
+    ) -> Result<(), EbpfguardError> {
+        let audit_key = audit::value(&(&subject, port));
+        let bin_inode = {
+            let mut map = INODE_SUBJECT_MAP.lock().await;
+            map.resolve_path(&*self.resolver, subject)?
+        };
+        let key = SocketBindGrantKey::new(subject_key(self.namespace, bin_inode), port);
+
+        let left = self.grants.lock().await.revoke(&key)?;
+
+        self.audit.record(
+            "revoke_temporary_allow",
+            audit_key,
+            left.map(|left| audit::value(&left)),
+            None,
+        );
+
+        Ok(())
+    }
+
+    /// Returns the temporary allows of the namespace with the time they have
+    /// left, `None` for grants not made through this hook.
+    pub async fn list_temporary_allows(
+        &self,
+    ) -> Result<Vec<(policy::PolicySubject, u16, Option<Duration>)>, EbpfguardError> {
+        let grants = self.grants.lock().await;
+        let now = Instant::now();
+
+        let mut allows = Vec::new();
+        for key in grants.map.keys() {
+            let key = key?;
+            if key.namespace != self.namespace {
+                continue;
+            }
+            let subject = {
+                let map = INODE_SUBJECT_MAP.lock().await;
+                map.resolve_inode(key.binprm_inode)
+            };
+            let left = grants
+                .deadlines
+                .get(&key)
+                .map(|deadline| deadline.saturating_duration_since(now));
+            allows.push((subject, key.port, left));
+        }
+
+        Ok(allows)
+    }
+
     pub async fn alerts(&mut self) -> Result<Receiver<alerts::SocketBind>, EbpfguardError> {
-        perf_array_alerts::<ebpf_alerts::SocketBind, alerts::SocketBind>(&mut self.perf_array).await
+        perf_array_alerts::<ebpf_alerts::SocketBind, alerts::SocketBind>(
+            &mut self.perf_array,
+            self.namespace,
+            &self.monitor,
+        )
+        .await
     }
 }
+
+/// Bumps the generation of the binds cached in the namespace, see
+/// [`SocketBind::bump_generation`].
+pub(crate) fn bump_generation(
+    generation_map: &mut HashMap<MapData, u32, u64>,
+    namespace: u32,
+) -> Result<(), EbpfguardError> {
+    let generation = match generation_map.get(&namespace, 0) {
+        Ok(generation) => generation,
+        Err(MapError::KeyNotFound) => 0,
+        Err(e) => return Err(e.into()),
+    };
+    generation_map.insert(namespace, generation.wrapping_add(1), 0)?;
+
+    Ok(())
+}
+
+/// Returns the key of the comm maps matching the pattern in the namespace.
+fn comm_key(namespace: u32, pattern: &CommPattern) -> Key<CommKey> {
+    let (comm, len) = pattern.to_bytes();
+    Key::new(
+        COMM_KEY_PREFIX_LEN + 8 * len as u32,
+        CommKey::new(namespace, comm),
+    )
+}
@@ -1,27 +1,227 @@
-use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+use std::{
+    collections::{HashMap as StdHashMap, HashSet},
+    net::{IpAddr, Ipv4Addr, Ipv6Addr},
+    sync::Arc,
+    time::Duration,
+};
 
 use aya::{
-    maps::{AsyncPerfEventArray, HashMap, MapData},
+    maps::{
+        lpm_trie::{Key, LpmTrie},
+        HashMap, MapData, MapError,
+    },
     programs::lsm::LsmLink,
 };
 use ebpfguard_common::{
     alerts as ebpf_alerts,
-    policy::{self as ebpf_policy, IpAddrs},
+    consts::INODE_WILDCARD,
+    policy::{
+        self as ebpf_policy, untagged_namespace, InodeKey, IpAddrs, Ipv4CidrKey, Ipv4Key,
+        Ipv6CidrKey, Ipv6Key, RateKey, RateWindow, CIDR_KEY_PREFIX_LEN,
+    },
+};
+use log::warn;
+use serde_yaml::Value;
+use tokio::{
+    sync::{mpsc::Receiver, Mutex},
+    task::{self, JoinHandle},
 };
-use tokio::sync::mpsc::Receiver;
 
-use crate::{alerts, error::EbpfguardError, policy};
+use crate::{
+    alerts,
+    audit::{self, AuditLog},
+    config::ConfigMap,
+    error::EbpfguardError,
+    fs::InodeResolver,
+    generation::Generation,
+    health::HookMonitor,
+    policy::{
+        self,
+        cidr::{self, Cidr},
+        geo::CidrDatabase,
+        inode::subject_key,
+        GeoSelector,
+    },
+};
 
-use super::{perf_array_alerts, INODE_SUBJECT_MAP};
+use super::{binaries_paths, perf_array_alerts, resolve_binaries, AlertArray, INODE_SUBJECT_MAP};
 
 pub struct SocketConnect {
     #[allow(dead_code)]
     pub(crate) program_link: Option<LsmLink>,
-    pub(crate) allowed_map_v4: HashMap<MapData, u64, ebpf_policy::Ipv4Addrs>,
-    pub(crate) denied_map_v4: HashMap<MapData, u64, ebpf_policy::Ipv4Addrs>,
-    pub(crate) allowed_map_v6: HashMap<MapData, u64, ebpf_policy::Ipv6Addrs>,
-    pub(crate) denied_map_v6: HashMap<MapData, u64, ebpf_policy::Ipv6Addrs>,
-    pub(crate) perf_array: AsyncPerfEventArray<MapData>,
+    pub(crate) allowed_map_v4: HashMap<MapData, InodeKey, ebpf_policy::Ipv4Addrs>,
+    pub(crate) denied_map_v4: HashMap<MapData, InodeKey, ebpf_policy::Ipv4Addrs>,
+    pub(crate) allowed_map_v6: HashMap<MapData, InodeKey, ebpf_policy::Ipv6Addrs>,
+    pub(crate) denied_map_v6: HashMap<MapData, InodeKey, ebpf_policy::Ipv6Addrs>,
+    pub(crate) protected_map_v4: HashMap<MapData, Ipv4Key, ebpf_policy::Binaries>,
+    pub(crate) protected_map_v6: HashMap<MapData, Ipv6Key, ebpf_policy::Binaries>,
+    pub(crate) allowed_ports_map: HashMap<MapData, InodeKey, ebpf_policy::PortRanges>,
+    pub(crate) denied_ports_map: HashMap<MapData, InodeKey, ebpf_policy::PortRanges>,
+    pub(crate) config: ConfigMap,
+    pub(crate) metadata_map_v4: LpmTrie<MapData, Ipv4CidrKey, u8>,
+    pub(crate) metadata_map_v6: LpmTrie<MapData, Ipv6CidrKey, u8>,
+    pub(crate) rate_map_v4: LpmTrie<MapData, Ipv4CidrKey, ebpf_policy::ConnectRate>,
+    pub(crate) rate_map_v6: LpmTrie<MapData, Ipv6CidrKey, ebpf_policy::ConnectRate>,
+    pub(crate) rate_windows_map: HashMap<MapData, RateKey, RateWindow>,
+    pub(crate) metadata: StdHashMap<InodeKey, MetadataRules>,
+    pub(crate) geo: Arc<Mutex<GeoRules>>,
+    pub(crate) audit: AuditLog,
+    pub(crate) monitor: HookMonitor,
+    pub(crate) perf_array: AlertArray,
+    pub(crate) resolver: Arc<dyn InodeResolver>,
+    pub(crate) generation: Generation,
+    pub(crate) namespace: u32,
+}
+
+/// Rules of a `socket_connect_metadata` policy and the CIDRs added to the
+/// maps for them, by the key of the subject without the generation of the
+/// policies.
+pub(crate) struct MetadataRules {
+    metadata: Vec<IpAddr>,
+    link_local: bool,
+    cidrs: Vec<Cidr>,
+}
+
+/// Selectors of `socket_connect_geo` policies, the database translating them
+/// to CIDRs and the CIDRs currently added to the maps.
+pub(crate) struct GeoRules {
+    denied_map_v4: LpmTrie<MapData, Ipv4CidrKey, u8>,
+    denied_map_v6: LpmTrie<MapData, Ipv6CidrKey, u8>,
+    database: Option<Box<dyn CidrDatabase>>,
+    /// Selectors by the key of the subject without the generation of the
+    /// policies.
+    selectors: Vec<(InodeKey, GeoSelector)>,
+    installed: HashSet<(InodeKey, Cidr)>,
+    generation: Generation,
+}
+
+impl GeoRules {
+    pub(crate) fn new(
+        denied_map_v4: LpmTrie<MapData, Ipv4CidrKey, u8>,
+        denied_map_v6: LpmTrie<MapData, Ipv6CidrKey, u8>,
+        generation: Generation,
+    ) -> Self {
+        Self {
+            denied_map_v4,
+            denied_map_v6,
+            database: None,
+            selectors: Vec::new(),
+            installed: HashSet::new(),
+            generation,
+        }
+    }
+
+    /// Translates all selectors to CIDRs again, adds the new CIDRs to the
+    /// maps and removes the ones which aren't selected anymore.
+    ///
+    /// Fails without changing the maps if there are more CIDRs than the maps
+    /// can hold. New CIDRs are added before the stale ones are removed, so
+    /// addresses selected both before and after the refresh stay denied.
+    /// The CIDRs are added in the generation of the policies which the
+    /// programs read, so after an atomic plan switched it, the ones of the
+    /// previous generation are removed like stale ones.
+    fn refresh(&mut self) -> Result<(), EbpfguardError> {
+        let namespace = self.generation.policy_namespace()?;
+        let mut selected: StdHashMap<InodeKey, Vec<Cidr>> = StdHashMap::new();
+        for (key, selector) in self.selectors.iter() {
+            let cidrs = match (selector, &self.database) {
+                (GeoSelector::Cidr(cidr), _) => vec![*cidr],
+                (selector, Some(database)) => database.lookup(selector),
+                (_, None) => return Err(EbpfguardError::NoCidrDatabase),
+            };
+            selected
+                .entry(InodeKey { namespace, ..*key })
+                .or_default()
+                .extend(cidrs);
+        }
+
+        let expected: HashSet<_> = selected
+            .into_iter()
+            .flat_map(|(key, cidrs)| {
+                cidr::compact(cidrs)
+                    .into_iter()
+                    .map(move |cidr| (key, cidr))
+            })
+            .collect();
+        let (v4, v6): (Vec<_>, Vec<_>) =
+            expected.iter().partition(|(_, cidr)| cidr.addr().is_ipv4());
+        if v4.len() > ebpf_policy::MAX_CIDRS as usize || v6.len() > ebpf_policy::MAX_CIDRS as usize
+        {
+            return Err(EbpfguardError::TooManyCidrs(
+                ebpf_policy::MAX_CIDRS as usize,
+            ));
+        }
+
+        let added: Vec<_> = expected.difference(&self.installed).copied().collect();
+        let removed: Vec<_> = self.installed.difference(&expected).copied().collect();
+
+        for (key, cidr) in added {
+            self.insert(key, cidr)?;
+            self.installed.insert((key, cidr));
+        }
+        for (key, cidr) in removed {
+            self.remove(key, cidr)?;
+            self.installed.remove(&(key, cidr));
+        }
+
+        Ok(())
+    }
+
+    fn insert(&mut self, key: InodeKey, cidr: Cidr) -> Result<(), EbpfguardError> {
+        insert_cidr(&mut self.denied_map_v4, &mut self.denied_map_v6, key, cidr)
+    }
+
+    fn remove(&mut self, key: InodeKey, cidr: Cidr) -> Result<(), EbpfguardError> {
+        remove_cidr(&mut self.denied_map_v4, &mut self.denied_map_v6, key, cidr)
+    }
+}
+
+/// Adds the CIDR of the binary (or the wildcard) to the CIDR map of its
+/// family.
+fn insert_cidr(
+    map_v4: &mut LpmTrie<MapData, Ipv4CidrKey, u8>,
+    map_v6: &mut LpmTrie<MapData, Ipv6CidrKey, u8>,
+    key: InodeKey,
+    cidr: Cidr,
+) -> Result<(), EbpfguardError> {
+    let prefix_len = CIDR_KEY_PREFIX_LEN + u32::from(cidr.prefix_len());
+    match cidr.addr() {
+        IpAddr::V4(addr) => {
+            let data = Ipv4CidrKey::new(key.namespace, key.inode, u32::from(addr));
+            map_v4.insert(&Key::new(prefix_len, data), 0, 0)?
+        }
+        IpAddr::V6(addr) => {
+            let data = Ipv6CidrKey::new(key.namespace, key.inode, addr.octets());
+            map_v6.insert(&Key::new(prefix_len, data), 0, 0)?
+        }
+    }
+    Ok(())
+}
+
+/// Removes the CIDR of the binary (or the wildcard) from the CIDR map of its
+/// family. It might be gone already if it was added in a previous generation
+/// of the policies.
+fn remove_cidr(
+    map_v4: &mut LpmTrie<MapData, Ipv4CidrKey, u8>,
+    map_v6: &mut LpmTrie<MapData, Ipv6CidrKey, u8>,
+    key: InodeKey,
+    cidr: Cidr,
+) -> Result<(), EbpfguardError> {
+    let prefix_len = CIDR_KEY_PREFIX_LEN + u32::from(cidr.prefix_len());
+    let res = match cidr.addr() {
+        IpAddr::V4(addr) => {
+            let data = Ipv4CidrKey::new(key.namespace, key.inode, u32::from(addr));
+            map_v4.remove(&Key::new(prefix_len, data))
+        }
+        IpAddr::V6(addr) => {
+            let data = Ipv6CidrKey::new(key.namespace, key.inode, addr.octets());
+            map_v6.remove(&Key::new(prefix_len, data))
+        }
+    };
+    match res {
+        Ok(()) | Err(MapError::KeyNotFound) => Ok(()),
+        Err(e) => Err(e.into()),
+    }
 }
 
 impl SocketConnect {
@@ -29,34 +229,48 @@ impl SocketConnect {
         &mut self,
         policy: policy::SocketConnect,
     ) -> Result<(), EbpfguardError> {
+        let old = if self.audit.enabled() {
+            audit::previous(self.list_policies().await?, |p| p.subject == policy.subject)
+        } else {
+            None
+        };
+        let (audit_key, new) = (audit::value(&policy.subject), audit::value(&policy));
+
         let bin_inode = {
             let mut map = INODE_SUBJECT_MAP.lock().await;
-            map.resolve_path(policy.subject)?
+            map.resolve_path(&*self.resolver, policy.subject)?
         };
 
         let (allow_v4, allow_v6) = policy.allow.into_ebpf();
         let (deny_v4, deny_v6) = policy.deny.into_ebpf();
 
-        self.allowed_map_v4.insert(bin_inode, allow_v4, 0)?;
-        self.denied_map_v4.insert(bin_inode, deny_v4, 0)?;
-        self.allowed_map_v6.insert(bin_inode, allow_v6, 0)?;
-        self.denied_map_v6.insert(bin_inode, deny_v6, 0)?;
+        let key = subject_key(self.generation.policy_namespace()?, bin_inode);
+        self.allowed_map_v4.insert(key, allow_v4, 0)?;
+        self.denied_map_v4.insert(key, deny_v4, 0)?;
+        self.allowed_map_v6.insert(key, allow_v6, 0)?;
+        self.denied_map_v6.insert(key, deny_v6, 0)?;
+
+        self.audit.record("add_policy", audit_key, old, Some(new));
 
         Ok(())
     }
 
     pub async fn list_policies(&self) -> Result<Vec<policy::SocketConnect>, EbpfguardError> {
         let mut policies = Vec::new();
+        let namespace = self.generation.policy_namespace()?;
 
         for res in self.allowed_map_v4.iter() {
-            let (bin_inode, allow_v4) = res?;
-            let deny_v4 = self.denied_map_v4.get(&bin_inode, 0)?;
-            let allow_v6 = self.allowed_map_v6.get(&bin_inode, 0)?;
-            let deny_v6 = self.denied_map_v6.get(&bin_inode, 0)?;
+            let (key, allow_v4) = res?;
+            if key.namespace != namespace {
+                continue;
+            }
+            let deny_v4 = self.denied_map_v4.get(&key, 0)?;
+            let allow_v6 = self.allowed_map_v6.get(&key, 0)?;
+            let deny_v6 = self.denied_map_v6.get(&key, 0)?;
 
             let subject = {
                 let map = INODE_SUBJECT_MAP.lock().await;
-                map.resolve_inode(bin_inode)
+                map.resolve_inode(key.inode)
             };
 
             let allow = if allow_v4.all() && allow_v6.all() {
@@ -106,8 +320,570 @@ impl SocketConnect {
         Ok(policies)
     }
 
+    /// Adds a `socket_connect_ports` policy, replacing the previous one of
+    /// its subject.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use ebpfguard::{
+    ///     policy::{PolicySubject, PortRange, SocketConnectPorts},
+    ///     PolicyManager,
+    /// };
+    ///
+    /// # #[tokio::main(flavor = "current_thread")]
+    /// # async fn main() {
+    /// let mut policy_manager = PolicyManager::with_default_path().unwrap();
+    /// let mut socket_connect = policy_manager.attach_socket_connect().unwrap();
+    /// socket_connect
+    ///     .add_ports_policy(SocketConnectPorts {
+    ///         subject: PolicySubject::All,
+    ///         allow: vec![],
+    ///         deny: vec!["6660-6669".parse().unwrap(), PortRange::port(6697)],
+    ///     })
+    ///     .await
+    ///     .unwrap();
+    /// # }
+    /// ```
+    pub async fn add_ports_policy(
+        &mut self,
+        policy: policy::SocketConnectPorts,
+    ) -> Result<(), EbpfguardError> {
+        let (allow, deny) = policy.to_ebpf()?;
+        let old = if self.audit.enabled() {
+            audit::previous(self.list_ports_policies().await?, |p| {
+                p.subject == policy.subject
+            })
+        } else {
+            None
+        };
+        let (audit_key, new) = (audit::value(&policy.subject), audit::value(&policy));
+
+        let bin_inode = {
+            let mut map = INODE_SUBJECT_MAP.lock().await;
+            map.resolve_path(&*self.resolver, policy.subject)?
+        };
+
+        let key = subject_key(self.generation.policy_namespace()?, bin_inode);
+        self.allowed_ports_map.insert(key, allow, 0)?;
+        self.denied_ports_map.insert(key, deny, 0)?;
+
+        self.audit
+            .record("add_ports_policy", audit_key, old, Some(new));
+
+        Ok(())
+    }
+
+    pub async fn list_ports_policies(
+        &self,
+    ) -> Result<Vec<policy::SocketConnectPorts>, EbpfguardError> {
+        let mut policies = Vec::new();
+        let namespace = self.generation.policy_namespace()?;
+
+        for res in self.denied_ports_map.iter() {
+            let (key, deny) = res?;
+            if key.namespace != namespace {
+                continue;
+            }
+            let allow = match self.allowed_ports_map.get(&key, 0) {
+                Ok(allow) => allow,
+                Err(MapError::KeyNotFound) => ebpf_policy::PortRanges::default(),
+                Err(e) => return Err(e.into()),
+            };
+
+            let subject = {
+                let map = INODE_SUBJECT_MAP.lock().await;
+                map.resolve_inode(key.inode)
+            };
+
+            policies.push(policy::SocketConnectPorts::from_ebpf(
+                subject, &allow, &deny,
+            ));
+        }
+
+        Ok(policies)
+    }
+
+    /// Returns the key layout of the protected address maps in the namespace.
+    pub fn key_layout(&self) -> Result<policy::KeyLayout, EbpfguardError> {
+        let config = self.config.get()?;
+        Ok(policy::KeyLayout::from_flags(config.key_layout))
+    }
+
+    /// Sets the key layout of the protected address maps in the namespace,
+    /// which has to be done before adding `socket_connect_protected`
+    /// policies: the existing keys would not match the new layout.
+    pub fn set_key_layout(&mut self, layout: policy::KeyLayout) -> Result<(), EbpfguardError> {
+        let old = self.key_layout()?;
+        if layout == old {
+            return Ok(());
+        }
+        if self.has_protected_keys()? {
+            return Err(EbpfguardError::KeyLayoutInUse);
+        }
+
+        self.config
+            .update(|config| config.key_layout = layout.to_flags())?;
+
+        self.audit.record(
+            "set_key_layout",
+            Value::Null,
+            Some(audit::value(&old)),
+            Some(audit::value(&layout)),
+        );
+
+        Ok(())
+    }
+
+    /// Checks for keys of both generations of the policies, since the
+    /// programs might still read the ones of the previous generation.
+    fn has_protected_keys(&self) -> Result<bool, EbpfguardError> {
+        for key in self.protected_map_v4.keys() {
+            if untagged_namespace(key?.namespace) == self.namespace {
+                return Ok(true);
+            }
+        }
+        for key in self.protected_map_v6.keys() {
+            if untagged_namespace(key?.namespace) == self.namespace {
+                return Ok(true);
+            }
+        }
+        Ok(false)
+    }
+
+    /// Adds a `socket_connect_protected` policy. It has to have a port if
+    /// the key layout of the namespace includes the port, and no port
+    /// otherwise.
+    pub async fn add_protected_policy(
+        &mut self,
+        policy: policy::SocketConnectProtected,
+    ) -> Result<(), EbpfguardError> {
+        let old = if self.audit.enabled() {
+            audit::previous(self.list_protected_policies().await?, |p| {
+                p.addr == policy.addr && p.port == policy.port
+            })
+        } else {
+            None
+        };
+        let (audit_key, new) = (
+            audit::value(&(policy.addr, policy.port)),
+            audit::value(&policy),
+        );
+
+        let layout = self.key_layout()?;
+        if layout.port != policy.port.is_some() {
+            return Err(EbpfguardError::KeyLayoutMismatch(layout.port));
+        }
+        let flags = layout.to_flags();
+        let port = policy.port.unwrap_or(0);
+
+        let binaries = resolve_binaries(&*self.resolver, policy.allow).await?;
+
+        let namespace = self.generation.policy_namespace()?;
+        match policy.addr {
+            IpAddr::V4(addr) => {
+                let key = Ipv4Key::new(flags, namespace, u32::from(addr), port);
+                self.protected_map_v4.insert(key, binaries, 0)?
+            }
+            IpAddr::V6(addr) => {
+                let key = Ipv6Key::new(flags, namespace, addr.octets(), port);
+                self.protected_map_v6.insert(key, binaries, 0)?
+            }
+        }
+
+        self.audit
+            .record("add_protected_policy", audit_key, old, Some(new));
+
+        Ok(())
+    }
+
+    pub async fn list_protected_policies(
+        &self,
+    ) -> Result<Vec<policy::SocketConnectProtected>, EbpfguardError> {
+        let mut policies = Vec::new();
+        let with_port = self.key_layout()?.port;
+        let namespace = self.generation.policy_namespace()?;
+
+        for res in self.protected_map_v4.iter() {
+            let (key, binaries) = res?;
+            if key.namespace != namespace {
+                continue;
+            }
+            policies.push(policy::SocketConnectProtected {
+                addr: IpAddr::V4(Ipv4Addr::from(key.addr)),
+                port: with_port.then_some(key.port),
+                allow: binaries_paths(&binaries).await,
+            });
+        }
+
+        for res in self.protected_map_v6.iter() {
+            let (key, binaries) = res?;
+            if key.namespace != namespace {
+                continue;
+            }
+            policies.push(policy::SocketConnectProtected {
+                addr: IpAddr::V6(Ipv6Addr::from(key.addr)),
+                port: with_port.then_some(key.port),
+                allow: binaries_paths(&binaries).await,
+            });
+        }
+
+        Ok(policies)
+    }
+
+    /// Adds a `socket_connect_metadata` policy, replacing the previous one of
+    /// the subject. New CIDRs are added before the ones of the previous
+    /// policy are removed, so addresses denied by both stay denied.
+    pub async fn add_metadata_policy(
+        &mut self,
+        policy: policy::SocketConnectMetadata,
+    ) -> Result<(), EbpfguardError> {
+        let old = if self.audit.enabled() {
+            audit::previous(self.list_metadata_policies().await, |p| {
+                p.subject == policy.subject
+            })
+        } else {
+            None
+        };
+        let (audit_key, new) = (audit::value(&policy.subject), audit::value(&policy));
+
+        let cidrs = policy.cidrs()?;
+        let bin_inode = {
+            let mut map = INODE_SUBJECT_MAP.lock().await;
+            map.resolve_path(&*self.resolver, policy.subject)?
+        };
+        let key = subject_key(self.namespace, bin_inode);
+
+        let installed = self
+            .metadata
+            .get(&key)
+            .map(|rules| rules.cidrs.clone())
+            .unwrap_or_default();
+        let count = |v4| {
+            self.metadata
+                .iter()
+                .filter(|(k, _)| **k != key)
+                .flat_map(|(_, rules)| &rules.cidrs)
+                .chain(&cidrs)
+                .filter(|cidr| cidr.addr().is_ipv4() == v4)
+                .count()
+        };
+        let max = ebpf_policy::MAX_METADATA_CIDRS as usize;
+        if count(true) > max || count(false) > max {
+            return Err(EbpfguardError::TooManyCidrs(max));
+        }
+
+        let policy_key = InodeKey {
+            namespace: self.generation.policy_namespace()?,
+            ..key
+        };
+        for cidr in cidrs.iter().filter(|cidr| !installed.contains(cidr)) {
+            insert_cidr(
+                &mut self.metadata_map_v4,
+                &mut self.metadata_map_v6,
+                policy_key,
+                *cidr,
+            )?;
+        }
+        for cidr in installed.iter().filter(|cidr| !cidrs.contains(cidr)) {
+            remove_cidr(
+                &mut self.metadata_map_v4,
+                &mut self.metadata_map_v6,
+                policy_key,
+                *cidr,
+            )?;
+        }
+        self.metadata.insert(
+            key,
+            MetadataRules {
+                metadata: policy.metadata,
+                link_local: policy.link_local,
+                cidrs,
+            },
+        );
+
+        self.audit
+            .record("add_metadata_policy", audit_key, old, Some(new));
+
+        Ok(())
+    }
+
+    pub async fn list_metadata_policies(&self) -> Vec<policy::SocketConnectMetadata> {
+        let map = INODE_SUBJECT_MAP.lock().await;
+        self.metadata
+            .iter()
+            .map(|(key, rules)| policy::SocketConnectMetadata {
+                subject: map.resolve_inode(key.inode),
+                metadata: rules.metadata.clone(),
+                link_local: rules.link_local,
+            })
+            .collect()
+    }
+
+    /// Sets the database translating ASN and country selectors of
+    /// `socket_connect_geo` policies to CIDRs, and translates the selectors
+    /// of already added policies with it.
+    pub async fn set_cidr_database<D: CidrDatabase + 'static>(
+        &mut self,
+        database: D,
+    ) -> Result<(), EbpfguardError> {
+        let mut geo = self.geo.lock().await;
+        geo.database = Some(Box::new(database));
+        geo.refresh()?;
+
+        self.audit
+            .record("set_cidr_database", Value::Null, None, None);
+
+        Ok(())
+    }
+
+    /// Adds a `socket_connect_geo` policy. ASN and country selectors require
+    /// a database (see [`SocketConnect::set_cidr_database`]).
+    ///
+    /// If the CIDRs don't fit in the maps, the policy is not added.
+    pub async fn add_geo_policy(
+        &mut self,
+        policy: policy::SocketConnectGeo,
+    ) -> Result<(), EbpfguardError> {
+        let old = if self.audit.enabled() {
+            audit::previous(self.list_geo_policies().await?, |p| {
+                p.subject == policy.subject
+            })
+        } else {
+            None
+        };
+        let (audit_key, new) = (audit::value(&policy.subject), audit::value(&policy));
+
+        let bin_inode = {
+            let mut map = INODE_SUBJECT_MAP.lock().await;
+            map.resolve_path(&*self.resolver, policy.subject)?
+        };
+        let key = subject_key(self.namespace, bin_inode);
+
+        let mut geo = self.geo.lock().await;
+        let len = geo.selectors.len();
+        geo.selectors
+            .extend(policy.deny.into_iter().map(|selector| (key, selector)));
+        if let Err(e) = geo.refresh() {
+            geo.selectors.truncate(len);
+            return Err(e);
+        }
+
+        self.audit
+            .record("add_geo_policy", audit_key, old, Some(new));
+
+        Ok(())
+    }
+
+    pub async fn list_geo_policies(&self) -> Result<Vec<policy::SocketConnectGeo>, EbpfguardError> {
+        let mut policies: Vec<(InodeKey, policy::SocketConnectGeo)> = Vec::new();
+
+        let geo = self.geo.lock().await;
+        for (key, selector) in geo.selectors.iter() {
+            let i = match policies.iter().position(|(k, _)| k == key) {
+                Some(i) => i,
+                None => {
+                    let subject = {
+                        let map = INODE_SUBJECT_MAP.lock().await;
+                        map.resolve_inode(key.inode)
+                    };
+                    policies.push((
+                        *key,
+                        policy::SocketConnectGeo {
+                            subject,
+                            deny: Vec::new(),
+                        },
+                    ));
+                    policies.len() - 1
+                }
+            };
+            policies[i].1.deny.push(selector.clone());
+        }
+
+        Ok(policies.into_iter().map(|(_, policy)| policy).collect())
+    }
+
+    /// Starts a background task reloading the CIDR database and translating
+    /// the selectors of `socket_connect_geo` policies again every `period`.
+    ///
+    /// Networks which moved between autonomous systems or countries are
+    /// matched by their old ones until the database is updated and the next
+    /// refresh runs. A refresh which fails (e.g. because the database grew
+    /// past the capacity of the maps) keeps the previous CIDRs. Abort the
+    /// returned handle to stop the watcher.
+    pub fn watch_geo(&self, period: Duration) -> JoinHandle<()> {
+        let geo = self.geo.clone();
+
+        task::spawn(async move {
+            let mut interval = tokio::time::interval(period);
+            loop {
+                interval.tick().await;
+                let mut geo = geo.lock().await;
+                if let Some(database) = geo.database.as_mut() {
+                    if let Err(e) = database.reload() {
+                        warn!("failed to reload the CIDR database: {e}");
+                        continue;
+                    }
+                }
+                if let Err(e) = geo.refresh() {
+                    warn!("failed to refresh socket_connect_geo policies: {e}");
+                }
+            }
+        })
+    }
+
+    /// Limits connects to the destination in the namespace to
+    /// `limit.connects` in each window of `limit.window`, counted across all
+    /// processes, or removes the limit if `None`. Once the limit is reached,
+    /// connects allowed by the policies are denied with
+    /// [`Reason::RateLimit`](crate::alerts::Reason::RateLimit) until the
+    /// window ends.
+    ///
+    /// The destination is an address (as a host CIDR) or a CIDR whose
+    /// addresses share one counter. An address in several rate-limited CIDRs
+    /// counts only against the longest of them. Changing the limit of a
+    /// destination keeps its current window.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use std::time::Duration;
+    ///
+    /// use ebpfguard::{policy::ConnectRateLimit, PolicyManager};
+    ///
+    /// let mut policy_manager = PolicyManager::with_default_path().unwrap();
+    /// let mut socket_connect = policy_manager.attach_socket_connect().unwrap();
+    ///
+    /// socket_connect
+    ///     .set_rate_limit(
+    ///         "10.0.0.0/24".parse().unwrap(),
+    ///         Some(ConnectRateLimit {
+    ///             connects: 100,
+    ///             window: Duration::from_secs(1),
+    ///         }),
+    ///     )
+    ///     .unwrap();
+    /// ```
+    pub fn set_rate_limit(
+        &mut self,
+        destination: Cidr,
+        limit: Option<policy::ConnectRateLimit>,
+    ) -> Result<(), EbpfguardError> {
+        let rates = self.rate_entries()?;
+        let current = rates
+            .iter()
+            .find(|(cidr, _)| *cidr == destination)
+            .map(|(_, rate)| *rate);
+
+        let prefix_len = CIDR_KEY_PREFIX_LEN + u32::from(destination.prefix_len());
+        match limit {
+            Some(limit) => {
+                // Longest prefix lookups don't tell which CIDR matched, so
+                // each destination gets a counter of its own.
+                let id = match current {
+                    Some(rate) => rate.id,
+                    None => rates.iter().map(|(_, rate)| rate.id).max().unwrap_or(0) + 1,
+                };
+                let rate = limit.to_ebpf(id)?;
+                match destination.addr() {
+                    IpAddr::V4(addr) => {
+                        let data =
+                            Ipv4CidrKey::new(self.namespace, INODE_WILDCARD, u32::from(addr));
+                        self.rate_map_v4
+                            .insert(&Key::new(prefix_len, data), rate, 0)?
+                    }
+                    IpAddr::V6(addr) => {
+                        let data = Ipv6CidrKey::new(self.namespace, INODE_WILDCARD, addr.octets());
+                        self.rate_map_v6
+                            .insert(&Key::new(prefix_len, data), rate, 0)?
+                    }
+                }
+            }
+            None => {
+                if let Some(rate) = current {
+                    match destination.addr() {
+                        IpAddr::V4(addr) => {
+                            let data =
+                                Ipv4CidrKey::new(self.namespace, INODE_WILDCARD, u32::from(addr));
+                            self.rate_map_v4.remove(&Key::new(prefix_len, data))?
+                        }
+                        IpAddr::V6(addr) => {
+                            let data =
+                                Ipv6CidrKey::new(self.namespace, INODE_WILDCARD, addr.octets());
+                            self.rate_map_v6.remove(&Key::new(prefix_len, data))?
+                        }
+                    }
+                    // The counter may be taken by another destination next.
+                    match self
+                        .rate_windows_map
+                        .remove(&RateKey::new(self.namespace, rate.id))
+                    {
+                        Ok(()) | Err(MapError::KeyNotFound) => {}
+                        Err(e) => return Err(e.into()),
+                    }
+                }
+            }
+        }
+
+        self.audit.record(
+            "set_rate_limit",
+            audit::value(&destination),
+            current.map(|rate| audit::value(&policy::ConnectRateLimit::from_ebpf(&rate))),
+            limit.map(|limit| audit::value(&limit)),
+        );
+
+        Ok(())
+    }
+
+    /// Returns the connect rate limits of the namespace by destination.
+    pub fn rate_limits(&self) -> Result<Vec<(Cidr, policy::ConnectRateLimit)>, EbpfguardError> {
+        Ok(self
+            .rate_entries()?
+            .into_iter()
+            .map(|(cidr, rate)| (cidr, policy::ConnectRateLimit::from_ebpf(&rate)))
+            .collect())
+    }
+
+    fn rate_entries(&self) -> Result<Vec<(Cidr, ebpf_policy::ConnectRate)>, EbpfguardError> {
+        let mut rates = Vec::new();
+
+        for key in self.rate_map_v4.keys() {
+            let key = key?;
+            // Copied out, since fields of LPM trie keys can't be borrowed.
+            let data = key.data;
+            if u32::from_be(data.namespace) != self.namespace {
+                continue;
+            }
+            let addr = Ipv4Addr::from(u32::from_be(data.addr));
+            let cidr = Cidr::new(
+                IpAddr::V4(addr),
+                (key.prefix_len - CIDR_KEY_PREFIX_LEN) as u8,
+            )?;
+            rates.push((cidr, self.rate_map_v4.get(&key, 0)?));
+        }
+        for key in self.rate_map_v6.keys() {
+            let key = key?;
+            let data = key.data;
+            if u32::from_be(data.namespace) != self.namespace {
+                continue;
+            }
+            let addr = Ipv6Addr::from(data.addr);
+            let cidr = Cidr::new(
+                IpAddr::V6(addr),
+                (key.prefix_len - CIDR_KEY_PREFIX_LEN) as u8,
+            )?;
+            rates.push((cidr, self.rate_map_v6.get(&key, 0)?));
+        }
+
+        Ok(rates)
+    }
+
     pub async fn alerts(&mut self) -> Result<Receiver<alerts::SocketConnect>, EbpfguardError> {
-        perf_array_alerts::<ebpf_alerts::SocketConnect, alerts::SocketConnect>(&mut self.perf_array)
-            .await
+        perf_array_alerts::<ebpf_alerts::SocketConnect, alerts::SocketConnect>(
+            &mut self.perf_array,
+            self.namespace,
+            &self.monitor,
+        )
+        .await
     }
 }
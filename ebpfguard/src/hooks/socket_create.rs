@@ -0,0 +1,212 @@
+use std::sync::Arc;
+
+use aya::{
+    maps::{HashMap, MapData, MapError},
+    programs::lsm::LsmLink,
+};
+use ebpfguard_common::{
+    alerts as ebpf_alerts,
+    policy::{self as ebpf_policy, untagged_namespace, InodeKey},
+};
+use serde_yaml::Value;
+use tokio::sync::mpsc::Receiver;
+
+use crate::{
+    alerts,
+    audit::{self, AuditLog},
+    error::EbpfguardError,
+    fs::InodeResolver,
+    generation::Generation,
+    health::HookMonitor,
+    policy::{self, inode::subject_key},
+};
+
+use super::{perf_array_alerts, AlertArray, INODE_SUBJECT_MAP};
+
+pub struct SocketCreate {
+    #[allow(dead_code)]
+    pub(crate) program_link: Option<LsmLink>,
+    /// Slots of the allowed and denied maps, see [`SocketCreate::swap_policies`].
+    pub(crate) allowed_maps: [HashMap<MapData, InodeKey, ebpf_policy::SocketKinds>; 2],
+    pub(crate) denied_maps: [HashMap<MapData, InodeKey, ebpf_policy::SocketKinds>; 2],
+    pub(crate) slot_map: HashMap<MapData, u32, u32>,
+    pub(crate) audit: AuditLog,
+    pub(crate) monitor: HookMonitor,
+    pub(crate) perf_array: AlertArray,
+    pub(crate) resolver: Arc<dyn InodeResolver>,
+    pub(crate) generation: Generation,
+    pub(crate) namespace: u32,
+}
+
+impl SocketCreate {
+    pub async fn add_policy(&mut self, policy: policy::SocketCreate) -> Result<(), EbpfguardError> {
+        let old = if self.audit.enabled() {
+            audit::previous(self.list_policies().await?, |p| p.subject == policy.subject)
+        } else {
+            None
+        };
+        let (audit_key, new) = (audit::value(&policy.subject), audit::value(&policy));
+
+        let allow = policy.allow.into_ebpf()?;
+        let deny = policy.deny.into_ebpf()?;
+        let bin_inode = {
+            let mut map = INODE_SUBJECT_MAP.lock().await;
+            map.resolve_path(&*self.resolver, policy.subject)?
+        };
+
+        let slot = active_slot(&self.slot_map, self.namespace)?;
+        let key = subject_key(self.generation.policy_namespace()?, bin_inode);
+        self.allowed_maps[slot].insert(key, allow, 0)?;
+        self.denied_maps[slot].insert(key, deny, 0)?;
+
+        self.audit.record("add_policy", audit_key, old, Some(new));
+
+        Ok(())
+    }
+
+    /// Replaces all policies of the namespace with `policies` at once: the
+    /// policies are written to the slot of the maps which the eBPF program
+    /// doesn't read, which then becomes the one it reads with a single map
+    /// update. Every socket creation is decided either by the previous
+    /// policies or by the new ones, never by a mix of them, unlike when
+    /// adding the policies one by one.
+    ///
+    /// When any policy is invalid or its subject can't be resolved, nothing
+    /// is changed. The slot switched away from keeps the previous policies
+    /// until the next swap clears it, so programs which read the slot before
+    /// the switch still find them.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use ebpfguard::{
+    ///     policy::{PolicySubject, SocketCreate, SocketFamily, SocketKind, SocketKinds},
+    ///     PolicyManager,
+    /// };
+    ///
+    /// # #[tokio::main(flavor = "current_thread")]
+    /// # async fn main() {
+    /// let mut policy_manager = PolicyManager::with_default_path().unwrap();
+    /// let mut socket_create = policy_manager.attach_socket_create().unwrap();
+    ///
+    /// socket_create
+    ///     .swap_policies(vec![SocketCreate {
+    ///         subject: PolicySubject::All,
+    ///         allow: SocketKinds::All,
+    ///         deny: SocketKinds::Kinds(vec![SocketKind {
+    ///             family: SocketFamily::Packet,
+    ///             socket_type: None,
+    ///         }]),
+    ///     }])
+    ///     .await
+    ///     .unwrap();
+    /// # }
+    /// ```
+    pub async fn swap_policies(
+        &mut self,
+        policies: Vec<policy::SocketCreate>,
+    ) -> Result<(), EbpfguardError> {
+        let old = if self.audit.enabled() {
+            audit::previous(self.list_policies().await?, |_| true)
+        } else {
+            None
+        };
+        let new = audit::value(&policies);
+
+        let namespace = self.generation.policy_namespace()?;
+        let mut entries = Vec::with_capacity(policies.len());
+        for policy in policies {
+            let allow = policy.allow.into_ebpf()?;
+            let deny = policy.deny.into_ebpf()?;
+            let bin_inode = {
+                let mut map = INODE_SUBJECT_MAP.lock().await;
+                map.resolve_path(&*self.resolver, policy.subject)?
+            };
+            entries.push((subject_key(namespace, bin_inode), allow, deny));
+        }
+
+        let slot = 1 - active_slot(&self.slot_map, self.namespace)?;
+        clear_namespace(&mut self.allowed_maps[slot], self.namespace)?;
+        clear_namespace(&mut self.denied_maps[slot], self.namespace)?;
+        for (key, allow, deny) in entries {
+            self.allowed_maps[slot].insert(key, allow, 0)?;
+            self.denied_maps[slot].insert(key, deny, 0)?;
+        }
+        self.slot_map.insert(self.namespace, slot as u32, 0)?;
+
+        self.audit
+            .record("swap_policies", Value::Null, old, Some(new));
+
+        Ok(())
+    }
+
+    pub async fn list_policies(&self) -> Result<Vec<policy::SocketCreate>, EbpfguardError> {
+        let mut policies = Vec::new();
+        let namespace = self.generation.policy_namespace()?;
+
+        let slot = active_slot(&self.slot_map, self.namespace)?;
+        for res in self.allowed_maps[slot].iter() {
+            let (key, allow) = res?;
+            if key.namespace != namespace {
+                continue;
+            }
+            let deny = self.denied_maps[slot].get(&key, 0)?;
+
+            let subject = {
+                let map = INODE_SUBJECT_MAP.lock().await;
+                map.resolve_inode(key.inode)
+            };
+
+            policies.push(policy::SocketCreate {
+                subject,
+                allow: allow.into(),
+                deny: deny.into(),
+            });
+        }
+
+        Ok(policies)
+    }
+
+    pub async fn alerts(&mut self) -> Result<Receiver<alerts::SocketCreate>, EbpfguardError> {
+        perf_array_alerts::<ebpf_alerts::SocketCreate, alerts::SocketCreate>(
+            &mut self.perf_array,
+            self.namespace,
+            &self.monitor,
+        )
+        .await
+    }
+}
+
+/// Returns the slot of the socket create maps which the eBPF program reads
+/// in the namespace, 0 until the first swap.
+pub(crate) fn active_slot(
+    slot_map: &HashMap<MapData, u32, u32>,
+    namespace: u32,
+) -> Result<usize, EbpfguardError> {
+    match slot_map.get(&namespace, 0) {
+        Ok(slot) => Ok((slot & 1) as usize),
+        Err(MapError::KeyNotFound) => Ok(0),
+        Err(e) => Err(e.into()),
+    }
+}
+
+/// Removes all entries of the namespace from the map, in both generations of
+/// the policies.
+fn clear_namespace(
+    map: &mut HashMap<MapData, InodeKey, ebpf_policy::SocketKinds>,
+    namespace: u32,
+) -> Result<(), EbpfguardError> {
+    let keys = map
+        .keys()
+        .filter_map(|key| key.ok())
+        .filter(|key| untagged_namespace(key.namespace) == namespace)
+        .collect::<Vec<_>>();
+    for key in keys {
+        match map.remove(&key) {
+            Ok(()) | Err(MapError::KeyNotFound) => {}
+            Err(e) => return Err(e.into()),
+        }
+    }
+
+    Ok(())
+}
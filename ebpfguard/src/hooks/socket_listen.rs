@@ -0,0 +1,103 @@
+use std::sync::Arc;
+
+use aya::{
+    maps::{HashMap, MapData},
+    programs::lsm::LsmLink,
+};
+use ebpfguard_common::{
+    alerts as ebpf_alerts,
+    policy::{self as ebpf_policy, InodeKey},
+};
+use tokio::sync::mpsc::Receiver;
+
+use crate::{
+    alerts,
+    audit::{self, AuditLog},
+    error::EbpfguardError,
+    fs::InodeResolver,
+    generation::Generation,
+    health::HookMonitor,
+    policy::{self, inode::subject_key},
+};
+
+use super::{perf_array_alerts, AlertArray, INODE_SUBJECT_MAP};
+
+pub struct SocketListen {
+    #[allow(dead_code)]
+    pub(crate) program_link: Option<LsmLink>,
+    pub(crate) allowed_map: HashMap<MapData, InodeKey, ebpf_policy::Ports>,
+    pub(crate) denied_map: HashMap<MapData, InodeKey, ebpf_policy::Ports>,
+    pub(crate) options_map: HashMap<MapData, InodeKey, u8>,
+    pub(crate) audit: AuditLog,
+    pub(crate) monitor: HookMonitor,
+    pub(crate) perf_array: AlertArray,
+    pub(crate) resolver: Arc<dyn InodeResolver>,
+    pub(crate) generation: Generation,
+    pub(crate) namespace: u32,
+}
+
+impl SocketListen {
+    pub async fn add_policy(&mut self, policy: policy::SocketListen) -> Result<(), EbpfguardError> {
+        let old = if self.audit.enabled() {
+            audit::previous(self.list_policies().await?, |p| p.subject == policy.subject)
+        } else {
+            None
+        };
+        let (audit_key, new) = (audit::value(&policy.subject), audit::value(&policy));
+
+        let bin_inode = {
+            let mut map = INODE_SUBJECT_MAP.lock().await;
+            map.resolve_path(&*self.resolver, policy.subject)?
+        };
+
+        let allow: ebpf_policy::Ports = policy.allow.into();
+        let deny: ebpf_policy::Ports = policy.deny.into();
+        let options = policy::SocketOption::to_flags(&policy.deny_options);
+
+        let key = subject_key(self.generation.policy_namespace()?, bin_inode);
+        self.allowed_map.insert(key, allow, 0)?;
+        self.denied_map.insert(key, deny, 0)?;
+        self.options_map.insert(key, options, 0)?;
+
+        self.audit.record("add_policy", audit_key, old, Some(new));
+
+        Ok(())
+    }
+
+    pub async fn list_policies(&self) -> Result<Vec<policy::SocketListen>, EbpfguardError> {
+        let mut policies = Vec::new();
+        let namespace = self.generation.policy_namespace()?;
+
+        for res in self.allowed_map.iter() {
+            let (key, allow) = res?;
+            if key.namespace != namespace {
+                continue;
+            }
+            let deny = self.denied_map.get(&key, 0)?;
+            let options = self.options_map.get(&key, 0).unwrap_or(0);
+
+            let subject = {
+                let map = INODE_SUBJECT_MAP.lock().await;
+                map.resolve_inode(key.inode)
+            };
+
+            policies.push(policy::SocketListen {
+                subject,
+                allow: allow.into(),
+                deny: deny.into(),
+                deny_options: policy::SocketOption::from_flags(options),
+            });
+        }
+
+        Ok(policies)
+    }
+
+    pub async fn alerts(&mut self) -> Result<Receiver<alerts::SocketListen>, EbpfguardError> {
+        perf_array_alerts::<ebpf_alerts::SocketListen, alerts::SocketListen>(
+            &mut self.perf_array,
+            self.namespace,
+            &self.monitor,
+        )
+        .await
+    }
+}
@@ -0,0 +1,164 @@
+use std::sync::Arc;
+
+use aya::{
+    maps::{HashMap, MapData, MapError},
+    programs::lsm::LsmLink,
+};
+use ebpfguard_common::{
+    alerts as ebpf_alerts,
+    policy::{GidKey, InodeKey},
+};
+use serde_yaml::Value;
+use tokio::sync::mpsc::Receiver;
+
+use crate::{
+    alerts,
+    audit::{self, AuditLog},
+    error::EbpfguardError,
+    fs::InodeResolver,
+    generation::Generation,
+    health::HookMonitor,
+    policy::{self, inode::subject_key},
+};
+
+use super::{perf_array_alerts, AlertArray, INODE_SUBJECT_MAP};
+
+pub struct TaskFixSetgid {
+    #[allow(dead_code)]
+    pub(crate) program_link: Option<LsmLink>,
+    pub(crate) allowed_map: HashMap<MapData, InodeKey, u8>,
+    pub(crate) denied_map: HashMap<MapData, InodeKey, u8>,
+    pub(crate) privileged_map: HashMap<MapData, GidKey, u8>,
+    pub(crate) audit: AuditLog,
+    pub(crate) monitor: HookMonitor,
+    pub(crate) perf_array: AlertArray,
+    pub(crate) resolver: Arc<dyn InodeResolver>,
+    pub(crate) generation: Generation,
+    pub(crate) namespace: u32,
+}
+
+impl TaskFixSetgid {
+    pub async fn add_policy(
+        &mut self,
+        policy: policy::TaskFixSetgid,
+    ) -> Result<(), EbpfguardError> {
+        let old = if self.audit.enabled() {
+            audit::previous(self.list_policies().await?, |p| p.subject == policy.subject)
+        } else {
+            None
+        };
+        let (audit_key, new) = (audit::value(&policy.subject), audit::value(&policy));
+
+        let bin_inode = {
+            let mut map = INODE_SUBJECT_MAP.lock().await;
+            map.resolve_path(&*self.resolver, policy.subject)?
+        };
+
+        let key = subject_key(self.generation.policy_namespace()?, bin_inode);
+        if policy.allow {
+            self.allowed_map.insert(key, 0, 0)?;
+        } else {
+            self.denied_map.insert(key, 0, 0)?;
+        }
+
+        self.audit.record("add_policy", audit_key, old, Some(new));
+
+        Ok(())
+    }
+
+    pub async fn list_policies(&self) -> Result<Vec<policy::TaskFixSetgid>, EbpfguardError> {
+        let mut policies = Vec::new();
+        let namespace = self.generation.policy_namespace()?;
+
+        for (map, allow) in [(&self.allowed_map, true), (&self.denied_map, false)] {
+            for res in map.iter() {
+                let (key, _) = res?;
+                if key.namespace != namespace {
+                    continue;
+                }
+
+                let subject = {
+                    let map = INODE_SUBJECT_MAP.lock().await;
+                    map.resolve_inode(key.inode)
+                };
+
+                policies.push(policy::TaskFixSetgid { subject, allow });
+            }
+        }
+
+        Ok(policies)
+    }
+
+    /// Sets the groups treated as privileged in the namespace, in addition to
+    /// group 0, replacing the previous ones. Only switches to privileged
+    /// groups are checked against the policies.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use ebpfguard::PolicyManager;
+    ///
+    /// let mut policy_manager = PolicyManager::with_default_path().unwrap();
+    /// let mut task_fix_setgid = policy_manager.attach_task_fix_setgid().unwrap();
+    ///
+    /// // wheel and docker
+    /// task_fix_setgid.set_privileged_gids(&[10, 998]).unwrap();
+    /// ```
+    pub fn set_privileged_gids(&mut self, gids: &[u32]) -> Result<(), EbpfguardError> {
+        let old = if self.audit.enabled() {
+            Some(audit::value(&self.privileged_gids()?))
+        } else {
+            None
+        };
+
+        let stale = self
+            .privileged_map
+            .keys()
+            .filter_map(|key| key.ok())
+            .filter(|key| key.namespace == self.namespace && !gids.contains(&key.gid))
+            .collect::<Vec<_>>();
+        for key in stale {
+            match self.privileged_map.remove(&key) {
+                Ok(()) | Err(MapError::KeyNotFound) => {}
+                Err(e) => return Err(e.into()),
+            }
+        }
+        for gid in gids {
+            self.privileged_map
+                .insert(GidKey::new(self.namespace, *gid), 0, 0)?;
+        }
+
+        self.audit.record(
+            "set_privileged_gids",
+            Value::Null,
+            old,
+            Some(audit::value(&gids)),
+        );
+
+        Ok(())
+    }
+
+    /// Returns the groups treated as privileged in the namespace, besides
+    /// group 0, in ascending order.
+    pub fn privileged_gids(&self) -> Result<Vec<u32>, EbpfguardError> {
+        let mut gids = Vec::new();
+        for key in self.privileged_map.keys() {
+            let key = key?;
+            if key.namespace == self.namespace {
+                gids.push(key.gid);
+            }
+        }
+        gids.sort_unstable();
+
+        Ok(gids)
+    }
+
+    pub async fn alerts(&mut self) -> Result<Receiver<alerts::TaskFixSetgid>, EbpfguardError> {
+        perf_array_alerts::<ebpf_alerts::TaskFixSetgid, alerts::TaskFixSetgid>(
+            &mut self.perf_array,
+            self.namespace,
+            &self.monitor,
+        )
+        .await
+    }
+}
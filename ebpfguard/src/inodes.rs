@@ -0,0 +1,246 @@
+//! Inodes referenced by the keys of the policy and settings maps, see
+//! [`PolicyManager::referenced_inodes`](crate::PolicyManager::referenced_inodes).
+
+use std::{
+    collections::HashSet,
+    path::{Path, PathBuf},
+};
+
+use aya::{
+    maps::{HashMap, Map, MapData},
+    Pod,
+};
+use ebpfguard_common::policy::{
+    self as ebpf_policy, untagged_namespace, FileInodeKey, HookKey, InodeKey, SocketBindGrantKey,
+};
+
+use crate::{error::EbpfguardError, fs::InodeResolver};
+
+/// Whether an inode referenced by the maps still resolves to a file, see
+/// [`PolicyManager::inode_status`](crate::PolicyManager::inode_status).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum InodeStatus {
+    /// The path the inode was resolved from still resolves to it.
+    Resolves(PathBuf),
+    /// The path the inode was resolved from resolves to another inode or
+    /// not at all anymore, e.g. the file was replaced by an upgrade or
+    /// removed. Its entries don't match the file at the path.
+    Stale(PathBuf),
+    /// The inode wasn't resolved from a path by this process, e.g. its
+    /// policy was added by a previous run, so it can't be checked.
+    Unknown,
+}
+
+impl InodeStatus {
+    /// Checks the inode against the path it was resolved from, if known,
+    /// resolving the path again with the resolver.
+    pub(crate) fn check(resolver: &dyn InodeResolver, inode: u64, path: Option<PathBuf>) -> Self {
+        match path {
+            Some(path) => match resolver.resolve(&path) {
+                Ok(resolved) if resolved.inode == inode => InodeStatus::Resolves(path),
+                _ => InodeStatus::Stale(path),
+            },
+            None => InodeStatus::Unknown,
+        }
+    }
+}
+
+/// Key of a map which references inodes.
+trait InodeRefs: Pod {
+    /// Adds the inodes referenced by the key, without the wildcard policy.
+    /// Keys of policy maps are added without the generation of the policies.
+    fn collect(&self, inodes: &mut HashSet<InodeKey>);
+}
+
+impl InodeRefs for InodeKey {
+    fn collect(&self, inodes: &mut HashSet<InodeKey>) {
+        if self.wildcard == 0 {
+            inodes.insert(InodeKey::new(
+                untagged_namespace(self.namespace),
+                self.inode,
+            ));
+        }
+    }
+}
+
+impl InodeRefs for FileInodeKey {
+    fn collect(&self, inodes: &mut HashSet<InodeKey>) {
+        let namespace = untagged_namespace(self.namespace);
+        if self.wildcard == 0 {
+            inodes.insert(InodeKey::new(namespace, self.binprm_inode));
+        }
+        inodes.insert(InodeKey::new(namespace, self.inode));
+    }
+}
+
+impl InodeRefs for HookKey {
+    fn collect(&self, inodes: &mut HashSet<InodeKey>) {
+        if self.wildcard == 0 {
+            inodes.insert(InodeKey::new(self.namespace, self.inode));
+        }
+    }
+}
+
+impl InodeRefs for SocketBindGrantKey {
+    fn collect(&self, inodes: &mut HashSet<InodeKey>) {
+        if self.wildcard == 0 {
+            inodes.insert(InodeKey::new(self.namespace, self.binprm_inode));
+        }
+    }
+}
+
+type CollectFn = fn(MapData, &'static str, &mut HashSet<InodeKey>) -> Result<(), EbpfguardError>;
+
+/// Map keyed by binaries or files, with the function collecting its inodes.
+struct InodeMap {
+    name: &'static str,
+    collect: CollectFn,
+}
+
+impl InodeMap {
+    const fn hash<K: InodeRefs, V: Pod>(name: &'static str) -> Self {
+        Self {
+            name,
+            collect: collect_hash::<K, V>,
+        }
+    }
+}
+
+fn collect_hash<K: InodeRefs, V: Pod>(
+    map: MapData,
+    name: &'static str,
+    inodes: &mut HashSet<InodeKey>,
+) -> Result<(), EbpfguardError> {
+    let map = HashMap::<_, K, V>::try_from(Map::HashMap(map))
+        .map_err(|e| EbpfguardError::from_map_error(name, e))?;
+    for key in map.keys() {
+        key?.collect(inodes);
+    }
+    Ok(())
+}
+
+/// Policy maps (both slots of the slotted ones) and settings maps whose keys
+/// reference binaries or files.
+static INODE_MAPS: &[InodeMap] = &[
+    InodeMap::hash::<InodeKey, u64>("ALERT_WINDOWS"),
+    InodeMap::hash::<InodeKey, u8>("ALERT_CHANNELS"),
+    InodeMap::hash::<InodeKey, ebpf_policy::RetryEscalation>("RETRY_ESCALATIONS"),
+    InodeMap::hash::<HookKey, u16>("MESSAGE_IDS"),
+    InodeMap::hash::<InodeKey, ebpf_policy::Paths>("ALLOWED_FILE_OPEN"),
+    InodeMap::hash::<InodeKey, ebpf_policy::Paths>("DENIED_FILE_OPEN"),
+    InodeMap::hash::<FileInodeKey, u8>("ALLOWED_FILE_OPEN_INODES"),
+    InodeMap::hash::<FileInodeKey, u8>("DENIED_FILE_OPEN_INODES"),
+    InodeMap::hash::<InodeKey, ebpf_policy::Binaries>("PROTECTED_FILE_OPEN"),
+    InodeMap::hash::<InodeKey, ebpf_policy::Paths>("ALLOWED_INODE_CREATE"),
+    InodeMap::hash::<InodeKey, ebpf_policy::Paths>("DENIED_INODE_CREATE"),
+    InodeMap::hash::<InodeKey, u8>("ALLOWED_TASK_FIX_SETUID"),
+    InodeMap::hash::<InodeKey, u8>("DENIED_TASK_FIX_SETUID"),
+    InodeMap::hash::<InodeKey, u8>("ALLOWED_TASK_FIX_SETGID"),
+    InodeMap::hash::<InodeKey, u8>("DENIED_TASK_FIX_SETGID"),
+    InodeMap::hash::<InodeKey, u8>("ALLOWED_SB_MOUNT"),
+    InodeMap::hash::<InodeKey, u8>("DENIED_SB_MOUNT"),
+    InodeMap::hash::<InodeKey, u8>("ALLOWED_SB_REMOUNT"),
+    InodeMap::hash::<InodeKey, u8>("DENIED_SB_REMOUNT"),
+    InodeMap::hash::<InodeKey, u8>("ALLOWED_SB_UMOUNT"),
+    InodeMap::hash::<InodeKey, u8>("DENIED_SB_UMOUNT"),
+    InodeMap::hash::<InodeKey, u8>("ALLOWED_BPF"),
+    InodeMap::hash::<InodeKey, u8>("DENIED_BPF"),
+    InodeMap::hash::<InodeKey, ebpf_policy::Ports>("ALLOWED_SOCKET_BIND"),
+    InodeMap::hash::<InodeKey, ebpf_policy::Ports>("DENIED_SOCKET_BIND"),
+    InodeMap::hash::<InodeKey, ebpf_policy::Ports>("ALLOWED_SOCKET_BIND_V4"),
+    InodeMap::hash::<InodeKey, ebpf_policy::Ports>("DENIED_SOCKET_BIND_V4"),
+    InodeMap::hash::<InodeKey, ebpf_policy::Ports>("ALLOWED_SOCKET_BIND_V6"),
+    InodeMap::hash::<InodeKey, ebpf_policy::Ports>("DENIED_SOCKET_BIND_V6"),
+    InodeMap::hash::<InodeKey, u8>("ALLOWED_SOCKET_BIND_PACKET"),
+    InodeMap::hash::<InodeKey, u8>("DENIED_SOCKET_BIND_PACKET"),
+    InodeMap::hash::<SocketBindGrantKey, u8>("GRANTS_SOCKET_BIND"),
+    InodeMap::hash::<InodeKey, u8>("ESCALATE_SOCKET_BIND"),
+    InodeMap::hash::<InodeKey, u8>("OPTIONS_SOCKET_BIND"),
+    InodeMap::hash::<InodeKey, u32>("BIND_LIMIT_SOCKET_BIND"),
+    InodeMap::hash::<InodeKey, ebpf_policy::SocketKinds>("ALLOWED_SOCKET_CREATE"),
+    InodeMap::hash::<InodeKey, ebpf_policy::SocketKinds>("DENIED_SOCKET_CREATE"),
+    InodeMap::hash::<InodeKey, ebpf_policy::SocketKinds>("ALLOWED_SOCKET_CREATE_1"),
+    InodeMap::hash::<InodeKey, ebpf_policy::SocketKinds>("DENIED_SOCKET_CREATE_1"),
+    InodeMap::hash::<InodeKey, ebpf_policy::Ports>("ALLOWED_SOCKET_LISTEN"),
+    InodeMap::hash::<InodeKey, ebpf_policy::Ports>("DENIED_SOCKET_LISTEN"),
+    InodeMap::hash::<InodeKey, u8>("OPTIONS_SOCKET_LISTEN"),
+    InodeMap::hash::<InodeKey, ebpf_policy::Ipv4Addrs>("ALLOWED_SOCKET_CONNECT_V4"),
+    InodeMap::hash::<InodeKey, ebpf_policy::Ipv4Addrs>("DENIED_SOCKET_CONNECT_V4"),
+    InodeMap::hash::<InodeKey, ebpf_policy::Ipv6Addrs>("ALLOWED_SOCKET_CONNECT_V6"),
+    InodeMap::hash::<InodeKey, ebpf_policy::Ipv6Addrs>("DENIED_SOCKET_CONNECT_V6"),
+    InodeMap::hash::<InodeKey, ebpf_policy::PortRanges>("ALLOWED_SOCKET_CONNECT_PORTS"),
+    InodeMap::hash::<InodeKey, ebpf_policy::PortRanges>("DENIED_SOCKET_CONNECT_PORTS"),
+];
+
+/// Returns the inodes referenced by the keys of the maps, of all namespaces.
+pub(crate) fn referenced_inodes(maps_path: &Path) -> Result<HashSet<InodeKey>, EbpfguardError> {
+    let mut inodes = HashSet::new();
+    for map in INODE_MAPS {
+        let data = MapData::from_pin(maps_path.join(map.name))
+            .map_err(|e| EbpfguardError::from_map_error(map.name, e))?;
+        (map.collect)(data, map.name, &mut inodes)?;
+    }
+    Ok(inodes)
+}
+
+#[cfg(test)]
+mod test {
+    use ebpfguard_common::policy::generation_namespace;
+
+    use super::*;
+    use crate::fs::LocalInodeResolver;
+
+    #[test]
+    fn test_collect() {
+        let binary = InodeKey::new(1, 100);
+        let mut inodes = HashSet::new();
+        binary.collect(&mut inodes);
+        InodeKey::wildcard(1).collect(&mut inodes);
+        FileInodeKey::new(binary, 200).collect(&mut inodes);
+        FileInodeKey::new(InodeKey::wildcard(1), 300).collect(&mut inodes);
+        HookKey::new(binary, 1).collect(&mut inodes);
+        HookKey::new(InodeKey::wildcard(1), 1).collect(&mut inodes);
+        // A binary with the inode of the wildcard policy.
+        HookKey::new(InodeKey::new(1, 0), 1).collect(&mut inodes);
+        SocketBindGrantKey::new(binary, 8080).collect(&mut inodes);
+        // Same inode in another namespace.
+        InodeKey::new(2, 100).collect(&mut inodes);
+        // Keys of both generations of the policies.
+        InodeKey::new(generation_namespace(1, 1), 100).collect(&mut inodes);
+        FileInodeKey::new(InodeKey::new(generation_namespace(2, 1), 100), 200).collect(&mut inodes);
+
+        let mut inodes: Vec<_> = inodes
+            .into_iter()
+            .map(|key| (key.namespace, key.inode))
+            .collect();
+        inodes.sort();
+        assert_eq!(
+            inodes,
+            [(1, 0), (1, 100), (1, 200), (1, 300), (2, 100), (2, 200)]
+        );
+    }
+
+    #[test]
+    fn test_status() {
+        let exe = std::env::current_exe().unwrap();
+        let resolver = &LocalInodeResolver;
+        let inode = resolver.resolve(&exe).unwrap().inode;
+        assert_eq!(
+            InodeStatus::check(resolver, inode, Some(exe.clone())),
+            InodeStatus::Resolves(exe.clone())
+        );
+        assert_eq!(
+            InodeStatus::check(resolver, inode + 1, Some(exe.clone())),
+            InodeStatus::Stale(exe)
+        );
+        let removed = PathBuf::from("/nonexistent/ebpfguard");
+        assert_eq!(
+            InodeStatus::check(resolver, inode, Some(removed.clone())),
+            InodeStatus::Stale(removed)
+        );
+        assert_eq!(
+            InodeStatus::check(resolver, inode, None),
+            InodeStatus::Unknown
+        );
+    }
+}
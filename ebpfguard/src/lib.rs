@@ -7,11 +7,21 @@
 //! the need to use them directly.
 
 pub mod alerts;
+pub mod audit;
+mod config;
 pub mod error;
 pub mod fs;
+mod generation;
+pub mod health;
 pub mod hooks;
+pub mod inodes;
 pub mod manager;
+pub mod messages;
+pub mod plan;
 pub mod policy;
+pub mod raw;
+pub mod simulate;
+pub mod sink;
 
 pub use manager::PolicyManager;
 pub use policy::inode::InodeSubjectMap;
@@ -1,24 +1,148 @@
-use std::path::{Path, PathBuf};
+use std::{
+    collections::{HashMap as StdHashMap, HashSet},
+    path::{Path, PathBuf},
+    sync::{Arc, Weak},
+    time::{Duration, Instant},
+};
 
 use aya::{
     include_bytes_aligned,
-    programs::{lsm::LsmLink, Lsm},
-    Bpf, BpfLoader, Btf,
+    maps::{HashMap, LpmTrie, Map, MapData, MapError},
+    programs::{
+        links::{FdLink, PinnedLink},
+        lsm::LsmLink,
+        Lsm,
+    },
+    Bpf, BpfLoader, Btf, Pod,
+};
+use ebpfguard_common::{
+    alerts::{CHANNEL_DEFAULT, MESSAGE_NONE},
+    consts::NAMESPACE_DEFAULT,
+    policy::{
+        self as ebpf_policy, CommKey, DevInodeKey, FileInodeKey, GidKey, HookKey, InodeKey,
+        Ipv4CidrKey, Ipv4Key, Ipv6CidrKey, Ipv6Key,
+    },
+};
+use serde_yaml::Value;
+use tokio::{
+    sync::{
+        mpsc::{self, Receiver},
+        Mutex,
+    },
+    task,
+    time::MissedTickBehavior,
 };
 
 use crate::{
+    alerts::{AlertBuffers, Heartbeat, HookStatus},
+    audit::{self, AuditEvent, AuditLog},
+    config::ConfigMap,
     error::EbpfguardError,
+    fs::{self, InodeResolver, LocalInodeResolver},
+    generation::Generation,
+    health::{AlertStats, Health, HookHealth, HookMonitor, MapHealth},
     hooks::{
-        bprm_check_security::BprmCheckSecurity, file_open::FileOpen, sb_mount::SbMount,
-        sb_remount::SbRemount, sb_umount::SbUmount, socket_bind::SocketBind,
-        socket_connect::SocketConnect, task_fix_setuid::TaskFixSetuid, All,
+        self,
+        bpf::Bpf as BpfHook,
+        bprm_check_security::BprmCheckSecurity,
+        file_open::{FileOpen, GlobRules},
+        inode_create::InodeCreate,
+        sb_mount::SbMount,
+        sb_remount::SbRemount,
+        sb_umount::SbUmount,
+        socket_bind::{self, Grants, SocketBind},
+        socket_connect::{GeoRules, SocketConnect},
+        socket_create::SocketCreate,
+        socket_listen::SocketListen,
+        task_fix_setgid::TaskFixSetgid,
+        task_fix_setuid::TaskFixSetuid,
+        AlertArray, All,
     },
+    inodes::{self, InodeStatus},
+    messages::Hook,
+    plan::{self, MapSnapshot, PolicyDiff},
+    policy::{Policy, PolicySubject, RetryEscalation},
+    raw::RawMap,
 };
 
+/// Names of all LSM programs in the eBPF object.
+const PROGRAMS: [&str; 13] = [
+    "bpf",
+    "bprm_check_security",
+    "file_open",
+    "inode_create",
+    "sb_mount",
+    "sb_remount",
+    "sb_umount",
+    "socket_bind",
+    "socket_connect",
+    "socket_create",
+    "socket_listen",
+    "task_fix_setgid",
+    "task_fix_setuid",
+];
+
+/// Manages eBPF programs and policy maps.
+///
+/// Policies are scoped to a policy namespace (see [`PolicyManager::set_namespace`]),
+/// which allows running independent policy sets, e.g. one per tenant, on the
+/// same host. All namespaces share the same maps: the namespace ID is a part
+/// of every map key, and the eBPF programs look up the namespace of the
+/// current process (by its cgroup) before looking up the policies, so the
+/// number of maps doesn't grow with the number of namespaces.
 pub struct PolicyManager {
     bpf: Bpf,
+    maps_path: PathBuf,
+    namespace: u32,
+    links_path: Option<PathBuf>,
+    hooks: Vec<ManagedHook>,
+    maps_health: Option<(Instant, Vec<MapHealth>)>,
+    alert_buffers: AlertBuffers,
+    audit: AuditLog,
+    resolver: Arc<dyn InodeResolver>,
+    created_at: Instant,
+}
+
+/// Hook handed out by the policy manager, tracked for health checks.
+#[derive(Clone)]
+struct ManagedHook {
+    name: &'static str,
+    /// Whether the program was attached by the policy manager.
+    intended: bool,
+    /// Pin of the link, if links are pinned.
+    pin: Option<PathBuf>,
+    alive: Weak<()>,
+    alerts: Arc<AlertStats>,
+    checked_at: Instant,
+    checked_lost: u64,
 }
 
+impl ManagedHook {
+    /// Whether the program is attached, by the pinned link if links are
+    /// pinned, otherwise by the link held by the hook.
+    fn attached(&self) -> bool {
+        match &self.pin {
+            Some(pin) => pin.exists(),
+            None => self.intended && self.alive.strong_count() > 0,
+        }
+    }
+}
+
+/// Capacity of the per-binary policy maps, as defined in
+/// `ebpfguard-ebpf/src/maps.rs`.
+const POLICY_MAP_ENTRIES: usize = 1024;
+
+/// Capacity of the per-file inode maps of `file_open`.
+const INODE_MAP_ENTRIES: usize = 16384;
+
+/// Longest alert rate-limit window accepted by
+/// [`PolicyManager::set_alert_window`].
+pub const MAX_ALERT_WINDOW: Duration = Duration::from_secs(3600);
+
+/// Minimum time between two walks of the policy maps by
+/// [`PolicyManager::health`].
+const MAPS_HEALTH_INTERVAL: Duration = Duration::from_secs(30);
+
 impl PolicyManager {
     /// Default path for storage of eBPFGuard maps
     pub const DEFAULT_BPFFS_MAPS_PATH: &str = "/sys/fs/bpf/ebpfguard_default";
@@ -72,259 +196,1217 @@ impl PolicyManager {
                 "../../ebpfguard-ebpf/ebpfguard.release.obj"
             ))?;
 
-        Ok(Self { bpf })
+        verify_maps(&bpf)?;
+
+        Ok(Self {
+            bpf,
+            maps_path: bpf_path.as_ref().to_path_buf(),
+            namespace: NAMESPACE_DEFAULT,
+            links_path: None,
+            hooks: Vec::new(),
+            maps_health: None,
+            alert_buffers: AlertBuffers::default(),
+            audit: AuditLog::default(),
+            resolver: Arc::new(LocalInodeResolver),
+            created_at: Instant::now(),
+        })
+    }
+
+    /// Returns the policy namespace which hooks managed from now on are
+    /// scoped to.
+    pub fn namespace(&self) -> u32 {
+        self.namespace
+    }
+
+    /// Sets the policy namespace which hooks managed from now on are scoped
+    /// to. Their policies and alerts don't affect other namespaces.
+    /// Namespaces have to be below 2^31, managing hooks in the others fails
+    /// with [`ReservedNamespace`](EbpfguardError::ReservedNamespace).
+    ///
+    /// Each hook can be managed only once per policy manager. To manage the
+    /// same hook in several namespaces at once, create a policy manager per
+    /// namespace with [`PolicyManager::new`] and the same maps path.
+    ///
+    /// The hooks of all namespaces share the perf event arrays of alerts.
+    /// The policy managers of a process read each array once and pass each
+    /// alert on to the `alerts()` receivers of its namespace. Processes
+    /// reading the alerts of the same maps path take the arrays over from
+    /// each other, so read the alerts of all namespaces from one process.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use ebpfguard::PolicyManager;
+    ///
+    /// let mut policy_manager = PolicyManager::with_default_path().unwrap();
+    /// policy_manager.assign_cgroup("/sys/fs/cgroup/tenant-a", 1).unwrap();
+    /// policy_manager.set_namespace(1);
+    /// let mut tenant_a = policy_manager.manage_all().unwrap();
+    /// ```
+    pub fn set_namespace(&mut self, namespace: u32) {
+        self.namespace = namespace;
+    }
+
+    /// Pins links of programs attached from now on in the given directory
+    /// (on a bpf filesystem), one per program, named after the program.
+    ///
+    /// Pinned programs stay attached after the policy manager is dropped and
+    /// the process exits, until the pin is removed. Attaching a program which
+    /// already has a pinned link adopts the existing attachment instead, so
+    /// a restarted agent doesn't leave a gap in enforcement. Use
+    /// [`PolicyManager::upgrade`] to replace the adopted programs with the
+    /// ones of this policy manager.
+    ///
+    /// The directory must not be the maps path used with
+    /// [`PolicyManager::with_default_path`], which removes it.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use ebpfguard::PolicyManager;
+    ///
+    /// let mut policy_manager = PolicyManager::new("/sys/fs/bpf/mypolicies").unwrap();
+    /// policy_manager.pin_links("/sys/fs/bpf/mylinks").unwrap();
+    /// let mut socket_bind = policy_manager.attach_socket_bind().unwrap();
+    /// ```
+    pub fn pin_links<P: AsRef<Path>>(&mut self, path: P) -> Result<(), EbpfguardError> {
+        std::fs::create_dir_all(&path)?;
+        self.links_path = Some(path.as_ref().to_path_buf());
+
+        Ok(())
+    }
+
+    /// Replaces all programs attached through links pinned in the directory
+    /// set with [`PolicyManager::pin_links`] with the programs of this policy
+    /// manager, e.g. after an upgrade of ebpfguard.
+    ///
+    /// Each new program is attached before the old one is detached. All BPF
+    /// LSM programs attached to a hook are run and any of them can deny the
+    /// operation, so during the overlap both programs are enforced. Programs
+    /// without a pinned link are left alone.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use ebpfguard::PolicyManager;
+    ///
+    /// let mut policy_manager = PolicyManager::new("/sys/fs/bpf/mypolicies").unwrap();
+    /// policy_manager.pin_links("/sys/fs/bpf/mylinks").unwrap();
+    /// policy_manager.upgrade().unwrap();
+    /// ```
+    pub fn upgrade(&mut self) -> Result<(), EbpfguardError> {
+        let links_path = self
+            .links_path
+            .clone()
+            .ok_or(EbpfguardError::LinksNotPinned)?;
+
+        let mut upgraded = Vec::new();
+        for name in PROGRAMS {
+            let pin = links_path.join(name);
+            if pin.exists() {
+                self.upgrade_program(name, &pin)?;
+                upgraded.push(name);
+            }
+        }
+
+        self.audit.scoped(self.namespace, None).record(
+            "upgrade",
+            audit::value(&links_path),
+            None,
+            Some(audit::value(&upgraded)),
+        );
+
+        Ok(())
+    }
+
+    /// Assigns processes of the given cgroup (by path in the cgroup v2
+    /// hierarchy) to a policy namespace. Processes of cgroups without an
+    /// assigned namespace are in the default namespace. Namespaces are not
+    /// inherited by child cgroups, and have to be below 2^31.
+    pub fn assign_cgroup<P: AsRef<Path>>(
+        &mut self,
+        cgroup: P,
+        namespace: u32,
+    ) -> Result<(), EbpfguardError> {
+        if namespace & ebpf_policy::GENERATION_BIT != 0 {
+            return Err(EbpfguardError::ReservedNamespace(namespace));
+        }
+        // On cgroup v2, the cgroup ID is the inode number of its directory,
+        // which the cgroup filesystem always gives, whatever the resolver.
+        let cgroup_id = LocalInodeResolver.resolve(cgroup.as_ref())?.inode;
+        let name = "POLICY_NAMESPACES";
+        let map = self
+            .bpf
+            .map_mut(name)
+            .ok_or_else(|| EbpfguardError::MapNotFound(name.to_owned()))?;
+        let mut map: HashMap<&mut MapData, u64, u32> =
+            HashMap::try_from(map).map_err(|e| EbpfguardError::from_map_error(name, e))?;
+        let old = map.get(&cgroup_id, 0).ok();
+        map.insert(cgroup_id, namespace, 0)?;
+
+        self.audit.scoped(self.namespace, None).record(
+            "assign_cgroup",
+            audit::value(&cgroup.as_ref()),
+            old.map(|old| audit::value(&old)),
+            Some(audit::value(&namespace)),
+        );
+
+        Ok(())
+    }
+
+    /// Sets the message ID of the rules of the hook for the subject in the
+    /// current namespace. Alerts of operations denied by these rules carry
+    /// the ID, which user space maps to a text with
+    /// [`Messages`](crate::messages::Messages). Message ID 0 removes the
+    /// message.
+    ///
+    /// Denials by the policy of all binaries (e.g. a default deny) carry the
+    /// message of [`PolicySubject::All`]. Denials by the policy of a binary
+    /// carry its message, or the one of [`PolicySubject::All`] if it has none.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use ebpfguard::{messages::Hook, policy::PolicySubject, PolicyManager};
+    ///
+    /// let mut policy_manager = PolicyManager::with_default_path().unwrap();
+    /// policy_manager
+    ///     .set_message_id(Hook::SocketBind, &PolicySubject::All, 1)
+    ///     .unwrap();
+    /// ```
+    pub fn set_message_id(
+        &mut self,
+        hook: Hook,
+        subject: &PolicySubject,
+        message_id: u16,
+    ) -> Result<(), EbpfguardError> {
+        let key = match subject {
+            PolicySubject::Binary(path) => {
+                InodeKey::new(self.namespace, fs::binary_inode(&*self.resolver, path)?)
+            }
+            PolicySubject::All => InodeKey::wildcard(self.namespace),
+        };
+        let key = HookKey::new(key, hook.id());
+
+        let name = "MESSAGE_IDS";
+        let map = self
+            .bpf
+            .map_mut(name)
+            .ok_or_else(|| EbpfguardError::MapNotFound(name.to_owned()))?;
+        let mut map: HashMap<&mut MapData, HookKey, u16> =
+            HashMap::try_from(map).map_err(|e| EbpfguardError::from_map_error(name, e))?;
+        let old = map.get(&key, 0).ok();
+        if message_id == MESSAGE_NONE {
+            match map.remove(&key) {
+                Ok(()) | Err(MapError::KeyNotFound) => {}
+                Err(e) => return Err(e.into()),
+            }
+        } else {
+            map.insert(key, message_id, 0)?;
+        }
+
+        self.audit.scoped(self.namespace, Some(hook)).record(
+            "set_message_id",
+            audit::value(subject),
+            old.map(|old| audit::value(&old)),
+            (message_id != MESSAGE_NONE).then(|| audit::value(&message_id)),
+        );
+
+        Ok(())
+    }
+
+    /// Sets the alert rate-limit window of the binary (or, with
+    /// [`PolicySubject::All`], the default window of all binaries) in the
+    /// current namespace. `None` removes the window, so the binary falls back
+    /// to the default window, and no window at all means no rate limiting.
+    ///
+    /// Within the window after an alert, further alerts of the same binary
+    /// and hook are dropped in the kernel, while the operations are still
+    /// denied. A zero window disables rate limiting for the binary even if a
+    /// default window is set. Windows longer than [`MAX_ALERT_WINDOW`] are
+    /// rejected.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use std::time::Duration;
+    ///
+    /// use ebpfguard::{policy::PolicySubject, PolicyManager};
+    ///
+    /// let mut policy_manager = PolicyManager::with_default_path().unwrap();
+    /// policy_manager
+    ///     .set_alert_window(&PolicySubject::All, Some(Duration::from_secs(10)))
+    ///     .unwrap();
+    /// ```
+    pub fn set_alert_window(
+        &mut self,
+        subject: &PolicySubject,
+        window: Option<Duration>,
+    ) -> Result<(), EbpfguardError> {
+        if let Some(window) = window {
+            if window > MAX_ALERT_WINDOW {
+                return Err(EbpfguardError::AlertWindowTooLong(MAX_ALERT_WINDOW));
+            }
+        }
+        let key = match subject {
+            PolicySubject::Binary(path) => {
+                InodeKey::new(self.namespace, fs::binary_inode(&*self.resolver, path)?)
+            }
+            PolicySubject::All => InodeKey::wildcard(self.namespace),
+        };
+
+        let name = "ALERT_WINDOWS";
+        let map = self
+            .bpf
+            .map_mut(name)
+            .ok_or_else(|| EbpfguardError::MapNotFound(name.to_owned()))?;
+        let mut map: HashMap<&mut MapData, InodeKey, u64> =
+            HashMap::try_from(map).map_err(|e| EbpfguardError::from_map_error(name, e))?;
+        let old = map.get(&key, 0).ok().map(Duration::from_nanos);
+        match window {
+            Some(window) => map.insert(key, window.as_nanos() as u64, 0)?,
+            None => match map.remove(&key) {
+                Ok(()) | Err(MapError::KeyNotFound) => {}
+                Err(e) => return Err(e.into()),
+            },
+        }
+
+        self.audit.scoped(self.namespace, None).record(
+            "set_alert_window",
+            audit::value(subject),
+            old.map(|old| audit::value(&old)),
+            window.map(|window| audit::value(&window)),
+        );
+
+        Ok(())
+    }
+
+    /// Sets the retry escalation of the binary (or, with
+    /// [`PolicySubject::All`], the default escalation of all binaries) in the
+    /// current namespace. `None` removes the escalation, so the binary falls
+    /// back to the default one, and no escalation at all means denials are
+    /// not counted.
+    ///
+    /// Denials are counted per process and hook, in windows starting at the
+    /// first denial. The denial reaching `denials` within a window, and every
+    /// later one in the window, is alerted with
+    /// [`Reason::Persistent`](crate::alerts::Reason::Persistent) instead of
+    /// its own reason, keeping the message ID of the rule. The
+    /// alert of the denial reaching the threshold isn't dropped by the alert
+    /// rate limit (see [`set_alert_window`](Self::set_alert_window)), the
+    /// later ones are.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use std::time::Duration;
+    ///
+    /// use ebpfguard::{
+    ///     policy::{PolicySubject, RetryEscalation},
+    ///     PolicyManager,
+    /// };
+    ///
+    /// let mut policy_manager = PolicyManager::with_default_path().unwrap();
+    /// policy_manager
+    ///     .set_retry_escalation(
+    ///         &PolicySubject::All,
+    ///         Some(RetryEscalation {
+    ///             denials: 5,
+    ///             window: Duration::from_secs(10),
+    ///         }),
+    ///     )
+    ///     .unwrap();
+    /// ```
+    pub fn set_retry_escalation(
+        &mut self,
+        subject: &PolicySubject,
+        escalation: Option<RetryEscalation>,
+    ) -> Result<(), EbpfguardError> {
+        let value = escalation.map(RetryEscalation::to_ebpf).transpose()?;
+        let key = match subject {
+            PolicySubject::Binary(path) => {
+                InodeKey::new(self.namespace, fs::binary_inode(&*self.resolver, path)?)
+            }
+            PolicySubject::All => InodeKey::wildcard(self.namespace),
+        };
+
+        let name = "RETRY_ESCALATIONS";
+        let map = self
+            .bpf
+            .map_mut(name)
+            .ok_or_else(|| EbpfguardError::MapNotFound(name.to_owned()))?;
+        let mut map: HashMap<&mut MapData, InodeKey, ebpf_policy::RetryEscalation> =
+            HashMap::try_from(map).map_err(|e| EbpfguardError::from_map_error(name, e))?;
+        let old = map
+            .get(&key, 0)
+            .ok()
+            .map(|old| RetryEscalation::from_ebpf(&old));
+        match value {
+            Some(value) => map.insert(key, value, 0)?,
+            None => match map.remove(&key) {
+                Ok(()) | Err(MapError::KeyNotFound) => {}
+                Err(e) => return Err(e.into()),
+            },
+        }
+
+        self.audit.scoped(self.namespace, None).record(
+            "set_retry_escalation",
+            audit::value(subject),
+            old.map(|old| audit::value(&old)),
+            escalation.map(|escalation| audit::value(&escalation)),
+        );
+
+        Ok(())
+    }
+
+    /// Sets the alert channel of the binary (or, with [`PolicySubject::All`],
+    /// the default channel of all binaries) in the current namespace. Alerts
+    /// of the binary carry the channel, which a
+    /// [`ChannelRouter`](crate::sink::ChannelRouter) routes them to sinks by.
+    /// Channel [`CHANNEL_DEFAULT`] removes the channel, so the binary falls
+    /// back to the default channel.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use ebpfguard::{policy::PolicySubject, PolicyManager};
+    ///
+    /// let mut policy_manager = PolicyManager::with_default_path().unwrap();
+    /// policy_manager
+    ///     .set_alert_channel(&PolicySubject::Binary("/usr/sbin/payments".into()), 1)
+    ///     .unwrap();
+    /// ```
+    pub fn set_alert_channel(
+        &mut self,
+        subject: &PolicySubject,
+        channel: u8,
+    ) -> Result<(), EbpfguardError> {
+        let key = match subject {
+            PolicySubject::Binary(path) => {
+                InodeKey::new(self.namespace, fs::binary_inode(&*self.resolver, path)?)
+            }
+            PolicySubject::All => InodeKey::wildcard(self.namespace),
+        };
+
+        let name = "ALERT_CHANNELS";
+        let map = self
+            .bpf
+            .map_mut(name)
+            .ok_or_else(|| EbpfguardError::MapNotFound(name.to_owned()))?;
+        let mut map: HashMap<&mut MapData, InodeKey, u8> =
+            HashMap::try_from(map).map_err(|e| EbpfguardError::from_map_error(name, e))?;
+        let old = map.get(&key, 0).ok();
+        if channel == CHANNEL_DEFAULT {
+            match map.remove(&key) {
+                Ok(()) | Err(MapError::KeyNotFound) => {}
+                Err(e) => return Err(e.into()),
+            }
+        } else {
+            map.insert(key, channel, 0)?;
+        }
+
+        self.audit.scoped(self.namespace, None).record(
+            "set_alert_channel",
+            audit::value(subject),
+            old.map(|old| audit::value(&old)),
+            (channel != CHANNEL_DEFAULT).then(|| audit::value(&channel)),
+        );
+
+        Ok(())
+    }
+
+    /// Sets the sizes of the buffers alerts of hooks managed from now on are
+    /// read through. Buffers which don't fit the largest alert of all hooks
+    /// are rejected, see [`AlertBuffers::check`].
+    ///
+    /// The buffers of a hook are opened when its alerts are first read in
+    /// the process, in any namespace (see [`PolicyManager::set_namespace`]),
+    /// and later readers of the hook's alerts share them.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use ebpfguard::{alerts::AlertBuffers, PolicyManager};
+    ///
+    /// let mut policy_manager = PolicyManager::with_default_path().unwrap();
+    /// policy_manager
+    ///     .set_alert_buffers(AlertBuffers {
+    ///         pages: 16,
+    ///         ..Default::default()
+    ///     })
+    ///     .unwrap();
+    /// ```
+    pub fn set_alert_buffers(&mut self, buffers: AlertBuffers) -> Result<(), EbpfguardError> {
+        buffers.check()?;
+        self.alert_buffers = buffers;
+        Ok(())
+    }
+
+    /// Sets the resolver of paths in policies to inodes, by default `stat` of
+    /// the path (see [`InodeResolver`]).
+    ///
+    /// The resolver belongs to the policy manager: it applies to the paths
+    /// the policy manager resolves after the call and to the hooks it hands
+    /// out after the call, which keep the resolver they were created with.
+    /// Policies added before keep the inodes they were resolved to. Other
+    /// policy managers of the process keep their own resolvers.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use std::{io, path::Path};
+    ///
+    /// use ebpfguard::{
+    ///     fs::{InodeResolver, LocalInodeResolver},
+    ///     PolicyManager,
+    /// };
+    /// use ebpfguard_common::policy::DevInodeKey;
+    ///
+    /// /// Resolves the paths of binaries inside the chroot of the agent.
+    /// struct ChrootResolver;
+    ///
+    /// impl InodeResolver for ChrootResolver {
+    ///     fn resolve(&self, path: &Path) -> Result<DevInodeKey, io::Error> {
+    ///         let path = path.strip_prefix("/").unwrap_or(path);
+    ///         LocalInodeResolver.resolve(&Path::new("/srv/chroot").join(path))
+    ///     }
+    /// }
+    ///
+    /// let mut policy_manager = PolicyManager::with_default_path().unwrap();
+    /// policy_manager.set_inode_resolver(ChrootResolver);
+    /// ```
+    pub fn set_inode_resolver<R: InodeResolver + 'static>(&mut self, resolver: R) {
+        self.resolver = Arc::new(resolver);
+    }
+
+    /// Returns a receiver of audit events of policy changes made through the
+    /// policy manager and its hooks, in all namespaces, see
+    /// [`audit`](crate::audit). It replaces the receiver returned by the
+    /// previous call.
+    pub fn audit_events(&mut self) -> Receiver<AuditEvent> {
+        self.audit.subscribe()
+    }
+
+    /// Returns the entries of the policy maps in the current namespace, see
+    /// [`plan`](crate::plan).
+    pub fn snapshot(&self) -> Result<MapSnapshot, EbpfguardError> {
+        plan::read_snapshot(&self.maps_path, self.namespace)
+    }
+
+    /// Returns the inodes (of binaries, files and directories) referenced by
+    /// the keys of the policy and settings maps, of all namespaces, without
+    /// the policies of all binaries. Check them with
+    /// [`inode_status`](Self::inode_status) to find the entries of files
+    /// which were replaced or removed since their policies were added.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use ebpfguard::{inodes::InodeStatus, PolicyManager};
+    ///
+    /// # #[tokio::main(flavor = "current_thread")]
+    /// # async fn main() {
+    /// let policy_manager = PolicyManager::with_default_path().unwrap();
+    /// let inodes = policy_manager.referenced_inodes().unwrap();
+    /// for (key, status) in policy_manager.inode_status(inodes).await {
+    ///     if let InodeStatus::Stale(path) = status {
+    ///         println!("namespace {}: {} is stale", key.namespace, path.display());
+    ///     }
+    /// }
+    /// # }
+    /// ```
+    pub fn referenced_inodes(&self) -> Result<HashSet<InodeKey>, EbpfguardError> {
+        inodes::referenced_inodes(&self.maps_path)
+    }
+
+    /// Checks whether the inodes still resolve to the paths they were
+    /// resolved from by this process (with the [`InodeResolver`] of the
+    /// policy manager), see [`InodeStatus`].
+    pub async fn inode_status<I>(&self, inodes: I) -> StdHashMap<InodeKey, InodeStatus>
+    where
+        I: IntoIterator<Item = InodeKey>,
+    {
+        let mut statuses = StdHashMap::new();
+        for key in inodes {
+            let path = hooks::resolved_path(key.inode).await;
+            statuses.insert(key, InodeStatus::check(&*self.resolver, key.inode, path));
+        }
+        statuses
+    }
+
+    /// Computes the changes of the policy maps which replacing all policies
+    /// of the current namespace with `policies` would make, without making
+    /// them. See [`plan`](crate::plan) for what is compared.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use ebpfguard::{
+    ///     policy::{Policy, PolicySubject, Ports, SocketBind},
+    ///     PolicyManager,
+    /// };
+    ///
+    /// let policy_manager = PolicyManager::with_default_path().unwrap();
+    /// let plan = policy_manager
+    ///     .plan(vec![Policy::SocketBind(SocketBind {
+    ///         subject: PolicySubject::All,
+    ///         allow: Ports::All,
+    ///         deny: Ports::Ports(vec![22]),
+    ///         deny_options: vec![],
+    ///         family: None,
+    ///     })])
+    ///     .unwrap();
+    /// println!("{plan}");
+    /// ```
+    pub fn plan<I>(&self, policies: I) -> Result<PolicyDiff, EbpfguardError>
+    where
+        I: IntoIterator<Item = Policy>,
+    {
+        let live = self.snapshot()?;
+        let layout = plan::key_layout(&self.maps_path, self.namespace)?;
+        let target = plan::target(&*self.resolver, policies, self.namespace, layout)?;
+
+        Ok(live.diff(&target))
+    }
+
+    /// Makes the changes of a plan in the policy maps, and invalidates the
+    /// binds cached by `socket_bind`.
+    ///
+    /// The changes are made one by one, so the eBPF programs can see a
+    /// partially applied plan, and a failing change leaves the previous ones
+    /// made. The maps are changed under the hooks, which don't learn about
+    /// the changes: policies listed by them and the rules their background
+    /// tasks refresh (glob patterns, geo selectors) are the ones added to
+    /// them. A plan computed before another change of the maps can undo
+    /// that change.
+    pub fn apply_plan(&mut self, plan: &PolicyDiff) -> Result<(), EbpfguardError> {
+        plan::write_diff(&self.maps_path, self.namespace, plan)?;
+
+        let name = "GENERATION_SOCKET_BIND";
+        let map = MapData::from_pin(self.maps_path.join(name))
+            .map_err(|e| EbpfguardError::from_map_error(name, e))?;
+        let mut map = HashMap::try_from(Map::HashMap(map))
+            .map_err(|e| EbpfguardError::from_map_error(name, e))?;
+        socket_bind::bump_generation(&mut map, self.namespace)?;
+
+        self.audit.scoped(self.namespace, None).record(
+            "apply_plan",
+            Value::Null,
+            None,
+            Some(audit::value(plan)),
+        );
+
+        Ok(())
+    }
+
+    /// Makes the changes of a plan in the policy maps as one transaction, so
+    /// no decision of a hook sees its policies partially changed, and
+    /// invalidates the binds cached by `socket_bind`.
+    ///
+    /// The plan is checked first against the entries of the maps, which
+    /// fails with [`PlanOutdated`](EbpfguardError::PlanOutdated) if they
+    /// changed since the plan was computed. The policy maps hold two
+    /// generations of the policies of each hook, and the programs read the
+    /// one which its entry in `POLICY_GENERATIONS` selects. The new policies
+    /// of each changed hook are written to the generation it doesn't read,
+    /// then the hooks are switched to them with one map update each, like the
+    /// slots of
+    /// [`SocketCreate::swap_policies`](crate::hooks::socket_create::SocketCreate::swap_policies).
+    /// Every decision sees either the old or the new policies of a hook, and
+    /// nothing is denied during the commit. A write which fails before the
+    /// switch leaves the policies the programs read intact. If undoing the
+    /// switch of the hooks fails, the error is
+    /// [`RollbackFailed`](EbpfguardError::RollbackFailed).
+    ///
+    /// The switches of several hooks are separate updates, so a decision of
+    /// one hook can see the new policies while one of another hook still sees
+    /// the old ones. During the commit, the maps hold the entries of the
+    /// changed hooks twice, so they need room for both generations. Entries
+    /// of a generation which a failed commit leaves behind aren't read, and
+    /// the next commit removes them. Policies added through the hooks while
+    /// a commit runs can be lost, and only one process should apply atomic
+    /// plans with the same maps path at a time.
+    ///
+    /// Like [`PolicyManager::apply_plan`], the hooks don't learn about the
+    /// changes.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use ebpfguard::{policy::reader::read_policies, PolicyManager};
+    ///
+    /// let mut policy_manager = PolicyManager::with_default_path().unwrap();
+    /// let plan = policy_manager
+    ///     .plan(read_policies("policies.yaml").unwrap())
+    ///     .unwrap();
+    /// policy_manager.apply_plan_atomic(&plan).unwrap();
+    /// ```
+    pub fn apply_plan_atomic(&mut self, plan: &PolicyDiff) -> Result<(), EbpfguardError> {
+        plan::write_diff_atomic(&self.maps_path, self.namespace, plan)?;
+
+        self.audit.scoped(self.namespace, None).record(
+            "apply_plan_atomic",
+            Value::Null,
+            None,
+            Some(audit::value(plan)),
+        );
+
+        Ok(())
+    }
+
+    /// Removes all policies of the hook in the current namespace, e.g. before
+    /// applying a new full set of policies, and invalidates the binds cached
+    /// by `socket_bind`. Returns the removed entries as a diff, which
+    /// [`apply_plan`](Self::apply_plan) of its
+    /// [`inverse`](PolicyDiff::inverse) restores.
+    ///
+    /// The hook can stay attached. Its policy maps are cleared one after
+    /// another, so while they are, the hook decides on the entries left and
+    /// then falls back to allowing everything, like without policies. Use
+    /// [`apply_plan_atomic`](Self::apply_plan_atomic) with a plan of the new
+    /// policies instead if there must be no such window. Like plans, it only
+    /// clears the maps of [`Policy`] rules: key layouts, exempt ports,
+    /// escalations and the other settings of the hook are kept. The hook
+    /// doesn't learn about the clear, so its background tasks (glob
+    /// patterns, geo selectors) can write the rules they refresh again.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use ebpfguard::{messages::Hook, PolicyManager};
+    ///
+    /// let mut policy_manager = PolicyManager::with_default_path().unwrap();
+    /// let removed = policy_manager.clear_hook(Hook::SocketBind).unwrap();
+    /// println!("{removed}");
+    /// ```
+    pub fn clear_hook(&mut self, hook: Hook) -> Result<PolicyDiff, EbpfguardError> {
+        let removed = plan::clear_hook(&self.maps_path, self.namespace, hook.id())?;
+
+        let name = "GENERATION_SOCKET_BIND";
+        let map = MapData::from_pin(self.maps_path.join(name))
+            .map_err(|e| EbpfguardError::from_map_error(name, e))?;
+        let mut map = HashMap::try_from(Map::HashMap(map))
+            .map_err(|e| EbpfguardError::from_map_error(name, e))?;
+        socket_bind::bump_generation(&mut map, self.namespace)?;
+
+        self.audit.scoped(self.namespace, Some(hook)).record(
+            "clear_hook",
+            Value::Null,
+            Some(audit::value(&removed)),
+            None,
+        );
+
+        Ok(removed)
+    }
+
+    /// Opens a raw handle of the pinned hash map `name`, an escape hatch
+    /// outside of the stable API. See [`raw`](crate::raw) for which maps are
+    /// safe to change directly.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use ebpfguard::PolicyManager;
+    /// use ebpfguard_common::policy::InodeKey;
+    ///
+    /// let policy_manager = PolicyManager::with_default_path().unwrap();
+    /// let mut map = policy_manager
+    ///     .raw_hash_map::<InodeKey, u32>("BIND_LIMIT_SOCKET_BIND")
+    ///     .unwrap();
+    /// // Removes the bind limits of all namespaces.
+    /// let keys = map.keys().filter_map(|key| key.ok()).collect::<Vec<_>>();
+    /// for key in keys {
+    ///     map.remove(&key).unwrap();
+    /// }
+    /// ```
+    pub fn raw_hash_map<K: Pod, V: Pod>(
+        &self,
+        name: &str,
+    ) -> Result<RawMap<HashMap<MapData, K, V>>, EbpfguardError> {
+        RawMap::open(&self.maps_path, name, Map::HashMap)
+    }
+
+    /// Opens a raw handle of the pinned LPM trie map `name`, see
+    /// [`PolicyManager::raw_hash_map`].
+    pub fn raw_lpm_trie<K: Pod, V: Pod>(
+        &self,
+        name: &str,
+    ) -> Result<RawMap<LpmTrie<MapData, K, V>>, EbpfguardError> {
+        RawMap::open(&self.maps_path, name, Map::LpmTrie)
+    }
+
+    /// Opens a raw handle of `ALLOWED_SOCKET_BIND`, see
+    /// [`PolicyManager::raw_hash_map`].
+    pub fn allowed_socket_bind_map(
+        &self,
+    ) -> Result<RawMap<HashMap<MapData, InodeKey, ebpf_policy::Ports>>, EbpfguardError> {
+        self.raw_hash_map("ALLOWED_SOCKET_BIND")
+    }
+
+    /// Opens a raw handle of `DENIED_SOCKET_BIND`, see
+    /// [`PolicyManager::raw_hash_map`].
+    pub fn denied_socket_bind_map(
+        &self,
+    ) -> Result<RawMap<HashMap<MapData, InodeKey, ebpf_policy::Ports>>, EbpfguardError> {
+        self.raw_hash_map("DENIED_SOCKET_BIND")
+    }
+
+    /// Opens a raw handle of `ALLOWED_SOCKET_CONNECT_V4`, see
+    /// [`PolicyManager::raw_hash_map`].
+    pub fn allowed_socket_connect_v4_map(
+        &self,
+    ) -> Result<RawMap<HashMap<MapData, InodeKey, ebpf_policy::Ipv4Addrs>>, EbpfguardError> {
+        self.raw_hash_map("ALLOWED_SOCKET_CONNECT_V4")
+    }
+
+    /// Opens a raw handle of `DENIED_SOCKET_CONNECT_V4`, see
+    /// [`PolicyManager::raw_hash_map`].
+    pub fn denied_socket_connect_v4_map(
+        &self,
+    ) -> Result<RawMap<HashMap<MapData, InodeKey, ebpf_policy::Ipv4Addrs>>, EbpfguardError> {
+        self.raw_hash_map("DENIED_SOCKET_CONNECT_V4")
+    }
+
+    /// Opens a raw handle of `ALLOWED_SOCKET_CONNECT_V6`, see
+    /// [`PolicyManager::raw_hash_map`].
+    pub fn allowed_socket_connect_v6_map(
+        &self,
+    ) -> Result<RawMap<HashMap<MapData, InodeKey, ebpf_policy::Ipv6Addrs>>, EbpfguardError> {
+        self.raw_hash_map("ALLOWED_SOCKET_CONNECT_V6")
+    }
+
+    /// Opens a raw handle of `DENIED_SOCKET_CONNECT_V6`, see
+    /// [`PolicyManager::raw_hash_map`].
+    pub fn denied_socket_connect_v6_map(
+        &self,
+    ) -> Result<RawMap<HashMap<MapData, InodeKey, ebpf_policy::Ipv6Addrs>>, EbpfguardError> {
+        self.raw_hash_map("DENIED_SOCKET_CONNECT_V6")
     }
 
     /// Attaches and returns a handle to all LSM hooks.
     pub fn attach_all(&mut self) -> Result<All, EbpfguardError> {
+        let bpf = self.attach_bpf()?;
         let bprm_check_security = self.attach_bprm_check_security()?;
         let file_open = self.attach_file_open()?;
+        let inode_create = self.attach_inode_create()?;
         let sb_mount = self.attach_sb_mount()?;
         let sb_remount = self.attach_sb_remount()?;
         let sb_umount = self.attach_sb_umount()?;
         let socket_bind = self.attach_socket_bind()?;
         let socket_connect = self.attach_socket_connect()?;
+        let socket_create = self.attach_socket_create()?;
+        let socket_listen = self.attach_socket_listen()?;
+        let task_fix_setgid = self.attach_task_fix_setgid()?;
         let task_fix_setuid = self.attach_task_fix_setuid()?;
 
         Ok(All {
+            bpf,
             bprm_check_security,
             file_open,
+            inode_create,
             sb_mount,
             sb_remount,
             sb_umount,
             socket_bind,
             socket_connect,
+            socket_create,
+            socket_listen,
+            task_fix_setgid,
             task_fix_setuid,
         })
     }
 
     pub fn manage_all(&mut self) -> Result<All, EbpfguardError> {
+        let bpf = self.manage_bpf()?;
         let bprm_check_security = self.manage_bprm_check_security()?;
         let file_open = self.manage_file_open()?;
+        let inode_create = self.manage_inode_create()?;
         let sb_mount = self.manage_sb_mount()?;
         let sb_remount = self.manage_sb_remount()?;
         let sb_umount = self.manage_sb_umount()?;
         let socket_bind = self.manage_socket_bind()?;
         let socket_connect = self.manage_socket_connect()?;
+        let socket_create = self.manage_socket_create()?;
+        let socket_listen = self.manage_socket_listen()?;
+        let task_fix_setgid = self.manage_task_fix_setgid()?;
         let task_fix_setuid = self.manage_task_fix_setuid()?;
 
         Ok(All {
+            bpf,
             bprm_check_security,
             file_open,
+            inode_create,
             sb_mount,
             sb_remount,
             sb_umount,
             socket_bind,
             socket_connect,
+            socket_create,
+            socket_listen,
+            task_fix_setgid,
             task_fix_setuid,
         })
     }
 
+    /// Attaches the `bpf` hook, which restricts the `bpf` syscall to the
+    /// binaries allowed by its policies, so other processes can't load
+    /// programs or change the maps enforcing the policies.
+    ///
+    /// The binary of the running process is exempt from the policies of all
+    /// namespaces before the hook is attached, since the policy manager
+    /// needs the syscall for every change of the maps. Other binaries of
+    /// policy managers (e.g. new versions) have to be exempted with
+    /// [`Bpf::exempt_binary`](BpfHook::exempt_binary) before they run: with
+    /// a deny-all policy, a binary missing from the exemptions can't open
+    /// the pinned maps to exempt itself.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use ebpfguard::{
+    ///     policy::{self, PolicySubject},
+    ///     PolicyManager,
+    /// };
+    ///
+    /// # #[tokio::main(flavor = "current_thread")]
+    /// # async fn main() {
+    /// let mut policy_manager = PolicyManager::with_default_path().unwrap();
+    /// let mut bpf = policy_manager.attach_bpf().unwrap();
+    /// bpf.add_policy(policy::Bpf {
+    ///     subject: PolicySubject::All,
+    ///     allow: false,
+    /// })
+    /// .await
+    /// .unwrap();
+    /// # }
+    /// ```
+    pub fn attach_bpf(&mut self) -> Result<BpfHook, EbpfguardError> {
+        let mut bpf = self.manage_bpf()?;
+        bpf.program_link = self.attach_program("bpf")?;
+
+        Ok(bpf)
+    }
+
+    pub fn manage_bpf(&mut self) -> Result<BpfHook, EbpfguardError> {
+        let allowed_map = self.take_map("ALLOWED_BPF")?;
+        let denied_map = self.take_map("DENIED_BPF")?;
+        let exempt_map = self.take_map("EXEMPT_BPF")?;
+        let perf_array = self.alert_array("ALERT_BPF")?;
+        let generation = self.generation(ebpf_policy::HOOK_BPF)?;
+
+        let mut bpf = BpfHook {
+            program_link: None,
+            allowed_map,
+            denied_map,
+            exempt_map,
+            audit: self.hook_audit(Hook::Bpf),
+            monitor: self.monitor("bpf"),
+            perf_array,
+            resolver: self.resolver.clone(),
+            generation,
+            namespace: self.namespace,
+        };
+        bpf.exempt_current()?;
+
+        Ok(bpf)
+    }
+
     pub fn attach_bprm_check_security(&mut self) -> Result<BprmCheckSecurity, EbpfguardError> {
         let mut bprm_check_security = self.manage_bprm_check_security()?;
-        let program_link = self.attach_program("bprm_check_security")?;
-        bprm_check_security.program_link = Some(program_link);
+        bprm_check_security.program_link = self.attach_program("bprm_check_security")?;
 
         Ok(bprm_check_security)
     }
 
     pub fn manage_bprm_check_security(&mut self) -> Result<BprmCheckSecurity, EbpfguardError> {
-        let perf_array = self
-            .bpf
-            .take_map("ALERT_BPRM_CHECK_SECURITY")
-            .unwrap()
-            .try_into()?;
+        let perf_array = self.alert_array("ALERT_BPRM_CHECK_SECURITY")?;
 
         Ok(BprmCheckSecurity {
             program_link: None,
+            monitor: self.monitor("bprm_check_security"),
             perf_array,
+            namespace: self.namespace,
         })
     }
 
     pub fn attach_file_open(&mut self) -> Result<FileOpen, EbpfguardError> {
         let mut file_open = self.manage_file_open()?;
-        let program_link = self.attach_program("file_open")?;
-        file_open.program_link = Some(program_link);
+        file_open.program_link = self.attach_program("file_open")?;
 
         Ok(file_open)
     }
 
     pub fn manage_file_open(&mut self) -> Result<FileOpen, EbpfguardError> {
-        let allowed_map = self.bpf.take_map("ALLOWED_FILE_OPEN").unwrap().try_into()?;
-        let denied_map = self.bpf.take_map("DENIED_FILE_OPEN").unwrap().try_into()?;
-        let perf_array = self.bpf.take_map("ALERT_FILE_OPEN").unwrap().try_into()?;
+        let allowed_map = self.take_map("ALLOWED_FILE_OPEN")?;
+        let denied_map = self.take_map("DENIED_FILE_OPEN")?;
+        let protected_map = self.take_map("PROTECTED_FILE_OPEN")?;
+        let allowed_inodes_map = self.take_map("ALLOWED_FILE_OPEN_INODES")?;
+        let denied_inodes_map = self.take_map("DENIED_FILE_OPEN_INODES")?;
+        let perf_array = self.alert_array("ALERT_FILE_OPEN")?;
+        let generation = self.generation(ebpf_policy::HOOK_FILE_OPEN)?;
 
         Ok(FileOpen {
             program_link: None,
             allowed_map,
             denied_map,
+            protected_map,
+            globs: Arc::new(Mutex::new(GlobRules::new(
+                allowed_inodes_map,
+                denied_inodes_map,
+                self.generation(ebpf_policy::HOOK_FILE_OPEN)?,
+                self.resolver.clone(),
+            ))),
+            audit: self.hook_audit(Hook::FileOpen),
+            monitor: self.monitor("file_open"),
+            perf_array,
+            resolver: self.resolver.clone(),
+            generation,
+            namespace: self.namespace,
+        })
+    }
+
+    pub fn attach_inode_create(&mut self) -> Result<InodeCreate, EbpfguardError> {
+        let mut inode_create = self.manage_inode_create()?;
+        inode_create.program_link = self.attach_program("inode_create")?;
+
+        Ok(inode_create)
+    }
+
+    pub fn manage_inode_create(&mut self) -> Result<InodeCreate, EbpfguardError> {
+        let allowed_map = self.take_map("ALLOWED_INODE_CREATE")?;
+        let denied_map = self.take_map("DENIED_INODE_CREATE")?;
+        let perf_array = self.alert_array("ALERT_INODE_CREATE")?;
+        let generation = self.generation(ebpf_policy::HOOK_INODE_CREATE)?;
+
+        Ok(InodeCreate {
+            program_link: None,
+            allowed_map,
+            denied_map,
+            audit: self.hook_audit(Hook::InodeCreate),
+            monitor: self.monitor("inode_create"),
+            perf_array,
+            resolver: self.resolver.clone(),
+            generation,
+            namespace: self.namespace,
+        })
+    }
+
+    pub fn attach_task_fix_setgid(&mut self) -> Result<TaskFixSetgid, EbpfguardError> {
+        let mut task_fix_setgid = self.manage_task_fix_setgid()?;
+        task_fix_setgid.program_link = self.attach_program("task_fix_setgid")?;
+
+        Ok(task_fix_setgid)
+    }
+
+    pub fn manage_task_fix_setgid(&mut self) -> Result<TaskFixSetgid, EbpfguardError> {
+        let allowed_map = self.take_map("ALLOWED_TASK_FIX_SETGID")?;
+        let denied_map = self.take_map("DENIED_TASK_FIX_SETGID")?;
+        let privileged_map = self.take_map("PRIVILEGED_TASK_FIX_SETGID")?;
+        let perf_array = self.alert_array("ALERT_TASK_FIX_SETGID")?;
+        let generation = self.generation(ebpf_policy::HOOK_TASK_FIX_SETGID)?;
+
+        Ok(TaskFixSetgid {
+            program_link: None,
+            allowed_map,
+            denied_map,
+            privileged_map,
+            audit: self.hook_audit(Hook::TaskFixSetgid),
+            monitor: self.monitor("task_fix_setgid"),
             perf_array,
+            resolver: self.resolver.clone(),
+            generation,
+            namespace: self.namespace,
         })
     }
 
     pub fn attach_task_fix_setuid(&mut self) -> Result<TaskFixSetuid, EbpfguardError> {
         let mut task_fix_setuid = self.manage_task_fix_setuid()?;
-        let program_link = self.attach_program("task_fix_setuid")?;
-        task_fix_setuid.program_link = Some(program_link);
+        task_fix_setuid.program_link = self.attach_program("task_fix_setuid")?;
 
         Ok(task_fix_setuid)
     }
 
     pub fn manage_task_fix_setuid(&mut self) -> Result<TaskFixSetuid, EbpfguardError> {
-        let allowed_map = self
-            .bpf
-            .take_map("ALLOWED_TASK_FIX_SETUID")
-            .unwrap()
-            .try_into()?;
-        let denied_map = self
-            .bpf
-            .take_map("DENIED_TASK_FIX_SETUID")
-            .unwrap()
-            .try_into()?;
-        let perf_array = self
-            .bpf
-            .take_map("ALERT_TASK_FIX_SETUID")
-            .unwrap()
-            .try_into()?;
+        let allowed_map = self.take_map("ALLOWED_TASK_FIX_SETUID")?;
+        let denied_map = self.take_map("DENIED_TASK_FIX_SETUID")?;
+        let perf_array = self.alert_array("ALERT_TASK_FIX_SETUID")?;
+        let generation = self.generation(ebpf_policy::HOOK_TASK_FIX_SETUID)?;
 
         Ok(TaskFixSetuid {
             program_link: None,
             allowed_map,
             denied_map,
+            audit: self.hook_audit(Hook::TaskFixSetuid),
+            monitor: self.monitor("task_fix_setuid"),
             perf_array,
+            resolver: self.resolver.clone(),
+            generation,
+            namespace: self.namespace,
         })
     }
 
     pub fn attach_sb_mount(&mut self) -> Result<SbMount, EbpfguardError> {
         let mut sb_mount = self.manage_sb_mount()?;
-        let program_link = self.attach_program("sb_mount")?;
-        sb_mount.program_link = Some(program_link);
+        sb_mount.program_link = self.attach_program("sb_mount")?;
 
         Ok(sb_mount)
     }
 
     pub fn manage_sb_mount(&mut self) -> Result<SbMount, EbpfguardError> {
-        let allowed_map = self.bpf.take_map("ALLOWED_SB_MOUNT").unwrap().try_into()?;
-        let denied_map = self.bpf.take_map("DENIED_SB_MOUNT").unwrap().try_into()?;
-        let perf_array = self.bpf.take_map("ALERT_SB_MOUNT").unwrap().try_into()?;
+        let allowed_map = self.take_map("ALLOWED_SB_MOUNT")?;
+        let denied_map = self.take_map("DENIED_SB_MOUNT")?;
+        let perf_array = self.alert_array("ALERT_SB_MOUNT")?;
+        let generation = self.generation(ebpf_policy::HOOK_SB_MOUNT)?;
 
         Ok(SbMount {
             program_link: None,
             allowed_map,
             denied_map,
+            audit: self.hook_audit(Hook::SbMount),
+            monitor: self.monitor("sb_mount"),
             perf_array,
+            resolver: self.resolver.clone(),
+            generation,
+            namespace: self.namespace,
         })
     }
 
     pub fn attach_sb_remount(&mut self) -> Result<SbRemount, EbpfguardError> {
         let mut sb_remount = self.manage_sb_remount()?;
-        let program_link = self.attach_program("sb_remount")?;
-        sb_remount.program_link = Some(program_link);
+        sb_remount.program_link = self.attach_program("sb_remount")?;
 
         Ok(sb_remount)
     }
 
     pub fn manage_sb_remount(&mut self) -> Result<SbRemount, EbpfguardError> {
-        let allowed_map = self
-            .bpf
-            .take_map("ALLOWED_SB_REMOUNT")
-            .unwrap()
-            .try_into()?;
-        let denied_map = self.bpf.take_map("DENIED_SB_REMOUNT").unwrap().try_into()?;
-        let perf_array = self.bpf.take_map("ALERT_SB_REMOUNT").unwrap().try_into()?;
+        let allowed_map = self.take_map("ALLOWED_SB_REMOUNT")?;
+        let denied_map = self.take_map("DENIED_SB_REMOUNT")?;
+        let perf_array = self.alert_array("ALERT_SB_REMOUNT")?;
+        let generation = self.generation(ebpf_policy::HOOK_SB_REMOUNT)?;
 
         Ok(SbRemount {
             program_link: None,
             allowed_map,
             denied_map,
+            audit: self.hook_audit(Hook::SbRemount),
+            monitor: self.monitor("sb_remount"),
             perf_array,
+            resolver: self.resolver.clone(),
+            generation,
+            namespace: self.namespace,
         })
     }
 
     pub fn attach_sb_umount(&mut self) -> Result<SbUmount, EbpfguardError> {
         let mut sb_umount = self.manage_sb_umount()?;
-        let program_link = self.attach_program("sb_umount")?;
-        sb_umount.program_link = Some(program_link);
+        sb_umount.program_link = self.attach_program("sb_umount")?;
 
         Ok(sb_umount)
     }
 
     pub fn manage_sb_umount(&mut self) -> Result<SbUmount, EbpfguardError> {
-        let allowed_map = self.bpf.take_map("ALLOWED_SB_UMOUNT").unwrap().try_into()?;
-        let denied_map = self.bpf.take_map("DENIED_SB_UMOUNT").unwrap().try_into()?;
-        let perf_array = self.bpf.take_map("ALERT_SB_UMOUNT").unwrap().try_into()?;
+        let allowed_map = self.take_map("ALLOWED_SB_UMOUNT")?;
+        let denied_map = self.take_map("DENIED_SB_UMOUNT")?;
+        let perf_array = self.alert_array("ALERT_SB_UMOUNT")?;
+        let generation = self.generation(ebpf_policy::HOOK_SB_UMOUNT)?;
 
         Ok(SbUmount {
             program_link: None,
             allowed_map,
             denied_map,
+            audit: self.hook_audit(Hook::SbUmount),
+            monitor: self.monitor("sb_umount"),
             perf_array,
+            resolver: self.resolver.clone(),
+            generation,
+            namespace: self.namespace,
         })
     }
 
     pub fn attach_socket_bind(&mut self) -> Result<SocketBind, EbpfguardError> {
         let mut socket_bind = self.manage_socket_bind()?;
-        let program_link = self.attach_program("socket_bind")?;
-        socket_bind.program_link = Some(program_link);
+        socket_bind.program_link = self.attach_program("socket_bind")?;
 
         Ok(socket_bind)
     }
 
     pub fn manage_socket_bind(&mut self) -> Result<SocketBind, EbpfguardError> {
-        let allowed_map = self
-            .bpf
-            .take_map("ALLOWED_SOCKET_BIND")
-            .unwrap()
-            .try_into()?;
-        let denied_map = self
-            .bpf
-            .take_map("DENIED_SOCKET_BIND")
-            .unwrap()
-            .try_into()?;
-        let perf_array = self.bpf.take_map("ALERT_SOCKET_BIND").unwrap().try_into()?;
+        let allowed_map = self.take_map("ALLOWED_SOCKET_BIND")?;
+        let denied_map = self.take_map("DENIED_SOCKET_BIND")?;
+        let allowed_v4_map = self.take_map("ALLOWED_SOCKET_BIND_V4")?;
+        let denied_v4_map = self.take_map("DENIED_SOCKET_BIND_V4")?;
+        let allowed_v6_map = self.take_map("ALLOWED_SOCKET_BIND_V6")?;
+        let denied_v6_map = self.take_map("DENIED_SOCKET_BIND_V6")?;
+        let options_map = self.take_map("OPTIONS_SOCKET_BIND")?;
+        let allowed_comm_map = self.take_map("ALLOWED_SOCKET_BIND_COMM")?;
+        let denied_comm_map = self.take_map("DENIED_SOCKET_BIND_COMM")?;
+        let allowed_packet_map = self.take_map("ALLOWED_SOCKET_BIND_PACKET")?;
+        let denied_packet_map = self.take_map("DENIED_SOCKET_BIND_PACKET")?;
+        let escalate_map = self.take_map("ESCALATE_SOCKET_BIND")?;
+        let verdict_map = self.take_map("VERDICT_SOCKET_BIND")?;
+        let generation_map = self.take_map("GENERATION_SOCKET_BIND")?;
+        let config = ConfigMap::open(&self.maps_path, self.namespace)?;
+        let bind_limit_map = self.take_map("BIND_LIMIT_SOCKET_BIND")?;
+        let grants_map = self.take_map("GRANTS_SOCKET_BIND")?;
+        let perf_array = self.alert_array("ALERT_SOCKET_BIND")?;
+        let escalation_perf_array = self.alert_array("ALERT_SOCKET_BIND_ESCALATION")?;
+        let generation = self.generation(ebpf_policy::HOOK_SOCKET_BIND)?;
 
         Ok(SocketBind {
             program_link: None,
             allowed_map,
             denied_map,
+            allowed_v4_map,
+            denied_v4_map,
+            allowed_v6_map,
+            denied_v6_map,
+            options_map,
+            allowed_comm_map,
+            denied_comm_map,
+            allowed_packet_map,
+            denied_packet_map,
+            escalate_map,
+            verdict_map: Some(verdict_map),
+            generation_map,
+            config,
+            bind_limit_map,
+            grants: Arc::new(Mutex::new(Grants::new(grants_map))),
+            audit: self.hook_audit(Hook::SocketBind),
+            monitor: self.monitor("socket_bind"),
             perf_array,
+            escalation_perf_array,
+            resolver: self.resolver.clone(),
+            generation,
+            namespace: self.namespace,
         })
     }
 
     pub fn attach_socket_connect(&mut self) -> Result<SocketConnect, EbpfguardError> {
         let mut socket_connect = self.manage_socket_connect()?;
-        let program_link = self.attach_program("socket_connect")?;
-        socket_connect.program_link = Some(program_link);
+        socket_connect.program_link = self.attach_program("socket_connect")?;
 
         Ok(socket_connect)
     }
 
     pub fn manage_socket_connect(&mut self) -> Result<SocketConnect, EbpfguardError> {
-        let allowed_map_v4 = self
-            .bpf
-            .take_map("ALLOWED_SOCKET_CONNECT_V4")
-            .unwrap()
-            .try_into()?;
-        let denied_map_v4 = self
-            .bpf
-            .take_map("DENIED_SOCKET_CONNECT_V4")
-            .unwrap()
-            .try_into()?;
-        let allowed_map_v6 = self
-            .bpf
-            .take_map("ALLOWED_SOCKET_CONNECT_V6")
-            .unwrap()
-            .try_into()?;
-        let denied_map_v6 = self
-            .bpf
-            .take_map("DENIED_SOCKET_CONNECT_V6")
-            .unwrap()
-            .try_into()?;
-        let perf_array = self
-            .bpf
-            .take_map("ALERT_SOCKET_CONNECT")
-            .unwrap()
-            .try_into()?;
+        let allowed_map_v4 = self.take_map("ALLOWED_SOCKET_CONNECT_V4")?;
+        let denied_map_v4 = self.take_map("DENIED_SOCKET_CONNECT_V4")?;
+        let allowed_map_v6 = self.take_map("ALLOWED_SOCKET_CONNECT_V6")?;
+        let denied_map_v6 = self.take_map("DENIED_SOCKET_CONNECT_V6")?;
+        let protected_map_v4 = self.take_map("PROTECTED_SOCKET_CONNECT_V4")?;
+        let protected_map_v6 = self.take_map("PROTECTED_SOCKET_CONNECT_V6")?;
+        let allowed_ports_map = self.take_map("ALLOWED_SOCKET_CONNECT_PORTS")?;
+        let denied_ports_map = self.take_map("DENIED_SOCKET_CONNECT_PORTS")?;
+        let config = ConfigMap::open(&self.maps_path, self.namespace)?;
+        let denied_cidr_map_v4 = self.take_map("DENIED_SOCKET_CONNECT_CIDR_V4")?;
+        let denied_cidr_map_v6 = self.take_map("DENIED_SOCKET_CONNECT_CIDR_V6")?;
+        let metadata_map_v4 = self.take_map("DENIED_SOCKET_CONNECT_METADATA_V4")?;
+        let metadata_map_v6 = self.take_map("DENIED_SOCKET_CONNECT_METADATA_V6")?;
+        let rate_map_v4 = self.take_map("RATE_SOCKET_CONNECT_V4")?;
+        let rate_map_v6 = self.take_map("RATE_SOCKET_CONNECT_V6")?;
+        let rate_windows_map = self.take_map("RATE_WINDOWS_SOCKET_CONNECT")?;
+        let perf_array = self.alert_array("ALERT_SOCKET_CONNECT")?;
+        let generation = self.generation(ebpf_policy::HOOK_SOCKET_CONNECT)?;
 
         Ok(SocketConnect {
             program_link: None,
@@ -332,11 +1414,477 @@ impl PolicyManager {
             denied_map_v4,
             allowed_map_v6,
             denied_map_v6,
+            protected_map_v4,
+            protected_map_v6,
+            allowed_ports_map,
+            denied_ports_map,
+            config,
+            metadata_map_v4,
+            metadata_map_v6,
+            rate_map_v4,
+            rate_map_v6,
+            rate_windows_map,
+            metadata: StdHashMap::new(),
+            geo: Arc::new(Mutex::new(GeoRules::new(
+                denied_cidr_map_v4,
+                denied_cidr_map_v6,
+                self.generation(ebpf_policy::HOOK_SOCKET_CONNECT)?,
+            ))),
+            audit: self.hook_audit(Hook::SocketConnect),
+            monitor: self.monitor("socket_connect"),
             perf_array,
+            resolver: self.resolver.clone(),
+            generation,
+            namespace: self.namespace,
         })
     }
 
-    fn attach_program(&mut self, name: &str) -> Result<LsmLink, EbpfguardError> {
+    pub fn attach_socket_create(&mut self) -> Result<SocketCreate, EbpfguardError> {
+        let mut socket_create = self.manage_socket_create()?;
+        socket_create.program_link = self.attach_program("socket_create")?;
+
+        Ok(socket_create)
+    }
+
+    pub fn manage_socket_create(&mut self) -> Result<SocketCreate, EbpfguardError> {
+        let allowed_maps = [
+            self.take_map("ALLOWED_SOCKET_CREATE")?,
+            self.take_map("ALLOWED_SOCKET_CREATE_1")?,
+        ];
+        let denied_maps = [
+            self.take_map("DENIED_SOCKET_CREATE")?,
+            self.take_map("DENIED_SOCKET_CREATE_1")?,
+        ];
+        let slot_map = self.take_map("SLOT_SOCKET_CREATE")?;
+        let perf_array = self.alert_array("ALERT_SOCKET_CREATE")?;
+        let generation = self.generation(ebpf_policy::HOOK_SOCKET_CREATE)?;
+
+        Ok(SocketCreate {
+            program_link: None,
+            allowed_maps,
+            denied_maps,
+            slot_map,
+            audit: self.hook_audit(Hook::SocketCreate),
+            monitor: self.monitor("socket_create"),
+            perf_array,
+            resolver: self.resolver.clone(),
+            generation,
+            namespace: self.namespace,
+        })
+    }
+
+    pub fn attach_socket_listen(&mut self) -> Result<SocketListen, EbpfguardError> {
+        let mut socket_listen = self.manage_socket_listen()?;
+        socket_listen.program_link = self.attach_program("socket_listen")?;
+
+        Ok(socket_listen)
+    }
+
+    pub fn manage_socket_listen(&mut self) -> Result<SocketListen, EbpfguardError> {
+        let allowed_map = self.take_map("ALLOWED_SOCKET_LISTEN")?;
+        let denied_map = self.take_map("DENIED_SOCKET_LISTEN")?;
+        let options_map = self.take_map("OPTIONS_SOCKET_LISTEN")?;
+        let perf_array = self.alert_array("ALERT_SOCKET_LISTEN")?;
+        let generation = self.generation(ebpf_policy::HOOK_SOCKET_LISTEN)?;
+
+        Ok(SocketListen {
+            program_link: None,
+            allowed_map,
+            denied_map,
+            options_map,
+            audit: self.hook_audit(Hook::SocketListen),
+            monitor: self.monitor("socket_listen"),
+            perf_array,
+            resolver: self.resolver.clone(),
+            generation,
+            namespace: self.namespace,
+        })
+    }
+
+    /// Returns the health of the hooks handed out by the policy manager and
+    /// of the policy maps.
+    ///
+    /// A hook is failed if its program was attached by the policy manager
+    /// and isn't attached anymore (e.g. the hook was dropped and its link
+    /// wasn't pinned), or if any of its alert readers stopped. It's degraded
+    /// if alerts were lost since the previous call, so calling it
+    /// periodically (e.g. from a liveness probe) measures sustained loss. A
+    /// map is failed if it can't be read and degraded if it's at least
+    /// [`MAP_UTILIZATION_DEGRADED`](crate::health::MAP_UTILIZATION_DEGRADED)
+    /// full. The overall state is the worst of them.
+    ///
+    /// Hook checks are cheap (atomic counters and a pin lookup). Counting map
+    /// entries walks the keys of every policy map, so map utilization is
+    /// refreshed at most every 30 seconds and reused in between.
+    pub fn health(&mut self) -> Health {
+        let now = Instant::now();
+
+        let hooks = self
+            .hooks
+            .iter_mut()
+            .map(|hook| {
+                let attached = hook.attached();
+                let lost = hook.alerts.lost();
+                let secs = now.duration_since(hook.checked_at).as_secs_f64();
+                let health = HookHealth::new(hook.name, hook.intended, attached, &hook.alerts)
+                    .with_lost(lost - hook.checked_lost, secs);
+                hook.checked_at = now;
+                hook.checked_lost = lost;
+                health
+            })
+            .collect();
+
+        let maps = match &self.maps_health {
+            Some((checked_at, maps)) if now.duration_since(*checked_at) < MAPS_HEALTH_INTERVAL => {
+                maps.clone()
+            }
+            _ => {
+                let maps = self.maps_health();
+                self.maps_health = Some((now, maps.clone()));
+                maps
+            }
+        };
+
+        Health::new(hooks, maps)
+    }
+
+    /// Starts emitting a [`Heartbeat`] every `interval` (the first one right
+    /// away), with the uptime of the policy manager and whether the programs
+    /// of the hooks handed out so far are attached. Consumers can alert if
+    /// heartbeats stop, which tells a dead policy manager from a quiet
+    /// period without alerts.
+    ///
+    /// Heartbeats are emitted by user space: they prove that the policy
+    /// manager runs and its hooks are attached, not that the programs were
+    /// triggered. Hooks handed out after the call are not reported, so start
+    /// the heartbeat after attaching them. It stops when the receiver is
+    /// dropped.
+    pub fn heartbeat(&self, interval: Duration) -> Result<Receiver<Heartbeat>, EbpfguardError> {
+        if interval.is_zero() {
+            return Err(EbpfguardError::HeartbeatIntervalZero);
+        }
+
+        let hooks = self.hooks.clone();
+        let namespace = self.namespace;
+        let created_at = self.created_at;
+        let (tx, rx) = mpsc::channel(32);
+
+        task::spawn(async move {
+            let mut interval = tokio::time::interval(interval);
+            // A stalled runtime shows up as a late heartbeat, not a burst.
+            interval.set_missed_tick_behavior(MissedTickBehavior::Delay);
+            let mut seq = 0;
+            loop {
+                interval.tick().await;
+                seq += 1;
+                let heartbeat = Heartbeat {
+                    seq,
+                    namespace,
+                    uptime: created_at.elapsed(),
+                    hooks: hooks
+                        .iter()
+                        .map(|hook| HookStatus {
+                            name: hook.name,
+                            attached: hook.attached(),
+                        })
+                        .collect(),
+                };
+                if tx.send(heartbeat).await.is_err() {
+                    break;
+                }
+            }
+        });
+
+        Ok(rx)
+    }
+
+    fn maps_health(&self) -> Vec<MapHealth> {
+        vec![
+            self.map_health::<u64, u32>("POLICY_NAMESPACES", POLICY_MAP_ENTRIES),
+            self.map_health::<u32, ebpf_policy::Config>("CONFIG", POLICY_MAP_ENTRIES),
+            self.map_health::<HookKey, u16>("MESSAGE_IDS", POLICY_MAP_ENTRIES),
+            self.map_health::<HookKey, u32>("POLICY_GENERATIONS", POLICY_MAP_ENTRIES),
+            self.map_health::<InodeKey, u64>("ALERT_WINDOWS", POLICY_MAP_ENTRIES),
+            self.map_health::<InodeKey, u8>("ALERT_CHANNELS", POLICY_MAP_ENTRIES),
+            self.map_health::<InodeKey, ebpf_policy::RetryEscalation>(
+                "RETRY_ESCALATIONS",
+                POLICY_MAP_ENTRIES,
+            ),
+            self.map_health::<InodeKey, ebpf_policy::Paths>(
+                "ALLOWED_FILE_OPEN",
+                POLICY_MAP_ENTRIES,
+            ),
+            self.map_health::<InodeKey, ebpf_policy::Paths>("DENIED_FILE_OPEN", POLICY_MAP_ENTRIES),
+            self.map_health::<InodeKey, ebpf_policy::Binaries>(
+                "PROTECTED_FILE_OPEN",
+                POLICY_MAP_ENTRIES,
+            ),
+            self.map_health::<FileInodeKey, u8>("ALLOWED_FILE_OPEN_INODES", INODE_MAP_ENTRIES),
+            self.map_health::<FileInodeKey, u8>("DENIED_FILE_OPEN_INODES", INODE_MAP_ENTRIES),
+            self.map_health::<InodeKey, ebpf_policy::Paths>(
+                "ALLOWED_INODE_CREATE",
+                POLICY_MAP_ENTRIES,
+            ),
+            self.map_health::<InodeKey, ebpf_policy::Paths>(
+                "DENIED_INODE_CREATE",
+                POLICY_MAP_ENTRIES,
+            ),
+            self.map_health::<InodeKey, u8>("ALLOWED_TASK_FIX_SETUID", POLICY_MAP_ENTRIES),
+            self.map_health::<InodeKey, u8>("DENIED_TASK_FIX_SETUID", POLICY_MAP_ENTRIES),
+            self.map_health::<InodeKey, u8>("ALLOWED_BPF", POLICY_MAP_ENTRIES),
+            self.map_health::<InodeKey, u8>("DENIED_BPF", POLICY_MAP_ENTRIES),
+            self.map_health::<DevInodeKey, u8>("EXEMPT_BPF", ebpf_policy::MAX_BPF_EXEMPT as usize),
+            self.map_health::<InodeKey, u8>("ALLOWED_TASK_FIX_SETGID", POLICY_MAP_ENTRIES),
+            self.map_health::<InodeKey, u8>("DENIED_TASK_FIX_SETGID", POLICY_MAP_ENTRIES),
+            self.map_health::<GidKey, u8>("PRIVILEGED_TASK_FIX_SETGID", POLICY_MAP_ENTRIES),
+            self.map_health::<InodeKey, u8>("ALLOWED_SB_MOUNT", POLICY_MAP_ENTRIES),
+            self.map_health::<InodeKey, u8>("DENIED_SB_MOUNT", POLICY_MAP_ENTRIES),
+            self.map_health::<InodeKey, u8>("ALLOWED_SB_REMOUNT", POLICY_MAP_ENTRIES),
+            self.map_health::<InodeKey, u8>("DENIED_SB_REMOUNT", POLICY_MAP_ENTRIES),
+            self.map_health::<InodeKey, u8>("ALLOWED_SB_UMOUNT", POLICY_MAP_ENTRIES),
+            self.map_health::<InodeKey, u8>("DENIED_SB_UMOUNT", POLICY_MAP_ENTRIES),
+            self.map_health::<InodeKey, ebpf_policy::Ports>(
+                "ALLOWED_SOCKET_BIND",
+                POLICY_MAP_ENTRIES,
+            ),
+            self.map_health::<InodeKey, ebpf_policy::Ports>(
+                "DENIED_SOCKET_BIND",
+                POLICY_MAP_ENTRIES,
+            ),
+            self.map_health::<InodeKey, ebpf_policy::Ports>(
+                "ALLOWED_SOCKET_BIND_V4",
+                POLICY_MAP_ENTRIES,
+            ),
+            self.map_health::<InodeKey, ebpf_policy::Ports>(
+                "DENIED_SOCKET_BIND_V4",
+                POLICY_MAP_ENTRIES,
+            ),
+            self.map_health::<InodeKey, ebpf_policy::Ports>(
+                "ALLOWED_SOCKET_BIND_V6",
+                POLICY_MAP_ENTRIES,
+            ),
+            self.map_health::<InodeKey, ebpf_policy::Ports>(
+                "DENIED_SOCKET_BIND_V6",
+                POLICY_MAP_ENTRIES,
+            ),
+            self.map_health::<InodeKey, u8>("OPTIONS_SOCKET_BIND", POLICY_MAP_ENTRIES),
+            self.lpm_trie_health::<CommKey, ebpf_policy::Ports>(
+                "ALLOWED_SOCKET_BIND_COMM",
+                POLICY_MAP_ENTRIES as u32,
+            ),
+            self.lpm_trie_health::<CommKey, ebpf_policy::Ports>(
+                "DENIED_SOCKET_BIND_COMM",
+                POLICY_MAP_ENTRIES as u32,
+            ),
+            self.map_health::<InodeKey, u8>("ALLOWED_SOCKET_BIND_PACKET", POLICY_MAP_ENTRIES),
+            self.map_health::<InodeKey, u8>("DENIED_SOCKET_BIND_PACKET", POLICY_MAP_ENTRIES),
+            self.map_health::<InodeKey, u8>("ESCALATE_SOCKET_BIND", POLICY_MAP_ENTRIES),
+            self.map_health::<InodeKey, u32>("BIND_LIMIT_SOCKET_BIND", POLICY_MAP_ENTRIES),
+            self.map_health::<ebpf_policy::SocketBindGrantKey, u8>(
+                "GRANTS_SOCKET_BIND",
+                POLICY_MAP_ENTRIES,
+            ),
+            self.map_health::<InodeKey, ebpf_policy::Ipv4Addrs>(
+                "ALLOWED_SOCKET_CONNECT_V4",
+                POLICY_MAP_ENTRIES,
+            ),
+            self.map_health::<InodeKey, ebpf_policy::Ipv4Addrs>(
+                "DENIED_SOCKET_CONNECT_V4",
+                POLICY_MAP_ENTRIES,
+            ),
+            self.map_health::<InodeKey, ebpf_policy::Ipv6Addrs>(
+                "ALLOWED_SOCKET_CONNECT_V6",
+                POLICY_MAP_ENTRIES,
+            ),
+            self.map_health::<InodeKey, ebpf_policy::Ipv6Addrs>(
+                "DENIED_SOCKET_CONNECT_V6",
+                POLICY_MAP_ENTRIES,
+            ),
+            self.map_health::<Ipv4Key, ebpf_policy::Binaries>(
+                "PROTECTED_SOCKET_CONNECT_V4",
+                POLICY_MAP_ENTRIES,
+            ),
+            self.map_health::<Ipv6Key, ebpf_policy::Binaries>(
+                "PROTECTED_SOCKET_CONNECT_V6",
+                POLICY_MAP_ENTRIES,
+            ),
+            self.map_health::<InodeKey, ebpf_policy::PortRanges>(
+                "ALLOWED_SOCKET_CONNECT_PORTS",
+                POLICY_MAP_ENTRIES,
+            ),
+            self.map_health::<InodeKey, ebpf_policy::PortRanges>(
+                "DENIED_SOCKET_CONNECT_PORTS",
+                POLICY_MAP_ENTRIES,
+            ),
+            self.lpm_trie_health::<Ipv4CidrKey, u8>(
+                "DENIED_SOCKET_CONNECT_CIDR_V4",
+                ebpf_policy::MAX_CIDRS,
+            ),
+            self.lpm_trie_health::<Ipv6CidrKey, u8>(
+                "DENIED_SOCKET_CONNECT_CIDR_V6",
+                ebpf_policy::MAX_CIDRS,
+            ),
+            self.lpm_trie_health::<Ipv4CidrKey, u8>(
+                "DENIED_SOCKET_CONNECT_METADATA_V4",
+                ebpf_policy::MAX_METADATA_CIDRS,
+            ),
+            self.lpm_trie_health::<Ipv6CidrKey, u8>(
+                "DENIED_SOCKET_CONNECT_METADATA_V6",
+                ebpf_policy::MAX_METADATA_CIDRS,
+            ),
+            self.lpm_trie_health::<Ipv4CidrKey, ebpf_policy::ConnectRate>(
+                "RATE_SOCKET_CONNECT_V4",
+                ebpf_policy::MAX_RATE_LIMITS,
+            ),
+            self.lpm_trie_health::<Ipv6CidrKey, ebpf_policy::ConnectRate>(
+                "RATE_SOCKET_CONNECT_V6",
+                ebpf_policy::MAX_RATE_LIMITS,
+            ),
+            self.map_health::<InodeKey, ebpf_policy::SocketKinds>(
+                "ALLOWED_SOCKET_CREATE",
+                POLICY_MAP_ENTRIES,
+            ),
+            self.map_health::<InodeKey, ebpf_policy::SocketKinds>(
+                "DENIED_SOCKET_CREATE",
+                POLICY_MAP_ENTRIES,
+            ),
+            self.map_health::<InodeKey, ebpf_policy::SocketKinds>(
+                "ALLOWED_SOCKET_CREATE_1",
+                POLICY_MAP_ENTRIES,
+            ),
+            self.map_health::<InodeKey, ebpf_policy::SocketKinds>(
+                "DENIED_SOCKET_CREATE_1",
+                POLICY_MAP_ENTRIES,
+            ),
+            self.map_health::<u32, u32>("SLOT_SOCKET_CREATE", POLICY_MAP_ENTRIES),
+            self.map_health::<InodeKey, ebpf_policy::Ports>(
+                "ALLOWED_SOCKET_LISTEN",
+                POLICY_MAP_ENTRIES,
+            ),
+            self.map_health::<InodeKey, ebpf_policy::Ports>(
+                "DENIED_SOCKET_LISTEN",
+                POLICY_MAP_ENTRIES,
+            ),
+            self.map_health::<InodeKey, u8>("OPTIONS_SOCKET_LISTEN", POLICY_MAP_ENTRIES),
+        ]
+    }
+
+    /// Counts the entries of the hash map pinned under the given name. The
+    /// map is opened from its pin, since the hooks own the map handles.
+    fn map_health<K: Pod, V: Pod>(&self, name: &'static str, max_entries: usize) -> MapHealth {
+        let entries = MapData::from_pin(self.maps_path.join(name))
+            .ok()
+            .and_then(|map| HashMap::<_, K, V>::try_from(Map::HashMap(map)).ok())
+            .map(|map| map.keys().filter(|key| key.is_ok()).count());
+        MapHealth::new(name, entries, max_entries)
+    }
+
+    fn lpm_trie_health<K: Pod, V: Pod>(&self, name: &'static str, max_entries: u32) -> MapHealth {
+        let entries = MapData::from_pin(self.maps_path.join(name))
+            .ok()
+            .and_then(|map| LpmTrie::<_, K, V>::try_from(Map::LpmTrie(map)).ok())
+            .map(|map| map.keys().filter(|key| key.is_ok()).count());
+        MapHealth::new(name, entries, max_entries as usize)
+    }
+
+    /// Registers a hook handed out by the policy manager, for health checks.
+    fn monitor(&mut self, name: &'static str) -> HookMonitor {
+        let monitor = HookMonitor {
+            buffers: self.alert_buffers,
+            ..Default::default()
+        };
+        self.hooks.push(ManagedHook {
+            name,
+            intended: false,
+            pin: None,
+            alive: monitor.alive(),
+            alerts: monitor.alerts.clone(),
+            checked_at: Instant::now(),
+            checked_lost: 0,
+        });
+        monitor
+    }
+
+    /// Opens the handle of the generation of the hook's policies in the
+    /// current namespace.
+    fn generation(&self, hook: u32) -> Result<Generation, EbpfguardError> {
+        Generation::open(&self.maps_path, self.namespace, hook)
+    }
+
+    /// Returns the emitter of audit events of the hook in the current
+    /// namespace.
+    fn hook_audit(&self, hook: Hook) -> AuditLog {
+        self.audit.scoped(self.namespace, Some(hook))
+    }
+
+    /// Takes the map with the given name out of the eBPF object and converts
+    /// it into the requested map type.
+    fn take_map<T>(&mut self, name: &str) -> Result<T, EbpfguardError>
+    where
+        T: TryFrom<Map, Error = MapError>,
+    {
+        let map = self
+            .bpf
+            .take_map(name)
+            .ok_or_else(|| EbpfguardError::MapNotFound(name.to_owned()))?;
+        T::try_from(map).map_err(|e| EbpfguardError::from_map_error(name, e))
+    }
+
+    /// Takes the perf event array of alerts with the given name.
+    fn alert_array(&mut self, name: &str) -> Result<AlertArray, EbpfguardError> {
+        Ok(AlertArray {
+            perf_array: self.take_map(name)?,
+            path: self.maps_path.join(name),
+        })
+    }
+
+    /// Attaches the program with the given name. Returns its link, unless
+    /// links are pinned (see [`PolicyManager::pin_links`]), in which case the
+    /// pin keeps the program attached.
+    fn attach_program(&mut self, name: &str) -> Result<Option<LsmLink>, EbpfguardError> {
+        let pin = self
+            .links_path
+            .as_ref()
+            .map(|links_path| links_path.join(name));
+        if let Some(hook) = self.hooks.iter_mut().rev().find(|hook| hook.name == name) {
+            hook.intended = true;
+            hook.pin = pin;
+        }
+
+        let pin = match &self.links_path {
+            Some(links_path) => links_path.join(name),
+            None => return self.load_and_attach(name).map(Some),
+        };
+
+        if !pin.exists() {
+            let link = self.load_and_attach(name)?;
+            FdLink::from(link).pin(&pin)?;
+        }
+
+        Ok(None)
+    }
+
+    /// Attaches the program with the given name and pins it in place of the
+    /// old link pinned at `pin`.
+    fn upgrade_program(&mut self, name: &str, pin: &Path) -> Result<(), EbpfguardError> {
+        // Pin of an upgrade which was interrupted before the rename below.
+        let new_pin = pin.with_extension("new");
+        if new_pin.exists() {
+            std::fs::remove_file(&new_pin)?;
+        }
+
+        let link = self.load_and_attach(name)?;
+        FdLink::from(link).pin(&new_pin)?;
+
+        // Dropping the last reference to the old link detaches the old
+        // program.
+        PinnedLink::from_pin(pin)?.unpin()?;
+        std::fs::rename(&new_pin, pin)?;
+
+        Ok(())
+    }
+
+    fn load_and_attach(&mut self, name: &str) -> Result<LsmLink, EbpfguardError> {
         let btf = Btf::from_sys_fs()?;
         let program: &mut Lsm = self.bpf.program_mut(name).unwrap().try_into()?;
         program.load(name, &btf)?;
@@ -346,3 +1894,108 @@ impl PolicyManager {
         Ok(link)
     }
 }
+
+/// Verifies that key and value sizes of all policy maps in the loaded eBPF
+/// object match the `ebpfguard-common` types used in user space, so an ABI
+/// skew between the two crates fails loudly at load time instead of
+/// corrupting map operations.
+fn verify_maps(bpf: &Bpf) -> Result<(), EbpfguardError> {
+    verify_map::<u64, u32>(bpf, "POLICY_NAMESPACES")?;
+    verify_map::<u32, ebpf_policy::Config>(bpf, "CONFIG")?;
+    verify_map::<HookKey, u16>(bpf, "MESSAGE_IDS")?;
+    verify_map::<HookKey, u32>(bpf, "POLICY_GENERATIONS")?;
+    verify_map::<InodeKey, u64>(bpf, "ALERT_WINDOWS")?;
+    verify_map::<InodeKey, u8>(bpf, "ALERT_CHANNELS")?;
+    verify_map::<HookKey, u64>(bpf, "LAST_ALERTS")?;
+    verify_map::<InodeKey, ebpf_policy::RetryEscalation>(bpf, "RETRY_ESCALATIONS")?;
+    verify_map::<ebpf_policy::RetryKey, ebpf_policy::RateWindow>(bpf, "RETRY_WINDOWS")?;
+    verify_map::<ebpf_policy::ProcessKey, u64>(bpf, "SESSIONS")?;
+    verify_map::<InodeKey, ebpf_policy::Paths>(bpf, "ALLOWED_FILE_OPEN")?;
+    verify_map::<InodeKey, ebpf_policy::Paths>(bpf, "DENIED_FILE_OPEN")?;
+    verify_map::<InodeKey, ebpf_policy::Binaries>(bpf, "PROTECTED_FILE_OPEN")?;
+    verify_map::<FileInodeKey, u8>(bpf, "ALLOWED_FILE_OPEN_INODES")?;
+    verify_map::<FileInodeKey, u8>(bpf, "DENIED_FILE_OPEN_INODES")?;
+    verify_map::<InodeKey, ebpf_policy::Paths>(bpf, "ALLOWED_INODE_CREATE")?;
+    verify_map::<InodeKey, ebpf_policy::Paths>(bpf, "DENIED_INODE_CREATE")?;
+    verify_map::<InodeKey, u8>(bpf, "ALLOWED_TASK_FIX_SETUID")?;
+    verify_map::<InodeKey, u8>(bpf, "DENIED_TASK_FIX_SETUID")?;
+    verify_map::<InodeKey, u8>(bpf, "ALLOWED_BPF")?;
+    verify_map::<InodeKey, u8>(bpf, "DENIED_BPF")?;
+    verify_map::<DevInodeKey, u8>(bpf, "EXEMPT_BPF")?;
+    verify_map::<InodeKey, u8>(bpf, "ALLOWED_TASK_FIX_SETGID")?;
+    verify_map::<InodeKey, u8>(bpf, "DENIED_TASK_FIX_SETGID")?;
+    verify_map::<GidKey, u8>(bpf, "PRIVILEGED_TASK_FIX_SETGID")?;
+    verify_map::<InodeKey, u8>(bpf, "ALLOWED_SB_MOUNT")?;
+    verify_map::<InodeKey, u8>(bpf, "DENIED_SB_MOUNT")?;
+    verify_map::<InodeKey, u8>(bpf, "ALLOWED_SB_REMOUNT")?;
+    verify_map::<InodeKey, u8>(bpf, "DENIED_SB_REMOUNT")?;
+    verify_map::<InodeKey, u8>(bpf, "ALLOWED_SB_UMOUNT")?;
+    verify_map::<InodeKey, u8>(bpf, "DENIED_SB_UMOUNT")?;
+    verify_map::<InodeKey, ebpf_policy::Ports>(bpf, "ALLOWED_SOCKET_BIND")?;
+    verify_map::<InodeKey, ebpf_policy::Ports>(bpf, "DENIED_SOCKET_BIND")?;
+    verify_map::<InodeKey, ebpf_policy::Ports>(bpf, "ALLOWED_SOCKET_BIND_V4")?;
+    verify_map::<InodeKey, ebpf_policy::Ports>(bpf, "DENIED_SOCKET_BIND_V4")?;
+    verify_map::<InodeKey, ebpf_policy::Ports>(bpf, "ALLOWED_SOCKET_BIND_V6")?;
+    verify_map::<InodeKey, ebpf_policy::Ports>(bpf, "DENIED_SOCKET_BIND_V6")?;
+    verify_map::<InodeKey, u8>(bpf, "OPTIONS_SOCKET_BIND")?;
+    verify_lpm_trie::<CommKey, ebpf_policy::Ports>(bpf, "ALLOWED_SOCKET_BIND_COMM")?;
+    verify_lpm_trie::<CommKey, ebpf_policy::Ports>(bpf, "DENIED_SOCKET_BIND_COMM")?;
+    verify_map::<InodeKey, u8>(bpf, "ALLOWED_SOCKET_BIND_PACKET")?;
+    verify_map::<InodeKey, u8>(bpf, "DENIED_SOCKET_BIND_PACKET")?;
+    verify_map::<InodeKey, u8>(bpf, "ESCALATE_SOCKET_BIND")?;
+    verify_map::<ebpf_policy::SocketBindVerdictKey, u8>(bpf, "VERDICT_SOCKET_BIND")?;
+    verify_map::<u32, u64>(bpf, "GENERATION_SOCKET_BIND")?;
+    verify_map::<ebpf_policy::SocketBindVerdictKey, u64>(bpf, "CACHE_SOCKET_BIND")?;
+    verify_map::<InodeKey, u32>(bpf, "BIND_LIMIT_SOCKET_BIND")?;
+    verify_map::<ebpf_policy::SocketBindGrantKey, u8>(bpf, "GRANTS_SOCKET_BIND")?;
+    verify_map::<ebpf_policy::ProcessKey, u32>(bpf, "BIND_COUNT_SOCKET_BIND")?;
+    verify_map::<ebpf_policy::ProcessPortKey, u8>(bpf, "BOUND_PORTS_SOCKET_BIND")?;
+    verify_map::<InodeKey, ebpf_policy::Ipv4Addrs>(bpf, "ALLOWED_SOCKET_CONNECT_V4")?;
+    verify_map::<InodeKey, ebpf_policy::Ipv4Addrs>(bpf, "DENIED_SOCKET_CONNECT_V4")?;
+    verify_map::<InodeKey, ebpf_policy::Ipv6Addrs>(bpf, "ALLOWED_SOCKET_CONNECT_V6")?;
+    verify_map::<InodeKey, ebpf_policy::Ipv6Addrs>(bpf, "DENIED_SOCKET_CONNECT_V6")?;
+    verify_map::<Ipv4Key, ebpf_policy::Binaries>(bpf, "PROTECTED_SOCKET_CONNECT_V4")?;
+    verify_map::<Ipv6Key, ebpf_policy::Binaries>(bpf, "PROTECTED_SOCKET_CONNECT_V6")?;
+    verify_map::<InodeKey, ebpf_policy::PortRanges>(bpf, "ALLOWED_SOCKET_CONNECT_PORTS")?;
+    verify_map::<InodeKey, ebpf_policy::PortRanges>(bpf, "DENIED_SOCKET_CONNECT_PORTS")?;
+    verify_lpm_trie::<Ipv4CidrKey, u8>(bpf, "DENIED_SOCKET_CONNECT_CIDR_V4")?;
+    verify_lpm_trie::<Ipv6CidrKey, u8>(bpf, "DENIED_SOCKET_CONNECT_CIDR_V6")?;
+    verify_lpm_trie::<Ipv4CidrKey, u8>(bpf, "DENIED_SOCKET_CONNECT_METADATA_V4")?;
+    verify_lpm_trie::<Ipv6CidrKey, u8>(bpf, "DENIED_SOCKET_CONNECT_METADATA_V6")?;
+    verify_lpm_trie::<Ipv4CidrKey, ebpf_policy::ConnectRate>(bpf, "RATE_SOCKET_CONNECT_V4")?;
+    verify_lpm_trie::<Ipv6CidrKey, ebpf_policy::ConnectRate>(bpf, "RATE_SOCKET_CONNECT_V6")?;
+    verify_map::<ebpf_policy::RateKey, ebpf_policy::RateWindow>(
+        bpf,
+        "RATE_WINDOWS_SOCKET_CONNECT",
+    )?;
+    verify_map::<InodeKey, ebpf_policy::SocketKinds>(bpf, "ALLOWED_SOCKET_CREATE")?;
+    verify_map::<InodeKey, ebpf_policy::SocketKinds>(bpf, "DENIED_SOCKET_CREATE")?;
+    verify_map::<InodeKey, ebpf_policy::SocketKinds>(bpf, "ALLOWED_SOCKET_CREATE_1")?;
+    verify_map::<InodeKey, ebpf_policy::SocketKinds>(bpf, "DENIED_SOCKET_CREATE_1")?;
+    verify_map::<u32, u32>(bpf, "SLOT_SOCKET_CREATE")?;
+    verify_map::<InodeKey, ebpf_policy::Ports>(bpf, "ALLOWED_SOCKET_LISTEN")?;
+    verify_map::<InodeKey, ebpf_policy::Ports>(bpf, "DENIED_SOCKET_LISTEN")?;
+    verify_map::<InodeKey, u8>(bpf, "OPTIONS_SOCKET_LISTEN")?;
+
+    Ok(())
+}
+
+fn verify_map<K: Pod, V: Pod>(bpf: &Bpf, name: &str) -> Result<(), EbpfguardError> {
+    let map = bpf
+        .map(name)
+        .ok_or_else(|| EbpfguardError::MapNotFound(name.to_owned()))?;
+    HashMap::<&MapData, K, V>::try_from(map)
+        .map_err(|e| EbpfguardError::from_map_error(name, e))?;
+
+    Ok(())
+}
+
+fn verify_lpm_trie<K: Pod, V: Pod>(bpf: &Bpf, name: &str) -> Result<(), EbpfguardError> {
+    let map = bpf
+        .map(name)
+        .ok_or_else(|| EbpfguardError::MapNotFound(name.to_owned()))?;
+    LpmTrie::<&MapData, K, V>::try_from(map)
+        .map_err(|e| EbpfguardError::from_map_error(name, e))?;
+
+    Ok(())
+}
@@ -0,0 +1,175 @@
+//! Messages attached to alerts, e.g. guidance for the user of a denied
+//! operation.
+//!
+//! Rules carry only a message ID (see
+//! [`PolicyManager::set_message_id`](crate::PolicyManager::set_message_id)),
+//! which the eBPF programs copy to the alerts of the operations the rules
+//! deny. User space maps the IDs to texts with [`Messages`], so the texts can
+//! be changed or translated without touching the policies. ID 0 means no
+//! message.
+
+use std::{collections::HashMap, fmt};
+
+use ebpfguard_common::{alerts::MESSAGE_NONE, policy as ebpf_policy};
+use serde::{Deserialize, Serialize};
+
+use crate::alerts::Alert;
+
+/// LSM hook of the rules a message is set for.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Hook {
+    Bpf,
+    BprmCheckSecurity,
+    FileOpen,
+    InodeCreate,
+    SbMount,
+    SbRemount,
+    SbUmount,
+    SocketBind,
+    SocketConnect,
+    SocketCreate,
+    SocketListen,
+    TaskFixSetgid,
+    TaskFixSetuid,
+}
+
+impl Hook {
+    pub(crate) fn id(self) -> u32 {
+        match self {
+            Hook::Bpf => ebpf_policy::HOOK_BPF,
+            Hook::BprmCheckSecurity => ebpf_policy::HOOK_BPRM_CHECK_SECURITY,
+            Hook::FileOpen => ebpf_policy::HOOK_FILE_OPEN,
+            Hook::InodeCreate => ebpf_policy::HOOK_INODE_CREATE,
+            Hook::SbMount => ebpf_policy::HOOK_SB_MOUNT,
+            Hook::SbRemount => ebpf_policy::HOOK_SB_REMOUNT,
+            Hook::SbUmount => ebpf_policy::HOOK_SB_UMOUNT,
+            Hook::SocketBind => ebpf_policy::HOOK_SOCKET_BIND,
+            Hook::SocketConnect => ebpf_policy::HOOK_SOCKET_CONNECT,
+            Hook::SocketCreate => ebpf_policy::HOOK_SOCKET_CREATE,
+            Hook::SocketListen => ebpf_policy::HOOK_SOCKET_LISTEN,
+            Hook::TaskFixSetgid => ebpf_policy::HOOK_TASK_FIX_SETGID,
+            Hook::TaskFixSetuid => ebpf_policy::HOOK_TASK_FIX_SETUID,
+        }
+    }
+}
+
+/// Table of message texts by ID.
+///
+/// It can be deserialized from a map of IDs to texts, e.g. in YAML:
+///
+/// ```yaml
+/// 1: Binding ports is not allowed in this container, use port 8080.
+/// 2: Ask the security team to allow the connection.
+/// ```
+///
+/// # Example
+///
+/// ```
+/// use ebpfguard::messages::Messages;
+///
+/// let mut messages = Messages::new();
+/// messages.insert(1, "Ask the security team to allow the connection.");
+/// assert_eq!(
+///     messages.get(1),
+///     Some("Ask the security team to allow the connection.")
+/// );
+/// assert_eq!(messages.get(2), None);
+/// ```
+#[derive(Debug, Default, Clone, Deserialize)]
+#[serde(transparent)]
+pub struct Messages {
+    messages: HashMap<u16, String>,
+}
+
+impl Messages {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets the text of the message ID. A text set for ID 0 is never shown.
+    pub fn insert<S: Into<String>>(&mut self, message_id: u16, text: S) {
+        self.messages.insert(message_id, text.into());
+    }
+
+    /// Returns the text of the message ID, if any.
+    pub fn get(&self, message_id: u16) -> Option<&str> {
+        if message_id == MESSAGE_NONE {
+            return None;
+        }
+        self.messages.get(&message_id).map(String::as_str)
+    }
+
+    /// Returns the alert with the text of its message, for logs.
+    pub fn render<'a, A: Alert>(&'a self, alert: &'a A) -> Rendered<'a, A> {
+        Rendered {
+            alert,
+            message: self.get(alert.message_id()),
+        }
+    }
+}
+
+/// Alert with the text of its message, serialized as the alert with an
+/// additional `message` field. Displayed as JSON.
+#[derive(Debug, Serialize)]
+pub struct Rendered<'a, A> {
+    #[serde(flatten)]
+    pub alert: &'a A,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub message: Option<&'a str>,
+}
+
+impl<A: Alert> fmt::Display for Rendered<'_, A> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let rendered = serde_json::to_string(self).map_err(|_| fmt::Error)?;
+        f.write_str(&rendered)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use ebpfguard_common::alerts::{self as ebpf_alerts, Alert as _, REASON_BINARY_DENY};
+
+    use super::*;
+    use crate::alerts::SocketBind;
+
+    #[test]
+    fn test_render_message() {
+        let messages: Messages =
+            serde_yaml::from_str("1: Use a port above 8000.\n2: Other message.\n").unwrap();
+
+        let mut alert = ebpf_alerts::SocketBind::new(1, 0, 0, REASON_BINARY_DENY, 42, 2, 80);
+        alert.set_message_id(1);
+        let alert = SocketBind::from(alert);
+        assert_eq!(alert.message_id, 1);
+
+        let rendered = messages.render(&alert);
+        assert_eq!(rendered.message, Some("Use a port above 8000."));
+        let json: serde_json::Value = serde_json::from_str(&rendered.to_string()).unwrap();
+        assert_eq!(json["message"], "Use a port above 8000.");
+        assert_eq!(json["message_id"], 1);
+        assert_eq!(json["port"], 80);
+    }
+
+    #[test]
+    fn test_render_without_message() {
+        let mut messages = Messages::new();
+        messages.insert(0, "Never shown.");
+
+        let alert = SocketBind::from(ebpf_alerts::SocketBind::new(
+            1,
+            0,
+            0,
+            REASON_BINARY_DENY,
+            42,
+            2,
+            80,
+        ));
+        assert_eq!(alert.message_id, MESSAGE_NONE);
+
+        let rendered = messages.render(&alert);
+        assert_eq!(rendered.message, None);
+        let json: serde_json::Value = serde_json::from_str(&rendered.to_string()).unwrap();
+        assert!(json.get("message").is_none());
+    }
+}
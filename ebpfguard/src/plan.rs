@@ -0,0 +1,1493 @@
+//! Dry runs of policy changes: the policy map entries which a set of policies
+//! would write, compared to the entries in the maps, e.g. to review a change
+//! before applying it.
+//!
+//! A plan ([`PolicyManager::plan`](crate::PolicyManager::plan)) replaces all
+//! policies of the namespace with the new ones, so entries which the new
+//! policies don't write are deleted. Entries are built the same way as by the
+//! hooks and compared as the raw bytes of their keys and values. Only the
+//! maps of [`Policy`] rules are compared: key layouts, exempt ports,
+//! escalations, message IDs and caches are not part of a plan.
+//!
+//! # Example
+//!
+//! ```no_run
+//! use ebpfguard::{policy::reader::read_policies, PolicyManager};
+//!
+//! let mgr = PolicyManager::with_default_path().unwrap();
+//! let policies = read_policies("policies.yaml").unwrap();
+//!
+//! let plan = mgr.plan(policies).unwrap();
+//! println!("{plan}");
+//! println!("{}", serde_json::to_string(&plan).unwrap());
+//! ```
+//!
+//! Snapshots ([`PolicyManager::snapshot`](crate::PolicyManager::snapshot))
+//! serialize to JSON and back, so a known-good baseline can be stored and
+//! the live maps compared against it with [`diff`], e.g. to detect policy
+//! changes made outside of the deployment:
+//!
+//! ```no_run
+//! use ebpfguard::{plan::{self, MapSnapshot}, PolicyManager};
+//!
+//! let mgr = PolicyManager::with_default_path().unwrap();
+//! let baseline: MapSnapshot =
+//!     serde_json::from_str(&std::fs::read_to_string("baseline.json").unwrap()).unwrap();
+//!
+//! let drift = plan::diff(&baseline, &mgr.snapshot().unwrap());
+//! if !drift.is_empty() {
+//!     println!("{drift}");
+//! }
+//! ```
+
+use std::{
+    collections::{BTreeMap, HashMap as StdHashMap},
+    fmt::{self, Display, Formatter},
+    mem,
+    net::IpAddr,
+    path::Path,
+    ptr,
+    sync::Mutex,
+};
+
+use aya::{
+    maps::{
+        lpm_trie::{Key, LpmTrie},
+        HashMap, Map, MapData, MapError,
+    },
+    Pod,
+};
+use ebpfguard_common::policy::{
+    self as ebpf_policy, untagged_namespace, CommKey, FileInodeKey, InodeKey, Ipv4CidrKey, Ipv4Key,
+    Ipv6CidrKey, Ipv6Key, CIDR_KEY_PREFIX_LEN, COMM_KEY_PREFIX_LEN, HOOK_BPF, HOOK_FILE_OPEN,
+    HOOK_INODE_CREATE, HOOK_SB_MOUNT, HOOK_SB_REMOUNT, HOOK_SB_UMOUNT, HOOK_SOCKET_BIND,
+    HOOK_SOCKET_CONNECT, HOOK_SOCKET_CREATE, HOOK_SOCKET_LISTEN, HOOK_TASK_FIX_SETGID,
+    HOOK_TASK_FIX_SETUID,
+};
+use log::warn;
+use serde::{de, Deserialize, Deserializer, Serialize, Serializer};
+
+use crate::{
+    config::ConfigMap,
+    error::EbpfguardError,
+    fs::InodeResolver,
+    generation::Generation,
+    hooks::{socket_bind, socket_create},
+    policy::{
+        cidr::{self, Cidr},
+        glob,
+        inode::subject_key,
+        BindFamily, GeoSelector, KeyLayout, Policy, PolicySubject, SocketOption,
+    },
+    simulate::{resolve, resolve_binaries},
+};
+
+/// Change of a single map entry. Keys and values are the raw bytes stored in
+/// the map (for LPM trie maps, the key starts with the prefix length),
+/// serialized as hex strings.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(tag = "op", rename_all = "snake_case")]
+pub enum Change {
+    Insert {
+        map: &'static str,
+        #[serde(serialize_with = "serialize_hex")]
+        key: Vec<u8>,
+        #[serde(serialize_with = "serialize_hex")]
+        value: Vec<u8>,
+    },
+    Update {
+        map: &'static str,
+        #[serde(serialize_with = "serialize_hex")]
+        key: Vec<u8>,
+        #[serde(serialize_with = "serialize_hex")]
+        old: Vec<u8>,
+        #[serde(serialize_with = "serialize_hex")]
+        new: Vec<u8>,
+    },
+    Delete {
+        map: &'static str,
+        #[serde(serialize_with = "serialize_hex")]
+        key: Vec<u8>,
+        #[serde(serialize_with = "serialize_hex")]
+        old: Vec<u8>,
+    },
+}
+
+impl Change {
+    /// Returns the change undoing this one.
+    pub fn inverse(&self) -> Change {
+        match self.clone() {
+            Change::Insert { map, key, value } => Change::Delete {
+                map,
+                key,
+                old: value,
+            },
+            Change::Update { map, key, old, new } => Change::Update {
+                map,
+                key,
+                old: new,
+                new: old,
+            },
+            Change::Delete { map, key, old } => Change::Insert {
+                map,
+                key,
+                value: old,
+            },
+        }
+    }
+
+    /// Returns the name of the changed map.
+    pub fn map(&self) -> &'static str {
+        match self {
+            Change::Insert { map, .. }
+            | Change::Update { map, .. }
+            | Change::Delete { map, .. } => *map,
+        }
+    }
+
+    /// Returns the raw key of the changed entry.
+    pub fn key(&self) -> &[u8] {
+        match self {
+            Change::Insert { key, .. }
+            | Change::Update { key, .. }
+            | Change::Delete { key, .. } => key,
+        }
+    }
+
+    /// Returns the raw value of the entry after the change, `None` if the
+    /// entry is deleted.
+    pub fn value(&self) -> Option<&[u8]> {
+        match self {
+            Change::Insert { value, .. } => Some(value),
+            Change::Update { new, .. } => Some(new),
+            Change::Delete { .. } => None,
+        }
+    }
+}
+
+/// Displayed as a line in the style of a diff: `+ MAP key => value` for
+/// inserts, `~ MAP key => new (was old)` for updates and
+/// `- MAP key (was old)` for deletes.
+impl Display for Change {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            Change::Insert { map, key, value } => {
+                write!(f, "+ {map} {} => {}", Hex(key), Hex(value))
+            }
+            Change::Update { map, key, old, new } => {
+                write!(f, "~ {map} {} => {} (was {})", Hex(key), Hex(new), Hex(old))
+            }
+            Change::Delete { map, key, old } => {
+                write!(f, "- {map} {} (was {})", Hex(key), Hex(old))
+            }
+        }
+    }
+}
+
+/// Changes of the policy maps, ordered by map and key. Displayed as one line
+/// per change (see [`Change`]), serialized as a JSON object with the list of
+/// `changes`.
+#[derive(Debug, Default, Clone, PartialEq, Eq, Serialize)]
+pub struct PolicyDiff {
+    changes: Vec<Change>,
+}
+
+impl PolicyDiff {
+    pub fn changes(&self) -> &[Change] {
+        &self.changes
+    }
+
+    /// Returns whether the plan doesn't change anything.
+    pub fn is_empty(&self) -> bool {
+        self.changes.is_empty()
+    }
+
+    /// Returns the changes undoing this diff, in the same order.
+    pub fn inverse(&self) -> PolicyDiff {
+        PolicyDiff {
+            changes: self.changes.iter().map(Change::inverse).collect(),
+        }
+    }
+}
+
+/// Returns the changes turning the `old` snapshot into `new`, e.g. the drift
+/// of the live maps from a baseline: entries only in `new` are inserts, only
+/// in `old` deletes, and entries with different values updates. Changes are
+/// ordered by map and key, and `diff(new, old)` is the
+/// [`inverse`](PolicyDiff::inverse) of `diff(old, new)`.
+pub fn diff(old: &MapSnapshot, new: &MapSnapshot) -> PolicyDiff {
+    old.diff(new)
+}
+
+impl Display for PolicyDiff {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        if self.changes.is_empty() {
+            return f.write_str("no changes");
+        }
+        for (i, change) in self.changes.iter().enumerate() {
+            if i > 0 {
+                writeln!(f)?;
+            }
+            write!(f, "{change}")?;
+        }
+        Ok(())
+    }
+}
+
+/// Entries of the policy maps of a namespace, by map name and raw key.
+/// Serialized as a JSON object with the list of `entries`, each with the
+/// `map` and the hex `key` and `value`. Deserializing fails on entries of
+/// maps which aren't policy maps, or whose sizes don't match the map.
+#[derive(Debug, Default, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(into = "SnapshotEntries", try_from = "SnapshotEntries")]
+pub struct MapSnapshot {
+    entries: BTreeMap<(&'static str, Vec<u8>), Vec<u8>>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct SnapshotEntries {
+    entries: Vec<SnapshotEntry>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct SnapshotEntry {
+    map: String,
+    #[serde(serialize_with = "serialize_hex", deserialize_with = "deserialize_hex")]
+    key: Vec<u8>,
+    #[serde(serialize_with = "serialize_hex", deserialize_with = "deserialize_hex")]
+    value: Vec<u8>,
+}
+
+impl From<MapSnapshot> for SnapshotEntries {
+    fn from(snapshot: MapSnapshot) -> Self {
+        let entries = snapshot
+            .entries
+            .into_iter()
+            .map(|((map, key), value)| SnapshotEntry {
+                map: map.to_owned(),
+                key,
+                value,
+            })
+            .collect();
+        SnapshotEntries { entries }
+    }
+}
+
+impl TryFrom<SnapshotEntries> for MapSnapshot {
+    type Error = EbpfguardError;
+
+    fn try_from(entries: SnapshotEntries) -> Result<Self, Self::Error> {
+        let mut snapshot = MapSnapshot::default();
+        for entry in entries.entries {
+            let map = POLICY_MAPS
+                .iter()
+                .find(|map| map.name == entry.map)
+                .filter(|map| {
+                    entry.key.len() == map.key_size && entry.value.len() == map.value_size
+                })
+                .ok_or(EbpfguardError::InvalidSnapshotEntry(entry.map))?;
+            snapshot.entries.insert((map.name, entry.key), entry.value);
+        }
+        Ok(snapshot)
+    }
+}
+
+impl MapSnapshot {
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Returns the changes turning this snapshot into `target`.
+    pub fn diff(&self, target: &MapSnapshot) -> PolicyDiff {
+        let mut changes = Vec::new();
+
+        for ((map, key), value) in target.entries.iter() {
+            match self.entries.get(&(*map, key.clone())) {
+                None => changes.push(Change::Insert {
+                    map: *map,
+                    key: key.clone(),
+                    value: value.clone(),
+                }),
+                Some(old) if old != value => changes.push(Change::Update {
+                    map: *map,
+                    key: key.clone(),
+                    old: old.clone(),
+                    new: value.clone(),
+                }),
+                Some(_) => {}
+            }
+        }
+        for ((map, key), old) in self.entries.iter() {
+            if !target.entries.contains_key(&(*map, key.clone())) {
+                changes.push(Change::Delete {
+                    map: *map,
+                    key: key.clone(),
+                    old: old.clone(),
+                });
+            }
+        }
+
+        changes.sort_by(|a, b| (a.map(), a.key()).cmp(&(b.map(), b.key())));
+        PolicyDiff { changes }
+    }
+
+    /// Checks that the diff was computed from entries equal to the ones of
+    /// the snapshot, i.e. that the maps didn't change since.
+    fn check(&self, diff: &PolicyDiff) -> Result<(), EbpfguardError> {
+        for change in diff.changes.iter() {
+            let entry = self.entries.get(&(change.map(), change.key().to_vec()));
+            let expected = match change {
+                Change::Insert { .. } => None,
+                Change::Update { old, .. } | Change::Delete { old, .. } => Some(old),
+            };
+            if entry != expected {
+                return Err(EbpfguardError::PlanOutdated(change.map().to_owned()));
+            }
+        }
+        Ok(())
+    }
+
+    /// Makes the changes of the diff in the snapshot.
+    pub fn apply(&mut self, diff: &PolicyDiff) {
+        for change in diff.changes.iter() {
+            let key = (change.map(), change.key().to_vec());
+            match change.value() {
+                Some(value) => self.entries.insert(key, value.to_vec()),
+                None => self.entries.remove(&key),
+            };
+        }
+    }
+
+    fn insert<K: Pod, V: Pod>(&mut self, map: &'static str, key: &K, value: &V) {
+        self.entries.insert((map, to_bytes(key)), to_bytes(value));
+    }
+
+    fn remove<K: Pod>(&mut self, map: &'static str, key: &K) {
+        self.entries.remove(&(map, to_bytes(key)));
+    }
+
+    fn insert_lpm<K: Pod, V: Pod>(
+        &mut self,
+        map: &'static str,
+        prefix_len: u32,
+        key: &K,
+        value: &V,
+    ) {
+        self.entries
+            .insert((map, lpm_key_bytes(prefix_len, key)), to_bytes(value));
+    }
+
+    fn insert_cidr(&mut self, maps: (&'static str, &'static str), key: InodeKey, cidr: Cidr) {
+        let prefix_len = CIDR_KEY_PREFIX_LEN + u32::from(cidr.prefix_len());
+        match cidr.addr() {
+            IpAddr::V4(addr) => {
+                let data = Ipv4CidrKey::new(key.namespace, key.inode, u32::from(addr));
+                self.insert_lpm(maps.0, prefix_len, &data, &0u8)
+            }
+            IpAddr::V6(addr) => {
+                let data = Ipv6CidrKey::new(key.namespace, key.inode, addr.octets());
+                self.insert_lpm(maps.1, prefix_len, &data, &0u8)
+            }
+        }
+    }
+}
+
+/// Builds the entries which the policies write to the maps of the namespace,
+/// applied in order like when adding them to the hooks. Subjects are
+/// resolved to inodes, so their binaries have to exist. `socket_connect_geo`
+/// policies can select CIDRs only, since ASNs and countries need a CIDR
+/// database.
+pub(crate) fn target<I>(
+    resolver: &dyn InodeResolver,
+    policies: I,
+    namespace: u32,
+    layout: KeyLayout,
+) -> Result<MapSnapshot, EbpfguardError>
+where
+    I: IntoIterator<Item = Policy>,
+{
+    let mut target = MapSnapshot::default();
+    let mut geo: StdHashMap<InodeKey, Vec<Cidr>> = StdHashMap::new();
+    let mut metadata: StdHashMap<InodeKey, Vec<Cidr>> = StdHashMap::new();
+    let key = |subject: PolicySubject| -> Result<InodeKey, EbpfguardError> {
+        Ok(subject_key(namespace, resolve(resolver, subject)?))
+    };
+
+    for policy in policies {
+        match policy {
+            Policy::Bpf(policy) => {
+                let map = allow_map(policy.allow, "ALLOWED_BPF", "DENIED_BPF");
+                target.insert(map, &key(policy.subject)?, &0u8);
+            }
+            Policy::FileOpen(policy) => {
+                let key = key(policy.subject)?;
+                let allow = policy.allow.resolve(resolver)?;
+                let deny = policy.deny.resolve(resolver)?;
+                target.insert("ALLOWED_FILE_OPEN", &key, &allow);
+                target.insert("DENIED_FILE_OPEN", &key, &deny);
+            }
+            Policy::FileOpenGlob(policy) => {
+                let key = key(policy.subject)?;
+                for (patterns, map) in [
+                    (policy.allow, "ALLOWED_FILE_OPEN_INODES"),
+                    (policy.deny, "DENIED_FILE_OPEN_INODES"),
+                ] {
+                    for pattern in patterns {
+                        for path in glob::expand(&pattern) {
+                            if let Ok(resolved) = resolver.resolve(&path) {
+                                let inode = resolved.inode;
+                                target.insert(map, &FileInodeKey::new(key, inode), &0u8);
+                            }
+                        }
+                    }
+                }
+            }
+            Policy::FileOpenProtected(policy) => {
+                let key = InodeKey::new(namespace, resolver.resolve(&policy.path)?.inode);
+                let binaries = resolve_binaries(resolver, policy.allow)?;
+                target.insert("PROTECTED_FILE_OPEN", &key, &binaries);
+            }
+            Policy::InodeCreate(policy) => {
+                let key = key(policy.subject)?;
+                for (paths, map) in [
+                    (policy.allow, "ALLOWED_INODE_CREATE"),
+                    (policy.deny, "DENIED_INODE_CREATE"),
+                ] {
+                    // An empty list of paths is a missing entry, like in
+                    // the hook.
+                    match paths {
+                        crate::policy::Paths::Paths(paths) if paths.is_empty() => {
+                            target.remove(map, &key)
+                        }
+                        paths => target.insert(map, &key, &paths.resolve(resolver)?),
+                    }
+                }
+            }
+            Policy::SbMount(policy) => {
+                let map = allow_map(policy.allow, "ALLOWED_SB_MOUNT", "DENIED_SB_MOUNT");
+                target.insert(map, &key(policy.subject)?, &0u8);
+            }
+            Policy::SbRemount(policy) => {
+                let map = allow_map(policy.allow, "ALLOWED_SB_REMOUNT", "DENIED_SB_REMOUNT");
+                target.insert(map, &key(policy.subject)?, &0u8);
+            }
+            Policy::SbUmount(policy) => {
+                let map = allow_map(policy.allow, "ALLOWED_SB_UMOUNT", "DENIED_SB_UMOUNT");
+                target.insert(map, &key(policy.subject)?, &0u8);
+            }
+            Policy::SocketBind(policy) => {
+                let key = key(policy.subject)?;
+                let allow: ebpf_policy::Ports = policy.allow.into();
+                let deny: ebpf_policy::Ports = policy.deny.into();
+                let options = SocketOption::to_flags(&policy.deny_options);
+                let (allowed_map, denied_map) = match policy.family {
+                    None => ("ALLOWED_SOCKET_BIND", "DENIED_SOCKET_BIND"),
+                    Some(BindFamily::Ipv4) => ("ALLOWED_SOCKET_BIND_V4", "DENIED_SOCKET_BIND_V4"),
+                    Some(BindFamily::Ipv6) => ("ALLOWED_SOCKET_BIND_V6", "DENIED_SOCKET_BIND_V6"),
+                };
+                target.insert(allowed_map, &key, &allow);
+                target.insert(denied_map, &key, &deny);
+                if policy.family.is_none() || options != 0 {
+                    target.insert("OPTIONS_SOCKET_BIND", &key, &options);
+                }
+            }
+            Policy::SocketBindComm(policy) => {
+                let (comm, len) = policy.comm.to_bytes();
+                let prefix_len = COMM_KEY_PREFIX_LEN + 8 * len as u32;
+                let key = CommKey::new(namespace, comm);
+                let allow: ebpf_policy::Ports = policy.allow.into();
+                let deny: ebpf_policy::Ports = policy.deny.into();
+                target.insert_lpm("ALLOWED_SOCKET_BIND_COMM", prefix_len, &key, &allow);
+                target.insert_lpm("DENIED_SOCKET_BIND_COMM", prefix_len, &key, &deny);
+            }
+            Policy::SocketBindPacket(policy) => {
+                let map = allow_map(
+                    policy.allow,
+                    "ALLOWED_SOCKET_BIND_PACKET",
+                    "DENIED_SOCKET_BIND_PACKET",
+                );
+                target.insert(map, &key(policy.subject)?, &0u8);
+            }
+            Policy::SocketConnect(policy) => {
+                let key = key(policy.subject)?;
+                let (allow_v4, allow_v6) = policy.allow.into_ebpf();
+                let (deny_v4, deny_v6) = policy.deny.into_ebpf();
+                target.insert("ALLOWED_SOCKET_CONNECT_V4", &key, &allow_v4);
+                target.insert("DENIED_SOCKET_CONNECT_V4", &key, &deny_v4);
+                target.insert("ALLOWED_SOCKET_CONNECT_V6", &key, &allow_v6);
+                target.insert("DENIED_SOCKET_CONNECT_V6", &key, &deny_v6);
+            }
+            Policy::SocketConnectGeo(policy) => {
+                let cidrs = geo.entry(key(policy.subject)?).or_default();
+                for selector in policy.deny {
+                    match selector {
+                        GeoSelector::Cidr(cidr) => cidrs.push(cidr),
+                        _ => return Err(EbpfguardError::NoCidrDatabase),
+                    }
+                }
+            }
+            Policy::SocketConnectMetadata(policy) => {
+                let cidrs = policy.cidrs()?;
+                // Replaces the previous policy of the subject, like the hook.
+                metadata.insert(key(policy.subject)?, cidrs);
+            }
+            Policy::SocketConnectPorts(policy) => {
+                let (allow, deny) = policy.to_ebpf()?;
+                let key = key(policy.subject)?;
+                target.insert("ALLOWED_SOCKET_CONNECT_PORTS", &key, &allow);
+                target.insert("DENIED_SOCKET_CONNECT_PORTS", &key, &deny);
+            }
+            Policy::SocketConnectProtected(policy) => {
+                if layout.port != policy.port.is_some() {
+                    return Err(EbpfguardError::KeyLayoutMismatch(layout.port));
+                }
+                let flags = layout.to_flags();
+                let port = policy.port.unwrap_or(0);
+                let binaries = resolve_binaries(resolver, policy.allow)?;
+                match policy.addr {
+                    IpAddr::V4(addr) => {
+                        let key = Ipv4Key::new(flags, namespace, u32::from(addr), port);
+                        target.insert("PROTECTED_SOCKET_CONNECT_V4", &key, &binaries);
+                    }
+                    IpAddr::V6(addr) => {
+                        let key = Ipv6Key::new(flags, namespace, addr.octets(), port);
+                        target.insert("PROTECTED_SOCKET_CONNECT_V6", &key, &binaries);
+                    }
+                }
+            }
+            Policy::SocketCreate(policy) => {
+                let key = key(policy.subject)?;
+                let allow = policy.allow.into_ebpf()?;
+                let deny = policy.deny.into_ebpf()?;
+                target.insert("ALLOWED_SOCKET_CREATE", &key, &allow);
+                target.insert("DENIED_SOCKET_CREATE", &key, &deny);
+            }
+            Policy::SocketListen(policy) => {
+                let key = key(policy.subject)?;
+                let allow: ebpf_policy::Ports = policy.allow.into();
+                let deny: ebpf_policy::Ports = policy.deny.into();
+                let options = SocketOption::to_flags(&policy.deny_options);
+                target.insert("ALLOWED_SOCKET_LISTEN", &key, &allow);
+                target.insert("DENIED_SOCKET_LISTEN", &key, &deny);
+                target.insert("OPTIONS_SOCKET_LISTEN", &key, &options);
+            }
+            Policy::TaskFixSetgid(policy) => {
+                let map = allow_map(
+                    policy.allow,
+                    "ALLOWED_TASK_FIX_SETGID",
+                    "DENIED_TASK_FIX_SETGID",
+                );
+                target.insert(map, &key(policy.subject)?, &0u8);
+            }
+            Policy::TaskFixSetuid(policy) => {
+                let map = allow_map(
+                    policy.allow,
+                    "ALLOWED_TASK_FIX_SETUID",
+                    "DENIED_TASK_FIX_SETUID",
+                );
+                target.insert(map, &key(policy.subject)?, &0u8);
+            }
+        }
+    }
+
+    for (key, cidrs) in geo {
+        for cidr in cidr::compact(cidrs) {
+            target.insert_cidr(
+                (
+                    "DENIED_SOCKET_CONNECT_CIDR_V4",
+                    "DENIED_SOCKET_CONNECT_CIDR_V6",
+                ),
+                key,
+                cidr,
+            );
+        }
+    }
+    for (key, cidrs) in metadata {
+        for cidr in cidrs {
+            target.insert_cidr(
+                (
+                    "DENIED_SOCKET_CONNECT_METADATA_V4",
+                    "DENIED_SOCKET_CONNECT_METADATA_V6",
+                ),
+                key,
+                cidr,
+            );
+        }
+    }
+
+    Ok(target)
+}
+
+fn allow_map(allow: bool, allowed: &'static str, denied: &'static str) -> &'static str {
+    if allow {
+        allowed
+    } else {
+        denied
+    }
+}
+
+/// Reads the entries of all policy maps of the namespace, opening the maps
+/// from their pins. Only the generation of each hook's policies which the
+/// eBPF programs read is read, and keys are stored without the generation.
+pub(crate) fn read_snapshot(
+    maps_path: &Path,
+    namespace: u32,
+) -> Result<MapSnapshot, EbpfguardError> {
+    let mut snapshot = MapSnapshot::default();
+    for map in POLICY_MAPS.iter() {
+        let key_namespace = policy_namespace(maps_path, namespace, map.hook)?;
+        read_map(maps_path, map, namespace, key_namespace, &mut snapshot)?;
+    }
+    Ok(snapshot)
+}
+
+/// Reads the key layout of the protected address maps in the namespace.
+pub(crate) fn key_layout(maps_path: &Path, namespace: u32) -> Result<KeyLayout, EbpfguardError> {
+    let config = ConfigMap::open(maps_path, namespace)?.get()?;
+    Ok(KeyLayout::from_flags(config.key_layout))
+}
+
+/// Makes the changes of the diff in the maps of the namespace. Fails on the
+/// first change which can't be made, leaving the previous ones made.
+pub(crate) fn write_diff(
+    maps_path: &Path,
+    namespace: u32,
+    diff: &PolicyDiff,
+) -> Result<(), EbpfguardError> {
+    for change in diff.changes.iter() {
+        let map = policy_map(change)?;
+        let key_namespace = policy_namespace(maps_path, namespace, map.hook)?;
+        (map.write)(
+            &mut map.open(maps_path, namespace)?,
+            map.name,
+            key_namespace,
+            change,
+        )?;
+    }
+    Ok(())
+}
+
+/// Removes the entries of the hook's policy maps in the namespace, see
+/// [`PolicyManager::clear_hook`](crate::PolicyManager::clear_hook), and
+/// returns the removals as a diff.
+///
+/// The maps are shared by all namespaces and aya has no batch operations,
+/// so each map is opened once, its keys of the namespace are collected and
+/// removed one by one, in the order of [`POLICY_MAPS`].
+pub(crate) fn clear_hook(
+    maps_path: &Path,
+    namespace: u32,
+    hook: u32,
+) -> Result<PolicyDiff, EbpfguardError> {
+    let key_namespace = policy_namespace(maps_path, namespace, hook)?;
+    let mut changes = Vec::new();
+    for map in POLICY_MAPS.iter().filter(|map| map.hook == hook) {
+        let mut snapshot = MapSnapshot::default();
+        read_map(maps_path, map, namespace, key_namespace, &mut snapshot)?;
+        let diff = snapshot.diff(&MapSnapshot::default());
+        let mut data = map.open(maps_path, namespace)?;
+        for change in diff.changes.iter() {
+            (map.write)(&mut data, map.name, key_namespace, change)?;
+        }
+        changes.extend(diff.changes);
+    }
+    Ok(PolicyDiff { changes })
+}
+
+/// Serializes the commits of atomic plans within this process.
+static COMMITS: Mutex<()> = Mutex::new(());
+
+/// Makes the changes of the diff in the maps of the namespace as one
+/// transaction, see [`PolicyManager::apply_plan_atomic`](crate::PolicyManager::apply_plan_atomic).
+///
+/// The entries of the namespace are checked against the diff first. Each
+/// changed hook then gets its entries with the changes of the diff staged
+/// in the generation of its policies which the programs don't read,
+/// replacing what a failed commit might have left there. A write which
+/// fails rolls back the staged ones. The commit switches the changed hooks
+/// to their staged generations, bumps the `socket_bind` generation
+/// (switching the hooks back if that fails) and removes the entries of the
+/// previous generations, which the next commit removes otherwise.
+pub(crate) fn write_diff_atomic(
+    maps_path: &Path,
+    namespace: u32,
+    diff: &PolicyDiff,
+) -> Result<(), EbpfguardError> {
+    let name = "GENERATION_SOCKET_BIND";
+    let mut bind_generation =
+        HashMap::<_, u32, u64>::try_from(Map::HashMap(open(maps_path, name)?))
+            .map_err(|e| EbpfguardError::from_map_error(name, e))?;
+
+    let _guard = COMMITS.lock().unwrap();
+    let live = read_snapshot(maps_path, namespace)?;
+    live.check(diff)?;
+
+    let mut generations = BTreeMap::new();
+    for change in diff.changes.iter() {
+        let hook = policy_map(change)?.hook;
+        if !generations.contains_key(&hook) {
+            generations.insert(hook, Generation::open(maps_path, namespace, hook)?);
+        }
+    }
+    // Namespaces of the keys of the read and the staged generations.
+    let mut namespaces = BTreeMap::new();
+    for (hook, generation) in generations.iter() {
+        namespaces.insert(
+            *hook,
+            (
+                generation.policy_namespace()?,
+                generation.staged_namespace()?,
+            ),
+        );
+    }
+    let mut maps = BTreeMap::new();
+    let mut stale = MapSnapshot::default();
+    for map in POLICY_MAPS
+        .iter()
+        .filter(|map| generations.contains_key(&map.hook))
+    {
+        read_map(
+            maps_path,
+            map,
+            namespace,
+            namespaces[&map.hook].1,
+            &mut stale,
+        )?;
+        maps.insert(map.name, (map, map.open(maps_path, namespace)?));
+    }
+    let mut target = live.clone();
+    target.apply(diff);
+    let in_changed_maps = |change: &Change| maps.contains_key(change.map());
+    let staging: Vec<_> = stale
+        .diff(&target)
+        .changes
+        .into_iter()
+        .filter(in_changed_maps)
+        .collect();
+    let previous: Vec<_> = live
+        .diff(&MapSnapshot::default())
+        .changes
+        .into_iter()
+        .filter(in_changed_maps)
+        .collect();
+
+    let mut write = |change: &Change, staged: bool| {
+        let (map, data) = maps.get_mut(change.map()).unwrap();
+        let (read, stage) = namespaces[&map.hook];
+        let key_namespace = if staged { stage } else { read };
+        (map.write)(data, map.name, key_namespace, change)
+    };
+    commit(&staging, &mut |change: &Change| write(change, true))?;
+
+    let (mut res, mut flipped) = (Ok(()), 0);
+    for generation in generations.values_mut() {
+        res = generation.flip();
+        if res.is_err() {
+            break;
+        }
+        flipped += 1;
+    }
+    let res = res.and_then(|()| socket_bind::bump_generation(&mut bind_generation, namespace));
+    if let Err(e) = res {
+        for generation in generations.values_mut().take(flipped).rev() {
+            if let Err(rollback) = generation.flip() {
+                return Err(EbpfguardError::RollbackFailed(
+                    Box::new(e),
+                    Box::new(rollback),
+                ));
+            }
+        }
+        return Err(e);
+    }
+
+    for change in previous.iter() {
+        if let Err(e) = write(change, false) {
+            warn!("failed to remove the previous policies of an atomic plan: {e}");
+            break;
+        }
+    }
+    Ok(())
+}
+
+/// Makes the changes with `write`. If one fails, the ones made before it are
+/// rolled back.
+fn commit<W>(changes: &[Change], write: &mut W) -> Result<(), EbpfguardError>
+where
+    W: FnMut(&Change) -> Result<(), EbpfguardError>,
+{
+    for (i, change) in changes.iter().enumerate() {
+        if let Err(e) = write(change) {
+            return Err(rollback(&changes[..i], write, e));
+        }
+    }
+    Ok(())
+}
+
+/// Undoes the changes made before `error` in reverse order, and returns the
+/// error to report.
+fn rollback<W>(made: &[Change], write: &mut W, error: EbpfguardError) -> EbpfguardError
+where
+    W: FnMut(&Change) -> Result<(), EbpfguardError>,
+{
+    for change in made.iter().rev() {
+        if let Err(e) = write(&change.inverse()) {
+            return EbpfguardError::RollbackFailed(Box::new(error), Box::new(e));
+        }
+    }
+    error
+}
+
+/// Policy maps with a second slot, by the names of their first slot (which
+/// snapshots and plans use), the second slot and the map of active slots,
+/// see [`SocketCreate::swap_policies`](crate::hooks::socket_create::SocketCreate::swap_policies).
+const SLOT_MAPS: [(&str, &str, &str); 2] = [
+    (
+        "ALLOWED_SOCKET_CREATE",
+        "ALLOWED_SOCKET_CREATE_1",
+        "SLOT_SOCKET_CREATE",
+    ),
+    (
+        "DENIED_SOCKET_CREATE",
+        "DENIED_SOCKET_CREATE_1",
+        "SLOT_SOCKET_CREATE",
+    ),
+];
+
+/// Returns the namespace of the keys of the hook's policies which the eBPF
+/// programs read in the namespace.
+fn policy_namespace(maps_path: &Path, namespace: u32, hook: u32) -> Result<u32, EbpfguardError> {
+    Generation::open(maps_path, namespace, hook)?.policy_namespace()
+}
+
+/// Reads the entries of the policy map with keys in `key_namespace` from the
+/// slot which the eBPF programs read in the namespace.
+fn read_map(
+    maps_path: &Path,
+    map: &PolicyMap,
+    namespace: u32,
+    key_namespace: u32,
+    snapshot: &mut MapSnapshot,
+) -> Result<(), EbpfguardError> {
+    let data = open(maps_path, slot_name(maps_path, map.name, namespace)?)?;
+    (map.read)(data, map.name, key_namespace, snapshot)
+}
+
+/// Returns the name of the pin of the policy map's slot which the eBPF
+/// programs read in the namespace.
+fn slot_name(
+    maps_path: &Path,
+    name: &'static str,
+    namespace: u32,
+) -> Result<&'static str, EbpfguardError> {
+    let (second, slot) = match SLOT_MAPS.iter().find(|(first, _, _)| *first == name) {
+        Some((_, second, slot)) => (*second, *slot),
+        None => return Ok(name),
+    };
+    let map = HashMap::<_, u32, u32>::try_from(Map::HashMap(open(maps_path, slot)?))
+        .map_err(|e| EbpfguardError::from_map_error(slot, e))?;
+    match socket_create::active_slot(&map, namespace)? {
+        0 => Ok(name),
+        _ => Ok(second),
+    }
+}
+
+fn open(maps_path: &Path, name: &str) -> Result<MapData, EbpfguardError> {
+    MapData::from_pin(maps_path.join(name)).map_err(|e| EbpfguardError::from_map_error(name, e))
+}
+
+type ReadFn = fn(MapData, &'static str, u32, &mut MapSnapshot) -> Result<(), EbpfguardError>;
+type WriteFn = fn(&mut Map, &'static str, u32, &Change) -> Result<(), EbpfguardError>;
+
+/// Policy map, with functions reading and writing its entries as raw bytes,
+/// the sizes of the raw keys and values and the hook reading it.
+struct PolicyMap {
+    name: &'static str,
+    map: fn(MapData) -> Map,
+    read: ReadFn,
+    write: WriteFn,
+    key_size: usize,
+    value_size: usize,
+    hook: u32,
+}
+
+impl PolicyMap {
+    const fn hash<K: PolicyKey, V: Pod>(name: &'static str, hook: u32) -> Self {
+        Self {
+            name,
+            map: Map::HashMap,
+            read: read_hash::<K, V>,
+            write: write_hash::<K, V>,
+            key_size: mem::size_of::<K>(),
+            value_size: mem::size_of::<V>(),
+            hook,
+        }
+    }
+
+    const fn lpm_trie<K: PolicyKey, V: Pod>(name: &'static str, hook: u32) -> Self {
+        Self {
+            name,
+            map: Map::LpmTrie,
+            read: read_lpm_trie::<K, V>,
+            write: write_lpm_trie::<K, V>,
+            key_size: mem::size_of::<u32>() + mem::size_of::<K>(),
+            value_size: mem::size_of::<V>(),
+            hook,
+        }
+    }
+
+    /// Opens the slot of the map which the eBPF programs read in the
+    /// namespace.
+    fn open(&self, maps_path: &Path, namespace: u32) -> Result<Map, EbpfguardError> {
+        let pin = slot_name(maps_path, self.name, namespace)?;
+        Ok((self.map)(open(maps_path, pin)?))
+    }
+}
+
+/// Returns the policy map changed by the change.
+fn policy_map(change: &Change) -> Result<&'static PolicyMap, EbpfguardError> {
+    POLICY_MAPS
+        .iter()
+        .find(|map| map.name == change.map())
+        .ok_or_else(|| EbpfguardError::MapNotFound(change.map().to_owned()))
+}
+
+/// Maps written by policies, see [`target`].
+const POLICY_MAPS: [PolicyMap; 47] = [
+    PolicyMap::hash::<InodeKey, u8>("ALLOWED_BPF", HOOK_BPF),
+    PolicyMap::hash::<InodeKey, u8>("DENIED_BPF", HOOK_BPF),
+    PolicyMap::hash::<InodeKey, ebpf_policy::Paths>("ALLOWED_FILE_OPEN", HOOK_FILE_OPEN),
+    PolicyMap::hash::<InodeKey, ebpf_policy::Paths>("DENIED_FILE_OPEN", HOOK_FILE_OPEN),
+    PolicyMap::hash::<InodeKey, ebpf_policy::Binaries>("PROTECTED_FILE_OPEN", HOOK_FILE_OPEN),
+    PolicyMap::hash::<FileInodeKey, u8>("ALLOWED_FILE_OPEN_INODES", HOOK_FILE_OPEN),
+    PolicyMap::hash::<FileInodeKey, u8>("DENIED_FILE_OPEN_INODES", HOOK_FILE_OPEN),
+    PolicyMap::hash::<InodeKey, ebpf_policy::Paths>("ALLOWED_INODE_CREATE", HOOK_INODE_CREATE),
+    PolicyMap::hash::<InodeKey, ebpf_policy::Paths>("DENIED_INODE_CREATE", HOOK_INODE_CREATE),
+    PolicyMap::hash::<InodeKey, u8>("ALLOWED_SB_MOUNT", HOOK_SB_MOUNT),
+    PolicyMap::hash::<InodeKey, u8>("DENIED_SB_MOUNT", HOOK_SB_MOUNT),
+    PolicyMap::hash::<InodeKey, u8>("ALLOWED_SB_REMOUNT", HOOK_SB_REMOUNT),
+    PolicyMap::hash::<InodeKey, u8>("DENIED_SB_REMOUNT", HOOK_SB_REMOUNT),
+    PolicyMap::hash::<InodeKey, u8>("ALLOWED_SB_UMOUNT", HOOK_SB_UMOUNT),
+    PolicyMap::hash::<InodeKey, u8>("DENIED_SB_UMOUNT", HOOK_SB_UMOUNT),
+    PolicyMap::hash::<InodeKey, ebpf_policy::Ports>("ALLOWED_SOCKET_BIND", HOOK_SOCKET_BIND),
+    PolicyMap::hash::<InodeKey, ebpf_policy::Ports>("DENIED_SOCKET_BIND", HOOK_SOCKET_BIND),
+    PolicyMap::hash::<InodeKey, ebpf_policy::Ports>("ALLOWED_SOCKET_BIND_V4", HOOK_SOCKET_BIND),
+    PolicyMap::hash::<InodeKey, ebpf_policy::Ports>("DENIED_SOCKET_BIND_V4", HOOK_SOCKET_BIND),
+    PolicyMap::hash::<InodeKey, ebpf_policy::Ports>("ALLOWED_SOCKET_BIND_V6", HOOK_SOCKET_BIND),
+    PolicyMap::hash::<InodeKey, ebpf_policy::Ports>("DENIED_SOCKET_BIND_V6", HOOK_SOCKET_BIND),
+    PolicyMap::hash::<InodeKey, u8>("OPTIONS_SOCKET_BIND", HOOK_SOCKET_BIND),
+    PolicyMap::lpm_trie::<CommKey, ebpf_policy::Ports>(
+        "ALLOWED_SOCKET_BIND_COMM",
+        HOOK_SOCKET_BIND,
+    ),
+    PolicyMap::lpm_trie::<CommKey, ebpf_policy::Ports>("DENIED_SOCKET_BIND_COMM", HOOK_SOCKET_BIND),
+    PolicyMap::hash::<InodeKey, u8>("ALLOWED_SOCKET_BIND_PACKET", HOOK_SOCKET_BIND),
+    PolicyMap::hash::<InodeKey, u8>("DENIED_SOCKET_BIND_PACKET", HOOK_SOCKET_BIND),
+    PolicyMap::hash::<InodeKey, ebpf_policy::Ipv4Addrs>(
+        "ALLOWED_SOCKET_CONNECT_V4",
+        HOOK_SOCKET_CONNECT,
+    ),
+    PolicyMap::hash::<InodeKey, ebpf_policy::Ipv4Addrs>(
+        "DENIED_SOCKET_CONNECT_V4",
+        HOOK_SOCKET_CONNECT,
+    ),
+    PolicyMap::hash::<InodeKey, ebpf_policy::Ipv6Addrs>(
+        "ALLOWED_SOCKET_CONNECT_V6",
+        HOOK_SOCKET_CONNECT,
+    ),
+    PolicyMap::hash::<InodeKey, ebpf_policy::Ipv6Addrs>(
+        "DENIED_SOCKET_CONNECT_V6",
+        HOOK_SOCKET_CONNECT,
+    ),
+    PolicyMap::hash::<Ipv4Key, ebpf_policy::Binaries>(
+        "PROTECTED_SOCKET_CONNECT_V4",
+        HOOK_SOCKET_CONNECT,
+    ),
+    PolicyMap::hash::<Ipv6Key, ebpf_policy::Binaries>(
+        "PROTECTED_SOCKET_CONNECT_V6",
+        HOOK_SOCKET_CONNECT,
+    ),
+    PolicyMap::hash::<InodeKey, ebpf_policy::PortRanges>(
+        "ALLOWED_SOCKET_CONNECT_PORTS",
+        HOOK_SOCKET_CONNECT,
+    ),
+    PolicyMap::hash::<InodeKey, ebpf_policy::PortRanges>(
+        "DENIED_SOCKET_CONNECT_PORTS",
+        HOOK_SOCKET_CONNECT,
+    ),
+    PolicyMap::lpm_trie::<Ipv4CidrKey, u8>("DENIED_SOCKET_CONNECT_CIDR_V4", HOOK_SOCKET_CONNECT),
+    PolicyMap::lpm_trie::<Ipv6CidrKey, u8>("DENIED_SOCKET_CONNECT_CIDR_V6", HOOK_SOCKET_CONNECT),
+    PolicyMap::lpm_trie::<Ipv4CidrKey, u8>(
+        "DENIED_SOCKET_CONNECT_METADATA_V4",
+        HOOK_SOCKET_CONNECT,
+    ),
+    PolicyMap::lpm_trie::<Ipv6CidrKey, u8>(
+        "DENIED_SOCKET_CONNECT_METADATA_V6",
+        HOOK_SOCKET_CONNECT,
+    ),
+    PolicyMap::hash::<InodeKey, ebpf_policy::SocketKinds>(
+        "ALLOWED_SOCKET_CREATE",
+        HOOK_SOCKET_CREATE,
+    ),
+    PolicyMap::hash::<InodeKey, ebpf_policy::SocketKinds>(
+        "DENIED_SOCKET_CREATE",
+        HOOK_SOCKET_CREATE,
+    ),
+    PolicyMap::hash::<InodeKey, ebpf_policy::Ports>("ALLOWED_SOCKET_LISTEN", HOOK_SOCKET_LISTEN),
+    PolicyMap::hash::<InodeKey, ebpf_policy::Ports>("DENIED_SOCKET_LISTEN", HOOK_SOCKET_LISTEN),
+    PolicyMap::hash::<InodeKey, u8>("OPTIONS_SOCKET_LISTEN", HOOK_SOCKET_LISTEN),
+    PolicyMap::hash::<InodeKey, u8>("ALLOWED_TASK_FIX_SETGID", HOOK_TASK_FIX_SETGID),
+    PolicyMap::hash::<InodeKey, u8>("DENIED_TASK_FIX_SETGID", HOOK_TASK_FIX_SETGID),
+    PolicyMap::hash::<InodeKey, u8>("ALLOWED_TASK_FIX_SETUID", HOOK_TASK_FIX_SETUID),
+    PolicyMap::hash::<InodeKey, u8>("DENIED_TASK_FIX_SETUID", HOOK_TASK_FIX_SETUID),
+];
+
+/// Key of a policy map, which belongs to a namespace tagged with the
+/// generation of the policies, see [`Generation`].
+trait PolicyKey: Pod {
+    fn namespace(&self) -> u32;
+    fn with_namespace(self, namespace: u32) -> Self;
+}
+
+macro_rules! impl_policy_key {
+    ($($key:ty),*) => {
+        $(
+            impl PolicyKey for $key {
+                fn namespace(&self) -> u32 {
+                    self.namespace
+                }
+
+                fn with_namespace(mut self, namespace: u32) -> Self {
+                    self.namespace = namespace;
+                    self
+                }
+            }
+        )*
+    };
+}
+
+impl_policy_key!(InodeKey, FileInodeKey, Ipv4Key, Ipv6Key);
+
+// Namespaces of LPM trie keys are big-endian.
+macro_rules! impl_policy_key_be {
+    ($($key:ty),*) => {
+        $(
+            impl PolicyKey for $key {
+                fn namespace(&self) -> u32 {
+                    u32::from_be(self.namespace)
+                }
+
+                fn with_namespace(mut self, namespace: u32) -> Self {
+                    self.namespace = namespace.to_be();
+                    self
+                }
+            }
+        )*
+    };
+}
+
+impl_policy_key_be!(Ipv4CidrKey, Ipv6CidrKey, CommKey);
+
+/// Reads the entries with keys in `namespace`, storing the keys without the
+/// generation.
+fn read_hash<K: PolicyKey, V: Pod>(
+    map: MapData,
+    name: &'static str,
+    namespace: u32,
+    snapshot: &mut MapSnapshot,
+) -> Result<(), EbpfguardError> {
+    let map = HashMap::<_, K, V>::try_from(Map::HashMap(map))
+        .map_err(|e| EbpfguardError::from_map_error(name, e))?;
+    for res in map.iter() {
+        let (key, value) = res?;
+        if key.namespace() == namespace {
+            let key = key.with_namespace(untagged_namespace(namespace));
+            snapshot.insert(name, &key, &value);
+        }
+    }
+    Ok(())
+}
+
+/// Makes the change with its key in `namespace`.
+fn write_hash<K: PolicyKey, V: Pod>(
+    map: &mut Map,
+    name: &'static str,
+    namespace: u32,
+    change: &Change,
+) -> Result<(), EbpfguardError> {
+    let mut map =
+        HashMap::<_, K, V>::try_from(map).map_err(|e| EbpfguardError::from_map_error(name, e))?;
+    let key = from_bytes::<K>(change.key()).with_namespace(namespace);
+    match change.value() {
+        Some(value) => map.insert(key, from_bytes::<V>(value), 0)?,
+        None => match map.remove(&key) {
+            Ok(()) | Err(MapError::KeyNotFound) => {}
+            Err(e) => return Err(e.into()),
+        },
+    }
+    Ok(())
+}
+
+fn read_lpm_trie<K: PolicyKey, V: Pod>(
+    map: MapData,
+    name: &'static str,
+    namespace: u32,
+    snapshot: &mut MapSnapshot,
+) -> Result<(), EbpfguardError> {
+    let map = LpmTrie::<_, K, V>::try_from(Map::LpmTrie(map))
+        .map_err(|e| EbpfguardError::from_map_error(name, e))?;
+    for key in map.keys() {
+        let key = key?;
+        // Copied out, since fields of LPM trie keys can't be borrowed.
+        let (prefix_len, data) = (key.prefix_len, key.data);
+        if data.namespace() == namespace {
+            let value = map.get(&key, 0)?;
+            let data = data.with_namespace(untagged_namespace(namespace));
+            snapshot.insert_lpm(name, prefix_len, &data, &value);
+        }
+    }
+    Ok(())
+}
+
+fn write_lpm_trie<K: PolicyKey, V: Pod>(
+    map: &mut Map,
+    name: &'static str,
+    namespace: u32,
+    change: &Change,
+) -> Result<(), EbpfguardError> {
+    let mut map =
+        LpmTrie::<_, K, V>::try_from(map).map_err(|e| EbpfguardError::from_map_error(name, e))?;
+    let (prefix_len, data) = change.key().split_at(mem::size_of::<u32>());
+    let key = Key::new(
+        u32::from_ne_bytes(prefix_len.try_into().unwrap()),
+        from_bytes::<K>(data).with_namespace(namespace),
+    );
+    match change.value() {
+        Some(value) => map.insert(&key, from_bytes::<V>(value), 0)?,
+        None => match map.remove(&key) {
+            Ok(()) | Err(MapError::KeyNotFound) => {}
+            Err(e) => return Err(e.into()),
+        },
+    }
+    Ok(())
+}
+
+fn lpm_key_bytes<K: Pod>(prefix_len: u32, data: &K) -> Vec<u8> {
+    let mut bytes = prefix_len.to_ne_bytes().to_vec();
+    bytes.extend(to_bytes(data));
+    bytes
+}
+
+fn to_bytes<T: Pod>(value: &T) -> Vec<u8> {
+    // SAFETY: `Pod` types are plain data without uninitialized bytes.
+    unsafe { std::slice::from_raw_parts(value as *const T as *const u8, mem::size_of::<T>()) }
+        .to_vec()
+}
+
+/// Reads a value from raw bytes. Diffs are only built from entries of maps
+/// with the same types, so the size always matches.
+fn from_bytes<T: Pod>(bytes: &[u8]) -> T {
+    assert_eq!(bytes.len(), mem::size_of::<T>(), "entry size mismatch");
+    // SAFETY: `Pod` types are valid for any bytes, and the size is checked.
+    unsafe { ptr::read_unaligned(bytes.as_ptr() as *const T) }
+}
+
+struct Hex<'a>(&'a [u8]);
+
+impl Display for Hex<'_> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        for byte in self.0 {
+            write!(f, "{byte:02x}")?;
+        }
+        Ok(())
+    }
+}
+
+fn serialize_hex<S: Serializer>(bytes: &[u8], serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.collect_str(&Hex(bytes))
+}
+
+fn deserialize_hex<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<u8>, D::Error> {
+    let hex = String::deserialize(deserializer)?;
+    if hex.len() % 2 != 0 {
+        return Err(de::Error::custom("odd number of hex digits"));
+    }
+    (0..hex.len())
+        .step_by(2)
+        .map(|i| {
+            hex.get(i..i + 2)
+                .and_then(|byte| u8::from_str_radix(byte, 16).ok())
+                .ok_or_else(|| de::Error::custom(format!("invalid hex `{hex}`")))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use std::net::Ipv4Addr;
+
+    use ebpfguard_common::policy::generation_namespace;
+
+    use super::*;
+    use crate::{
+        fs::LocalInodeResolver,
+        policy::{
+            comm::CommPattern, Ports, SbMount, SbUmount, SocketBind, SocketBindComm,
+            SocketConnectProtected,
+        },
+    };
+
+    fn socket_bind(deny: Vec<u16>) -> Policy {
+        Policy::SocketBind(SocketBind {
+            subject: PolicySubject::All,
+            allow: Ports::All,
+            deny: Ports::Ports(deny),
+            deny_options: vec![],
+            family: None,
+        })
+    }
+
+    fn snapshot(policies: Vec<Policy>) -> MapSnapshot {
+        target(&LocalInodeResolver, policies, 0, KeyLayout::default()).unwrap()
+    }
+
+    #[test]
+    fn test_diff() {
+        let live = snapshot(vec![socket_bind(vec![22])]);
+        let target = snapshot(vec![
+            socket_bind(vec![22, 23]),
+            Policy::SocketBindComm(SocketBindComm {
+                comm: CommPattern::Prefix("java".to_owned()),
+                allow: Ports::All,
+                deny: Ports::All,
+            }),
+        ]);
+
+        let diff = live.diff(&target);
+        let ops: Vec<_> = diff
+            .changes()
+            .iter()
+            .map(|change| match change {
+                Change::Insert { map, .. } => ("insert", *map),
+                Change::Update { map, .. } => ("update", *map),
+                Change::Delete { map, .. } => ("delete", *map),
+            })
+            .collect();
+        assert_eq!(
+            ops,
+            [
+                ("insert", "ALLOWED_SOCKET_BIND_COMM"),
+                ("update", "DENIED_SOCKET_BIND"),
+                ("insert", "DENIED_SOCKET_BIND_COMM"),
+            ]
+        );
+
+        let diff = target.diff(&live);
+        assert_eq!(diff.changes().len(), 3);
+        assert!(matches!(diff.changes()[0], Change::Delete { .. }));
+        assert!(live.diff(&live).is_empty());
+    }
+
+    #[test]
+    fn test_apply() {
+        let live = snapshot(vec![socket_bind(vec![22])]);
+        let target = snapshot(vec![Policy::SocketConnectProtected(
+            SocketConnectProtected {
+                addr: IpAddr::V4(Ipv4Addr::LOCALHOST),
+                port: None,
+                allow: vec![],
+            },
+        )]);
+
+        let diff = live.diff(&target);
+        let mut applied = live.clone();
+        applied.apply(&diff);
+        assert_eq!(applied, target);
+        assert!(applied.diff(&target).is_empty());
+    }
+
+    #[test]
+    fn test_drift() {
+        let sb_mount = Policy::SbMount(SbMount {
+            subject: PolicySubject::All,
+            allow: false,
+        });
+        let sb_umount = Policy::SbUmount(SbUmount {
+            subject: PolicySubject::All,
+            allow: false,
+        });
+        let baseline = snapshot(vec![socket_bind(vec![22]), sb_mount]);
+        let live = snapshot(vec![socket_bind(vec![22, 23]), sb_umount]);
+
+        let drift = diff(&baseline, &live);
+        let ops: Vec<_> = drift
+            .changes()
+            .iter()
+            .map(|change| match change {
+                Change::Insert { map, .. } => ("insert", *map),
+                Change::Update { map, .. } => ("update", *map),
+                Change::Delete { map, .. } => ("delete", *map),
+            })
+            .collect();
+        assert_eq!(
+            ops,
+            [
+                ("delete", "DENIED_SB_MOUNT"),
+                ("insert", "DENIED_SB_UMOUNT"),
+                ("update", "DENIED_SOCKET_BIND"),
+            ]
+        );
+        assert_eq!(diff(&live, &baseline), drift.inverse());
+        assert_eq!(drift.inverse().inverse(), drift);
+
+        let mut undone = live.clone();
+        undone.apply(&diff(&live, &baseline));
+        assert_eq!(undone, baseline);
+    }
+
+    /// Returns a writer of changes to the snapshot, failing the writes
+    /// numbered in `fail` (counting the writes of rollbacks too).
+    fn writer(
+        maps: &mut MapSnapshot,
+        fail: Vec<usize>,
+    ) -> impl FnMut(&Change) -> Result<(), EbpfguardError> + '_ {
+        let mut writes = 0;
+        move |change| {
+            writes += 1;
+            if fail.contains(&writes) {
+                return Err(EbpfguardError::MapNotFound(change.map().to_owned()));
+            }
+            maps.apply(&PolicyDiff {
+                changes: vec![change.clone()],
+            });
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_commit_rollback() {
+        let live = snapshot(vec![socket_bind(vec![22])]);
+        let target = snapshot(vec![
+            socket_bind(vec![22, 23]),
+            Policy::SbMount(SbMount {
+                subject: PolicySubject::All,
+                allow: false,
+            }),
+            Policy::SbUmount(SbUmount {
+                subject: PolicySubject::All,
+                allow: false,
+            }),
+        ]);
+        let plan = live.diff(&target);
+        assert_eq!(plan.changes().len(), 3);
+
+        let mut maps = live.clone();
+        commit(&plan.changes, &mut writer(&mut maps, vec![])).unwrap();
+        assert_eq!(maps, target);
+
+        // The third change fails, the first two are rolled back.
+        let mut maps = live.clone();
+        let err = commit(&plan.changes, &mut writer(&mut maps, vec![3])).unwrap_err();
+        assert!(matches!(err, EbpfguardError::MapNotFound(_)));
+        assert_eq!(maps, live);
+
+        // The rollback of the first change fails too.
+        let mut maps = live.clone();
+        let err = commit(&plan.changes, &mut writer(&mut maps, vec![3, 5])).unwrap_err();
+        assert!(matches!(err, EbpfguardError::RollbackFailed(..)));
+        assert_ne!(maps, live);
+    }
+
+    #[test]
+    fn test_check() {
+        let live = snapshot(vec![socket_bind(vec![22])]);
+        let plan = live.diff(&snapshot(vec![socket_bind(vec![23])]));
+        assert!(live.check(&plan).is_ok());
+
+        // Changed since the plan.
+        let changed = snapshot(vec![socket_bind(vec![24])]);
+        assert!(matches!(
+            changed.check(&plan),
+            Err(EbpfguardError::PlanOutdated(map)) if map == "DENIED_SOCKET_BIND"
+        ));
+    }
+
+    #[test]
+    fn test_key_generation() {
+        let tagged = generation_namespace(3, 1);
+        let key = InodeKey::new(3, 100).with_namespace(tagged);
+        assert_eq!(key, InodeKey::new(tagged, 100));
+        // Namespaces of LPM trie keys are big-endian.
+        let key = Ipv4CidrKey::new(3, 100, 0x7f00_0001).with_namespace(tagged);
+        assert_eq!(key.namespace(), tagged);
+        assert_eq!(key.namespace, tagged.to_be());
+        assert_eq!(untagged_namespace(key.namespace()), 3);
+    }
+
+    #[test]
+    fn test_snapshot_json() {
+        let snapshot = snapshot(vec![
+            socket_bind(vec![22]),
+            Policy::SocketBindComm(SocketBindComm {
+                comm: CommPattern::Prefix("java".to_owned()),
+                allow: Ports::All,
+                deny: Ports::All,
+            }),
+        ]);
+
+        let json = serde_json::to_string(&snapshot).unwrap();
+        let parsed: MapSnapshot = serde_json::from_str(&json).unwrap();
+        assert_eq!(parsed, snapshot);
+        assert!(diff(&parsed, &snapshot).is_empty());
+
+        let entry = |map: &str, key: &str| {
+            format!(r#"{{"entries":[{{"map":"{map}","key":"{key}","value":"00"}}]}}"#)
+        };
+        let key = "00000000000000000000000000000000";
+        assert!(serde_json::from_str::<MapSnapshot>(&entry("DENIED_SB_MOUNT", key)).is_ok());
+        // Not a policy map, wrong key size, invalid hex.
+        for json in [
+            entry("ALERT_SB_MOUNT", key),
+            entry("DENIED_SB_MOUNT", "0000"),
+            entry("DENIED_SB_MOUNT", &key.replace('0', "x")),
+        ] {
+            assert!(serde_json::from_str::<MapSnapshot>(&json).is_err());
+        }
+    }
+
+    #[test]
+    fn test_key_layout_mismatch() {
+        let policy = Policy::SocketConnectProtected(SocketConnectProtected {
+            addr: IpAddr::V4(Ipv4Addr::LOCALHOST),
+            port: Some(5432),
+            allow: vec![],
+        });
+        assert!(target(&LocalInodeResolver, vec![policy], 0, KeyLayout::default()).is_err());
+    }
+
+    #[test]
+    fn test_render() {
+        let live = snapshot(vec![]);
+        let target = snapshot(vec![socket_bind(vec![22])]);
+        let diff = live.diff(&target);
+
+        let rendered = diff.to_string();
+        assert_eq!(rendered.lines().count(), 3);
+        assert!(rendered
+            .lines()
+            .any(|line| line.starts_with("+ DENIED_SOCKET_BIND ")));
+
+        let json: serde_json::Value = serde_json::to_value(&diff).unwrap();
+        let changes = json["changes"].as_array().unwrap();
+        assert_eq!(changes.len(), 3);
+        assert_eq!(changes[0]["op"], "insert");
+        assert_eq!(changes[0]["map"], "ALLOWED_SOCKET_BIND");
+        // Wildcard key of namespace 0, tagged as the wildcard.
+        assert_eq!(changes[0]["key"], "00000000000000000000000001000000");
+
+        assert_eq!(PolicyDiff::default().to_string(), "no changes");
+    }
+}
@@ -0,0 +1,243 @@
+use std::{
+    fmt::{self, Display, Formatter},
+    net::{IpAddr, Ipv4Addr, Ipv6Addr},
+    str::FromStr,
+};
+
+use serde::{de, Deserialize, Deserializer, Serialize, Serializer};
+
+use crate::error::EbpfguardError;
+
+/// Address prefix, e.g. `203.0.113.0/24` or `2001:db8::/32`.
+///
+/// The address bits outside of the prefix are always cleared.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Cidr {
+    addr: IpAddr,
+    prefix_len: u8,
+}
+
+impl Cidr {
+    pub fn new(addr: IpAddr, prefix_len: u8) -> Result<Self, EbpfguardError> {
+        let max_len = match addr {
+            IpAddr::V4(_) => 32,
+            IpAddr::V6(_) => 128,
+        };
+        if prefix_len > max_len {
+            return Err(EbpfguardError::InvalidCidr(format!("{addr}/{prefix_len}")));
+        }
+
+        let mut cidr = Self { addr, prefix_len };
+        cidr.addr = cidr.with_bits(cidr.bits() & mask(prefix_len));
+        Ok(cidr)
+    }
+
+    /// Returns the prefix covering only the address.
+    pub fn host(addr: IpAddr) -> Self {
+        let prefix_len = match addr {
+            IpAddr::V4(_) => 32,
+            IpAddr::V6(_) => 128,
+        };
+        Self { addr, prefix_len }
+    }
+
+    pub fn addr(&self) -> IpAddr {
+        self.addr
+    }
+
+    pub fn prefix_len(&self) -> u8 {
+        self.prefix_len
+    }
+
+    /// Returns whether the other CIDR is within this one.
+    pub fn contains(&self, other: &Cidr) -> bool {
+        self.addr.is_ipv4() == other.addr.is_ipv4()
+            && self.prefix_len <= other.prefix_len
+            && other.bits() & mask(self.prefix_len) == self.bits()
+    }
+
+    /// Address bits aligned to the most significant bit, so IPv4 and IPv6
+    /// prefixes can be handled the same way.
+    fn bits(&self) -> u128 {
+        match self.addr {
+            IpAddr::V4(addr) => u128::from(u32::from(addr)) << 96,
+            IpAddr::V6(addr) => u128::from(addr),
+        }
+    }
+
+    fn with_bits(&self, bits: u128) -> IpAddr {
+        match self.addr {
+            IpAddr::V4(_) => IpAddr::V4(Ipv4Addr::from((bits >> 96) as u32)),
+            IpAddr::V6(_) => IpAddr::V6(Ipv6Addr::from(bits)),
+        }
+    }
+
+    /// Returns the CIDR covering this one and its sibling.
+    fn parent(&self) -> Self {
+        let prefix_len = self.prefix_len - 1;
+        Self {
+            addr: self.with_bits(self.bits() & mask(prefix_len)),
+            prefix_len,
+        }
+    }
+
+    fn is_sibling(&self, other: &Cidr) -> bool {
+        self.addr.is_ipv4() == other.addr.is_ipv4()
+            && self.prefix_len == other.prefix_len
+            && self.prefix_len > 0
+            && self.bits() ^ other.bits() == 1 << (128 - u32::from(self.prefix_len))
+    }
+
+    fn sort_key(&self) -> (bool, u128, u8) {
+        (self.addr.is_ipv6(), self.bits(), self.prefix_len)
+    }
+}
+
+/// Mask of the first `prefix_len` bits of [`Cidr::bits`].
+fn mask(prefix_len: u8) -> u128 {
+    match prefix_len {
+        0 => 0,
+        prefix_len => !0 << (128 - u32::from(prefix_len)),
+    }
+}
+
+impl Display for Cidr {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "{}/{}", self.addr, self.prefix_len)
+    }
+}
+
+impl FromStr for Cidr {
+    type Err = EbpfguardError;
+
+    /// Parses a CIDR. An address without a prefix length is a single host
+    /// prefix.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || EbpfguardError::InvalidCidr(s.to_owned());
+
+        let (addr, prefix_len) = match s.split_once('/') {
+            Some((addr, prefix_len)) => {
+                let addr: IpAddr = addr.parse().map_err(|_| invalid())?;
+                (addr, prefix_len.parse().map_err(|_| invalid())?)
+            }
+            None => {
+                let addr: IpAddr = s.parse().map_err(|_| invalid())?;
+                (addr, if addr.is_ipv4() { 32 } else { 128 })
+            }
+        };
+
+        Cidr::new(addr, prefix_len)
+    }
+}
+
+impl Serialize for Cidr {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+impl<'de> Deserialize<'de> for Cidr {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        String::deserialize(deserializer)?
+            .parse()
+            .map_err(de::Error::custom)
+    }
+}
+
+/// Returns the smallest set of CIDRs covering the same addresses: duplicates
+/// and CIDRs within other ones are dropped and sibling CIDRs are merged into
+/// their parent.
+///
+/// Databases list networks as announced, which often means many adjacent
+/// prefixes, so compacting them saves a considerable part of the map entries.
+pub fn compact<I: IntoIterator<Item = Cidr>>(cidrs: I) -> Vec<Cidr> {
+    let mut cidrs: Vec<_> = cidrs.into_iter().collect();
+    // CIDRs are sorted by address, covering CIDRs precede the ones they
+    // contain.
+    cidrs.sort_by_key(Cidr::sort_key);
+
+    let mut compacted: Vec<Cidr> = Vec::with_capacity(cidrs.len());
+    for cidr in cidrs {
+        if let Some(last) = compacted.last() {
+            if last.contains(&cidr) {
+                continue;
+            }
+        }
+        compacted.push(cidr);
+
+        while compacted.len() >= 2 {
+            let last = compacted[compacted.len() - 1];
+            let prev = compacted[compacted.len() - 2];
+            if !prev.is_sibling(&last) {
+                break;
+            }
+            compacted.truncate(compacted.len() - 2);
+            compacted.push(prev.parent());
+        }
+    }
+
+    compacted
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn cidr(s: &str) -> Cidr {
+        s.parse().unwrap()
+    }
+
+    #[test]
+    fn test_parse() {
+        assert_eq!(cidr("10.1.2.3/8").to_string(), "10.0.0.0/8");
+        assert_eq!(cidr("10.1.2.3").to_string(), "10.1.2.3/32");
+        assert_eq!(cidr("2001:db8::1/32").to_string(), "2001:db8::/32");
+        assert_eq!(cidr("0.0.0.0/0").to_string(), "0.0.0.0/0");
+        assert!("10.0.0.0/33".parse::<Cidr>().is_err());
+        assert!("2001:db8::/129".parse::<Cidr>().is_err());
+        assert!("10.0.0/8".parse::<Cidr>().is_err());
+        assert!("10.0.0.0/".parse::<Cidr>().is_err());
+    }
+
+    #[test]
+    fn test_contains() {
+        assert!(cidr("10.0.0.0/8").contains(&cidr("10.1.0.0/16")));
+        assert!(cidr("10.0.0.0/8").contains(&cidr("10.0.0.0/8")));
+        assert!(cidr("0.0.0.0/0").contains(&cidr("192.0.2.1")));
+        assert!(!cidr("10.1.0.0/16").contains(&cidr("10.0.0.0/8")));
+        assert!(!cidr("10.0.0.0/8").contains(&cidr("11.0.0.0/16")));
+        assert!(!cidr("0.0.0.0/0").contains(&cidr("::/0")));
+    }
+
+    #[test]
+    fn test_compact() {
+        assert_eq!(
+            compact([
+                cidr("10.1.0.0/16"),
+                cidr("10.0.0.0/8"),
+                cidr("10.0.0.0/8"),
+                cidr("192.0.2.0/25"),
+                cidr("192.0.2.128/25"),
+                cidr("198.51.100.0/24"),
+                cidr("198.51.101.0/24"),
+                cidr("198.51.102.0/23"),
+                cidr("203.0.113.0/24"),
+                cidr("2001:db8::/33"),
+                cidr("2001:db8:8000::/33"),
+            ]),
+            vec![
+                cidr("10.0.0.0/8"),
+                cidr("192.0.2.0/24"),
+                cidr("198.51.100.0/22"),
+                cidr("203.0.113.0/24"),
+                cidr("2001:db8::/32"),
+            ]
+        );
+        // Adjacent, but not siblings.
+        assert_eq!(
+            compact([cidr("198.51.101.0/24"), cidr("198.51.102.0/24")]),
+            vec![cidr("198.51.101.0/24"), cidr("198.51.102.0/24")]
+        );
+        assert!(compact([]).is_empty());
+    }
+}
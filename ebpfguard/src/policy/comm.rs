@@ -0,0 +1,165 @@
+use std::{
+    fmt::{self, Display, Formatter},
+    str::FromStr,
+};
+
+use ebpfguard_common::policy::COMM_LEN;
+use serde::{de, Deserialize, Deserializer, Serialize, Serializer};
+
+use crate::error::EbpfguardError;
+
+/// Pattern of the command name (`comm`) of a task, either exact (`nginx`) or
+/// a prefix ending with `*` (`java*`).
+///
+/// The kernel keeps only the first 15 bytes of the name (`TASK_COMM_LEN`
+/// minus the terminating NUL), so names are truncated before they are
+/// matched, and patterns longer than 15 bytes (without the `*`) are invalid:
+/// they could never match. A name longer than 15 bytes is matched by an
+/// exact pattern of its first 15 bytes.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum CommPattern {
+    Exact(String),
+    Prefix(String),
+}
+
+impl CommPattern {
+    /// Returns the bytes matched by the pattern, padded to [`COMM_LEN`], and
+    /// their number. Exact patterns include the terminating NUL, so they
+    /// don't match longer names.
+    pub(crate) fn to_bytes(&self) -> ([u8; COMM_LEN], usize) {
+        let (name, len) = match self {
+            CommPattern::Exact(name) => (name, name.len() + 1),
+            CommPattern::Prefix(name) => (name, name.len()),
+        };
+        let mut bytes = [0; COMM_LEN];
+        bytes[..name.len()].copy_from_slice(name.as_bytes());
+        (bytes, len)
+    }
+
+    /// Converts the bytes matched by a pattern back to the pattern.
+    pub(crate) fn from_bytes(bytes: &[u8; COMM_LEN], len: usize) -> Self {
+        let len = len.min(COMM_LEN);
+        match bytes[..len].split_last() {
+            Some((&0, name)) => CommPattern::Exact(String::from_utf8_lossy(name).into_owned()),
+            _ => CommPattern::Prefix(String::from_utf8_lossy(&bytes[..len]).into_owned()),
+        }
+    }
+}
+
+impl Display for CommPattern {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            CommPattern::Exact(name) => write!(f, "{name}"),
+            CommPattern::Prefix(name) => write!(f, "{name}*"),
+        }
+    }
+}
+
+impl FromStr for CommPattern {
+    type Err = EbpfguardError;
+
+    /// Parses a pattern. The name can't be empty (use a policy of all
+    /// binaries instead) or contain `*` anywhere else than at the end.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || EbpfguardError::InvalidCommPattern(s.to_owned());
+
+        let pattern = match s.strip_suffix('*') {
+            Some(name) => CommPattern::Prefix(name.to_owned()),
+            None => CommPattern::Exact(s.to_owned()),
+        };
+        let name = match &pattern {
+            CommPattern::Exact(name) | CommPattern::Prefix(name) => name,
+        };
+        if name.is_empty() || name.len() >= COMM_LEN || name.contains(['*', '\0']) {
+            return Err(invalid());
+        }
+
+        Ok(pattern)
+    }
+}
+
+impl Serialize for CommPattern {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+impl<'de> Deserialize<'de> for CommPattern {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        String::deserialize(deserializer)?
+            .parse()
+            .map_err(de::Error::custom)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn pattern(s: &str) -> CommPattern {
+        s.parse().unwrap()
+    }
+
+    /// Returns whether the pattern matches the name, like a longest prefix
+    /// match lookup of the name as reported by the kernel.
+    fn matches(pattern: &CommPattern, name: &str) -> bool {
+        let mut comm = [0; COMM_LEN];
+        let len = name.len().min(COMM_LEN - 1);
+        comm[..len].copy_from_slice(&name.as_bytes()[..len]);
+
+        let (bytes, len) = pattern.to_bytes();
+        bytes[..len] == comm[..len]
+    }
+
+    #[test]
+    fn test_parse() {
+        assert_eq!(pattern("nginx"), CommPattern::Exact("nginx".to_owned()));
+        assert_eq!(pattern("java*"), CommPattern::Prefix("java".to_owned()));
+        assert_eq!(pattern("java*").to_string(), "java*");
+        assert!("".parse::<CommPattern>().is_err());
+        assert!("*".parse::<CommPattern>().is_err());
+        assert!("ja*va".parse::<CommPattern>().is_err());
+        assert!("java**".parse::<CommPattern>().is_err());
+    }
+
+    #[test]
+    fn test_truncation_boundary() {
+        // 15 bytes, the longest name the kernel keeps.
+        assert!("abcdefghijklmno".parse::<CommPattern>().is_ok());
+        assert!("abcdefghijklmno*".parse::<CommPattern>().is_ok());
+        // 16 bytes can never match a truncated name.
+        assert!("abcdefghijklmnop".parse::<CommPattern>().is_err());
+        assert!("abcdefghijklmnop*".parse::<CommPattern>().is_err());
+
+        let exact = pattern("abcdefghijklmno");
+        assert_eq!(exact.to_bytes().1, COMM_LEN);
+        assert!(matches(&exact, "abcdefghijklmno"));
+        assert!(matches(&exact, "abcdefghijklmnopqrs"));
+        assert!(!matches(&exact, "abcdefghijklmn"));
+
+        let prefix = pattern("abcdefghijklmno*");
+        assert!(matches(&prefix, "abcdefghijklmnopqrs"));
+        assert!(!matches(&prefix, "abcdefghijklmn"));
+    }
+
+    #[test]
+    fn test_prefix_matching() {
+        let java = pattern("java*");
+        assert!(matches(&java, "java"));
+        assert!(matches(&java, "javac"));
+        assert!(!matches(&java, "jav"));
+
+        let nginx = pattern("nginx");
+        assert!(matches(&nginx, "nginx"));
+        assert!(!matches(&nginx, "nginx-worker"));
+    }
+
+    #[test]
+    fn test_bytes_roundtrip() {
+        for s in ["nginx", "java*", "abcdefghijklmno", "abcdefghijklmno*"] {
+            let pattern = pattern(s);
+            let (bytes, len) = pattern.to_bytes();
+            assert_eq!(CommPattern::from_bytes(&bytes, len), pattern);
+        }
+    }
+}
@@ -0,0 +1,194 @@
+use std::{
+    collections::HashMap,
+    fs,
+    path::{Path, PathBuf},
+    str::FromStr,
+};
+
+use crate::error::EbpfguardError;
+
+use super::{cidr::Cidr, GeoSelector};
+
+/// Source of the CIDRs announced by autonomous systems and assigned to
+/// countries, used to translate [`GeoSelector`]s of `socket_connect_geo`
+/// policies into CIDRs matched by the eBPF programs.
+///
+/// Implementations can wrap any ASN or geolocation database, the CIDRs they
+/// return don't need to be compacted.
+pub trait CidrDatabase: Send {
+    /// Returns the CIDRs of the autonomous system or country. Selectors
+    /// unknown to the database have no CIDRs.
+    fn lookup(&self, selector: &GeoSelector) -> Vec<Cidr>;
+
+    /// Reloads the database from its source, called before each refresh of
+    /// the policies (see `SocketConnect::watch_geo`).
+    fn reload(&mut self) -> Result<(), EbpfguardError> {
+        Ok(())
+    }
+}
+
+/// Database read from a text file with a CIDR, an ASN and a country code per
+/// line, separated by whitespace:
+///
+/// ```text
+/// # cidr asn country
+/// 192.0.2.0/24 64496 ZZ
+/// 2001:db8::/32 64496 ZZ
+/// ```
+///
+/// Empty lines and lines starting with `#` are ignored. Country codes are
+/// matched case-insensitively.
+#[derive(Debug, Default)]
+pub struct TextDatabase {
+    path: Option<PathBuf>,
+    asns: HashMap<u32, Vec<Cidr>>,
+    countries: HashMap<String, Vec<Cidr>>,
+}
+
+impl TextDatabase {
+    /// Reads the database from the file, which is read again on every
+    /// [`CidrDatabase::reload`].
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Self, EbpfguardError> {
+        let path = path.as_ref().to_path_buf();
+        let mut database: TextDatabase = fs::read_to_string(&path)?.parse()?;
+        database.path = Some(path);
+        Ok(database)
+    }
+}
+
+impl FromStr for TextDatabase {
+    type Err = EbpfguardError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut database = TextDatabase::default();
+
+        for (i, line) in s.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            let invalid = || EbpfguardError::InvalidCidrDatabase(i + 1);
+            let mut fields = line.split_whitespace();
+            let cidr: Cidr = fields
+                .next()
+                .ok_or_else(invalid)?
+                .parse()
+                .map_err(|_| invalid())?;
+            let asn: u32 = fields
+                .next()
+                .ok_or_else(invalid)?
+                .parse()
+                .map_err(|_| invalid())?;
+            let country = fields.next().ok_or_else(invalid)?.to_uppercase();
+            if fields.next().is_some() {
+                return Err(invalid());
+            }
+
+            database.asns.entry(asn).or_default().push(cidr);
+            database.countries.entry(country).or_default().push(cidr);
+        }
+
+        Ok(database)
+    }
+}
+
+impl CidrDatabase for TextDatabase {
+    fn lookup(&self, selector: &GeoSelector) -> Vec<Cidr> {
+        let cidrs = match selector {
+            GeoSelector::Asn(asn) => self.asns.get(asn),
+            GeoSelector::Country(country) => self.countries.get(&country.to_uppercase()),
+            GeoSelector::Cidr(cidr) => return vec![*cidr],
+        };
+        cidrs.cloned().unwrap_or_default()
+    }
+
+    fn reload(&mut self) -> Result<(), EbpfguardError> {
+        if let Some(path) = self.path.clone() {
+            *self = TextDatabase::open(path)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const FIXTURE: &str = "
+# cidr asn country
+192.0.2.0/25 64496 ZZ
+192.0.2.128/25 64496 ZZ
+2001:db8::/32 64496 ZZ
+198.51.100.0/24 64497 zz
+203.0.113.0/24 64498 YY
+";
+
+    fn cidrs(cidrs: &[&str]) -> Vec<Cidr> {
+        cidrs.iter().map(|cidr| cidr.parse().unwrap()).collect()
+    }
+
+    #[test]
+    fn test_lookup() {
+        let database: TextDatabase = FIXTURE.parse().unwrap();
+
+        assert_eq!(
+            database.lookup(&GeoSelector::Asn(64496)),
+            cidrs(&["192.0.2.0/25", "192.0.2.128/25", "2001:db8::/32"])
+        );
+        assert_eq!(
+            database.lookup(&GeoSelector::Country("zz".to_owned())),
+            cidrs(&[
+                "192.0.2.0/25",
+                "192.0.2.128/25",
+                "2001:db8::/32",
+                "198.51.100.0/24"
+            ])
+        );
+        assert_eq!(
+            database.lookup(&GeoSelector::Cidr("10.0.0.0/8".parse().unwrap())),
+            cidrs(&["10.0.0.0/8"])
+        );
+        assert!(database.lookup(&GeoSelector::Asn(64511)).is_empty());
+        assert!(database
+            .lookup(&GeoSelector::Country("XX".to_owned()))
+            .is_empty());
+    }
+
+    #[test]
+    fn test_invalid() {
+        assert!(matches!(
+            "192.0.2.0/24 64496 ZZ\n192.0.2.0/33 64496 ZZ".parse::<TextDatabase>(),
+            Err(EbpfguardError::InvalidCidrDatabase(2))
+        ));
+        assert!(matches!(
+            "192.0.2.0/24 AS64496 ZZ".parse::<TextDatabase>(),
+            Err(EbpfguardError::InvalidCidrDatabase(1))
+        ));
+        assert!(matches!(
+            "192.0.2.0/24 64496".parse::<TextDatabase>(),
+            Err(EbpfguardError::InvalidCidrDatabase(1))
+        ));
+    }
+
+    #[test]
+    fn test_reload() {
+        let path = std::env::temp_dir().join(format!("ebpfguard-geo-{}", std::process::id()));
+        fs::write(&path, "192.0.2.0/24 64496 ZZ\n").unwrap();
+
+        let mut database = TextDatabase::open(&path).unwrap();
+        assert_eq!(
+            database.lookup(&GeoSelector::Asn(64496)),
+            cidrs(&["192.0.2.0/24"])
+        );
+
+        fs::write(&path, "198.51.100.0/24 64496 ZZ\n").unwrap();
+        database.reload().unwrap();
+        assert_eq!(
+            database.lookup(&GeoSelector::Asn(64496)),
+            cidrs(&["198.51.100.0/24"])
+        );
+
+        fs::remove_file(&path).unwrap();
+    }
+}
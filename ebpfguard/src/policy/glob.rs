@@ -0,0 +1,170 @@
+use std::{
+    fs,
+    path::{Component, Path, PathBuf},
+};
+
+/// Returns whether the pattern contains wildcards.
+pub fn is_pattern(pattern: &str) -> bool {
+    pattern.contains(['*', '?'])
+}
+
+/// Matches a single path component against a pattern, where `*` matches any
+/// sequence of characters and `?` matches a single character.
+///
+/// Like in shells, wildcards don't match a leading `.`, hidden files have to
+/// be matched with a pattern starting with `.`.
+pub fn matches(pattern: &str, name: &str) -> bool {
+    if name.starts_with('.') && !pattern.starts_with('.') {
+        return false;
+    }
+
+    let pattern: Vec<char> = pattern.chars().collect();
+    let name: Vec<char> = name.chars().collect();
+
+    let (mut p, mut n) = (0, 0);
+    // Position of the last `*` in the pattern and of the name character it
+    // currently matches up to, for backtracking.
+    let mut star: Option<(usize, usize)> = None;
+
+    while n < name.len() {
+        match pattern.get(p) {
+            Some('*') => {
+                star = Some((p, n));
+                p += 1;
+            }
+            Some('?') => {
+                p += 1;
+                n += 1;
+            }
+            Some(c) if *c == name[n] => {
+                p += 1;
+                n += 1;
+            }
+            _ => match star {
+                Some((star_p, star_n)) => {
+                    p = star_p + 1;
+                    n = star_n + 1;
+                    star = Some((star_p, star_n + 1));
+                }
+                None => return false,
+            },
+        }
+    }
+
+    pattern[p..].iter().all(|c| *c == '*')
+}
+
+/// Expands a glob pattern to the existing paths matching it. Wildcards are
+/// supported within path components, `**` isn't supported (it's treated as
+/// `*`) and each wildcard component requires reading a directory.
+///
+/// Directories which can't be read are skipped.
+pub fn expand<P: AsRef<Path>>(pattern: P) -> Vec<PathBuf> {
+    let mut paths = vec![PathBuf::new()];
+
+    for component in pattern.as_ref().components() {
+        let component = match component {
+            Component::Normal(component) => component.to_string_lossy(),
+            component => {
+                for path in paths.iter_mut() {
+                    path.push(component);
+                }
+                continue;
+            }
+        };
+
+        if !is_pattern(&component) {
+            for path in paths.iter_mut() {
+                path.push(component.as_ref());
+            }
+            continue;
+        }
+
+        let mut expanded = Vec::new();
+        for path in paths.iter() {
+            let dir = if path.as_os_str().is_empty() {
+                Path::new(".")
+            } else {
+                path.as_path()
+            };
+            let entries = match fs::read_dir(dir) {
+                Ok(entries) => entries,
+                Err(_) => continue,
+            };
+            let mut names: Vec<_> = entries
+                .filter_map(|entry| entry.ok())
+                .map(|entry| entry.file_name().to_string_lossy().into_owned())
+                .filter(|name| matches(&component, name))
+                .collect();
+            names.sort();
+            expanded.extend(names.into_iter().map(|name| path.join(name)));
+        }
+        paths = expanded;
+    }
+
+    paths.retain(|path| fs::symlink_metadata(path).is_ok());
+    paths
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_matches() {
+        assert!(matches("*", "id_rsa"));
+        assert!(matches("id_*", "id_rsa"));
+        assert!(matches("*.pub", "id_rsa.pub"));
+        assert!(matches("id_???", "id_rsa"));
+        assert!(matches("*_*.pub", "id_ed25519.pub"));
+        assert!(matches("a*b*c", "aXbYbc"));
+        assert!(!matches("id_*", "authorized_keys"));
+        assert!(!matches("*.pub", "id_rsa"));
+        assert!(!matches("id_??", "id_rsa"));
+        assert!(!matches("*", ".ssh"));
+        assert!(matches(".*", ".ssh"));
+    }
+
+    #[test]
+    fn test_expand() {
+        let root = std::env::temp_dir().join(format!("ebpfguard-glob-{}", std::process::id()));
+        for user in ["alice", "bob", "carol"] {
+            fs::create_dir_all(root.join("home").join(user)).unwrap();
+        }
+        for (user, file) in [
+            ("alice", "id_rsa"),
+            ("alice", "id_rsa.pub"),
+            ("bob", "id_ed25519"),
+        ] {
+            let ssh = root.join("home").join(user).join(".ssh");
+            fs::create_dir_all(&ssh).unwrap();
+            fs::write(ssh.join(file), "").unwrap();
+        }
+        fs::write(root.join("home").join("carol").join(".profile"), "").unwrap();
+
+        assert_eq!(
+            expand(root.join("home/*/.ssh/*")),
+            vec![
+                root.join("home/alice/.ssh/id_rsa"),
+                root.join("home/alice/.ssh/id_rsa.pub"),
+                root.join("home/bob/.ssh/id_ed25519"),
+            ]
+        );
+        assert_eq!(
+            expand(root.join("home/*/.ssh/*.pub")),
+            vec![root.join("home/alice/.ssh/id_rsa.pub")]
+        );
+        assert_eq!(
+            expand(root.join("home/*/.ssh")),
+            vec![root.join("home/alice/.ssh"), root.join("home/bob/.ssh")]
+        );
+        assert_eq!(
+            expand(root.join("home/carol/.profile")),
+            vec![root.join("home/carol/.profile")]
+        );
+        assert!(expand(root.join("home/*/.missing/*")).is_empty());
+        assert!(expand(root.join("home/dave/.ssh")).is_empty());
+
+        fs::remove_dir_all(&root).unwrap();
+    }
+}
@@ -1,34 +1,88 @@
 use std::{collections::HashMap, path::PathBuf};
 
-use crate::{error::EbpfguardError, fs};
+use ebpfguard_common::{
+    consts::INODE_WILDCARD,
+    policy::{DevInodeKey, InodeKey},
+};
+
+use crate::{
+    error::EbpfguardError,
+    fs::{self, InodeResolver},
+};
 
 use super::PolicySubject;
 
+/// Returns the map key of a subject resolved to `inode`, the key of the
+/// wildcard policy for [`INODE_WILDCARD`]. Binaries never resolve to that
+/// inode (see [`fs::binary_inode`]).
+pub(crate) fn subject_key(namespace: u32, inode: u64) -> InodeKey {
+    match inode {
+        INODE_WILDCARD => InodeKey::wildcard(namespace),
+        inode => InodeKey::new(namespace, inode),
+    }
+}
+
 #[derive(Default)]
 pub struct InodeSubjectMap {
     map: HashMap<u64, PathBuf>,
 }
 
 impl InodeSubjectMap {
-    pub fn resolve_path(&mut self, subject: PolicySubject) -> Result<u64, EbpfguardError> {
+    /// Resolves the subject to the inode of its binary with the resolver,
+    /// remembering the path for listing.
+    pub fn resolve_path(
+        &mut self,
+        resolver: &dyn InodeResolver,
+        subject: PolicySubject,
+    ) -> Result<u64, EbpfguardError> {
         match subject {
-            PolicySubject::Binary(path) => {
-                let inode = fs::inode(&path)?;
-                self.map.insert(inode, path);
-                Ok(inode)
-            }
-            PolicySubject::All => Ok(0),
+            PolicySubject::Binary(path) => Ok(self.resolve_binary_key(resolver, path)?.inode),
+            PolicySubject::All => Ok(INODE_WILDCARD),
         }
     }
 
+    /// Resolves the path of a binary to its inode and device with the
+    /// resolver, remembering the path for listing.
+    pub fn resolve_binary_key(
+        &mut self,
+        resolver: &dyn InodeResolver,
+        path: PathBuf,
+    ) -> Result<DevInodeKey, EbpfguardError> {
+        let key = fs::binary_key(resolver, &path)?;
+        self.map.insert(key.inode, path);
+        Ok(key)
+    }
+
+    /// Resolves the path of a file other than a binary (e.g. protected by a
+    /// `file_open` policy) to its inode, remembering the path for listing.
+    pub fn resolve_file(
+        &mut self,
+        resolver: &dyn InodeResolver,
+        path: PathBuf,
+    ) -> Result<u64, EbpfguardError> {
+        let inode = resolver.resolve(&path)?.inode;
+        self.map.insert(inode, path);
+        Ok(inode)
+    }
+
     pub fn resolve_inode(&self, inode: u64) -> PolicySubject {
         match inode {
-            0 => PolicySubject::All,
-            _ => self
-                .map
-                .get(&inode)
-                .map(|p| PolicySubject::Binary(p.to_owned()))
-                .unwrap_or(PolicySubject::Binary(PathBuf::from(inode.to_string()))),
+            INODE_WILDCARD => PolicySubject::All,
+            _ => PolicySubject::Binary(self.resolve_binary(inode)),
         }
     }
+
+    /// Returns the path the inode was resolved from, if any.
+    pub fn path(&self, inode: u64) -> Option<&PathBuf> {
+        self.map.get(&inode)
+    }
+
+    /// Resolves the inode of a binary to its path. Falls back to the inode
+    /// number if the path is not known.
+    pub fn resolve_binary(&self, inode: u64) -> PathBuf {
+        self.map
+            .get(&inode)
+            .map(|p| p.to_owned())
+            .unwrap_or(PathBuf::from(inode.to_string()))
+    }
 }
@@ -1,14 +1,20 @@
 use std::{
     fmt::{Display, Formatter},
-    net::IpAddr,
+    net::{IpAddr, Ipv4Addr, Ipv6Addr},
     path::PathBuf,
+    str::FromStr,
+    time::Duration,
 };
 
 use ebpfguard_common::policy as ebpf_policy;
-use serde::{Deserialize, Serialize};
+use serde::{de, Deserialize, Deserializer, Serialize, Serializer};
 
-use crate::fs;
+use crate::{error::EbpfguardError, fs::InodeResolver};
 
+pub mod cidr;
+pub mod comm;
+pub mod geo;
+pub mod glob;
 pub mod inode;
 pub mod reader;
 
@@ -41,21 +47,25 @@ pub enum Paths {
 // requires resolving inodes to paths. Inode/path resolution is not a
 // symmetrical operation (path -> inode resolution is a simple file metadata
 // lookup, while inode -> path resolution requires more complex per-filesystem
-// operations). Therefore, `Into` and `From` traits have to be implemented
-// separately.
-#[allow(clippy::from_over_into)]
-impl Into<ebpf_policy::Paths> for Paths {
-    fn into(self) -> ebpf_policy::Paths {
+// operations). Therefore, resolving paths (`Paths::resolve`) and `From` have
+// to be implemented separately.
+impl Paths {
+    /// Resolves the paths to the inodes the eBPF programs see, with the
+    /// resolver of the policy manager.
+    pub(crate) fn resolve(
+        self,
+        resolver: &dyn InodeResolver,
+    ) -> Result<ebpf_policy::Paths, EbpfguardError> {
         match self {
-            Paths::All => ebpf_policy::Paths {
+            Paths::All => Ok(ebpf_policy::Paths {
                 paths: [0; ebpf_policy::MAX_PATHS],
-            },
+            }),
             Paths::Paths(paths) => {
                 let mut ebpf_paths = [0; ebpf_policy::MAX_PATHS];
                 for (i, path) in paths.iter().enumerate() {
-                    ebpf_paths[i] = fs::inode(path).unwrap();
+                    ebpf_paths[i] = resolver.resolve(path)?.inode;
                 }
-                ebpf_policy::Paths { paths: ebpf_paths }
+                Ok(ebpf_policy::Paths { paths: ebpf_paths })
             }
         }
     }
@@ -120,6 +130,83 @@ impl From<ebpf_policy::Ports> for Ports {
     }
 }
 
+/// Inclusive range of ports, written as a single port (`6697`) or as
+/// `start-end` (`6660-6669`). Ports are 1 to 65535.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PortRange {
+    pub start: u16,
+    pub end: u16,
+}
+
+impl PortRange {
+    pub fn port(port: u16) -> Self {
+        Self {
+            start: port,
+            end: port,
+        }
+    }
+
+    fn validate(self) -> Result<Self, EbpfguardError> {
+        if self.start == 0 || self.start > self.end {
+            return Err(EbpfguardError::InvalidPortRange(self.to_string()));
+        }
+        Ok(self)
+    }
+}
+
+impl Display for PortRange {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        if self.start == self.end {
+            write!(f, "{}", self.start)
+        } else {
+            write!(f, "{}-{}", self.start, self.end)
+        }
+    }
+}
+
+impl FromStr for PortRange {
+    type Err = EbpfguardError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || EbpfguardError::InvalidPortRange(s.to_owned());
+        let range = match s.split_once('-') {
+            Some((start, end)) => Self {
+                start: start.trim().parse().map_err(|_| invalid())?,
+                end: end.trim().parse().map_err(|_| invalid())?,
+            },
+            None => Self::port(s.trim().parse().map_err(|_| invalid())?),
+        };
+        range.validate()
+    }
+}
+
+impl Serialize for PortRange {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        if self.start == self.end {
+            serializer.serialize_u16(self.start)
+        } else {
+            serializer.collect_str(self)
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for PortRange {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        #[derive(Deserialize)]
+        #[serde(untagged)]
+        enum Raw {
+            Port(u16),
+            Range(String),
+        }
+
+        match Raw::deserialize(deserializer)? {
+            Raw::Port(port) => PortRange::port(port).validate(),
+            Raw::Range(range) => range.parse(),
+        }
+        .map_err(de::Error::custom)
+    }
+}
+
 #[derive(Debug, PartialEq, Eq, Serialize, Deserialize)]
 pub enum Addresses {
     #[serde(rename = "all")]
@@ -161,10 +248,36 @@ impl Addresses {
     }
 }
 
+/// Verdict for an operation escalated to user space.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Verdict {
+    #[serde(rename = "allow")]
+    Allow,
+    #[serde(rename = "deny")]
+    Deny,
+}
+
+impl From<Verdict> for u8 {
+    fn from(verdict: Verdict) -> Self {
+        match verdict {
+            Verdict::Allow => ebpf_policy::VERDICT_ALLOW,
+            Verdict::Deny => ebpf_policy::VERDICT_DENY,
+        }
+    }
+}
+
 #[derive(Debug, PartialEq, Eq, Serialize, Deserialize)]
 pub enum Policy {
+    #[serde(rename = "bpf")]
+    Bpf(Bpf),
     #[serde(rename = "file_open")]
     FileOpen(FileOpen),
+    #[serde(rename = "file_open_glob")]
+    FileOpenGlob(FileOpenGlob),
+    #[serde(rename = "file_open_protected")]
+    FileOpenProtected(FileOpenProtected),
+    #[serde(rename = "inode_create")]
+    InodeCreate(InodeCreate),
     #[serde(rename = "sb_mount")]
     SbMount(SbMount),
     #[serde(rename = "sb_remount")]
@@ -173,12 +286,41 @@ pub enum Policy {
     SbUmount(SbUmount),
     #[serde(rename = "socket_bind")]
     SocketBind(SocketBind),
+    #[serde(rename = "socket_bind_comm")]
+    SocketBindComm(SocketBindComm),
+    #[serde(rename = "socket_bind_packet")]
+    SocketBindPacket(SocketBindPacket),
     #[serde(rename = "socket_connect")]
     SocketConnect(SocketConnect),
+    #[serde(rename = "socket_connect_geo")]
+    SocketConnectGeo(SocketConnectGeo),
+    #[serde(rename = "socket_connect_metadata")]
+    SocketConnectMetadata(SocketConnectMetadata),
+    #[serde(rename = "socket_connect_ports")]
+    SocketConnectPorts(SocketConnectPorts),
+    #[serde(rename = "socket_connect_protected")]
+    SocketConnectProtected(SocketConnectProtected),
+    #[serde(rename = "socket_create")]
+    SocketCreate(SocketCreate),
+    #[serde(rename = "socket_listen")]
+    SocketListen(SocketListen),
+    #[serde(rename = "task_fix_setgid")]
+    TaskFixSetgid(TaskFixSetgid),
     #[serde(rename = "task_fix_setuid")]
     TaskFixSetuid(TaskFixSetuid),
 }
 
+/// Policy of the `bpf` syscall, with the same semantics as [`SbMount`]: a
+/// wildcard policy allows or denies all binaries, and policies of binaries
+/// are the exceptions. The binaries of the policy managers are exempt from
+/// these policies in all namespaces, see
+/// [`PolicyManager::attach_bpf`](crate::PolicyManager::attach_bpf).
+#[derive(Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Bpf {
+    pub subject: PolicySubject,
+    pub allow: bool,
+}
+
 #[derive(Debug, PartialEq, Eq, Serialize, Deserialize)]
 pub struct FileOpen {
     pub subject: PolicySubject,
@@ -186,6 +328,53 @@ pub struct FileOpen {
     pub deny: Paths,
 }
 
+/// Policy extending the `file_open` policy of a subject with glob patterns
+/// (e.g. `/home/*/.ssh/*`, see [`glob::expand`]).
+///
+/// Patterns are expanded in user space to the inodes of matching files and
+/// directories, which are added to the allowed or denied paths of the
+/// subject. The subject needs a [`FileOpen`] policy enabling the hook for it.
+///
+/// Expansions are refreshed periodically by a watcher (see
+/// `FileOpen::watch_globs`). Newly created files are not covered until the
+/// next refresh, unless their parent directory is. Each wildcard component of
+/// a pattern requires reading directories, so patterns with many matches
+/// cost a directory walk and a map update per file on every refresh.
+#[derive(Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FileOpenGlob {
+    pub subject: PolicySubject,
+    pub allow: Vec<String>,
+    pub deny: Vec<String>,
+}
+
+/// Policy protecting a single file, which can be opened only by the listed
+/// binaries.
+///
+/// It's checked before the per-binary `file_open` policies: binaries missing
+/// from `allow` are denied regardless of their own policies, while the listed
+/// ones are still subject to them. Only the file itself is protected, not
+/// the files below it if it's a directory.
+#[derive(Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FileOpenProtected {
+    pub path: PathBuf,
+    pub allow: Vec<PathBuf>,
+}
+
+/// Policy of the directories in which the subject can create files, e.g. to
+/// stop droppers from writing binaries to `/usr/bin`. Paths cover the files
+/// created in the directories and their subdirectories.
+///
+/// The policy of [`PolicySubject::All`] sets the default: with `allow: all`,
+/// creating files in its `deny` directories is denied, except for binaries
+/// whose own `allow` lists them. With `deny: all`, only its `allow`
+/// directories and the ones allowed for a binary are writable.
+#[derive(Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct InodeCreate {
+    pub subject: PolicySubject,
+    pub allow: Paths,
+    pub deny: Paths,
+}
+
 #[derive(Debug, PartialEq, Eq, Serialize, Deserialize)]
 pub struct SbMount {
     pub subject: PolicySubject,
@@ -204,11 +393,129 @@ pub struct SbUmount {
     pub allow: bool,
 }
 
+/// Socket option which can be denied in [`SocketBind`] and [`SocketListen`]
+/// policies.
+///
+/// Only options stored in the socket by the time of the hook can be matched,
+/// so they have to be set with `setsockopt` before `bind`/`listen`, which is
+/// the case for all of them in practice (they affect the bind itself):
+///
+/// * `reuseaddr` - `SO_REUSEADDR` (`sk_reuse`),
+/// * `reuseport` - `SO_REUSEPORT` (`sk_reuseport`),
+/// * `bindtodevice` - `SO_BINDTODEVICE` (`sk_bound_dev_if`).
+///
+/// Options set after the hook, like `SO_KEEPALIVE` or `TCP_NODELAY` set
+/// after `listen`, can't be matched.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum SocketOption {
+    #[serde(rename = "reuseaddr")]
+    ReuseAddr,
+    #[serde(rename = "reuseport")]
+    ReusePort,
+    #[serde(rename = "bindtodevice")]
+    BindToDevice,
+}
+
+impl SocketOption {
+    const ALL: [SocketOption; 3] = [
+        SocketOption::ReuseAddr,
+        SocketOption::ReusePort,
+        SocketOption::BindToDevice,
+    ];
+
+    fn flag(self) -> u8 {
+        match self {
+            SocketOption::ReuseAddr => ebpf_policy::SOCKET_OPTION_REUSEADDR,
+            SocketOption::ReusePort => ebpf_policy::SOCKET_OPTION_REUSEPORT,
+            SocketOption::BindToDevice => ebpf_policy::SOCKET_OPTION_BINDTODEVICE,
+        }
+    }
+
+    /// Converts the options to `SOCKET_OPTION_*` flags.
+    pub(crate) fn to_flags(options: &[SocketOption]) -> u8 {
+        options
+            .iter()
+            .fold(0, |flags, option| flags | option.flag())
+    }
+
+    /// Converts `SOCKET_OPTION_*` flags to the options.
+    pub(crate) fn from_flags(flags: u8) -> Vec<SocketOption> {
+        Self::ALL
+            .into_iter()
+            .filter(|option| flags & option.flag() != 0)
+            .collect()
+    }
+}
+
+/// Address family a [`SocketBind`] policy is scoped to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum BindFamily {
+    /// `AF_INET` binds, including binds of `AF_INET6` sockets to IPv4-mapped
+    /// addresses (`::ffff:0:0/96`).
+    #[serde(rename = "ipv4")]
+    Ipv4,
+    /// `AF_INET6` binds of other addresses.
+    #[serde(rename = "ipv6")]
+    Ipv6,
+}
+
+/// Policy of the ports the subject can bind.
+///
+/// Without `family`, the policy applies to IPv4 and IPv6 binds alike, so a
+/// denied port can't be bound over the other family. A policy with `family`
+/// applies only to binds of that family, where it replaces the
+/// family-agnostic policy of the same subject (if any). The precedence
+/// between the policies of all binaries and of a binary doesn't depend on
+/// the family: a family-agnostic policy of a binary still overrides a scoped
+/// policy of all binaries.
+///
+/// Socket options are kept per binary, not per family. `deny_options` of a
+/// scoped policy, unless empty, replace those of the other policies of the
+/// subject, for binds of both families.
 #[derive(Debug, PartialEq, Eq, Serialize, Deserialize)]
 pub struct SocketBind {
     pub subject: PolicySubject,
     pub allow: Ports,
     pub deny: Ports,
+    /// Socket options for which binds allowed by the policy are denied.
+    /// Empty by default, i.e. options are not matched.
+    #[serde(default)]
+    pub deny_options: Vec<SocketOption>,
+    /// Family the policy is scoped to, `None` (the default) for both.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub family: Option<BindFamily>,
+}
+
+/// Policy for binding ports by processes matched by their command name
+/// (`comm`) instead of their binary, e.g. `java*` for JVM threads or
+/// `nginx` for workers of a binary shared with other programs.
+///
+/// **The command name is not a security boundary.** Any process can change
+/// its own name with `prctl(PR_SET_NAME)` or by writing to
+/// `/proc/self/comm`, so any process can get the ports allowed by these
+/// policies and evade the ports they deny. Use them to partition cooperative
+/// workloads, not to contain untrusted ones.
+///
+/// Policies of the binary always win: command name rules apply only to
+/// binaries without a [`SocketBind`] policy of their own. Among command name
+/// policies, the longest matching pattern applies.
+#[derive(Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SocketBindComm {
+    pub comm: comm::CommPattern,
+    pub allow: Ports,
+    pub deny: Ports,
+}
+
+/// Policy for binding `AF_PACKET` (raw link-layer) sockets, enforced in the
+/// `socket_bind` LSM hook.
+///
+/// Enforcement is opt-in. A policy denying `all` subjects enables it, and
+/// policies allowing specific binaries (e.g. DHCP clients or `tcpdump`) act as
+/// an allowlist of legitimate packet socket users.
+#[derive(Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SocketBindPacket {
+    pub subject: PolicySubject,
+    pub allow: bool,
 }
 
 #[derive(Debug, PartialEq, Eq, Serialize, Deserialize)]
@@ -218,6 +525,499 @@ pub struct SocketConnect {
     pub deny: Addresses,
 }
 
+/// Selector of the addresses denied by a [`SocketConnectGeo`] policy.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum GeoSelector {
+    /// Addresses announced by the autonomous system.
+    #[serde(rename = "asn")]
+    Asn(u32),
+    /// Addresses assigned to the country (ISO 3166-1 alpha-2 code).
+    #[serde(rename = "country")]
+    Country(String),
+    #[serde(rename = "cidr")]
+    Cidr(cidr::Cidr),
+}
+
+/// Policy denying a subject from connecting to the addresses of autonomous
+/// systems, countries or CIDRs.
+///
+/// ASNs and countries are translated to CIDRs in user space, by the
+/// [`geo::CidrDatabase`] set with `SocketConnect::set_cidr_database`. The
+/// CIDRs are compacted and added to longest prefix match maps checked by
+/// `socket_connect` before the per-binary allow/deny rules, so the addresses
+/// are denied even if the `socket_connect` policy of the subject allows them.
+///
+/// The translation is only as fresh as the database and the last refresh (see
+/// `SocketConnect::watch_geo`): networks moved between the autonomous systems
+/// or countries since then are matched by their old ones. The maps hold up to
+/// [`ebpf_policy::MAX_CIDRS`] CIDRs per address family, policies exceeding
+/// that are rejected.
+#[derive(Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SocketConnectGeo {
+    pub subject: PolicySubject,
+    pub deny: Vec<GeoSelector>,
+}
+
+/// Instance metadata addresses of the common cloud providers: the IPv4
+/// address used by AWS, GCP, Azure and others, and the IPv6 address of AWS.
+pub const DEFAULT_METADATA_ADDRS: [IpAddr; 2] = [
+    IpAddr::V4(Ipv4Addr::new(169, 254, 169, 254)),
+    IpAddr::V6(Ipv6Addr::new(0xfd00, 0x0ec2, 0, 0, 0, 0, 0, 0x0254)),
+];
+
+/// Built-in policy denying a subject from connecting to the instance metadata
+/// endpoint and (optionally) to all link-local addresses, which prevents
+/// SSRF-based theft of cloud credentials.
+///
+/// The addresses are added as CIDRs to longest prefix match maps checked by
+/// `socket_connect` after protected addresses and before the other rules, so
+/// they are denied with [`Reason::Metadata`](crate::alerts::Reason::Metadata)
+/// even if the `socket_connect` policy of the subject allows them. A new
+/// policy of the same subject replaces the previous one.
+#[derive(Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SocketConnectMetadata {
+    pub subject: PolicySubject,
+    /// Metadata endpoint addresses, [`DEFAULT_METADATA_ADDRS`] by default.
+    /// Clouds with another endpoint (e.g. `100.100.100.200`) need it listed.
+    #[serde(default = "default_metadata_addrs")]
+    pub metadata: Vec<IpAddr>,
+    /// Whether all link-local addresses (`169.254.0.0/16` and `fe80::/10`)
+    /// are denied too.
+    #[serde(default = "default_link_local")]
+    pub link_local: bool,
+}
+
+fn default_metadata_addrs() -> Vec<IpAddr> {
+    DEFAULT_METADATA_ADDRS.to_vec()
+}
+
+fn default_link_local() -> bool {
+    true
+}
+
+impl SocketConnectMetadata {
+    /// Denies the default metadata addresses and link-local addresses.
+    pub fn new(subject: PolicySubject) -> Self {
+        Self {
+            subject,
+            metadata: default_metadata_addrs(),
+            link_local: default_link_local(),
+        }
+    }
+
+    /// Returns the denied CIDRs, compacted.
+    pub(crate) fn cidrs(&self) -> Result<Vec<cidr::Cidr>, EbpfguardError> {
+        let mut cidrs: Vec<_> = self
+            .metadata
+            .iter()
+            .copied()
+            .map(cidr::Cidr::host)
+            .collect();
+        if self.link_local {
+            cidrs.push(cidr::Cidr::new(Ipv4Addr::new(169, 254, 0, 0).into(), 16)?);
+            cidrs.push(cidr::Cidr::new(
+                Ipv6Addr::new(0xfe80, 0, 0, 0, 0, 0, 0, 0).into(),
+                10,
+            )?);
+        }
+        Ok(cidr::compact(cidrs))
+    }
+}
+
+/// Policy denying a subject from connecting to ranges of destination ports,
+/// of any address, e.g. `6660-6669` (IRC).
+///
+/// `allow` ranges are exceptions within the `deny` ranges: of the ranges of
+/// the subject containing the port, the narrowest decides, and the denied one
+/// if an allowed and a denied range are as narrow. So `deny: [6660-6669]`
+/// with `allow: [6667]` denies all of the range but 6667, and a single
+/// denied port within an allowed range is denied. Ranges of a binary
+/// containing the port take precedence over the ones of
+/// [`PolicySubject::All`].
+///
+/// The policies can only deny. They are checked after protected addresses,
+/// CIDRs and the `socket_connect` address rules allowed a connect, so a port
+/// which they don't deny is still subject to those. A new policy of the same
+/// subject replaces the previous one.
+#[derive(Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SocketConnectPorts {
+    pub subject: PolicySubject,
+    #[serde(default)]
+    pub allow: Vec<PortRange>,
+    pub deny: Vec<PortRange>,
+}
+
+impl SocketConnectPorts {
+    /// Converts the allowed and denied ranges to the values of the port
+    /// range maps.
+    pub(crate) fn to_ebpf(
+        &self,
+    ) -> Result<(ebpf_policy::PortRanges, ebpf_policy::PortRanges), EbpfguardError> {
+        Ok((
+            port_ranges_to_ebpf(&self.allow)?,
+            port_ranges_to_ebpf(&self.deny)?,
+        ))
+    }
+
+    pub(crate) fn from_ebpf(
+        subject: PolicySubject,
+        allow: &ebpf_policy::PortRanges,
+        deny: &ebpf_policy::PortRanges,
+    ) -> Self {
+        Self {
+            subject,
+            allow: port_ranges_from_ebpf(allow),
+            deny: port_ranges_from_ebpf(deny),
+        }
+    }
+}
+
+fn port_ranges_to_ebpf(ranges: &[PortRange]) -> Result<ebpf_policy::PortRanges, EbpfguardError> {
+    if ranges.len() > ebpf_policy::MAX_PORT_RANGES {
+        return Err(EbpfguardError::TooManyPortRanges(
+            ebpf_policy::MAX_PORT_RANGES,
+        ));
+    }
+    let mut ebpf_ranges = [ebpf_policy::PortRange::default(); ebpf_policy::MAX_PORT_RANGES];
+    for (i, range) in ranges.iter().enumerate() {
+        let range = range.validate()?;
+        ebpf_ranges[i] = ebpf_policy::PortRange::new(range.start, range.end);
+    }
+    Ok(ebpf_policy::PortRanges::new(ebpf_ranges))
+}
+
+fn port_ranges_from_ebpf(ranges: &ebpf_policy::PortRanges) -> Vec<PortRange> {
+    ranges
+        .ranges
+        .iter()
+        .take_while(|range| range.end != 0)
+        .map(|range| PortRange {
+            start: range.start,
+            end: range.end,
+        })
+        .collect()
+}
+
+/// Policy protecting a single address, which can be connected to only by the
+/// listed binaries.
+///
+/// It's checked before the per-binary `socket_connect` policies, with the
+/// same precedence as [`FileOpenProtected`].
+///
+/// With a `port`, only connects to that port of the address are protected.
+/// Whether policies have a port is set per namespace by the [`KeyLayout`]:
+/// all of them have one if it includes the port, none otherwise.
+#[derive(Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SocketConnectProtected {
+    pub addr: IpAddr,
+    #[serde(default)]
+    pub port: Option<u16>,
+    pub allow: Vec<PathBuf>,
+}
+
+/// Composition of the keys of the protected address maps of a namespace,
+/// shared by user space and the eBPF programs (see
+/// [`Ipv4Key`](ebpfguard_common::policy::Ipv4Key) for the layouts). The
+/// default is address only.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct KeyLayout {
+    /// Whether keys include the destination port, protecting addresses per
+    /// port.
+    #[serde(default)]
+    pub port: bool,
+    /// Whether keys include the address family.
+    #[serde(default)]
+    pub family: bool,
+}
+
+impl KeyLayout {
+    /// Converts the layout to `KEY_LAYOUT_*` flags.
+    pub(crate) fn to_flags(self) -> u8 {
+        let mut flags = 0;
+        if self.port {
+            flags |= ebpf_policy::KEY_LAYOUT_PORT;
+        }
+        if self.family {
+            flags |= ebpf_policy::KEY_LAYOUT_FAMILY;
+        }
+        flags
+    }
+
+    /// Converts `KEY_LAYOUT_*` flags to the layout.
+    pub(crate) fn from_flags(flags: u8) -> Self {
+        Self {
+            port: flags & ebpf_policy::KEY_LAYOUT_PORT != 0,
+            family: flags & ebpf_policy::KEY_LAYOUT_FAMILY != 0,
+        }
+    }
+}
+
+/// Longest window of a [`ConnectRateLimit`].
+pub const MAX_RATE_WINDOW: Duration = Duration::from_secs(86400);
+
+/// Rate limit of connects to a destination, counted across all processes of
+/// a namespace (see
+/// [`SocketConnect::set_rate_limit`](crate::hooks::socket_connect::SocketConnect::set_rate_limit)).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ConnectRateLimit {
+    /// Number of connects allowed in each window, at least 1.
+    pub connects: u32,
+    /// Length of the windows, non-zero and at most [`MAX_RATE_WINDOW`].
+    pub window: Duration,
+}
+
+impl ConnectRateLimit {
+    /// Converts the limit to the value of the rate limit maps, counting
+    /// connects in the counter `id`.
+    pub(crate) fn to_ebpf(self, id: u32) -> Result<ebpf_policy::ConnectRate, EbpfguardError> {
+        if self.connects == 0 || self.window.is_zero() || self.window > MAX_RATE_WINDOW {
+            return Err(EbpfguardError::InvalidRateLimit {
+                connects: self.connects,
+                window: self.window,
+            });
+        }
+        Ok(ebpf_policy::ConnectRate::new(
+            self.window.as_nanos() as u64,
+            self.connects,
+            id,
+        ))
+    }
+
+    pub(crate) fn from_ebpf(rate: &ebpf_policy::ConnectRate) -> Self {
+        Self {
+            connects: rate.limit,
+            window: Duration::from_nanos(rate.window),
+        }
+    }
+}
+
+/// Longest window of a [`RetryEscalation`].
+pub const MAX_RETRY_WINDOW: Duration = Duration::from_secs(86400);
+
+/// Escalation of repeated denials of a process as a persistent violation
+/// (see [`PolicyManager::set_retry_escalation`](crate::PolicyManager::set_retry_escalation)).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RetryEscalation {
+    /// Number of denials by the same hook from which on the denials are
+    /// persistent violations, at least 1.
+    pub denials: u32,
+    /// Length of the windows the denials are counted in, non-zero and at
+    /// most [`MAX_RETRY_WINDOW`].
+    pub window: Duration,
+}
+
+impl RetryEscalation {
+    pub(crate) fn to_ebpf(self) -> Result<ebpf_policy::RetryEscalation, EbpfguardError> {
+        if self.denials == 0 || self.window.is_zero() || self.window > MAX_RETRY_WINDOW {
+            return Err(EbpfguardError::InvalidRetryEscalation {
+                denials: self.denials,
+                window: self.window,
+            });
+        }
+        Ok(ebpf_policy::RetryEscalation::new(
+            self.window.as_nanos() as u64,
+            self.denials,
+        ))
+    }
+
+    pub(crate) fn from_ebpf(escalation: &ebpf_policy::RetryEscalation) -> Self {
+        Self {
+            denials: escalation.threshold,
+            window: Duration::from_nanos(escalation.window),
+        }
+    }
+}
+
+/// Socket family, by its `AF_*` name without the prefix.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum SocketFamily {
+    #[serde(rename = "unix")]
+    Unix,
+    #[serde(rename = "inet")]
+    Inet,
+    #[serde(rename = "inet6")]
+    Inet6,
+    #[serde(rename = "netlink")]
+    Netlink,
+    #[serde(rename = "packet")]
+    Packet,
+    /// Any other family, by number.
+    #[serde(rename = "other")]
+    Other(u16),
+}
+
+impl SocketFamily {
+    pub fn code(self) -> u16 {
+        match self {
+            SocketFamily::Unix => 1,
+            SocketFamily::Inet => 2,
+            SocketFamily::Inet6 => 10,
+            SocketFamily::Netlink => 16,
+            SocketFamily::Packet => 17,
+            SocketFamily::Other(family) => family,
+        }
+    }
+
+    pub fn from_code(family: u16) -> Self {
+        match family {
+            1 => SocketFamily::Unix,
+            2 => SocketFamily::Inet,
+            10 => SocketFamily::Inet6,
+            16 => SocketFamily::Netlink,
+            17 => SocketFamily::Packet,
+            family => SocketFamily::Other(family),
+        }
+    }
+}
+
+/// Socket type, by its `SOCK_*` name without the prefix.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum SocketType {
+    #[serde(rename = "stream")]
+    Stream,
+    #[serde(rename = "dgram")]
+    Dgram,
+    #[serde(rename = "raw")]
+    Raw,
+    #[serde(rename = "seqpacket")]
+    Seqpacket,
+    /// Any other type, by number.
+    #[serde(rename = "other")]
+    Other(u16),
+}
+
+impl SocketType {
+    pub fn code(self) -> u16 {
+        match self {
+            SocketType::Stream => 1,
+            SocketType::Dgram => 2,
+            SocketType::Raw => 3,
+            SocketType::Seqpacket => 5,
+            SocketType::Other(ty) => ty,
+        }
+    }
+
+    pub fn from_code(ty: u16) -> Self {
+        match ty {
+            1 => SocketType::Stream,
+            2 => SocketType::Dgram,
+            3 => SocketType::Raw,
+            5 => SocketType::Seqpacket,
+            ty => SocketType::Other(ty),
+        }
+    }
+}
+
+/// Family and type of sockets. Without a type, all types of the family
+/// match.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SocketKind {
+    pub family: SocketFamily,
+    #[serde(rename = "type", default, skip_serializing_if = "Option::is_none")]
+    pub socket_type: Option<SocketType>,
+}
+
+#[derive(Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum SocketKinds {
+    #[serde(rename = "all")]
+    All,
+    #[serde(rename = "kinds")]
+    Kinds(Vec<SocketKind>),
+}
+
+impl SocketKinds {
+    pub fn into_ebpf(self) -> Result<ebpf_policy::SocketKinds, EbpfguardError> {
+        match self {
+            SocketKinds::All => Ok(ebpf_policy::SocketKinds::new_all()),
+            SocketKinds::Kinds(kinds) => {
+                if kinds.len() > ebpf_policy::MAX_SOCKET_KINDS {
+                    return Err(EbpfguardError::TooManySocketKinds(
+                        ebpf_policy::MAX_SOCKET_KINDS,
+                    ));
+                }
+                let mut ebpf_kinds = [0; ebpf_policy::MAX_SOCKET_KINDS];
+                for (i, kind) in kinds.iter().enumerate() {
+                    ebpf_kinds[i] = ebpf_policy::SocketKinds::kind(
+                        kind.family.code(),
+                        kind.socket_type.map(SocketType::code).unwrap_or(0),
+                    );
+                }
+                Ok(ebpf_policy::SocketKinds::new(ebpf_kinds))
+            }
+        }
+    }
+}
+
+impl From<ebpf_policy::SocketKinds> for SocketKinds {
+    fn from(kinds: ebpf_policy::SocketKinds) -> Self {
+        if kinds.all() {
+            return SocketKinds::All;
+        }
+        SocketKinds::Kinds(
+            kinds
+                .kinds
+                .iter()
+                .take_while(|kind| **kind != 0)
+                .map(|kind| SocketKind {
+                    family: SocketFamily::from_code((kind >> 16) as u16),
+                    socket_type: match *kind as u16 {
+                        0 => None,
+                        ty => Some(SocketType::from_code(ty)),
+                    },
+                })
+                .collect(),
+        )
+    }
+}
+
+/// Policy of the kinds of sockets the subject can create, enforced in the
+/// `socket_create` LSM hook, e.g. to deny raw or netlink sockets.
+///
+/// Kinds are matched with the same precedence as ports in [`SocketBind`].
+/// The policy of [`PolicySubject::All`] with `deny: all` denies all sockets
+/// except for its `allow` kinds and the ones allowed for a binary. With
+/// `allow: all`, its `deny` kinds are denied to all binaries, and the `deny`
+/// kinds of a binary to that binary.
+#[derive(Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SocketCreate {
+    pub subject: PolicySubject,
+    pub allow: SocketKinds,
+    pub deny: SocketKinds,
+}
+
+/// Policy for listening on sockets, enforced in the `socket_listen` LSM hook.
+///
+/// Ports are matched against the local port the socket is bound to, with the
+/// same precedence as [`SocketBind`]. It allows e.g. letting a binary bind
+/// (reserve) a port while denying it from accepting connections on it.
+#[derive(Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SocketListen {
+    pub subject: PolicySubject,
+    pub allow: Ports,
+    pub deny: Ports,
+    /// Socket options for which listens allowed by the policy are denied.
+    /// Empty by default, i.e. options are not matched.
+    #[serde(default)]
+    pub deny_options: Vec<SocketOption>,
+}
+
+/// Policy of switches to privileged groups (group 0 and the groups set with
+/// `TaskFixSetgid::set_privileged_gids`), with the same semantics as
+/// [`TaskFixSetuid`]: a wildcard policy allows or denies all binaries, and
+/// policies of binaries are the exceptions. Switches to other groups are
+/// always allowed.
+///
+/// A switch is checked when the new real or effective group is privileged
+/// and differs from the old one. `setuid` and `setgid` calls are checked by
+/// separate hooks, each with its own policies: a process switching both its
+/// group and user (e.g. `setgid(0)` followed by `setuid(0)`) needs both
+/// allowed, and neither hook looks at the other's IDs.
+#[derive(Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TaskFixSetgid {
+    pub subject: PolicySubject,
+    pub allow: bool,
+}
+
 #[derive(Debug, PartialEq, Eq, Serialize, Deserialize)]
 pub struct TaskFixSetuid {
     pub subject: PolicySubject,
@@ -228,8 +1028,6 @@ pub struct TaskFixSetuid {
 mod test {
     use super::*;
 
-    use std::net::{Ipv4Addr, Ipv6Addr};
-
     #[test]
     fn test_file_open() {
         let yaml = "
@@ -264,6 +1062,85 @@ mod test {
         );
     }
 
+    #[test]
+    fn test_file_open_glob() {
+        let yaml = "
+- !file_open_glob
+  subject: all
+  allow: []
+  deny:
+    - /home/*/.ssh/*
+    - /root/.ssh/id_*
+";
+        let policy = serde_yaml::from_str::<Vec<Policy>>(yaml).unwrap();
+        assert_eq!(policy.len(), 1);
+        assert_eq!(
+            policy[0],
+            Policy::FileOpenGlob(FileOpenGlob {
+                subject: PolicySubject::All,
+                allow: vec![],
+                deny: vec!["/home/*/.ssh/*".to_owned(), "/root/.ssh/id_*".to_owned()],
+            })
+        );
+    }
+
+    #[test]
+    fn test_file_open_protected() {
+        let yaml = "
+- !file_open_protected
+  path: /etc/myapp/secret
+  allow:
+    - /usr/bin/myapp
+    - /usr/bin/myapp-backup
+";
+        let policy = serde_yaml::from_str::<Vec<Policy>>(yaml).unwrap();
+        assert_eq!(policy.len(), 1);
+        assert_eq!(
+            policy[0],
+            Policy::FileOpenProtected(FileOpenProtected {
+                path: PathBuf::from("/etc/myapp/secret"),
+                allow: vec![
+                    PathBuf::from("/usr/bin/myapp"),
+                    PathBuf::from("/usr/bin/myapp-backup")
+                ]
+            })
+        );
+    }
+
+    #[test]
+    fn test_inode_create() {
+        let yaml = "
+- !inode_create
+  subject: all
+  allow: all
+  deny: !paths
+    - /usr/bin
+- !inode_create
+  subject: !binary /usr/bin/dpkg
+  allow: !paths
+    - /usr/bin
+  deny: !paths []
+";
+        let policy = serde_yaml::from_str::<Vec<Policy>>(yaml).unwrap();
+        assert_eq!(policy.len(), 2);
+        assert_eq!(
+            policy[0],
+            Policy::InodeCreate(InodeCreate {
+                subject: PolicySubject::All,
+                allow: Paths::All,
+                deny: Paths::Paths(vec![PathBuf::from("/usr/bin")])
+            })
+        );
+        assert_eq!(
+            policy[1],
+            Policy::InodeCreate(InodeCreate {
+                subject: PolicySubject::Binary(PathBuf::from("/usr/bin/dpkg")),
+                allow: Paths::Paths(vec![PathBuf::from("/usr/bin")]),
+                deny: Paths::Paths(vec![])
+            })
+        );
+    }
+
     #[test]
     fn test_sb_mount() {
         let yaml = "
@@ -314,7 +1191,9 @@ mod test {
             Policy::SocketBind(SocketBind {
                 subject: PolicySubject::Binary(PathBuf::from("/usr/bin/nginx")),
                 allow: Ports::Ports(vec![80, 443]),
-                deny: Ports::All
+                deny: Ports::All,
+                deny_options: vec![],
+                family: None,
             })
         );
         assert_eq!(
@@ -322,7 +1201,124 @@ mod test {
             Policy::SocketBind(SocketBind {
                 subject: PolicySubject::Binary(PathBuf::from("/usr/bin/python")),
                 allow: Ports::Ports(vec![8080]),
-                deny: Ports::All
+                deny: Ports::All,
+                deny_options: vec![],
+                family: None,
+            })
+        );
+    }
+
+    #[test]
+    fn test_socket_bind_family() {
+        let yaml = "
+- !socket_bind
+  subject: all
+  allow: all
+  deny: !ports
+    - 8080
+  family: ipv6
+";
+        let policy = serde_yaml::from_str::<Vec<Policy>>(yaml).unwrap();
+        assert_eq!(
+            policy[0],
+            Policy::SocketBind(SocketBind {
+                subject: PolicySubject::All,
+                allow: Ports::All,
+                deny: Ports::Ports(vec![8080]),
+                deny_options: vec![],
+                family: Some(BindFamily::Ipv6),
+            })
+        );
+
+        let yaml = "
+- !socket_bind
+  subject: all
+  allow: all
+  deny: all
+  family: inet6
+";
+        assert!(serde_yaml::from_str::<Vec<Policy>>(yaml).is_err());
+    }
+
+    #[test]
+    fn test_socket_option_flags() {
+        let options = vec![SocketOption::ReuseAddr, SocketOption::BindToDevice];
+        let flags = SocketOption::to_flags(&options);
+        assert_eq!(
+            flags,
+            ebpf_policy::SOCKET_OPTION_REUSEADDR | ebpf_policy::SOCKET_OPTION_BINDTODEVICE
+        );
+        assert_eq!(SocketOption::from_flags(flags), options);
+        assert_eq!(SocketOption::to_flags(&[]), 0);
+        assert!(SocketOption::from_flags(0).is_empty());
+    }
+
+    #[test]
+    fn test_socket_bind_comm() {
+        let yaml = "
+- !socket_bind_comm
+  comm: java*
+  allow: !ports
+    - 8080
+  deny: all
+- !socket_bind_comm
+  comm: nginx
+  allow: all
+  deny: !ports
+    - 22
+";
+        let policy = serde_yaml::from_str::<Vec<Policy>>(yaml).unwrap();
+        assert_eq!(policy.len(), 2);
+        assert_eq!(
+            policy[0],
+            Policy::SocketBindComm(SocketBindComm {
+                comm: comm::CommPattern::Prefix("java".to_owned()),
+                allow: Ports::Ports(vec![8080]),
+                deny: Ports::All,
+            })
+        );
+        assert_eq!(
+            policy[1],
+            Policy::SocketBindComm(SocketBindComm {
+                comm: comm::CommPattern::Exact("nginx".to_owned()),
+                allow: Ports::All,
+                deny: Ports::Ports(vec![22]),
+            })
+        );
+
+        let yaml = "
+- !socket_bind_comm
+  comm: a-very-long-thread-name
+  allow: all
+  deny: all
+";
+        assert!(serde_yaml::from_str::<Vec<Policy>>(yaml).is_err());
+    }
+
+    #[test]
+    fn test_socket_bind_packet() {
+        let yaml = "
+- !socket_bind_packet
+  subject: all
+  allow: false
+- !socket_bind_packet
+  subject: !binary /usr/sbin/dhclient
+  allow: true
+";
+        let policy = serde_yaml::from_str::<Vec<Policy>>(yaml).unwrap();
+        assert_eq!(policy.len(), 2);
+        assert_eq!(
+            policy[0],
+            Policy::SocketBindPacket(SocketBindPacket {
+                subject: PolicySubject::All,
+                allow: false
+            })
+        );
+        assert_eq!(
+            policy[1],
+            Policy::SocketBindPacket(SocketBindPacket {
+                subject: PolicySubject::Binary(PathBuf::from("/usr/sbin/dhclient")),
+                allow: true
             })
         );
     }
@@ -373,6 +1369,303 @@ mod test {
         );
     }
 
+    #[test]
+    fn test_socket_connect_geo() {
+        let yaml = "
+- !socket_connect_geo
+  subject: all
+  deny:
+    - !asn 64496
+    - !country ZZ
+    - !cidr 192.0.2.0/24
+";
+        let policy = serde_yaml::from_str::<Vec<Policy>>(yaml).unwrap();
+        assert_eq!(policy.len(), 1);
+        assert_eq!(
+            policy[0],
+            Policy::SocketConnectGeo(SocketConnectGeo {
+                subject: PolicySubject::All,
+                deny: vec![
+                    GeoSelector::Asn(64496),
+                    GeoSelector::Country("ZZ".to_owned()),
+                    GeoSelector::Cidr("192.0.2.0/24".parse().unwrap()),
+                ]
+            })
+        );
+    }
+
+    #[test]
+    fn test_socket_connect_metadata() {
+        let yaml = "
+- !socket_connect_metadata
+  subject: all
+- !socket_connect_metadata
+  subject: all
+  metadata:
+    - 100.100.100.200
+  link_local: false
+";
+        let policy = serde_yaml::from_str::<Vec<Policy>>(yaml).unwrap();
+        assert_eq!(policy.len(), 2);
+        assert_eq!(
+            policy[0],
+            Policy::SocketConnectMetadata(SocketConnectMetadata::new(PolicySubject::All))
+        );
+        assert_eq!(
+            policy[1],
+            Policy::SocketConnectMetadata(SocketConnectMetadata {
+                subject: PolicySubject::All,
+                metadata: vec![IpAddr::V4(Ipv4Addr::new(100, 100, 100, 200))],
+                link_local: false,
+            })
+        );
+
+        // The IPv4 metadata address is covered by the link-local range.
+        let cidrs = SocketConnectMetadata::new(PolicySubject::All)
+            .cidrs()
+            .unwrap();
+        let mut cidrs: Vec<_> = cidrs.iter().map(ToString::to_string).collect();
+        cidrs.sort();
+        assert_eq!(cidrs, ["169.254.0.0/16", "fd00:ec2::254/128", "fe80::/10"]);
+    }
+
+    #[test]
+    fn test_socket_connect_protected() {
+        let yaml = "
+- !socket_connect_protected
+  addr: 10.0.0.5
+  allow:
+    - /usr/bin/psql
+- !socket_connect_protected
+  addr: 2001:db8::5
+  port: 5432
+  allow:
+    - /usr/bin/psql
+";
+        let policy = serde_yaml::from_str::<Vec<Policy>>(yaml).unwrap();
+        assert_eq!(policy.len(), 2);
+        assert_eq!(
+            policy[0],
+            Policy::SocketConnectProtected(SocketConnectProtected {
+                addr: IpAddr::V4(Ipv4Addr::new(10, 0, 0, 5)),
+                port: None,
+                allow: vec![PathBuf::from("/usr/bin/psql")]
+            })
+        );
+        assert_eq!(
+            policy[1],
+            Policy::SocketConnectProtected(SocketConnectProtected {
+                addr: IpAddr::V6(Ipv6Addr::new(0x2001, 0x0db8, 0, 0, 0, 0, 0, 5)),
+                port: Some(5432),
+                allow: vec![PathBuf::from("/usr/bin/psql")]
+            })
+        );
+    }
+
+    #[test]
+    fn test_socket_connect_ports() {
+        let yaml = "
+- !socket_connect_ports
+  subject: all
+  deny:
+    - 6660-6669
+    - 6697
+  allow:
+    - 6667
+";
+        let policy = serde_yaml::from_str::<Vec<Policy>>(yaml).unwrap();
+        let expected = SocketConnectPorts {
+            subject: PolicySubject::All,
+            allow: vec![PortRange::port(6667)],
+            deny: vec![
+                PortRange {
+                    start: 6660,
+                    end: 6669,
+                },
+                PortRange::port(6697),
+            ],
+        };
+        assert_eq!(policy, vec![Policy::SocketConnectPorts(expected)]);
+        let yaml = serde_yaml::to_string(&policy).unwrap();
+        assert_eq!(serde_yaml::from_str::<Vec<Policy>>(&yaml).unwrap(), policy);
+
+        let policy = match policy.into_iter().next() {
+            Some(Policy::SocketConnectPorts(policy)) => policy,
+            _ => unreachable!(),
+        };
+        let (allow, deny) = policy.to_ebpf().unwrap();
+        assert_eq!(
+            SocketConnectPorts::from_ebpf(PolicySubject::All, &allow, &deny),
+            policy
+        );
+
+        for range in ["0", "0-80", "6669-6660", "80-", "65536", "a-b"] {
+            assert!(matches!(
+                range.parse::<PortRange>(),
+                Err(EbpfguardError::InvalidPortRange(_))
+            ));
+        }
+        assert!(serde_yaml::from_str::<Vec<PortRange>>("[0]").is_err());
+        let policy = SocketConnectPorts {
+            subject: PolicySubject::All,
+            allow: vec![],
+            deny: (1..=5).map(PortRange::port).collect(),
+        };
+        assert!(matches!(
+            policy.to_ebpf(),
+            Err(EbpfguardError::TooManyPortRanges(4))
+        ));
+    }
+
+    #[test]
+    fn test_connect_rate_limit() {
+        let limit = ConnectRateLimit {
+            connects: 10,
+            window: Duration::from_secs(60),
+        };
+        let rate = limit.to_ebpf(3).unwrap();
+        assert_eq!(rate, ebpf_policy::ConnectRate::new(60_000_000_000, 10, 3));
+        assert_eq!(ConnectRateLimit::from_ebpf(&rate), limit);
+
+        for (connects, window) in [
+            (0, Duration::from_secs(60)),
+            (10, Duration::ZERO),
+            (10, MAX_RATE_WINDOW + Duration::from_secs(1)),
+        ] {
+            assert!(matches!(
+                ConnectRateLimit { connects, window }.to_ebpf(1),
+                Err(EbpfguardError::InvalidRateLimit { .. })
+            ));
+        }
+    }
+
+    #[test]
+    fn test_retry_escalation() {
+        let escalation = RetryEscalation {
+            denials: 5,
+            window: Duration::from_secs(10),
+        };
+        let ebpf = escalation.to_ebpf().unwrap();
+        assert_eq!(ebpf, ebpf_policy::RetryEscalation::new(10_000_000_000, 5));
+        assert_eq!(RetryEscalation::from_ebpf(&ebpf), escalation);
+
+        for (denials, window) in [
+            (0, Duration::from_secs(10)),
+            (5, Duration::ZERO),
+            (5, MAX_RETRY_WINDOW + Duration::from_secs(1)),
+        ] {
+            assert!(matches!(
+                RetryEscalation { denials, window }.to_ebpf(),
+                Err(EbpfguardError::InvalidRetryEscalation { .. })
+            ));
+        }
+    }
+
+    #[test]
+    fn test_socket_create() {
+        let yaml = "
+- !socket_create
+  subject: all
+  allow: all
+  deny: !kinds
+    - family: netlink
+    - family: inet
+      type: raw
+    - family: !other 40
+";
+        let policy = serde_yaml::from_str::<Vec<Policy>>(yaml).unwrap();
+        let deny = vec![
+            SocketKind {
+                family: SocketFamily::Netlink,
+                socket_type: None,
+            },
+            SocketKind {
+                family: SocketFamily::Inet,
+                socket_type: Some(SocketType::Raw),
+            },
+            SocketKind {
+                family: SocketFamily::Other(40),
+                socket_type: None,
+            },
+        ];
+        assert_eq!(
+            policy[0],
+            Policy::SocketCreate(SocketCreate {
+                subject: PolicySubject::All,
+                allow: SocketKinds::All,
+                deny: SocketKinds::Kinds(deny.clone()),
+            })
+        );
+
+        let kinds = SocketKinds::Kinds(deny.clone()).into_ebpf().unwrap();
+        assert!(kinds.contains(16, 3));
+        assert!(kinds.contains(2, 3));
+        assert!(!kinds.contains(2, 1));
+        assert_eq!(SocketKinds::from(kinds), SocketKinds::Kinds(deny));
+
+        let too_many = SocketKinds::Kinds(vec![
+            SocketKind {
+                family: SocketFamily::Unix,
+                socket_type: None,
+            };
+            ebpf_policy::MAX_SOCKET_KINDS + 1
+        ]);
+        assert!(matches!(
+            too_many.into_ebpf(),
+            Err(EbpfguardError::TooManySocketKinds(_))
+        ));
+    }
+
+    #[test]
+    fn test_socket_listen() {
+        let yaml = "
+- !socket_listen
+  subject: !binary /usr/bin/python
+  allow: all
+  deny: !ports
+    - 8080
+  deny_options:
+    - reuseport
+";
+        let policy = serde_yaml::from_str::<Vec<Policy>>(yaml).unwrap();
+        assert_eq!(policy.len(), 1);
+        assert_eq!(
+            policy[0],
+            Policy::SocketListen(SocketListen {
+                subject: PolicySubject::Binary(PathBuf::from("/usr/bin/python")),
+                allow: Ports::All,
+                deny: Ports::Ports(vec![8080]),
+                deny_options: vec![SocketOption::ReusePort],
+            })
+        );
+    }
+
+    #[test]
+    fn test_task_fix_setgid() {
+        let yaml = "
+- !task_fix_setgid
+  subject: all
+  allow: false
+- !task_fix_setgid
+  subject: !binary /usr/bin/newgrp
+  allow: true
+";
+        let policy = serde_yaml::from_str::<Vec<Policy>>(yaml).unwrap();
+        assert_eq!(
+            policy,
+            vec![
+                Policy::TaskFixSetgid(TaskFixSetgid {
+                    subject: PolicySubject::All,
+                    allow: false
+                }),
+                Policy::TaskFixSetgid(TaskFixSetgid {
+                    subject: PolicySubject::Binary(PathBuf::from("/usr/bin/newgrp")),
+                    allow: true
+                }),
+            ]
+        );
+    }
+
     #[test]
     fn test_task_fix_setuid() {
         let yaml = "
@@ -400,4 +1693,30 @@ mod test {
             })
         );
     }
+
+    #[test]
+    fn test_bpf() {
+        let yaml = "
+- !bpf
+  subject: all
+  allow: false
+- !bpf
+  subject: !binary /usr/sbin/bpftool
+  allow: true
+";
+        let policy = serde_yaml::from_str::<Vec<Policy>>(yaml).unwrap();
+        assert_eq!(
+            policy,
+            vec![
+                Policy::Bpf(Bpf {
+                    subject: PolicySubject::All,
+                    allow: false
+                }),
+                Policy::Bpf(Bpf {
+                    subject: PolicySubject::Binary(PathBuf::from("/usr/sbin/bpftool")),
+                    allow: true
+                }),
+            ]
+        );
+    }
 }
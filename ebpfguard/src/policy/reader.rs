@@ -1,12 +1,252 @@
+//! Reading and writing policy files.
+//!
+//! Policy files are YAML documents with the version of their schema and the
+//! list of policies:
+//!
+//! ```yaml
+//! version: 2
+//! policies:
+//!   - !socket_bind
+//!     subject: all
+//!     allow: all
+//!     deny: !ports
+//!       - 22
+//! ```
+//!
+//! Files of versions from [`MIN_POLICY_VERSION`] to [`POLICY_VERSION`] are
+//! read, older ones are migrated to the current schema first. Files of other
+//! versions are rejected instead of being guessed at, since a field whose
+//! meaning changed would otherwise be applied silently.
+//!
+//! Version 1 files are bare lists of policies without a version, as written
+//! before the version field existed (a document declaring `version: 1` is
+//! read the same way). They tag `task_fix_setuid` policies as `setuid`.
+
 use std::{fs, path::Path};
 
+use serde::{Deserialize, Serialize};
+use serde_yaml::{
+    value::{Tag, TaggedValue},
+    Value,
+};
+
 use crate::error::EbpfguardError;
 
 use super::Policy;
 
+/// Version of the policy file schema written by this version of ebpfguard.
+pub const POLICY_VERSION: u32 = 2;
+
+/// Oldest version of the policy file schema which can be read, by migrating
+/// it to [`POLICY_VERSION`].
+pub const MIN_POLICY_VERSION: u32 = 1;
+
+/// Policy file of the current schema version.
+#[derive(Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct PolicyFile {
+    pub version: u32,
+    pub policies: Vec<Policy>,
+}
+
+impl PolicyFile {
+    pub fn new(policies: Vec<Policy>) -> Self {
+        Self {
+            version: POLICY_VERSION,
+            policies,
+        }
+    }
+}
+
+/// Version of a policy file, read before the rest of the file, whose fields
+/// depend on it.
+#[derive(Deserialize)]
+struct Version {
+    version: u32,
+}
+
+/// Policy file of a supported version, with the policies not parsed yet.
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct VersionedFile {
+    #[allow(dead_code)]
+    version: u32,
+    policies: Value,
+}
+
 pub fn read_policies<P: AsRef<Path>>(path: P) -> Result<Vec<Policy>, EbpfguardError> {
     let path = path.as_ref();
     let yaml = fs::read_to_string(path)?;
-    let policies = serde_yaml::from_str::<Vec<Policy>>(&yaml)?;
-    Ok(policies)
+    parse_policies(&yaml)
+}
+
+/// Parses the policies of a policy file, migrating them from older versions.
+pub fn parse_policies(yaml: &str) -> Result<Vec<Policy>, EbpfguardError> {
+    let file = serde_yaml::from_str::<Value>(yaml)?;
+    let version = match file {
+        Value::Sequence(_) => 1,
+        _ => serde_yaml::from_value::<Version>(file.clone())?.version,
+    };
+    if !(MIN_POLICY_VERSION..=POLICY_VERSION).contains(&version) {
+        return Err(EbpfguardError::UnsupportedPolicyVersion {
+            version,
+            min: MIN_POLICY_VERSION,
+            max: POLICY_VERSION,
+        });
+    }
+
+    let mut policies = match file {
+        policies @ Value::Sequence(_) => policies,
+        file => serde_yaml::from_value::<VersionedFile>(file)?.policies,
+    };
+
+    for version in version..POLICY_VERSION {
+        policies = migrate(version, policies);
+    }
+
+    Ok(serde_yaml::from_value(policies)?)
+}
+
+/// Writes the policies to a policy file of the current version.
+pub fn write_policies<P: AsRef<Path>>(
+    path: P,
+    policies: Vec<Policy>,
+) -> Result<(), EbpfguardError> {
+    let yaml = serde_yaml::to_string(&PolicyFile::new(policies))?;
+    fs::write(path, yaml)?;
+    Ok(())
+}
+
+/// Migrates the policies of a file of the given version to the next version.
+fn migrate(version: u32, policies: Value) -> Value {
+    match version {
+        1 => rename_tag(policies, "setuid", "task_fix_setuid"),
+        _ => policies,
+    }
+}
+
+/// Renames the tag of the policies tagged `from` in the list.
+fn rename_tag(policies: Value, from: &str, to: &str) -> Value {
+    match policies {
+        Value::Sequence(policies) => Value::Sequence(
+            policies
+                .into_iter()
+                .map(|policy| match policy {
+                    Value::Tagged(tagged) if tagged.tag == from => {
+                        Value::Tagged(Box::new(TaggedValue {
+                            tag: Tag::new(to),
+                            value: tagged.value,
+                        }))
+                    }
+                    policy => policy,
+                })
+                .collect(),
+        ),
+        policies => policies,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::policy::{PolicySubject, Ports, SocketBind, TaskFixSetuid};
+
+    #[test]
+    fn test_current_version() {
+        let yaml = "
+version: 2
+policies:
+  - !task_fix_setuid
+    subject: all
+    allow: false
+  - !socket_bind
+    subject: all
+    allow: all
+    deny: !ports
+      - 22
+";
+        let policies = parse_policies(yaml).unwrap();
+        assert_eq!(
+            policies,
+            vec![
+                Policy::TaskFixSetuid(TaskFixSetuid {
+                    subject: PolicySubject::All,
+                    allow: false,
+                }),
+                Policy::SocketBind(SocketBind {
+                    subject: PolicySubject::All,
+                    allow: Ports::All,
+                    deny: Ports::Ports(vec![22]),
+                    deny_options: vec![],
+                    family: None,
+                }),
+            ]
+        );
+
+        let yaml = serde_yaml::to_string(&PolicyFile::new(policies)).unwrap();
+        assert!(yaml.starts_with("version: 2\n"));
+        assert_eq!(parse_policies(&yaml).unwrap().len(), 2);
+    }
+
+    #[test]
+    fn test_previous_version_migrated() {
+        let yaml = "
+- !setuid
+  subject: !binary /usr/bin/sudo
+  allow: true
+";
+        let policies = parse_policies(yaml).unwrap();
+        assert_eq!(
+            policies,
+            vec![Policy::TaskFixSetuid(TaskFixSetuid {
+                subject: PolicySubject::Binary("/usr/bin/sudo".into()),
+                allow: true,
+            })]
+        );
+
+        let versioned = "
+version: 1
+policies:
+  - !setuid
+    subject: !binary /usr/bin/sudo
+    allow: true
+";
+        assert_eq!(parse_policies(versioned).unwrap(), policies);
+    }
+
+    #[test]
+    fn test_unsupported_version() {
+        let yaml = "
+version: 3
+defaults:
+  alert_window: 10s
+policies:
+  - !task_fix_setuid
+    subject: all
+    allow: false
+";
+        let err = parse_policies(yaml).unwrap_err();
+        assert!(matches!(
+            err,
+            EbpfguardError::UnsupportedPolicyVersion {
+                version: 3,
+                min: MIN_POLICY_VERSION,
+                max: POLICY_VERSION,
+            }
+        ));
+
+        let yaml = "
+version: 0
+policies: []
+";
+        assert!(parse_policies(yaml).is_err());
+
+        // Fields unknown to the current schema are rejected too.
+        let yaml = "
+version: 2
+defaults: {}
+policies: []
+";
+        assert!(parse_policies(yaml).is_err());
+    }
 }
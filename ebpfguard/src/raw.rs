@@ -0,0 +1,169 @@
+//! Raw handles of the pinned maps, an escape hatch for operations the hooks
+//! don't cover, e.g. bulk edits or inspecting the entries of all namespaces.
+//!
+//! Handles are typed aya maps opened from the pins of the policy manager,
+//! see [`PolicyManager::raw_hash_map`](crate::PolicyManager::raw_hash_map)
+//! and [`PolicyManager::raw_lpm_trie`](crate::PolicyManager::raw_lpm_trie).
+//! They are not part of the stable API: map names and the key and value
+//! types of `ebpfguard_common::policy` change between versions without
+//! notice, and a handle of the wrong types fails with
+//! [`MapSizeMismatch`](crate::error::EbpfguardError::MapSizeMismatch) only
+//! if the sizes differ.
+//!
+//! # Example
+//!
+//! ```no_run
+//! use ebpfguard::PolicyManager;
+//!
+//! let policy_manager = PolicyManager::with_default_path().unwrap();
+//! let map = policy_manager.allowed_socket_bind_map().unwrap();
+//! for res in map.iter() {
+//!     let (key, ports) = res.unwrap();
+//!     println!("namespace {} inode {}: {:?}", key.namespace, key.inode, ports.all());
+//! }
+//! ```
+//!
+//! What is safe to change directly:
+//!
+//! * The policy maps (`ALLOWED_*`, `DENIED_*`, `PROTECTED_*`, the CIDR and
+//!   comm maps) and the settings maps (`CONFIG`, `EXEMPT_*`, `BIND_LIMIT_*`,
+//!   `RATE_*`, `ALERT_WINDOWS`, `ALERT_CHANNELS`, `MESSAGE_IDS`,
+//!   `RETRY_ESCALATIONS`), with the entries built as the hooks build them.
+//!   Handles of the `socket_bind` policy maps, which its verdict cache
+//!   depends on, clear the cached binds of all namespaces when dropped after
+//!   a mutable borrow. The namespaces of the keys of policy maps carry
+//!   `GENERATION_BIT` of `ebpfguard_common::policy` in the generation 1 of
+//!   the hook's policies, and the programs only read the keys of the
+//!   generation which atomic plans last switched the hook to.
+//! * Kernel state (`LAST_ALERTS`, `BIND_COUNT_*`, `BOUND_PORTS_*`,
+//!   `RATE_WINDOWS_*`, `RETRY_WINDOWS`, `SESSIONS`) can be read, and removing entries
+//!   resets it.
+//!
+//! Direct changes bypass the hooks: they emit no audit events, and policies
+//! listed by the hooks and the rules their background tasks refresh (glob
+//! patterns, geo selectors) don't reflect them. Handles of maps whose direct
+//! use would break the programs are refused with
+//! [`RawMapRefused`](crate::error::EbpfguardError::RawMapRefused):
+//!
+//! * the alert perf event arrays (`ALERT_*`), since user space opening their
+//!   buffers again takes the alerts away from the hooks, use their `alerts`
+//!   methods instead,
+//! * `GENERATION_SOCKET_BIND` and `CACHE_SOCKET_BIND`, which have to change
+//!   together with the `socket_bind` policy maps,
+//! * `POLICY_GENERATIONS`, which has to change together with the policy maps.
+
+use std::{
+    ops::{Deref, DerefMut},
+    path::Path,
+};
+
+use aya::maps::{HashMap, Map, MapData, MapError};
+use ebpfguard_common::policy::SocketBindVerdictKey;
+use log::warn;
+
+use crate::error::EbpfguardError;
+
+/// Maps which have to stay consistent with other maps, refused as raw
+/// handles (besides the alert perf event arrays).
+const REFUSED_MAPS: &[&str] = &[
+    "GENERATION_SOCKET_BIND",
+    "CACHE_SOCKET_BIND",
+    "POLICY_GENERATIONS",
+];
+
+/// Maps whose names start with `ALERT_` but aren't perf event arrays.
+const ALERT_SETTINGS_MAPS: &[&str] = &["ALERT_WINDOWS", "ALERT_CHANNELS"];
+
+/// Maps whose changes have to invalidate the binds cached by `socket_bind`.
+const SOCKET_BIND_POLICY_MAPS: &[&str] = &[
+    "ALLOWED_SOCKET_BIND",
+    "DENIED_SOCKET_BIND",
+    "ALLOWED_SOCKET_BIND_V4",
+    "DENIED_SOCKET_BIND_V4",
+    "ALLOWED_SOCKET_BIND_V6",
+    "DENIED_SOCKET_BIND_V6",
+    "OPTIONS_SOCKET_BIND",
+    "ALLOWED_SOCKET_BIND_COMM",
+    "DENIED_SOCKET_BIND_COMM",
+];
+
+/// Raw handle of a pinned map, dereferencing to the aya map.
+pub struct RawMap<T> {
+    map: T,
+    /// `CACHE_SOCKET_BIND`, for handles of the `socket_bind` policy maps.
+    cache: Option<HashMap<MapData, SocketBindVerdictKey, u64>>,
+    mutated: bool,
+}
+
+impl<T> RawMap<T> {
+    /// Opens the pinned map and converts it with `convert` (e.g.
+    /// `Map::HashMap`), refusing the maps listed in [`raw`](crate::raw).
+    pub(crate) fn open<F>(maps_path: &Path, name: &str, convert: F) -> Result<Self, EbpfguardError>
+    where
+        F: FnOnce(MapData) -> Map,
+        T: TryFrom<Map, Error = MapError>,
+    {
+        let perf_array = name.starts_with("ALERT_") && !ALERT_SETTINGS_MAPS.contains(&name);
+        if perf_array || REFUSED_MAPS.contains(&name) {
+            return Err(EbpfguardError::RawMapRefused(name.to_owned()));
+        }
+
+        let data = MapData::from_pin(maps_path.join(name))
+            .map_err(|e| EbpfguardError::from_map_error(name, e))?;
+        let map =
+            T::try_from(convert(data)).map_err(|e| EbpfguardError::from_map_error(name, e))?;
+        let cache = if SOCKET_BIND_POLICY_MAPS.contains(&name) {
+            let name = "CACHE_SOCKET_BIND";
+            let data = MapData::from_pin(maps_path.join(name))
+                .map_err(|e| EbpfguardError::from_map_error(name, e))?;
+            Some(
+                HashMap::try_from(Map::LruHashMap(data))
+                    .map_err(|e| EbpfguardError::from_map_error(name, e))?,
+            )
+        } else {
+            None
+        };
+
+        Ok(Self {
+            map,
+            cache,
+            mutated: false,
+        })
+    }
+}
+
+impl<T> Deref for RawMap<T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        &self.map
+    }
+}
+
+impl<T> DerefMut for RawMap<T> {
+    fn deref_mut(&mut self) -> &mut T {
+        self.mutated = true;
+        &mut self.map
+    }
+}
+
+impl<T> Drop for RawMap<T> {
+    fn drop(&mut self) {
+        let cache = match (&mut self.cache, self.mutated) {
+            (Some(cache), true) => cache,
+            _ => return,
+        };
+        // The changed entries may be of any namespace, including ones whose
+        // generation was never bumped, so the cache is cleared instead.
+        let keys = cache.keys().filter_map(|key| key.ok()).collect::<Vec<_>>();
+        for key in keys {
+            match cache.remove(&key) {
+                Ok(()) | Err(MapError::KeyNotFound) => {}
+                Err(e) => {
+                    warn!("failed to clear the binds cached by socket_bind: {e}");
+                    return;
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,848 @@
+//! Evaluation of policies against hypothetical events, without loading them
+//! into the kernel, e.g. to check the impact of a policy change before
+//! deploying it.
+//!
+//! Events are decided by the same functions as in the eBPF programs (see
+//! `ebpfguard_common::decision`), from map entries built the same way as by
+//! the hooks. Only the policies are simulated, not the state around them:
+//! escalations, socket options, exempt ports, connect rate limits, namespaces
+//! and command names (see [`SocketBindComm`](crate::policy::SocketBindComm))
+//! are not taken into account, and binds which the policies don't decide are
+//! allowed.
+//!
+//! # Example
+//!
+//! ```no_run
+//! use ebpfguard::{
+//!     policy::{Policy, PolicySubject, Ports, SocketBind},
+//!     simulate::{simulate, Event, Verdict},
+//! };
+//!
+//! let policies = vec![Policy::SocketBind(SocketBind {
+//!     subject: PolicySubject::All,
+//!     allow: Ports::All,
+//!     deny: Ports::Ports(vec![22]),
+//!     deny_options: vec![],
+//!     family: None,
+//! })];
+//! let events = [Event::SocketBind {
+//!     binprm_inode: 1234,
+//!     family: 2,
+//!     port: 22,
+//! }];
+//! let verdicts = simulate(policies, &events).unwrap();
+//! assert!(matches!(verdicts[0], Verdict::Deny(_)));
+//! ```
+
+use std::{collections::HashMap, hash::Hash, net::IpAddr, path::PathBuf};
+
+use ebpfguard_common::{
+    alerts::REASON_DEFAULT_DENY,
+    consts::{AF_INET, AF_INET6, AF_PACKET, INODE_WILDCARD},
+    decision::{self, Action, PortRules, Rules},
+    policy as ebpf_policy,
+};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    alerts::Reason,
+    error::EbpfguardError,
+    fs::{self, InodeResolver, LocalInodeResolver},
+    policy::{cidr::Cidr, BindFamily, GeoSelector, Policy, PolicySubject},
+};
+
+/// Hypothetical operation of a binary (by inode).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Event {
+    /// Bind of a socket of the family (`AF_INET`, `AF_INET6`, `AF_PACKET` or
+    /// any other, which is always allowed) to the port. Binds to IPv4-mapped
+    /// IPv6 addresses are `AF_INET` binds.
+    SocketBind {
+        binprm_inode: u64,
+        family: u16,
+        port: u16,
+    },
+    /// Connect of a socket to the address and port. The port matters only
+    /// for protected addresses with a port and for port ranges.
+    SocketConnect {
+        binprm_inode: u64,
+        addr: IpAddr,
+        #[serde(default)]
+        port: u16,
+    },
+}
+
+/// Simulated decision about an event.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Verdict {
+    Allow,
+    /// Denied, with the reason the alert would carry.
+    Deny(Reason),
+}
+
+impl From<Action> for Verdict {
+    fn from(action: Action) -> Self {
+        match action {
+            Action::Allow => Verdict::Allow,
+            Action::Deny(reason) => Verdict::Deny(reason.into()),
+        }
+    }
+}
+
+/// Evaluates the policies against the events and returns a verdict for every
+/// event, in order.
+///
+/// Policies are applied in order, a later policy of a subject replaces an
+/// earlier one, as when adding them to the hooks. Policies of other hooks
+/// are ignored. Subjects are resolved to inodes, so their binaries have to
+/// exist. `socket_connect_geo` policies can select CIDRs only, since ASNs and
+/// countries need a CIDR database.
+pub fn simulate<I>(policies: I, events: &[Event]) -> Result<Vec<Verdict>, EbpfguardError>
+where
+    I: IntoIterator<Item = Policy>,
+{
+    simulate_with_resolver(&LocalInodeResolver, policies, events)
+}
+
+/// Like [`simulate`], resolving the subjects with the resolver instead of
+/// `stat`, e.g. the one set with
+/// [`PolicyManager::set_inode_resolver`](crate::PolicyManager::set_inode_resolver).
+pub fn simulate_with_resolver<I>(
+    resolver: &dyn InodeResolver,
+    policies: I,
+    events: &[Event],
+) -> Result<Vec<Verdict>, EbpfguardError>
+where
+    I: IntoIterator<Item = Policy>,
+{
+    let mut maps = Maps::default();
+    for policy in policies {
+        maps.add_policy(resolver, policy)?;
+    }
+
+    Ok(events
+        .iter()
+        .map(|event| maps.decide(event).into())
+        .collect())
+}
+
+/// Policy map entries of a single namespace, keyed by binary inode.
+#[derive(Default)]
+struct Maps {
+    allowed_bind: HashMap<u64, ebpf_policy::Ports>,
+    denied_bind: HashMap<u64, ebpf_policy::Ports>,
+    allowed_bind_v4: HashMap<u64, ebpf_policy::Ports>,
+    denied_bind_v4: HashMap<u64, ebpf_policy::Ports>,
+    allowed_bind_v6: HashMap<u64, ebpf_policy::Ports>,
+    denied_bind_v6: HashMap<u64, ebpf_policy::Ports>,
+    allowed_bind_packet: HashMap<u64, ()>,
+    denied_bind_packet: HashMap<u64, ()>,
+    allowed_connect_v4: HashMap<u64, ebpf_policy::Ipv4Addrs>,
+    denied_connect_v4: HashMap<u64, ebpf_policy::Ipv4Addrs>,
+    allowed_connect_v6: HashMap<u64, ebpf_policy::Ipv6Addrs>,
+    denied_connect_v6: HashMap<u64, ebpf_policy::Ipv6Addrs>,
+    allowed_connect_ports: HashMap<u64, ebpf_policy::PortRanges>,
+    denied_connect_ports: HashMap<u64, ebpf_policy::PortRanges>,
+    protected_connect_v4: HashMap<(u32, Option<u16>), ebpf_policy::Binaries>,
+    protected_connect_v6: HashMap<([u8; 16], Option<u16>), ebpf_policy::Binaries>,
+    denied_cidrs: Vec<(u64, Cidr)>,
+    metadata_cidrs: Vec<(u64, Cidr)>,
+}
+
+impl Maps {
+    fn add_policy(
+        &mut self,
+        resolver: &dyn InodeResolver,
+        policy: Policy,
+    ) -> Result<(), EbpfguardError> {
+        match policy {
+            Policy::SocketBind(policy) => {
+                let inode = resolve(resolver, policy.subject)?;
+                let (allowed, denied) = match policy.family {
+                    None => (&mut self.allowed_bind, &mut self.denied_bind),
+                    Some(BindFamily::Ipv4) => (&mut self.allowed_bind_v4, &mut self.denied_bind_v4),
+                    Some(BindFamily::Ipv6) => (&mut self.allowed_bind_v6, &mut self.denied_bind_v6),
+                };
+                allowed.insert(inode, policy.allow.into());
+                denied.insert(inode, policy.deny.into());
+            }
+            Policy::SocketBindPacket(policy) => {
+                let inode = resolve(resolver, policy.subject)?;
+                if policy.allow {
+                    self.allowed_bind_packet.insert(inode, ());
+                } else {
+                    self.denied_bind_packet.insert(inode, ());
+                }
+            }
+            Policy::SocketConnect(policy) => {
+                let inode = resolve(resolver, policy.subject)?;
+                let (allow_v4, allow_v6) = policy.allow.into_ebpf();
+                let (deny_v4, deny_v6) = policy.deny.into_ebpf();
+                self.allowed_connect_v4.insert(inode, allow_v4);
+                self.denied_connect_v4.insert(inode, deny_v4);
+                self.allowed_connect_v6.insert(inode, allow_v6);
+                self.denied_connect_v6.insert(inode, deny_v6);
+            }
+            Policy::SocketConnectGeo(policy) => {
+                let inode = resolve(resolver, policy.subject)?;
+                for selector in policy.deny {
+                    match selector {
+                        GeoSelector::Cidr(cidr) => self.denied_cidrs.push((inode, cidr)),
+                        _ => return Err(EbpfguardError::NoCidrDatabase),
+                    }
+                }
+            }
+            Policy::SocketConnectMetadata(policy) => {
+                let cidrs = policy.cidrs()?;
+                let inode = resolve(resolver, policy.subject)?;
+                // Replaces the previous policy of the subject, like the hook.
+                self.metadata_cidrs
+                    .retain(|(cidr_inode, _)| *cidr_inode != inode);
+                self.metadata_cidrs
+                    .extend(cidrs.into_iter().map(|cidr| (inode, cidr)));
+            }
+            Policy::SocketConnectPorts(policy) => {
+                let (allow, deny) = policy.to_ebpf()?;
+                let inode = resolve(resolver, policy.subject)?;
+                self.allowed_connect_ports.insert(inode, allow);
+                self.denied_connect_ports.insert(inode, deny);
+            }
+            Policy::SocketConnectProtected(policy) => {
+                let binaries = resolve_binaries(resolver, policy.allow)?;
+                match policy.addr {
+                    IpAddr::V4(addr) => {
+                        self.protected_connect_v4
+                            .insert((u32::from(addr), policy.port), binaries);
+                    }
+                    IpAddr::V6(addr) => {
+                        self.protected_connect_v6
+                            .insert((addr.octets(), policy.port), binaries);
+                    }
+                }
+            }
+            _ => {}
+        }
+
+        Ok(())
+    }
+
+    fn decide(&self, event: &Event) -> Action {
+        match *event {
+            Event::SocketBind {
+                binprm_inode,
+                family: family @ (AF_INET | AF_INET6),
+                port,
+            } => {
+                if decision::socket_bind_exempt(port, None) {
+                    return Action::Allow;
+                }
+                let wildcard = self.port_rules(INODE_WILDCARD, family);
+                let binary = match binprm_inode {
+                    INODE_WILDCARD => PortRules {
+                        allowed: None,
+                        denied: None,
+                    },
+                    inode => self.port_rules(inode, family),
+                };
+                match decision::socket_bind(
+                    Rules {
+                        wildcard: wildcard.allowed,
+                        binary: binary.allowed,
+                    },
+                    Rules {
+                        wildcard: wildcard.denied,
+                        binary: binary.denied,
+                    },
+                    port,
+                ) {
+                    Some(Action::Deny(REASON_DEFAULT_DENY)) => {
+                        let other = match family {
+                            AF_INET6 => AF_INET,
+                            _ => AF_INET6,
+                        };
+                        let wildcard = self.port_rules(INODE_WILDCARD, other);
+                        let binary = match binprm_inode {
+                            INODE_WILDCARD => PortRules {
+                                allowed: None,
+                                denied: None,
+                            },
+                            inode => self.port_rules(inode, other),
+                        };
+                        decision::socket_bind_default_deny(
+                            Rules {
+                                wildcard: wildcard.allowed,
+                                binary: binary.allowed,
+                            },
+                            Rules {
+                                wildcard: wildcard.denied,
+                                binary: binary.denied,
+                            },
+                            port,
+                        )
+                    }
+                    action => action.unwrap_or(Action::Allow),
+                }
+            }
+            Event::SocketBind {
+                binprm_inode,
+                family: AF_PACKET,
+                ..
+            } => decision::binary_rules(
+                rules(&self.allowed_bind_packet, binprm_inode),
+                rules(&self.denied_bind_packet, binprm_inode),
+            ),
+            Event::SocketBind { .. } => Action::Allow,
+            Event::SocketConnect {
+                binprm_inode,
+                addr,
+                port,
+            } => {
+                let action = match addr {
+                    IpAddr::V4(addr) => decision::socket_connect(
+                        binprm_inode,
+                        u32::from(addr),
+                        protected(&self.protected_connect_v4, u32::from(addr), port),
+                        cidr_rules(&self.metadata_cidrs, binprm_inode, IpAddr::V4(addr)),
+                        cidr_rules(&self.denied_cidrs, binprm_inode, IpAddr::V4(addr)),
+                        rules(&self.allowed_connect_v4, binprm_inode),
+                        rules(&self.denied_connect_v4, binprm_inode),
+                    ),
+                    IpAddr::V6(addr) => decision::socket_connect(
+                        binprm_inode,
+                        addr.octets(),
+                        protected(&self.protected_connect_v6, addr.octets(), port),
+                        cidr_rules(&self.metadata_cidrs, binprm_inode, IpAddr::V6(addr)),
+                        cidr_rules(&self.denied_cidrs, binprm_inode, IpAddr::V6(addr)),
+                        rules(&self.allowed_connect_v6, binprm_inode),
+                        rules(&self.denied_connect_v6, binprm_inode),
+                    ),
+                };
+                match action {
+                    // Port ranges are checked after the other rules allowed
+                    // the connect, like in the program.
+                    Action::Allow => decision::socket_connect_ports(
+                        rules(&self.allowed_connect_ports, binprm_inode),
+                        rules(&self.denied_connect_ports, binprm_inode),
+                        port,
+                    ),
+                    action => action,
+                }
+            }
+        }
+    }
+}
+
+/// Matches the address against the CIDRs, like a longest prefix match lookup
+/// of the wildcard and of the binary.
+fn cidr_rules(cidrs: &[(u64, Cidr)], binprm_inode: u64, addr: IpAddr) -> Rules<()> {
+    let host = Cidr::host(addr);
+    let matches = |inode| {
+        cidrs
+            .iter()
+            .any(|(cidr_inode, cidr)| *cidr_inode == inode && cidr.contains(&host))
+            .then_some(())
+    };
+    Rules {
+        wildcard: matches(INODE_WILDCARD),
+        binary: match binprm_inode {
+            INODE_WILDCARD => None,
+            inode => matches(inode),
+        },
+    }
+}
+
+/// Looks up the binaries allowed to connect to a protected address, with
+/// the port or without it. The key layout of a namespace allows only one of
+/// them (see [`KeyLayout`](crate::policy::KeyLayout)), so the lookups match
+/// what the kernel reads.
+fn protected<A: Eq + Hash>(
+    map: &HashMap<(A, Option<u16>), ebpf_policy::Binaries>,
+    addr: A,
+    port: u16,
+) -> Option<&ebpf_policy::Binaries> {
+    map.get(&(addr, Some(port)))
+        .or_else(|| map.get(&(addr, None)))
+}
+
+impl Maps {
+    /// Rules of the subject for binds of the family, as selected by the eBPF
+    /// program.
+    fn port_rules(&self, inode: u64, family: u16) -> PortRules<'_> {
+        let (allowed, denied) = match family {
+            AF_INET6 => (&self.allowed_bind_v6, &self.denied_bind_v6),
+            _ => (&self.allowed_bind_v4, &self.denied_bind_v4),
+        };
+        PortRules {
+            allowed: self.allowed_bind.get(&inode),
+            denied: self.denied_bind.get(&inode),
+        }
+        .scoped(PortRules {
+            allowed: allowed.get(&inode),
+            denied: denied.get(&inode),
+        })
+    }
+}
+
+/// Looks up the entries of the wildcard and of the binary. The kernel looks
+/// up a key of its own for a binary whose inode is [`INODE_WILDCARD`] (see
+/// [`InodeKey`](ebpf_policy::InodeKey)), which has no entries since policies
+/// can't be set for it.
+fn rules<T>(map: &HashMap<u64, T>, binprm_inode: u64) -> Rules<&T> {
+    Rules {
+        wildcard: map.get(&INODE_WILDCARD),
+        binary: match binprm_inode {
+            INODE_WILDCARD => None,
+            inode => map.get(&inode),
+        },
+    }
+}
+
+pub(crate) fn resolve(
+    resolver: &dyn InodeResolver,
+    subject: PolicySubject,
+) -> Result<u64, EbpfguardError> {
+    match subject {
+        PolicySubject::Binary(path) => fs::binary_inode(resolver, path),
+        PolicySubject::All => Ok(INODE_WILDCARD),
+    }
+}
+
+pub(crate) fn resolve_binaries(
+    resolver: &dyn InodeResolver,
+    paths: Vec<PathBuf>,
+) -> Result<ebpf_policy::Binaries, EbpfguardError> {
+    if paths.len() > ebpf_policy::MAX_BINARIES {
+        return Err(EbpfguardError::TooManyBinaries(ebpf_policy::MAX_BINARIES));
+    }
+
+    let mut binaries = [0; ebpf_policy::MAX_BINARIES];
+    for (i, path) in paths.into_iter().enumerate() {
+        binaries[i] = fs::binary_inode(resolver, path)?;
+    }
+
+    Ok(ebpf_policy::Binaries::new(binaries))
+}
+
+#[cfg(test)]
+mod test {
+    use std::net::{Ipv4Addr, Ipv6Addr};
+
+    use super::*;
+    use crate::policy::{
+        Addresses, PortRange, Ports, SocketBind, SocketConnect, SocketConnectMetadata,
+        SocketConnectPorts, SocketConnectProtected,
+    };
+
+    /// Inode of the test binary, which policies of the binary refer to.
+    fn binary() -> (PolicySubject, u64) {
+        let path = std::env::current_exe().unwrap();
+        let inode = LocalInodeResolver.resolve(&path).unwrap().inode;
+        (PolicySubject::Binary(path), inode)
+    }
+
+    fn bind(subject: PolicySubject, allow: Ports, deny: Ports) -> Policy {
+        Policy::SocketBind(SocketBind {
+            subject,
+            allow,
+            deny,
+            deny_options: vec![],
+            family: None,
+        })
+    }
+
+    fn bind_event(binprm_inode: u64, port: u16) -> Event {
+        Event::SocketBind {
+            binprm_inode,
+            family: AF_INET,
+            port,
+        }
+    }
+
+    // Same cases as `test_socket_bind_reasons` in the integration tests,
+    // which checks the alerts of the eBPF program.
+    #[test]
+    fn test_simulate_bind() {
+        let (subject, inode) = binary();
+        let other = inode + 1;
+        let cases = [
+            (
+                vec![bind(
+                    PolicySubject::All,
+                    Ports::Ports(vec![8585]),
+                    Ports::All,
+                )],
+                vec![
+                    (inode, 8586, Verdict::Deny(Reason::DefaultDeny)),
+                    (inode, 8585, Verdict::Allow),
+                ],
+            ),
+            (
+                vec![bind(
+                    PolicySubject::All,
+                    Ports::Ports(vec![8585]),
+                    Ports::Ports(vec![8587]),
+                )],
+                vec![
+                    (inode, 8587, Verdict::Deny(Reason::WildcardDenyListed)),
+                    (inode, 8588, Verdict::Allow),
+                ],
+            ),
+            (
+                vec![bind(PolicySubject::All, Ports::All, Ports::All)],
+                vec![(inode, 8588, Verdict::Deny(Reason::WildcardDenyAll))],
+            ),
+            (
+                vec![bind(
+                    PolicySubject::All,
+                    Ports::All,
+                    Ports::Ports(vec![8589]),
+                )],
+                vec![
+                    (inode, 8589, Verdict::Deny(Reason::WildcardDeny)),
+                    (inode, 8590, Verdict::Allow),
+                ],
+            ),
+            (
+                vec![
+                    bind(PolicySubject::All, Ports::All, Ports::Ports(vec![8589])),
+                    bind(subject, Ports::All, Ports::All),
+                ],
+                vec![
+                    (inode, 8590, Verdict::Deny(Reason::BinaryDenyAll)),
+                    (other, 8590, Verdict::Allow),
+                    // Port 0 is always allowed.
+                    (inode, 0, Verdict::Allow),
+                ],
+            ),
+        ];
+
+        for (policies, expected) in cases {
+            let events: Vec<_> = expected
+                .iter()
+                .map(|(inode, port, _)| bind_event(*inode, *port))
+                .collect();
+            let verdicts = simulate(policies, &events).unwrap();
+            let expected: Vec<_> = expected.into_iter().map(|(_, _, v)| v).collect();
+            assert_eq!(verdicts, expected);
+        }
+    }
+
+    #[test]
+    fn test_simulate_wildcard_inode() {
+        let protected = IpAddr::V4(Ipv4Addr::new(10, 0, 0, 2));
+        let policies = vec![
+            bind(PolicySubject::All, Ports::All, Ports::Ports(vec![8589])),
+            Policy::SocketConnectProtected(SocketConnectProtected {
+                addr: protected,
+                port: None,
+                allow: vec![std::env::current_exe().unwrap()],
+            }),
+        ];
+        // A binary whose inode is the sentinel gets the rules of all
+        // binaries as such, and isn't listed by the unused slots of the
+        // protected address.
+        let events = [
+            bind_event(INODE_WILDCARD, 8589),
+            Event::SocketConnect {
+                binprm_inode: INODE_WILDCARD,
+                addr: protected,
+                port: 80,
+            },
+        ];
+        assert_eq!(
+            simulate(policies, &events).unwrap(),
+            vec![
+                Verdict::Deny(Reason::WildcardDeny),
+                Verdict::Deny(Reason::Protected),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_simulate_bind_family() {
+        let (subject, inode) = binary();
+        let event = |family, port| Event::SocketBind {
+            binprm_inode: inode,
+            family,
+            port,
+        };
+        let scoped = |subject, allow, deny, family| {
+            Policy::SocketBind(SocketBind {
+                subject,
+                allow,
+                deny,
+                deny_options: vec![],
+                family: Some(family),
+            })
+        };
+
+        // Family-agnostic policies apply to both families.
+        let agnostic = || bind(PolicySubject::All, Ports::All, Ports::Ports(vec![8950]));
+        let events = [event(AF_INET, 8950), event(AF_INET6, 8950)];
+        assert_eq!(
+            simulate(vec![agnostic()], &events).unwrap(),
+            vec![Verdict::Deny(Reason::WildcardDeny); 2]
+        );
+
+        // A scoped policy replaces the agnostic one for its family only.
+        let policies = vec![
+            agnostic(),
+            scoped(
+                PolicySubject::All,
+                Ports::All,
+                Ports::Ports(vec![8951]),
+                BindFamily::Ipv6,
+            ),
+        ];
+        let events = [
+            event(AF_INET, 8950),
+            event(AF_INET6, 8950),
+            event(AF_INET6, 8951),
+            event(AF_INET, 8951),
+        ];
+        assert_eq!(
+            simulate(policies, &events).unwrap(),
+            vec![
+                Verdict::Deny(Reason::WildcardDeny),
+                Verdict::Allow,
+                Verdict::Deny(Reason::WildcardDeny),
+                Verdict::Allow,
+            ]
+        );
+
+        // An agnostic policy of the binary still overrides a scoped policy of
+        // all binaries.
+        let policies = vec![
+            scoped(
+                PolicySubject::All,
+                Ports::Ports(vec![8953]),
+                Ports::All,
+                BindFamily::Ipv4,
+            ),
+            bind(subject, Ports::All, Ports::Ports(vec![8954])),
+        ];
+        let events = [
+            event(AF_INET, 8952),
+            Event::SocketBind {
+                binprm_inode: inode + 1,
+                family: AF_INET,
+                port: 8952,
+            },
+            Event::SocketBind {
+                binprm_inode: inode + 1,
+                family: AF_INET6,
+                port: 8952,
+            },
+        ];
+        assert_eq!(
+            simulate(policies, &events).unwrap(),
+            vec![
+                Verdict::Allow,
+                Verdict::Deny(Reason::DefaultDeny),
+                Verdict::Allow,
+            ]
+        );
+
+        // A bind denied by default, which the rules for the other family
+        // allow, is told apart.
+        let policies = vec![
+            bind(PolicySubject::All, Ports::Ports(vec![8955]), Ports::All),
+            scoped(
+                binary().0,
+                Ports::Ports(vec![8956]),
+                Ports::All,
+                BindFamily::Ipv4,
+            ),
+        ];
+        let events = [
+            event(AF_INET, 8956),
+            event(AF_INET6, 8956),
+            event(AF_INET6, 8957),
+            Event::SocketBind {
+                binprm_inode: inode + 1,
+                family: AF_INET6,
+                port: 8956,
+            },
+        ];
+        assert_eq!(
+            simulate(policies, &events).unwrap(),
+            vec![
+                Verdict::Allow,
+                Verdict::Deny(Reason::FamilyMismatch),
+                Verdict::Deny(Reason::DefaultDeny),
+                Verdict::Deny(Reason::DefaultDeny),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_simulate_bind_packet() {
+        let (subject, inode) = binary();
+        let policies = vec![
+            Policy::SocketBindPacket(crate::policy::SocketBindPacket {
+                subject: PolicySubject::All,
+                allow: false,
+            }),
+            Policy::SocketBindPacket(crate::policy::SocketBindPacket {
+                subject,
+                allow: true,
+            }),
+        ];
+        let events = [
+            Event::SocketBind {
+                binprm_inode: inode,
+                family: AF_PACKET,
+                port: 0,
+            },
+            Event::SocketBind {
+                binprm_inode: inode + 1,
+                family: AF_PACKET,
+                port: 0,
+            },
+            // Other families are not checked.
+            Event::SocketBind {
+                binprm_inode: inode + 1,
+                family: 1,
+                port: 0,
+            },
+        ];
+        assert_eq!(
+            simulate(policies, &events).unwrap(),
+            vec![
+                Verdict::Allow,
+                Verdict::Deny(Reason::DefaultDeny),
+                Verdict::Allow
+            ]
+        );
+    }
+
+    #[test]
+    fn test_simulate_connect() {
+        let (subject, inode) = binary();
+        let denied = IpAddr::V4(Ipv4Addr::new(10, 0, 0, 1));
+        let protected = IpAddr::V4(Ipv4Addr::new(10, 0, 0, 2));
+        let geo = IpAddr::V4(Ipv4Addr::new(192, 0, 2, 1));
+        let allowed = IpAddr::V4(Ipv4Addr::new(10, 0, 0, 3));
+        let protected_port = IpAddr::V4(Ipv4Addr::new(10, 0, 0, 4));
+        let metadata = IpAddr::V4(Ipv4Addr::new(169, 254, 169, 254));
+        let link_local = IpAddr::V6(Ipv6Addr::new(0xfe80, 0, 0, 0, 0, 0, 0, 1));
+
+        let policies = vec![
+            Policy::SocketConnect(SocketConnect {
+                subject: PolicySubject::All,
+                allow: Addresses::All,
+                deny: Addresses::Addresses(vec![denied]),
+            }),
+            Policy::SocketConnectProtected(SocketConnectProtected {
+                addr: protected,
+                port: None,
+                allow: vec![std::env::current_exe().unwrap()],
+            }),
+            Policy::SocketConnectProtected(SocketConnectProtected {
+                addr: protected_port,
+                port: Some(5432),
+                allow: vec![std::env::current_exe().unwrap()],
+            }),
+            Policy::SocketConnectGeo(crate::policy::SocketConnectGeo {
+                subject,
+                deny: vec![GeoSelector::Cidr("192.0.2.0/24".parse().unwrap())],
+            }),
+            Policy::SocketConnectMetadata(SocketConnectMetadata::new(PolicySubject::All)),
+        ];
+        let connect = |binprm_inode, addr, port| Event::SocketConnect {
+            binprm_inode,
+            addr,
+            port,
+        };
+        let events = [
+            connect(inode, denied, 80),
+            connect(inode, protected, 80),
+            connect(inode + 1, protected, 80),
+            connect(inode, geo, 80),
+            connect(inode + 1, geo, 80),
+            connect(inode, allowed, 80),
+            connect(inode + 1, protected_port, 5432),
+            connect(inode + 1, protected_port, 80),
+            connect(inode, metadata, 80),
+            connect(inode, link_local, 80),
+        ];
+        assert_eq!(
+            simulate(policies, &events).unwrap(),
+            vec![
+                Verdict::Deny(Reason::WildcardDeny),
+                Verdict::Allow,
+                Verdict::Deny(Reason::Protected),
+                Verdict::Deny(Reason::BinaryDeny),
+                Verdict::Allow,
+                Verdict::Allow,
+                Verdict::Deny(Reason::Protected),
+                Verdict::Allow,
+                Verdict::Deny(Reason::Metadata),
+                Verdict::Deny(Reason::Metadata),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_simulate_connect_ports() {
+        let (subject, inode) = binary();
+        let other = inode + 1;
+        let addr = IpAddr::V4(Ipv4Addr::new(192, 0, 2, 20));
+        let policies = vec![
+            Policy::SocketConnectPorts(SocketConnectPorts {
+                subject: PolicySubject::All,
+                allow: vec![PortRange::port(6667)],
+                deny: vec!["6660-6669".parse().unwrap(), PortRange::port(6697)],
+            }),
+            Policy::SocketConnectPorts(SocketConnectPorts {
+                subject,
+                allow: vec![PortRange::port(6697)],
+                deny: vec![],
+            }),
+        ];
+        let connect = |binprm_inode, port| Event::SocketConnect {
+            binprm_inode,
+            addr,
+            port,
+        };
+        let events = [
+            connect(other, 6659),
+            connect(other, 6660),
+            connect(other, 6667),
+            connect(other, 6669),
+            connect(other, 6670),
+            connect(other, 6697),
+            // The allowed range of the binary takes precedence over the
+            // denied range of all binaries.
+            connect(inode, 6697),
+            connect(inode, 6660),
+        ];
+        assert_eq!(
+            simulate(policies, &events).unwrap(),
+            vec![
+                Verdict::Allow,
+                Verdict::Deny(Reason::WildcardDeny),
+                Verdict::Allow,
+                Verdict::Deny(Reason::WildcardDeny),
+                Verdict::Allow,
+                Verdict::Deny(Reason::WildcardDeny),
+                Verdict::Allow,
+                Verdict::Deny(Reason::WildcardDeny),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_simulate_geo_without_database() {
+        let policies = vec![Policy::SocketConnectGeo(crate::policy::SocketConnectGeo {
+            subject: PolicySubject::All,
+            deny: vec![GeoSelector::Asn(64496)],
+        })];
+        assert!(matches!(
+            simulate(policies, &[]),
+            Err(EbpfguardError::NoCidrDatabase)
+        ));
+    }
+}
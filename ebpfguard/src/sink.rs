@@ -0,0 +1,471 @@
+//! Export of alerts to a local collector over a Unix domain socket.
+//!
+//! A [`UnixSocketSink`] connects to the socket of the collector (e.g. a
+//! sidecar) and writes every record it's given as JSON, either one per line
+//! or prefixed with its length. Records are written by a background task, so
+//! sending never blocks the alert stream:
+//!
+//! * Records are buffered in a bounded queue while the collector is slow or
+//!   down. When the queue is full, new records are dropped and counted in
+//!   [`SinkStats::dropped`].
+//! * When the connection fails, the record being written is dropped and the
+//!   sink reconnects, retrying every [`SinkConfig::retry_interval`] until the
+//!   collector accepts connections again.
+//! * Records are not acknowledged, so records written just before the
+//!   collector went away can be lost without being counted.
+//!
+//! Alerts of different binaries can go to different sinks with a
+//! [`ChannelRouter`], by the channel set for the binaries with
+//! [`PolicyManager::set_alert_channel`](crate::PolicyManager::set_alert_channel).
+
+use std::{
+    collections::HashMap,
+    path::PathBuf,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
+
+use ebpfguard_common::alerts::CHANNEL_DEFAULT;
+use log::{debug, warn};
+use serde::Serialize;
+use tokio::{
+    io::AsyncWriteExt,
+    net::UnixStream,
+    sync::mpsc::{self, error::TrySendError, Receiver, Sender},
+    task::{self, JoinHandle},
+    time,
+};
+
+use crate::alerts::Alert;
+
+/// Framing of the records written to the socket.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    /// One JSON object per line.
+    JsonLines,
+    /// JSON objects prefixed with their length in bytes, as a big-endian
+    /// `u32`.
+    LengthPrefixed,
+}
+
+#[derive(Debug, Clone)]
+pub struct SinkConfig {
+    pub path: PathBuf,
+    pub format: Format,
+    /// Number of records buffered while the collector is slow or down.
+    pub buffer: usize,
+    /// Time between attempts to connect to the collector.
+    pub retry_interval: Duration,
+}
+
+impl SinkConfig {
+    /// Creates a configuration writing JSON lines to the socket at `path`,
+    /// with a buffer of 1024 records and a retry interval of one second.
+    pub fn new<P: Into<PathBuf>>(path: P) -> Self {
+        Self {
+            path: path.into(),
+            format: Format::JsonLines,
+            buffer: 1024,
+            retry_interval: Duration::from_secs(1),
+        }
+    }
+}
+
+/// Counters of a sink, since it was created.
+#[derive(Debug, Default)]
+pub struct SinkStats {
+    /// Records written to the socket.
+    pub sent: AtomicU64,
+    /// Records dropped because the buffer was full or their write failed.
+    pub dropped: AtomicU64,
+    /// Connections established to the collector, including the first one.
+    pub connections: AtomicU64,
+}
+
+/// Sink writing records to a Unix domain socket, see the [module](self)
+/// documentation.
+///
+/// # Example
+///
+/// ```no_run
+/// use ebpfguard::{
+///     sink::{SinkConfig, UnixSocketSink},
+///     PolicyManager,
+/// };
+///
+/// # #[tokio::main(flavor = "current_thread")]
+/// # async fn main() {
+/// let mut policy_manager = PolicyManager::with_default_path().unwrap();
+/// let mut socket_bind = policy_manager.attach_socket_bind().unwrap();
+///
+/// let sink = UnixSocketSink::new(SinkConfig::new("/run/collector.sock"));
+/// sink.forward(socket_bind.alerts().await.unwrap());
+/// # }
+/// ```
+#[derive(Clone)]
+pub struct UnixSocketSink {
+    tx: Sender<Vec<u8>>,
+    format: Format,
+    stats: Arc<SinkStats>,
+}
+
+impl UnixSocketSink {
+    /// Creates the sink and starts its background task, which connects to the
+    /// collector. It has to be called within a Tokio runtime.
+    pub fn new(config: SinkConfig) -> Self {
+        let (tx, rx) = mpsc::channel(config.buffer.max(1));
+        let stats = Arc::new(SinkStats::default());
+
+        task::spawn(write_records(config.clone(), rx, stats.clone()));
+
+        Self {
+            tx,
+            format: config.format,
+            stats,
+        }
+    }
+
+    /// Queues the record for writing. Records which can't be serialized and
+    /// records not fitting into the buffer are dropped.
+    pub fn send<T: Serialize>(&self, record: &T) {
+        let record = match encode(self.format, record) {
+            Ok(record) => record,
+            Err(e) => {
+                warn!("failed to serialize record: {e}");
+                self.stats.dropped.fetch_add(1, Ordering::Relaxed);
+                return;
+            }
+        };
+        match self.tx.try_send(record) {
+            Ok(()) => {}
+            Err(TrySendError::Full(_)) | Err(TrySendError::Closed(_)) => {
+                self.stats.dropped.fetch_add(1, Ordering::Relaxed);
+            }
+        }
+    }
+
+    /// Forwards all records received from `rx` (e.g. the alerts of a hook)
+    /// to the sink, until the sender is dropped.
+    pub fn forward<T>(&self, mut rx: Receiver<T>) -> JoinHandle<()>
+    where
+        T: Serialize + Send + 'static,
+    {
+        let sink = self.clone();
+        task::spawn(async move {
+            while let Some(record) = rx.recv().await {
+                sink.send(&record);
+            }
+        })
+    }
+
+    pub fn stats(&self) -> &SinkStats {
+        &self.stats
+    }
+}
+
+/// Router of alerts to sinks by their channel. Alerts of channels without a
+/// route, including [`CHANNEL_DEFAULT`], go to the primary sink. The kernel
+/// only stamps the channel into the alerts, the routes are kept here.
+///
+/// # Example
+///
+/// ```no_run
+/// use ebpfguard::{
+///     policy::PolicySubject,
+///     sink::{ChannelRouter, SinkConfig, UnixSocketSink},
+///     PolicyManager,
+/// };
+///
+/// # #[tokio::main(flavor = "current_thread")]
+/// # async fn main() {
+/// let mut policy_manager = PolicyManager::with_default_path().unwrap();
+/// policy_manager
+///     .set_alert_channel(&PolicySubject::Binary("/usr/sbin/payments".into()), 1)
+///     .unwrap();
+/// let mut socket_bind = policy_manager.attach_socket_bind().unwrap();
+///
+/// let mut router = ChannelRouter::new(UnixSocketSink::new(SinkConfig::new(
+///     "/run/collector.sock",
+/// )));
+/// router.route(1, UnixSocketSink::new(SinkConfig::new("/run/audit.sock")));
+/// router.forward(socket_bind.alerts().await.unwrap());
+/// # }
+/// ```
+#[derive(Clone)]
+pub struct ChannelRouter {
+    primary: UnixSocketSink,
+    routes: HashMap<u8, UnixSocketSink>,
+}
+
+impl ChannelRouter {
+    pub fn new(primary: UnixSocketSink) -> Self {
+        Self {
+            primary,
+            routes: HashMap::new(),
+        }
+    }
+
+    /// Routes alerts of the channel to the sink, replacing its previous
+    /// route. Routing [`CHANNEL_DEFAULT`] replaces the primary sink.
+    pub fn route(&mut self, channel: u8, sink: UnixSocketSink) {
+        if channel == CHANNEL_DEFAULT {
+            self.primary = sink;
+        } else {
+            self.routes.insert(channel, sink);
+        }
+    }
+
+    /// Returns the sink alerts of the channel go to.
+    pub fn sink(&self, channel: u8) -> &UnixSocketSink {
+        self.routes.get(&channel).unwrap_or(&self.primary)
+    }
+
+    /// Queues the alert for writing to the sink of its channel.
+    pub fn send<A: Alert>(&self, alert: &A) {
+        self.sink(alert.channel()).send(alert);
+    }
+
+    /// Forwards all alerts received from `rx` to the sinks of their channels,
+    /// until the sender is dropped.
+    pub fn forward<A>(&self, mut rx: Receiver<A>) -> JoinHandle<()>
+    where
+        A: Alert + Send + 'static,
+    {
+        let router = self.clone();
+        task::spawn(async move {
+            while let Some(alert) = rx.recv().await {
+                router.send(&alert);
+            }
+        })
+    }
+}
+
+fn encode<T: Serialize>(format: Format, record: &T) -> Result<Vec<u8>, serde_json::Error> {
+    let json = serde_json::to_vec(record)?;
+    Ok(match format {
+        Format::JsonLines => {
+            let mut line = json;
+            line.push(b'\n');
+            line
+        }
+        Format::LengthPrefixed => {
+            let mut framed = (json.len() as u32).to_be_bytes().to_vec();
+            framed.extend_from_slice(&json);
+            framed
+        }
+    })
+}
+
+/// Writes the queued records to the collector, reconnecting whenever the
+/// connection fails. Stops once all senders are dropped.
+async fn write_records(config: SinkConfig, mut rx: Receiver<Vec<u8>>, stats: Arc<SinkStats>) {
+    let mut stream: Option<UnixStream> = None;
+
+    while let Some(record) = rx.recv().await {
+        let conn = match stream.as_mut() {
+            Some(conn) => conn,
+            None => match connect(&config, &mut rx, &stats).await {
+                Some(conn) => stream.insert(conn),
+                None => return,
+            },
+        };
+
+        match conn.write_all(&record).await {
+            Ok(()) => {
+                stats.sent.fetch_add(1, Ordering::Relaxed);
+            }
+            Err(e) => {
+                debug!("collector connection failed: {e}");
+                stats.dropped.fetch_add(1, Ordering::Relaxed);
+                stream = None;
+            }
+        }
+    }
+}
+
+/// Connects to the collector, retrying until it succeeds. Returns `None` if
+/// all senders were dropped in the meantime and nothing is left to write.
+async fn connect(
+    config: &SinkConfig,
+    rx: &mut Receiver<Vec<u8>>,
+    stats: &SinkStats,
+) -> Option<UnixStream> {
+    loop {
+        match UnixStream::connect(&config.path).await {
+            Ok(stream) => {
+                stats.connections.fetch_add(1, Ordering::Relaxed);
+                return Some(stream);
+            }
+            Err(e) => debug!(
+                "failed to connect to collector at {}: {e}",
+                config.path.display()
+            ),
+        }
+        if rx.is_closed() && rx.is_empty() {
+            return None;
+        }
+        time::sleep(config.retry_interval).await;
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use tokio::{
+        io::{AsyncBufReadExt, AsyncReadExt, BufReader},
+        net::UnixListener,
+    };
+
+    use ebpfguard_common::alerts::{self as ebpf_alerts, Alert as _, REASON_BINARY_DENY};
+
+    use super::*;
+    use crate::alerts::SocketBind;
+
+    #[derive(Serialize)]
+    struct Record {
+        seq: u64,
+    }
+
+    fn socket_path(name: &str) -> PathBuf {
+        let path =
+            std::env::temp_dir().join(format!("ebpfguard-sink-{name}-{}.sock", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        path
+    }
+
+    fn config(path: &PathBuf, format: Format) -> SinkConfig {
+        SinkConfig {
+            format,
+            retry_interval: Duration::from_millis(10),
+            ..SinkConfig::new(path)
+        }
+    }
+
+    async fn read_line(reader: &mut BufReader<UnixStream>) -> serde_json::Value {
+        let mut line = String::new();
+        time::timeout(Duration::from_secs(5), reader.read_line(&mut line))
+            .await
+            .expect("timeout elapsed")
+            .unwrap();
+        serde_json::from_str(&line).unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_json_lines_reconnect() {
+        let path = socket_path("reconnect");
+        let listener = UnixListener::bind(&path).unwrap();
+        let sink = UnixSocketSink::new(config(&path, Format::JsonLines));
+
+        sink.send(&Record { seq: 1 });
+        let (conn, _) = listener.accept().await.unwrap();
+        let mut reader = BufReader::new(conn);
+        assert_eq!(read_line(&mut reader).await["seq"], 1);
+
+        // The collector restarts: writes fail until it listens again.
+        drop(reader);
+        drop(listener);
+        std::fs::remove_file(&path).unwrap();
+        for seq in 2..=3 {
+            sink.send(&Record { seq });
+            time::sleep(Duration::from_millis(50)).await;
+        }
+
+        let listener = UnixListener::bind(&path).unwrap();
+        sink.send(&Record { seq: 4 });
+        let (conn, _) = time::timeout(Duration::from_secs(5), listener.accept())
+            .await
+            .expect("timeout elapsed")
+            .unwrap();
+        let mut reader = BufReader::new(conn);
+        let seq = read_line(&mut reader).await["seq"].as_u64().unwrap();
+        assert!((3..=4).contains(&seq), "unexpected record {seq}");
+
+        assert!(sink.stats().connections.load(Ordering::Relaxed) >= 2);
+        assert!(sink.stats().dropped.load(Ordering::Relaxed) >= 1);
+        let _ = std::fs::remove_file(&path);
+    }
+
+    #[tokio::test]
+    async fn test_buffer_full_drops() {
+        let path = socket_path("full");
+        let sink = UnixSocketSink::new(SinkConfig {
+            buffer: 2,
+            ..config(&path, Format::JsonLines)
+        });
+
+        // Nobody listens, so records pile up in the buffer.
+        for seq in 0..10 {
+            sink.send(&Record { seq });
+        }
+        assert!(sink.stats().dropped.load(Ordering::Relaxed) >= 7);
+        assert_eq!(sink.stats().sent.load(Ordering::Relaxed), 0);
+    }
+
+    #[tokio::test]
+    async fn test_channel_routes() {
+        let primary_path = socket_path("primary");
+        let audit_path = socket_path("audit");
+        let primary_listener = UnixListener::bind(&primary_path).unwrap();
+        let audit_listener = UnixListener::bind(&audit_path).unwrap();
+
+        let mut router = ChannelRouter::new(UnixSocketSink::new(config(
+            &primary_path,
+            Format::JsonLines,
+        )));
+        router.route(
+            3,
+            UnixSocketSink::new(config(&audit_path, Format::JsonLines)),
+        );
+
+        // Channel 5 has no route, so it goes to the primary sink.
+        for (port, channel) in [(80, CHANNEL_DEFAULT), (443, 3), (8080, 5)] {
+            let mut alert = ebpf_alerts::SocketBind::new(1, 0, 0, REASON_BINARY_DENY, 42, 2, port);
+            alert.set_channel(channel);
+            router.send(&SocketBind::from(alert));
+        }
+
+        let (conn, _) = primary_listener.accept().await.unwrap();
+        let mut primary = BufReader::new(conn);
+        let (conn, _) = audit_listener.accept().await.unwrap();
+        let mut audit = BufReader::new(conn);
+
+        let alert = read_line(&mut primary).await;
+        assert_eq!(
+            (alert["port"].as_u64(), alert["channel"].as_u64()),
+            (Some(80), Some(0))
+        );
+        let alert = read_line(&mut primary).await;
+        assert_eq!(
+            (alert["port"].as_u64(), alert["channel"].as_u64()),
+            (Some(8080), Some(5))
+        );
+        let alert = read_line(&mut audit).await;
+        assert_eq!(
+            (alert["port"].as_u64(), alert["channel"].as_u64()),
+            (Some(443), Some(3))
+        );
+
+        assert_eq!(router.sink(5).stats().sent.load(Ordering::Relaxed), 2);
+        assert_eq!(router.sink(3).stats().sent.load(Ordering::Relaxed), 1);
+        let _ = std::fs::remove_file(&primary_path);
+        let _ = std::fs::remove_file(&audit_path);
+    }
+
+    #[tokio::test]
+    async fn test_length_prefixed() {
+        let path = socket_path("prefixed");
+        let listener = UnixListener::bind(&path).unwrap();
+        let sink = UnixSocketSink::new(config(&path, Format::LengthPrefixed));
+
+        sink.send(&Record { seq: 7 });
+        let (mut conn, _) = listener.accept().await.unwrap();
+        let len = conn.read_u32().await.unwrap() as usize;
+        let mut json = vec![0; len];
+        conn.read_exact(&mut json).await.unwrap();
+        let record: serde_json::Value = serde_json::from_slice(&json).unwrap();
+        assert_eq!(record["seq"], 7);
+        let _ = std::fs::remove_file(&path);
+    }
+}
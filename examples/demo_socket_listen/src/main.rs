@@ -47,6 +47,8 @@ async fn main() -> anyhow::Result<()> {
         subject: PolicySubject::All,
         allow: Ports::All,
         deny: Ports::Ports(opt.deny.clone()),
+        deny_options: vec![],
+        family: None,
     };
 
     socket_bind
@@ -0,0 +1,268 @@
+//! End-to-end tests of the enforcement by the running kernel: they load the
+//! programs, apply policies, perform real binds and check that they are
+//! allowed or denied and that the alerts arrive.
+//!
+//! The tests are ignored by default, since they need root (or `CAP_BPF`,
+//! `CAP_SYS_ADMIN` and `CAP_NET_ADMIN`), a kernel with the BPF LSM enabled
+//! and cgroup v2. Run them with the privileged runner of this crate, after
+//! building the eBPF programs:
+//!
+//! ```text
+//! $ cargo xtask build-ebpf
+//! $ cd tests
+//! $ cargo test --test enforcement -- --ignored --nocapture
+//! ```
+//!
+//! Each test checks these requirements first and fails with the one which
+//! isn't met. Failures of the checks themselves describe the state the
+//! decision depended on: whether the program is attached, the namespace of
+//! the cgroup of the test, the policies of the namespace and the decision
+//! simulated from them.
+
+use std::{
+    io,
+    net::TcpListener,
+    os::unix::fs::MetadataExt,
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+use ebpfguard::{
+    alerts::{self, Reason},
+    hooks::socket_bind::SocketBind,
+    messages::Hook,
+    policy::{self, Policy, PolicySubject, Ports},
+    simulate::{simulate, Event},
+    PolicyManager,
+};
+use tokio::sync::{mpsc::Receiver, Mutex, MutexGuard};
+
+/// Time to wait for an alert of a denied bind.
+const ALERT_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Time to wait for an alert which must not arrive.
+const NO_ALERT_TIMEOUT: Duration = Duration::from_millis(500);
+
+/// Serializes the tests, since the cgroup of the test process is assigned to
+/// the namespace of one test at a time.
+static CGROUP: Mutex<()> = Mutex::const_new(());
+
+/// Policy manager of a namespace used only by one test.
+struct Harness {
+    mgr: PolicyManager,
+    namespace: u32,
+    cgroup: PathBuf,
+    _guard: MutexGuard<'static, ()>,
+}
+
+impl Harness {
+    async fn new(namespace: u32) -> Self {
+        let guard = CGROUP.lock().await;
+        check_environment();
+
+        let mut mgr = PolicyManager::with_default_path().unwrap_or_else(|e| {
+            panic!("failed to load the eBPF programs (built with `cargo xtask build-ebpf`?): {e}")
+        });
+        mgr.set_namespace(namespace);
+        // Policies are pinned, so the ones of previous runs are still there.
+        mgr.clear_hook(Hook::SocketBind)
+            .expect("failed to clear the policies of previous runs");
+        Self {
+            mgr,
+            namespace,
+            cgroup: current_cgroup(),
+            _guard: guard,
+        }
+    }
+
+    /// Runs `f` with the test process in the namespace of the harness, so
+    /// that its policies apply. Outside of `f`, the process is in the default
+    /// namespace.
+    fn enforced<T, F: FnOnce() -> T>(&mut self, f: F) -> T {
+        self.mgr
+            .assign_cgroup(&self.cgroup, self.namespace)
+            .expect("failed to assign the cgroup of the test");
+        let res = f();
+        self.mgr
+            .assign_cgroup(&self.cgroup, 0)
+            .expect("failed to unassign the cgroup of the test");
+        res
+    }
+
+    /// Binds a TCP socket to the port of 127.0.0.1 in the namespace.
+    fn bind(&mut self, port: u16) -> io::Result<TcpListener> {
+        self.enforced(|| TcpListener::bind(("127.0.0.1", port)))
+    }
+
+    /// Describes the state which decided a bind to the port, for the message
+    /// of a failed check.
+    async fn diagnose(&mut self, socket_bind: &SocketBind, port: u16) -> String {
+        let mut lines = Vec::new();
+
+        let health = self.mgr.health();
+        match health.hooks.iter().find(|hook| hook.name == "socket_bind") {
+            Some(hook) => lines.push(format!(
+                "socket_bind program: attached {}, state {:?}, {} alert readers ({} stopped)",
+                hook.attached, hook.state, hook.alert_readers, hook.stopped_alert_readers
+            )),
+            None => lines.push("socket_bind program: not managed".to_owned()),
+        }
+
+        let cgroup_id = std::fs::metadata(&self.cgroup).map(|m| m.ino());
+        let assigned = self
+            .mgr
+            .raw_hash_map::<u64, u32>("POLICY_NAMESPACES")
+            .ok()
+            .and_then(|map| map.get(cgroup_id.as_ref().ok()?, 0).ok());
+        lines.push(format!(
+            "cgroup {} (ID {:?}) is assigned to namespace {:?} outside of binds, \
+             binds are made in namespace {}",
+            self.cgroup.display(),
+            cgroup_id,
+            assigned,
+            self.namespace
+        ));
+
+        let exe = std::env::current_exe().unwrap();
+        let binprm_inode = std::fs::metadata(&exe).map(|m| m.ino()).unwrap_or(0);
+        lines.push(format!("binary {} (inode {binprm_inode})", exe.display()));
+
+        match socket_bind.list_policies().await {
+            Ok(policies) => {
+                lines.push(format!("policies of namespace {}:", self.namespace));
+                for policy in &policies {
+                    lines.push(format!("  {policy:?}"));
+                }
+                let event = Event::SocketBind {
+                    binprm_inode,
+                    family: libc::AF_INET as u16,
+                    port,
+                };
+                let verdicts = simulate(policies.into_iter().map(Policy::SocketBind), &[event]);
+                lines.push(format!("simulated decision of port {port}: {verdicts:?}"));
+            }
+            Err(e) => lines.push(format!("failed to list the policies: {e}")),
+        }
+
+        lines.join("\n")
+    }
+}
+
+/// Panics with the requirement of the tests which the environment doesn't
+/// meet.
+fn check_environment() {
+    if unsafe { libc::geteuid() } != 0 {
+        panic!("the tests need root, run them with the runner of the tests crate (`sudo -E`)");
+    }
+    let lsms = std::fs::read_to_string("/sys/kernel/security/lsm")
+        .expect("failed to read the active LSMs, is securityfs mounted?");
+    if !lsms.trim().split(',').any(|lsm| lsm == "bpf") {
+        panic!(
+            "the BPF LSM is not enabled (active LSMs: {}), add `bpf` to the `lsm=` \
+             kernel parameter, e.g. with enable-bpf-lsm.py",
+            lsms.trim()
+        );
+    }
+    if !Path::new("/sys/fs/cgroup/cgroup.controllers").exists() {
+        panic!("cgroup v2 is not mounted on /sys/fs/cgroup");
+    }
+}
+
+fn current_cgroup() -> PathBuf {
+    let cgroup = std::fs::read_to_string("/proc/self/cgroup").unwrap();
+    let path = cgroup
+        .lines()
+        .find_map(|line| line.strip_prefix("0::"))
+        .expect("cgroup v2 is not mounted");
+    PathBuf::from("/sys/fs/cgroup").join(path.trim_start_matches('/'))
+}
+
+fn deny_port(port: u16) -> policy::SocketBind {
+    policy::SocketBind {
+        subject: PolicySubject::All,
+        allow: Ports::All,
+        deny: Ports::Ports(vec![port]),
+        deny_options: vec![],
+        family: None,
+    }
+}
+
+async fn recv_alert(
+    rx: &mut Receiver<alerts::SocketBind>,
+    timeout: Duration,
+) -> Option<alerts::SocketBind> {
+    tokio::time::timeout(timeout, rx.recv())
+        .await
+        .ok()
+        .map(|alert| alert.expect("alert channel closed"))
+}
+
+#[ignore = "needs root and the BPF LSM, see the module documentation"]
+#[tokio::test]
+async fn test_socket_bind_allow() {
+    let mut harness = Harness::new(21).await;
+    let mut socket_bind = harness.mgr.attach_socket_bind().unwrap();
+    let mut rx = socket_bind.alerts().await.unwrap();
+
+    socket_bind.add_policy(deny_port(8960)).await.unwrap();
+
+    let res = harness.bind(8961);
+    if let Err(e) = res {
+        panic!(
+            "bind to a port which no policy denies failed: {e}\n{}",
+            harness.diagnose(&socket_bind, 8961).await
+        );
+    }
+    if let Some(alert) = recv_alert(&mut rx, NO_ALERT_TIMEOUT).await {
+        panic!(
+            "allowed bind raised an alert: {alert:?}\n{}",
+            harness.diagnose(&socket_bind, 8961).await
+        );
+    }
+}
+
+#[ignore = "needs root and the BPF LSM, see the module documentation"]
+#[tokio::test]
+async fn test_socket_bind_deny() {
+    let mut harness = Harness::new(22).await;
+    let mut socket_bind = harness.mgr.attach_socket_bind().unwrap();
+
+    socket_bind.add_policy(deny_port(8962)).await.unwrap();
+
+    match harness.bind(8962) {
+        // The LSM denies with EPERM, other errors come from the bind itself
+        // (e.g. EADDRINUSE) and don't show that it was denied.
+        Err(e) if e.raw_os_error() == Some(libc::EPERM) => {}
+        res => panic!(
+            "bind to a denied port wasn't blocked by the program: {res:?}\n{}",
+            harness.diagnose(&socket_bind, 8962).await
+        ),
+    }
+}
+
+#[ignore = "needs root and the BPF LSM, see the module documentation"]
+#[tokio::test]
+async fn test_socket_bind_alert() {
+    let mut harness = Harness::new(23).await;
+    let mut socket_bind = harness.mgr.attach_socket_bind().unwrap();
+    let mut rx = socket_bind.alerts().await.unwrap();
+
+    socket_bind.add_policy(deny_port(8963)).await.unwrap();
+
+    harness
+        .bind(8963)
+        .expect_err("bind to a denied port should be denied");
+    let alert = match recv_alert(&mut rx, ALERT_TIMEOUT).await {
+        Some(alert) => alert,
+        None => panic!(
+            "no alert of the denied bind within {ALERT_TIMEOUT:?}\n{}",
+            harness.diagnose(&socket_bind, 8963).await
+        ),
+    };
+    println!("alert found: {:?}", alert);
+    assert_eq!(alert.pid, std::process::id());
+    assert_eq!(alert.namespace, harness.namespace);
+    assert_eq!(alert.port, 8963);
+    assert_eq!(alert.family, libc::AF_INET as u16);
+    assert_eq!(alert.reason, Reason::WildcardDeny);
+}
@@ -1,7 +1,31 @@
-use std::{net::IpAddr, os::unix::fs::MetadataExt, path::PathBuf, time::Duration};
+use std::{
+    io, mem,
+    net::IpAddr,
+    os::unix::fs::MetadataExt,
+    path::PathBuf,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
 
 use ebpfguard::{
-    policy::{Addresses, PolicySubject, SocketConnect},
+    alerts::{AlertBuffers, Gap, GapDetector, Reason},
+    error::EbpfguardError,
+    health::State,
+    inodes::InodeStatus,
+    messages::{Hook, Messages},
+    plan::Change,
+    policy::{
+        cidr::Cidr, geo::TextDatabase, Addresses, BindFamily, Bpf, ConnectRateLimit,
+        FileOpenProtected, GeoSelector, InodeCreate, KeyLayout, Paths, Policy, PolicySubject,
+        PortRange, Ports, RetryEscalation, SbMount, SbUmount, SocketBind, SocketBindComm,
+        SocketBindPacket, SocketConnect, SocketConnectGeo, SocketConnectMetadata,
+        SocketConnectPorts, SocketConnectProtected, SocketCreate, SocketFamily, SocketKind,
+        SocketKinds, SocketListen, SocketOption, SocketType, TaskFixSetgid, TaskFixSetuid, Verdict,
+    },
+    simulate::{simulate, Event, Verdict as SimulatedVerdict},
     PolicyManager,
 };
 use tokio::{net::TcpListener, sync::oneshot};
@@ -145,3 +169,2526 @@ async fn test_socket_connect_deny_one() {
         .expect("timeout elapsed")
         .expect("task panicked");
 }
+
+/// Creates a raw `AF_PACKET` socket and binds it to the loopback interface.
+fn bind_packet_socket() -> io::Result<()> {
+    let protocol = (libc::ETH_P_ALL as u16).to_be();
+    let fd = unsafe { libc::socket(libc::AF_PACKET, libc::SOCK_RAW, protocol as i32) };
+    if fd < 0 {
+        return Err(io::Error::last_os_error());
+    }
+
+    let mut addr: libc::sockaddr_ll = unsafe { mem::zeroed() };
+    addr.sll_family = libc::AF_PACKET as u16;
+    addr.sll_protocol = protocol;
+    addr.sll_ifindex = 1;
+
+    let ret = unsafe {
+        libc::bind(
+            fd,
+            &addr as *const libc::sockaddr_ll as *const libc::sockaddr,
+            mem::size_of::<libc::sockaddr_ll>() as u32,
+        )
+    };
+    let res = if ret < 0 {
+        Err(io::Error::last_os_error())
+    } else {
+        Ok(())
+    };
+    unsafe { libc::close(fd) };
+
+    res
+}
+
+#[tokio::test]
+async fn test_socket_bind_packet_deny_all() {
+    let mut mgr: PolicyManager = PolicyManager::with_default_path().unwrap();
+
+    let mut socket_bind = mgr.attach_socket_bind().unwrap();
+
+    let mut rx = socket_bind.alerts().await.unwrap();
+
+    println!("registering deny policy");
+    socket_bind
+        .add_packet_policy(SocketBindPacket {
+            subject: PolicySubject::All,
+            allow: false,
+        })
+        .await
+        .unwrap();
+
+    let err = bind_packet_socket().expect_err("packet socket bind should be denied");
+    assert_eq!(err.raw_os_error(), Some(libc::EPERM));
+
+    let alert = tokio::time::timeout(Duration::from_secs(5), rx.recv())
+        .await
+        .expect("timeout elapsed")
+        .expect("alert channel closed");
+    println!("alert found: {:?}", alert);
+    assert_eq!(alert.family, libc::AF_PACKET as u16);
+}
+
+#[tokio::test]
+async fn test_file_open_protected() {
+    let dir = PathBuf::from("/tmp/ebpfguard-test-file-open-protected");
+    tokio::fs::create_dir_all(&dir)
+        .await
+        .expect("failed to create test directory");
+
+    let secret = dir.join("secret");
+    tokio::fs::write(&secret, "s3cr3t")
+        .await
+        .expect("failed to write secret file");
+
+    let callers = ["cat1", "cat2", "cat3"].map(|name| dir.join(name));
+    for caller in callers.iter() {
+        tokio::fs::copy("/usr/bin/cat", caller)
+            .await
+            .expect("failed to make cat copy");
+    }
+
+    let mut mgr: PolicyManager = PolicyManager::with_default_path().unwrap();
+
+    let mut file_open = mgr.attach_file_open().unwrap();
+
+    println!("registering protected file policy");
+    file_open
+        .add_protected_policy(FileOpenProtected {
+            path: secret.clone(),
+            allow: vec![callers[0].clone(), callers[1].clone()],
+        })
+        .await
+        .unwrap();
+    let policies = file_open.list_protected_policies().await.unwrap();
+    let policy = policies
+        .iter()
+        .find(|policy| policy.path == secret)
+        .expect("protected file should be listed by its path");
+    assert_eq!(policy.allow, callers[..2]);
+
+    for caller in callers[..2].iter() {
+        let cmd = tokio::process::Command::new(caller)
+            .arg(&secret)
+            .output()
+            .await
+            .expect("unexpected execution failure");
+        assert!(cmd.status.success(), "{caller:?} should be allowed");
+    }
+
+    let cmd = tokio::process::Command::new(&callers[2])
+        .arg(&secret)
+        .output()
+        .await
+        .expect("unexpected execution failure");
+    assert!(!cmd.status.success(), "{:?} should be denied", callers[2]);
+}
+
+#[tokio::test]
+async fn test_inode_create() {
+    let dir = PathBuf::from("/tmp/ebpfguard-test-inode-create");
+    let protected = dir.join("protected");
+    let nested = protected.join("nested");
+    let _ = tokio::fs::remove_dir_all(&dir).await;
+    tokio::fs::create_dir_all(&nested)
+        .await
+        .expect("failed to create test directories");
+
+    let installer = dir.join("touch");
+    tokio::fs::copy("/usr/bin/touch", &installer)
+        .await
+        .expect("failed to make touch copy");
+
+    let mut mgr: PolicyManager = PolicyManager::with_default_path().unwrap();
+
+    let mut inode_create = mgr.attach_inode_create().unwrap();
+
+    let mut rx = inode_create.alerts().await.unwrap();
+
+    inode_create
+        .add_policy(InodeCreate {
+            subject: PolicySubject::All,
+            allow: Paths::All,
+            deny: Paths::Paths(vec![protected.clone()]),
+        })
+        .await
+        .unwrap();
+    inode_create
+        .add_policy(InodeCreate {
+            subject: PolicySubject::Binary(installer.clone()),
+            allow: Paths::Paths(vec![protected.clone()]),
+            deny: Paths::Paths(vec![]),
+        })
+        .await
+        .unwrap();
+
+    for parent in [&protected, &nested] {
+        let err = std::fs::File::create(parent.join("dropper"))
+            .expect_err("file creation should be denied");
+        assert_eq!(err.raw_os_error(), Some(libc::EPERM));
+
+        let alert = tokio::time::timeout(Duration::from_secs(5), rx.recv())
+            .await
+            .expect("timeout elapsed")
+            .expect("alert channel closed");
+        println!("alert found: {alert:?}");
+        let parent_inode = std::fs::metadata(parent).unwrap().ino();
+        assert_eq!(alert.dir, PathBuf::from(parent_inode.to_string()));
+        assert_eq!(alert.mode & libc::S_IFMT as u16, libc::S_IFREG as u16);
+        assert_eq!(alert.reason, Reason::WildcardDeny);
+    }
+
+    std::fs::File::create(dir.join("allowed")).expect("file creation should be allowed");
+
+    let cmd = tokio::process::Command::new(&installer)
+        .arg(protected.join("installed"))
+        .output()
+        .await
+        .expect("unexpected execution failure");
+    assert!(cmd.status.success(), "{installer:?} should be allowed");
+}
+
+#[tokio::test]
+async fn test_socket_bind_escalation() {
+    let mut mgr: PolicyManager = PolicyManager::with_default_path().unwrap();
+
+    let mut socket_bind = mgr.attach_socket_bind().unwrap();
+
+    println!("registering verdict callback");
+    socket_bind
+        .register_verdict_callback(|escalation| {
+            println!("escalation found: {:?}", escalation);
+            if escalation.port == 8181 {
+                Verdict::Deny
+            } else {
+                Verdict::Allow
+            }
+        })
+        .await
+        .unwrap();
+    socket_bind
+        .escalate(PolicySubject::All, Verdict::Allow)
+        .await
+        .unwrap();
+
+    // The first bind is decided by the fallback verdict.
+    drop(std::net::TcpListener::bind("127.0.0.1:8181").expect("fallback should allow"));
+
+    // Subsequent binds get the verdict of the callback, once it's stored.
+    let mut denied = false;
+    for _ in 0..50 {
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        if std::net::TcpListener::bind("127.0.0.1:8181").is_err() {
+            denied = true;
+            break;
+        }
+    }
+    assert!(denied, "user space verdict was not applied");
+
+    drop(std::net::TcpListener::bind("127.0.0.1:8182").expect("other ports should be allowed"));
+}
+
+/// Returns the path of the cgroup (v2) of the current process.
+fn current_cgroup() -> PathBuf {
+    let cgroup = std::fs::read_to_string("/proc/self/cgroup").unwrap();
+    let path = cgroup
+        .lines()
+        .find_map(|line| line.strip_prefix("0::"))
+        .expect("cgroup v2 is not mounted");
+    PathBuf::from("/sys/fs/cgroup").join(path.trim_start_matches('/'))
+}
+
+#[tokio::test]
+async fn test_namespaces() {
+    let mut mgr: PolicyManager = PolicyManager::with_default_path().unwrap();
+
+    mgr.set_namespace(1);
+    let mut socket_bind = mgr.attach_socket_bind().unwrap();
+
+    println!("registering deny policy in namespace 1");
+    socket_bind
+        .add_packet_policy(SocketBindPacket {
+            subject: PolicySubject::All,
+            allow: false,
+        })
+        .await
+        .unwrap();
+
+    bind_packet_socket().expect("namespace 1 policy should not affect namespace 0");
+
+    let cgroup = current_cgroup();
+    mgr.assign_cgroup(&cgroup, 1).unwrap();
+    let res = bind_packet_socket();
+    mgr.assign_cgroup(&cgroup, 0).unwrap();
+
+    let err = res.expect_err("packet socket bind should be denied in namespace 1");
+    assert_eq!(err.raw_os_error(), Some(libc::EPERM));
+}
+
+/// Binds a TCP socket to the given loopback port, then starts listening on it.
+/// Returns the result of each step.
+fn bind_and_listen(port: u16) -> (io::Result<()>, io::Result<()>) {
+    let fd = unsafe { libc::socket(libc::AF_INET, libc::SOCK_STREAM, 0) };
+    assert!(fd >= 0, "failed to create socket");
+
+    let mut addr: libc::sockaddr_in = unsafe { mem::zeroed() };
+    addr.sin_family = libc::AF_INET as u16;
+    addr.sin_port = port.to_be();
+    addr.sin_addr.s_addr = u32::from_be_bytes([127, 0, 0, 1]).to_be();
+
+    let ret = unsafe {
+        libc::bind(
+            fd,
+            &addr as *const libc::sockaddr_in as *const libc::sockaddr,
+            mem::size_of::<libc::sockaddr_in>() as u32,
+        )
+    };
+    let bind = if ret < 0 {
+        Err(io::Error::last_os_error())
+    } else {
+        Ok(())
+    };
+    let listen = if unsafe { libc::listen(fd, 1) } < 0 {
+        Err(io::Error::last_os_error())
+    } else {
+        Ok(())
+    };
+    unsafe { libc::close(fd) };
+
+    (bind, listen)
+}
+
+#[tokio::test]
+async fn test_socket_listen_deny_bind_allow() {
+    let mut mgr: PolicyManager = PolicyManager::with_default_path().unwrap();
+
+    let _socket_bind = mgr.attach_socket_bind().unwrap();
+    let mut socket_listen = mgr.attach_socket_listen().unwrap();
+
+    let mut rx = socket_listen.alerts().await.unwrap();
+
+    println!("registering deny policy");
+    socket_listen
+        .add_policy(SocketListen {
+            subject: PolicySubject::All,
+            allow: Ports::All,
+            deny: Ports::Ports(vec![8383]),
+            deny_options: vec![],
+        })
+        .await
+        .unwrap();
+
+    let (bind, listen) = bind_and_listen(8383);
+    bind.expect("bind should be allowed");
+    let err = listen.expect_err("listen should be denied");
+    assert_eq!(err.raw_os_error(), Some(libc::EPERM));
+
+    let alert = tokio::time::timeout(Duration::from_secs(5), rx.recv())
+        .await
+        .expect("timeout elapsed")
+        .expect("alert channel closed");
+    println!("alert found: {:?}", alert);
+    assert_eq!(alert.port, 8383);
+
+    let (bind, listen) = bind_and_listen(8384);
+    bind.expect("bind should be allowed");
+    listen.expect("listen on other ports should be allowed");
+}
+
+#[tokio::test]
+async fn test_alert_sessions() {
+    let mut mgr: PolicyManager = PolicyManager::with_default_path().unwrap();
+
+    let _bprm_check_security = mgr.attach_bprm_check_security().unwrap();
+    let mut socket_connect = mgr.attach_socket_connect().unwrap();
+
+    let mut rx = socket_connect.alerts().await.unwrap();
+
+    println!("registering deny policy");
+    socket_connect
+        .add_policy(SocketConnect {
+            subject: PolicySubject::All,
+            allow: Addresses::All,
+            deny: Addresses::Addresses(vec![IpAddr::from([127, 1, 2, 4])]),
+        })
+        .await
+        .unwrap();
+
+    let mut sessions = Vec::new();
+    for _ in 0..2 {
+        let cmd = tokio::process::Command::new("/usr/bin/nc")
+            .args(["-w", "1", "127.1.2.4", "8080"])
+            .output()
+            .await
+            .expect("unexpected execution failure");
+        assert!(!cmd.status.success(), "process should fail");
+
+        let alert = tokio::time::timeout(Duration::from_secs(5), rx.recv())
+            .await
+            .expect("timeout elapsed")
+            .expect("alert channel closed");
+        println!("alert found: {:?}", alert);
+        sessions.push(alert.session);
+    }
+
+    assert_ne!(sessions[0], 0, "session should be started on exec");
+    assert_ne!(
+        sessions[0], sessions[1],
+        "each exec should start a new session"
+    );
+}
+
+#[tokio::test]
+async fn test_socket_bind_reasons() {
+    let mut mgr: PolicyManager = PolicyManager::with_default_path().unwrap();
+
+    let mut socket_bind = mgr.attach_socket_bind().unwrap();
+
+    let mut rx = socket_bind.alerts().await.unwrap();
+
+    let binary = || PolicySubject::Binary(std::env::current_exe().unwrap());
+    let cases = [
+        (
+            PolicySubject::All,
+            Ports::Ports(vec![8585]),
+            Ports::All,
+            8586,
+            Reason::DefaultDeny,
+        ),
+        (
+            PolicySubject::All,
+            Ports::Ports(vec![8585]),
+            Ports::Ports(vec![8587]),
+            8587,
+            Reason::WildcardDenyListed,
+        ),
+        (
+            PolicySubject::All,
+            Ports::All,
+            Ports::All,
+            8588,
+            Reason::WildcardDenyAll,
+        ),
+        (
+            PolicySubject::All,
+            Ports::All,
+            Ports::Ports(vec![8589]),
+            8589,
+            Reason::WildcardDeny,
+        ),
+        (
+            binary(),
+            Ports::All,
+            Ports::All,
+            8590,
+            Reason::BinaryDenyAll,
+        ),
+        (
+            binary(),
+            Ports::Ports(vec![8585]),
+            Ports::Ports(vec![8591]),
+            8591,
+            Reason::BinaryDeny,
+        ),
+    ];
+
+    for (subject, allow, deny, port, reason) in cases {
+        println!("registering policy for {:?}", reason);
+        socket_bind
+            .add_policy(SocketBind {
+                subject,
+                allow,
+                deny,
+                deny_options: vec![],
+                family: None,
+            })
+            .await
+            .unwrap();
+
+        std::net::TcpListener::bind(("127.0.0.1", port)).expect_err("bind should be denied");
+
+        let alert = tokio::time::timeout(Duration::from_secs(5), rx.recv())
+            .await
+            .expect("timeout elapsed")
+            .expect("alert channel closed");
+        println!("alert found: {:?}", alert);
+        assert_eq!(alert.port, port);
+        assert_eq!(alert.reason, reason);
+    }
+}
+
+#[tokio::test]
+async fn test_upgrade_pinned_links() {
+    let links = PathBuf::from("/sys/fs/bpf/ebpfguard_test_links");
+    if links.exists() {
+        std::fs::remove_dir_all(&links).unwrap();
+    }
+
+    let mut mgr: PolicyManager = PolicyManager::with_default_path().unwrap();
+    mgr.pin_links(&links).unwrap();
+    let mut socket_bind = mgr.attach_socket_bind().unwrap();
+
+    println!("registering deny policy");
+    socket_bind
+        .add_policy(SocketBind {
+            subject: PolicySubject::All,
+            allow: Ports::All,
+            deny: Ports::Ports(vec![8686]),
+            deny_options: vec![],
+            family: None,
+        })
+        .await
+        .unwrap();
+    drop(socket_bind);
+    drop(mgr);
+
+    std::net::TcpListener::bind("127.0.0.1:8686").expect_err("pinned program should stay attached");
+
+    let stop = Arc::new(AtomicBool::new(false));
+    let binder = std::thread::spawn({
+        let stop = stop.clone();
+        move || {
+            let (mut attempts, mut allowed) = (0, 0);
+            while !stop.load(Ordering::Relaxed) {
+                if std::net::TcpListener::bind("127.0.0.1:8686").is_ok() {
+                    allowed += 1;
+                }
+                attempts += 1;
+            }
+            (attempts, allowed)
+        }
+    });
+
+    println!("upgrading programs");
+    let mut mgr = PolicyManager::new(PolicyManager::DEFAULT_BPFFS_MAPS_PATH).unwrap();
+    mgr.pin_links(&links).unwrap();
+    mgr.upgrade().unwrap();
+
+    stop.store(true, Ordering::Relaxed);
+    let (attempts, allowed) = binder.join().unwrap();
+    println!("binds during upgrade: {attempts}, allowed: {allowed}");
+    assert!(attempts > 0);
+    assert_eq!(allowed, 0, "binds slipped through during the upgrade");
+
+    std::net::TcpListener::bind("127.0.0.1:8686")
+        .expect_err("upgraded program should enforce the policy");
+
+    std::fs::remove_dir_all(&links).unwrap();
+    drop(std::net::TcpListener::bind("127.0.0.1:8686").expect("removing pins should detach"));
+}
+
+#[tokio::test]
+async fn test_socket_bind_cache_invalidation() {
+    let mut mgr: PolicyManager = PolicyManager::with_default_path().unwrap();
+
+    let mut socket_bind = mgr.attach_socket_bind().unwrap();
+
+    println!("registering allow policy");
+    socket_bind
+        .add_policy(SocketBind {
+            subject: PolicySubject::All,
+            allow: Ports::All,
+            deny: Ports::Ports(vec![8788]),
+            deny_options: vec![],
+            family: None,
+        })
+        .await
+        .unwrap();
+
+    // Populate the cache.
+    for _ in 0..10 {
+        drop(std::net::TcpListener::bind("127.0.0.1:8787").expect("bind should be allowed"));
+    }
+
+    println!("registering deny policy");
+    socket_bind
+        .add_policy(SocketBind {
+            subject: PolicySubject::All,
+            allow: Ports::All,
+            deny: Ports::Ports(vec![8787, 8788]),
+            deny_options: vec![],
+            family: None,
+        })
+        .await
+        .unwrap();
+
+    for _ in 0..10 {
+        std::net::TcpListener::bind("127.0.0.1:8787")
+            .expect_err("cached allow should be invalidated");
+    }
+
+    println!("registering allow policy again");
+    socket_bind
+        .add_policy(SocketBind {
+            subject: PolicySubject::All,
+            allow: Ports::All,
+            deny: Ports::Ports(vec![8788]),
+            deny_options: vec![],
+            family: None,
+        })
+        .await
+        .unwrap();
+
+    drop(std::net::TcpListener::bind("127.0.0.1:8787").expect("bind should be allowed again"));
+}
+
+/// Binds a TCP socket to the given loopback port from a thread with the
+/// given name.
+fn bind_in_thread(name: &str, port: u16) -> io::Result<()> {
+    std::thread::Builder::new()
+        .name(name.to_owned())
+        .spawn(move || std::net::TcpListener::bind(("127.0.0.1", port)).map(drop))
+        .unwrap()
+        .join()
+        .unwrap()
+}
+
+#[tokio::test]
+async fn test_socket_bind_comm() {
+    let mut mgr: PolicyManager = PolicyManager::with_default_path().unwrap();
+
+    mgr.set_namespace(3);
+    let mut socket_bind = mgr.attach_socket_bind().unwrap();
+    let mut rx = socket_bind.alerts().await.unwrap();
+
+    println!("registering command name policy");
+    socket_bind
+        .add_policy(SocketBind {
+            subject: PolicySubject::All,
+            allow: Ports::All,
+            deny: Ports::Ports(vec![8599]),
+            deny_options: vec![],
+            family: None,
+        })
+        .await
+        .unwrap();
+    socket_bind
+        .add_comm_policy(SocketBindComm {
+            comm: "ebpfguard-comm-*".parse().unwrap(),
+            allow: Ports::Ports(vec![8585]),
+            deny: Ports::Ports(vec![8600]),
+        })
+        .unwrap();
+    let policies = socket_bind.list_comm_policies().unwrap();
+    assert_eq!(policies.len(), 1);
+    assert_eq!(policies[0].comm.to_string(), "ebpfguard-comm-*");
+
+    let cgroup = current_cgroup();
+    mgr.assign_cgroup(&cgroup, 3).unwrap();
+    // The kernel truncates the name to its first 15 bytes, which the
+    // pattern matches exactly.
+    let denied = bind_in_thread("ebpfguard-comm-truncated", 8600);
+    let other = bind_in_thread("ebpfguard-other", 8600);
+    mgr.assign_cgroup(&cgroup, 0).unwrap();
+
+    let err = denied.expect_err("bind of the matching thread should be denied");
+    assert_eq!(err.raw_os_error(), Some(libc::EPERM));
+    let alert = tokio::time::timeout(Duration::from_secs(5), rx.recv())
+        .await
+        .expect("timeout elapsed")
+        .expect("alert channel closed");
+    println!("alert found: {:?}", alert);
+    assert_eq!(alert.port, 8600);
+    assert_eq!(alert.reason, Reason::BinaryDeny);
+
+    other.expect("bind of another thread should be allowed");
+}
+
+/// Binds a TCP socket to the given loopback port, optionally with
+/// `SO_REUSEPORT` set.
+fn bind_with_reuseport(port: u16, reuseport: bool) -> io::Result<()> {
+    let fd = unsafe { libc::socket(libc::AF_INET, libc::SOCK_STREAM, 0) };
+    assert!(fd >= 0, "failed to create socket");
+
+    if reuseport {
+        let enable: libc::c_int = 1;
+        let ret = unsafe {
+            libc::setsockopt(
+                fd,
+                libc::SOL_SOCKET,
+                libc::SO_REUSEPORT,
+                &enable as *const libc::c_int as *const libc::c_void,
+                mem::size_of::<libc::c_int>() as u32,
+            )
+        };
+        assert_eq!(ret, 0, "failed to set SO_REUSEPORT");
+    }
+
+    let mut addr: libc::sockaddr_in = unsafe { mem::zeroed() };
+    addr.sin_family = libc::AF_INET as u16;
+    addr.sin_port = port.to_be();
+    addr.sin_addr.s_addr = u32::from_be_bytes([127, 0, 0, 1]).to_be();
+
+    let ret = unsafe {
+        libc::bind(
+            fd,
+            &addr as *const libc::sockaddr_in as *const libc::sockaddr,
+            mem::size_of::<libc::sockaddr_in>() as u32,
+        )
+    };
+    let res = if ret < 0 {
+        Err(io::Error::last_os_error())
+    } else {
+        Ok(())
+    };
+    unsafe { libc::close(fd) };
+
+    res
+}
+
+#[tokio::test]
+async fn test_socket_bind_deny_options() {
+    let mut mgr: PolicyManager = PolicyManager::with_default_path().unwrap();
+
+    let mut socket_bind = mgr.attach_socket_bind().unwrap();
+
+    let mut rx = socket_bind.alerts().await.unwrap();
+
+    println!("registering policy denying SO_REUSEPORT");
+    socket_bind
+        .add_policy(SocketBind {
+            subject: PolicySubject::All,
+            allow: Ports::All,
+            deny: Ports::Ports(vec![8890]),
+            deny_options: vec![SocketOption::ReusePort],
+            family: None,
+        })
+        .await
+        .unwrap();
+
+    bind_with_reuseport(8889, false).expect("bind without SO_REUSEPORT should be allowed");
+
+    // Hits the cache of the previous bind, options are still checked.
+    let err = bind_with_reuseport(8889, true).expect_err("bind with SO_REUSEPORT should be denied");
+    assert_eq!(err.raw_os_error(), Some(libc::EPERM));
+
+    let alert = tokio::time::timeout(Duration::from_secs(5), rx.recv())
+        .await
+        .expect("timeout elapsed")
+        .expect("alert channel closed");
+    println!("alert found: {:?}", alert);
+    assert_eq!(alert.port, 8889);
+    assert_eq!(alert.reason, Reason::SocketOption);
+}
+
+#[tokio::test]
+async fn test_alert_sequence_gaps() {
+    let mut mgr: PolicyManager = PolicyManager::with_default_path().unwrap();
+
+    let mut socket_bind = mgr.attach_socket_bind().unwrap();
+
+    let mut rx = socket_bind.alerts().await.unwrap();
+
+    println!("registering deny policy");
+    socket_bind
+        .add_policy(SocketBind {
+            subject: PolicySubject::All,
+            allow: Ports::All,
+            deny: Ports::Ports(vec![8991]),
+            deny_options: vec![],
+            family: None,
+        })
+        .await
+        .unwrap();
+
+    // Don't receive while binding, so the channel and then the perf buffer
+    // fill up and the kernel drops alerts.
+    for _ in 0..20000 {
+        std::net::TcpListener::bind("127.0.0.1:8991").expect_err("bind should be denied");
+    }
+
+    let mut gaps = GapDetector::new();
+    let mut missed = 0;
+    let mut received = 0;
+    while let Ok(Some(alert)) = tokio::time::timeout(Duration::from_secs(1), rx.recv()).await {
+        received += 1;
+        match gaps.observe(alert.seq) {
+            None => {}
+            Some(Gap::Missed { count, .. }) => missed += count,
+            Some(Gap::Restarted) => panic!("sequence went back after {}", gaps.cursor()),
+        }
+    }
+    println!("received: {received}, missed: {missed}");
+
+    assert!(received > 0);
+    assert!(missed > 0, "dropped alerts should be reported as a gap");
+    assert_eq!(gaps.cursor(), received + missed);
+}
+
+#[tokio::test]
+async fn test_socket_connect_geo() {
+    let database = std::env::temp_dir().join("ebpfguard-test-geo");
+    std::fs::write(
+        &database,
+        "127.1.0.0/17 64512 ZZ\n127.1.128.0/17 64512 ZZ\n127.2.0.0/16 64513 ZZ\n",
+    )
+    .unwrap();
+
+    let mut mgr: PolicyManager = PolicyManager::with_default_path().unwrap();
+
+    let mut socket_connect = mgr.attach_socket_connect().unwrap();
+
+    let mut rx = socket_connect.alerts().await.unwrap();
+
+    socket_connect
+        .set_cidr_database(TextDatabase::open(&database).unwrap())
+        .await
+        .unwrap();
+
+    println!("registering deny policy");
+    socket_connect
+        .add_geo_policy(SocketConnectGeo {
+            subject: PolicySubject::All,
+            deny: vec![GeoSelector::Asn(64512)],
+        })
+        .await
+        .unwrap();
+
+    let err = std::net::TcpStream::connect("127.1.2.5:8080").expect_err("connect should be denied");
+    assert_eq!(err.raw_os_error(), Some(libc::EPERM));
+
+    let alert = tokio::time::timeout(Duration::from_secs(5), rx.recv())
+        .await
+        .expect("timeout elapsed")
+        .expect("alert channel closed");
+    println!("alert found: {:?}", alert);
+    assert_eq!(alert.addr, IpAddr::from([127, 1, 2, 5]));
+    assert_eq!(alert.reason, Reason::WildcardDeny);
+
+    // Addresses of other autonomous systems are not denied.
+    let err = std::net::TcpStream::connect("127.2.2.5:8080").unwrap_err();
+    assert_ne!(err.raw_os_error(), Some(libc::EPERM));
+
+    std::fs::remove_file(&database).unwrap();
+}
+
+#[tokio::test]
+async fn test_socket_connect_protected_ports() {
+    let mut mgr: PolicyManager = PolicyManager::with_default_path().unwrap();
+
+    mgr.set_namespace(2);
+    let mut socket_connect = mgr.attach_socket_connect().unwrap();
+    let mut rx = socket_connect.alerts().await.unwrap();
+
+    let protected = |port| SocketConnectProtected {
+        addr: IpAddr::from([127, 0, 0, 1]),
+        port,
+        allow: vec![PathBuf::from("/usr/bin/true")],
+    };
+
+    println!("registering protected address policies keyed by port");
+    socket_connect
+        .set_key_layout(KeyLayout {
+            port: true,
+            ..Default::default()
+        })
+        .unwrap();
+    assert!(socket_connect
+        .add_protected_policy(protected(None))
+        .await
+        .is_err());
+    socket_connect
+        .add_protected_policy(protected(Some(5432)))
+        .await
+        .unwrap();
+    assert!(socket_connect.set_key_layout(KeyLayout::default()).is_err());
+
+    let cgroup = current_cgroup();
+    mgr.assign_cgroup(&cgroup, 2).unwrap();
+    let denied = std::net::TcpStream::connect("127.0.0.1:5432");
+    let other = std::net::TcpStream::connect("127.0.0.1:5433");
+    mgr.assign_cgroup(&cgroup, 0).unwrap();
+
+    let err = denied.expect_err("connect to the protected port should be denied");
+    assert_eq!(err.raw_os_error(), Some(libc::EPERM));
+    let alert = tokio::time::timeout(Duration::from_secs(5), rx.recv())
+        .await
+        .expect("timeout elapsed")
+        .expect("alert channel closed");
+    println!("alert found: {:?}", alert);
+    assert_eq!(alert.reason, Reason::Protected);
+
+    // Other ports of the address are not protected.
+    let err = other.unwrap_err();
+    assert_ne!(err.raw_os_error(), Some(libc::EPERM));
+}
+
+#[tokio::test]
+async fn test_socket_connect_metadata() {
+    let mut mgr: PolicyManager = PolicyManager::with_default_path().unwrap();
+
+    let mut socket_connect = mgr.attach_socket_connect().unwrap();
+    let mut rx = socket_connect.alerts().await.unwrap();
+
+    println!("registering metadata policy");
+    socket_connect
+        .add_metadata_policy(SocketConnectMetadata::new(PolicySubject::All))
+        .await
+        .unwrap();
+
+    let connect = |addr: &str| {
+        std::net::TcpStream::connect_timeout(&addr.parse().unwrap(), Duration::from_millis(500))
+    };
+
+    let err = connect("169.254.169.254:80").expect_err("connect should be denied");
+    assert_eq!(err.raw_os_error(), Some(libc::EPERM));
+    let alert = tokio::time::timeout(Duration::from_secs(5), rx.recv())
+        .await
+        .expect("timeout elapsed")
+        .expect("alert channel closed");
+    println!("alert found: {:?}", alert);
+    assert_eq!(alert.addr, IpAddr::from([169, 254, 169, 254]));
+    assert_eq!(alert.reason, Reason::Metadata);
+
+    println!("replacing the metadata address");
+    socket_connect
+        .add_metadata_policy(SocketConnectMetadata {
+            subject: PolicySubject::All,
+            metadata: vec![IpAddr::from([127, 3, 0, 1])],
+            link_local: false,
+        })
+        .await
+        .unwrap();
+
+    let err = connect("127.3.0.1:80").expect_err("connect should be denied");
+    assert_eq!(err.raw_os_error(), Some(libc::EPERM));
+    if let Err(err) = connect("169.254.169.254:80") {
+        assert_ne!(err.raw_os_error(), Some(libc::EPERM));
+    }
+}
+
+#[tokio::test]
+async fn test_health() {
+    let mut mgr: PolicyManager = PolicyManager::with_default_path().unwrap();
+
+    let mut socket_bind = mgr.attach_socket_bind().unwrap();
+    let _sb_mount = mgr.manage_sb_mount().unwrap();
+    let _rx = socket_bind.alerts().await.unwrap();
+
+    let health = mgr.health();
+    println!("health: {:?}", health);
+    assert_eq!(health.state, State::Healthy);
+    let hook = health
+        .hooks
+        .iter()
+        .find(|hook| hook.name == "socket_bind")
+        .unwrap();
+    assert!(hook.intended && hook.attached);
+    assert!(hook.alert_readers > 0);
+    let hook = health
+        .hooks
+        .iter()
+        .find(|hook| hook.name == "sb_mount")
+        .unwrap();
+    assert!(!hook.intended && !hook.attached);
+    assert!(health
+        .maps
+        .iter()
+        .all(|map| map.state == State::Healthy && map.entries.is_some()));
+
+    // Dropping the hook drops the link of its program.
+    drop(socket_bind);
+
+    let health = mgr.health();
+    println!("health: {:?}", health);
+    assert_eq!(health.state, State::Failed);
+    let hook = health
+        .hooks
+        .iter()
+        .find(|hook| hook.name == "socket_bind")
+        .unwrap();
+    assert!(!hook.attached);
+    assert_eq!(hook.state, State::Failed);
+}
+
+#[tokio::test]
+async fn test_heartbeat() {
+    let mut mgr: PolicyManager = PolicyManager::with_default_path().unwrap();
+
+    let _file_open = mgr.attach_file_open().unwrap();
+    let interval = Duration::from_millis(200);
+    let mut rx = mgr.heartbeat(interval).unwrap();
+
+    let mut heartbeats = Vec::new();
+    for _ in 0..4 {
+        let heartbeat = tokio::time::timeout(Duration::from_secs(5), rx.recv())
+            .await
+            .expect("timeout elapsed")
+            .expect("heartbeat channel closed");
+        println!("heartbeat: {:?}", heartbeat);
+        heartbeats.push(heartbeat);
+    }
+
+    for (i, heartbeat) in heartbeats.iter().enumerate() {
+        assert_eq!(heartbeat.seq, i as u64 + 1);
+        assert_eq!(heartbeat.hooks.len(), 1);
+        assert_eq!(heartbeat.hooks[0].name, "file_open");
+        assert!(heartbeat.hooks[0].attached);
+    }
+    let elapsed = heartbeats[3].uptime - heartbeats[0].uptime;
+    assert!(
+        elapsed >= interval * 3 - Duration::from_millis(50),
+        "{elapsed:?}"
+    );
+    assert!(
+        elapsed < interval * 3 + Duration::from_secs(1),
+        "{elapsed:?}"
+    );
+
+    assert!(mgr.heartbeat(Duration::ZERO).is_err());
+}
+
+#[tokio::test]
+async fn test_socket_bind_exempt_ports() {
+    let mut mgr: PolicyManager = PolicyManager::with_default_path().unwrap();
+
+    let mut socket_bind = mgr.attach_socket_bind().unwrap();
+
+    socket_bind
+        .add_policy(SocketBind {
+            subject: PolicySubject::All,
+            allow: Ports::All,
+            deny: Ports::Ports(vec![8920, 8930]),
+            deny_options: vec![],
+            family: None,
+        })
+        .await
+        .unwrap();
+    socket_bind.set_exempt_ports(Some(8910..=8920)).unwrap();
+    assert_eq!(socket_bind.exempt_ports().unwrap(), Some(8910..=8920));
+
+    // Inside of the range, the deny rule doesn't apply.
+    std::net::TcpListener::bind("127.0.0.1:8920").expect("exempt bind should be allowed");
+    // Outside of the range, it does.
+    std::net::TcpListener::bind("127.0.0.1:8930").expect_err("bind should be denied");
+
+    socket_bind.set_exempt_ports(None).unwrap();
+    assert_eq!(socket_bind.exempt_ports().unwrap(), None);
+    std::net::TcpListener::bind("127.0.0.1:8920").expect_err("bind should be denied");
+}
+
+#[tokio::test]
+async fn test_message_id() {
+    let mut mgr: PolicyManager = PolicyManager::with_default_path().unwrap();
+    mgr.set_message_id(Hook::SocketBind, &PolicySubject::All, 1)
+        .unwrap();
+    mgr.set_message_id(
+        Hook::SocketBind,
+        &PolicySubject::Binary(std::env::current_exe().unwrap()),
+        2,
+    )
+    .unwrap();
+
+    let mut messages = Messages::new();
+    messages.insert(1, "Ports 8940-8941 are reserved.");
+    messages.insert(2, "This binary must not bind port 8941.");
+
+    let mut socket_bind = mgr.attach_socket_bind().unwrap();
+
+    let mut rx = socket_bind.alerts().await.unwrap();
+
+    socket_bind
+        .add_policy(SocketBind {
+            subject: PolicySubject::All,
+            allow: Ports::All,
+            deny: Ports::Ports(vec![8940]),
+            deny_options: vec![],
+            family: None,
+        })
+        .await
+        .unwrap();
+    socket_bind
+        .add_policy(SocketBind {
+            subject: PolicySubject::Binary(std::env::current_exe().unwrap()),
+            allow: Ports::All,
+            deny: Ports::Ports(vec![8941]),
+            deny_options: vec![],
+            family: None,
+        })
+        .await
+        .unwrap();
+
+    for (port, message_id, message) in [
+        (8940, 1, "Ports 8940-8941 are reserved."),
+        (8941, 2, "This binary must not bind port 8941."),
+    ] {
+        std::net::TcpListener::bind(("127.0.0.1", port)).expect_err("bind should be denied");
+
+        let alert = tokio::time::timeout(Duration::from_secs(5), rx.recv())
+            .await
+            .expect("timeout elapsed")
+            .expect("alert channel closed");
+        println!("alert found: {}", messages.render(&alert));
+        assert_eq!(alert.port, port);
+        assert_eq!(alert.message_id, message_id);
+        assert_eq!(messages.render(&alert).message, Some(message));
+    }
+}
+
+#[tokio::test]
+async fn test_alert_window() {
+    let dir = PathBuf::from("/tmp/ebpfguard-test-alert-window");
+    tokio::fs::create_dir_all(&dir)
+        .await
+        .expect("failed to create test directory");
+
+    let secret = dir.join("secret");
+    tokio::fs::write(&secret, "s3cr3t")
+        .await
+        .expect("failed to write secret file");
+
+    let callers = ["cat1", "cat2"].map(|name| dir.join(name));
+    for caller in callers.iter() {
+        tokio::fs::copy("/usr/bin/cat", caller)
+            .await
+            .expect("failed to make cat copy");
+    }
+
+    let mut mgr: PolicyManager = PolicyManager::with_default_path().unwrap();
+    mgr.set_alert_window(
+        &PolicySubject::Binary(dir.join("cat3")),
+        Some(Duration::ZERO),
+    )
+    .expect_err("window of a missing binary should fail");
+    mgr.set_alert_window(&PolicySubject::All, Some(Duration::from_secs(7200)))
+        .expect_err("window above the maximum should fail");
+
+    // cat1 falls back to the default window, cat2 is not rate-limited.
+    mgr.set_alert_window(&PolicySubject::All, Some(Duration::from_secs(10)))
+        .unwrap();
+    mgr.set_alert_window(
+        &PolicySubject::Binary(callers[1].clone()),
+        Some(Duration::ZERO),
+    )
+    .unwrap();
+
+    let mut file_open = mgr.attach_file_open().unwrap();
+
+    let mut rx = file_open.alerts().await.unwrap();
+
+    file_open
+        .add_protected_policy(FileOpenProtected {
+            path: secret.clone(),
+            allow: vec![std::env::current_exe().unwrap()],
+        })
+        .await
+        .unwrap();
+
+    for caller in callers.iter() {
+        for _ in 0..3 {
+            let cmd = tokio::process::Command::new(caller)
+                .arg(&secret)
+                .output()
+                .await
+                .expect("unexpected execution failure");
+            assert!(!cmd.status.success(), "{caller:?} should be denied");
+        }
+    }
+
+    let subjects = callers.clone().map(|caller| {
+        let inode = std::fs::metadata(caller).unwrap().ino();
+        PolicySubject::Binary(PathBuf::from(inode.to_string()))
+    });
+    let mut counts = [0; 2];
+    while let Ok(Some(alert)) = tokio::time::timeout(Duration::from_secs(1), rx.recv()).await {
+        if let Some(i) = subjects
+            .iter()
+            .position(|subject| *subject == alert.subject)
+        {
+            counts[i] += 1;
+        }
+    }
+    assert_eq!(counts, [1, 3]);
+
+    mgr.set_alert_window(&PolicySubject::All, None).unwrap();
+    mgr.set_alert_window(&PolicySubject::Binary(callers[1].clone()), None)
+        .unwrap();
+}
+
+#[tokio::test]
+async fn test_alert_channel() {
+    let dir = PathBuf::from("/tmp/ebpfguard-test-alert-channel");
+    tokio::fs::create_dir_all(&dir)
+        .await
+        .expect("failed to create test directory");
+
+    let secret = dir.join("secret");
+    tokio::fs::write(&secret, "s3cr3t")
+        .await
+        .expect("failed to write secret file");
+
+    let callers = ["cat1", "cat2"].map(|name| dir.join(name));
+    for caller in callers.iter() {
+        tokio::fs::copy("/usr/bin/cat", caller)
+            .await
+            .expect("failed to make cat copy");
+    }
+
+    // cat1 goes to the audit channel, cat2 stays on the default one.
+    let mut mgr: PolicyManager = PolicyManager::with_default_path().unwrap();
+    mgr.set_alert_channel(&PolicySubject::Binary(callers[0].clone()), 2)
+        .unwrap();
+
+    let mut file_open = mgr.attach_file_open().unwrap();
+    let mut rx = file_open.alerts().await.unwrap();
+
+    file_open
+        .add_protected_policy(FileOpenProtected {
+            path: secret.clone(),
+            allow: vec![std::env::current_exe().unwrap()],
+        })
+        .await
+        .unwrap();
+
+    for caller in callers.iter() {
+        let cmd = tokio::process::Command::new(caller)
+            .arg(&secret)
+            .output()
+            .await
+            .expect("unexpected execution failure");
+        assert!(!cmd.status.success(), "{caller:?} should be denied");
+    }
+
+    let subjects = callers.clone().map(|caller| {
+        let inode = std::fs::metadata(caller).unwrap().ino();
+        PolicySubject::Binary(PathBuf::from(inode.to_string()))
+    });
+    let mut channels = [None; 2];
+    while let Ok(Some(alert)) = tokio::time::timeout(Duration::from_secs(1), rx.recv()).await {
+        if let Some(i) = subjects
+            .iter()
+            .position(|subject| *subject == alert.subject)
+        {
+            channels[i] = Some(alert.channel);
+        }
+    }
+    assert_eq!(channels, [Some(2), Some(0)]);
+
+    mgr.set_alert_channel(&PolicySubject::Binary(callers[0].clone()), 0)
+        .unwrap();
+}
+
+#[tokio::test]
+async fn test_simulate_matches_kernel() {
+    let mut mgr: PolicyManager = PolicyManager::with_default_path().unwrap();
+
+    let mut socket_bind = mgr.attach_socket_bind().unwrap();
+
+    let mut rx = socket_bind.alerts().await.unwrap();
+
+    let binary = || PolicySubject::Binary(std::env::current_exe().unwrap());
+    let binprm_inode = std::fs::metadata(std::env::current_exe().unwrap())
+        .unwrap()
+        .ino();
+    let policies = || {
+        vec![
+            SocketBind {
+                subject: PolicySubject::All,
+                allow: Ports::All,
+                deny: Ports::Ports(vec![8950]),
+                deny_options: vec![],
+                family: None,
+            },
+            SocketBind {
+                subject: binary(),
+                allow: Ports::All,
+                deny: Ports::Ports(vec![8951]),
+                deny_options: vec![],
+                family: None,
+            },
+        ]
+    };
+    let ports = [8950, 8951, 8952];
+
+    let events: Vec<_> = ports
+        .iter()
+        .map(|port| Event::SocketBind {
+            binprm_inode,
+            family: libc::AF_INET as u16,
+            port: *port,
+        })
+        .collect();
+    let verdicts = simulate(policies().into_iter().map(Policy::SocketBind), &events).unwrap();
+
+    for policy in policies() {
+        socket_bind.add_policy(policy).await.unwrap();
+    }
+
+    for (port, verdict) in ports.into_iter().zip(verdicts) {
+        let res = std::net::TcpListener::bind(("127.0.0.1", port));
+        println!("port {port}: simulated {verdict:?}, bind {res:?}");
+        match verdict {
+            SimulatedVerdict::Allow => {
+                res.expect("bind should be allowed");
+            }
+            SimulatedVerdict::Deny(reason) => {
+                res.expect_err("bind should be denied");
+                let alert = tokio::time::timeout(Duration::from_secs(5), rx.recv())
+                    .await
+                    .expect("timeout elapsed")
+                    .expect("alert channel closed");
+                assert_eq!(alert.port, port);
+                assert_eq!(alert.reason, reason);
+            }
+        }
+    }
+}
+
+#[tokio::test]
+async fn test_plan() {
+    let mut mgr: PolicyManager = PolicyManager::with_default_path().unwrap();
+
+    mgr.set_namespace(4);
+    let policies = || {
+        vec![
+            Policy::SocketBind(SocketBind {
+                subject: PolicySubject::All,
+                allow: Ports::All,
+                deny: Ports::Ports(vec![8960]),
+                deny_options: vec![SocketOption::ReuseAddr],
+                family: None,
+            }),
+            Policy::SocketBindComm(SocketBindComm {
+                comm: "ebpfguard-plan*".parse().unwrap(),
+                allow: Ports::All,
+                deny: Ports::Ports(vec![8961]),
+            }),
+            Policy::SocketConnectMetadata(SocketConnectMetadata::new(PolicySubject::All)),
+        ]
+    };
+
+    let before = mgr.snapshot().unwrap();
+    let plan = mgr.plan(policies()).unwrap();
+    println!("plan:\n{plan}");
+    assert!(!plan.is_empty());
+
+    let mut expected = before.clone();
+    expected.apply(&plan);
+    mgr.apply_plan(&plan).unwrap();
+    assert_eq!(mgr.snapshot().unwrap(), expected);
+    assert!(mgr.plan(policies()).unwrap().is_empty());
+
+    // The hooks write the same entries.
+    let mut socket_bind = mgr.manage_socket_bind().unwrap();
+    let mut socket_connect = mgr.manage_socket_connect().unwrap();
+    for policy in policies() {
+        match policy {
+            Policy::SocketBind(policy) => socket_bind.add_policy(policy).await.unwrap(),
+            Policy::SocketBindComm(policy) => socket_bind.add_comm_policy(policy).unwrap(),
+            Policy::SocketConnectMetadata(policy) => {
+                socket_connect.add_metadata_policy(policy).await.unwrap()
+            }
+            _ => unreachable!(),
+        }
+    }
+    assert_eq!(mgr.snapshot().unwrap(), expected);
+
+    println!("planning removal of the command name policy");
+    let mut policies = policies();
+    policies.remove(1);
+    let plan = mgr.plan(policies).unwrap();
+    println!("plan:\n{plan}");
+    assert_eq!(plan.changes().len(), 2);
+    assert!(plan
+        .changes()
+        .iter()
+        .all(|change| matches!(change, Change::Delete { .. })));
+
+    let mut expected = mgr.snapshot().unwrap();
+    expected.apply(&plan);
+    mgr.apply_plan(&plan).unwrap();
+    assert_eq!(mgr.snapshot().unwrap(), expected);
+}
+
+#[tokio::test]
+async fn test_socket_bind_limit() {
+    let mut mgr: PolicyManager = PolicyManager::with_default_path().unwrap();
+
+    mgr.set_namespace(5);
+    let mut socket_bind = mgr.attach_socket_bind().unwrap();
+    let mut rx = socket_bind.alerts().await.unwrap();
+
+    println!("limiting binds to 3 ports");
+    socket_bind
+        .set_bind_limit(PolicySubject::All, Some(3))
+        .await
+        .unwrap();
+    assert_eq!(
+        socket_bind.bind_limit(PolicySubject::All).await.unwrap(),
+        Some(3)
+    );
+
+    let cgroup = current_cgroup();
+    mgr.assign_cgroup(&cgroup, 5).unwrap();
+    let allowed: Vec<io::Result<()>> = (8940..8943)
+        .map(|port| std::net::TcpListener::bind(("127.0.0.1", port)).map(drop))
+        .collect();
+    // Counted ports can be bound again.
+    let again = std::net::TcpListener::bind("127.0.0.1:8940").map(drop);
+    let denied = std::net::TcpListener::bind("127.0.0.1:8943").map(drop);
+    mgr.assign_cgroup(&cgroup, 0).unwrap();
+
+    for res in allowed {
+        res.expect("binds within the limit should be allowed");
+    }
+    again.expect("bind of a counted port should be allowed");
+    let err = denied.expect_err("bind over the limit should be denied");
+    assert_eq!(err.raw_os_error(), Some(libc::EPERM));
+
+    let alert = tokio::time::timeout(Duration::from_secs(5), rx.recv())
+        .await
+        .expect("timeout elapsed")
+        .expect("alert channel closed");
+    println!("alert found: {:?}", alert);
+    assert_eq!(alert.port, 8943);
+    assert_eq!(alert.reason, Reason::BindLimit);
+    assert_eq!(alert.count, 3);
+
+    socket_bind
+        .set_bind_limit(PolicySubject::All, None)
+        .await
+        .unwrap();
+    assert_eq!(
+        socket_bind.bind_limit(PolicySubject::All).await.unwrap(),
+        None
+    );
+}
+
+#[tokio::test]
+async fn test_socket_connect_rate_limit() {
+    let mut mgr: PolicyManager = PolicyManager::with_default_path().unwrap();
+
+    mgr.set_namespace(11);
+    let mut socket_connect = mgr.attach_socket_connect().unwrap();
+    let mut rx = socket_connect.alerts().await.unwrap();
+
+    println!("limiting connects to 127.4.0.1 to 3 per minute");
+    let destination: Cidr = "127.4.0.1/32".parse().unwrap();
+    let limit = ConnectRateLimit {
+        connects: 3,
+        window: Duration::from_secs(60),
+    };
+    socket_connect
+        .set_rate_limit(destination, Some(limit))
+        .unwrap();
+    assert_eq!(
+        socket_connect.rate_limits().unwrap(),
+        vec![(destination, limit)]
+    );
+
+    let connect = |addr: &str| {
+        std::net::TcpStream::connect_timeout(&addr.parse().unwrap(), Duration::from_millis(500))
+            .map(drop)
+    };
+    let cgroup = current_cgroup();
+    mgr.assign_cgroup(&cgroup, 11).unwrap();
+    // Nothing listens, refused connects count too.
+    let allowed: Vec<io::Result<()>> = (0..3).map(|_| connect("127.4.0.1:80")).collect();
+    let denied = connect("127.4.0.1:80");
+    let other: Vec<io::Result<()>> = (0..5).map(|_| connect("127.4.0.2:80")).collect();
+    mgr.assign_cgroup(&cgroup, 0).unwrap();
+
+    for res in allowed.into_iter().chain(other) {
+        if let Err(err) = res {
+            assert_ne!(err.raw_os_error(), Some(libc::EPERM));
+        }
+    }
+    let err = denied.expect_err("connect over the limit should be denied");
+    assert_eq!(err.raw_os_error(), Some(libc::EPERM));
+
+    let alert = tokio::time::timeout(Duration::from_secs(5), rx.recv())
+        .await
+        .expect("timeout elapsed")
+        .expect("alert channel closed");
+    println!("alert found: {:?}", alert);
+    assert_eq!(alert.addr, IpAddr::from([127, 4, 0, 1]));
+    assert_eq!(alert.reason, Reason::RateLimit);
+    assert_eq!(alert.rate, 3);
+
+    socket_connect.set_rate_limit(destination, None).unwrap();
+    assert!(socket_connect.rate_limits().unwrap().is_empty());
+}
+
+#[tokio::test]
+async fn test_socket_bind_temporary_allow() {
+    let mut mgr: PolicyManager = PolicyManager::with_default_path().unwrap();
+
+    mgr.set_namespace(12);
+    let mut events = mgr.audit_events();
+    let mut socket_bind = mgr.attach_socket_bind().unwrap();
+
+    println!("denying binds of port 8970");
+    socket_bind
+        .add_policy(SocketBind {
+            subject: PolicySubject::All,
+            allow: Ports::All,
+            deny: Ports::Ports(vec![8970]),
+            deny_options: vec![],
+            family: None,
+        })
+        .await
+        .unwrap();
+
+    let binary = || PolicySubject::Binary(std::env::current_exe().unwrap());
+    let bind = || std::net::TcpListener::bind("127.0.0.1:8970").map(drop);
+    let cgroup = current_cgroup();
+    mgr.assign_cgroup(&cgroup, 12).unwrap();
+    let before = bind();
+    socket_bind
+        .grant_temporary_allow(binary(), 8970, Duration::from_secs(2))
+        .await
+        .unwrap();
+    let during = bind();
+    let allows = socket_bind.list_temporary_allows().await.unwrap();
+    tokio::time::sleep(Duration::from_secs(3)).await;
+    let after = bind();
+    mgr.assign_cgroup(&cgroup, 0).unwrap();
+
+    let err = before.expect_err("bind should be denied before the grant");
+    assert_eq!(err.raw_os_error(), Some(libc::EPERM));
+    during.expect("bind should be allowed during the grant");
+    assert_eq!(allows.len(), 1);
+    assert_eq!(allows[0].0, binary());
+    assert_eq!(allows[0].1, 8970);
+    assert!(allows[0].2.unwrap() <= Duration::from_secs(2));
+    let err = after.expect_err("bind should be denied after the grant expired");
+    assert_eq!(err.raw_os_error(), Some(libc::EPERM));
+    assert!(socket_bind
+        .list_temporary_allows()
+        .await
+        .unwrap()
+        .is_empty());
+
+    let operations: Vec<&str> = std::iter::from_fn(|| events.try_recv().ok())
+        .map(|event| event.operation)
+        .collect();
+    assert_eq!(
+        operations,
+        [
+            "add_policy",
+            "assign_cgroup",
+            "grant_temporary_allow",
+            "revoke_temporary_allow",
+            "assign_cgroup",
+        ]
+    );
+}
+
+#[tokio::test]
+async fn test_raw_maps() {
+    let mut mgr: PolicyManager = PolicyManager::with_default_path().unwrap();
+
+    mgr.set_namespace(13);
+    let mut socket_bind = mgr.attach_socket_bind().unwrap();
+    socket_bind
+        .add_policy(SocketBind {
+            subject: PolicySubject::All,
+            allow: Ports::All,
+            deny: Ports::Ports(vec![]),
+            deny_options: vec![],
+            family: None,
+        })
+        .await
+        .unwrap();
+
+    let bind = || std::net::TcpListener::bind("127.0.0.1:8976").map(drop);
+    let cgroup = current_cgroup();
+    mgr.assign_cgroup(&cgroup, 13).unwrap();
+    // Cached as allowed.
+    let before = bind();
+
+    println!("denying port 8976 through the raw handle");
+    let key = mgr
+        .allowed_socket_bind_map()
+        .unwrap()
+        .keys()
+        .filter_map(|key| key.ok())
+        .find(|key| key.namespace == 13)
+        .expect("policy should be in the map");
+    {
+        let mut denied = mgr.denied_socket_bind_map().unwrap();
+        denied
+            .insert(key, Ports::Ports(vec![8976]).into(), 0)
+            .unwrap();
+    }
+    let after = bind();
+    mgr.assign_cgroup(&cgroup, 0).unwrap();
+
+    before.expect("bind should be allowed by the policy");
+    let err = after.expect_err("bind should be denied after the raw change");
+    assert_eq!(err.raw_os_error(), Some(libc::EPERM));
+
+    for name in ["ALERT_SOCKET_BIND", "GENERATION_SOCKET_BIND"] {
+        assert!(matches!(
+            mgr.raw_hash_map::<u32, u64>(name),
+            Err(EbpfguardError::RawMapRefused(_))
+        ));
+    }
+}
+
+/// Creates and closes a socket of the family, type and protocol.
+fn create_socket(family: i32, ty: i32, protocol: i32) -> io::Result<()> {
+    let fd = unsafe { libc::socket(family, ty, protocol) };
+    if fd < 0 {
+        return Err(io::Error::last_os_error());
+    }
+    unsafe { libc::close(fd) };
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_socket_create_deny() {
+    let mut mgr: PolicyManager = PolicyManager::with_default_path().unwrap();
+
+    mgr.set_namespace(7);
+    let mut socket_create = mgr.attach_socket_create().unwrap();
+    let mut rx = socket_create.alerts().await.unwrap();
+
+    println!("denying raw and netlink sockets");
+    socket_create
+        .add_policy(SocketCreate {
+            subject: PolicySubject::All,
+            allow: SocketKinds::All,
+            deny: SocketKinds::Kinds(vec![
+                SocketKind {
+                    family: SocketFamily::Inet,
+                    socket_type: Some(SocketType::Raw),
+                },
+                SocketKind {
+                    family: SocketFamily::Netlink,
+                    socket_type: None,
+                },
+            ]),
+        })
+        .await
+        .unwrap();
+
+    let cgroup = current_cgroup();
+    mgr.assign_cgroup(&cgroup, 7).unwrap();
+    let raw = create_socket(libc::AF_INET, libc::SOCK_RAW, libc::IPPROTO_ICMP);
+    let netlink = create_socket(libc::AF_NETLINK, libc::SOCK_DGRAM, libc::NETLINK_ROUTE);
+    let tcp = create_socket(libc::AF_INET, libc::SOCK_STREAM, 0);
+    mgr.assign_cgroup(&cgroup, 0).unwrap();
+
+    tcp.expect("tcp socket should be allowed");
+    for (res, family, ty) in [
+        (raw, libc::AF_INET, libc::SOCK_RAW),
+        (netlink, libc::AF_NETLINK, libc::SOCK_DGRAM),
+    ] {
+        let err = res.expect_err("socket creation should be denied");
+        assert_eq!(err.raw_os_error(), Some(libc::EPERM));
+
+        let alert = tokio::time::timeout(Duration::from_secs(5), rx.recv())
+            .await
+            .expect("timeout elapsed")
+            .expect("alert channel closed");
+        println!("alert found: {:?}", alert);
+        assert_eq!(alert.family, family as u16);
+        assert_eq!(alert.socket_type, ty as u16);
+        assert_eq!(alert.reason, Reason::WildcardDeny);
+    }
+
+    let policies = socket_create.list_policies().await.unwrap();
+    assert_eq!(policies.len(), 1);
+}
+
+#[tokio::test]
+async fn test_socket_create_swap() {
+    let mut mgr: PolicyManager = PolicyManager::with_default_path().unwrap();
+
+    mgr.set_namespace(8);
+    let mut socket_create = mgr.attach_socket_create().unwrap();
+
+    // Both policy sets deny netlink sockets, so a netlink socket created while
+    // they're swapped can only be allowed by partially written policies.
+    let netlink = SocketKind {
+        family: SocketFamily::Netlink,
+        socket_type: None,
+    };
+    let packet = SocketKind {
+        family: SocketFamily::Packet,
+        socket_type: None,
+    };
+    let policies = |i: usize| {
+        let deny = match i % 2 {
+            0 => vec![netlink],
+            _ => vec![packet, netlink],
+        };
+        vec![SocketCreate {
+            subject: PolicySubject::All,
+            allow: SocketKinds::All,
+            deny: SocketKinds::Kinds(deny),
+        }]
+    };
+    socket_create.swap_policies(policies(0)).await.unwrap();
+
+    let cgroup = current_cgroup();
+    mgr.assign_cgroup(&cgroup, 8).unwrap();
+    let done = Arc::new(AtomicBool::new(false));
+    let creator = {
+        let done = done.clone();
+        std::thread::spawn(move || {
+            let mut allowed = 0;
+            while !done.load(Ordering::Relaxed) {
+                if create_socket(libc::AF_NETLINK, libc::SOCK_DGRAM, libc::NETLINK_ROUTE).is_ok() {
+                    allowed += 1;
+                }
+            }
+            allowed
+        })
+    };
+    for i in 1..=200 {
+        socket_create.swap_policies(policies(i)).await.unwrap();
+    }
+    done.store(true, Ordering::Relaxed);
+    let allowed = creator.join().unwrap();
+    mgr.assign_cgroup(&cgroup, 0).unwrap();
+
+    assert_eq!(allowed, 0, "netlink sockets created during swaps");
+    assert_eq!(socket_create.list_policies().await.unwrap(), policies(200));
+}
+
+#[tokio::test]
+async fn test_namespaces_alerts() {
+    // A policy manager per namespace, both reading the alerts of the shared
+    // `ALERT_SOCKET_CREATE` array at the same time.
+    let mut mgr_a: PolicyManager = PolicyManager::with_default_path().unwrap();
+    mgr_a.set_namespace(27);
+    let mut socket_create_a = mgr_a.attach_socket_create().unwrap();
+    let mut mgr_b: PolicyManager = PolicyManager::with_default_path().unwrap();
+    mgr_b.set_namespace(28);
+    let mut socket_create_b = mgr_b.manage_socket_create().unwrap();
+
+    let mut rx_a = socket_create_a.alerts().await.unwrap();
+    let mut rx_b = socket_create_b.alerts().await.unwrap();
+
+    println!("denying raw sockets in namespace 27 and netlink sockets in 28");
+    let raw = SocketKind {
+        family: SocketFamily::Inet,
+        socket_type: Some(SocketType::Raw),
+    };
+    let netlink = SocketKind {
+        family: SocketFamily::Netlink,
+        socket_type: None,
+    };
+    for (socket_create, kind) in [(&mut socket_create_a, raw), (&mut socket_create_b, netlink)] {
+        socket_create
+            .add_policy(SocketCreate {
+                subject: PolicySubject::All,
+                allow: SocketKinds::All,
+                deny: SocketKinds::Kinds(vec![kind]),
+            })
+            .await
+            .unwrap();
+    }
+
+    let cgroup = current_cgroup();
+    mgr_a.assign_cgroup(&cgroup, 27).unwrap();
+    let raw = create_socket(libc::AF_INET, libc::SOCK_RAW, libc::IPPROTO_ICMP);
+    mgr_b.assign_cgroup(&cgroup, 28).unwrap();
+    let netlink = create_socket(libc::AF_NETLINK, libc::SOCK_DGRAM, libc::NETLINK_ROUTE);
+    mgr_a.assign_cgroup(&cgroup, 0).unwrap();
+
+    for (res, rx, namespace, family) in [
+        (raw, &mut rx_a, 27, libc::AF_INET),
+        (netlink, &mut rx_b, 28, libc::AF_NETLINK),
+    ] {
+        let err = res.expect_err("socket creation should be denied");
+        assert_eq!(err.raw_os_error(), Some(libc::EPERM));
+
+        let alert = tokio::time::timeout(Duration::from_secs(5), rx.recv())
+            .await
+            .expect("timeout elapsed")
+            .expect("alert channel closed");
+        println!("alert found: {:?}", alert);
+        assert_eq!(alert.namespace, namespace);
+        assert_eq!(alert.family, family as u16);
+        assert_eq!(alert.seq, 1);
+    }
+    assert!(rx_a.try_recv().is_err(), "namespace 27 got another alert");
+    assert!(rx_b.try_recv().is_err(), "namespace 28 got another alert");
+}
+
+#[tokio::test]
+async fn test_alert_buffers_too_small() {
+    let mut mgr: PolicyManager = PolicyManager::with_default_path().unwrap();
+
+    let err = mgr
+        .set_alert_buffers(AlertBuffers {
+            pages: 1,
+            record_size: 16,
+        })
+        .expect_err("buffers smaller than the largest alert should be rejected");
+    assert!(matches!(
+        err,
+        EbpfguardError::AlertBuffersTooSmall { budget: 16, .. }
+    ));
+
+    // Hooks managed afterwards read through the default buffers.
+    let mut socket_bind = mgr.manage_socket_bind().unwrap();
+    socket_bind.alerts().await.unwrap();
+}
+
+#[tokio::test]
+async fn test_socket_bind_family() {
+    let mut mgr: PolicyManager = PolicyManager::with_default_path().unwrap();
+
+    mgr.set_namespace(6);
+    let mut socket_bind = mgr.attach_socket_bind().unwrap();
+    let mut rx = socket_bind.alerts().await.unwrap();
+
+    println!("registering family-agnostic deny policy");
+    socket_bind
+        .add_policy(SocketBind {
+            subject: PolicySubject::All,
+            allow: Ports::All,
+            deny: Ports::Ports(vec![8950]),
+            deny_options: vec![],
+            family: None,
+        })
+        .await
+        .unwrap();
+
+    let cgroup = current_cgroup();
+    mgr.assign_cgroup(&cgroup, 6).unwrap();
+    let v4 = std::net::TcpListener::bind("127.0.0.1:8950").map(drop);
+    let v6 = std::net::TcpListener::bind("[::1]:8950").map(drop);
+    let mapped = std::net::TcpListener::bind("[::ffff:127.0.0.1]:8950").map(drop);
+    mgr.assign_cgroup(&cgroup, 0).unwrap();
+
+    for (res, family) in [
+        (v4, libc::AF_INET),
+        (v6, libc::AF_INET6),
+        (mapped, libc::AF_INET),
+    ] {
+        let err = res.expect_err("bind should be denied");
+        assert_eq!(err.raw_os_error(), Some(libc::EPERM));
+
+        let alert = tokio::time::timeout(Duration::from_secs(5), rx.recv())
+            .await
+            .expect("timeout elapsed")
+            .expect("alert channel closed");
+        println!("alert found: {:?}", alert);
+        assert_eq!(alert.port, 8950);
+        assert_eq!(alert.family, family as u16);
+        assert_eq!(alert.reason, Reason::WildcardDeny);
+    }
+
+    println!("registering ipv6 policy");
+    socket_bind
+        .add_policy(SocketBind {
+            subject: PolicySubject::All,
+            allow: Ports::All,
+            deny: Ports::Ports(vec![8951]),
+            deny_options: vec![],
+            family: Some(BindFamily::Ipv6),
+        })
+        .await
+        .unwrap();
+    let policies = socket_bind.list_policies().await.unwrap();
+    assert!(policies
+        .iter()
+        .any(|policy| policy.family == Some(BindFamily::Ipv6)));
+
+    mgr.assign_cgroup(&cgroup, 6).unwrap();
+    let v4 = std::net::TcpListener::bind("127.0.0.1:8950").map(drop);
+    let v6 = std::net::TcpListener::bind("[::1]:8950").map(drop);
+    mgr.assign_cgroup(&cgroup, 0).unwrap();
+
+    v4.expect_err("ipv4 bind should still be denied");
+    v6.expect("ipv6 policy should replace the agnostic one");
+}
+
+/// Runs `true` in a child which drops to the group, then switches to the
+/// group `to` before exec.
+fn switch_group(from: u32, to: u32) -> io::Result<()> {
+    use std::os::unix::process::CommandExt;
+
+    let mut cmd = std::process::Command::new("true");
+    unsafe {
+        cmd.pre_exec(move || {
+            for gid in [from, to] {
+                if libc::setresgid(gid, gid, gid) < 0 {
+                    return Err(io::Error::last_os_error());
+                }
+            }
+            Ok(())
+        });
+    }
+    cmd.status().map(drop)
+}
+
+#[tokio::test]
+async fn test_task_fix_setgid() {
+    let mut mgr: PolicyManager = PolicyManager::with_default_path().unwrap();
+
+    mgr.set_namespace(9);
+    let mut task_fix_setgid = mgr.attach_task_fix_setgid().unwrap();
+    let mut rx = task_fix_setgid.alerts().await.unwrap();
+
+    println!("denying switches to privileged groups");
+    task_fix_setgid.set_privileged_gids(&[27]).unwrap();
+    task_fix_setgid
+        .add_policy(TaskFixSetgid {
+            subject: PolicySubject::All,
+            allow: false,
+        })
+        .await
+        .unwrap();
+
+    let cgroup = current_cgroup();
+    mgr.assign_cgroup(&cgroup, 9).unwrap();
+    let unprivileged = switch_group(1000, 1001);
+    let root = switch_group(1000, 0);
+    let listed = switch_group(1000, 27);
+    mgr.assign_cgroup(&cgroup, 0).unwrap();
+
+    unprivileged.expect("switch to an unprivileged group should be allowed");
+    for (res, gid) in [(root, 0), (listed, 27)] {
+        let err = res.expect_err("switch to a privileged group should be denied");
+        assert_eq!(err.raw_os_error(), Some(libc::EPERM));
+
+        let alert = tokio::time::timeout(Duration::from_secs(5), rx.recv())
+            .await
+            .expect("timeout elapsed")
+            .expect("alert channel closed");
+        println!("alert found: {:?}", alert);
+        assert_eq!(alert.old_gid, 1000);
+        assert_eq!(alert.new_gid, gid);
+        assert_eq!(alert.new_egid, gid);
+    }
+
+    assert_eq!(task_fix_setgid.privileged_gids().unwrap(), vec![27]);
+}
+
+#[tokio::test]
+async fn test_audit_events() {
+    let mut mgr: PolicyManager = PolicyManager::with_default_path().unwrap();
+
+    mgr.set_namespace(10);
+    let mut events = mgr.audit_events();
+    let mut socket_bind = mgr.manage_socket_bind().unwrap();
+
+    let policy = |port| SocketBind {
+        subject: PolicySubject::All,
+        allow: Ports::All,
+        deny: Ports::Ports(vec![port]),
+        deny_options: vec![],
+        family: None,
+    };
+    socket_bind.add_policy(policy(8960)).await.unwrap();
+    socket_bind.add_policy(policy(8961)).await.unwrap();
+    socket_bind
+        .set_bind_limit(PolicySubject::All, Some(4))
+        .await
+        .unwrap();
+    socket_bind
+        .set_bind_limit(PolicySubject::All, None)
+        .await
+        .unwrap();
+    mgr.set_message_id(Hook::SocketBind, &PolicySubject::All, 3)
+        .unwrap();
+    mgr.set_alert_window(&PolicySubject::All, Some(Duration::from_secs(10)))
+        .unwrap();
+    // Failed changes emit no event.
+    mgr.set_alert_window(&PolicySubject::All, Some(Duration::from_secs(7200)))
+        .unwrap_err();
+
+    let all = to_value(&PolicySubject::All);
+    let uid = std::fs::metadata("/proc/self").unwrap().uid();
+    let expected = [
+        (
+            Some(Hook::SocketBind),
+            "add_policy",
+            None,
+            Some(to_value(&policy(8960))),
+        ),
+        (
+            Some(Hook::SocketBind),
+            "add_policy",
+            Some(to_value(&vec![policy(8960)])),
+            Some(to_value(&policy(8961))),
+        ),
+        (
+            Some(Hook::SocketBind),
+            "set_bind_limit",
+            None,
+            Some(to_value(&4)),
+        ),
+        (
+            Some(Hook::SocketBind),
+            "set_bind_limit",
+            Some(to_value(&4)),
+            None,
+        ),
+        (
+            Some(Hook::SocketBind),
+            "set_message_id",
+            None,
+            Some(to_value(&3)),
+        ),
+        (
+            None,
+            "set_alert_window",
+            None,
+            Some(to_value(&Duration::from_secs(10))),
+        ),
+    ];
+    for (seq, (hook, operation, old, new)) in expected.into_iter().enumerate() {
+        let event = events.try_recv().expect("missing audit event");
+        println!("audit event: {:?}", event);
+        assert_eq!(event.seq, seq as u64 + 1);
+        assert_eq!(event.pid, std::process::id());
+        assert_eq!(event.uid, Some(uid));
+        assert_eq!(event.namespace, 10);
+        assert_eq!(event.hook, hook);
+        assert_eq!(event.operation, operation);
+        assert_eq!(event.old, old);
+        assert_eq!(event.new, new);
+        if operation == "add_policy" {
+            assert_eq!(
+                event.key,
+                to_value(&(&PolicySubject::All, None::<BindFamily>))
+            );
+        } else {
+            assert_eq!(event.key, all);
+        }
+    }
+    assert!(events.try_recv().is_err());
+}
+
+/// Converts a key or value to the value of an audit event.
+fn to_value<T: serde::Serialize>(value: &T) -> serde_yaml::Value {
+    serde_yaml::to_value(value).unwrap()
+}
+
+#[tokio::test]
+async fn test_bpf() {
+    let mut mgr: PolicyManager = PolicyManager::with_default_path().unwrap();
+
+    mgr.set_namespace(14);
+    let mut bpf = mgr.attach_bpf().unwrap();
+    let mut rx = bpf.alerts().await.unwrap();
+
+    println!("denying the bpf syscall to all binaries");
+    bpf.add_policy(Bpf {
+        subject: PolicySubject::All,
+        allow: false,
+    })
+    .await
+    .unwrap();
+
+    // A copy of the test binary has an inode of its own, so unlike the
+    // binary managing the hook it isn't exempt.
+    let child = std::env::temp_dir().join("ebpfguard-test-bpf");
+    std::fs::copy(std::env::current_exe().unwrap(), &child).unwrap();
+    let child_inode = std::fs::metadata(&child).unwrap().ino();
+
+    let cgroup = current_cgroup();
+    mgr.assign_cgroup(&cgroup, 14).unwrap();
+    let status = std::process::Command::new(&child)
+        .args(["--exact", "bpf_child", "--ignored", "--nocapture"])
+        .env("EBPFGUARD_BPF_CHILD", "1")
+        .status();
+    // The exempt policy manager keeps access to its maps.
+    let policies = bpf.list_policies().await;
+    mgr.assign_cgroup(&cgroup, 0).unwrap();
+    std::fs::remove_file(&child).unwrap();
+
+    assert!(status.unwrap().success(), "bpf syscall should be denied");
+    assert_eq!(policies.unwrap().len(), 1);
+
+    let alert = tokio::time::timeout(Duration::from_secs(5), rx.recv())
+        .await
+        .expect("timeout elapsed")
+        .expect("alert channel closed");
+    println!("alert found: {:?}", alert);
+    assert_eq!(
+        alert.subject,
+        PolicySubject::Binary(PathBuf::from(child_inode.to_string()))
+    );
+    assert_eq!(alert.cmd, 0);
+
+    let exe = std::env::current_exe().unwrap();
+    assert!(bpf.exempt_binaries().await.unwrap().contains(&exe));
+}
+
+/// Creates a map with the `bpf` syscall, run by `test_bpf` from a copy of
+/// the test binary.
+#[test]
+#[ignore]
+fn bpf_child() {
+    if std::env::var_os("EBPFGUARD_BPF_CHILD").is_none() {
+        return;
+    }
+    // BPF_MAP_CREATE, denied before the attributes are looked at.
+    let attr = [0u8; 8];
+    let res = unsafe { libc::syscall(libc::SYS_bpf, 0, attr.as_ptr(), attr.len()) };
+    assert_eq!(res, -1);
+    assert_eq!(io::Error::last_os_error().raw_os_error(), Some(libc::EPERM));
+}
+
+fn switch_user(to: u32) -> io::Result<()> {
+    use std::os::unix::process::CommandExt;
+
+    let mut cmd = std::process::Command::new("true");
+    unsafe {
+        cmd.pre_exec(move || {
+            if libc::setresuid(to, to, to) < 0 {
+                return Err(io::Error::last_os_error());
+            }
+            Ok(())
+        });
+    }
+    cmd.status().map(drop)
+}
+
+#[tokio::test]
+async fn test_task_fix_setuid_context() {
+    let mut mgr: PolicyManager = PolicyManager::with_default_path().unwrap();
+
+    mgr.set_namespace(16);
+    let mut task_fix_setuid = mgr.attach_task_fix_setuid().unwrap();
+    let mut rx = task_fix_setuid.alerts().await.unwrap();
+
+    println!("denying uid switches to all binaries");
+    task_fix_setuid
+        .add_policy(TaskFixSetuid {
+            subject: PolicySubject::All,
+            allow: false,
+        })
+        .await
+        .unwrap();
+
+    let cgroup = current_cgroup();
+    mgr.assign_cgroup(&cgroup, 16).unwrap();
+    let res = switch_user(1000);
+    mgr.assign_cgroup(&cgroup, 0).unwrap();
+
+    let err = res.expect_err("uid switch should be denied");
+    assert_eq!(err.raw_os_error(), Some(libc::EPERM));
+
+    let alert = tokio::time::timeout(Duration::from_secs(5), rx.recv())
+        .await
+        .expect("timeout elapsed")
+        .expect("alert channel closed");
+    println!("alert found: {:?}", alert);
+    // The forked child switches before exec, so it still runs the test
+    // binary, with the name of the thread which forked it.
+    let exe_inode = std::fs::metadata(std::env::current_exe().unwrap())
+        .unwrap()
+        .ino();
+    let comm = std::thread::current()
+        .name()
+        .unwrap()
+        .chars()
+        .take(15)
+        .collect::<String>();
+    assert_eq!(alert.namespace, 16);
+    assert_eq!(alert.reason, Reason::DefaultDeny);
+    assert_eq!(
+        alert.subject,
+        PolicySubject::Binary(PathBuf::from(exe_inode.to_string()))
+    );
+    assert_ne!(alert.pid, std::process::id());
+    assert_eq!(alert.ppid, std::process::id());
+    assert_eq!(alert.comm, comm);
+    assert_eq!(alert.old_uid, 0);
+    assert_eq!(alert.old_gid, 0);
+    assert_eq!(alert.new_uid, 1000);
+    assert_eq!(alert.new_gid, 0);
+}
+
+#[tokio::test]
+async fn test_apply_plan_atomic() {
+    let mut mgr: PolicyManager = PolicyManager::with_default_path().unwrap();
+
+    mgr.set_namespace(15);
+    let hook = mgr.attach_socket_bind().unwrap();
+    let socket_bind = |port| {
+        Policy::SocketBind(SocketBind {
+            subject: PolicySubject::All,
+            allow: Ports::All,
+            deny: Ports::Ports(vec![port]),
+            deny_options: vec![],
+            family: None,
+        })
+    };
+
+    let plan = mgr.plan(vec![socket_bind(8980)]).unwrap();
+    mgr.apply_plan_atomic(&plan).unwrap();
+    let before = mgr.snapshot().unwrap();
+
+    // More binaries than fit into `DENIED_SB_UMOUNT`, so staging fails after
+    // writing `DENIED_SB_MOUNT` and part of `DENIED_SB_UMOUNT`.
+    let dir = std::env::temp_dir().join("ebpfguard-test-atomic");
+    std::fs::create_dir_all(&dir).unwrap();
+    let mut policies = vec![
+        socket_bind(8981),
+        Policy::SbMount(SbMount {
+            subject: PolicySubject::All,
+            allow: false,
+        }),
+    ];
+    for i in 0..1100 {
+        let binary = dir.join(i.to_string());
+        std::fs::write(&binary, []).unwrap();
+        policies.push(Policy::SbUmount(SbUmount {
+            subject: PolicySubject::Binary(binary),
+            allow: false,
+        }));
+    }
+    let plan = mgr.plan(policies).unwrap();
+    let res = mgr.apply_plan_atomic(&plan);
+    std::fs::remove_dir_all(&dir).unwrap();
+    println!("apply result: {res:?}");
+    assert!(matches!(res, Err(EbpfguardError::Map(_))));
+    assert_eq!(mgr.snapshot().unwrap(), before);
+
+    // A plan computed before another change is refused.
+    let plan = mgr.plan(vec![socket_bind(8981)]).unwrap();
+    let stale = mgr.plan(vec![socket_bind(8982)]).unwrap();
+    mgr.apply_plan_atomic(&plan).unwrap();
+    assert!(matches!(
+        mgr.apply_plan_atomic(&stale),
+        Err(EbpfguardError::PlanOutdated(_))
+    ));
+
+    // The hooks were switched to the generation of the committed policies.
+    let policies = hook.list_policies().await.unwrap();
+    assert_eq!(policies.len(), 1);
+    assert_eq!(policies[0].deny, Ports::Ports(vec![8981]));
+    let cgroup = current_cgroup();
+    mgr.assign_cgroup(&cgroup, 15).unwrap();
+    let allowed = std::net::TcpListener::bind("127.0.0.1:8980");
+    let denied = std::net::TcpListener::bind("127.0.0.1:8981");
+    mgr.assign_cgroup(&cgroup, 0).unwrap();
+    allowed.expect("bind should be allowed after the commit");
+    denied.expect_err("bind should be denied after the commit");
+}
+
+#[tokio::test]
+async fn test_clear_hook() {
+    let mut mgr: PolicyManager = PolicyManager::with_default_path().unwrap();
+
+    mgr.set_namespace(17);
+    let mut socket_bind = mgr.attach_socket_bind().unwrap();
+    let sb_mount = || {
+        Policy::SbMount(SbMount {
+            subject: PolicySubject::All,
+            allow: false,
+        })
+    };
+    let plan = mgr.plan(vec![sb_mount()]).unwrap();
+    mgr.apply_plan(&plan).unwrap();
+
+    println!("denying binds to port 8990, then clearing socket_bind");
+    socket_bind
+        .add_policy(SocketBind {
+            subject: PolicySubject::All,
+            allow: Ports::All,
+            deny: Ports::Ports(vec![8990]),
+            deny_options: vec![],
+            family: None,
+        })
+        .await
+        .unwrap();
+    let cgroup = current_cgroup();
+    mgr.assign_cgroup(&cgroup, 17).unwrap();
+    let denied = std::net::TcpListener::bind("127.0.0.1:8990").map(drop);
+    let removed = mgr.clear_hook(Hook::SocketBind);
+    let allowed = std::net::TcpListener::bind("127.0.0.1:8990").map(drop);
+    mgr.assign_cgroup(&cgroup, 0).unwrap();
+
+    denied.expect_err("bind should be denied before the clear");
+    let removed = removed.unwrap();
+    println!("removed:\n{removed}");
+    assert!(removed
+        .changes()
+        .iter()
+        .all(|change| matches!(change, Change::Delete { .. })));
+    assert!(!removed.is_empty());
+    allowed.expect("bind should be allowed after the clear");
+
+    // Only the entries of the other hooks are left, and nothing else to
+    // clear.
+    assert!(mgr.plan(vec![sb_mount()]).unwrap().is_empty());
+    assert!(mgr.clear_hook(Hook::SocketBind).unwrap().is_empty());
+    mgr.clear_hook(Hook::SbMount).unwrap();
+    assert!(mgr.snapshot().unwrap().is_empty());
+}
+
+#[tokio::test]
+async fn test_retry_escalation() {
+    let mut mgr: PolicyManager = PolicyManager::with_default_path().unwrap();
+
+    mgr.set_namespace(18);
+    mgr.set_retry_escalation(
+        &PolicySubject::All,
+        Some(RetryEscalation {
+            denials: 0,
+            window: Duration::from_secs(60),
+        }),
+    )
+    .expect_err("escalation without denials should fail");
+    mgr.set_retry_escalation(
+        &PolicySubject::All,
+        Some(RetryEscalation {
+            denials: 3,
+            window: Duration::from_secs(60),
+        }),
+    )
+    .unwrap();
+
+    let mut socket_bind = mgr.attach_socket_bind().unwrap();
+    let mut rx = socket_bind.alerts().await.unwrap();
+
+    println!("denying binds to port 8995 and retrying them");
+    socket_bind
+        .add_policy(SocketBind {
+            subject: PolicySubject::All,
+            allow: Ports::All,
+            deny: Ports::Ports(vec![8995]),
+            deny_options: vec![],
+            family: None,
+        })
+        .await
+        .unwrap();
+
+    let cgroup = current_cgroup();
+    mgr.assign_cgroup(&cgroup, 18).unwrap();
+    let binds = (0..4)
+        .map(|_| std::net::TcpListener::bind("127.0.0.1:8995").map(drop))
+        .collect::<Vec<_>>();
+    mgr.assign_cgroup(&cgroup, 0).unwrap();
+
+    for (i, res) in binds.into_iter().enumerate() {
+        res.expect_err("bind should be denied");
+
+        let alert = tokio::time::timeout(Duration::from_secs(5), rx.recv())
+            .await
+            .expect("timeout elapsed")
+            .expect("alert channel closed");
+        println!("alert found: {:?}", alert);
+        assert_eq!(alert.port, 8995);
+        if i < 2 {
+            assert_ne!(alert.reason, Reason::Persistent);
+        } else {
+            assert_eq!(alert.reason, Reason::Persistent);
+        }
+    }
+
+    mgr.set_retry_escalation(&PolicySubject::All, None).unwrap();
+}
+
+#[tokio::test]
+async fn test_config() {
+    let mut mgr: PolicyManager = PolicyManager::with_default_path().unwrap();
+
+    mgr.set_namespace(19);
+    let mut socket_bind = mgr.attach_socket_bind().unwrap();
+    let mut socket_connect = mgr.attach_socket_connect().unwrap();
+    let entry = |mgr: &PolicyManager| {
+        mgr.raw_hash_map::<u32, [u8; 8]>("CONFIG")
+            .unwrap()
+            .get(&19, 0)
+            .ok()
+    };
+    assert_eq!(entry(&mgr), None);
+    assert_eq!(socket_bind.exempt_ports().unwrap(), None);
+    assert_eq!(socket_connect.key_layout().unwrap(), KeyLayout::default());
+
+    // Both fields are kept in the same entry, and setting one keeps the
+    // other.
+    let layout = KeyLayout {
+        port: true,
+        family: true,
+    };
+    socket_bind.set_exempt_ports(Some(8940..=8950)).unwrap();
+    socket_connect.set_key_layout(layout).unwrap();
+    assert_eq!(socket_bind.exempt_ports().unwrap(), Some(8940..=8950));
+    assert_eq!(socket_connect.key_layout().unwrap(), layout);
+    let config = entry(&mgr).expect("config should be stored");
+    assert_eq!(u16::from_ne_bytes([config[0], config[1]]), 8940);
+    assert_eq!(u16::from_ne_bytes([config[2], config[3]]), 8950);
+    // `KEY_LAYOUT_PORT | KEY_LAYOUT_FAMILY` and `CONFIG_EXEMPT_PORTS`.
+    assert_eq!(config[4..], [0b11, 0b1, 0, 0]);
+
+    socket_bind.set_exempt_ports(None).unwrap();
+    assert_eq!(socket_bind.exempt_ports().unwrap(), None);
+    assert_eq!(socket_connect.key_layout().unwrap(), layout);
+
+    // Back to the default settings, the entry is removed.
+    socket_connect.set_key_layout(KeyLayout::default()).unwrap();
+    assert_eq!(socket_connect.key_layout().unwrap(), KeyLayout::default());
+    assert_eq!(entry(&mgr), None);
+}
+
+#[tokio::test]
+async fn test_socket_connect_ports() {
+    let mut mgr: PolicyManager = PolicyManager::with_default_path().unwrap();
+
+    mgr.set_namespace(20);
+    let mut socket_connect = mgr.attach_socket_connect().unwrap();
+    let mut rx = socket_connect.alerts().await.unwrap();
+
+    let policy = SocketConnectPorts {
+        subject: PolicySubject::All,
+        allow: vec![PortRange::port(6667)],
+        deny: vec!["6660-6669".parse().unwrap()],
+    };
+    socket_connect.add_ports_policy(policy).await.unwrap();
+    assert_eq!(
+        socket_connect.list_ports_policies().await.unwrap(),
+        vec![SocketConnectPorts {
+            subject: PolicySubject::All,
+            allow: vec![PortRange::port(6667)],
+            deny: vec![PortRange {
+                start: 6660,
+                end: 6669,
+            }],
+        }]
+    );
+
+    let cgroup = current_cgroup();
+    mgr.assign_cgroup(&cgroup, 20).unwrap();
+    let results: Vec<_> = [6659, 6660, 6667, 6669, 6670]
+        .into_iter()
+        .map(|port| (port, std::net::TcpStream::connect(("127.0.0.1", port))))
+        .collect();
+    mgr.assign_cgroup(&cgroup, 0).unwrap();
+
+    // Nothing listens on the ports, so allowed connects are refused instead.
+    for (port, res) in results {
+        let denied = matches!(port, 6660 | 6669);
+        let err = res.unwrap_err();
+        assert_eq!(
+            err.raw_os_error() == Some(libc::EPERM),
+            denied,
+            "port {port}"
+        );
+    }
+    for port in [6660, 6669] {
+        let alert = tokio::time::timeout(Duration::from_secs(5), rx.recv())
+            .await
+            .expect("timeout elapsed")
+            .expect("alert channel closed");
+        println!("alert found: {:?}", alert);
+        assert_eq!(alert.reason, Reason::WildcardDeny);
+        assert_eq!(alert.port, port);
+    }
+}
+
+#[tokio::test]
+async fn test_socket_bind_family_mismatch() {
+    let mut mgr: PolicyManager = PolicyManager::with_default_path().unwrap();
+
+    mgr.set_namespace(24);
+    let mut socket_bind = mgr.attach_socket_bind().unwrap();
+    let mut rx = socket_bind.alerts().await.unwrap();
+
+    println!("registering default deny and ipv4 allow policies");
+    socket_bind
+        .add_policy(SocketBind {
+            subject: PolicySubject::All,
+            allow: Ports::Ports(vec![8975]),
+            deny: Ports::All,
+            deny_options: vec![],
+            family: None,
+        })
+        .await
+        .unwrap();
+    socket_bind
+        .add_policy(SocketBind {
+            subject: PolicySubject::Binary(std::env::current_exe().unwrap()),
+            allow: Ports::Ports(vec![8976]),
+            deny: Ports::All,
+            deny_options: vec![],
+            family: Some(BindFamily::Ipv4),
+        })
+        .await
+        .unwrap();
+
+    let cgroup = current_cgroup();
+    mgr.assign_cgroup(&cgroup, 24).unwrap();
+    let v4 = std::net::TcpListener::bind("127.0.0.1:8976").map(drop);
+    let v6 = std::net::TcpListener::bind("[::1]:8976").map(drop);
+    let other = std::net::TcpListener::bind("[::1]:8977").map(drop);
+    mgr.assign_cgroup(&cgroup, 0).unwrap();
+
+    v4.expect("ipv4 bind should be allowed by the ipv4 policy");
+    // The ipv4 rules allow the port, so the ipv6 bind is denied for its
+    // family, while the other port is denied by default.
+    for (res, port, reason) in [
+        (v6, 8976, Reason::FamilyMismatch),
+        (other, 8977, Reason::DefaultDeny),
+    ] {
+        let err = res.expect_err("ipv6 bind should be denied");
+        assert_eq!(err.raw_os_error(), Some(libc::EPERM));
+
+        let alert = tokio::time::timeout(Duration::from_secs(5), rx.recv())
+            .await
+            .expect("timeout elapsed")
+            .expect("alert channel closed");
+        println!("alert found: {:?}", alert);
+        assert_eq!(alert.port, port);
+        assert_eq!(alert.family, libc::AF_INET6 as u16);
+        assert_eq!(alert.reason, reason);
+    }
+}
+
+#[tokio::test]
+async fn test_referenced_inodes() {
+    let mut mgr: PolicyManager = PolicyManager::with_default_path().unwrap();
+
+    mgr.set_namespace(25);
+    let mut socket_bind = mgr.attach_socket_bind().unwrap();
+    let mut sb_mount = mgr.manage_sb_mount().unwrap();
+    let exe = std::env::current_exe().unwrap();
+    let binary = || PolicySubject::Binary(std::env::current_exe().unwrap());
+
+    // The binary is referenced by several maps, and all binaries by none.
+    socket_bind
+        .add_policy(SocketBind {
+            subject: binary(),
+            allow: Ports::All,
+            deny: Ports::Ports(vec![8980]),
+            deny_options: vec![],
+            family: Some(BindFamily::Ipv6),
+        })
+        .await
+        .unwrap();
+    socket_bind
+        .add_policy(SocketBind {
+            subject: PolicySubject::All,
+            allow: Ports::All,
+            deny: Ports::Ports(vec![8981]),
+            deny_options: vec![],
+            family: None,
+        })
+        .await
+        .unwrap();
+    sb_mount
+        .add_policy(SbMount {
+            subject: binary(),
+            allow: false,
+        })
+        .await
+        .unwrap();
+    mgr.set_message_id(Hook::SocketBind, &binary(), 4).unwrap();
+
+    let inodes: Vec<_> = mgr
+        .referenced_inodes()
+        .unwrap()
+        .into_iter()
+        .filter(|key| key.namespace == 25)
+        .collect();
+    let inode = std::fs::metadata(&exe).unwrap().ino();
+    assert_eq!(inodes.len(), 1);
+    assert_eq!(inodes[0].inode, inode);
+
+    let status = mgr.inode_status(inodes).await;
+    assert_eq!(
+        status.into_values().collect::<Vec<_>>(),
+        vec![InodeStatus::Resolves(exe)]
+    );
+}